@@ -1,9 +1,14 @@
 #[cfg(feature = "svg")]
 mod button_svgs;
-mod manager;
+mod devices;
+mod song_end;
 mod tracker;
+mod volume;
 
 #[cfg(feature = "svg")]
 pub use button_svgs::*;
-pub use manager::*;
+pub use devices::*;
+pub use song_end::*;
 pub use tracker::*;
+pub use volume::*;
+pub use ytm_rs_core::audio::*;
@@ -0,0 +1,91 @@
+use cpal::traits::{DeviceTrait, HostTrait};
+
+/// How many polls in a row a new device has to be seen before switching to it
+pub const DEBOUNCE_POLLS: u32 = 2;
+
+/// The name of the current default output device
+pub fn default_output_device() -> Option<String> {
+    cpal::default_host().default_output_device()?.name().ok()
+}
+
+/// Asks for the default output device away from the UI, as some hosts take a while to answer
+pub async fn poll_output_device() -> Option<String> {
+    async_std::task::spawn_blocking(default_output_device).await
+}
+
+/// Follows the active output device, ignoring changes that don't last.
+#[derive(Debug, Default)]
+pub struct DeviceWatch {
+    active: Option<String>,
+    pending: Option<(Option<String>, u32)>,
+    started: bool,
+}
+
+impl DeviceWatch {
+    pub fn active(&self) -> Option<&str> {
+        self.active.as_deref()
+    }
+
+    /// Whether a device was seen yet, so the next change is a switch rather than the start
+    pub fn started(&self) -> bool {
+        self.started
+    }
+
+    /// Feeds a polled device. Returns the new active device when it changes.
+    pub fn observe(&mut self, device: Option<String>) -> Option<Option<String>> {
+        // The first poll is the device the app started on
+        if !self.started {
+            self.started = true;
+            self.active = device.clone();
+            return Some(device);
+        }
+        if device == self.active {
+            self.pending = None;
+            return None;
+        }
+
+        let seen = match &self.pending {
+            Some((candidate, seen)) if *candidate == device => seen + 1,
+            _ => 1,
+        };
+        match seen >= DEBOUNCE_POLLS {
+            true => {
+                self.pending = None;
+                self.active = device.clone();
+                Some(device)
+            }
+            false => {
+                self.pending = Some((device, seen));
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DeviceWatch;
+
+    fn dev(name: &str) -> Option<String> {
+        Some(name.to_string())
+    }
+
+    #[test]
+    fn device_changes_are_debounced() {
+        let mut watch = DeviceWatch::default();
+        assert![!watch.started()];
+        assert_eq![watch.observe(dev("speakers")), Some(dev("speakers"))];
+        assert![watch.started()];
+        assert_eq![watch.observe(dev("speakers")), None];
+
+        // A single glitched poll doesn't switch
+        assert_eq![watch.observe(None), None];
+        assert_eq![watch.observe(dev("speakers")), None];
+        assert_eq![watch.active(), Some("speakers")];
+
+        // A lasting change does
+        assert_eq![watch.observe(dev("dac")), None];
+        assert_eq![watch.observe(dev("dac")), Some(dev("dac"))];
+        assert_eq![watch.active(), Some("dac")];
+    }
+}
@@ -0,0 +1,28 @@
+//! Telling the app when the playing song should have ended, see
+//! [`YTMRSAudioManager::next_change`]
+
+use std::time::Duration;
+
+use futures::stream;
+use iced::Subscription;
+
+use super::{ChangeSong, YTMRSAudioManager};
+
+/// Fires every `interval`, for the song numbered `song`
+fn every(change: ChangeSong, interval: Duration) -> Subscription<ChangeSong> {
+    let ChangeSong { poll, song } = change;
+    iced::subscription::run_with_id(
+        ("change_song", song, interval, poll),
+        stream::unfold((), move |()| async move {
+            async_std::task::sleep(interval).await;
+            Some((ChangeSong { poll, song }, ()))
+        }),
+    )
+}
+
+pub fn song_end_subscription(manager: &YTMRSAudioManager) -> Subscription<ChangeSong> {
+    match manager.next_change() {
+        Some((change, interval)) => every(change, interval),
+        None => Subscription::none(),
+    }
+}
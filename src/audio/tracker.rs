@@ -1,8 +1,22 @@
-use super::YTMRSAudioManager;
-use crate::{settings::YTMRUserSettings, song::format_duration, styling::FullYtmrsScheme};
+use super::{
+    amplitude_to_slider, scrolled, EqBand, EqSettings, YTMRSAudioManager, MAX_EQ_GAIN_DB,
+    VOLUME_SLIDER_MAX,
+};
+use crate::sleep_timer::{parse_sleep, SLEEP_CHOICES};
+use crate::{
+    settings::YTMRUserSettings,
+    song::{format_duration, format_minutes, SongDuration},
+    song_operations::Repeat,
+    styling::FullYtmrsScheme,
+};
+use std::time::{Duration, Instant};
+
 use iced::{
     alignment::Vertical,
-    widget::{button, column, container, hover, progress_bar, row, slider, Text},
+    widget::{
+        button, checkbox, column, container, hover, lazy, mouse_area, progress_bar, row, slider,
+        text_input, tooltip, Column, Row, Text,
+    },
     Alignment, Border, Color, Command, Element, Length,
 };
 
@@ -56,15 +70,125 @@ fn previous_button() -> Text<'static> {
     Text::new("|<").horizontal_alignment(iced::alignment::Horizontal::Center)
 }
 
+/// Holding a skip button this long skips a whole group instead
+pub const LONG_PRESS: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Skip {
+    Next,
+    Previous,
+}
+
 #[derive(Debug, Clone)]
 pub enum TrackerMsg {
     Pause,
     Play,
     Next,
     Previous,
+    /// Jumps to the first song of the next group
+    NextGroup,
+    /// Jumps to the first song of the previous group
+    PreviousGroup,
+    /// Goes back to the first song of the playing song's group
+    RestartGroup,
+    SkipPressed(Skip),
+    SkipReleased(Skip),
+    /// Moves the volume slider, out of [`VOLUME_SLIDER_MAX`]. Unmutes.
     UpdateVolume(f64),
+    /// The mouse wheel turned over the tracker
+    ScrollVolume(iced::mouse::ScrollDelta),
+    /// Silences playback, or goes back to the volume it had
+    ToggleMute,
+    /// Plays the rest of the queue in a random order, or in order again
+    ToggleShuffle,
+    /// Switches to the next repeat mode
+    CycleRepeat,
+    /// Pressing or dragging the progress bar, to seek to this many seconds
     ProgressSliderChanged(f64),
-    ProgressSliderReleased(f64),
+    /// Seeks to where the progress bar was let go
+    ProgressSliderReleased,
+    /// Shows or hides the equalizer
+    ToggleEqualizer,
+    /// Sets the band's gain, in decibels
+    EqualizerChanged(EqBand, f64),
+    ToggleLimiter,
+    /// Shows or hides the sleep timer's menu
+    ToggleSleepMenu,
+    /// Types a custom time for the sleep timer
+    SleepTextEdited(String),
+    /// Starts the sleep timer over with the time, None turns it off
+    SetSleepTimer(Option<Duration>),
+}
+
+/// The frequently changing part of the tracker, in whole seconds so that
+/// sub-second playback progress doesn't change what's shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ProgressDisplay {
+    pub elapsed: u32,
+    /// None when the length of the song is unknown, which disables seeking
+    pub total: Option<u32>,
+    /// Where the progress bar is being dragged to
+    pub seeking: Option<u32>,
+    /// Whether the song is decoded in memory rather than streamed
+    pub loaded: bool,
+}
+impl ProgressDisplay {
+    pub fn text<'a>(&self) -> Text<'a> {
+        Text::new(format!(
+            "{} / {}",
+            format_duration(&(self.elapsed as f32)),
+            match self.total {
+                Some(total) => SongDuration::Known(total as f64),
+                None => SongDuration::Unknown,
+            }
+            .format()
+        ))
+    }
+
+    /// The bar in the `[r, g, b, a]` color, which can be dragged to seek
+    pub fn bar(self, color: [u8; 4]) -> Element<'static, TrackerMsg> {
+        let elapsed = self.elapsed as f32;
+        let position = self.seeking.map_or(elapsed, |target| target as f32);
+        let range = 0.0..=self.total.unwrap_or(1).max(1) as f32;
+
+        let [r, g, b, a] = color;
+        let progress_color = Color::from_rgba8(r, g, b, a as f32 / 255.0);
+        // A decoded song is all there, so the part that's left is faintly filled in
+        let background = match self.loaded {
+            true => Color {
+                a: 0.3,
+                ..progress_color
+            },
+            false => Color::BLACK,
+        };
+        let bar = container(
+            progress_bar(range.clone(), elapsed)
+                .height(8)
+                .style(move |_| progress_bar::Style {
+                    background: iced::Background::Color(background),
+                    bar: iced::Background::Color(progress_color),
+                    border: Border::rounded(0),
+                }),
+        )
+        .align_y(Vertical::Center)
+        .height(10);
+        // The slider moves to where it's pressed, so a click seeks as soon as it's let go
+        match self.total {
+            None => bar.into(),
+            Some(_) => hover(
+                bar,
+                tooltip(
+                    slider(range, position, |x| {
+                        TrackerMsg::ProgressSliderChanged(x as f64)
+                    })
+                    .on_release(TrackerMsg::ProgressSliderReleased)
+                    .height(10),
+                    Text::new(format_duration(&position)),
+                    tooltip::Position::Top,
+                ),
+            ),
+        }
+    }
 }
 
 /// A struct that shows the progress of the manager's audio playback.
@@ -73,9 +197,28 @@ pub struct AudioProgressTracker {
     pub elapsed: Option<f64>,
     pub total: Option<f64>,
     pub paused: bool,
+    /// Where the volume slider is, see [`VOLUME_SLIDER_MAX`]
     pub volume: f64,
+    pub muted: bool,
     pub next_available: bool,
     pub previous_available: bool,
+    pub shuffle: bool,
+    pub repeat: Repeat,
+    /// The skip button being held down, and since when
+    pressed: Option<(Skip, Instant)>,
+    /// Where the progress bar is being dragged to. Kept apart from `elapsed`, which the
+    /// manager overwrites while the bar is held.
+    seeking: Option<f64>,
+    /// Whether the song is decoded in memory rather than streamed
+    fully_loaded: bool,
+    pub eq: EqSettings,
+    /// Whether the equalizer is shown, under the volume
+    eq_open: bool,
+    /// Whether the sleep timer's menu is shown, under the buttons
+    sleep_open: bool,
+    sleep_text: String,
+    /// The whole seconds left on the sleep timer, if it's running
+    pub sleep_left: Option<u64>,
 }
 impl Default for AudioProgressTracker {
     fn default() -> Self {
@@ -83,9 +226,20 @@ impl Default for AudioProgressTracker {
             elapsed: None,
             total: None,
             paused: false,
-            volume: 1000.,
+            volume: VOLUME_SLIDER_MAX,
+            muted: false,
             next_available: true,
             previous_available: false,
+            shuffle: false,
+            repeat: Repeat::Off,
+            pressed: None,
+            seeking: None,
+            fully_loaded: false,
+            eq: EqSettings::default(),
+            eq_open: false,
+            sleep_open: false,
+            sleep_text: String::new(),
+            sleep_left: None,
         }
     }
 }
@@ -93,7 +247,11 @@ impl Default for AudioProgressTracker {
 impl AudioProgressTracker {
     pub fn new(settings: &YTMRUserSettings) -> Self {
         Self {
-            volume: settings.volume as f64 * 1000_f64,
+            volume: amplitude_to_slider(settings.volume as f64),
+            muted: settings.muted,
+            shuffle: settings.shuffle,
+            repeat: settings.repeat,
+            eq: settings.eq,
             ..Default::default()
         }
     }
@@ -102,49 +260,77 @@ impl AudioProgressTracker {
         self.elapsed = manager.elapsed();
         self.total = manager.total().map(|d| d.as_secs_f64());
         self.paused = manager.playback_state() == PlaybackState::Paused;
+        self.fully_loaded = manager.fully_loaded();
     }
 
-    pub fn view(&self, scheme: &FullYtmrsScheme) -> Element<TrackerMsg> {
-        let elapsed = self.elapsed.unwrap_or(0.0) as f32;
-        let range = 0.0..=self.total.unwrap_or(1.0) as f32;
+    /// The position the progress bar was let go at, which is now the elapsed time
+    pub fn finish_seek(&mut self) -> Option<f64> {
+        let target = self.seeking.take()?;
+        self.elapsed = Some(target);
+        Some(target)
+    }
 
-        let duration_display = Text::new(format!(
-            "{} / {}",
-            format_duration(&elapsed),
-            format_duration(range.end())
-        ));
-        let progress_color = scheme.colors.primary_color;
-        let progress_bar = hover(
-            container(
-                progress_bar(range.clone(), elapsed)
-                    .height(8)
-                    .style(move |_| progress_bar::Style {
-                        background: iced::Background::Color(Color::BLACK),
-                        bar: iced::Background::Color(progress_color),
-                        border: Border::rounded(0),
-                    }),
-            )
-            .align_y(Vertical::Center)
-            .height(10),
-            slider(range, elapsed, |x| {
-                TrackerMsg::ProgressSliderChanged(x as f64)
-            })
-            .on_release(TrackerMsg::ProgressSliderReleased(elapsed as f64))
-            .height(10),
+    /// What letting go of a skip button does, depending on how long it was held
+    pub fn release(&mut self, skip: Skip, now: Instant) -> TrackerMsg {
+        let long = match self.pressed.take() {
+            Some((pressed, since)) => pressed == skip && now - since >= LONG_PRESS,
+            // The button's padding is outside the mouse area, so it only sees the release
+            None => false,
+        };
+        match (skip, long) {
+            (Skip::Next, false) => TrackerMsg::Next,
+            (Skip::Next, true) => TrackerMsg::NextGroup,
+            (Skip::Previous, false) => TrackerMsg::Previous,
+            (Skip::Previous, true) => TrackerMsg::PreviousGroup,
+        }
+    }
+
+    /// Where the volume slider goes when the wheel turns over the tracker
+    pub fn scrolled_volume(&self, delta: iced::mouse::ScrollDelta) -> f64 {
+        scrolled(self.volume, delta)
+    }
+
+    pub fn progress_display(&self) -> ProgressDisplay {
+        ProgressDisplay {
+            elapsed: self.elapsed.unwrap_or(0.0) as u32,
+            total: self
+                .total
+                .and_then(|total| SongDuration::from_parts(total, false).known())
+                .map(|total| total as u32),
+            seeking: self.seeking.map(|target| target as u32),
+            loaded: self.fully_loaded,
+        }
+    }
+
+    pub fn view(&self, scheme: &FullYtmrsScheme) -> Element<TrackerMsg> {
+        let display = self.progress_display();
+        let duration_display = display.text();
+        // Only built again once what it shows changes, which is at most once a second
+        let progress_bar = lazy(
+            (display, scheme.colors.primary_color.into_rgba8()),
+            |(display, color)| display.bar(*color),
         );
 
         let next_button = {
             let button_style = scheme.playback_button_style.clone();
-            button(next_button().width(32).height(32))
-                .on_press(TrackerMsg::Next)
-                .style(move |_, s| button_style.clone().update(s))
+            button(
+                mouse_area(next_button().width(32).height(32))
+                    .on_press(TrackerMsg::SkipPressed(Skip::Next))
+                    .on_release(TrackerMsg::SkipReleased(Skip::Next)),
+            )
+            .on_press(TrackerMsg::SkipReleased(Skip::Next))
+            .style(move |_, s| button_style.clone().update(s))
         };
         let previous_button = {
             let button_style = scheme.playback_button_style.clone();
 
-            button(previous_button().width(32).height(32))
-                .on_press(TrackerMsg::Previous)
-                .style(move |_, s| button_style.clone().update(s))
+            button(
+                mouse_area(previous_button().width(32).height(32))
+                    .on_press(TrackerMsg::SkipPressed(Skip::Previous))
+                    .on_release(TrackerMsg::SkipReleased(Skip::Previous)),
+            )
+            .on_press(TrackerMsg::SkipReleased(Skip::Previous))
+            .style(move |_, s| button_style.clone().update(s))
         };
 
         let pause_play_button = {
@@ -156,16 +342,104 @@ impl AudioProgressTracker {
                 .style(move |_, s| button_style.clone().update(s))
         };
 
-        let volume_slider = slider(0.0..=1000.0, self.volume, TrackerMsg::UpdateVolume);
-        Element::new(
+        let shuffle_button = {
+            let button_style = scheme.playback_button_style.clone();
+            let shuffle = self.shuffle;
+            button(
+                Text::new("shuffle")
+                    .height(32)
+                    .vertical_alignment(Vertical::Center),
+            )
+            .on_press(TrackerMsg::ToggleShuffle)
+            .style(move |_, s| button_style.clone().toggle(shuffle, s))
+        };
+
+        let repeat_button = {
+            let button_style = scheme.playback_button_style.clone();
+            let repeat = self.repeat;
+            button(
+                Text::new(repeat.label())
+                    .height(32)
+                    .vertical_alignment(Vertical::Center),
+            )
+            .on_press(TrackerMsg::CycleRepeat)
+            .style(move |_, s| button_style.clone().toggle(repeat != Repeat::Off, s))
+        };
+
+        let eq_button = {
+            let button_style = scheme.playback_button_style.clone();
+            let open = self.eq_open;
+            button(
+                Text::new("eq")
+                    .height(32)
+                    .vertical_alignment(Vertical::Center),
+            )
+            .on_press(TrackerMsg::ToggleEqualizer)
+            .style(move |_, s| button_style.clone().toggle(open, s))
+        };
+
+        let sleep_button = {
+            let button_style = scheme.playback_button_style.clone();
+            let running = self.sleep_left.is_some();
+            button(
+                Text::new("sleep")
+                    .height(32)
+                    .vertical_alignment(Vertical::Center),
+            )
+            .on_press(TrackerMsg::ToggleSleepMenu)
+            .style(move |_, s| button_style.clone().toggle(running, s))
+        };
+        let sleep_left = self
+            .sleep_left
+            .map(|secs| Text::new(format!("  sleep {}", format_minutes(secs))));
+
+        let mute_button = {
+            let button_style = scheme.playback_button_style.clone();
+            let muted = self.muted;
+            button(
+                Text::new("mute")
+                    .height(32)
+                    .vertical_alignment(Vertical::Center),
+            )
+            .on_press(TrackerMsg::ToggleMute)
+            .style(move |_, s| button_style.clone().toggle(muted, s))
+        };
+        let volume_slider = slider(
+            0.0..=VOLUME_SLIDER_MAX,
+            self.volume,
+            TrackerMsg::UpdateVolume,
+        );
+        let volume = row![
+            mute_button,
+            volume_slider.width(100),
+            Text::new(format!("{:.0}%", self.volume / VOLUME_SLIDER_MAX * 100.0)).width(40),
+        ]
+        .spacing(8)
+        .align_items(Alignment::Center);
+        // The wheel changes the volume anywhere over the tracker, the slider included
+        mouse_area(
             column![
                 progress_bar,
                 row![
-                    row![duration_display].width(Length::Fill),
-                    column![row![previous_button, pause_play_button, next_button]]
-                        .align_items(Alignment::Center)
+                    row![duration_display]
+                        .push_maybe(sleep_left)
                         .width(Length::Fill),
-                    column![volume_slider.width(100)]
+                    column![row![
+                        previous_button,
+                        pause_play_button,
+                        next_button,
+                        shuffle_button,
+                        repeat_button,
+                        eq_button,
+                        sleep_button
+                    ]]
+                    .push_maybe(self.sleep_open.then(|| self.sleep_menu()))
+                    .spacing(8)
+                    .align_items(Alignment::Center)
+                    .width(Length::Fill),
+                    column![volume]
+                        .push_maybe(self.eq_open.then(|| self.equalizer()))
+                        .spacing(8)
                         .align_items(Alignment::End)
                         .width(Length::Fill),
                 ]
@@ -174,20 +448,158 @@ impl AudioProgressTracker {
             ]
             .width(Length::Fill),
         )
+        .on_scroll(TrackerMsg::ScrollVolume)
+        .into()
+    }
+
+    fn equalizer(&self) -> Element<TrackerMsg> {
+        let bands = EqBand::ALL.map(|band| {
+            let gain = self.eq.gain(band);
+            row![
+                Text::new(band.label()).width(40),
+                slider(-MAX_EQ_GAIN_DB..=MAX_EQ_GAIN_DB, gain, move |gain| {
+                    TrackerMsg::EqualizerChanged(band, gain)
+                })
+                .step(0.5)
+                .width(120),
+                Text::new(format!("{gain:+.1} dB")).width(70),
+            ]
+            .spacing(8)
+            .align_items(Alignment::Center)
+            .into()
+        });
+        Column::with_children(bands)
+            .push(checkbox("limiter", self.eq.limiter).on_toggle(|_| TrackerMsg::ToggleLimiter))
+            .spacing(4)
+            .into()
+    }
+
+    /// The times the sleep timer can be set to, and a field for any other
+    fn sleep_menu(&self) -> Element<TrackerMsg> {
+        let set = |time: Duration| TrackerMsg::SetSleepTimer(Some(time));
+        let custom = parse_sleep(&self.sleep_text);
+        let choices = SLEEP_CHOICES.map(|minutes| {
+            button(Text::new(format!("{minutes} min")))
+                .on_press(set(Duration::from_secs(minutes * 60)))
+                .into()
+        });
+        let input = text_input("minutes", &self.sleep_text)
+            .on_input(TrackerMsg::SleepTextEdited)
+            .width(70);
+        let input = match custom {
+            Some(time) => input.on_submit(set(time)),
+            None => input,
+        };
+        Row::with_children(choices)
+            .push(input)
+            .push(button("set").on_press_maybe(custom.map(set)))
+            .push_maybe(
+                self.sleep_left
+                    .map(|_| button("off").on_press(TrackerMsg::SetSleepTimer(None))),
+            )
+            .spacing(4)
+            .align_items(Alignment::Center)
+            .into()
     }
 
     pub fn update(&mut self, signal: TrackerMsg) -> Command<TrackerMsg> {
         match signal {
             TrackerMsg::ProgressSliderChanged(v) => {
-                self.elapsed = Some(v);
+                self.seeking = Some(v);
+                Command::none()
+            }
+            TrackerMsg::SkipPressed(skip) => {
+                self.pressed = Some((skip, Instant::now()));
                 Command::none()
             }
             TrackerMsg::Pause => todo!(),
             TrackerMsg::Play => todo!(),
             TrackerMsg::Next => todo!(),
             TrackerMsg::Previous => todo!(),
+            // Handled by the app, which owns the player and the tree
+            TrackerMsg::NextGroup
+            | TrackerMsg::PreviousGroup
+            | TrackerMsg::RestartGroup
+            | TrackerMsg::SkipReleased(_)
+            | TrackerMsg::ToggleShuffle
+            | TrackerMsg::CycleRepeat
+            | TrackerMsg::EqualizerChanged(..)
+            | TrackerMsg::ToggleLimiter
+            | TrackerMsg::ScrollVolume(_)
+            | TrackerMsg::ToggleMute => Command::none(),
             TrackerMsg::UpdateVolume(_) => todo!(),
-            TrackerMsg::ProgressSliderReleased(_) => Command::none(),
+            TrackerMsg::ToggleEqualizer => {
+                self.eq_open = !self.eq_open;
+                Command::none()
+            }
+            TrackerMsg::ToggleSleepMenu => {
+                self.sleep_open = !self.sleep_open;
+                Command::none()
+            }
+            TrackerMsg::SleepTextEdited(text) => {
+                self.sleep_text = text;
+                Command::none()
+            }
+            TrackerMsg::SetSleepTimer(_) => {
+                self.sleep_open = false;
+                Command::none()
+            }
+            TrackerMsg::ProgressSliderReleased => Command::none(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use iced::mouse::ScrollDelta;
+
+    use super::{AudioProgressTracker, Repeat, TrackerMsg, VOLUME_SLIDER_MAX};
+    use crate::settings::YTMRUserSettings;
+
+    #[test]
+    fn repeat_cycles_through_its_modes() {
+        let modes: Vec<Repeat> = std::iter::successors(Some(Repeat::Off), |r| Some(r.next()))
+            .take(4)
+            .collect();
+        assert_eq![modes, [Repeat::Off, Repeat::All, Repeat::One, Repeat::Off]];
+    }
+
+    #[test]
+    fn seeking_survives_progress_updates() {
+        let mut tracker = AudioProgressTracker::default();
+        let _ = tracker.update(TrackerMsg::ProgressSliderChanged(30.0));
+        // The playing song moved on while the bar was held
+        tracker.elapsed = Some(2.0);
+        assert_eq![tracker.progress_display().seeking, Some(30)];
+
+        assert_eq![tracker.finish_seek(), Some(30.0)];
+        assert_eq![tracker.progress_display().elapsed, 30];
+        assert_eq![tracker.finish_seek(), None];
+    }
+
+    #[test]
+    fn volume_starts_where_it_was_saved() {
+        let mut settings = YTMRUserSettings::default();
+        settings.volume = 0.001;
+        settings.muted = true;
+        let tracker = AudioProgressTracker::new(&settings);
+        // 60 dB down is the bottom of the slider
+        assert![tracker.volume.abs() < 1e-9 && tracker.muted];
+        settings.volume = 1.0;
+        let tracker = AudioProgressTracker::new(&settings);
+        let up = ScrollDelta::Lines { x: 0.0, y: 1.0 };
+        assert_eq![tracker.scrolled_volume(up), VOLUME_SLIDER_MAX];
+    }
+
+    #[test]
+    fn setting_a_sleep_timer_closes_its_menu() {
+        let mut tracker = AudioProgressTracker::default();
+        let _ = tracker.update(TrackerMsg::ToggleSleepMenu);
+        let _ = tracker.update(TrackerMsg::SleepTextEdited("20".into()));
+        assert![tracker.sleep_open];
+        let _ = tracker.update(TrackerMsg::SetSleepTimer(Some(Duration::from_secs(1200))));
+        assert![!tracker.sleep_open];
+    }
+}
@@ -0,0 +1,74 @@
+//! How the volume slider maps to what's heard. Loudness is heard on a logarithmic scale, so the
+//! slider moves through decibels rather than amplitude, and its low end isn't all near silence.
+
+use iced::mouse::ScrollDelta;
+
+/// The slider's range, which the volume keys step through too
+pub const VOLUME_SLIDER_MAX: f64 = 1000.0;
+/// How far a notch of the mouse wheel moves the slider
+pub const VOLUME_WHEEL_STEP: f64 = 25.0;
+/// How quiet the slider gets just above the bottom, which is silent
+const QUIETEST_DB: f64 = -60.0;
+/// Touchpads scroll by pixels, this many make a notch
+const PIXELS_PER_NOTCH: f32 = 50.0;
+
+/// The amplitude to play at, for where the slider is
+pub fn slider_to_amplitude(position: f64) -> f64 {
+    let fraction = (position / VOLUME_SLIDER_MAX).clamp(0.0, 1.0);
+    match fraction > 0.0 {
+        true => 10_f64.powf(QUIETEST_DB * (1.0 - fraction) / 20.0),
+        false => 0.0,
+    }
+}
+
+/// Where the slider is for the amplitude. Anything quieter than the scale is at the bottom.
+pub fn amplitude_to_slider(amplitude: f64) -> f64 {
+    match amplitude > 0.0 {
+        true => {
+            let db = 20.0 * amplitude.min(1.0).log10();
+            (1.0 - db / QUIETEST_DB).max(0.0) * VOLUME_SLIDER_MAX
+        }
+        false => 0.0,
+    }
+}
+
+/// Where the slider moves to when scrolled over, up is louder
+pub fn scrolled(position: f64, delta: ScrollDelta) -> f64 {
+    let notches = match delta {
+        ScrollDelta::Lines { y, .. } => y,
+        ScrollDelta::Pixels { y, .. } => y / PIXELS_PER_NOTCH,
+    };
+    (position + notches as f64 * VOLUME_WHEEL_STEP).clamp(0.0, VOLUME_SLIDER_MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use iced::mouse::ScrollDelta;
+
+    use super::{amplitude_to_slider, scrolled, slider_to_amplitude, VOLUME_SLIDER_MAX};
+
+    #[test]
+    fn the_slider_moves_in_decibels() {
+        assert_eq![slider_to_amplitude(VOLUME_SLIDER_MAX), 1.0];
+        assert_eq![slider_to_amplitude(0.0), 0.0];
+        // Halfway is 30 dB down, rather than half the amplitude
+        let half = slider_to_amplitude(500.0);
+        assert![(half - 0.0316).abs() < 0.001];
+        for position in [1.0, 250.0, 500.0, 999.0] {
+            let back = amplitude_to_slider(slider_to_amplitude(position));
+            assert![(back - position).abs() < 1e-6];
+        }
+        assert_eq![amplitude_to_slider(0.0), 0.0];
+        assert_eq![amplitude_to_slider(1e-9), 0.0];
+    }
+
+    #[test]
+    fn scrolling_stays_on_the_slider() {
+        let lines = |y| ScrollDelta::Lines { x: 0.0, y };
+        assert_eq![scrolled(500.0, lines(2.0)), 550.0];
+        assert_eq![scrolled(10.0, lines(-1.0)), 0.0];
+        assert_eq![scrolled(990.0, lines(1.0)), VOLUME_SLIDER_MAX];
+        let pixels = ScrollDelta::Pixels { x: 0.0, y: -100.0 };
+        assert_eq![scrolled(500.0, pixels), 450.0];
+    }
+}
@@ -0,0 +1,110 @@
+//! The form the backend settings are edited in. Edits are kept in it until "reconnect" is
+//! pressed, so half-typed addresses aren't tried.
+
+use iced::{
+    widget::{button, checkbox, row, text, text_input},
+    Alignment, Element,
+};
+
+pub use ytm_rs_core::backend_settings::*;
+
+#[derive(Debug, Clone)]
+pub enum BackendFormMsg {
+    HostEdited(String),
+    PortEdited(String),
+    AutoLaunchToggled(bool),
+    Reconnect,
+}
+impl BackendFormMsg {
+    pub fn is_text_edit(&self) -> bool {
+        matches!(self, Self::HostEdited(_) | Self::PortEdited(_))
+    }
+}
+
+/// The backend settings being edited
+#[derive(Debug, Default)]
+pub struct BackendForm {
+    host: String,
+    port: String,
+    auto_launch: bool,
+}
+impl BackendForm {
+    pub fn new(settings: &BackendSettings) -> Self {
+        Self {
+            host: settings.host.clone(),
+            port: settings.port.to_string(),
+            auto_launch: settings.auto_launch,
+        }
+    }
+
+    /// Reconnecting is left to the caller
+    pub fn update(&mut self, msg: BackendFormMsg) {
+        match msg {
+            BackendFormMsg::HostEdited(host) => self.host = host,
+            BackendFormMsg::PortEdited(port) => self.port = port,
+            BackendFormMsg::AutoLaunchToggled(auto_launch) => self.auto_launch = auto_launch,
+            BackendFormMsg::Reconnect => {}
+        }
+    }
+
+    /// The settings the form describes, or what's wrong with them
+    pub fn parse(&self) -> Result<BackendSettings, String> {
+        let port = match self.port.trim().parse::<u16>() {
+            Ok(port) if port > 0 => port,
+            _ => return Err(format!("{:?} is not a port", self.port)),
+        };
+        let settings = BackendSettings {
+            host: self.host.trim().to_string(),
+            port,
+            auto_launch: self.auto_launch,
+        };
+        match settings.url() {
+            Some(_) => Ok(settings),
+            None => Err(format!("{:?} is not a host", settings.host)),
+        }
+    }
+
+    pub fn view(&self, status: &'static str) -> Element<BackendFormMsg> {
+        row![
+            text(status),
+            text_input("host", &self.host)
+                .on_input(BackendFormMsg::HostEdited)
+                .on_submit(BackendFormMsg::Reconnect)
+                .width(140),
+            text_input("port", &self.port)
+                .on_input(BackendFormMsg::PortEdited)
+                .on_submit(BackendFormMsg::Reconnect)
+                .width(70),
+            checkbox("launch", self.auto_launch).on_toggle(BackendFormMsg::AutoLaunchToggled),
+            button("reconnect").on_press(BackendFormMsg::Reconnect),
+        ]
+        .spacing(8)
+        .align_items(Alignment::Center)
+        .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BackendForm, BackendFormMsg, BackendSettings, DEFAULT_PORT};
+
+    #[test]
+    fn forms_are_checked_before_reconnecting() {
+        let mut form = BackendForm::new(&BackendSettings::default());
+        form.update(BackendFormMsg::HostEdited(" localhost ".into()));
+        form.update(BackendFormMsg::PortEdited("8080".into()));
+        form.update(BackendFormMsg::AutoLaunchToggled(false));
+        let settings = form.parse().unwrap();
+        assert_eq![(settings.host.as_str(), settings.port), ("localhost", 8080)];
+        assert_eq![settings.url().unwrap().as_str(), "http://localhost:8080/"];
+        assert![!settings.auto_launch];
+
+        for port in ["", "0", "70000", "port"] {
+            form.update(BackendFormMsg::PortEdited(port.into()));
+            assert![form.parse().is_err()];
+        }
+        form.update(BackendFormMsg::PortEdited(DEFAULT_PORT.to_string()));
+        form.update(BackendFormMsg::HostEdited("not a host".into()));
+        assert![form.parse().is_err()];
+    }
+}
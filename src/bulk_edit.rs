@@ -0,0 +1,373 @@
+use std::fmt::Display;
+
+use iced::{
+    widget::{button, checkbox, column, container, row, text, text_input, Column, Space},
+    Alignment, Command, Element, Length,
+};
+
+use crate::{caching::RwMap, song::Song};
+
+/// An editable field, which is only written to the songs when `apply` is checked
+#[derive(Debug, Clone, Default)]
+pub struct Field {
+    pub apply: bool,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    AddTags,
+    RemoveTags,
+    Album,
+    Artists,
+}
+impl FieldKind {
+    const ALL: [FieldKind; 4] = [Self::AddTags, Self::RemoveTags, Self::Album, Self::Artists];
+
+    fn label(&self) -> &'static str {
+        match self {
+            Self::AddTags => "add tags",
+            Self::RemoveTags => "remove tags",
+            Self::Album => "set album",
+            Self::Artists => "set artists",
+        }
+    }
+
+    fn input_id(&self) -> text_input::Id {
+        text_input::Id::new(match self {
+            Self::AddTags => "bulk_edit_add_tags",
+            Self::RemoveTags => "bulk_edit_remove_tags",
+            Self::Album => "bulk_edit_album",
+            Self::Artists => "bulk_edit_artists",
+        })
+    }
+}
+
+/// Splits a comma separated list, dropping empty entries and repeats
+pub fn split_list(list: &str) -> Vec<String> {
+    let mut items: Vec<String> = vec![];
+    for item in list.split(',').map(str::trim) {
+        if !item.is_empty() && !items.iter().any(|i| i == item) {
+            items.push(item.to_string());
+        }
+    }
+    items
+}
+
+/// The same changes, made to many songs at once
+#[derive(Debug, Clone, Default)]
+pub struct BulkEdit {
+    pub add_tags: Field,
+    pub remove_tags: Field,
+    /// Cleared when applied empty
+    pub album: Field,
+    /// Comma separated, cleared when applied empty
+    pub artists: Field,
+}
+
+impl BulkEdit {
+    pub fn field(&self, kind: FieldKind) -> &Field {
+        match kind {
+            FieldKind::AddTags => &self.add_tags,
+            FieldKind::RemoveTags => &self.remove_tags,
+            FieldKind::Album => &self.album,
+            FieldKind::Artists => &self.artists,
+        }
+    }
+
+    pub fn field_mut(&mut self, kind: FieldKind) -> &mut Field {
+        match kind {
+            FieldKind::AddTags => &mut self.add_tags,
+            FieldKind::RemoveTags => &mut self.remove_tags,
+            FieldKind::Album => &mut self.album,
+            FieldKind::Artists => &mut self.artists,
+        }
+    }
+
+    /// Whether any field would be applied
+    pub fn is_empty(&self) -> bool {
+        FieldKind::ALL.iter().all(|kind| !self.field(*kind).apply)
+    }
+
+    /// Applies the checked fields to the song. A tag that's both added and removed is kept.
+    /// Returns whether the song changed.
+    pub fn apply(&self, song: &mut Song) -> bool {
+        let add = match self.add_tags.apply {
+            true => split_list(&self.add_tags.value),
+            false => vec![],
+        };
+        let mut tags = song.tags.clone();
+        if self.remove_tags.apply {
+            let remove = split_list(&self.remove_tags.value);
+            tags.retain(|tag| !remove.contains(tag) || add.contains(tag));
+        }
+        for tag in add {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+
+        let album = match self.album.apply {
+            true => Some(self.album.value.trim())
+                .filter(|album| !album.is_empty())
+                .map(str::to_string),
+            false => song.album.clone(),
+        };
+        let artists = match self.artists.apply {
+            true => Some(split_list(&self.artists.value)).filter(|a| !a.is_empty()),
+            false => song.artists.clone(),
+        };
+
+        let changed = tags != song.tags || album != song.album || artists != song.artists;
+        if changed {
+            song.tags = tags;
+            song.album = album;
+            song.artists = artists;
+            song.user_edited = true;
+        }
+        changed
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BulkEditSummary {
+    pub updated: usize,
+    pub unchanged: usize,
+    /// Selected songs whose metadata wasn't in the cache
+    pub uncached: usize,
+}
+
+/// e.g. "3 updated, 1 skipped because uncached"
+impl Display for BulkEditSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} updated", self.updated)?;
+        if self.unchanged > 0 {
+            write!(f, ", {} already matched", self.unchanged)?;
+        }
+        if self.uncached > 0 {
+            write!(f, ", {} skipped because uncached", self.uncached)?;
+        }
+        Ok(())
+    }
+}
+
+/// Applies the edit to every cached song among the keys, each under its own lock.
+/// Returns the songs that changed, to be written in a single batch.
+pub fn apply_to<'a>(
+    edit: &BulkEdit,
+    songs: &RwMap<String, Song>,
+    keys: impl IntoIterator<Item = &'a String>,
+) -> (Vec<Song>, BulkEditSummary) {
+    let mut summary = BulkEditSummary::default();
+    let mut modified = vec![];
+    for key in keys {
+        match songs.get(key) {
+            Some(song) => {
+                let mut song = song.write();
+                match edit.apply(&mut song) {
+                    true => {
+                        summary.updated += 1;
+                        modified.push(song.clone());
+                    }
+                    false => summary.unchanged += 1,
+                }
+            }
+            None => summary.uncached += 1,
+        }
+    }
+    (modified, summary)
+}
+
+#[derive(Debug, Clone)]
+pub enum BulkEditMsg {
+    Toggle(FieldKind, bool),
+    Edit(FieldKind, String),
+    Apply,
+    Cancel,
+}
+
+/// The dialog editing the selected songs
+#[derive(Debug, Clone)]
+pub struct BulkEditor {
+    pub keys: Vec<String>,
+    pub edit: BulkEdit,
+    /// The result of the last apply
+    pub summary: Option<BulkEditSummary>,
+}
+
+impl BulkEditor {
+    /// Opens the editor, focusing its first field
+    pub fn open<M: 'static>(keys: Vec<String>) -> (Self, Command<M>) {
+        let editor = Self {
+            keys,
+            edit: BulkEdit::default(),
+            summary: None,
+        };
+        (editor, text_input::focus(FieldKind::ALL[0].input_id()))
+    }
+
+    /// Updates the fields. Editing a field checks it, so typing is enough to apply it.
+    pub fn update(&mut self, msg: &BulkEditMsg) {
+        match msg {
+            BulkEditMsg::Toggle(kind, apply) => self.edit.field_mut(*kind).apply = *apply,
+            BulkEditMsg::Edit(kind, value) => {
+                let field = self.edit.field_mut(*kind);
+                field.value = value.clone();
+                field.apply = true;
+            }
+            BulkEditMsg::Apply | BulkEditMsg::Cancel => {}
+        }
+    }
+
+    pub fn view(&self) -> Element<BulkEditMsg> {
+        let fields = FieldKind::ALL.iter().map(|kind| {
+            let field = self.edit.field(*kind);
+            let kind = *kind;
+            row![
+                checkbox(kind.label(), field.apply)
+                    .on_toggle(move |apply| BulkEditMsg::Toggle(kind, apply))
+                    .width(120),
+                text_input("comma separated", &field.value)
+                    .id(kind.input_id())
+                    .on_input(move |value| BulkEditMsg::Edit(kind, value))
+                    .on_submit(BulkEditMsg::Apply),
+            ]
+            .spacing(4)
+            .align_items(Alignment::Center)
+            .into()
+        });
+
+        let apply =
+            button("apply").on_press_maybe((!self.edit.is_empty()).then_some(BulkEditMsg::Apply));
+
+        container(
+            column![row![
+                text(format!("Edit {} songs", self.keys.len())).size(20),
+                Space::with_width(Length::Fill),
+                button("close").on_press(BulkEditMsg::Cancel),
+            ]
+            .align_items(Alignment::Center)]
+            .push(Column::with_children(fields).spacing(4))
+            .push(
+                row![apply]
+                    .push_maybe(self.summary.as_ref().map(|s| text(s.to_string())))
+                    .spacing(8)
+                    .align_items(Alignment::Center),
+            )
+            .spacing(4),
+        )
+        .padding(10)
+        .width(Length::Fill)
+        .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{caching::ToRwMapExt, song::Song};
+
+    use super::{apply_to, split_list, BulkEdit, BulkEditSummary, Field};
+
+    fn field(value: &str) -> Field {
+        Field {
+            apply: true,
+            value: value.to_string(),
+        }
+    }
+
+    fn song(tags: &[&str]) -> Song {
+        Song {
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            album: Some("Album".into()),
+            artists: Some(vec!["Artist".into()]),
+            ..Song::basic()
+        }
+    }
+
+    #[test]
+    fn lists_ignore_empty_entries_and_repeats() {
+        assert_eq![split_list(" a, ,b,a,, c "), vec!["a", "b", "c"]];
+        assert![split_list(" , ").is_empty()];
+    }
+
+    #[test]
+    fn only_checked_fields_are_applied() {
+        // Values without their checkbox are left alone
+        let edit = BulkEdit {
+            album: Field {
+                apply: false,
+                value: "Other".into(),
+            },
+            ..Default::default()
+        };
+        assert![edit.is_empty()];
+        let mut s = song(&["x"]);
+        assert![!edit.apply(&mut s)];
+        assert![!s.user_edited];
+
+        let edit = BulkEdit {
+            add_tags: field("y, x, , y"),
+            remove_tags: field("z"),
+            ..Default::default()
+        };
+        let mut s = song(&["x", "z", "z"]);
+        assert![edit.apply(&mut s)];
+        assert_eq![s.tags, vec!["x", "y"]];
+        assert_eq![s.album.as_deref(), Some("Album")];
+        assert![s.user_edited];
+
+        let edit = BulkEdit {
+            album: field("  "),
+            artists: field("A, B"),
+            ..Default::default()
+        };
+        assert![edit.apply(&mut s)];
+        assert_eq![s.album, None];
+        assert_eq![s.artists, Some(vec!["A".to_string(), "B".to_string()])];
+        assert_eq![s.tags, vec!["x", "y"]];
+
+        // A tag being added and removed stays once
+        let edit = BulkEdit {
+            add_tags: field("x"),
+            remove_tags: field("x"),
+            ..Default::default()
+        };
+        assert![!edit.apply(&mut s)];
+        assert_eq![s.tags, vec!["x", "y"]];
+    }
+
+    #[test]
+    fn changed_songs_are_written_in_one_batch() {
+        let songs = [
+            ("a".to_string(), song(&[])),
+            ("b".to_string(), song(&["fav"])),
+            ("c".to_string(), song(&["old"])),
+        ]
+        .into_iter()
+        .map(|(k, s)| (k.clone(), Song { id: k, ..s }))
+        .to_rwmap();
+        let edit = BulkEdit {
+            add_tags: field("fav"),
+            ..Default::default()
+        };
+
+        let keys = ["a", "b", "c", "missing"].map(String::from);
+        let (batch, summary) = apply_to(&edit, &songs, &keys);
+        let ids: Vec<_> = batch.iter().map(|s| s.id.as_str()).collect();
+        assert_eq![ids, vec!["a", "c"]];
+        assert_eq![
+            summary,
+            BulkEditSummary {
+                updated: 2,
+                unchanged: 1,
+                uncached: 1,
+            }
+        ];
+        assert_eq![
+            summary.to_string(),
+            "2 updated, 1 already matched, 1 skipped because uncached"
+        ];
+        // The cached songs were updated in place
+        assert_eq![songs["c"].read().tags, vec!["old", "fav"]];
+    }
+}
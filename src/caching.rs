@@ -1,16 +1,12 @@
 use parking_lot::RwLock;
 use std::sync::Arc;
 
-mod folder_cache;
-mod item_cache;
-mod ndjson_cache;
-pub mod readers;
-mod sound_data;
+mod key_registry;
+mod used_keys;
 
-pub use folder_cache::*;
-pub use item_cache::*;
-pub use ndjson_cache::*;
-pub use sound_data::*;
+pub use key_registry::*;
+pub use used_keys::*;
+pub use ytm_rs_core::caching::*;
 
 use crate::{
     settings::{song_audio_path, song_metadata_path, thumbnails_directory},
@@ -23,6 +19,7 @@ pub struct YtmrsCache {
     pub song_metadata: RwArc<NDJsonCache<Song>>,
     pub sounds: FolderCache<BasicSoundData>,
     pub thumbnails: LazyFolderBasedReader,
+    pub keys: KeyRegistry,
 }
 
 impl Default for YtmrsCache {
@@ -31,8 +28,33 @@ impl Default for YtmrsCache {
             song_metadata: Arc::new(RwLock::new(NDJsonCache::new(LineBasedReader::new(
                 song_metadata_path(),
             )))),
-            sounds: FolderCache::new(FolderBasedReader::new(song_audio_path())),
+            sounds: FolderCache::new(
+                FolderBasedReader::new(song_audio_path()),
+                // Decoded songs are big, so only keep a few around
+                LruPolicy::new(Some(16), Some(1024 * 1024 * 1024)),
+            ),
             thumbnails: LazyFolderBasedReader::new(thumbnails_directory()),
+            keys: KeyRegistry::default(),
+        }
+    }
+}
+
+impl YtmrsCache {
+    pub fn stats(&self) -> CacheStats {
+        let song_metadata = self.song_metadata.read();
+        CacheStats {
+            song_metadata: song_metadata
+                .reader
+                .counters
+                .snapshot(song_metadata.items().len()),
+            sounds: self
+                .sounds
+                .reader
+                .counters
+                .snapshot(self.sounds.items().len()),
+            thumbnails: self.thumbnails.counters().snapshot(0),
+            key_collisions: self.keys.collisions(),
+            views: view_counts(),
         }
     }
 }
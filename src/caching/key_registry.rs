@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+
+use reqwest::Url;
+
+use crate::{settings::SongKey, song::Song};
+
+/// A stable 64-bit FNV-1a hash. `DefaultHasher` is not guaranteed to be stable
+/// between Rust releases, and the derived keys get written to disk.
+fn fnv1a(parts: &[&str]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for part in parts {
+        for byte in part.as_bytes().iter().chain([0xff].iter()) {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    hash
+}
+
+/// Normalizes a webpage url so that trivial differences (scheme, `www.`/`music.` prefixes,
+/// tracking query params) don't count as a different song.
+/// Returns None when the url can't tell two songs apart.
+pub fn normalize_webpage_url(url: &str) -> Option<String> {
+    let parsed = Url::parse(url).ok()?;
+    let host = parsed.host_str()?.to_lowercase();
+    let host = ["www.", "music.", "m."]
+        .iter()
+        .find_map(|prefix| host.strip_prefix(prefix))
+        .unwrap_or(&host)
+        .to_string();
+
+    let video = parsed
+        .query_pairs()
+        .find(|(k, _)| k == "v")
+        .map(|(_, v)| v.to_string());
+
+    let path = parsed.path().trim_end_matches('/');
+    Some(match video {
+        Some(v) => format!("{host}{path}?v={v}"),
+        None => format!("{host}{path}"),
+    })
+}
+
+/// Whether two webpage urls point to materially different songs.
+/// Urls that can't be compared are assumed to be the same song.
+pub fn urls_differ(a: &str, b: &str) -> bool {
+    match (normalize_webpage_url(a), normalize_webpage_url(b)) {
+        (Some(a), Some(b)) => a != b,
+        _ => false,
+    }
+}
+
+/// Derives the namespaced key given to a song whose raw id collides with another song's.
+pub fn namespaced_key(extractor: Option<&str>, raw_id: &str, webpage_url: &str) -> SongKey {
+    let url = normalize_webpage_url(webpage_url).unwrap_or_else(|| webpage_url.to_string());
+    let hash = fnv1a(&[extractor.unwrap_or_default(), raw_id, &url]);
+    format!("{raw_id}~{hash:016x}")
+}
+
+/// The single place where song keys are decided.
+///
+/// Every cache is keyed by the id string, so two songs from different extractors that reuse
+/// an id would otherwise be merged. When a newcomer's id is already taken by a song with a
+/// different `webpage_url`, the newcomer gets a [`namespaced_key`] instead.
+#[derive(Debug, Default)]
+pub struct KeyRegistry {
+    /// (raw id, normalized url) -> assigned key
+    remapped: HashMap<(String, String), SongKey>,
+    /// Number of collisions detected this session
+    collisions: usize,
+}
+
+impl KeyRegistry {
+    /// Decides the key for a song with the given raw id and url.
+    /// `existing` looks up the song currently stored under a key.
+    pub fn derive_key<F>(
+        &mut self,
+        extractor: Option<&str>,
+        raw_id: &str,
+        webpage_url: &str,
+        existing: F,
+    ) -> SongKey
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let normalized = normalize_webpage_url(webpage_url).unwrap_or_default();
+        if let Some(key) = self.remapped.get(&(raw_id.to_string(), normalized.clone())) {
+            return key.clone();
+        }
+
+        match existing(raw_id) {
+            Some(existing_url) if urls_differ(&existing_url, webpage_url) => {
+                let key = namespaced_key(extractor, raw_id, webpage_url);
+                self.collisions += 1;
+                println![
+                    "Key collision: {raw_id:?} is already used by {existing_url:?}, \
+                     storing {webpage_url:?} as {key:?}"
+                ];
+                self.remapped
+                    .insert((raw_id.to_string(), normalized), key.clone());
+                key
+            }
+            _ => raw_id.to_string(),
+        }
+    }
+
+    /// Rewrites the id of the song to its derived key.
+    pub fn assign<F>(&mut self, song: &mut Song, existing: F)
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let key = self.derive_key(
+            song.extractor_key.as_deref(),
+            &song.id,
+            &song.webpage_url,
+            existing,
+        );
+        if key != song.id {
+            song.raw_id = Some(std::mem::replace(&mut song.id, key));
+        }
+    }
+
+    /// Rewrites the ids of songs ingested together. They're checked against each other as well
+    /// as against the stored songs, whose url `stored` looks up by key.
+    pub fn assign_batch<F>(&mut self, songs: &mut [Song], stored: F)
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let mut assigned: HashMap<SongKey, String> = HashMap::new();
+        for song in songs {
+            self.assign(song, |k| assigned.get(k).cloned().or_else(|| stored(k)));
+            assigned.insert(song.id.clone(), song.webpage_url.clone());
+        }
+    }
+
+    /// Number of collisions detected this session
+    pub fn collisions(&self) -> usize {
+        self.collisions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::song::Song;
+
+    use super::{urls_differ, KeyRegistry};
+
+    fn song(id: &str, url: &str, extractor: &str) -> Song {
+        Song {
+            id: id.to_string(),
+            webpage_url: url.to_string(),
+            extractor_key: Some(extractor.to_string()),
+            ..Song::basic()
+        }
+    }
+
+    #[test]
+    fn trivial_url_differences_are_ignored() {
+        assert![!urls_differ(
+            "https://music.youtube.com/watch?v=abc&si=123",
+            "https://www.youtube.com/watch?v=abc"
+        )];
+        assert![urls_differ(
+            "https://music.youtube.com/watch?v=abc",
+            "https://soundcloud.com/someone/abc"
+        )];
+        assert![!urls_differ("...", "https://soundcloud.com/someone/abc")];
+    }
+
+    #[test]
+    fn colliding_songs_get_distinct_keys() {
+        let mut registry = KeyRegistry::default();
+        let youtube = "https://music.youtube.com/watch?v=abc";
+        let soundcloud = "https://soundcloud.com/someone/abc";
+
+        // Songs of the same batch collide with each other
+        let mut batch = [
+            song("abc", youtube, "Youtube"),
+            song("abc", soundcloud, "Soundcloud"),
+        ];
+        registry.assign_batch(&mut batch, |_| None);
+        assert_eq![batch[0].id, "abc"];
+        assert_ne![batch[1].id, "abc"];
+        assert_eq![batch[1].raw_id.as_deref(), Some("abc")];
+        assert_eq![registry.collisions(), 1];
+
+        // Ingesting the same newcomer again resolves to the same key, as a stored song does
+        let stored = |key: &str| (key == "abc").then(|| youtube.to_string());
+        let mut again = [song("abc", soundcloud, "Soundcloud")];
+        registry.assign_batch(&mut again, stored);
+        assert_eq![again[0].id, batch[1].id];
+        let mut fresh = KeyRegistry::default();
+        let mut again = [song("abc", soundcloud, "Soundcloud")];
+        fresh.assign_batch(&mut again, stored);
+        assert_eq![again[0].id, batch[1].id];
+        assert_eq![registry.collisions(), 1];
+    }
+
+    #[test]
+    fn same_song_keeps_its_id() {
+        let mut registry = KeyRegistry::default();
+        let mut s = song("abc", "https://www.youtube.com/watch?v=abc", "Youtube");
+        registry.assign(&mut s, |_| {
+            Some("https://music.youtube.com/watch?v=abc".to_string())
+        });
+        assert_eq![s.id, "abc"];
+        assert_eq![s.raw_id, None];
+        assert_eq![registry.collisions(), 0];
+    }
+}
@@ -0,0 +1,317 @@
+use std::collections::HashMap;
+
+use crate::settings::SongKey;
+
+/// Something that keeps songs in use, and so in the cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeySource {
+    Playlist,
+    Search,
+    /// The saved playlists, including the open one as it was saved
+    Library,
+    History,
+    Queue,
+}
+
+/// The keys a source used when it was last synced
+#[derive(Debug, Default)]
+struct Contribution {
+    /// None until the first sync
+    revision: Option<u64>,
+    keys: Vec<SongKey>,
+}
+
+/// The keys in use by any source, kept up to date by syncing each source when it changes
+/// instead of gathering every key again.
+#[derive(Debug, Default)]
+pub struct UsedKeys {
+    /// How many times each key is used, over every source
+    counts: HashMap<SongKey, usize>,
+    contributions: HashMap<KeySource, Contribution>,
+}
+
+impl UsedKeys {
+    /// Updates what the source uses. Nothing is walked if the revision is the one last synced,
+    /// otherwise only the keys between the unchanged start and end are counted again.
+    pub fn sync<'a>(
+        &mut self,
+        source: KeySource,
+        revision: u64,
+        keys: impl IntoIterator<Item = &'a SongKey>,
+    ) {
+        let contribution = self.contributions.entry(source).or_default();
+        if contribution.revision == Some(revision) {
+            return;
+        }
+        contribution.revision = Some(revision);
+
+        let new: Vec<&SongKey> = keys.into_iter().collect();
+        let old = &contribution.keys;
+        let start = old.iter().zip(&new).take_while(|(a, b)| a == *b).count();
+        let end = old[start..]
+            .iter()
+            .rev()
+            .zip(new[start..].iter().rev())
+            .take_while(|(a, b)| a == *b)
+            .count();
+        let removed = start..old.len() - end;
+        let added = &new[start..new.len() - end];
+
+        for key in &old[removed.clone()] {
+            if let Some(count) = self.counts.get_mut(key) {
+                *count -= 1;
+                if *count == 0 {
+                    self.counts.remove(key);
+                }
+            }
+        }
+        for key in added {
+            match self.counts.get_mut(*key) {
+                Some(count) => *count += 1,
+                None => {
+                    self.counts.insert((*key).clone(), 1);
+                }
+            }
+        }
+        contribution
+            .keys
+            .splice(removed, added.iter().map(|key| (*key).clone()));
+    }
+
+    pub fn contains(&self, key: &SongKey) -> bool {
+        self.counts.contains_key(key)
+    }
+
+    /// Every key in use, once
+    pub fn keys(&self) -> impl Iterator<Item = &SongKey> {
+        self.counts.keys()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{HashSet, VecDeque},
+        time::Instant,
+    };
+
+    use chrono::Local;
+
+    use crate::{
+        caching::readers::LineBasedReader,
+        history::{History, PlayTrigger},
+        playlist::{Playlist, PlaylistLibrary},
+        queue::Queue,
+        search_window::{SearchType, SearchWindow},
+        settings::SongKey,
+        song_operations::{
+            ActualRecursiveOps, CItemMessage, ConstructorItem, SongOpConstructor, SongOpMessage,
+            SongOpTracker, TreeDirected,
+        },
+    };
+
+    use super::{KeySource, UsedKeys};
+
+    fn song(key: &str) -> ConstructorItem {
+        ConstructorItem::from(key.to_string())
+    }
+
+    fn sync(used: &mut UsedKeys, tree: &SongOpConstructor, search: &SearchWindow) {
+        used.sync(
+            KeySource::Playlist,
+            tree.revision(),
+            tree.all_song_keys_rec(),
+        );
+        used.sync(KeySource::Search, search.revision(), search.used_keys());
+    }
+
+    /// What used to be gathered on every tick
+    fn from_scratch(tree: &SongOpConstructor, search: &SearchWindow) -> HashSet<SongKey> {
+        let from_search: HashSet<&String> = search.used_keys().into_iter().collect();
+        let from_tree: HashSet<&String> = tree.all_song_keys_rec().collect();
+        from_search.union(&from_tree).cloned().cloned().collect()
+    }
+
+    fn assert_synced(used: &mut UsedKeys, tree: &SongOpConstructor, search: &SearchWindow) {
+        sync(used, tree, search);
+        let keys: HashSet<SongKey> = used.keys().cloned().collect();
+        assert_eq![keys, from_scratch(tree, search)];
+        assert![keys.iter().all(|key| used.contains(key))];
+    }
+
+    #[test]
+    fn stays_equal_to_a_recomputation() {
+        let mut used = UsedKeys::default();
+        let mut tree = SongOpConstructor::from(vec![
+            song("a"),
+            song("b"),
+            ConstructorItem::Operation(SongOpConstructor::new(
+                ActualRecursiveOps::LoopNTimes,
+                vec![song("c"), song("a")],
+                None,
+            )),
+        ]);
+        let mut search = SearchWindow::default();
+        assert_synced(&mut used, &tree, &search);
+
+        // Through the tree's own messages
+        tree.update(SongOpMessage::Remove(0));
+        assert_synced(&mut used, &tree, &search);
+        tree.update(SongOpMessage::ItemMessage(
+            1,
+            CItemMessage::Operation(Box::new(SongOpMessage::Remove(0))),
+        ));
+        assert_synced(&mut used, &tree, &search);
+        tree.update(SongOpMessage::NewGroup);
+        assert_synced(&mut used, &tree, &search);
+
+        // Through the edits the app makes to it
+        tree.push_to_path(VecDeque::from([1, 0]), song("d"));
+        tree.touch();
+        assert_synced(&mut used, &tree, &search);
+        tree.wrap_song(vec![0], ActualRecursiveOps::PlayOnce, 1);
+        assert_synced(&mut used, &tree, &search);
+        tree.flatten_group(vec![1]);
+        assert_synced(&mut used, &tree, &search);
+        tree.promote(vec![0], true);
+        assert_synced(&mut used, &tree, &search);
+
+        // A song shown in the search stays used after leaving the tree, and the other way around
+        search.search_type = SearchType::new_tab(vec!["b".into(), "e".into()]);
+        search.touch();
+        assert_synced(&mut used, &tree, &search);
+        tree = SongOpConstructor::from(vec![song("e")]);
+        assert_synced(&mut used, &tree, &search);
+        assert![used.contains(&"b".to_string())];
+        search.search_type = SearchType::new_tab(vec![]);
+        search.touch();
+        assert_synced(&mut used, &tree, &search);
+        assert_eq![used.keys().collect::<Vec<_>>(), vec!["e"]];
+    }
+
+    #[test]
+    fn unchanged_sources_are_not_walked() {
+        let mut used = UsedKeys::default();
+        let tree = SongOpConstructor::from(vec![song("a")]);
+        used.sync(
+            KeySource::Playlist,
+            tree.revision(),
+            tree.all_song_keys_rec(),
+        );
+
+        let walked = std::iter::from_fn(|| -> Option<&'static SongKey> { panic!["walked"] });
+        used.sync(KeySource::Playlist, tree.revision(), walked);
+        assert![used.contains(&"a".to_string())];
+
+        // A replaced tree is walked even though nothing touched it
+        let tree = SongOpConstructor::from(vec![song("b")]);
+        used.sync(
+            KeySource::Playlist,
+            tree.revision(),
+            tree.all_song_keys_rec(),
+        );
+        assert_eq![used.keys().collect::<Vec<_>>(), vec!["b"]];
+    }
+
+    #[test]
+    fn switching_playlists_keeps_shared_songs_once() {
+        let mut used = UsedKeys::default();
+        let search = SearchWindow::default();
+        let focus = SongOpConstructor::from(vec![song("a"), song("shared")]);
+        let gym = SongOpConstructor::from(vec![song("shared"), song("shared"), song("b")]);
+        assert_synced(&mut used, &focus, &search);
+
+        // Playlists read from their files are walked too
+        let gym: SongOpConstructor =
+            serde_json::from_str(&serde_json::to_string(&gym).unwrap()).unwrap();
+        assert_synced(&mut used, &gym, &search);
+        let mut keys: Vec<&SongKey> = used.keys().collect();
+        keys.sort();
+        assert_eq![keys, vec!["b", "shared"]];
+    }
+
+    #[test]
+    fn songs_outside_the_open_playlist_stay_used() {
+        let mut used = UsedKeys::default();
+        let mut library = PlaylistLibrary::default();
+        let dir = tempfile::tempdir().unwrap();
+        let mut history = History::new(LineBasedReader::new(dir.path().join("h.ndjson")), 2);
+        let mut queue = Queue::default();
+        let sync_others =
+            |used: &mut UsedKeys, library: &PlaylistLibrary, history: &History, queue: &Queue| {
+                used.sync(KeySource::Library, library.revision(), library.used_keys());
+                used.sync(KeySource::History, history.revision(), history.used_keys());
+                used.sync(KeySource::Queue, queue.revision(), queue.used_keys());
+            };
+        let used_keys = |used: &UsedKeys| {
+            let mut keys: Vec<&str> = used.keys().map(String::as_str).collect();
+            keys.sort();
+            keys.join(",")
+        };
+
+        let gym = Playlist {
+            constructor: SongOpConstructor::from(vec![song("gym")]),
+            ..Default::default()
+        };
+        library.record(&gym);
+        // Only what's kept in memory is used, so the file isn't written
+        drop(history.record("played".into(), PlayTrigger::Picked, Local::now()));
+        let tree = SongOpConstructor::from(vec![song("now"), song("next")]);
+        let tracker = SongOpTracker::start(&tree.build(), [0].into()).unwrap();
+        queue.refresh(Some(&tracker), &tree);
+        sync_others(&mut used, &library, &history, &queue);
+        assert_eq![used_keys(&used), "gym,next,now,played"];
+
+        // Each source lets go of its songs on its own
+        library.forget(gym.id);
+        drop(history.record("a".into(), PlayTrigger::Picked, Local::now()));
+        drop(history.record("b".into(), PlayTrigger::Picked, Local::now()));
+        queue.refresh(None, &tree);
+        sync_others(&mut used, &library, &history, &queue);
+        assert_eq![used_keys(&used), "a,b"];
+    }
+
+    /// Run with `cargo test tick_cost -- --ignored --nocapture`
+    #[test]
+    #[ignore = "benchmark"]
+    fn tick_cost_on_a_large_library() {
+        const TICKS: u32 = 100;
+        let groups = (0..100)
+            .map(|g| {
+                let songs = (0..100).map(|i| song(&format!("song-{g}-{i}"))).collect();
+                ConstructorItem::Operation(SongOpConstructor::new(
+                    ActualRecursiveOps::PlayOnce,
+                    songs,
+                    None,
+                ))
+            })
+            .collect();
+        let mut tree = SongOpConstructor::from(groups);
+        let mut search = SearchWindow::default();
+        search.search_type = SearchType::new_tab((0..50).map(|i| format!("song-0-{i}")).collect());
+        assert_eq![from_scratch(&tree, &search).len(), 10_000];
+
+        let start = Instant::now();
+        for _ in 0..TICKS {
+            std::hint::black_box(from_scratch(&tree, &search));
+        }
+        let recomputed = start.elapsed() / TICKS;
+
+        let mut used = UsedKeys::default();
+        sync(&mut used, &tree, &search);
+        let start = Instant::now();
+        for tick in 0..TICKS {
+            // An edit every 10 ticks, which is already a lot for 20 seconds apart
+            if tick % 10 == 0 {
+                tree.insert(0, song(&format!("new-{tick}")));
+                tree.touch();
+            }
+            sync(&mut used, &tree, &search);
+        }
+        let synced = start.elapsed() / TICKS;
+
+        println!["Per tick: {recomputed:?} recomputed, {synced:?} synced"];
+        assert_eq![used.keys().count(), 10_010];
+        assert![synced < recomputed];
+    }
+}
@@ -0,0 +1,254 @@
+//! The flags the app is started with. They're checked before the window opens, so a mistake is
+//! printed instead of doing nothing.
+
+use std::{fmt, path::PathBuf};
+
+use crate::{
+    backend_settings::BackendSettings,
+    playlist::PlaylistHeader,
+    settings::{playlist_path, playlists_directory},
+    verbosity::Verbosity,
+};
+
+pub const USAGE: &str = "\
+Usage: ytm-rs [options]
+
+  --settings <path>     Reads and saves the settings in this file
+  --playlist <name>     Opens the saved playlist with the name, or the playlist file at the path
+  --play                Starts playing once the playlist is open
+  --port <port>         Connects to the backend on this port, without saving it
+  --no-backend-launch   Never starts the backend, only connects to a running one
+  --new-instance        Starts even if the app is running already, instead of showing it
+  --demo                Runs on generated songs, in builds with the demo feature
+  -v, -vv, --verbose    Prints more of what the app is doing
+  -h, --help            Prints this";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlaylistChoice {
+    File(PathBuf),
+    /// A playlist in the library, found before the window opens
+    Named(String),
+}
+
+impl PlaylistChoice {
+    fn new(value: String) -> Result<Self, ArgError> {
+        let path = PathBuf::from(&value);
+        let is_path = value.contains(['/', std::path::MAIN_SEPARATOR]) || value.ends_with(".json");
+        match (path.is_file(), is_path) {
+            (true, _) => Ok(Self::File(path)),
+            (false, true) => Err(ArgError::MissingFile(path)),
+            (false, false) => Ok(Self::Named(value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StartupOptions {
+    /// Replaces the saved port for this run
+    pub port: Option<u16>,
+    pub no_backend_launch: bool,
+    pub playlist: Option<PlaylistChoice>,
+    /// Plays the playlist that's open once it's loaded
+    pub play: bool,
+    pub settings: Option<PathBuf>,
+    /// Skips looking for a running instance
+    pub new_instance: bool,
+    pub help: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgError {
+    Unknown(String),
+    MissingValue(&'static str),
+    Repeated(&'static str),
+    InvalidPort(String),
+    /// The flags can't be used together
+    Conflict(&'static str, &'static str),
+    MissingFile(PathBuf),
+    NoPlaylistNamed(String),
+    SeveralNamed(String),
+}
+
+impl fmt::Display for ArgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unknown(arg) => write!(f, "Unknown argument {arg:?}"),
+            Self::MissingValue(flag) => write!(f, "{flag} needs a value after it"),
+            Self::Repeated(flag) => write!(f, "{flag} was given more than once"),
+            Self::InvalidPort(value) => write!(f, "{value:?} isn't a port, use 1 to 65535"),
+            Self::Conflict(a, b) => write!(f, "{a} can't be used with {b}"),
+            Self::MissingFile(path) => write!(f, "There's no playlist file at {path:?}"),
+            Self::NoPlaylistNamed(name) => write!(f, "There's no saved playlist named {name:?}"),
+            Self::SeveralNamed(name) => write!(
+                f,
+                "More than one saved playlist is named {name:?}, pass its file instead"
+            ),
+        }
+    }
+}
+
+/// Sets the option, unless it was set already
+fn once<T>(option: &mut Option<T>, value: T, flag: &'static str) -> Result<(), ArgError> {
+    match option.replace(value) {
+        Some(_) => Err(ArgError::Repeated(flag)),
+        None => Ok(()),
+    }
+}
+
+fn value(args: &mut impl Iterator<Item = String>, flag: &'static str) -> Result<String, ArgError> {
+    args.next().ok_or(ArgError::MissingValue(flag))
+}
+
+fn switch(on: &mut bool, flag: &'static str) -> Result<(), ArgError> {
+    match std::mem::replace(on, true) {
+        true => Err(ArgError::Repeated(flag)),
+        false => Ok(()),
+    }
+}
+
+impl StartupOptions {
+    /// The first argument is the program's name
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, ArgError> {
+        let mut options = Self::default();
+        let mut demo = false;
+        let mut args = args.into_iter().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--port" => {
+                    let port = value(&mut args, "--port")?;
+                    match port.parse::<u16>() {
+                        Ok(parsed) if parsed > 0 => once(&mut options.port, parsed, "--port")?,
+                        _ => return Err(ArgError::InvalidPort(port)),
+                    }
+                }
+                "--playlist" => {
+                    let choice = PlaylistChoice::new(value(&mut args, "--playlist")?)?;
+                    once(&mut options.playlist, choice, "--playlist")?
+                }
+                "--settings" => {
+                    let path = PathBuf::from(value(&mut args, "--settings")?);
+                    once(&mut options.settings, path, "--settings")?
+                }
+                "--no-backend-launch" => {
+                    switch(&mut options.no_backend_launch, "--no-backend-launch")?
+                }
+                "--play" => switch(&mut options.play, "--play")?,
+                "--new-instance" => switch(&mut options.new_instance, "--new-instance")?,
+                "-h" | "--help" => options.help = true,
+                "--demo" if cfg!(feature = "demo") => demo = true,
+                arg if Verbosity::raised_by(arg) > 0 => {}
+                _ => return Err(ArgError::Unknown(arg)),
+            }
+        }
+        // The demo moves every file elsewhere, the settings included
+        if demo && options.settings.is_some() {
+            return Err(ArgError::Conflict("--demo", "--settings"));
+        }
+        Ok(options)
+    }
+
+    /// Finds the named playlist among the saved ones. Headers are only read if it's named.
+    pub fn resolve(
+        mut self,
+        headers: impl FnOnce() -> Vec<PlaylistHeader>,
+    ) -> Result<Self, ArgError> {
+        let name = match &self.playlist {
+            Some(PlaylistChoice::Named(name)) => name.clone(),
+            _ => return Ok(self),
+        };
+        let headers = headers();
+        let mut named = headers
+            .iter()
+            .filter(|header| header.name.to_lowercase() == name.to_lowercase());
+        match (named.next(), named.next()) {
+            (Some(header), None) => {
+                let path = playlist_path(&playlists_directory(), header.id);
+                self.playlist = Some(PlaylistChoice::File(path));
+                Ok(self)
+            }
+            (Some(_), Some(_)) => Err(ArgError::SeveralNamed(name)),
+            (None, _) => Err(ArgError::NoPlaylistNamed(name)),
+        }
+    }
+
+    /// The playlist file to open, once it's resolved
+    pub fn playlist_file(&self) -> Option<PathBuf> {
+        match &self.playlist {
+            Some(PlaylistChoice::File(path)) => Some(path.clone()),
+            _ => None,
+        }
+    }
+
+    /// The saved settings, with the flags applied
+    pub fn backend(&self, saved: &BackendSettings) -> BackendSettings {
+        BackendSettings {
+            port: self.port.unwrap_or(saved.port),
+            auto_launch: saved.auto_launch && !self.no_backend_launch,
+            ..saved.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{ArgError, PlaylistChoice, StartupOptions};
+    use crate::{
+        backend_settings::BackendSettings,
+        playlist::PlaylistHeader,
+        settings::{playlist_path, playlists_directory},
+    };
+
+    fn parse(args: &str) -> Result<StartupOptions, ArgError> {
+        let args = ["ytm-rs"].into_iter().chain(args.split_whitespace());
+        StartupOptions::parse(args.map(String::from))
+    }
+
+    #[test]
+    fn flags_are_read() {
+        let options = parse("-v --port 6000 --play --playlist Gym --no-backend-launch").unwrap();
+        assert_eq![options.port, Some(6000)];
+        assert_eq![options.playlist, Some(PlaylistChoice::Named("Gym".into()))];
+        assert![options.play && options.no_backend_launch];
+        let backend = options.backend(&BackendSettings::default());
+        assert_eq![(backend.port, backend.auto_launch), (6000, false)];
+
+        let options = parse("--settings other.json --new-instance").unwrap();
+        assert_eq![options.settings, Some(PathBuf::from("other.json"))];
+        assert![options.new_instance];
+        assert_eq![parse("").unwrap(), StartupOptions::default()];
+    }
+
+    #[test]
+    fn mistakes_are_errors() {
+        let error = |args| parse(args).unwrap_err();
+        assert_eq![error("--prot 6000"), ArgError::Unknown("--prot".into())];
+        assert_eq![error("--port"), ArgError::MissingValue("--port")];
+        assert_eq![error("--port 0"), ArgError::InvalidPort("0".into())];
+        assert_eq![error("--port 70000"), ArgError::InvalidPort("70000".into())];
+        assert_eq![error("--play --play"), ArgError::Repeated("--play")];
+        let missing = ArgError::MissingFile(PathBuf::from("no/such.json"));
+        assert_eq![error("--playlist no/such.json"), missing];
+    }
+
+    #[test]
+    fn named_playlists_are_found_in_the_library() {
+        let header = |name: &str| PlaylistHeader {
+            id: uuid::Uuid::new_v4(),
+            name: name.into(),
+            num_of_songs: 0,
+            keys: vec![],
+        };
+        let headers = vec![header("Gym"), header("Sleep"), header("sleep")];
+        let resolve = |name: &str| {
+            let options = parse(&format!("--playlist {name}")).unwrap();
+            options.resolve(|| headers.clone())
+        };
+        let gym = playlist_path(&playlists_directory(), headers[0].id);
+        assert_eq![resolve("gym").unwrap().playlist_file(), Some(gym)];
+        let error = |name| resolve(name).unwrap_err();
+        assert_eq![error("Sleep"), ArgError::SeveralNamed("Sleep".into())];
+        assert_eq![error("Run"), ArgError::NoPlaylistNamed("Run".into())];
+    }
+}
@@ -0,0 +1,134 @@
+//! A menu of actions for a song row, opened by right-clicking the row and shown under it.
+//! Only one menu is open at a time. It closes once one of its actions is picked, or when a
+//! click lands on something that doesn't handle it, see [`closes_menus`].
+
+use iced::{
+    advanced::widget::Id as WId,
+    widget::{button, column, container, mouse_area, text, Column, Row},
+    Background, Border, Color, Element, Length,
+};
+
+use crate::song::MAX_RATING;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SongAction {
+    PlayNow,
+    /// Plays the song after the current one, then goes on from where the queue was
+    PlayNext,
+    AddToPlaylist,
+    CopyUrl,
+    /// Asks the backend for the song's info again
+    RefreshInfo,
+    /// Deletes the song's file from the audio cache
+    RemoveDownload,
+    Remove,
+    /// Sets the song's rating, None clears it
+    Rate(Option<u8>),
+}
+
+impl SongAction {
+    /// Shown together as a row of stars under the other actions
+    pub const RATINGS: [SongAction; 6] = [
+        SongAction::Rate(Some(1)),
+        SongAction::Rate(Some(2)),
+        SongAction::Rate(Some(3)),
+        SongAction::Rate(Some(4)),
+        SongAction::Rate(Some(5)),
+        SongAction::Rate(None),
+    ];
+    /// Search results aren't in the playlist, so there's nothing to remove
+    pub const SEARCH: [SongAction; 6] = [
+        SongAction::PlayNow,
+        SongAction::PlayNext,
+        SongAction::AddToPlaylist,
+        SongAction::CopyUrl,
+        SongAction::RefreshInfo,
+        SongAction::RemoveDownload,
+    ];
+    pub const PLAYLIST: [SongAction; 7] = [
+        SongAction::PlayNow,
+        SongAction::PlayNext,
+        SongAction::AddToPlaylist,
+        SongAction::CopyUrl,
+        SongAction::RefreshInfo,
+        SongAction::RemoveDownload,
+        SongAction::Remove,
+    ];
+
+    fn label(&self) -> String {
+        match self {
+            SongAction::PlayNow => "Play now".into(),
+            SongAction::PlayNext => "Play next".into(),
+            SongAction::AddToPlaylist => "Add to current playlist".into(),
+            SongAction::CopyUrl => "Copy webpage URL".into(),
+            SongAction::RefreshInfo => "Refresh info".into(),
+            SongAction::RemoveDownload => "Remove download".into(),
+            SongAction::Remove => "Remove".into(),
+            SongAction::Rate(Some(rating)) => format!("{}★", rating.min(&MAX_RATING)),
+            SongAction::Rate(None) => "☆".into(),
+        }
+    }
+}
+
+/// The row whose menu is open
+#[derive(Debug, Clone, PartialEq)]
+pub enum MenuTarget {
+    /// The index of the search result
+    Search(usize),
+    Playlist(WId),
+}
+
+/// Makes the row open its menu when it's right-clicked, and shows the menu under it while
+/// it's open. Removing the download is only offered for songs with one.
+pub fn with_menu<'a, M: Clone + 'a>(
+    row: impl Into<Element<'a, M>>,
+    open: bool,
+    on_open: M,
+    actions: &[SongAction],
+    downloaded: bool,
+    on_action: impl Fn(SongAction) -> M,
+) -> Element<'a, M> {
+    let row = mouse_area(row).on_right_press(on_open);
+    if !open {
+        return row.into();
+    }
+    let actions = actions
+        .iter()
+        .filter(|action| downloaded || **action != SongAction::RemoveDownload);
+    let buttons = actions.map(|action| {
+        button(text(action.label()))
+            .width(Length::Fill)
+            .on_press(on_action(*action))
+            .into()
+    });
+    let stars = SongAction::RATINGS.iter().map(|action| {
+        button(text(action.label()))
+            .on_press(on_action(*action))
+            .into()
+    });
+    let actions = Column::with_children(buttons)
+        .push(Row::with_children(stars).spacing(2))
+        .spacing(2);
+    let menu = container(actions)
+        .padding(4)
+        .max_width(220)
+        .style(|_| container::Style {
+            background: Some(Background::Color(Color::BLACK)),
+            border: Border::rounded(4).with_width(1).with_color(Color::WHITE),
+            ..Default::default()
+        });
+    column![row, menu].into()
+}
+
+/// Closes the open menu on clicks that nothing under the cursor handled.
+/// Wraps everything the menus are shown in, `close` is None while no menu is open.
+pub fn closes_menus<'a, M: Clone + 'a>(
+    content: impl Into<Element<'a, M>>,
+    close: Option<M>,
+) -> Element<'a, M> {
+    let area = mouse_area(content);
+    match close {
+        Some(close) => area.on_press(close.clone()).on_right_press(close).into(),
+        None => area.into(),
+    }
+}
@@ -0,0 +1,11 @@
+//! Playing songs, through the equalizer, at a gain that evens out their loudness
+
+mod effects;
+mod loading;
+mod loudness;
+mod manager;
+
+pub use effects::*;
+pub use loading::*;
+pub use loudness::*;
+pub use manager::*;
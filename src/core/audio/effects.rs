@@ -0,0 +1,167 @@
+//! The equalizer and limiter every song is played through.
+//! They're on a track of their own, so changing them takes effect on the playing song.
+
+use std::time::Duration;
+
+use kira::{
+    effect::{
+        compressor::{CompressorBuilder, CompressorHandle},
+        eq_filter::{EqFilterBuilder, EqFilterHandle, EqFilterKind},
+    },
+    manager::AudioManager,
+    track::{TrackBuilder, TrackHandle},
+    tween::Tween,
+};
+use serde::{Deserialize, Serialize};
+
+/// How far a band can be boosted or cut, in decibels
+pub const MAX_EQ_GAIN_DB: f64 = 12.0;
+/// Short enough to feel immediate, long enough not to crackle while a slider is dragged
+const EFFECT_TWEEN: Duration = Duration::from_millis(50);
+/// The ratio the limiter compresses with, 1 leaves the sound alone
+const LIMITER_RATIO: f64 = 20.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EqBand {
+    Low,
+    Mid,
+    High,
+}
+
+impl EqBand {
+    pub const ALL: [EqBand; 3] = [EqBand::Low, EqBand::Mid, EqBand::High];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            EqBand::Low => "low",
+            EqBand::Mid => "mid",
+            EqBand::High => "high",
+        }
+    }
+
+    fn index(&self) -> usize {
+        match self {
+            EqBand::Low => 0,
+            EqBand::Mid => 1,
+            EqBand::High => 2,
+        }
+    }
+
+    fn filter(&self, gain_db: f64) -> EqFilterBuilder {
+        let (kind, frequency) = match self {
+            EqBand::Low => (EqFilterKind::LowShelf, 250.0),
+            EqBand::Mid => (EqFilterKind::Bell, 1000.0),
+            EqBand::High => (EqFilterKind::HighShelf, 4000.0),
+        };
+        EqFilterBuilder::new(kind, frequency, gain_db, 0.7)
+    }
+}
+
+/// The gain of each band in decibels, flat by default
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EqSettings {
+    pub low: f64,
+    pub mid: f64,
+    pub high: f64,
+    /// Keeps boosted bands from clipping
+    pub limiter: bool,
+}
+
+impl EqSettings {
+    pub fn gain(&self, band: EqBand) -> f64 {
+        match band {
+            EqBand::Low => self.low,
+            EqBand::Mid => self.mid,
+            EqBand::High => self.high,
+        }
+    }
+
+    /// Sets the band's gain, limited to [`MAX_EQ_GAIN_DB`] either way. Returns the gain set.
+    pub fn set_gain(&mut self, band: EqBand, gain_db: f64) -> f64 {
+        let gain_db = match gain_db.is_finite() {
+            true => gain_db.clamp(-MAX_EQ_GAIN_DB, MAX_EQ_GAIN_DB),
+            false => 0.0,
+        };
+        match band {
+            EqBand::Low => self.low = gain_db,
+            EqBand::Mid => self.mid = gain_db,
+            EqBand::High => self.high = gain_db,
+        }
+        gain_db
+    }
+}
+
+/// The track songs are played on, and the handles to its effects
+pub struct EffectsTrack {
+    track: TrackHandle,
+    bands: [EqFilterHandle; 3],
+    limiter: CompressorHandle,
+}
+
+impl EffectsTrack {
+    /// Adds the track to the manager, flat and without limiting
+    pub fn new(manager: &mut AudioManager) -> Self {
+        let mut builder = TrackBuilder::new();
+        let bands = EqBand::ALL.map(|band| builder.add_effect(band.filter(0.0)));
+        let limiter = builder.add_effect(
+            CompressorBuilder::new()
+                .threshold(-1.0)
+                .ratio(1.0)
+                .attack_duration(Duration::from_millis(1)),
+        );
+        Self {
+            track: manager.add_sub_track(builder).unwrap(),
+            bands,
+            limiter,
+        }
+    }
+
+    /// Where played sounds should go
+    pub fn output(&self) -> &TrackHandle {
+        &self.track
+    }
+
+    pub fn set_eq(&mut self, band: EqBand, gain_db: f64) {
+        self.bands[band.index()].set_gain(gain_db, tween());
+    }
+
+    pub fn set_limiter(&mut self, on: bool) {
+        let ratio = match on {
+            true => LIMITER_RATIO,
+            false => 1.0,
+        };
+        self.limiter.set_ratio(ratio, tween());
+    }
+
+    pub fn apply(&mut self, settings: &EqSettings) {
+        for band in EqBand::ALL {
+            self.set_eq(band, settings.gain(band));
+        }
+        self.set_limiter(settings.limiter);
+    }
+}
+
+fn tween() -> Tween {
+    Tween {
+        duration: EFFECT_TWEEN,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EqBand, EqSettings, MAX_EQ_GAIN_DB};
+
+    #[test]
+    fn gains_stay_in_range() {
+        let mut eq: EqSettings = serde_json::from_str("{}").unwrap();
+        assert_eq![eq, EqSettings::default()];
+        assert![EqBand::ALL.iter().all(|band| eq.gain(*band) == 0.0)];
+
+        assert_eq![eq.set_gain(EqBand::Low, 3.5), 3.5];
+        assert_eq![eq.set_gain(EqBand::High, 40.0), MAX_EQ_GAIN_DB];
+        assert_eq![eq.set_gain(EqBand::Mid, f64::NAN), 0.0];
+        assert_eq![(eq.low, eq.mid, eq.high), (3.5, 0.0, MAX_EQ_GAIN_DB)];
+    }
+}
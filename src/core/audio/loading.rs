@@ -0,0 +1,206 @@
+//! Whether a song is decoded into memory before it plays, or streamed from its file.
+//! Decoded songs seek instantly, but hours of decoded audio don't fit in memory.
+
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+/// How the user wants a song loaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PlaybackMode {
+    /// Decided from the song's size and the free memory
+    #[default]
+    Auto,
+    Static,
+    Stream,
+}
+
+impl PlaybackMode {
+    pub const ALL: [PlaybackMode; 3] = [Self::Auto, Self::Static, Self::Stream];
+
+    pub fn is_auto(&self) -> bool {
+        *self == Self::Auto
+    }
+}
+
+impl Display for PlaybackMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Auto => "load automatically",
+            Self::Static => "always load fully",
+            Self::Stream => "always stream",
+        })
+    }
+}
+
+/// How a song ends up being loaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Loading {
+    Static,
+    Stream,
+}
+
+/// What's known about a song when its loading is decided
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LoadFacts {
+    /// Of the downloaded file
+    pub file_size: Option<u64>,
+    /// In seconds
+    pub duration: Option<f64>,
+    /// The free memory, in bytes
+    pub headroom: Option<u64>,
+}
+
+/// A decoded second is 48000 frames of two f32s at most
+const DECODED_BYTES_PER_SECOND: f64 = 48_000.0 * 8.0;
+/// How much bigger decoding makes a file, for ~128kbps audio
+const DECODED_PER_FILE_BYTE: u64 = 24;
+/// Songs that decode to more than this are streamed, about 20 minutes of audio
+pub const STATIC_LIMIT: u64 = 448 * 1024 * 1024;
+/// A decoded song may take at most this fraction of the free memory
+const HEADROOM_SHARE: u64 = 4;
+/// Songs whose file is bigger than this stream, unless the user picked another size
+pub const DEFAULT_STREAM_OVER_MB: u64 = 30;
+
+/// About how much memory the song takes once decoded
+pub fn decoded_estimate(facts: &LoadFacts) -> Option<u64> {
+    match facts.duration.filter(|duration| *duration > 0.0) {
+        Some(duration) => Some((duration * DECODED_BYTES_PER_SECOND) as u64),
+        None => facts
+            .file_size
+            .filter(|size| *size > 0)
+            .map(|size| size.saturating_mul(DECODED_PER_FILE_BYTE)),
+    }
+}
+
+/// How the song is loaded. Left to decide, files over `stream_over` bytes are streamed, and so
+/// are songs too big to decode into memory.
+pub fn resolve(mode: PlaybackMode, facts: &LoadFacts, stream_over: u64) -> Loading {
+    match mode {
+        PlaybackMode::Static => Loading::Static,
+        PlaybackMode::Stream => Loading::Stream,
+        PlaybackMode::Auto if facts.file_size.is_some_and(|size| size > stream_over) => {
+            Loading::Stream
+        }
+        PlaybackMode::Auto => match decoded_estimate(facts) {
+            // Most songs are short, so nothing known means a normal song
+            None => Loading::Static,
+            Some(bytes) => {
+                let tight = facts
+                    .headroom
+                    .is_some_and(|headroom| bytes > headroom / HEADROOM_SHARE);
+                match bytes > STATIC_LIMIT || tight {
+                    true => Loading::Stream,
+                    false => Loading::Static,
+                }
+            }
+        },
+    }
+}
+
+/// The "MemAvailable" of /proc/meminfo, in bytes
+fn parse_meminfo(meminfo: &str) -> Option<u64> {
+    let line = meminfo
+        .lines()
+        .find(|line| line.starts_with("MemAvailable:"))?;
+    let kilobytes: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kilobytes * 1024)
+}
+
+/// The free memory, where it can be found
+pub fn available_memory() -> Option<u64> {
+    match cfg!(target_os = "linux") {
+        true => parse_meminfo(&std::fs::read_to_string("/proc/meminfo").ok()?),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decoded_estimate, parse_meminfo, resolve, LoadFacts, Loading, PlaybackMode};
+
+    const MB: u64 = 1024 * 1024;
+    const GB: u64 = 1024 * MB;
+
+    fn auto(file_size: Option<u64>, duration: Option<f64>, headroom: Option<u64>) -> Loading {
+        let facts = LoadFacts {
+            file_size,
+            duration,
+            headroom,
+        };
+        // Only the decoded size counts
+        resolve(PlaybackMode::Auto, &facts, u64::MAX)
+    }
+
+    #[test]
+    fn short_songs_load_and_long_mixes_stream() {
+        // A 4 minute song, with plenty of memory or none known
+        assert_eq![
+            auto(Some(4 * MB), Some(240.0), Some(8 * GB)),
+            Loading::Static
+        ];
+        assert_eq![auto(Some(4 * MB), Some(240.0), None), Loading::Static];
+        // A 3 hour mix
+        assert_eq![
+            auto(Some(170 * MB), Some(3.0 * 3600.0), Some(32 * GB)),
+            Loading::Stream
+        ];
+        // The duration is trusted over the file's size
+        assert_eq![auto(Some(170 * MB), Some(240.0), None), Loading::Static];
+    }
+
+    #[test]
+    fn tight_memory_streams_sooner() {
+        // A 10 minute song decodes to ~230MB
+        assert_eq![auto(None, Some(600.0), Some(4 * GB)), Loading::Static];
+        assert_eq![auto(None, Some(600.0), Some(512 * MB)), Loading::Stream];
+    }
+
+    #[test]
+    fn unknown_lengths_fall_back_to_the_file_size() {
+        // Live songs have no duration
+        assert_eq![auto(Some(40 * MB), None, None), Loading::Stream];
+        assert_eq![auto(Some(3 * MB), Some(0.0), None), Loading::Static];
+        assert_eq![auto(None, None, Some(MB)), Loading::Static];
+        assert_eq![decoded_estimate(&LoadFacts::default()), None];
+    }
+
+    #[test]
+    fn big_files_stream() {
+        // A 4 minute song, which is big as a wav
+        let four_minutes = |mode, file_size| {
+            let facts = LoadFacts {
+                file_size: Some(file_size),
+                duration: Some(240.0),
+                headroom: None,
+            };
+            resolve(mode, &facts, 30 * MB)
+        };
+        assert_eq![four_minutes(PlaybackMode::Auto, 40 * MB), Loading::Stream];
+        assert_eq![four_minutes(PlaybackMode::Auto, 20 * MB), Loading::Static];
+        // Unless the song is always loaded
+        assert_eq![four_minutes(PlaybackMode::Static, 40 * MB), Loading::Static];
+    }
+
+    #[test]
+    fn overrides_ignore_the_heuristic() {
+        let mix = LoadFacts {
+            duration: Some(3.0 * 3600.0),
+            ..Default::default()
+        };
+        assert_eq![resolve(PlaybackMode::Static, &mix, 0), Loading::Static];
+        let short = LoadFacts {
+            duration: Some(60.0),
+            ..Default::default()
+        };
+        let streamed = resolve(PlaybackMode::Stream, &short, u64::MAX);
+        assert_eq![streamed, Loading::Stream];
+    }
+
+    #[test]
+    fn free_memory_is_read_from_meminfo() {
+        let meminfo = "MemTotal:       16318480 kB\nMemAvailable:    8000000 kB\n";
+        assert_eq![parse_meminfo(meminfo), Some(8000000 * 1024)];
+        assert_eq![parse_meminfo("MemTotal: 1 kB"), None];
+    }
+}
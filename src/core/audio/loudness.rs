@@ -0,0 +1,106 @@
+//! Evening out how loud songs are. A song's loudness is measured once, when it's first decoded,
+//! and turned into a gain that brings it to the same level as the others.
+//! The measure is a gated RMS, close enough to EBU R128 without its weighting filter.
+
+use kira::Frame;
+
+/// The loudness songs are brought to, in dB of full scale
+pub const TARGET_LOUDNESS: f64 = -16.0;
+/// Quiet songs are raised by at most this much, so near-silent intros aren't blown up
+const MAX_BOOST: f64 = 4.0;
+/// Blocks are 400ms long, like R128's
+const BLOCK_SECONDS: f64 = 0.4;
+/// Blocks quieter than this are silence and don't count, in dB
+const SILENCE_GATE: f64 = -70.0;
+
+fn to_db(mean_square: f64) -> f64 {
+    10.0 * mean_square.log10()
+}
+
+/// The mean of the squared samples of both channels
+fn mean_square(frames: &[Frame]) -> f64 {
+    let sum: f64 = frames
+        .iter()
+        .map(|f| (f.left as f64).powi(2) + (f.right as f64).powi(2))
+        .sum();
+    sum / (frames.len() * 2) as f64
+}
+
+/// The loudness of the audio in dB of full scale, leaving out silent parts.
+/// None when it's silent throughout.
+pub fn loudness(frames: &[Frame], sample_rate: u32) -> Option<f64> {
+    let block = ((sample_rate as f64 * BLOCK_SECONDS) as usize).max(1);
+    let loud: Vec<f64> = frames
+        .chunks(block)
+        .map(mean_square)
+        .filter(|ms| *ms > 0.0 && to_db(*ms) > SILENCE_GATE)
+        .collect();
+    match loud.is_empty() {
+        true => None,
+        false => Some(to_db(loud.iter().sum::<f64>() / loud.len() as f64)),
+    }
+}
+
+/// The amplitude the audio is multiplied by to reach the target loudness.
+/// Boosts stop before the loudest sample would clip.
+pub fn normalizing_gain(frames: &[Frame], sample_rate: u32) -> Option<f64> {
+    let loudness = loudness(frames, sample_rate)?;
+    let peak = frames
+        .iter()
+        .map(|f| f.left.abs().max(f.right.abs()) as f64)
+        .fold(0.0, f64::max);
+    let gain = 10_f64.powf((TARGET_LOUDNESS - loudness) / 20.0);
+    match gain > 1.0 {
+        true => Some(gain.min(MAX_BOOST).min(1.0 / peak).max(1.0)),
+        false => Some(gain),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kira::Frame;
+
+    use super::{loudness, normalizing_gain, TARGET_LOUDNESS};
+
+    const RATE: u32 = 1000;
+
+    /// A square wave, whose RMS is its amplitude
+    fn square(amplitude: f32, frames: usize) -> Vec<Frame> {
+        (0..frames)
+            .map(|i| match i % 2 {
+                0 => Frame::from_mono(amplitude),
+                _ => Frame::from_mono(-amplitude),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn silence_is_left_out() {
+        let mut frames = square(0.1, 4000);
+        frames.extend(square(0.0, 40_000));
+        assert![(loudness(&frames, RATE).unwrap() - -20.0).abs() < 0.01];
+        assert_eq![loudness(&square(0.0, 4000), RATE), None];
+        assert_eq![normalizing_gain(&[], RATE), None];
+    }
+
+    #[test]
+    fn songs_are_brought_to_the_target() {
+        // Too loud, turned down
+        let loud = normalizing_gain(&square(0.5, 4000), RATE).unwrap();
+        let level = 20.0 * (0.5 * loud).log10();
+        assert![(level - TARGET_LOUDNESS).abs() < 0.01];
+
+        // Quiet, turned up
+        let quiet = normalizing_gain(&square(0.1, 4000), RATE).unwrap();
+        assert![quiet > 1.0 && (20.0 * (0.1 * quiet).log10() - TARGET_LOUDNESS).abs() < 0.01];
+    }
+
+    #[test]
+    fn boosts_stop_before_clipping() {
+        // Quiet on average, with one loud click
+        let mut frames = square(0.01, 4000);
+        frames[10] = Frame::from_mono(0.5);
+        let gain = normalizing_gain(&frames, RATE).unwrap();
+        assert![(gain - 2.0).abs() < 1e-6];
+    }
+}
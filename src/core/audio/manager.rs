@@ -0,0 +1,248 @@
+use std::{fmt::Debug, time::Duration};
+
+use kira::{
+    manager::{AudioManager, AudioManagerSettings, DefaultBackend},
+    sound::PlaybackState,
+    tween::Tween,
+    Volume,
+};
+
+use super::{EffectsTrack, EqBand, EqSettings};
+use crate::caching::{SoundData, SoundDataHandleType, SoundDataType};
+
+/// How long a replaced song fades out for, so it doesn't cut off with a click
+pub const DEFAULT_STOP_FADE: Duration = Duration::from_millis(50);
+
+pub struct CurrentSong {
+    /// Counts up with every song played, so checks meant for a replaced song can be told apart
+    pub number: u64,
+    pub handle: SoundDataHandleType,
+    pub duration: Duration,
+    /// Multiplied into the volume, to normalize the song's loudness
+    pub gain: f64,
+}
+
+pub struct YTMRSAudioManager {
+    manager: AudioManager,
+    effects: EffectsTrack,
+    current_song: Option<CurrentSong>,
+    songs_played: u64,
+}
+impl Debug for YTMRSAudioManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("YTMRSAudioManager")
+    }
+}
+impl Default for YTMRSAudioManager {
+    fn default() -> Self {
+        Self::new().unwrap()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ChangeSong {
+    /// Whether this is a check of the playback state, rather than the song's expected end
+    pub poll: bool,
+    /// The [`CurrentSong::number`] of the song it's for
+    pub song: u64,
+}
+
+impl YTMRSAudioManager {
+    /// Opens the default output device
+    pub fn new() -> Result<Self, String> {
+        let mut manager = AudioManager::<DefaultBackend>::new(AudioManagerSettings::default())
+            .map_err(|e| format!("{e:?}"))?;
+        Ok(Self {
+            effects: EffectsTrack::new(&mut manager),
+            manager,
+            current_song: None,
+            songs_played: 0,
+        })
+    }
+
+    /// The next check of the playing song, and how long to wait for it. The song's expected end
+    /// when its length is known, otherwise its playback state every second. None when nothing
+    /// plays.
+    pub fn next_change(&self) -> Option<(ChangeSong, Duration)> {
+        let song = self.current_song.as_ref()?.number;
+        match self.playback_state() {
+            PlaybackState::Playing => {
+                // Without a length to time the end by, check the handle until it stops
+                let total = match self.total() {
+                    Some(total) if !total.is_zero() => total,
+                    _ => return Some((ChangeSong { poll: true, song }, Duration::from_secs(1))),
+                };
+
+                // get the time that will take when the song will be finished
+                let remaining =
+                    total.checked_sub(Duration::from_secs_f64(self.elapsed().unwrap_or_default()));
+                remaining.map(|remaining| (ChangeSong { poll: false, song }, remaining))
+            }
+            PlaybackState::Pausing
+            | PlaybackState::Paused
+            | PlaybackState::Stopping
+            | PlaybackState::Stopped => None,
+        }
+    }
+
+    /// Whether the change is for the song that's playing, and not one that was replaced
+    pub fn is_current(&self, change: &ChangeSong) -> bool {
+        matches![&self.current_song, Some(s) if s.number == change.song]
+    }
+
+    pub fn playback_state(&self) -> PlaybackState {
+        match &self.current_song {
+            Some(s) => s.handle.playback_state(),
+            None => PlaybackState::Stopped,
+        }
+    }
+
+    /// Whether the playing song is decoded in memory, so seeking in it is instant.
+    /// Streamed songs are decoded from the cached file as they play.
+    pub fn fully_loaded(&self) -> bool {
+        matches![
+            self.current_song.as_ref().map(|s| &s.handle),
+            Some(SoundDataHandleType::Static(_))
+        ]
+    }
+
+    pub fn play(&mut self) {
+        if let Some(s) = &mut self.current_song {
+            match &mut s.handle {
+                SoundDataHandleType::Static(d) => d.resume(Tween::default()),
+                SoundDataHandleType::Stream(d) => d.resume(Tween::default()),
+            }
+        }
+    }
+
+    pub fn pause(&mut self) {
+        if let Some(s) = &mut self.current_song {
+            match &mut s.handle {
+                SoundDataHandleType::Static(d) => d.pause(Tween::default()),
+                SoundDataHandleType::Stream(d) => d.pause(Tween::default()),
+            }
+        }
+    }
+
+    pub fn set_volume(&mut self, volume: f64) {
+        self.fade_volume(volume, Tween::default())
+    }
+
+    /// Sets the user's volume, which the song's gain is applied on top of
+    pub fn fade_volume(&mut self, volume: f64, tween: Tween) {
+        if let Some(s) = &mut self.current_song {
+            let volume = volume * s.gain;
+            match &mut s.handle {
+                SoundDataHandleType::Static(d) => d.set_volume(Volume::Amplitude(volume), tween),
+                SoundDataHandleType::Stream(d) => d.set_volume(Volume::Amplitude(volume), tween),
+            }
+        }
+    }
+
+    pub fn seek(&mut self, secs: f64) {
+        if let Some(s) = &mut self.current_song {
+            match &mut s.handle {
+                SoundDataHandleType::Static(d) => d.seek_to(secs),
+                SoundDataHandleType::Stream(d) => d.seek_to(secs),
+            }
+        }
+    }
+
+    pub fn seek_to_start(&mut self) {
+        if let Some(s) = &mut self.current_song {
+            match &mut s.handle {
+                SoundDataHandleType::Static(d) => d.seek_to(0.),
+                SoundDataHandleType::Stream(d) => d.seek_to(0.),
+            }
+        }
+    }
+
+    /// Stops the song, fading it out over `fade`. Nothing is playing afterwards.
+    pub fn stop(&mut self, fade: Duration) {
+        if let Some(mut s) = self.current_song.take() {
+            let tween = Tween {
+                duration: fade,
+                ..Default::default()
+            };
+            match &mut s.handle {
+                SoundDataHandleType::Static(d) => d.stop(tween),
+                SoundDataHandleType::Stream(d) => d.stop(tween),
+            }
+        }
+    }
+
+    pub fn elapsed(&self) -> Option<f64> {
+        self.current_song.as_ref().map(|s| match &s.handle {
+            SoundDataHandleType::Static(d) => d.position(),
+            SoundDataHandleType::Stream(d) => d.position(),
+        })
+    }
+
+    pub fn total(&self) -> Option<Duration> {
+        self.current_song.as_ref().map(|s| s.duration)
+    }
+
+    /// Boosts or cuts the band, while the song plays
+    pub fn set_eq(&mut self, band: EqBand, gain_db: f64) {
+        self.effects.set_eq(band, gain_db)
+    }
+
+    pub fn set_limiter(&mut self, on: bool) {
+        self.effects.set_limiter(on)
+    }
+
+    /// Sets every band and the limiter
+    pub fn apply_eq(&mut self, settings: &EqSettings) {
+        self.effects.apply(settings)
+    }
+
+    /// Changes the playing song's gain, keeping the volume it's applied to
+    pub fn set_gain(&mut self, volume: f64, gain: f64) {
+        if let Some(s) = &mut self.current_song {
+            s.gain = gain;
+        }
+        self.set_volume(volume)
+    }
+
+    /// Opens the output again, so sound goes to the device that's now the default.
+    /// The playing song is stopped, and the old output is kept if a new one can't be opened.
+    pub fn rebuild(&mut self, eq: &EqSettings) -> Result<(), String> {
+        let mut manager = AudioManager::<DefaultBackend>::new(AudioManagerSettings::default())
+            .map_err(|e| format!("{e:?}"))?;
+        self.current_song = None;
+        self.effects = EffectsTrack::new(&mut manager);
+        self.manager = manager;
+        self.effects.apply(eq);
+        Ok(())
+    }
+
+    /// Plays the sound, with the gain applied to every volume it's given.
+    /// The song it replaces is stopped, fading out over `fade`.
+    pub fn play_once(&mut self, sound: SoundData, gain: f64, fade: Duration) {
+        self.stop(fade);
+
+        let data = sound.into_data();
+        let duration = data.duration();
+        // Streams are never decoded whole, so they usually have no measured gain
+        let output = self.effects.output();
+        let handle = match data {
+            SoundDataType::Static(d) => {
+                let d = d.output_destination(output);
+                SoundDataHandleType::Static(self.manager.play(d).unwrap())
+            }
+            SoundDataType::Stream(d) => {
+                let d = d.output_destination(output);
+                SoundDataHandleType::Stream(self.manager.play(d).unwrap())
+            }
+        };
+        self.songs_played += 1;
+        let current_song = CurrentSong {
+            number: self.songs_played,
+            handle,
+            duration,
+            gain,
+        };
+
+        self.current_song = Some(current_song);
+    }
+}
@@ -0,0 +1,864 @@
+//! Finding or launching the backend, and the requests sent to it. Answers come back as plain
+//! results, for the app to turn into its messages.
+
+use std::{
+    borrow::Borrow, collections::VecDeque, future::Future, process, sync::Arc, time::Duration,
+};
+
+use futures::{future::Either, stream, FutureExt, Stream};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use reqwest::{Client, RequestBuilder, Response, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+
+use crate::{backend_settings::BackendSettings, settings::DownloadFormat};
+
+/// How long the server has to answer, and then to exit, when it's asked to shut down
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+/// How long the server has to answer a poll, it's polled again soon after
+const POLL_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long searches and info requests can take, yt-dlp can be slow to answer them
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+/// How long a download can go without sending anything, songs are converted before they're sent
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(180);
+/// How many times a GET is sent before giving up
+const GET_ATTEMPTS: u32 = 3;
+/// How long is waited before sending a GET again, doubled after each attempt
+const RETRY_DELAY: Duration = Duration::from_millis(250);
+
+/// How the app connects to the server
+#[derive(Debug)]
+pub enum ConnectionMode {
+    /// The app is a direct parent to the server.
+    Child(process::Child, Url),
+
+    /// The app is a separate process that connects to the server.
+    External(Url),
+}
+
+#[derive(Debug, Serialize)]
+struct RequestInfoDict {
+    url: String,
+    process: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct DownloadSongDict {
+    url: String,
+    /// One of [`DownloadFormat`]'s names, the backend also knows aac
+    convert_to: String,
+    /// Asks for the progress as newline-delimited JSON, ending with the song's info
+    stream: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BackendReqErr {
+    /// The backend didn't answer in time
+    Timeout,
+    /// The backend couldn't be connected to, or the connection broke
+    Connection,
+    /// The backend answered with an error status
+    Status(u16),
+    /// The backend's answer couldn't be read
+    Decode,
+    DownloadFailed,
+    /// Nothing was sent, the backend isn't running
+    Offline,
+}
+impl BackendReqErr {
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Offline => "The backend is offline, only saved songs can be played".into(),
+            Self::Timeout => "The backend took too long to answer".into(),
+            Self::Connection => "The backend could not be reached".into(),
+            Self::Status(code) => match StatusCode::from_u16(*code) {
+                Ok(status) => format!("The backend answered with an error, {status}"),
+                Err(_) => format!("The backend answered with an error, {code}"),
+            },
+            Self::Decode => "The backend sent a response that couldn't be read".into(),
+            Self::DownloadFailed => "The backend couldn't download the song".into(),
+        }
+    }
+
+    /// Whether the same request might work if it's sent again
+    fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            Self::Timeout | Self::Connection | Self::Status(500..=599)
+        )
+    }
+}
+impl From<&reqwest::Error> for BackendReqErr {
+    fn from(e: &reqwest::Error) -> Self {
+        match e.status() {
+            Some(status) => Self::Status(status.as_u16()),
+            None if e.is_timeout() => Self::Timeout,
+            None if e.is_decode() => Self::Decode,
+            None => Self::Connection,
+        }
+    }
+}
+pub type RequestResult = Result<String, BackendReqErr>;
+
+/// What yt-dlp says of videos that were deleted or made private. The backend sends its errors
+/// as plain text in place of the info.
+const UNAVAILABLE_ERRORS: [&str; 4] = [
+    "Video unavailable",
+    "Private video",
+    "This video has been removed",
+    "This video is no longer available",
+];
+
+/// Whether the backend's answer to an info request means the video is gone.
+/// Only meaningful for answers that aren't info, a song could be titled like one.
+pub fn is_unavailable(response: &str) -> bool {
+    UNAVAILABLE_ERRORS
+        .iter()
+        .any(|error| response.contains(error))
+}
+
+/// How far a download is, as the backend reports it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+pub struct DownloadProgress {
+    #[serde(default)]
+    pub downloaded_bytes: u64,
+    /// Unknown until the server sends it, or only estimated
+    #[serde(default)]
+    pub total_bytes: Option<u64>,
+    /// In seconds
+    #[serde(default)]
+    pub eta: Option<f64>,
+}
+
+impl DownloadProgress {
+    /// How much is done, from 0 to 1
+    pub fn fraction(&self) -> Option<f32> {
+        self.total_bytes
+            .filter(|total| *total > 0)
+            .map(|total| (self.downloaded_bytes as f64 / total as f64).min(1.0) as f32)
+    }
+
+    pub fn describe(&self) -> String {
+        let done = match self.fraction() {
+            Some(fraction) => format!("{:.0}%", fraction * 100.0),
+            None => format!("{:.1}MB", self.downloaded_bytes as f64 / 1_000_000.0),
+        };
+        match self.eta {
+            Some(eta) => format!("{done}, {}s left", eta.round()),
+            None => done,
+        }
+    }
+}
+
+/// What a download's stream sends
+#[derive(Debug, Clone)]
+pub enum DownloadEvent {
+    Progress(DownloadProgress),
+    /// The song's info, with the path of the downloaded file
+    Finished(RequestResult),
+}
+
+/// A line of a streamed download
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum DownloadLine {
+    Progress(DownloadProgress),
+    Info(serde_json::Value),
+    Error(String),
+}
+
+impl From<Result<DownloadLine, serde_json::Error>> for DownloadEvent {
+    fn from(line: Result<DownloadLine, serde_json::Error>) -> Self {
+        match line {
+            Ok(DownloadLine::Progress(progress)) => Self::Progress(progress),
+            Ok(DownloadLine::Info(info)) => Self::Finished(Ok(info.to_string())),
+            Ok(DownloadLine::Error(e)) => {
+                println!["Download failed: {e}"];
+                Self::Finished(Err(BackendReqErr::DownloadFailed))
+            }
+            Err(e) => {
+                println!["{e:?}"];
+                Self::Finished(Err(BackendReqErr::Decode))
+            }
+        }
+    }
+}
+
+/// Whether the process exited before the timeout ran out
+fn wait_for_exit(process: &mut process::Child, timeout: Duration) -> bool {
+    let started = std::time::Instant::now();
+    while started.elapsed() < timeout {
+        match process.try_wait() {
+            Ok(Some(_)) => return true,
+            Ok(None) => std::thread::sleep(Duration::from_millis(50)),
+            Err(_) => return false,
+        }
+    }
+    false
+}
+
+/// Takes the complete lines out of the buffer, leaving a partial last line in it
+fn take_lines(buffer: &mut Vec<u8>) -> Vec<DownloadEvent> {
+    let complete = match buffer.iter().rposition(|b| *b == b'\n') {
+        Some(idx) => buffer.drain(..=idx).collect::<Vec<_>>(),
+        None => return vec![],
+    };
+    complete
+        .split(|b| *b == b'\n')
+        .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+        .map(|line| DownloadEvent::from(serde_json::from_slice::<DownloadLine>(line)))
+        .collect()
+}
+
+enum DownloadStage {
+    /// The client goes with the request, as the stream's closure can't give it away
+    Requesting(Client, Url, DownloadSongDict),
+    Reading {
+        response: Response,
+        buffer: Vec<u8>,
+        pending: VecDeque<DownloadEvent>,
+    },
+    Done,
+}
+impl DownloadStage {
+    /// The last item of the stream
+    fn failed(e: BackendReqErr) -> Option<(DownloadEvent, Self)> {
+        Some((DownloadEvent::Finished(Err(e)), Self::Done))
+    }
+}
+
+#[derive(Debug, Default)]
+pub enum BackendLaunchStatus {
+    #[default]
+    Unknown,
+    PythonMissing,
+    Launched(ConnectionMode),
+    Failed(std::io::Error),
+    Exited(usize), // exit code
+}
+impl BackendLaunchStatus {
+    pub fn as_string(&self) -> &'static str {
+        match self {
+            BackendLaunchStatus::Unknown => "?",
+            BackendLaunchStatus::Launched(_) => ":)",
+            BackendLaunchStatus::Failed(_) => ":(",
+            BackendLaunchStatus::PythonMissing => "missing",
+            BackendLaunchStatus::Exited(_) => "D:",
+        }
+    }
+
+    /// Whether searches and downloads can be sent
+    pub fn is_online(&self) -> bool {
+        matches!(self, BackendLaunchStatus::Launched(_))
+    }
+}
+
+/// What checking on the server came to
+#[derive(Debug, Clone, PartialEq)]
+pub enum Polled {
+    Answered,
+    Failed(String),
+    /// There was nothing to check, so it was connected to again
+    Connected,
+}
+
+#[derive(Debug, Default)]
+pub struct BackendHandler {
+    pub status: BackendLaunchStatus,
+    /// Whether the server answered a poll since it was connected to
+    answered: bool,
+    /// Counts the attempts to connect, so the results of overtaken ones are let go
+    attempt: u32,
+    /// Whether an attempt to connect is running
+    connecting: bool,
+    /// What it was last connected with, so it can try again
+    settings: BackendSettings,
+    /// Shared by every request, so connections to the server are reused
+    client: Client,
+    /// Asks the server to exit once the app's runtime is gone. It's made the first time it's
+    /// needed, as it can't be made or dropped inside the runtime.
+    shutdown_client: OnceCell<reqwest::blocking::Client>,
+}
+impl BackendHandler {
+    /// Connects to the server, see [`Self::reconnect`]. The handler has the status once the
+    /// future is done.
+    pub fn connect_with(
+        handler: &Arc<Mutex<Self>>,
+        settings: &BackendSettings,
+    ) -> impl Future<Output = ()> {
+        let (attempt, connecting) = handler.lock().reconnect(settings);
+        let handler = Arc::clone(handler);
+        async move {
+            let status = connecting.await;
+            handler.lock().connected(attempt, status);
+        }
+    }
+
+    /// Drops the connection, stopping the server if it was launched here. Returns the attempt
+    /// to connect again, whose status is given to [`Self::connected`].
+    pub fn reconnect(
+        &mut self,
+        settings: &BackendSettings,
+    ) -> (u32, impl Future<Output = BackendLaunchStatus>) {
+        self.stop_child();
+        self.status = BackendLaunchStatus::Unknown;
+        self.answered = false;
+        self.connecting = true;
+        self.attempt = self.attempt.wrapping_add(1);
+        self.settings = settings.clone();
+        let (client, settings) = (self.client.clone(), settings.clone());
+        let connecting = async move {
+            match settings.url() {
+                Some(url) => Self::connect(client, url, &settings).await,
+                None => {
+                    println!["{:?} is not a valid host", settings.host];
+                    BackendLaunchStatus::Unknown
+                }
+            }
+        };
+        (self.attempt, connecting)
+    }
+
+    /// Takes the status the attempt ended with. A server it launched after being overtaken is
+    /// stopped again.
+    pub fn connected(&mut self, attempt: u32, status: BackendLaunchStatus) {
+        if attempt != self.attempt {
+            if let BackendLaunchStatus::Launched(ConnectionMode::Child(mut process, _)) = status {
+                println![
+                    "Stopping a backend that was launched too late: {:?}",
+                    process.kill()
+                ];
+                let _ = process.wait();
+            }
+            return;
+        }
+        self.connecting = false;
+        // An existing server was found by asking it
+        self.answered = matches!(
+            status,
+            BackendLaunchStatus::Launched(ConnectionMode::External(_))
+        );
+        self.status = status;
+    }
+
+    /// Whether the server answered since it was connected to, which a launched one may not yet
+    pub fn is_online(&self) -> bool {
+        self.answered && self.status.is_online()
+    }
+
+    /// The server answered a poll
+    pub fn answered(&mut self) {
+        self.answered = self.status.is_online();
+    }
+
+    async fn connect(client: Client, url: Url, settings: &BackendSettings) -> BackendLaunchStatus {
+        let port = settings.port;
+
+        // Check the port for an existing server
+        let resp = client
+            .get(url.clone())
+            .timeout(Duration::from_millis(500))
+            .send()
+            .await;
+        let (exists, is_backend) = match resp {
+            Ok(re) => {
+                if let Ok(text) = re.text().await {
+                    println!["Port {port} is being used"];
+                    (true, text == "YTM_RS_BACKEND")
+                } else {
+                    (true, false)
+                }
+            }
+            Err(err) => {
+                println!["No server running at {url}. \n{err:?}"];
+                (false, false)
+            }
+        };
+
+        if exists && !is_backend {
+            println!["Port {port} is being used by something else"];
+            BackendLaunchStatus::Unknown
+        } else if exists {
+            // Assumes the existing server is a backend.
+            println!["Successfully polled to YTM_RS_BACKEND"];
+            BackendLaunchStatus::Launched(ConnectionMode::External(url))
+        } else if !settings.auto_launch {
+            println!["Launching the server is turned off"];
+            BackendLaunchStatus::Unknown
+        } else {
+            // Try to create the server as a child process
+            println!["Launching server as a child"];
+            let python_exe = which::which("python");
+            match python_exe {
+                Ok(exe) => {
+                    println!["Python found at {exe:?}"];
+                    let child = process::Command::new(exe)
+                        .args(["-m", "ytm_rs_backend", &format!["{}", port]])
+                        .stdout(process::Stdio::piped())
+                        // .kill_on_drop(true)
+                        .spawn();
+                    match child {
+                        Ok(c) => BackendLaunchStatus::Launched(ConnectionMode::Child(c, url)),
+                        Err(e) => BackendLaunchStatus::Failed(e),
+                    }
+                }
+                Err(_) => BackendLaunchStatus::PythonMissing,
+            }
+        }
+    }
+
+    /// Asks the server to exit if this process launched it, and kills it if it doesn't in time
+    pub fn shut_down_child(&mut self) {
+        if let BackendLaunchStatus::Launched(ConnectionMode::Child(process, host)) =
+            &mut self.status
+        {
+            let mut host = host.clone();
+            host.set_path("shutdown");
+            let asked = self
+                .shutdown_client
+                .get_or_init(reqwest::blocking::Client::new)
+                .post(host)
+                .timeout(SHUTDOWN_TIMEOUT)
+                .send();
+            let exited = match asked {
+                Ok(_) => wait_for_exit(process, SHUTDOWN_TIMEOUT),
+                Err(e) => {
+                    println!["The backend couldn't be asked to exit: {e:?}"];
+                    false
+                }
+            };
+            if exited {
+                println!["Backend shut down"];
+                self.status = BackendLaunchStatus::Unknown;
+                return;
+            }
+        }
+        self.stop_child();
+    }
+
+    /// Kills the server if this process launched it
+    pub fn stop_child(&mut self) {
+        if let BackendLaunchStatus::Launched(ConnectionMode::Child(process, _)) = &mut self.status {
+            println!["Kill result: {:?}", process.kill()];
+            // Reaps it, so it doesn't linger until the app exits
+            let _ = process.wait();
+            println!["Killed backend"];
+            self.status = BackendLaunchStatus::Unknown;
+        }
+    }
+
+    /// The server stopped answering. It's looked for again on the next poll.
+    pub fn lost(&mut self) {
+        self.stop_child();
+        self.status = BackendLaunchStatus::Unknown;
+        self.answered = false;
+    }
+
+    pub async fn poll_server(client: Client, url: Url) -> Result<(), reqwest::Error> {
+        let _ = client.get(url).timeout(POLL_TIMEOUT).send().await?;
+
+        Ok(())
+    }
+
+    /// Checks on the server over HTTP. When it isn't running, it's looked for again,
+    /// and launched again if that's allowed. Nothing is done while it's being connected to.
+    pub fn poll(handler: &Arc<Mutex<Self>>) -> Option<impl Future<Output = Polled>> {
+        let mut this = handler.lock();
+        if this.connecting {
+            return None;
+        }
+        if let BackendLaunchStatus::Launched(ConnectionMode::Child(c, _)) = &mut this.status {
+            if let Ok(Some(status)) = c.try_wait() {
+                println!["Backend exited with {status}"];
+                this.status =
+                    BackendLaunchStatus::Exited(status.code().unwrap_or_default() as usize);
+                this.answered = false;
+            }
+        }
+        let polling = match this.host() {
+            Some(url) => Either::Left(Self::poll_server(this.client.clone(), url).map(
+                |r| match r {
+                    Ok(()) => Polled::Answered,
+                    Err(e) => Polled::Failed(e.to_string()),
+                },
+            )),
+            None => {
+                let settings = this.settings.clone();
+                drop(this);
+                Either::Right(Self::connect_with(handler, &settings).map(|()| Polled::Connected))
+            }
+        };
+        Some(polling)
+    }
+
+    pub fn request_url_from_id<T: Borrow<String>>(id: T) -> String {
+        format!("https://music.youtube.com/watch?v={}", id.borrow())
+    }
+
+    pub fn request_info(&self, url: String) -> Option<impl Future<Output = RequestResult>> {
+        if let BackendLaunchStatus::Launched(mode) = &self.status {
+            println!["Requesting info for {}", url];
+            Some(match mode {
+                ConnectionMode::Child(_, host) | ConnectionMode::External(host) => {
+                    let mut host = host.clone();
+                    host.set_path("request_info");
+                    let info_dict = RequestInfoDict {
+                        url,
+                        process: false,
+                    };
+                    Self::__post(self.client.clone(), host, info_dict, REQUEST_TIMEOUT)
+                }
+            })
+        } else {
+            None
+        }
+    }
+
+    pub fn request_search(&self, query: String) -> Option<impl Future<Output = RequestResult>> {
+        if let BackendLaunchStatus::Launched(mode) = &self.status {
+            println!["Requesting search of {:#?}", query];
+            Some(match mode {
+                ConnectionMode::Child(_, host) | ConnectionMode::External(host) => {
+                    let mut host = host.clone();
+                    host.set_path("search");
+                    host.query_pairs_mut().append_pair("q", &query);
+                    Self::__get(self.client.clone(), host, REQUEST_TIMEOUT)
+                }
+            })
+        } else {
+            None
+        }
+    }
+
+    /// The address of the backend, once it's launched
+    pub fn host(&self) -> Option<Url> {
+        match &self.status {
+            BackendLaunchStatus::Launched(
+                ConnectionMode::Child(_, host) | ConnectionMode::External(host),
+            ) => Some(host.clone()),
+            _ => None,
+        }
+    }
+
+    /// The client requests are sent with, for the ones made away from the handler
+    pub fn client(&self) -> Client {
+        self.client.clone()
+    }
+
+    /// Downloads the song, streaming its progress. The stream ends once it's finished.
+    /// Dropping the stream drops the request.
+    pub fn download_song(
+        client: Client,
+        mut host: Url,
+        url: String,
+        format: DownloadFormat,
+    ) -> impl Stream<Item = DownloadEvent> {
+        host.set_path("download");
+        let dct = DownloadSongDict {
+            url,
+            convert_to: format.as_str().to_string(),
+            stream: true,
+        };
+        stream::unfold(
+            DownloadStage::Requesting(client, host, dct),
+            |mut stage| async move {
+                loop {
+                    stage = match stage {
+                        DownloadStage::Requesting(client, host, dct) => {
+                            // Not when the stream is made, subscriptions are made on every update
+                            println!["Requesting download of {}", dct.url];
+                            // Not a timeout of the request, which would end long downloads
+                            let request = client.post(host).json(&dct).send();
+                            let sent = tokio::time::timeout(DOWNLOAD_TIMEOUT, request).await;
+                            match sent.map(|sent| sent.and_then(Response::error_for_status)) {
+                                Ok(Ok(response)) => DownloadStage::Reading {
+                                    response,
+                                    buffer: vec![],
+                                    pending: VecDeque::new(),
+                                },
+                                Ok(Err(e)) => {
+                                    return DownloadStage::failed(request_failed("/download", &e))
+                                }
+                                Err(_) => {
+                                    println!["The download wasn't started in time"];
+                                    return DownloadStage::failed(BackendReqErr::Timeout);
+                                }
+                            }
+                        }
+                        DownloadStage::Reading {
+                            mut response,
+                            mut buffer,
+                            mut pending,
+                        } => {
+                            if let Some(event) = pending.pop_front() {
+                                let next = match event {
+                                    DownloadEvent::Finished(_) => DownloadStage::Done,
+                                    DownloadEvent::Progress(_) => DownloadStage::Reading {
+                                        response,
+                                        buffer,
+                                        pending,
+                                    },
+                                };
+                                return Some((event, next));
+                            }
+                            match tokio::time::timeout(DOWNLOAD_TIMEOUT, response.chunk()).await {
+                                Ok(Ok(Some(chunk))) => {
+                                    buffer.extend_from_slice(&chunk);
+                                    pending.extend(take_lines(&mut buffer));
+                                    DownloadStage::Reading {
+                                        response,
+                                        buffer,
+                                        pending,
+                                    }
+                                }
+                                // It ended without the song's info
+                                Ok(Ok(None)) => {
+                                    return DownloadStage::failed(BackendReqErr::Decode)
+                                }
+                                Ok(Err(e)) => {
+                                    return DownloadStage::failed(request_failed("/download", &e))
+                                }
+                                Err(_) => {
+                                    println!["The download stopped sending its progress"];
+                                    return DownloadStage::failed(BackendReqErr::Timeout);
+                                }
+                            }
+                        }
+                        DownloadStage::Done => return None,
+                    }
+                }
+            },
+        )
+    }
+
+    /// Sent once, the backend might be partway through it when it fails
+    async fn __post<T: Serialize>(
+        client: Client,
+        host: Url,
+        dct: T,
+        timeout: Duration,
+    ) -> RequestResult {
+        let request = client.post(host.clone()).timeout(timeout).json(&dct);
+        Self::__send(request)
+            .await
+            .map_err(|e| request_failed(host.path(), &e))
+    }
+
+    /// Sent again after failures that might pass, waiting longer each time
+    async fn __get(client: Client, host: Url, timeout: Duration) -> RequestResult {
+        let mut delay = RETRY_DELAY;
+        let mut attempt = 1;
+        loop {
+            let request = client.get(host.clone()).timeout(timeout);
+            let e = match Self::__send(request).await {
+                Ok(text) => return Ok(text),
+                Err(e) => request_failed(host.path(), &e),
+            };
+            if !e.is_transient() || attempt == GET_ATTEMPTS {
+                return Err(e);
+            }
+            println!["Sending it again in {delay:?}"];
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+            attempt += 1;
+        }
+    }
+
+    async fn __send(request: RequestBuilder) -> Result<String, reqwest::Error> {
+        request.send().await?.error_for_status()?.text().await
+    }
+}
+
+/// Logs which request failed and why
+fn request_failed(endpoint: &str, e: &reqwest::Error) -> BackendReqErr {
+    let error = BackendReqErr::from(e);
+    println!["Request to {endpoint} failed: {}\n{e:?}", error.describe()];
+    error
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::{Ipv4Addr, TcpListener},
+        thread,
+        time::Duration,
+    };
+
+    use reqwest::{Client, Url};
+
+    use crate::backend_settings::BackendSettings;
+
+    use super::{
+        is_unavailable, take_lines, BackendHandler, BackendLaunchStatus, BackendReqErr,
+        ConnectionMode, DownloadEvent, DownloadProgress, RequestResult,
+    };
+
+    /// Answers each connection with the next reply, or holds it without answering when it's
+    /// None. Connections past the last reply are refused.
+    fn serve(replies: Vec<Option<String>>) -> Url {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let url = format!("http://{}/search", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for reply in replies {
+                let (mut stream, _) = listener.accept().unwrap();
+                stream
+                    .set_read_timeout(Some(Duration::from_millis(50)))
+                    .unwrap();
+                while let Ok(1..) = stream.read(&mut [0; 1024]) {}
+                match reply {
+                    Some(reply) => stream.write_all(reply.as_bytes()).unwrap(),
+                    None => thread::sleep(Duration::from_secs(2)),
+                }
+            }
+        });
+        Url::parse(&url).unwrap()
+    }
+
+    fn reply(status: &str, body: &str) -> Option<String> {
+        Some(format!(
+            "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        ))
+    }
+
+    fn run(request: impl std::future::Future<Output = RequestResult>) -> RequestResult {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(request)
+    }
+
+    #[test]
+    fn gets_are_sent_again_after_failures_that_might_pass() {
+        let timeout = Duration::from_secs(5);
+        let failed = reply("503 Service Unavailable", "");
+        let url = serve(vec![failed.clone(), reply("200 OK", "results")]);
+        let result = run(BackendHandler::__get(Client::new(), url, timeout));
+        assert_eq![result, Ok("results".to_string())];
+
+        // Second attempts would be refused
+        let url = serve(vec![reply("404 Not Found", "")]);
+        let result = run(BackendHandler::__get(Client::new(), url, timeout));
+        assert_eq![result, Err(BackendReqErr::Status(404))];
+        let url = serve(vec![failed]);
+        let result = run(BackendHandler::__post(Client::new(), url, "info", timeout));
+        assert_eq![result, Err(BackendReqErr::Status(503))];
+    }
+
+    #[test]
+    fn requests_give_up_when_the_backend_doesnt_answer() {
+        let url = serve(vec![None]);
+        let timeout = Duration::from_millis(100);
+        let result = run(BackendHandler::__get(Client::new(), url, timeout));
+        assert_eq![result, Err(BackendReqErr::Timeout)];
+        assert![BackendReqErr::Timeout.is_transient()];
+        assert![!BackendReqErr::Status(404).is_transient()];
+        let described = BackendReqErr::Status(404).describe();
+        assert![described.contains("404 Not Found"), "{described}"];
+    }
+
+    #[test]
+    fn lost_backends_are_offline_until_found_again() {
+        let url = Url::parse("http://127.0.0.1:55001/").unwrap();
+        let mut handler = BackendHandler {
+            status: BackendLaunchStatus::Launched(ConnectionMode::External(url.clone())),
+            ..Default::default()
+        };
+        assert![handler.status.is_online()];
+        assert![handler.request_search("query".into()).is_some()];
+
+        handler.lost();
+        assert![!handler.status.is_online()];
+        assert![handler.host().is_none()];
+        assert![handler.request_search("query".into()).is_none()];
+        assert![handler.request_info("url".into()).is_none()];
+
+        for status in [
+            BackendLaunchStatus::PythonMissing,
+            BackendLaunchStatus::Exited(1),
+            BackendLaunchStatus::Failed(std::io::ErrorKind::NotFound.into()),
+        ] {
+            assert![!status.is_online()];
+        }
+
+        handler.status = BackendLaunchStatus::Launched(ConnectionMode::External(url.clone()));
+        assert_eq![handler.host(), Some(url)];
+    }
+
+    #[test]
+    fn launched_backends_are_online_once_they_answer() {
+        let url = Url::parse("http://127.0.0.1:55002/").unwrap();
+        let settings = BackendSettings::default();
+        let mut handler = BackendHandler::default();
+        let (overtaken, _) = handler.reconnect(&settings);
+        let (attempt, _) = handler.reconnect(&settings);
+
+        // The first attempt ended after the second started
+        let external = || BackendLaunchStatus::Launched(ConnectionMode::External(url.clone()));
+        handler.connected(overtaken, external());
+        assert![!handler.status.is_online()];
+
+        handler.connected(attempt, BackendLaunchStatus::Exited(1));
+        handler.answered();
+        assert![!handler.is_online()];
+
+        // Servers found by asking them have answered, and are dropped when they stop
+        let (attempt, _) = handler.reconnect(&settings);
+        handler.connected(attempt, external());
+        assert![handler.is_online()];
+        handler.lost();
+        handler.status = external();
+        assert![!handler.is_online()];
+        handler.answered();
+        assert![handler.is_online()];
+    }
+
+    #[test]
+    fn gone_videos_are_told_from_other_errors() {
+        for gone in [
+            "ERROR: [youtube] dQw4w9WgXcQ: Video unavailable. This video has been removed",
+            "ERROR: [youtube] dQw4w9WgXcQ: Private video",
+        ] {
+            assert![is_unavailable(gone), "{gone}"];
+        }
+        let timeout = "ERROR: Unable to download webpage: timed out";
+        assert![!is_unavailable(timeout)];
+    }
+
+    #[test]
+    fn lines_are_read_as_they_complete() {
+        let mut buffer = br#"{"progress": {"downloaded_bytes": 10, "total_bytes": 100}}
+{"progress": {"downloaded_"#
+            .to_vec();
+        match take_lines(&mut buffer).as_slice() {
+            [DownloadEvent::Progress(progress)] => assert_eq![
+                *progress,
+                DownloadProgress {
+                    downloaded_bytes: 10,
+                    total_bytes: Some(100),
+                    eta: None
+                }
+            ],
+            events => panic!["{events:?}"],
+        }
+        // The partial line waits for the rest of it
+        assert![take_lines(&mut buffer).is_empty()];
+
+        buffer.extend_from_slice(b"bytes\": 100}}\n\n{\"info\": {\"id\": \"a\"}}\n");
+        match take_lines(&mut buffer).as_slice() {
+            [DownloadEvent::Progress(_), DownloadEvent::Finished(Ok(info))] => {
+                assert_eq![info, r#"{"id":"a"}"#]
+            }
+            events => panic!["{events:?}"],
+        }
+        assert![buffer.is_empty()];
+
+        buffer.extend_from_slice(b"{\"error\": \"Video unavailable\"}\nnot json\n");
+        match take_lines(&mut buffer).as_slice() {
+            [DownloadEvent::Finished(Err(_)), DownloadEvent::Finished(Err(_))] => {}
+            events => panic!["{events:?}"],
+        }
+    }
+}
@@ -0,0 +1,30 @@
+//! Where the backend is, and whether the app launches one itself when nothing answers there
+
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_PORT: u16 = 55001;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackendSettings {
+    pub host: String,
+    pub port: u16,
+    /// Launches `python -m ytm_rs_backend` when nothing answers at the address.
+    /// It only listens on the port it's given, so this is for local hosts.
+    pub auto_launch: bool,
+}
+impl Default for BackendSettings {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            port: DEFAULT_PORT,
+            auto_launch: true,
+        }
+    }
+}
+impl BackendSettings {
+    /// None when the host can't be part of a url
+    pub fn url(&self) -> Option<Url> {
+        Url::parse(&format!("http://{}:{}/", self.host, self.port)).ok()
+    }
+}
@@ -0,0 +1,16 @@
+//! The app's caches and the files they're kept in, see [`readers`]
+
+mod folder_cache;
+mod item_cache;
+mod lru;
+mod ndjson_cache;
+pub mod readers;
+mod sound_data;
+mod stats;
+
+pub use folder_cache::*;
+pub use item_cache::*;
+pub use lru::*;
+pub use ndjson_cache::*;
+pub use sound_data::*;
+pub use stats::*;
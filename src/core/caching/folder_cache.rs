@@ -0,0 +1,52 @@
+use std::fmt::Debug;
+
+use super::{
+    readers::FolderBasedReader, BufferedCache, CacheCounters, IDed, LruPolicy, RwMap, SizeHint,
+};
+
+#[derive(Debug, Clone)]
+pub struct FolderCache<T: IDed<String>> {
+    map: RwMap<String, T>,
+    pub reader: FolderBasedReader,
+    pub policy: LruPolicy<String>,
+}
+impl<T: IDed<String> + Debug> FolderCache<T> {
+    pub fn new(reader: FolderBasedReader, policy: LruPolicy<String>) -> Self {
+        Self {
+            map: Default::default(),
+            reader,
+            policy,
+        }
+    }
+}
+
+impl<T: Debug + IDed<String> + SizeHint + From<(String, Vec<u8>)>> BufferedCache<String, T>
+    for FolderCache<T>
+{
+    fn policy(&self) -> Option<&LruPolicy<String>> {
+        Some(&self.policy)
+    }
+
+    fn item_size(&self, item: &T) -> usize {
+        item.size_hint()
+    }
+
+    fn items(&self) -> &RwMap<String, T> {
+        &self.map
+    }
+    fn items_mut(&mut self) -> &mut RwMap<String, T> {
+        &mut self.map
+    }
+
+    fn counters(&self) -> Option<&CacheCounters> {
+        Some(&self.reader.counters)
+    }
+
+    fn drop_from_cache(&mut self, keys: impl IntoIterator<Item = String>) {
+        let before = self.map.len();
+        keys.into_iter().for_each(|id| {
+            self.map.remove(&id);
+        });
+        self.reader.counters.evicted(before - self.map.len());
+    }
+}
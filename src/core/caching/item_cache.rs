@@ -0,0 +1,93 @@
+use std::{collections::HashMap, fmt::Debug, hash::Hash, sync::Arc};
+
+use parking_lot::RwLock;
+
+use super::{CacheCounters, LruPolicy};
+
+pub trait IDed<T> {
+    fn id(&self) -> &T;
+}
+
+pub type RwArc<T> = Arc<RwLock<T>>;
+pub type RwMap<K, V> = HashMap<K, RwArc<V>>;
+
+pub trait ToRwMapExt<K, V>: IntoIterator<Item = (K, V)> + Sized
+where
+    K: Hash + Clone + Eq + Debug,
+{
+    fn to_rwmap(self) -> RwMap<K, V> {
+        self.into_iter()
+            .map(|(k, v)| (k.clone(), Arc::new(RwLock::new(v))))
+            .collect()
+    }
+}
+
+impl<K: Hash + Clone + Eq + Debug, V, T: IntoIterator<Item = (K, V)>> ToRwMapExt<K, V> for T {}
+
+pub trait BufferedCache<K: Hash + Clone + Eq + Debug, V: IDed<K>> {
+    fn items(&self) -> &HashMap<K, Arc<RwLock<V>>>;
+    fn items_mut(&mut self) -> &mut HashMap<K, Arc<RwLock<V>>>;
+
+    fn drop_from_cache(&mut self, keys: impl IntoIterator<Item = K>);
+
+    /// The policy bounding the in-memory map, if there is one
+    fn policy(&self) -> Option<&LruPolicy<K>> {
+        None
+    }
+
+    /// The estimated size of a value, counted against the policy's byte budget
+    fn item_size(&self, _item: &V) -> usize {
+        0
+    }
+
+    /// Where the cache's hits and misses are counted, if they are
+    fn counters(&self) -> Option<&CacheCounters> {
+        None
+    }
+
+    /// Gets the cached values of the keys, skipping the ones that aren't cached
+    fn fetch_existing<'a>(&self, ids: impl IntoIterator<Item = &'a K>) -> RwMap<K, V>
+    where
+        K: 'a,
+    {
+        let items = self.items();
+        let mut asked = 0;
+        let existing: RwMap<K, V> = ids
+            .into_iter()
+            .inspect(|_| asked += 1)
+            .filter_map(|key| items.get(key).map(|s| (key.clone(), Arc::clone(s))))
+            .collect();
+        if let Some(policy) = self.policy() {
+            policy.touch(existing.keys());
+        }
+        if let Some(counters) = self.counters() {
+            counters.hit(existing.len());
+            counters.miss(asked - existing.len());
+        }
+        existing
+    }
+
+    /// Adds the items to the map, then evicts entries if the policy's budget is exceeded
+    fn insert_items(&mut self, items: RwMap<K, V>) {
+        if let Some(policy) = self.policy() {
+            policy.touch(items.keys());
+        }
+        self.items_mut().extend(items);
+
+        let victims = match self.policy() {
+            Some(policy) => policy.victims(
+                self.items()
+                    .iter()
+                    .map(|(key, item)| (key.clone(), self.item_size(&item.read()))),
+            ),
+            None => return,
+        };
+        if !victims.is_empty() {
+            println!["Evicting {} entries from the cache", victims.len()];
+            if let Some(policy) = self.policy() {
+                policy.forget(&victims);
+            }
+            self.drop_from_cache(victims);
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    hash::Hash,
+};
+
+use parking_lot::Mutex;
+
+/// An estimate of how much memory a cached value holds
+pub trait SizeHint {
+    fn size_hint(&self) -> usize;
+}
+
+#[derive(Debug)]
+struct LruState<K> {
+    clock: u64,
+    last_used: HashMap<K, u64>,
+    pinned: HashSet<K>,
+}
+impl<K> Default for LruState<K> {
+    fn default() -> Self {
+        Self {
+            clock: 0,
+            last_used: HashMap::new(),
+            pinned: HashSet::new(),
+        }
+    }
+}
+
+/// Bounds a cache's in-memory map, evicting the least recently used entries that aren't pinned.
+///
+/// Access is tracked behind a lock so that lookups through `&self` can update it.
+#[derive(Debug)]
+pub struct LruPolicy<K> {
+    pub max_entries: Option<usize>,
+    pub max_bytes: Option<usize>,
+    state: Mutex<LruState<K>>,
+}
+
+impl<K> Default for LruPolicy<K> {
+    fn default() -> Self {
+        Self::new(None, None)
+    }
+}
+
+impl<K: Clone> Clone for LruPolicy<K> {
+    fn clone(&self) -> Self {
+        let state = self.state.lock();
+        Self {
+            max_entries: self.max_entries,
+            max_bytes: self.max_bytes,
+            state: Mutex::new(LruState {
+                clock: state.clock,
+                last_used: state.last_used.clone(),
+                pinned: state.pinned.clone(),
+            }),
+        }
+    }
+}
+
+impl<K> LruPolicy<K> {
+    pub fn new(max_entries: Option<usize>, max_bytes: Option<usize>) -> Self {
+        Self {
+            max_entries,
+            max_bytes,
+            state: Mutex::new(LruState::default()),
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone + Debug> LruPolicy<K> {
+    /// Marks the keys as just used
+    pub fn touch<'a>(&self, keys: impl IntoIterator<Item = &'a K>)
+    where
+        K: 'a,
+    {
+        let mut state = self.state.lock();
+        for key in keys {
+            state.clock += 1;
+            let clock = state.clock;
+            state.last_used.insert(key.clone(), clock);
+        }
+    }
+
+    pub fn pin(&self, key: K) {
+        self.state.lock().pinned.insert(key);
+    }
+
+    pub fn unpin(&self, key: &K) {
+        self.state.lock().pinned.remove(key);
+    }
+
+    pub fn forget<'a>(&self, keys: impl IntoIterator<Item = &'a K>)
+    where
+        K: 'a,
+    {
+        let mut state = self.state.lock();
+        for key in keys {
+            state.last_used.remove(key);
+        }
+    }
+
+    /// Picks the entries to evict so that the cache fits the budget, given each entry's size.
+    /// Pinned entries are never picked, even if that leaves the cache over budget.
+    pub fn victims(&self, entries: impl IntoIterator<Item = (K, usize)>) -> Vec<K> {
+        let state = self.state.lock();
+        let mut entries: Vec<(K, usize)> = entries.into_iter().collect();
+
+        let mut count = entries.len();
+        let mut bytes: usize = entries.iter().map(|(_, size)| size).sum();
+        let over = |count: usize, bytes: usize| {
+            self.max_entries.is_some_and(|max| count > max)
+                || self.max_bytes.is_some_and(|max| bytes > max)
+        };
+        if !over(count, bytes) {
+            return vec![];
+        }
+
+        // Oldest first. Entries that were never touched count as oldest.
+        entries.retain(|(key, _)| !state.pinned.contains(key));
+        entries.sort_by_key(|(key, _)| state.last_used.get(key).copied().unwrap_or_default());
+
+        let mut victims = vec![];
+        for (key, size) in entries {
+            if !over(count, bytes) {
+                break;
+            }
+            count -= 1;
+            bytes -= size;
+            victims.push(key);
+        }
+        if over(count, bytes) {
+            println![
+                "Cache is over budget ({count} entries, {bytes} bytes) but the rest are pinned"
+            ];
+        }
+        victims
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LruPolicy;
+
+    fn keys(v: &[&str]) -> Vec<String> {
+        v.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn evicts_least_recently_used_first() {
+        let policy = LruPolicy::new(Some(2), None);
+        let all = keys(&["a", "b", "c"]);
+        policy.touch(&all);
+        policy.touch(&keys(&["a"]));
+
+        let victims = policy.victims(all.iter().map(|k| (k.clone(), 0)));
+        assert_eq![victims, keys(&["b"])];
+    }
+
+    #[test]
+    fn pinned_entries_are_never_evicted() {
+        let policy = LruPolicy::new(Some(1), None);
+        let all = keys(&["a", "b", "c"]);
+        policy.touch(&all);
+        policy.pin("a".to_string());
+        policy.pin("b".to_string());
+
+        // Still over budget, but only "c" can go
+        let victims = policy.victims(all.iter().map(|k| (k.clone(), 0)));
+        assert_eq![victims, keys(&["c"])];
+
+        policy.unpin(&"a".to_string());
+        let victims = policy.victims(all.iter().map(|k| (k.clone(), 0)));
+        assert_eq![victims, keys(&["a", "c"])];
+    }
+
+    #[test]
+    fn byte_budget_counts_entry_sizes() {
+        let policy = LruPolicy::new(None, Some(100));
+        policy.touch(&keys(&["big", "small", "medium"]));
+
+        let sizes = [("big", 80), ("small", 10), ("medium", 30)];
+        let entries = || sizes.iter().map(|(k, s)| (k.to_string(), *s));
+        // 120 bytes, dropping the oldest (80) is enough
+        assert_eq![policy.victims(entries()), keys(&["big"])];
+
+        let policy = LruPolicy::new(None, Some(1000));
+        assert![policy.victims(entries()).is_empty()];
+    }
+}
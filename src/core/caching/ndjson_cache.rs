@@ -0,0 +1,77 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+
+use super::{readers::LineBasedReader, BufferedCache, CacheCounters, IDed, RwMap};
+
+/// Counts the changes to a cache's items, so views built from them know when to read them again
+#[derive(Debug, Default)]
+struct Generation(AtomicU64);
+impl Clone for Generation {
+    fn clone(&self) -> Self {
+        Self(AtomicU64::new(self.0.load(Ordering::Relaxed)))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NDJsonCache<T: Serialize + for<'de> Deserialize<'de> + IDed<String>> {
+    map: RwMap<String, T>,
+    pub reader: LineBasedReader,
+    generation: Generation,
+}
+impl<T: Serialize + for<'de> Deserialize<'de> + IDed<String>> NDJsonCache<T> {
+    pub fn new(cache: LineBasedReader) -> Self {
+        let parent = cache.filepath.parent().unwrap();
+        if !parent.exists() {
+            std::fs::create_dir_all(parent).unwrap();
+        }
+
+        Self {
+            reader: cache,
+            map: Default::default(),
+            generation: Generation::default(),
+        }
+    }
+
+    /// Goes up whenever the items may have changed
+    pub fn generation(&self) -> u64 {
+        self.generation.0.load(Ordering::Relaxed)
+    }
+
+    /// Marks the items as changed, for edits made through an item's own lock
+    pub fn touch(&self) {
+        self.generation.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl<T: Serialize + for<'de> Deserialize<'de> + IDed<String>> BufferedCache<String, T>
+    for NDJsonCache<T>
+{
+    fn items(&self) -> &HashMap<String, Arc<RwLock<T>>> {
+        &self.map
+    }
+    fn items_mut(&mut self) -> &mut HashMap<String, Arc<RwLock<T>>> {
+        self.touch();
+        &mut self.map
+    }
+
+    fn counters(&self) -> Option<&CacheCounters> {
+        Some(&self.reader.counters)
+    }
+
+    fn drop_from_cache(&mut self, keys: impl IntoIterator<Item = String>) {
+        let before = self.map.len();
+        keys.into_iter().for_each(|key| {
+            self.map.remove(&key);
+        });
+        self.touch();
+        self.reader.counters.evicted(before - self.map.len());
+    }
+}
@@ -1,6 +1,7 @@
 pub mod cache_reader;
 pub mod folder_based_reader;
 pub mod line_based_reader;
+pub mod line_index;
 
 pub use cache_reader::*;
 pub use folder_based_reader::*;
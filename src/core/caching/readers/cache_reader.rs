@@ -11,6 +11,8 @@ pub struct SourceItemPair<S, T>(
     pub T, // result
 );
 
+// Only the app reads its caches, so the futures' bounds aren't needed
+#[allow(async_fn_in_trait)]
 pub trait CacheReader<SrcT, IDT, OutT>
 where
     IDT: Eq + PartialEq + Hash + Clone,
@@ -21,7 +23,12 @@ where
     async fn read_filter(
         &self,
         f: &HashSet<IDT>,
-    ) -> IoResult<Vec<(IDT, impl Future<Output = SourceItemPair<SrcT, OutT>>)>> {
+    ) -> IoResult<
+        Vec<(
+            IDT,
+            impl Future<Output = IoResult<SourceItemPair<SrcT, OutT>>>,
+        )>,
+    > {
         Ok(self
             .read()
             .await?
@@ -29,7 +36,7 @@ where
             .filter_map(|i| {
                 let id = i.1.id().clone();
                 match f.contains(&id) {
-                    true => Some((id, async { i })),
+                    true => Some((id, async { Ok(i) })),
                     false => None,
                 }
             })
@@ -48,14 +55,19 @@ where
                     Ok(v) => {
                         let futures = v.into_iter().map(|(_, f)| f);
                         let items = futures::future::join_all(futures).await;
+                        // One item that can't be read doesn't spoil the rest
                         items
                             .into_iter()
-                            .map(
-                                move |SourceItemPair(_, item): SourceItemPair<_, OutT>| async {
+                            .filter_map(|item| match item {
+                                Ok(SourceItemPair(_, item)) => Some(async move {
                                     let id = item.id();
                                     (id.clone(), Arc::new(RwLock::new(item)))
-                                },
-                            )
+                                }),
+                                Err(e) => {
+                                    println!["Skipping an item: {e:?}"];
+                                    None
+                                }
+                            })
                             .collect()
                     }
                     Err(e) => {
@@ -0,0 +1,1224 @@
+use async_std::{
+    fs::{self as afs},
+    io::{ReadExt, WriteExt},
+    sync::RwLock,
+};
+use futures::{future::join_all, Future};
+
+use std::{
+    borrow::Borrow,
+    collections::{HashMap, HashSet},
+    fs as sfs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    caching::{CacheCounters, IDed},
+    verbosity::{debug, trace},
+};
+
+use super::{CacheReader, LineBasedReader, SourceItemPair};
+
+fn random_uuid() -> String {
+    Uuid::new_v4().to_string()
+}
+
+pub async fn read_file<T: Borrow<PathBuf>>(filepath: T) -> Result<Vec<u8>, async_std::io::Error> {
+    let filepath = filepath.borrow();
+    println!["Reading data of: {:?}", filepath];
+    let mut file = afs::File::open(filepath).await?;
+    let mut data = Vec::with_capacity(file.metadata().await.map(|m| m.len()).unwrap_or(0) as usize); // approximate the file size
+    file.read_to_end(&mut data).await?;
+    println!["Finished reading, {:?} bytes", data.len()];
+    Ok(data)
+}
+
+pub async fn write_file(filepath: PathBuf, data: &[u8]) -> Result<(), async_std::io::Error> {
+    println!["Writing data to: {:?}", filepath];
+    let len = data.len();
+    {
+        let mut file = afs::File::create(&filepath).await?;
+        file.write_all(data).await?;
+        file.flush().await?;
+    }
+    println!["{:?} bytes written.", len];
+    Ok(())
+}
+
+/// How big a file is and when it was last played, kept in its index entry.
+/// Entries from before these were kept have neither, so they're the first to be evicted.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct FileUsage {
+    #[serde(default)]
+    pub bytes: u64,
+    /// In seconds since the unix epoch
+    #[serde(default)]
+    pub last_used: u64,
+}
+
+fn epoch_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or_default()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct FileData<T>(String, T, #[serde(default)] FileUsage);
+// I cannot believe this can't be derived
+impl<T> AsRef<FileData<T>> for FileData<T> {
+    #[inline]
+    fn as_ref(&self) -> &FileData<T> {
+        self
+    }
+}
+
+impl<T> FileData<T> {
+    pub fn new(id: String, data: T) -> Self {
+        FileData(id, data, FileUsage::default())
+    }
+    #[inline]
+    pub fn into_data(self) -> T {
+        self.1
+    }
+}
+impl<T> IDed<String> for FileData<T> {
+    #[inline]
+    fn id(&self) -> &String {
+        &self.0
+    }
+}
+
+/// Temporary files older than this are left over from a crash
+pub const STALE_TMP_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// Where files that aren't in the index are moved, in case they are still wanted
+pub const QUARANTINE_FOLDER: &str = "quarantine";
+
+/// Files that no index entry points to are deleted at startup once they're this old
+pub const ORPHAN_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Whether the file was last modified at least `age` before `now`
+fn older_than(metadata: &sfs::Metadata, age: Duration, now: SystemTime) -> bool {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|modified| now.duration_since(modified).ok())
+        .is_some_and(|file_age| file_age >= age)
+}
+
+/// What a validation pass of a folder found
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ValidationReport {
+    /// Index entries whose file was missing, which were dropped
+    pub pruned: usize,
+    /// Files that no index entry points to, which were left alone
+    pub orphans: Vec<PathBuf>,
+    /// Orphans that were old enough to be deleted
+    pub orphans_removed: usize,
+    pub bytes_reclaimed: u64,
+}
+
+/// What a maintenance pass of a folder did
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MaintenanceReport {
+    /// Index entries whose file was missing
+    pub pruned: usize,
+    /// Files that no index entry pointed to
+    pub quarantined: usize,
+    /// Leftover temporary files that were deleted
+    pub tmp_removed: usize,
+    pub bytes_reclaimed: u64,
+}
+
+/// What an eviction pass of a folder did
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EvictionReport {
+    pub evicted: usize,
+    pub bytes_freed: u64,
+    /// The size of what's left, over the budget when only kept files are left
+    pub bytes_left: u64,
+}
+
+/// What removing files from a folder did
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RemovalReport {
+    /// The ids whose files were deleted
+    pub removed: Vec<String>,
+    pub bytes_freed: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct FolderBasedReader {
+    pub filepath: PathBuf,
+    pub index_reader: LineBasedReader,
+    /// Counts the reads of the files, those of the index are counted by its reader
+    pub counters: Arc<CacheCounters>,
+    /// Held by writes and maintenance, so that only one of them runs at a time
+    activity: Arc<RwLock<()>>,
+}
+
+impl FolderBasedReader {
+    pub fn new(filepath: PathBuf) -> Self {
+        let linepath = filepath.join("index").with_extension("ndjson");
+        if !filepath.exists() {
+            std::fs::create_dir_all(&filepath).unwrap();
+        }
+
+        if !linepath.exists() {
+            // touch
+            println!["Creating index file at {:?}...", linepath];
+            println!["{:?}", sfs::File::create(&linepath)];
+        }
+        Self {
+            filepath,
+            index_reader: LineBasedReader::new(linepath),
+            counters: Arc::default(),
+            activity: Arc::new(RwLock::new(())),
+        }
+    }
+
+    /// The index, its sidecar and the quarantine aren't sound files
+    fn is_bookkeeping(&self, path: &Path) -> bool {
+        let index = &self.index_reader.filepath;
+        path == index
+            || path == super::line_index::LineIndex::path_for(index)
+            || path == self.filepath.join(QUARANTINE_FOLDER)
+    }
+
+    /// Drops the index entries whose file is gone. Returns the files the index still points
+    /// to, and how many entries were dropped.
+    async fn prune(&self) -> Result<(HashSet<PathBuf>, usize), std::io::Error> {
+        let index: Vec<SourceItemPair<_, FileData<PathBuf>>> = self.index_reader.read().await?;
+        let referenced: HashSet<PathBuf> = index
+            .iter()
+            .map(|SourceItemPair(_, FileData(_, path, _))| self.filepath.join(path))
+            .filter(|path| path.is_file())
+            .collect();
+        let pruned = self
+            .index_reader
+            .retain(|FileData(_, path, _): &FileData<PathBuf>| {
+                referenced.contains(&self.filepath.join(path))
+            })
+            .await?;
+        Ok((referenced, pruned))
+    }
+
+    /// The files in the folder that aren't in `referenced` or bookkeeping
+    fn unreferenced(
+        &self,
+        referenced: &HashSet<PathBuf>,
+    ) -> Result<Vec<(sfs::DirEntry, sfs::Metadata)>, std::io::Error> {
+        let mut files = vec![];
+        for entry in sfs::read_dir(&self.filepath)? {
+            let entry = entry?;
+            let path = entry.path();
+            let metadata = entry.metadata()?;
+            if metadata.is_file() && !self.is_bookkeeping(&path) && !referenced.contains(&path) {
+                files.push((entry, metadata));
+            }
+        }
+        Ok(files)
+    }
+
+    /// Checks that every index entry has a file and every file has an entry, since they drift
+    /// apart when the app dies between writing a file and extending the index. Entries whose
+    /// file is gone are dropped. Files without an entry are reported, and deleted if they're
+    /// older than `orphan_age`.
+    ///
+    /// Returns None without doing anything if a write or another pass is in progress.
+    pub async fn validate(
+        &self,
+        orphan_age: Option<Duration>,
+    ) -> Result<Option<ValidationReport>, std::io::Error> {
+        let _guard = match self.activity.try_write() {
+            Some(guard) => guard,
+            None => return Ok(None),
+        };
+        let now = SystemTime::now();
+        let (referenced, pruned) = self.prune().await?;
+        let mut report = ValidationReport {
+            pruned,
+            ..Default::default()
+        };
+        for (entry, metadata) in self.unreferenced(&referenced)? {
+            let old = orphan_age.is_some_and(|age| older_than(&metadata, age, now));
+            match old {
+                true => {
+                    sfs::remove_file(entry.path())?;
+                    report.orphans_removed += 1;
+                    report.bytes_reclaimed += metadata.len();
+                }
+                false => report.orphans.push(entry.path()),
+            }
+        }
+        Ok(Some(report))
+    }
+
+    /// Cleans up after crashes and deletions: removes temporary files older than `tmp_age`,
+    /// drops index entries whose file is gone, quarantines files the index doesn't know of,
+    /// and compacts the index.
+    ///
+    /// Returns None without doing anything if a write or another pass is in progress.
+    pub async fn maintain(
+        &self,
+        tmp_age: Duration,
+    ) -> Result<Option<MaintenanceReport>, std::io::Error> {
+        let _guard = match self.activity.try_write() {
+            Some(guard) => guard,
+            None => return Ok(None),
+        };
+        let now = SystemTime::now();
+        let (referenced, pruned) = self.prune().await?;
+        let mut report = MaintenanceReport {
+            pruned,
+            ..Default::default()
+        };
+
+        let quarantine = self.filepath.join(QUARANTINE_FOLDER);
+        for (entry, metadata) in self.unreferenced(&referenced)? {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "tmp") {
+                if older_than(&metadata, tmp_age, now) {
+                    sfs::remove_file(&path)?;
+                    report.tmp_removed += 1;
+                    report.bytes_reclaimed += metadata.len();
+                }
+                continue;
+            }
+
+            sfs::create_dir_all(&quarantine)?;
+            sfs::rename(&path, quarantine.join(entry.file_name()))?;
+            report.quarantined += 1;
+        }
+
+        Ok(Some(report))
+    }
+
+    /// Deletes the least recently played files until the folder fits in `budget` bytes.
+    /// The files of the ids in `keep` are never deleted, even if that leaves it over budget.
+    ///
+    /// Returns None without doing anything if a write or another pass is in progress.
+    pub async fn evict(
+        &self,
+        budget: u64,
+        keep: &HashSet<String>,
+    ) -> Result<Option<EvictionReport>, std::io::Error> {
+        let _guard = match self.activity.try_write() {
+            Some(guard) => guard,
+            None => return Ok(None),
+        };
+        let mut entries = self.sized_entries().await?;
+
+        let mut report = EvictionReport {
+            bytes_left: entries.iter().map(|(_, _, usage)| usage.bytes).sum(),
+            ..Default::default()
+        };
+        if report.bytes_left <= budget {
+            return Ok(Some(report));
+        }
+
+        entries.retain(|(id, _, _)| !keep.contains(id));
+        entries.sort_by_key(|(_, _, usage)| usage.last_used);
+        let mut evicted = HashSet::new();
+        for (id, path, usage) in entries {
+            if report.bytes_left <= budget {
+                break;
+            }
+            match sfs::remove_file(&path) {
+                Ok(()) => {}
+                // Maintenance drops the entries of files that are gone
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    println!["Failed to evict {:?}: {e:?}", path];
+                    continue;
+                }
+            }
+            report.evicted += 1;
+            report.bytes_freed += usage.bytes;
+            report.bytes_left = report.bytes_left.saturating_sub(usage.bytes);
+            evicted.insert(id);
+        }
+        self.index_reader
+            .retain(|FileData(id, _, _): &FileData<PathBuf>| !evicted.contains(id))
+            .await?;
+        Ok(Some(report))
+    }
+
+    /// Deletes the files of the ids and their index entries
+    pub async fn remove(&self, ids: &HashSet<String>) -> Result<RemovalReport, std::io::Error> {
+        self.remove_where(|id| ids.contains(id)).await
+    }
+
+    /// Deletes every file except those of the ids in `keep`
+    pub async fn clear(&self, keep: &HashSet<String>) -> Result<RemovalReport, std::io::Error> {
+        self.remove_where(|id| !keep.contains(id)).await
+    }
+
+    /// Unlike eviction, it waits for writes and other passes, since it's asked for by hand.
+    /// Files that can't be deleted keep their entries.
+    async fn remove_where(
+        &self,
+        remove: impl Fn(&str) -> bool,
+    ) -> Result<RemovalReport, std::io::Error> {
+        let _guard = self.activity.write().await;
+        let mut report = RemovalReport::default();
+        for (id, path, usage) in self.sized_entries().await? {
+            if !remove(&id) {
+                continue;
+            }
+            match sfs::remove_file(&path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    println!["Failed to remove {:?}: {e:?}", path];
+                    continue;
+                }
+            }
+            report.bytes_freed += usage.bytes;
+            report.removed.push(id);
+        }
+        let removed: HashSet<&String> = report.removed.iter().collect();
+        self.index_reader
+            .retain(|FileData(id, _, _): &FileData<PathBuf>| !removed.contains(id))
+            .await?;
+        Ok(report)
+    }
+
+    /// How many bytes the files in the folder take up
+    pub async fn usage(&self) -> Result<u64, std::io::Error> {
+        let entries = self.sized_entries().await?;
+        Ok(entries.iter().map(|(_, _, usage)| usage.bytes).sum())
+    }
+
+    /// Every entry of the index with the full path of its file
+    async fn sized_entries(&self) -> Result<Vec<(String, PathBuf, FileUsage)>, std::io::Error> {
+        let index: Vec<SourceItemPair<_, FileData<PathBuf>>> = self.index_reader.read().await?;
+        Ok(index
+            .into_iter()
+            .map(|SourceItemPair(_, FileData(id, path, mut usage))| {
+                let path = self.filepath.join(path);
+                // Sizes weren't kept for older entries
+                if usage.bytes == 0 {
+                    usage.bytes = sfs::metadata(&path).map(|m| m.len()).unwrap_or_default();
+                }
+                (id, path, usage)
+            })
+            .collect())
+    }
+
+    /// Marks the ids' files as played at `now`, so they're the last to be evicted
+    pub async fn touch(
+        &self,
+        ids: &HashSet<String>,
+        now: SystemTime,
+    ) -> Result<(), std::io::Error> {
+        let _guard = self.activity.write().await;
+        let last_used = epoch_secs(now);
+        self.index_reader
+            .update(|FileData(id, _, usage): &mut FileData<PathBuf>| {
+                let used = ids.contains(id);
+                if used {
+                    usage.last_used = last_used;
+                }
+                used
+            })
+            .await
+            .map(|_| ())
+    }
+
+    pub async fn extend_to<T: AsRef<FileData<Vec<u8>>>, V: AsRef<Vec<(T, PathBuf)>>>(
+        &self,
+        items: V,
+        overwrite: bool,
+    ) -> Result<(), std::io::Error> {
+        let items: Vec<(&FileData<_>, PathBuf)> = items
+            .as_ref()
+            .iter()
+            .map(|(item, pth)| (item.as_ref(), self.filepath.join(pth)))
+            .collect();
+        let _guard = self.activity.write().await;
+
+        // An id that's already in the index keeps its file, unless it's overwritten
+        let items = match overwrite {
+            true => items,
+            false => {
+                let index: Vec<SourceItemPair<_, FileData<PathBuf>>> =
+                    self.index_reader.read().await.unwrap_or_default();
+                let existing: HashSet<String> = index
+                    .into_iter()
+                    .map(|SourceItemPair(_, FileData(id, _, _))| id)
+                    .collect();
+                items
+                    .into_iter()
+                    .filter(|(data, _)| !existing.contains(&data.0))
+                    .collect()
+            }
+        };
+        if items.is_empty() {
+            return Ok(());
+        }
+
+        // Write to files
+        for (data, filepath) in items.iter() {
+            match (filepath.exists(), overwrite) {
+                // Does not exist and overwrite is allowed
+                // Does exist but overwrite is allowed
+                // Does not exist and overwrite is not allowed
+                (false, true) | (true, true) | (false, false) => {
+                    write_file(filepath.clone(), &data.1).await?;
+                }
+                // Does exist and overwrite is not allowed
+                (true, false) => {}
+            }
+        }
+
+        // Extend the index
+        let usage = |data: &FileData<Vec<u8>>| FileUsage {
+            bytes: data.1.len() as u64,
+            last_used: epoch_secs(SystemTime::now()),
+        };
+        let new_items: Vec<FileData<PathBuf>> = items
+            .iter()
+            .map(|(data, uuid)| FileData(data.0.to_string(), uuid.into(), usage(data)))
+            .collect();
+        self.index_reader.clone().extend(new_items, overwrite).await
+    }
+
+    /// Where the id's file is, without reading it
+    pub async fn locate(&self, id: &str) -> Result<Option<PathBuf>, std::io::Error> {
+        let index: Vec<SourceItemPair<_, FileData<PathBuf>>> = self.index_reader.read().await?;
+        Ok(index
+            .into_iter()
+            .find(|SourceItemPair(_, FileData(item_id, _, _))| item_id == id)
+            .map(|SourceItemPair(_, FileData(_, path, _))| self.filepath.join(path)))
+    }
+
+    /// Where the file of each id is, leaving out those whose file is gone
+    pub async fn locate_all(&self) -> Result<HashMap<String, PathBuf>, std::io::Error> {
+        let index: Vec<SourceItemPair<_, FileData<PathBuf>>> = self.index_reader.read().await?;
+        Ok(index
+            .into_iter()
+            .map(|SourceItemPair(_, FileData(id, path, _))| (id, self.filepath.join(path)))
+            .filter(|(_, path)| path.is_file())
+            .collect())
+    }
+
+    /// Like `extend`, with the files named with the extension. It records their format, so
+    /// files from before a format change aren't mistaken for the new one.
+    pub async fn extend_as<T: AsRef<FileData<Vec<u8>>>, V: AsRef<Vec<T>>>(
+        &self,
+        items: V,
+        extension: &str,
+        overwrite: bool,
+    ) -> Result<(), std::io::Error> {
+        let items: Vec<(&FileData<_>, PathBuf)> = items
+            .as_ref()
+            .iter()
+            .map(|f| {
+                let path = PathBuf::from(random_uuid()).with_extension(extension);
+                (f.as_ref(), path)
+            })
+            .collect();
+
+        self.extend_to(items, overwrite).await
+    }
+}
+impl CacheReader<String, String, FileData<Vec<u8>>> for FolderBasedReader {
+    // Returns an iterator of pairs of the key and the File.
+    // Entries whose file can't be read are left out.
+    async fn read(&self) -> Result<Vec<SourceItemPair<String, FileData<Vec<u8>>>>, std::io::Error> {
+        // Read the index file and find the filenames
+        let index: Vec<SourceItemPair<_, FileData<PathBuf>>> = self.index_reader.read().await?;
+
+        let mut items = Vec::new();
+
+        for SourceItemPair(source, FileData(uuid, path_id, usage)) in index {
+            println!["Source: {:?}", source];
+            let actual = self.filepath.join(&path_id);
+            let counters = &self.counters;
+            items.push(async move {
+                match read_file(&actual).await {
+                    Ok(data) => {
+                        counters.read(data.len());
+                        Some(SourceItemPair(source, FileData(uuid, data, usage)))
+                    }
+                    Err(e) => {
+                        println!["Skipping {uuid}, {:?} can't be read: {e:?}", actual];
+                        None
+                    }
+                }
+            })
+        }
+
+        Ok(join_all(items).await.into_iter().flatten().collect())
+    }
+
+    // Finds the files, but only actually reads files that have the right id
+    async fn read_filter(
+        &self,
+        f: &HashSet<String>,
+    ) -> Result<
+        Vec<(
+            String,
+            impl Future<Output = Result<SourceItemPair<String, FileData<Vec<u8>>>, std::io::Error>>,
+        )>,
+        std::io::Error,
+    > {
+        debug![
+            "Reading {} items from {:?}",
+            f.len(),
+            self.index_reader.filepath
+        ];
+        trace!["Filter: {:?}", f];
+
+        let items = self.index_reader.read_filter(f).await?;
+        let ids: Vec<String> = items.iter().map(|i| i.0.clone()).collect();
+        let futures = join_all(items.into_iter().map(|i| i.1)).await;
+
+        let index: Vec<(String, Result<SourceItemPair<_, FileData<PathBuf>>, _>)> =
+            ids.into_iter().zip(futures).collect();
+        let mut items = vec![];
+
+        for (id, entry) in index {
+            let SourceItemPair(source, FileData(uuid, path_id, usage)) = entry?;
+            println!["Source: {:?}", source];
+            let actual = self.filepath.join(path_id);
+            let counters = Arc::clone(&self.counters);
+            items.push((id, async move {
+                let data = read_file(&actual).await.map_err(|e| {
+                    std::io::Error::new(e.kind(), format!("{:?} can't be read: {e}", actual))
+                })?;
+                counters.read(data.len());
+                Ok(SourceItemPair(source, FileData(uuid, data, usage)))
+            }))
+        }
+
+        Ok(items)
+    }
+
+    async fn extend<T: AsRef<FileData<Vec<u8>>>, V: AsRef<Vec<T>>>(
+        &self,
+        items: V,
+        overwrite: bool,
+    ) -> Result<(), std::io::Error> {
+        let items: Vec<(&FileData<_>, PathBuf)> = items
+            .as_ref()
+            .iter()
+            .map(|f| (f.as_ref(), PathBuf::from(random_uuid())))
+            .collect();
+
+        self.extend_to(items, overwrite).await
+    }
+}
+
+/// Data of a lazy folder's index entry, which points at files in the folder
+pub trait FolderFiles {
+    fn files(&self) -> Vec<&PathBuf>;
+    fn files_mut(&mut self) -> Vec<&mut PathBuf>;
+}
+impl FolderFiles for PathBuf {
+    fn files(&self) -> Vec<&PathBuf> {
+        vec![self]
+    }
+    fn files_mut(&mut self) -> Vec<&mut PathBuf> {
+        vec![self]
+    }
+}
+
+/// A folder whose index only records where the files are, they're read by whoever needs them
+#[derive(Debug, Clone)]
+pub struct LazyFolderBasedReader {
+    pub filepath: PathBuf,
+    pub index_reader: LineBasedReader,
+    /// Held by writes, so that one's replaced files aren't deleted under another
+    activity: Arc<RwLock<()>>,
+}
+impl LazyFolderBasedReader {
+    pub fn new(filepath: PathBuf) -> Self {
+        let linepath = filepath.join("index").with_extension("ndjson");
+        if !filepath.exists() {
+            std::fs::create_dir_all(&filepath).unwrap();
+        }
+
+        if !linepath.exists() {
+            // touch
+            println!["Creating index file at {:?}...", linepath];
+            println!["{:?}", sfs::File::create(&linepath)];
+        }
+        Self {
+            filepath,
+            index_reader: LineBasedReader::new(linepath),
+            activity: Arc::new(RwLock::new(())),
+        }
+    }
+
+    pub fn generate_paths(
+        &self,
+        count: usize,
+    ) -> std::iter::Map<std::ops::Range<usize>, impl FnMut(usize) -> PathBuf> {
+        (0..count).map(|_| PathBuf::from(random_uuid()))
+    }
+
+    #[inline]
+    pub fn convert_path(&self, path: &PathBuf) -> PathBuf {
+        self.filepath.join(path)
+    }
+
+    /// Where the thumbnails looked up are counted, with the reads of the index
+    pub fn counters(&self) -> &CacheCounters {
+        &self.index_reader.counters
+    }
+
+    /// Deletes every file the index points to, and their entries. Only entries none of whose
+    /// files could be deleted are kept, the others would point at files that are gone.
+    pub async fn clear<D>(&self) -> Result<RemovalReport, std::io::Error>
+    where
+        D: FolderFiles + Serialize + for<'de> Deserialize<'de>,
+    {
+        let _guard = self.activity.write().await;
+        let index: Vec<SourceItemPair<String, FileData<D>>> = self.index_reader.read().await?;
+        let mut report = RemovalReport::default();
+        let mut kept = HashSet::new();
+        for SourceItemPair(_, FileData(id, files, _)) in index {
+            let mut deleted = false;
+            let mut failed = false;
+            for path in files.files() {
+                let path = self.convert_path(path);
+                let bytes = sfs::metadata(&path).map(|m| m.len()).unwrap_or_default();
+                match sfs::remove_file(&path) {
+                    Ok(()) => {
+                        report.bytes_freed += bytes;
+                        deleted = true;
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(e) => {
+                        println!["Failed to remove {:?}: {e:?}", path];
+                        failed = true;
+                    }
+                }
+            }
+            match failed && !deleted {
+                true => {
+                    kept.insert(id);
+                }
+                false => report.removed.push(id),
+            }
+        }
+        self.index_reader
+            .retain(|FileData(id, _, _): &FileData<D>| kept.contains(id))
+            .await?;
+        Ok(report)
+    }
+
+    /// Points the item at files in the folder. Paths inside it are made relative to it, and
+    /// files elsewhere are copied in.
+    async fn adopt<D: FolderFiles>(
+        &self,
+        mut item: FileData<D>,
+    ) -> Result<FileData<D>, std::io::Error> {
+        for path in item.1.files_mut() {
+            if path.is_relative() {
+                continue;
+            }
+            *path = match path.strip_prefix(&self.filepath) {
+                Ok(inside) => inside.to_path_buf(),
+                Err(_) => {
+                    let mut copied = PathBuf::from(random_uuid());
+                    if let Some(extension) = path.extension() {
+                        copied.set_extension(extension);
+                    }
+                    afs::copy(&*path, self.convert_path(&copied)).await?;
+                    copied
+                }
+            };
+        }
+        Ok(item)
+    }
+}
+impl<D> CacheReader<String, String, FileData<D>> for LazyFolderBasedReader
+where
+    D: FolderFiles + Clone + Serialize + for<'de> Deserialize<'de>,
+{
+    // The entries' paths are relative to the folder
+    async fn read(&self) -> Result<Vec<SourceItemPair<String, FileData<D>>>, std::io::Error> {
+        self.index_reader.read().await
+    }
+
+    async fn read_filter(
+        &self,
+        f: &HashSet<String>,
+    ) -> Result<
+        Vec<(
+            String,
+            impl Future<Output = Result<SourceItemPair<String, FileData<D>>, std::io::Error>>,
+        )>,
+        std::io::Error,
+    > {
+        <LineBasedReader as CacheReader<String, String, FileData<D>>>::read_filter(
+            &self.index_reader,
+            f,
+        )
+        .await
+    }
+
+    /// Records the items' files, copying in those that aren't in the folder already. When an
+    /// entry is overwritten, the files it pointed to that the new one doesn't are deleted.
+    /// Without overwriting, ids already in the index keep their entry.
+    async fn extend<I: AsRef<FileData<D>>, V: AsRef<Vec<I>>>(
+        &self,
+        items: V,
+        overwrite: bool,
+    ) -> Result<(), std::io::Error> {
+        let _guard = self.activity.write().await;
+        let ids: HashSet<String> = items
+            .as_ref()
+            .iter()
+            .map(|i| i.as_ref().0.clone())
+            .collect();
+        let entries = CacheReader::<String, String, FileData<D>>::read_filter(self, &ids).await?;
+        let entries = entries.into_iter().map(|(_, f)| f);
+        let existing: HashMap<String, D> = join_all(entries)
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .map(|SourceItemPair(_, FileData(id, data, _))| (id, data))
+            .collect();
+
+        let mut replaced = vec![];
+        let mut new_items = vec![];
+        for item in items.as_ref() {
+            let item = item.as_ref();
+            match (existing.get(&item.0), overwrite) {
+                (Some(_), false) => continue,
+                (Some(old), true) => replaced.push(old),
+                (None, _) => {}
+            }
+            new_items.push(self.adopt(item.clone()).await?);
+        }
+        if new_items.is_empty() {
+            return Ok(());
+        }
+        self.index_reader.extend(&new_items, overwrite).await?;
+
+        let kept: HashSet<&PathBuf> = new_items.iter().flat_map(|item| item.1.files()).collect();
+        for path in replaced.into_iter().flat_map(|old| old.files()) {
+            if kept.contains(path) {
+                continue;
+            }
+            match sfs::remove_file(self.convert_path(path)) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => println!["Failed to delete the replaced file {:?}: {e:?}", path],
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashSet,
+        io::Write,
+        path::PathBuf,
+        time::{Duration, SystemTime},
+    };
+
+    use crate::caching::readers::{CacheReader, SourceItemPair};
+
+    use serde::{Deserialize, Serialize};
+
+    use super::{
+        EvictionReport, FileData, FolderBasedReader, FolderFiles, LazyFolderBasedReader,
+        MaintenanceReport, RemovalReport, ValidationReport, QUARANTINE_FOLDER,
+    };
+
+    async fn index(reader: &FolderBasedReader) -> Vec<(String, PathBuf)> {
+        let index: Vec<SourceItemPair<String, FileData<PathBuf>>> =
+            reader.index_reader.read().await.unwrap();
+        index
+            .into_iter()
+            .map(|SourceItemPair(_, FileData(id, path, _))| (id, path))
+            .collect()
+    }
+
+    #[test]
+    fn maintenance_repairs_an_inconsistent_folder() {
+        async_std::task::block_on(async {
+            let dir = tempfile::tempdir().unwrap();
+            let reader = FolderBasedReader::new(dir.path().to_path_buf());
+            let items: Vec<FileData<Vec<u8>>> = ["a", "b", "c"]
+                .iter()
+                .map(|id| FileData::new(id.to_string(), vec![0; 16]))
+                .collect();
+            reader.extend(&items, true).await.unwrap();
+
+            // Lose a file, and leave behind a stray file and a crashed run's temporary file
+            let (_, lost) = index(&reader)
+                .await
+                .into_iter()
+                .find(|(id, _)| id == "b")
+                .unwrap();
+            std::fs::remove_file(dir.path().join(lost)).unwrap();
+            std::fs::write(dir.path().join("stray"), b"?").unwrap();
+            std::fs::write(dir.path().join("crashed.tmp"), [0; 10]).unwrap();
+
+            // Passes can't overlap with writes
+            {
+                let _write = reader.activity.read().await;
+                assert_eq![reader.maintain(Duration::ZERO).await.unwrap(), None];
+            }
+
+            // Fresh temporary files might still be in use
+            let report = reader.maintain(Duration::from_secs(60 * 60)).await.unwrap();
+            assert_eq![
+                report,
+                Some(MaintenanceReport {
+                    pruned: 1,
+                    quarantined: 1,
+                    ..Default::default()
+                })
+            ];
+            let report = reader.maintain(Duration::ZERO).await.unwrap();
+            assert_eq![
+                report,
+                Some(MaintenanceReport {
+                    tmp_removed: 1,
+                    bytes_reclaimed: 10,
+                    ..Default::default()
+                })
+            ];
+
+            // Every entry has a file, and every file has an entry
+            let index = index(&reader).await;
+            let mut ids: Vec<&str> = index.iter().map(|(id, _)| id.as_str()).collect();
+            ids.sort();
+            assert_eq![ids, ["a", "c"]];
+            let referenced: HashSet<PathBuf> = index
+                .iter()
+                .map(|(_, path)| dir.path().join(path))
+                .collect();
+            let files: HashSet<PathBuf> = std::fs::read_dir(dir.path())
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .filter(|path| path.is_file() && !reader.is_bookkeeping(path))
+                .collect();
+            assert_eq![files, referenced];
+            assert![dir.path().join(QUARANTINE_FOLDER).join("stray").is_file()];
+        });
+    }
+
+    async fn filled(dir: &tempfile::TempDir, ids: &[&str]) -> FolderBasedReader {
+        let reader = FolderBasedReader::new(dir.path().to_path_buf());
+        let items: Vec<FileData<Vec<u8>>> = ids
+            .iter()
+            .map(|id| FileData::new(id.to_string(), vec![0; 16]))
+            .collect();
+        reader.extend(&items, true).await.unwrap();
+        reader
+    }
+
+    #[test]
+    fn entries_whose_file_is_gone_are_skipped_then_pruned() {
+        async_std::task::block_on(async {
+            let dir = tempfile::tempdir().unwrap();
+            let reader = filled(&dir, &["a", "b"]).await;
+            let lost = reader.locate("b").await.unwrap().unwrap();
+            std::fs::remove_file(lost).unwrap();
+
+            // The rest is still read
+            let items = reader.read().await.unwrap();
+            let ids: Vec<&str> = items.iter().map(|item| item.1 .0.as_str()).collect();
+            assert_eq![ids, ["a"]];
+            let ids = HashSet::from(["a".to_string(), "b".to_string()]);
+            let read = reader.read_filter(&ids).await.unwrap();
+            let read = futures::future::join_all(read.into_iter().map(|(_, f)| f)).await;
+            assert_eq![read.iter().filter(|item| item.is_err()).count(), 1];
+            assert_eq![reader.read_from_ids(&ids).await.len(), 1];
+
+            let report = reader.validate(None).await.unwrap();
+            assert_eq![
+                report,
+                Some(ValidationReport {
+                    pruned: 1,
+                    ..Default::default()
+                })
+            ];
+            let ids: Vec<String> = index(&reader).await.into_iter().map(|(id, _)| id).collect();
+            assert_eq![ids, ["a"]];
+        });
+    }
+
+    #[test]
+    fn files_without_an_entry_are_reported_then_removed_once_old() {
+        async_std::task::block_on(async {
+            let dir = tempfile::tempdir().unwrap();
+            let reader = filled(&dir, &["a"]).await;
+            // Written, but the index was never extended
+            let orphan = dir.path().join("orphan");
+            std::fs::write(&orphan, [0; 10]).unwrap();
+
+            let an_hour = Some(Duration::from_secs(60 * 60));
+            let report = reader.validate(an_hour).await.unwrap();
+            assert_eq![
+                report,
+                Some(ValidationReport {
+                    orphans: vec![orphan.clone()],
+                    ..Default::default()
+                })
+            ];
+            assert![orphan.is_file()];
+
+            let report = reader.validate(Some(Duration::ZERO)).await.unwrap();
+            assert_eq![
+                report,
+                Some(ValidationReport {
+                    orphans_removed: 1,
+                    bytes_reclaimed: 10,
+                    ..Default::default()
+                })
+            ];
+            assert![!orphan.exists()];
+            assert_eq![index(&reader).await.len(), 1];
+        });
+    }
+
+    #[test]
+    fn least_recently_played_files_are_evicted_first() {
+        async_std::task::block_on(async {
+            let dir = tempfile::tempdir().unwrap();
+            let reader = FolderBasedReader::new(dir.path().to_path_buf());
+            let items: Vec<FileData<Vec<u8>>> = ["a", "b", "c", "d"]
+                .iter()
+                .map(|id| FileData::new(id.to_string(), vec![0; 10]))
+                .collect();
+            reader.extend(&items, true).await.unwrap();
+            for (id, secs) in [("a", 100), ("b", 300), ("c", 200), ("d", 400)] {
+                let played = SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+                reader
+                    .touch(&HashSet::from([id.to_string()]), played)
+                    .await
+                    .unwrap();
+            }
+            // An entry from before sizes and play times were kept
+            std::fs::write(dir.path().join("legacy"), [0; 10]).unwrap();
+            let mut entries = std::fs::OpenOptions::new()
+                .append(true)
+                .open(&reader.index_reader.filepath)
+                .unwrap();
+            writeln![entries, r#"["old","legacy"]"#].unwrap();
+            drop(entries);
+            assert_eq![reader.usage().await.unwrap(), 50];
+
+            // "a" was played longest ago, but it's kept
+            let keep = HashSet::from(["a".to_string()]);
+            let report = reader.evict(30, &keep).await.unwrap();
+            assert_eq![
+                report,
+                Some(EvictionReport {
+                    evicted: 2,
+                    bytes_freed: 20,
+                    bytes_left: 30,
+                })
+            ];
+            let index = index(&reader).await;
+            let mut ids: Vec<&str> = index.iter().map(|(id, _)| id.as_str()).collect();
+            ids.sort();
+            assert_eq![ids, ["a", "b", "d"]];
+            assert![!dir.path().join("legacy").exists()];
+            assert_eq![reader.usage().await.unwrap(), 30];
+
+            // Nothing goes while it fits
+            let report = reader.evict(30, &keep).await.unwrap().unwrap();
+            assert_eq![(report.evicted, report.bytes_left), (0, 30)];
+        });
+    }
+
+    #[test]
+    fn removed_files_leave_the_index() {
+        async_std::task::block_on(async {
+            let dir = tempfile::tempdir().unwrap();
+            let reader = FolderBasedReader::new(dir.path().to_path_buf());
+            let items: Vec<FileData<Vec<u8>>> = ["a", "b", "c"]
+                .iter()
+                .map(|id| FileData::new(id.to_string(), vec![0; 10]))
+                .collect();
+            reader.extend(&items, true).await.unwrap();
+
+            let ids = HashSet::from(["a".to_string(), "missing".to_string()]);
+            let report = reader.remove(&ids).await.unwrap();
+            assert_eq![
+                report,
+                RemovalReport {
+                    removed: vec!["a".to_string()],
+                    bytes_freed: 10,
+                }
+            ];
+            assert_eq![reader.locate("a").await.unwrap(), None];
+
+            // The playing song is kept
+            let keep = HashSet::from(["c".to_string()]);
+            let report = reader.clear(&keep).await.unwrap();
+            assert_eq![report.removed, ["b"]];
+            let index = index(&reader).await;
+            assert_eq![index.len(), 1];
+            assert_eq![index[0].0, "c"];
+            assert![dir.path().join(&index[0].1).exists()];
+        });
+    }
+
+    #[test]
+    fn concurrent_writes_of_an_id_only_store_it_once() {
+        async_std::task::block_on(async {
+            let dir = tempfile::tempdir().unwrap();
+            let reader = FolderBasedReader::new(dir.path().to_path_buf());
+            let first = vec![FileData::new("a".to_string(), vec![1; 16])];
+            let second = vec![FileData::new("a".to_string(), vec![2; 16])];
+            let (first, second) =
+                futures::join!(reader.extend(&first, false), reader.extend(&second, false));
+            first.unwrap();
+            second.unwrap();
+
+            let index = index(&reader).await;
+            assert_eq![index.len(), 1];
+            let files: Vec<PathBuf> = std::fs::read_dir(dir.path())
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .filter(|path| path.is_file() && !reader.is_bookkeeping(path))
+                .collect();
+            assert_eq![files, vec![dir.path().join(&index[0].1)]];
+        });
+    }
+
+    #[test]
+    fn files_can_be_named_after_their_format() {
+        async_std::task::block_on(async {
+            let dir = tempfile::tempdir().unwrap();
+            let reader = FolderBasedReader::new(dir.path().to_path_buf());
+            let old = vec![FileData::new("old".to_string(), vec![1; 16])];
+            let new = vec![FileData::new("new".to_string(), vec![2; 16])];
+            reader.extend(&old, true).await.unwrap();
+            reader.extend_as(&new, "flac", true).await.unwrap();
+
+            let old = reader.locate("old").await.unwrap().unwrap();
+            let new = reader.locate("new").await.unwrap().unwrap();
+            assert_eq![old.extension(), None];
+            assert_eq![new.extension().unwrap(), "flac"];
+            assert_eq![std::fs::read(new).unwrap(), vec![2; 16]];
+        });
+    }
+
+    async fn lazy_paths(reader: &LazyFolderBasedReader) -> Vec<PathBuf> {
+        let index: Vec<SourceItemPair<String, FileData<PathBuf>>> = reader.read().await.unwrap();
+        index.into_iter().map(|item| item.1.into_data()).collect()
+    }
+
+    #[test]
+    fn lazy_folders_record_files_and_delete_replaced_ones() {
+        async_std::task::block_on(async {
+            let dir = tempfile::tempdir().unwrap();
+            let reader = LazyFolderBasedReader::new(dir.path().join("lazy"));
+            let inside = reader.filepath.join("first.png");
+            std::fs::write(&inside, b"1").unwrap();
+            let first = vec![FileData::new("a".to_string(), inside)];
+            reader.extend(&first, true).await.unwrap();
+            // Paths in the folder are kept relative to it
+            assert_eq![lazy_paths(&reader).await, [PathBuf::from("first.png")]];
+
+            // Without overwriting, the old entry stays
+            std::fs::write(reader.filepath.join("second.png"), b"2").unwrap();
+            let second = vec![FileData::new("a".to_string(), PathBuf::from("second.png"))];
+            reader.extend(&second, false).await.unwrap();
+            assert_eq![lazy_paths(&reader).await, [PathBuf::from("first.png")]];
+            assert![reader.filepath.join("first.png").is_file()];
+
+            // Overwriting replaces it, and the file it pointed to goes
+            reader.extend(&second, true).await.unwrap();
+            assert_eq![lazy_paths(&reader).await, [PathBuf::from("second.png")]];
+            assert![!reader.filepath.join("first.png").exists()];
+            assert![reader.filepath.join("second.png").is_file()];
+        });
+    }
+
+    #[test]
+    fn lazy_folders_copy_in_files_from_elsewhere() {
+        async_std::task::block_on(async {
+            let dir = tempfile::tempdir().unwrap();
+            let reader = LazyFolderBasedReader::new(dir.path().join("lazy"));
+            let outside = dir.path().join("outside.png");
+            std::fs::write(&outside, b"image").unwrap();
+            let items = vec![FileData::new("a".to_string(), outside.clone())];
+            reader.extend(&items, true).await.unwrap();
+
+            let paths = lazy_paths(&reader).await;
+            assert_eq![paths.len(), 1];
+            let path = &paths[0];
+            assert![path.is_relative()];
+            assert_eq![path.extension().unwrap(), "png"];
+            assert_eq![std::fs::read(reader.convert_path(path)).unwrap(), b"image"];
+            assert![outside.is_file()];
+        });
+    }
+
+    /// Entries that point at several files, like thumbnails of different sizes
+    #[derive(Clone, Serialize, Deserialize)]
+    struct Files(Vec<PathBuf>);
+    impl FolderFiles for Files {
+        fn files(&self) -> Vec<&PathBuf> {
+            self.0.iter().collect()
+        }
+        fn files_mut(&mut self) -> Vec<&mut PathBuf> {
+            self.0.iter_mut().collect()
+        }
+    }
+
+    #[test]
+    fn clearing_a_lazy_folder_drops_entries_with_deleted_files() {
+        async_std::task::block_on(async {
+            let dir = tempfile::tempdir().unwrap();
+            let reader = LazyFolderBasedReader::new(dir.path().join("lazy"));
+            for name in ["a.png", "b.png"] {
+                std::fs::write(reader.filepath.join(name), b"1").unwrap();
+            }
+            // A folder can't be removed as a file, so it stands in for a file that's stuck
+            std::fs::create_dir(reader.filepath.join("stuck")).unwrap();
+            let entry = |id: &str, files: &[&str]| {
+                FileData::new(
+                    id.to_string(),
+                    Files(files.iter().map(PathBuf::from).collect()),
+                )
+            };
+            let items = vec![
+                entry("a", &["a.png"]),
+                entry("b", &["b.png", "stuck"]),
+                entry("c", &["stuck"]),
+            ];
+            reader.extend(&items, true).await.unwrap();
+
+            let mut report = reader.clear::<Files>().await.unwrap();
+            // The index isn't kept in the order it was extended in
+            report.removed.sort();
+            assert_eq![
+                report,
+                RemovalReport {
+                    removed: vec!["a".to_string(), "b".to_string()],
+                    bytes_freed: 2,
+                }
+            ];
+            assert![!reader.filepath.join("b.png").exists()];
+            // Only the entry none of whose files could go is kept
+            let index: Vec<SourceItemPair<String, FileData<Files>>> = reader.read().await.unwrap();
+            let ids: Vec<String> = index
+                .into_iter()
+                .map(|SourceItemPair(_, FileData(id, _, _))| id)
+                .collect();
+            assert_eq![ids, ["c"]];
+        });
+    }
+}
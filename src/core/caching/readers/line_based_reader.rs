@@ -0,0 +1,760 @@
+use std::collections::HashSet;
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use async_std::io::{prelude::SeekExt, ReadExt, SeekFrom, WriteExt};
+use async_std::{fs as afs, io::prelude::BufReadExt};
+use async_std::{io as aio, sync::Mutex};
+use futures::Future;
+
+use fs4::async_std::AsyncFileExt;
+use fs4::FileExt;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    caching::{CacheCounters, IDed},
+    verbosity::trace,
+};
+
+use super::{
+    cache_reader::{CacheReader, SourceItemPair},
+    line_index::LineIndex,
+};
+
+pub type LineItemPair<T> = SourceItemPair<String, T>;
+
+/// How much of the file is read at a time when reading it from the end
+const TAIL_CHUNK: u64 = 64 * 1024;
+
+/// A lock for each file that's written to, held for the whole of every write.
+/// The file locks only keep other processes out, and each write makes its own handle, so
+/// without these two tasks extending the same file would both read it before either
+/// replaced it, and one of the writes would be lost.
+static WRITE_LOCKS: Lazy<parking_lot::Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>> =
+    Lazy::new(Default::default);
+
+/// The lock shared by every reader of the file
+fn write_lock(path: &Path) -> Arc<Mutex<()>> {
+    let mut locks = WRITE_LOCKS.lock();
+    locks.entry(path.to_path_buf()).or_default().clone()
+}
+
+/// Waits for the writes that have started to finish, so exiting doesn't cut one off
+pub async fn flush_writes() {
+    let locks: Vec<Arc<Mutex<()>>> = WRITE_LOCKS.lock().values().cloned().collect();
+    for lock in locks {
+        let _written = lock.lock().await;
+    }
+}
+
+/// A path next to the file that no other write uses, to write its replacement to
+pub fn unique_tmp(path: &Path, extension: &str) -> PathBuf {
+    path.with_extension(format!("{extension}.{}.tmp", Uuid::new_v4().simple()))
+}
+
+fn filter_file_items<'a, T: IDed<String>>(
+    items: impl Iterator<Item = LineItemPair<T>> + 'a,
+    overwrite: bool,
+    filter: &'a HashSet<String>,
+) -> impl Iterator<Item = (String, Vec<u8>)> + 'a {
+    items.filter_map(move |SourceItemPair(mut line, item)| {
+        line.push('\n');
+        let id = item.id();
+        match (overwrite, filter.contains(id)) {
+            (true, true) => None,
+            (true, false) => Some((id.clone(), line.as_bytes().to_vec())),
+            (false, true) => Some((id.clone(), line.as_bytes().to_vec())),
+            (false, false) => Some((id.clone(), line.as_bytes().to_vec())),
+        }
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct LineBasedReader {
+    pub filepath: PathBuf,
+    /// Shared by its clones, and by the cache it reads for
+    pub counters: Arc<CacheCounters>,
+}
+impl LineBasedReader {
+    pub fn new(filepath: PathBuf) -> Self {
+        Self {
+            filepath,
+            counters: Arc::default(),
+        }
+    }
+
+    /// Reads every line of the file, and builds an index of where each one is
+    async fn scan<T: IDed<String> + for<'de> Deserialize<'de>>(
+        &self,
+    ) -> Result<(Vec<LineItemPair<T>>, LineIndex), std::io::Error> {
+        let file = afs::File::open(&self.filepath).await?;
+        let locked = file.lock_shared();
+        trace!["(READ) LOCKING {:?}: {:?}", self.filepath, locked];
+
+        let mut reader = aio::BufReader::new(&file);
+        let mut vec: Vec<LineItemPair<T>> = Vec::new();
+        let mut index = LineIndex::default();
+        let mut offset = 0;
+        let mut line = String::new();
+        while reader.read_line(&mut line).await? > 0 {
+            let len = line.len() as u64;
+            let trimmed = line.trim_end_matches(['\n', '\r']);
+            let item = serde_json::from_str::<T>(trimmed)?;
+            index.insert(item.id().clone(), offset, len);
+            vec.push(SourceItemPair(trimmed.to_string(), item));
+            offset += len;
+            line.clear();
+        }
+        index.stamp(&file.metadata().await?);
+        let unlocked = file.unlock();
+        trace!["(READ) UNLOCKING {:?}: {:?}", self.filepath, unlocked];
+        self.counters.read(offset as usize);
+
+        Ok((vec, index))
+    }
+
+    /// Reads only the lines of the given ids using the index.
+    /// Returns None when the index doesn't agree with the file, so it has to be scanned.
+    async fn read_indexed<T: IDed<String> + for<'de> Deserialize<'de>>(
+        &self,
+        index: &LineIndex,
+        f: &HashSet<String>,
+    ) -> Option<Vec<LineItemPair<T>>> {
+        let mut file = afs::File::open(&self.filepath).await.ok()?;
+        let mut items = Vec::with_capacity(f.len());
+        let mut bytes = 0;
+        for id in f {
+            // ids missing from a valid index are not in the file
+            let (offset, len) = match index.get(id) {
+                Some(range) => range,
+                None => continue,
+            };
+            file.seek(SeekFrom::Start(offset)).await.ok()?;
+            let mut buf = vec![0; len as usize];
+            file.read_exact(&mut buf).await.ok()?;
+
+            let line = String::from_utf8(buf).ok()?;
+            let line = line.trim_end_matches(['\n', '\r']).to_string();
+            let item = serde_json::from_str::<T>(&line).ok()?;
+            if item.id() != id {
+                return None;
+            }
+            items.push(SourceItemPair(line, item));
+            bytes += len as usize;
+        }
+        self.counters.read(bytes);
+        Some(items)
+    }
+
+    /// Replaces the file with the given lines, and writes their index next to it.
+    /// Has to be called with the file's write lock held.
+    fn write_lines(&self, lines: Vec<(String, Vec<u8>)>) -> Result<(), std::io::Error> {
+        let filepath = &self.filepath;
+        let tempfile = unique_tmp(filepath, "ndjson");
+        let index_tempfile;
+
+        {
+            // Create the temporary file to write the new list to
+            let output_file = File::create(&tempfile)?;
+            let locked = output_file.lock_exclusive();
+            trace!["(XTND) LOCKING {:?}: {:?}", tempfile, locked];
+
+            let mut out = std::io::BufWriter::new(&output_file);
+            let mut index = LineIndex::default();
+            let mut offset = 0;
+            for (id, bytes) in lines {
+                out.write_all(&bytes)?;
+                index.insert(id, offset, bytes.len() as u64);
+                offset += bytes.len() as u64;
+            }
+            out.flush()?;
+            drop(out);
+
+            // The rename keeps the metadata, so the index can be stamped with the temp file
+            index.stamp(&output_file.metadata()?);
+            index_tempfile = index.write_tmp(filepath)?;
+
+            let unlocked = output_file.unlock();
+            trace!["(XTND) UNLOCKING {:?}: {:?}", tempfile, unlocked];
+        }
+
+        // Replace songs.ndjson with songs.ndjson.tmp, then its index.
+        // A crash in between leaves an index that doesn't match and is rebuilt.
+
+        std::fs::rename(&tempfile, filepath)?;
+        std::fs::rename(&index_tempfile, LineIndex::path_for(filepath))?;
+
+        Ok(())
+    }
+
+    /// Adds the lines to the end of the file and to its index, without reading the file.
+    /// The index has to match the file, and none of the lines' ids can be in it.
+    /// Has to be called with the file's write lock held.
+    fn append_indexed(
+        &self,
+        mut index: LineIndex,
+        lines: Vec<(String, Vec<u8>)>,
+    ) -> Result<(), std::io::Error> {
+        let file = OpenOptions::new().append(true).open(&self.filepath)?;
+        let locked = file.lock_exclusive();
+        trace!["(XTND) LOCKING {:?}: {:?}", self.filepath, locked];
+
+        let mut offset = file.metadata()?.len();
+        let mut out = std::io::BufWriter::new(&file);
+        for (id, bytes) in lines {
+            out.write_all(&bytes)?;
+            index.insert(id, offset, bytes.len() as u64);
+            offset += bytes.len() as u64;
+        }
+        out.flush()?;
+        drop(out);
+        index.stamp(&file.metadata()?);
+
+        let unlocked = file.unlock();
+        trace!["(XTND) UNLOCKING {:?}: {:?}", self.filepath, unlocked];
+
+        let index_tempfile = index.write_tmp(&self.filepath)?;
+        std::fs::rename(&index_tempfile, LineIndex::path_for(&self.filepath))
+    }
+
+    /// Reads the last `count` items of the file, without reading the lines before them.
+    /// Also returns whether there were lines before them. Lines that don't parse are skipped.
+    pub async fn tail<T: for<'de> Deserialize<'de>>(
+        &self,
+        count: usize,
+    ) -> Result<(Vec<T>, bool), std::io::Error> {
+        let mut file = match afs::File::open(&self.filepath).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((vec![], false)),
+            Err(e) => return Err(e),
+        };
+        let locked = file.lock_shared();
+        trace!["(TAIL) LOCKING {:?}: {:?}", self.filepath, locked];
+
+        // One more line break than lines wanted, so the first of them is read whole
+        let mut start = file.metadata().await?.len();
+        let mut buf: Vec<u8> = vec![];
+        while start > 0 && buf.iter().filter(|b| **b == b'\n').count() <= count {
+            let chunk = TAIL_CHUNK.min(start);
+            start -= chunk;
+            file.seek(SeekFrom::Start(start)).await?;
+            let mut bytes = vec![0; chunk as usize];
+            file.read_exact(&mut bytes).await?;
+            bytes.extend(buf);
+            buf = bytes;
+        }
+        let unlocked = file.unlock();
+        trace!["(TAIL) UNLOCKING {:?}: {:?}", self.filepath, unlocked];
+        self.counters.read(buf.len());
+
+        let text = String::from_utf8_lossy(&buf);
+        let mut lines: Vec<&str> = text.split('\n').collect();
+        if start > 0 {
+            // Cut off by where reading started
+            lines.remove(0);
+        }
+        let lines: Vec<&str> = lines
+            .into_iter()
+            .map(|line| line.trim_end_matches('\r'))
+            .filter(|line| !line.is_empty())
+            .collect();
+        let skip = lines.len().saturating_sub(count);
+        let items = lines[skip..]
+            .iter()
+            .filter_map(|line| match serde_json::from_str::<T>(line) {
+                Ok(item) => Some(item),
+                Err(e) => {
+                    println!["Skipping unreadable line of {:?}: {e}", self.filepath];
+                    None
+                }
+            })
+            .collect();
+        Ok((items, start > 0 || skip > 0))
+    }
+
+    /// Adds the items to the end of the file, without reading it.
+    /// The index is left as it was, so it's rebuilt the next time it's used.
+    pub async fn append<T: Serialize>(&self, items: &[T]) -> Result<(), std::io::Error> {
+        let mut lines = vec![];
+        for item in items {
+            serde_json::to_writer(&mut lines, item)?;
+            lines.push(b'\n');
+        }
+        let lock = write_lock(&self.filepath);
+        let _writing = lock.lock().await;
+        let mut file = afs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.filepath)
+            .await?;
+        let locked = file.lock_exclusive();
+        trace!["(APND) LOCKING {:?}: {:?}", self.filepath, locked];
+
+        file.write_all(&lines).await?;
+        file.flush().await?;
+
+        let unlocked = file.unlock();
+        trace!["(APND) UNLOCKING {:?}: {:?}", self.filepath, unlocked];
+        Ok(())
+    }
+
+    /// Replaces the file with only the items, in one atomic replace
+    pub async fn replace<T: IDed<String> + Serialize>(
+        &self,
+        items: &[T],
+    ) -> Result<(), std::io::Error> {
+        let lock = write_lock(&self.filepath);
+        let _writing = lock.lock().await;
+        let lines = items
+            .iter()
+            .map(|item| -> Result<_, std::io::Error> {
+                let mut line = serde_json::to_string(item)?;
+                line.push('\n');
+                Ok((item.id().clone(), line.into_bytes()))
+            })
+            .collect::<Result<Vec<(String, Vec<u8>)>, _>>()?;
+        self.write_lines(lines)
+    }
+
+    /// Rewrites the file with only the items that pass the predicate, in one atomic replace.
+    /// Returns how many items were removed.
+    pub async fn retain<T: IDed<String> + for<'de> Deserialize<'de>>(
+        &self,
+        mut keep: impl FnMut(&T) -> bool,
+    ) -> Result<usize, std::io::Error> {
+        let lock = write_lock(&self.filepath);
+        let _writing = lock.lock().await;
+        let (items, _) = self.scan::<T>().await?;
+        let total = items.len();
+        let lines: Vec<(String, Vec<u8>)> = items
+            .into_iter()
+            .filter(|SourceItemPair(_, item)| keep(item))
+            .map(|SourceItemPair(mut line, item)| {
+                line.push('\n');
+                (item.id().clone(), line.into_bytes())
+            })
+            .collect();
+        let removed = total - lines.len();
+        self.write_lines(lines)?;
+        Ok(removed)
+    }
+
+    /// Rewrites the lines of the items that `change` changed, returning how many it changed.
+    /// The file is only replaced if something changed.
+    pub async fn update<T: IDed<String> + Serialize + for<'de> Deserialize<'de>>(
+        &self,
+        mut change: impl FnMut(&mut T) -> bool,
+    ) -> Result<usize, std::io::Error> {
+        let lock = write_lock(&self.filepath);
+        let _writing = lock.lock().await;
+        let (items, _) = self.scan::<T>().await?;
+        let mut changed = 0;
+        let lines = items
+            .into_iter()
+            .map(
+                |SourceItemPair(mut line, mut item)| -> Result<_, std::io::Error> {
+                    if change(&mut item) {
+                        changed += 1;
+                        line = serde_json::to_string(&item)?;
+                    }
+                    line.push('\n');
+                    Ok((item.id().clone(), line.into_bytes()))
+                },
+            )
+            .collect::<Result<Vec<(String, Vec<u8>)>, _>>()?;
+        if changed > 0 {
+            self.write_lines(lines)?;
+        }
+        Ok(changed)
+    }
+}
+
+impl<T: IDed<String> + Serialize + for<'de> Deserialize<'de>> CacheReader<String, String, T>
+    for LineBasedReader
+{
+    async fn read(&self) -> Result<Vec<SourceItemPair<String, T>>, std::io::Error> {
+        self.scan().await.map(|(items, _)| items)
+    }
+
+    // Seeks directly to the requested lines when the sidecar index is usable
+    async fn read_filter(
+        &self,
+        f: &HashSet<String>,
+    ) -> Result<
+        Vec<(
+            String,
+            impl Future<Output = std::io::Result<LineItemPair<T>>>,
+        )>,
+        std::io::Error,
+    > {
+        let indexed = match LineIndex::load(&self.filepath) {
+            Some(index) => self.read_indexed(&index, f).await,
+            None => None,
+        };
+        let items = match indexed {
+            Some(items) => items,
+            None => {
+                println!["(READ) Rebuilding index of {:?}", self.filepath];
+                let (items, index) = self.scan::<T>().await?;
+                match index.write_tmp(&self.filepath) {
+                    Ok(tmp) => {
+                        if let Err(e) = std::fs::rename(tmp, LineIndex::path_for(&self.filepath)) {
+                            println!["Failed to save index: {e:?}"];
+                        }
+                    }
+                    Err(e) => println!["Failed to save index: {e:?}"],
+                }
+                items
+                    .into_iter()
+                    .filter(|SourceItemPair(_, item)| f.contains(item.id()))
+                    .collect()
+            }
+        };
+
+        Ok(items
+            .into_iter()
+            .map(|i| (i.1.id().clone(), async { Ok(i) }))
+            .collect())
+    }
+    async fn extend<OutT: AsRef<T>, V: AsRef<Vec<OutT>>>(
+        &self,
+        items: V,
+        overwrite: bool,
+    ) -> Result<(), std::io::Error> {
+        // Held from reading the file to replacing it, so no other write lands in between
+        let lock = write_lock(&self.filepath);
+        let _writing = lock.lock().await;
+        {
+            let items: HashMap<String, &T> = items
+                .as_ref()
+                .iter()
+                .map(|i| {
+                    let r = i.as_ref();
+                    (r.id().to_string(), r)
+                })
+                .collect();
+
+            // When the index shows none of the items are in the file, there's nothing to
+            // overwrite or keep, so they're added to the end instead of rewriting it
+            let index = LineIndex::load(&self.filepath)
+                .filter(|index| items.keys().all(|id| index.get(id).is_none()));
+            if let Some(index) = index {
+                let lines = items
+                    .into_iter()
+                    .map(|(id, item)| -> Result<_, std::io::Error> {
+                        let mut json = serde_json::to_string(item)?;
+                        json.push('\n');
+                        Ok((id, json.into_bytes()))
+                    })
+                    .collect::<Result<Vec<(String, Vec<u8>)>, _>>()?;
+                return self.append_indexed(index, lines);
+            }
+
+            let mut lines = vec![];
+            // Read the original list
+            if let Ok(itemlist) = self.read().await {
+                let itemlist: Vec<SourceItemPair<String, T>> = itemlist;
+
+                // Filter through the lines, find existing keys and skip broken lines
+                let keys = items.keys().cloned().collect();
+                lines.extend(filter_file_items(itemlist.into_iter(), overwrite, &keys));
+            }
+
+            // Add remaining keys to the file. Without overwriting, existing lines are kept.
+            let existing: HashSet<String> = lines.iter().map(|(id, _)| id.clone()).collect();
+            for (id, item) in items {
+                if existing.contains(&id) {
+                    continue;
+                }
+                let mut json = serde_json::to_string(item).unwrap();
+                json.push('\n');
+                lines.push((id, json.into_bytes()));
+            }
+
+            self.write_lines(lines)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashSet,
+        io::Write,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use serde::{Deserialize, Serialize};
+
+    use crate::caching::{
+        readers::{line_index::LineIndex, CacheReader, SourceItemPair},
+        IDed,
+    };
+
+    use super::{flush_writes, write_lock, LineBasedReader};
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Item {
+        id: String,
+        value: usize,
+    }
+    impl IDed<String> for Item {
+        fn id(&self) -> &String {
+            &self.id
+        }
+    }
+    impl AsRef<Item> for Item {
+        fn as_ref(&self) -> &Item {
+            self
+        }
+    }
+
+    async fn filtered(reader: &LineBasedReader, ids: &[&str]) -> Vec<Item> {
+        let ids: HashSet<String> = ids.iter().map(|s| s.to_string()).collect();
+        let items = reader.read_filter(&ids).await.unwrap();
+        let mut items: Vec<Item> = futures::future::join_all(items.into_iter().map(|i| i.1))
+            .await
+            .into_iter()
+            .map(|i| i.unwrap().1)
+            .collect();
+        items.sort_by_key(|i| i.value);
+        items
+    }
+
+    #[test]
+    fn targeted_reads_use_the_index() {
+        async_std::task::block_on(async {
+            let dir = tempfile::tempdir().unwrap();
+            let reader = LineBasedReader::new(dir.path().join("items.ndjson"));
+            let items: Vec<Item> = (0..1000)
+                .map(|value| Item {
+                    id: format!("item{value}"),
+                    value,
+                })
+                .collect();
+            reader.extend(&items, true).await.unwrap();
+            assert![LineIndex::load(&reader.filepath).is_some()];
+
+            let found = filtered(&reader, &["item3", "item999", "missing"]).await;
+            assert_eq![found, vec![items[3].clone(), items[999].clone()]];
+        });
+    }
+
+    #[test]
+    fn stale_index_falls_back_to_a_scan() {
+        async_std::task::block_on(async {
+            let dir = tempfile::tempdir().unwrap();
+            let reader = LineBasedReader::new(dir.path().join("items.ndjson"));
+            let items: Vec<Item> = (0..10)
+                .map(|value| Item {
+                    id: format!("item{value}"),
+                    value,
+                })
+                .collect();
+            reader.extend(&items, true).await.unwrap();
+
+            // Rewrite the file behind the index's back
+            let mut file = std::fs::File::create(&reader.filepath).unwrap();
+            for item in items.iter().rev() {
+                writeln![file, "{}", serde_json::to_string(item).unwrap()].unwrap();
+            }
+            writeln![file, r#"{{"id":"item10","value":10}}"#].unwrap();
+            drop(file);
+            assert![LineIndex::load(&reader.filepath).is_none()];
+
+            let found = filtered(&reader, &["item0", "item9"]).await;
+            assert_eq![found, vec![items[0].clone(), items[9].clone()]];
+            // The scan rebuilt the index
+            assert![LineIndex::load(&reader.filepath).is_some()];
+
+            // A corrupted index is ignored too
+            std::fs::write(LineIndex::path_for(&reader.filepath), b"garbage").unwrap();
+            let found = filtered(&reader, &["item5"]).await;
+            assert_eq![found, vec![items[5].clone()]];
+        });
+    }
+
+    #[test]
+    fn appended_items_are_read_from_the_end() {
+        async_std::task::block_on(async {
+            let dir = tempfile::tempdir().unwrap();
+            let reader = LineBasedReader::new(dir.path().join("items.ndjson"));
+            assert_eq![reader.tail::<Item>(5).await.unwrap(), (vec![], false)];
+
+            // Enough to span a few chunks
+            let items: Vec<Item> = (0..5000)
+                .map(|value| Item {
+                    id: format!("item{value}"),
+                    value,
+                })
+                .collect();
+            reader.append(&items[..4000]).await.unwrap();
+            reader.append(&items[4000..]).await.unwrap();
+
+            let (tail, more) = reader.tail::<Item>(3000).await.unwrap();
+            assert_eq![tail, items[2000..]];
+            assert![more];
+            let (all, more) = reader.tail::<Item>(6000).await.unwrap();
+            assert_eq![all, items];
+            assert![!more];
+
+            reader.replace(&items[4990..]).await.unwrap();
+            let (last, more) = reader.tail::<Item>(20).await.unwrap();
+            assert_eq![last, items[4990..]];
+            assert![!more];
+        });
+    }
+
+    #[test]
+    fn concurrent_extends_are_all_kept() {
+        async_std::task::block_on(async {
+            let dir = tempfile::tempdir().unwrap();
+            let reader = LineBasedReader::new(dir.path().join("items.ndjson"));
+            reader.append::<Item>(&[]).await.unwrap();
+
+            // Each extend overlaps the ids of the ones around it
+            let extends = (0..50).map(|value| {
+                let reader = reader.clone();
+                async_std::task::spawn(async move {
+                    let items: Vec<Item> = (0..5)
+                        .map(|k| Item {
+                            id: format!("item{}", (value + k) % 20),
+                            value,
+                        })
+                        .collect();
+                    reader.extend(&items, true).await.unwrap();
+                })
+            });
+            // Reads in the meantime only ever see whole files
+            let reads = (0..20).map(|_| {
+                let reader = reader.clone();
+                async_std::task::spawn(async move {
+                    let items: Vec<SourceItemPair<String, Item>> = reader.read().await.unwrap();
+                    items.len()
+                })
+            });
+            let reads = futures::future::join_all(reads);
+            let (_, counts) = futures::join!(futures::future::join_all(extends), reads);
+            assert![counts.iter().all(|count| *count <= 20)];
+
+            let items: Vec<SourceItemPair<String, Item>> = reader.read().await.unwrap();
+            let ids: HashSet<&String> = items.iter().map(|item| &item.1.id).collect();
+            assert_eq![(items.len(), ids.len()), (20, 20)];
+            // Only the file and its index are left
+            assert_eq![std::fs::read_dir(dir.path()).unwrap().count(), 2];
+        });
+    }
+
+    #[test]
+    fn flushing_waits_for_writes_that_started() {
+        async_std::task::block_on(async {
+            let dir = tempfile::tempdir().unwrap();
+            let lock = write_lock(&dir.path().join("items.ndjson"));
+            let writing = lock.lock().await;
+
+            let flushed = Arc::new(AtomicBool::new(false));
+            let flush = {
+                let flushed = flushed.clone();
+                async_std::task::spawn(async move {
+                    flush_writes().await;
+                    flushed.store(true, Ordering::SeqCst);
+                })
+            };
+            async_std::task::sleep(Duration::from_millis(50)).await;
+            assert![!flushed.load(Ordering::SeqCst)];
+            drop(writing);
+            flush.await;
+            assert![flushed.load(Ordering::SeqCst)];
+        });
+    }
+
+    #[test]
+    fn new_items_are_appended_without_a_rewrite() {
+        async_std::task::block_on(async {
+            let dir = tempfile::tempdir().unwrap();
+            let reader = LineBasedReader::new(dir.path().join("items.ndjson"));
+            let items: Vec<Item> = (0..10)
+                .map(|value| Item {
+                    id: format!("item{value}"),
+                    value,
+                })
+                .collect();
+            let read_ids = || async {
+                let items: Vec<SourceItemPair<String, Item>> = reader.read().await.unwrap();
+                let ids: Vec<String> = items.into_iter().map(|item| item.1.id).collect();
+                ids
+            };
+            reader.extend(&items[..5].to_vec(), true).await.unwrap();
+
+            // Only added to, and the index still finds every line
+            let before = std::fs::read(&reader.filepath).unwrap();
+            reader.extend(&items[5..7].to_vec(), true).await.unwrap();
+            let after = std::fs::read(&reader.filepath).unwrap();
+            assert![after.starts_with(&before) && after.len() > before.len()];
+            let found = filtered(&reader, &["item1", "item6"]).await;
+            assert_eq![found, vec![items[1].clone(), items[6].clone()]];
+
+            // An id that's already there means a rewrite, kept or overwritten
+            let changed = |value| Item {
+                id: "item2".into(),
+                value,
+            };
+            reader
+                .extend(&vec![changed(100), items[7].clone()], false)
+                .await
+                .unwrap();
+            reader.extend(&vec![changed(200)], true).await.unwrap();
+            reader.extend(&items[8..].to_vec(), false).await.unwrap();
+            let found = filtered(&reader, &["item2", "item9"]).await;
+            assert_eq![found, vec![items[9].clone(), changed(200)]];
+
+            let ids = read_ids().await;
+            let unique: HashSet<&String> = ids.iter().collect();
+            assert_eq![(ids.len(), unique.len()), (10, 10)];
+
+            // Behind the index's back the file is read whole again, so nothing is doubled
+            std::fs::write(&reader.filepath, &before).unwrap();
+            reader.extend(&items[..1].to_vec(), false).await.unwrap();
+            assert_eq![read_ids().await.len(), 5];
+        });
+    }
+
+    #[test]
+    fn appends_and_rewrites_can_interleave() {
+        async_std::task::block_on(async {
+            let dir = tempfile::tempdir().unwrap();
+            let reader = LineBasedReader::new(dir.path().join("items.ndjson"));
+            let item = |id: String, value| Item { id, value };
+            let old: Vec<Item> = (0..10).map(|i| item(format!("old{i}"), i)).collect();
+            reader.extend(&old, true).await.unwrap();
+
+            // Half add new ids, half overwrite old ones
+            let writes = (0..40).map(|i| {
+                let reader = reader.clone();
+                let items = match i % 2 {
+                    0 => vec![item(format!("new{i}"), i)],
+                    _ => vec![item(format!("old{}", i % 10), i)],
+                };
+                async_std::task::spawn(async move { reader.extend(&items, true).await.unwrap() })
+            });
+            futures::future::join_all(writes).await;
+
+            let items: Vec<SourceItemPair<String, Item>> = reader.read().await.unwrap();
+            let ids: HashSet<&String> = items.iter().map(|item| &item.1.id).collect();
+            assert_eq![(items.len(), ids.len()), (30, 30)];
+            let found = filtered(&reader, &["new38", "old9"]).await;
+            assert_eq![found.len(), 2];
+        });
+    }
+}
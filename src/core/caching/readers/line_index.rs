@@ -0,0 +1,69 @@
+use std::{
+    collections::HashMap,
+    fs::{File, Metadata},
+    io::Write,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::line_based_reader::unique_tmp;
+
+/// A sidecar index of a line based cache, mapping ids to the byte range of their line.
+///
+/// The index remembers the length and modification time of the data file it was built for,
+/// so an index that's out of date with its data file is never used.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct LineIndex {
+    data_len: u64,
+    data_modified: Option<SystemTime>,
+    /// id -> (offset, length)
+    lines: HashMap<String, (u64, u64)>,
+}
+
+impl LineIndex {
+    pub fn path_for(data: &Path) -> PathBuf {
+        data.with_extension("ndjson.idx")
+    }
+
+    pub fn insert(&mut self, id: String, offset: u64, len: u64) {
+        self.lines.insert(id, (offset, len));
+    }
+
+    pub fn get(&self, id: &str) -> Option<(u64, u64)> {
+        self.lines.get(id).copied()
+    }
+
+    /// Marks the index as built for the data file with this metadata
+    pub fn stamp(&mut self, data: &Metadata) {
+        self.data_len = data.len();
+        self.data_modified = data.modified().ok();
+    }
+
+    fn matches(&self, data: &Metadata) -> bool {
+        self.data_modified.is_some()
+            && self.data_len == data.len()
+            && self.data_modified == data.modified().ok()
+    }
+
+    /// Loads the index of the data file.
+    /// Returns None if the index is missing, corrupted or stale.
+    pub fn load(data: &Path) -> Option<Self> {
+        let metadata = std::fs::metadata(data).ok()?;
+        let bytes = std::fs::read(Self::path_for(data)).ok()?;
+        let index: Self = serde_json::from_slice(&bytes).ok()?;
+        index.matches(&metadata).then_some(index)
+    }
+
+    /// Writes the index to a temporary file next to the data file.
+    /// The returned path must be renamed to [`Self::path_for`] after the data file is in place.
+    pub fn write_tmp(&self, data: &Path) -> std::io::Result<PathBuf> {
+        let tempfile = unique_tmp(data, "ndjson.idx");
+        let json = serde_json::to_vec(self)?;
+        let mut file = File::create(&tempfile)?;
+        file.write_all(&json)?;
+        file.flush()?;
+        Ok(tempfile)
+    }
+}
@@ -6,14 +6,22 @@ use kira::sound::{
     FromFileError, PlaybackState,
 };
 
-use super::IDed;
+use crate::audio::normalizing_gain;
 
+use super::{IDed, SizeHint};
+
+/// A decoded song, and the gain that normalizes its loudness
 #[derive(Debug, Clone)]
-pub struct BasicSoundData(String, StaticSoundData);
+pub struct BasicSoundData(String, StaticSoundData, Option<f64>);
 impl BasicSoundData {
     pub fn data(&self) -> &StaticSoundData {
         &self.1
     }
+
+    /// None when the song is silent
+    pub fn gain(&self) -> Option<f64> {
+        self.2
+    }
 }
 
 impl IDed<String> for BasicSoundData {
@@ -23,17 +31,26 @@ impl IDed<String> for BasicSoundData {
     }
 }
 
-impl From<(String, Vec<u8>)> for BasicSoundData {
-    fn from(value: (String, Vec<u8>)) -> Self {
-        let sound = StaticSoundData::from_cursor(Cursor::new(value.1));
-        println!["Created sound from bytes"];
-
-        Self(value.0, sound.unwrap())
+impl SizeHint for BasicSoundData {
+    fn size_hint(&self) -> usize {
+        self.1.frames.len() * std::mem::size_of::<kira::Frame>()
     }
 }
-impl From<(String, StaticSoundData)> for BasicSoundData {
-    fn from(value: (String, StaticSoundData)) -> Self {
-        Self(value.0, value.1)
+
+/// Decodes the file, which can be in any format the audio backend knows
+impl TryFrom<(String, Vec<u8>)> for BasicSoundData {
+    type Error = String;
+
+    fn try_from(value: (String, Vec<u8>)) -> Result<Self, Self::Error> {
+        let sound = match StaticSoundData::from_cursor(Cursor::new(value.1)) {
+            Ok(sound) => sound,
+            Err(e) => return Err(format!("Can't decode {}: {e}", value.0)),
+        };
+        println!["Created sound from bytes"];
+        // Measured while it's decoded anyway, it's saved with the song's metadata
+        let gain = normalizing_gain(&sound.frames, sound.sample_rate);
+
+        Ok(Self(value.0, sound, gain))
     }
 }
 
@@ -89,11 +106,17 @@ impl From<BasicSoundData> for SoundData {
         Self(value.0, SoundDataType::Static(value.1))
     }
 }
-impl From<(String, PathBuf)> for SoundData {
-    fn from(value: (String, PathBuf)) -> Self {
-        let sound = StreamingSoundData::from_file(value.1);
+/// Streams the file, going by its extension for the format when it has one
+impl TryFrom<(String, PathBuf)> for SoundData {
+    type Error = String;
+
+    fn try_from(value: (String, PathBuf)) -> Result<Self, Self::Error> {
+        let sound = match StreamingSoundData::from_file(&value.1) {
+            Ok(sound) => sound,
+            Err(e) => return Err(format!("Can't stream {}: {e}", value.0)),
+        };
         println!["Created sound from file"];
-        SoundData(value.0, SoundDataType::Stream(sound.unwrap()))
+        Ok(SoundData(value.0, SoundDataType::Stream(sound)))
     }
 }
 
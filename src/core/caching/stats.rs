@@ -0,0 +1,209 @@
+//! Counters of what the caches do, to see why something is read more often than it should be.
+//! They're atomics, so counting doesn't take a lock on the read path.
+
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// Shared by a cache and the clones of its reader
+#[derive(Debug, Default)]
+pub struct CacheCounters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+    reads: AtomicU64,
+    bytes_read: AtomicU64,
+}
+
+impl CacheCounters {
+    /// Keys asked of the in-memory map that were in it
+    pub fn hit(&self, count: usize) {
+        self.hits.fetch_add(count as u64, Ordering::Relaxed);
+    }
+    /// Keys asked of the in-memory map that weren't
+    pub fn miss(&self, count: usize) {
+        self.misses.fetch_add(count as u64, Ordering::Relaxed);
+    }
+    /// Entries dropped from the in-memory map
+    pub fn evicted(&self, count: usize) {
+        self.evictions.fetch_add(count as u64, Ordering::Relaxed);
+    }
+    /// A read of the files on disk
+    pub fn read(&self, bytes: usize) {
+        self.reads.fetch_add(1, Ordering::Relaxed);
+        self.bytes_read.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// The counts so far, with how many entries are in memory
+    pub fn snapshot(&self, entries: usize) -> CacheCounts {
+        CacheCounts {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            reads: self.reads.load(Ordering::Relaxed),
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            entries,
+        }
+    }
+}
+
+/// The counts of a cache at some point
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CacheCounts {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub reads: u64,
+    pub bytes_read: u64,
+    pub entries: usize,
+}
+
+impl CacheCounts {
+    pub fn describe(&self) -> String {
+        format!(
+            "{} in memory, {} hits, {} misses, {} evicted, {} reads of {:.1}MB",
+            self.entries,
+            self.hits,
+            self.misses,
+            self.evictions,
+            self.reads,
+            self.bytes_read as f64 / 1_000_000.0
+        )
+    }
+}
+
+/// What building the window's views cost, counted as they're built
+#[derive(Debug)]
+struct ViewCounters {
+    views: AtomicU64,
+    micros: AtomicU64,
+    rows: AtomicU64,
+    data_reads: AtomicU64,
+}
+
+static VIEWS: ViewCounters = ViewCounters {
+    views: AtomicU64::new(0),
+    micros: AtomicU64::new(0),
+    rows: AtomicU64::new(0),
+    data_reads: AtomicU64::new(0),
+};
+
+/// A view of the whole window, that took this long and built this many song rows
+pub fn view_built(took: Duration, rows: usize) {
+    VIEWS.views.fetch_add(1, Ordering::Relaxed);
+    VIEWS
+        .micros
+        .fetch_add(took.as_micros() as u64, Ordering::Relaxed);
+    VIEWS.rows.fetch_add(rows as u64, Ordering::Relaxed);
+}
+
+/// The display data of a list's songs was read from the cache again, instead of reused
+pub fn song_data_read() {
+    VIEWS.data_reads.fetch_add(1, Ordering::Relaxed);
+}
+
+/// The views built so far
+pub fn view_counts() -> ViewCounts {
+    ViewCounts {
+        views: VIEWS.views.load(Ordering::Relaxed),
+        micros: VIEWS.micros.load(Ordering::Relaxed),
+        rows: VIEWS.rows.load(Ordering::Relaxed),
+        data_reads: VIEWS.data_reads.load(Ordering::Relaxed),
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ViewCounts {
+    pub views: u64,
+    /// Spent building them
+    pub micros: u64,
+    /// Song rows built by them
+    pub rows: u64,
+    /// Times the rows' song data was read again
+    pub data_reads: u64,
+}
+
+impl ViewCounts {
+    pub fn describe(&self) -> String {
+        let views = self.views.max(1) as f64;
+        format!(
+            "{} built, {:.2}ms and {:.0} rows each, {} song data reads",
+            self.views,
+            self.micros as f64 / views / 1000.0,
+            self.rows as f64 / views,
+            self.data_reads
+        )
+    }
+}
+
+/// The counts of each of the app's caches
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CacheStats {
+    pub song_metadata: CacheCounts,
+    pub sounds: CacheCounts,
+    /// Their index is what's read, the images are loaded by iced
+    pub thumbnails: CacheCounts,
+    /// Songs given a namespaced key this session, since their id was taken
+    pub key_collisions: usize,
+    pub views: ViewCounts,
+}
+
+impl CacheStats {
+    /// A line for each cache, and one for the views reading them
+    pub fn lines(&self) -> [String; 5] {
+        [
+            format!("songs: {}", self.song_metadata.describe()),
+            format!("sounds: {}", self.sounds.describe()),
+            format!("thumbnails: {}", self.thumbnails.describe()),
+            format!("key collisions: {}", self.key_collisions),
+            format!("views: {}", self.views.describe()),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CacheCounters, CacheCounts, ViewCounts};
+
+    #[test]
+    fn counts_add_up() {
+        let counters = CacheCounters::default();
+        counters.hit(3);
+        counters.miss(1);
+        counters.hit(2);
+        counters.evicted(4);
+        counters.read(100);
+        counters.read(50);
+        assert_eq![
+            counters.snapshot(7),
+            CacheCounts {
+                hits: 5,
+                misses: 1,
+                evictions: 4,
+                reads: 2,
+                bytes_read: 150,
+                entries: 7,
+            }
+        ];
+    }
+
+    #[test]
+    fn view_costs_are_averaged_over_the_views() {
+        let counts = ViewCounts {
+            views: 4,
+            micros: 10_000,
+            rows: 120,
+            data_reads: 1,
+        };
+        assert_eq![
+            counts.describe(),
+            "4 built, 2.50ms and 30 rows each, 1 song data reads"
+        ];
+        // Nothing built yet doesn't divide by zero
+        assert_eq![
+            ViewCounts::default().describe(),
+            "0 built, 0.00ms and 0 rows each, 0 song data reads"
+        ];
+    }
+}
@@ -0,0 +1,85 @@
+//! What a group of the playlist's tree plays, and the op it's built into. The app's groups keep
+//! this beside their widgets, and saved playlists are read into it, so both build alike.
+
+use serde::{Deserialize, Serialize};
+
+use crate::playback::{ActualRecursiveOps, RecursiveSongOp};
+
+/// How long "Loop For" groups play when their file doesn't say
+pub const DEFAULT_LOOP_SECONDS: u64 = 30 * 60;
+
+fn enabled() -> bool {
+    true
+}
+
+fn default_seconds() -> u64 {
+    DEFAULT_LOOP_SECONDS
+}
+
+/// Saved flattened into the group, next to its items
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GroupPlay {
+    pub operation: ActualRecursiveOps,
+    /// Groups that are off are left out of the built op
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+    /// How often each item is picked in "Weighted Random" groups, items past the end weigh 1
+    #[serde(default)]
+    pub weights: Vec<u32>,
+    /// Used by some operations, like LoopNTimes and Stretch
+    pub n: u32,
+    /// How long "Loop For" groups play
+    #[serde(default = "default_seconds")]
+    pub seconds: u64,
+}
+
+impl Default for GroupPlay {
+    fn default() -> Self {
+        Self::new(ActualRecursiveOps::PlayOnce)
+    }
+}
+
+impl GroupPlay {
+    pub fn new(operation: ActualRecursiveOps) -> Self {
+        Self {
+            operation,
+            enabled: true,
+            weights: vec![],
+            n: 1,
+            seconds: DEFAULT_LOOP_SECONDS,
+        }
+    }
+
+    /// How often the item at the index is picked, in "Weighted Random" groups
+    pub fn weight(&self, idx: usize) -> u32 {
+        self.weights.get(idx).copied().unwrap_or(1)
+    }
+
+    /// The group's op, from the ops of the items that play by their index. Groups that are off
+    /// are left out by the caller.
+    pub fn build(
+        &self,
+        children: impl IntoIterator<Item = (usize, RecursiveSongOp)>,
+    ) -> RecursiveSongOp {
+        let (children, weights): (Vec<RecursiveSongOp>, Vec<u32>) = children
+            .into_iter()
+            .map(|(idx, op)| (op, self.weight(idx)))
+            .unzip();
+
+        match &self.operation {
+            ActualRecursiveOps::PlayOnce => RecursiveSongOp::PlayOnce(children),
+            ActualRecursiveOps::LoopNTimes => RecursiveSongOp::LoopNTimes(children, self.n),
+            ActualRecursiveOps::Stretch => RecursiveSongOp::Stretch(children, self.n),
+            ActualRecursiveOps::InfiniteLoop => RecursiveSongOp::InfiniteLoop(children),
+            ActualRecursiveOps::RandomPlay => RecursiveSongOp::RandomPlay(children),
+            ActualRecursiveOps::SingleRandom => RecursiveSongOp::SingleRandom(children),
+            ActualRecursiveOps::InfiniteRandom => RecursiveSongOp::InfiniteRandom(children),
+            ActualRecursiveOps::WeightedRandom => {
+                RecursiveSongOp::WeightedRandom(children.into_iter().zip(weights).collect())
+            }
+            ActualRecursiveOps::LoopUntilDuration => {
+                RecursiveSongOp::LoopUntilDuration(children, self.seconds)
+            }
+        }
+    }
+}
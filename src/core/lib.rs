@@ -0,0 +1,45 @@
+//! The core of ytm-rs, without the window: the ops a playlist builds, the trackers stepping
+//! through them, the caches, the audio they're played with, the settings and the other files the
+//! app saves, and the handler of the backend. The app puts its widgets and messages on top of
+//! these.
+//!
+//! ```no_run
+//! use std::{thread::sleep, time::Duration};
+//!
+//! use async_std::task::block_on;
+//! use ytm_rs_core::{
+//!     caching::readers::FolderBasedReader, paths::song_audio_path, player::Player,
+//!     settings::YTMRSettings,
+//! };
+//!
+//! let settings: YTMRSettings = block_on(YTMRSettings::load_default()).expect("nothing saved");
+//! let user = &settings.user;
+//! let files = FolderBasedReader::new(song_audio_path());
+//! let op = user.simple.build(settings.playlist.build());
+//! let mut player = Player::with_sound(op, files).unwrap();
+//! player.set_volume(user.unless_muted(user.volume) as f64);
+//! while let Some(key) = player.current() {
+//!     println!["{key}"];
+//!     while player.is_playing() {
+//!         sleep(Duration::from_secs(1));
+//!     }
+//!     player.next_song();
+//! }
+//! ```
+
+pub mod audio;
+pub mod backend_handler;
+pub mod backend_settings;
+pub mod caching;
+pub mod group;
+pub mod paths;
+pub mod playback;
+pub mod player;
+pub mod saved;
+pub mod scheduler;
+pub mod session;
+pub mod settings;
+pub mod verbosity;
+pub mod window_state;
+
+pub type SongKey = String;
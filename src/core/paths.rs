@@ -0,0 +1,123 @@
+//! Where the app keeps its files. They can all be moved under another directory for a run,
+//! so the demo doesn't touch the user's data.
+
+use std::path::{Path, PathBuf};
+
+use directories_next::ProjectDirs;
+use once_cell::sync::OnceCell;
+use uuid::Uuid;
+
+/// Where every file goes instead of the user's directories, when set
+static ROOT_OVERRIDE: OnceCell<PathBuf> = OnceCell::new();
+
+/// Keeps every file of this run under `root`, so it can't touch the user's data.
+/// Only the first call has an effect, and it has to happen before any path is used.
+#[cfg(feature = "demo")]
+pub fn redirect_dirs(root: PathBuf) {
+    let _ = ROOT_OVERRIDE.set(root);
+}
+
+/// The settings file of this run, when it isn't the usual one
+static SETTINGS_FILE: OnceCell<PathBuf> = OnceCell::new();
+
+/// Reads and saves the settings in `path` for this run. Only the first call has an effect.
+pub fn use_settings_file(path: PathBuf) {
+    let _ = SETTINGS_FILE.set(path);
+}
+
+fn overridden(dir: &str) -> Option<PathBuf> {
+    ROOT_OVERRIDE.get().map(|root| root.join(dir))
+}
+
+pub fn project_dir() -> Option<ProjectDirs> {
+    directories_next::ProjectDirs::from("rs", "zeptofine", "ytm-rs")
+}
+
+pub fn current_dir() -> PathBuf {
+    std::env::current_dir().unwrap_or_default()
+}
+
+pub fn project_data_dir() -> PathBuf {
+    if let Some(dir) = overridden("data") {
+        return dir;
+    }
+    match project_dir() {
+        Some(project_dirs) => project_dirs.data_dir().into(),
+        None => current_dir(),
+    }
+}
+pub fn project_config_dir() -> PathBuf {
+    if let Some(dir) = overridden("config") {
+        return dir;
+    }
+    match project_dir() {
+        Some(project_dirs) => project_dirs.config_dir().into(),
+        None => current_dir(),
+    }
+}
+pub fn project_cache_dir() -> PathBuf {
+    if let Some(dir) = overridden("cache") {
+        return dir;
+    }
+    match project_dir() {
+        Some(project_dirs) => project_dirs.cache_dir().into(),
+        None => current_dir(),
+    }
+}
+
+pub fn settings_path() -> PathBuf {
+    if let Some(path) = SETTINGS_FILE.get() {
+        return path.clone();
+    }
+    let mut path = project_config_dir();
+    path.push("songlist.json");
+    path
+}
+
+pub fn song_metadata_path() -> PathBuf {
+    let mut path = project_data_dir();
+    path.push("songs.ndjson");
+    path
+}
+
+pub fn song_audio_path() -> PathBuf {
+    let mut path = project_cache_dir();
+    path.push("songs");
+    path
+}
+
+pub fn thumbnails_directory() -> PathBuf {
+    let mut path = project_cache_dir();
+    path.push("thumbs");
+    path
+}
+
+pub fn playlists_directory() -> PathBuf {
+    let mut path = project_data_dir();
+    path.push("playlists");
+    path
+}
+
+/// Held by the running instance, so a second one can find it
+pub fn instance_lock_path() -> PathBuf {
+    let mut path = project_data_dir();
+    path.push("instance.lock");
+    path
+}
+
+pub fn history_path() -> PathBuf {
+    let mut path = project_data_dir();
+    path.push("history.ndjson");
+    path
+}
+
+pub fn exports_directory() -> PathBuf {
+    let mut path = project_data_dir();
+    path.push("exports");
+    path
+}
+
+/// The file of the playlist with the id, in `dir`
+pub fn playlist_path(dir: &Path, id: Uuid) -> PathBuf {
+    dir.join(format!("{id}.json"))
+}
@@ -0,0 +1,9 @@
+mod recursive_song_op;
+mod repeat;
+mod simple_mode;
+mod song_op_tracker;
+
+pub use recursive_song_op::*;
+pub use repeat::*;
+pub use simple_mode::*;
+pub use song_op_tracker::*;
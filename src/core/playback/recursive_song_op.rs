@@ -0,0 +1,264 @@
+use serde::{Deserialize, Serialize};
+
+use crate::SongKey;
+
+// A wrapper made for recursive song operations
+pub const CONSTRUCTOR_CHOICES: [&str; 9] = [
+    "Play Once",
+    "Loop N Times",
+    "Stretch",
+    "Infinite Loop",
+    "Random Play",
+    "Single Random",
+    "Infinite Random",
+    "Weighted Random",
+    "Loop For",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ActualRecursiveOps {
+    PlayOnce,
+    LoopNTimes,
+    Stretch,
+    InfiniteLoop,
+    RandomPlay,
+    SingleRandom,
+    InfiniteRandom,
+    WeightedRandom,
+    LoopUntilDuration,
+}
+impl ActualRecursiveOps {
+    pub fn as_str(&self) -> &'static str {
+        match &self {
+            ActualRecursiveOps::PlayOnce => CONSTRUCTOR_CHOICES[0],
+            ActualRecursiveOps::LoopNTimes => CONSTRUCTOR_CHOICES[1],
+            ActualRecursiveOps::Stretch => CONSTRUCTOR_CHOICES[2],
+            ActualRecursiveOps::InfiniteLoop => CONSTRUCTOR_CHOICES[3],
+            ActualRecursiveOps::RandomPlay => CONSTRUCTOR_CHOICES[4],
+            ActualRecursiveOps::SingleRandom => CONSTRUCTOR_CHOICES[5],
+            ActualRecursiveOps::InfiniteRandom => CONSTRUCTOR_CHOICES[6],
+            ActualRecursiveOps::WeightedRandom => CONSTRUCTOR_CHOICES[7],
+            ActualRecursiveOps::LoopUntilDuration => CONSTRUCTOR_CHOICES[8],
+        }
+    }
+
+    pub fn from_choice(s: &'static str) -> Option<ActualRecursiveOps> {
+        match s {
+            "Play Once" => Some(ActualRecursiveOps::PlayOnce),
+            "Loop N Times" => Some(ActualRecursiveOps::LoopNTimes),
+            "Stretch" => Some(ActualRecursiveOps::Stretch),
+            "Infinite Loop" => Some(ActualRecursiveOps::InfiniteLoop),
+            "Random Play" => Some(ActualRecursiveOps::RandomPlay),
+            "Single Random" => Some(ActualRecursiveOps::SingleRandom),
+            "Infinite Random" => Some(ActualRecursiveOps::InfiniteRandom),
+            "Weighted Random" => Some(ActualRecursiveOps::WeightedRandom),
+            "Loop For" => Some(ActualRecursiveOps::LoopUntilDuration),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum InfLoopType {
+    Never,
+    Maybe,
+    Always,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RecursiveSongOp {
+    // Plays a song.
+    SinglePlay(SongKey),
+    // Plays a list of songs once.
+    PlayOnce(Vec<RecursiveSongOp>),
+    // Loops a list of songs entirely N times.
+    LoopNTimes(Vec<RecursiveSongOp>, u32),
+    // Loops each song individually N times.
+    Stretch(Vec<RecursiveSongOp>, u32),
+    // Loops list indefinitely.
+    InfiniteLoop(Vec<RecursiveSongOp>),
+    // Plays a random selection of songs from the provided box
+    RandomPlay(Vec<RecursiveSongOp>),
+    // plays a single random song from the provided box.
+    SingleRandom(Vec<RecursiveSongOp>),
+    // Plays random songs indefinitely until stopped.
+    InfiniteRandom(Vec<RecursiveSongOp>),
+    // Plays random songs indefinitely, picking each as often as its weight says.
+    WeightedRandom(Vec<(RecursiveSongOp, u32)>),
+    // Loops a list of songs until they've played for about N seconds.
+    LoopUntilDuration(Vec<RecursiveSongOp>, u64),
+}
+
+/// The op's children, without their weights
+impl<'a> IntoIterator for &'a RecursiveSongOp {
+    type Item = &'a RecursiveSongOp;
+    type IntoIter = Box<dyn Iterator<Item = &'a RecursiveSongOp> + 'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        match self {
+            RecursiveSongOp::SinglePlay(_) => Box::new(std::iter::empty()),
+            RecursiveSongOp::PlayOnce(ops)
+            | RecursiveSongOp::LoopNTimes(ops, _)
+            | RecursiveSongOp::Stretch(ops, _)
+            | RecursiveSongOp::InfiniteLoop(ops)
+            | RecursiveSongOp::RandomPlay(ops)
+            | RecursiveSongOp::SingleRandom(ops)
+            | RecursiveSongOp::InfiniteRandom(ops)
+            | RecursiveSongOp::LoopUntilDuration(ops, _) => Box::new(ops.iter()),
+            RecursiveSongOp::WeightedRandom(weighted) => {
+                Box::new(weighted.iter().map(|(op, _)| op))
+            }
+        }
+    }
+}
+
+impl RecursiveSongOp {
+    /// How many ops are in the tree, including this one
+    pub fn node_count(&self) -> usize {
+        1 + self.into_iter().map(Self::node_count).sum::<usize>()
+    }
+
+    /// The song at the path, the path being as a tracker of the op gives it
+    pub fn song_at(&self, path: impl IntoIterator<Item = usize>) -> Option<&SongKey> {
+        let mut op = self;
+        for idx in path {
+            op = op.into_iter().nth(idx)?;
+        }
+        match op {
+            Self::SinglePlay(key) => Some(key),
+            _ => None,
+        }
+    }
+
+    /// Every song in the tree with its path, in the order of [`Self::song_keys`]
+    pub fn song_paths(&self) -> Vec<(Vec<usize>, &SongKey)> {
+        match self {
+            Self::SinglePlay(key) => vec![(vec![], key)],
+            op => op
+                .into_iter()
+                .enumerate()
+                .flat_map(|(idx, child)| {
+                    child.song_paths().into_iter().map(move |(mut path, key)| {
+                        path.insert(0, idx);
+                        (path, key)
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    /// Every song in the tree, in order
+    pub fn song_keys(&self) -> Vec<&SongKey> {
+        match self {
+            Self::SinglePlay(key) => vec![key],
+            op => op.into_iter().flat_map(Self::song_keys).collect(),
+        }
+    }
+
+    /// A one line description that stays short however big the tree is
+    pub fn summary(&self) -> String {
+        format!("{} ops, loops {:?}", self.node_count(), self.loop_type())
+    }
+
+    /// Why the op can't be played, the first problem found in the tree
+    pub fn problem(&self) -> Option<String> {
+        match self {
+            Self::LoopNTimes(_, 0) => {
+                Some("A \"Loop N Times\" group loops 0 times, give it 1 or more".into())
+            }
+            Self::Stretch(_, 0) => {
+                Some("A \"Stretch\" group plays each song 0 times, give it 1 or more".into())
+            }
+            Self::LoopUntilDuration(_, 0) => {
+                Some("A \"Loop For\" group plays for 0 seconds, give it a length".into())
+            }
+            Self::LoopUntilDuration(ops, _) if ops.is_empty() => {
+                Some("A \"Loop For\" group has nothing to loop, add songs or groups".into())
+            }
+            Self::WeightedRandom(weighted) if weighted.iter().all(|(_, weight)| *weight == 0) => {
+                Some("A \"Weighted Random\" group has no weights, give an item 1 or more".into())
+            }
+            _ => self.into_iter().find_map(Self::problem),
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.problem().is_none()
+    }
+
+    pub fn loop_type(&self) -> InfLoopType {
+        match self {
+            Self::InfiniteLoop(_) | Self::InfiniteRandom(_) | Self::WeightedRandom(_) => {
+                InfLoopType::Always
+            }
+            // Stops once its time is up, whatever it holds
+            Self::SinglePlay(_) | Self::LoopUntilDuration(..) => InfLoopType::Never,
+            Self::PlayOnce(ops)
+            | Self::LoopNTimes(ops, _)
+            | Self::Stretch(ops, _)
+            | Self::RandomPlay(ops) => {
+                match ops.iter().find_map(|op| {
+                    let inftype = op.loop_type();
+                    match inftype != InfLoopType::Never {
+                        true => Some(inftype),
+                        false => None,
+                    }
+                }) {
+                    Some(op) => op,
+                    None => InfLoopType::Never,
+                }
+            }
+            Self::SingleRandom(ops) => {
+                match ops.iter().any(|so| so.loop_type() != InfLoopType::Never) {
+                    true => InfLoopType::Maybe,
+                    false => InfLoopType::Never,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InfLoopType, RecursiveSongOp as RSO};
+
+    #[test]
+    fn ops_that_play_nothing_are_invalid() {
+        let song = || RSO::SinglePlay("a".to_string());
+        assert![RSO::PlayOnce(vec![]).is_valid()];
+        assert![RSO::LoopNTimes(vec![song()], 1).is_valid()];
+
+        let nested = RSO::PlayOnce(vec![song(), RSO::Stretch(vec![song()], 0)]);
+        assert![!nested.is_valid()];
+        assert![nested.problem().unwrap().contains("Stretch")];
+        assert![RSO::LoopNTimes(vec![song()], 0).problem().is_some()];
+    }
+
+    #[test]
+    fn weighted_ops_need_some_weight() {
+        let song = || RSO::SinglePlay("a".to_string());
+        let weighted = RSO::WeightedRandom(vec![(song(), 0), (RSO::PlayOnce(vec![song()]), 2)]);
+        assert![weighted.is_valid()];
+        assert_eq![weighted.into_iter().count(), 2];
+        assert_eq![weighted.node_count(), 4];
+        assert_eq![weighted.loop_type(), InfLoopType::Always];
+
+        assert![!RSO::WeightedRandom(vec![(song(), 0), (song(), 0)]).is_valid()];
+        assert![!RSO::WeightedRandom(vec![]).is_valid()];
+    }
+
+    #[test]
+    fn timed_loops_end() {
+        let song = |key: &str| RSO::SinglePlay(key.to_string());
+        let timed = RSO::LoopUntilDuration(vec![song("a"), RSO::InfiniteLoop(vec![song("b")])], 60);
+        assert_eq![timed.loop_type(), InfLoopType::Never];
+        assert_eq![timed.song_at([1, 0]), Some(&"b".to_string())];
+        assert_eq![timed.song_at([1]), None];
+        assert_eq![timed.song_keys(), vec!["a", "b"]];
+
+        assert![RSO::LoopUntilDuration(vec![song("a")], 0)
+            .problem()
+            .is_some()];
+        assert![RSO::LoopUntilDuration(vec![], 60).problem().is_some()];
+    }
+}
@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+
+/// What happens when a song or the whole queue ends. Ops that loop forever never end,
+/// so these do nothing for them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Repeat {
+    #[default]
+    Off,
+    /// Starts the queue over once it ends
+    All,
+    /// Plays the same song again
+    One,
+}
+impl Repeat {
+    /// The mode the repeat button switches to
+    pub fn next(self) -> Self {
+        match self {
+            Self::Off => Self::All,
+            Self::All => Self::One,
+            Self::One => Self::Off,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Off => "repeat",
+            Self::All => "repeat all",
+            Self::One => "repeat one",
+        }
+    }
+}
@@ -0,0 +1,49 @@
+//! The toggles of simple mode, which plays the root of the tree as a flat queue
+
+use serde::{Deserialize, Serialize};
+
+use super::RecursiveSongOp;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SimpleMode {
+    pub enabled: bool,
+    pub shuffle: bool,
+    pub repeat: bool,
+}
+
+impl SimpleMode {
+    /// What fresh installs start with
+    pub fn fresh() -> Self {
+        Self {
+            enabled: true,
+            ..Default::default()
+        }
+    }
+
+    /// The queue to play from the built tree, with the toggles in place of the root's operation
+    pub fn build(&self, op: RecursiveSongOp) -> RecursiveSongOp {
+        if !self.enabled {
+            return op;
+        }
+        let children = match op {
+            RecursiveSongOp::SinglePlay(_) => return op,
+            RecursiveSongOp::PlayOnce(ops)
+            | RecursiveSongOp::LoopNTimes(ops, _)
+            | RecursiveSongOp::Stretch(ops, _)
+            | RecursiveSongOp::InfiniteLoop(ops)
+            | RecursiveSongOp::RandomPlay(ops)
+            | RecursiveSongOp::SingleRandom(ops)
+            | RecursiveSongOp::InfiniteRandom(ops)
+            | RecursiveSongOp::LoopUntilDuration(ops, _) => ops,
+            RecursiveSongOp::WeightedRandom(weighted) => {
+                weighted.into_iter().map(|(op, _)| op).collect()
+            }
+        };
+        match (self.shuffle, self.repeat) {
+            (false, false) => RecursiveSongOp::PlayOnce(children),
+            (true, false) => RecursiveSongOp::RandomPlay(children),
+            (false, true) => RecursiveSongOp::InfiniteLoop(children),
+            (true, true) => RecursiveSongOp::InfiniteRandom(children),
+        }
+    }
+}
@@ -0,0 +1,1125 @@
+use std::collections::{HashMap, VecDeque};
+
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    seq::SliceRandom,
+    thread_rng, Rng,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    verbosity::{info, trace},
+    SongKey,
+};
+
+use super::RecursiveSongOp;
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BackResult {
+    /// You cannot move back anymore.
+    Rewound,
+    /// You are still in the current tracker
+    Current,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum NextResult {
+    /// You are still in the current tracker
+    Current,
+
+    /// You are in the next tracker
+    Ended,
+}
+
+pub trait OperationTracker {
+    fn move_back(&mut self) -> BackResult;
+
+    /// Returns the current tree of indexes to get to the current song.
+    fn get_current(&self) -> Box<dyn Iterator<Item = usize>>;
+
+    /// Sets the current tree of indexes to get to the current song.
+    fn set_current(&mut self, indices: VecDeque<usize>);
+
+    fn move_next(&mut self) -> NextResult;
+
+    // Moves the tracker to its start of the sequence.
+    fn to_start(&mut self) {}
+    // Moves the tracker to the end of the sequence.
+    fn to_end(&mut self) {}
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SongOpTracker {
+    SinglePlay,
+    PlayOnce {
+        current: usize,
+        children: Vec<SongOpTracker>,
+    },
+    LoopNTimes {
+        current: usize,
+        total_loops: usize,
+        children: Vec<SongOpTracker>,
+    },
+    Stretch {
+        current: usize,
+        length: usize,
+        children: Vec<SongOpTracker>,
+    },
+    InfiniteLoop {
+        current: usize,
+        children: Vec<SongOpTracker>,
+    },
+    RandomPlay {
+        current: usize,
+        randomized_indices: Vec<usize>,
+        children: Vec<SongOpTracker>,
+    },
+    SingleRandom {
+        current: usize,
+        children: Vec<SongOpTracker>,
+    },
+    InfiniteRandom {
+        current: usize,
+        children: Vec<SongOpTracker>,
+    },
+    WeightedRandom {
+        current: usize,
+        weights: Vec<u32>,
+        children: Vec<SongOpTracker>,
+    },
+    LoopUntilDuration {
+        current: usize,
+        /// In seconds
+        budget: u64,
+        /// The seconds counted so far
+        played: f64,
+        /// The song at each path below the group, sorted by path. Trackers don't know their
+        /// songs, this is to find the length of the one playing.
+        #[serde(default)]
+        songs: Vec<(Vec<usize>, SongKey)>,
+        /// Filled in by `measure`, again whenever songs are cached
+        #[serde(default)]
+        lengths: HashMap<SongKey, f64>,
+        /// What songs that aren't in `lengths` count for
+        unknown: f64,
+        children: Vec<SongOpTracker>,
+    },
+}
+
+/// What a song counts for in a timed group when its length isn't known
+pub const DEFAULT_UNKNOWN_SONG_SECS: u64 = 4 * 60;
+
+/// A random index, each picked as often as its weight says. 0 if there's nothing to pick.
+fn pick_weighted(weights: &[u32]) -> usize {
+    match WeightedIndex::new(weights) {
+        Ok(dist) => dist.sample(&mut thread_rng()),
+        Err(_) => 0,
+    }
+}
+
+/// The song at each path below the ops, which timed groups look their lengths up by
+fn songs_below(ops: &[RecursiveSongOp]) -> Vec<(Vec<usize>, SongKey)> {
+    ops.iter()
+        .enumerate()
+        .flat_map(|(idx, op)| {
+            op.song_paths().into_iter().map(move |(mut path, key)| {
+                path.insert(0, idx);
+                (path, key.clone())
+            })
+        })
+        .collect()
+}
+
+/// How long the song the child at the index is at counts for. Every song counts for a second
+/// at least, so the loop always ends.
+fn counted_length(
+    idx: usize,
+    child: &SongOpTracker,
+    songs: &[(Vec<usize>, SongKey)],
+    lengths: &HashMap<SongKey, f64>,
+    unknown: f64,
+) -> f64 {
+    let path: Vec<usize> = std::iter::once(idx).chain(child.get_current()).collect();
+    let length = match songs.binary_search_by(|(at, _)| at.cmp(&path)) {
+        Ok(found) => lengths.get(&songs[found].1).copied().unwrap_or(unknown),
+        Err(_) => unknown,
+    };
+    length.max(1.0)
+}
+
+/// A random index below `len`, 0 if there's nothing to pick
+fn pick_random(len: usize) -> usize {
+    match len {
+        0 => 0,
+        len => thread_rng().gen_range(0..len),
+    }
+}
+
+// The children are looked up with `get`, so empty groups and stale indices don't panic
+
+fn back_in(children: &mut [SongOpTracker], idx: usize) -> BackResult {
+    match children.get_mut(idx) {
+        Some(child) => child.move_back(),
+        None => BackResult::Rewound,
+    }
+}
+
+fn next_in(children: &mut [SongOpTracker], idx: usize) -> NextResult {
+    match children.get_mut(idx) {
+        Some(child) => child.move_next(),
+        None => NextResult::Ended,
+    }
+}
+
+fn start_in(children: &mut [SongOpTracker], idx: usize) {
+    if let Some(child) = children.get_mut(idx) {
+        child.to_start();
+    }
+}
+
+fn end_in(children: &mut [SongOpTracker], idx: usize) {
+    if let Some(child) = children.get_mut(idx) {
+        child.to_end();
+    }
+}
+
+/// Moves back a group whose `current` counts its places, `index` being the child at each
+fn step_back(
+    current: &mut usize,
+    children: &mut [SongOpTracker],
+    index: impl Fn(usize) -> usize,
+) -> BackResult {
+    match back_in(children, index(*current)) {
+        BackResult::Current => BackResult::Current,
+        BackResult::Rewound if *current == 0 => BackResult::Rewound,
+        BackResult::Rewound => {
+            *current -= 1;
+            end_in(children, index(*current));
+            BackResult::Current
+        }
+    }
+}
+
+/// Moves on in a group with `positions` places, `index` being the child at each
+fn step_next(
+    current: &mut usize,
+    children: &mut [SongOpTracker],
+    positions: usize,
+    index: impl Fn(usize) -> usize,
+) -> NextResult {
+    match next_in(children, index(*current)) {
+        NextResult::Current => NextResult::Current,
+        NextResult::Ended if *current + 1 >= positions => NextResult::Ended,
+        NextResult::Ended => {
+            *current += 1;
+            start_in(children, index(*current));
+            NextResult::Current
+        }
+    }
+}
+
+impl From<&RecursiveSongOp> for SongOpTracker {
+    fn from(value: &RecursiveSongOp) -> Self {
+        match value {
+            RecursiveSongOp::SinglePlay(_) => Self::SinglePlay,
+            RecursiveSongOp::PlayOnce(ops) => Self::PlayOnce {
+                current: 0,
+                children: Self::map(ops),
+            },
+            RecursiveSongOp::LoopNTimes(ops, n) => Self::LoopNTimes {
+                current: 0,
+                total_loops: *n as usize,
+                children: Self::map(ops),
+            },
+
+            RecursiveSongOp::Stretch(ops, n) => Self::Stretch {
+                current: 0,
+                length: *n as usize,
+                children: Self::map(ops),
+            },
+            RecursiveSongOp::InfiniteLoop(ops) => Self::InfiniteLoop {
+                current: 0,
+                children: Self::map(ops),
+            },
+            RecursiveSongOp::RandomPlay(ops) => {
+                let mut randomized_indices = (0..ops.len()).collect::<Vec<_>>();
+                randomized_indices.shuffle(&mut thread_rng());
+                Self::RandomPlay {
+                    current: 0,
+                    randomized_indices,
+                    children: Self::map(ops),
+                }
+            }
+            RecursiveSongOp::SingleRandom(ops) => Self::SingleRandom {
+                current: pick_random(ops.len()),
+                children: Self::map(ops),
+            },
+            RecursiveSongOp::InfiniteRandom(ops) => Self::InfiniteRandom {
+                current: pick_random(ops.len()),
+                children: Self::map(ops),
+            },
+            RecursiveSongOp::WeightedRandom(weighted) => {
+                let weights: Vec<u32> = weighted.iter().map(|(_, weight)| *weight).collect();
+                Self::WeightedRandom {
+                    current: pick_weighted(&weights),
+                    weights,
+                    children: weighted.iter().map(|(op, _)| Self::from(op)).collect(),
+                }
+            }
+            RecursiveSongOp::LoopUntilDuration(ops, secs) => Self::LoopUntilDuration {
+                current: 0,
+                budget: *secs,
+                played: 0.0,
+                songs: songs_below(ops),
+                lengths: HashMap::new(),
+                unknown: DEFAULT_UNKNOWN_SONG_SECS as f64,
+                children: Self::map(ops),
+            },
+        }
+    }
+}
+impl OperationTracker for SongOpTracker {
+    fn move_back(&mut self) -> BackResult {
+        match self {
+            SongOpTracker::SinglePlay => BackResult::Rewound,
+            SongOpTracker::PlayOnce { current, children }
+            | SongOpTracker::InfiniteLoop { current, children } => {
+                step_back(current, children, |c| c)
+            }
+            SongOpTracker::LoopNTimes {
+                current,
+                total_loops: _,
+                children,
+            } => {
+                let len = children.len();
+                step_back(current, children, |c| c.checked_rem(len).unwrap_or(0))
+            }
+            SongOpTracker::Stretch {
+                current,
+                length,
+                children,
+            } => {
+                let length = (*length).max(1);
+                step_back(current, children, |c| c / length)
+            }
+            SongOpTracker::RandomPlay {
+                current,
+                randomized_indices,
+                children,
+            } => step_back(current, children, |c| {
+                randomized_indices.get(c).copied().unwrap_or(c)
+            }),
+            SongOpTracker::SingleRandom {
+                current: index,
+                children,
+            } => match back_in(children, *index) {
+                BackResult::Rewound => {
+                    // make a new selection
+                    *index = pick_random(children.len());
+                    end_in(children, *index);
+                    BackResult::Rewound
+                }
+                BackResult::Current => BackResult::Current,
+            },
+            SongOpTracker::LoopUntilDuration {
+                current,
+                played,
+                songs,
+                lengths,
+                unknown,
+                children,
+                ..
+            } => {
+                let len = children.len();
+                let result = step_back(current, children, |c| c.checked_rem(len).unwrap_or(0));
+                // The song that's back to playing doesn't count anymore
+                let idx = current.checked_rem(len).unwrap_or(0);
+                if let Some(child) = children.get(idx) {
+                    let length = counted_length(idx, child, songs, lengths, *unknown);
+                    *played = (*played - length).max(0.0);
+                }
+                result
+            }
+            SongOpTracker::InfiniteRandom {
+                current: index,
+                children,
+            }
+            | SongOpTracker::WeightedRandom {
+                current: index,
+                children,
+                ..
+            } => back_in(children, *index),
+        }
+    }
+
+    fn get_current(&self) -> Box<dyn Iterator<Item = usize>> {
+        let idx = self.child_index();
+        match self.children().get(idx) {
+            Some(child) => Box::new([idx].into_iter().chain(child.get_current())),
+            // Songs, and groups with nothing in them
+            None => Box::new(std::iter::empty()),
+        }
+    }
+
+    fn set_current(&mut self, mut indices: VecDeque<usize>) {
+        let idx = match self {
+            SongOpTracker::SinglePlay => {
+                trace!["indices: {:?}", indices];
+                return;
+            }
+            _ => match indices.pop_front() {
+                Some(idx) => idx,
+                None => return,
+            },
+        };
+        // `current` counts plays for some trackers, so it's not always the child's index
+        let child = match self {
+            SongOpTracker::SinglePlay => None,
+            SongOpTracker::PlayOnce {
+                ref mut current,
+                ref mut children,
+            }
+            | SongOpTracker::InfiniteLoop {
+                ref mut current,
+                ref mut children,
+            }
+            | SongOpTracker::SingleRandom {
+                ref mut current,
+                ref mut children,
+            }
+            | SongOpTracker::InfiniteRandom {
+                ref mut current,
+                ref mut children,
+            }
+            | SongOpTracker::WeightedRandom {
+                ref mut current,
+                ref mut children,
+                ..
+            } => {
+                *current = idx;
+                children.get_mut(idx)
+            }
+            SongOpTracker::LoopNTimes {
+                ref mut current,
+                total_loops: _,
+                ref mut children,
+            }
+            | SongOpTracker::LoopUntilDuration {
+                ref mut current,
+                ref mut children,
+                ..
+            } => {
+                // Stays in the same loop
+                let within = current.checked_rem(children.len()).unwrap_or(0);
+                *current = *current - within + idx;
+                children.get_mut(idx)
+            }
+            SongOpTracker::Stretch {
+                ref mut current,
+                ref mut length,
+                ref mut children,
+            } => {
+                *current = idx * *length;
+                children.get_mut(idx)
+            }
+            SongOpTracker::RandomPlay {
+                ref mut current,
+                ref mut randomized_indices,
+                ref mut children,
+            } => {
+                if let Some(position) = randomized_indices.iter().position(|i| *i == idx) {
+                    *current = position;
+                }
+                children.get_mut(idx)
+            }
+        };
+        if let Some(child) = child.filter(|_| !indices.is_empty()) {
+            child.set_current(indices);
+        }
+    }
+
+    fn move_next(&mut self) -> NextResult {
+        match self {
+            SongOpTracker::SinglePlay => NextResult::Ended,
+            SongOpTracker::PlayOnce { current, children } => {
+                let positions = children.len();
+                step_next(current, children, positions, |c| c)
+            }
+            SongOpTracker::LoopNTimes {
+                current,
+                total_loops,
+                children,
+            } => {
+                let len = children.len();
+                let positions = len * *total_loops;
+                step_next(current, children, positions, |c| {
+                    c.checked_rem(len).unwrap_or(0)
+                })
+            }
+            SongOpTracker::Stretch {
+                current,
+                length,
+                children,
+            } => {
+                let positions = children.len() * *length;
+                let length = (*length).max(1);
+                step_next(current, children, positions, |c| c / length)
+            }
+            SongOpTracker::RandomPlay {
+                current,
+                randomized_indices,
+                children,
+            } => {
+                let positions = randomized_indices.len();
+                step_next(current, children, positions, |c| {
+                    randomized_indices.get(c).copied().unwrap_or(c)
+                })
+            }
+            SongOpTracker::SingleRandom {
+                current: index,
+                children,
+            } => next_in(children, *index),
+            // Looping nothing would never end
+            SongOpTracker::InfiniteLoop { children, .. }
+            | SongOpTracker::InfiniteRandom { children, .. }
+            | SongOpTracker::WeightedRandom { children, .. }
+            | SongOpTracker::LoopUntilDuration { children, .. }
+                if children.is_empty() =>
+            {
+                NextResult::Ended
+            }
+            SongOpTracker::InfiniteLoop { current, children } => {
+                match next_in(children, *current) {
+                    NextResult::Current => NextResult::Current,
+                    NextResult::Ended => {
+                        *current = (*current + 1) % children.len();
+                        start_in(children, *current);
+                        NextResult::Current
+                    }
+                }
+            }
+            SongOpTracker::InfiniteRandom {
+                current: index,
+                children,
+            } => match next_in(children, *index) {
+                NextResult::Ended => {
+                    *index = pick_random(children.len());
+                    start_in(children, *index);
+                    NextResult::Current
+                }
+                NextResult::Current => NextResult::Current,
+            },
+            SongOpTracker::WeightedRandom {
+                current: index,
+                weights,
+                children,
+            } => match next_in(children, *index) {
+                NextResult::Ended => {
+                    *index = pick_weighted(weights);
+                    start_in(children, *index);
+                    NextResult::Current
+                }
+                NextResult::Current => NextResult::Current,
+            },
+            SongOpTracker::LoopUntilDuration {
+                current,
+                budget,
+                played,
+                songs,
+                lengths,
+                unknown,
+                children,
+            } => {
+                let len = children.len();
+                let idx = *current % len;
+                let length = counted_length(idx, &children[idx], songs, lengths, *unknown);
+                // The song that goes over the budget is still played out
+                if *played + length >= *budget as f64 {
+                    return NextResult::Ended;
+                }
+                *played += length;
+                if children[idx].move_next() == NextResult::Ended {
+                    *current += 1;
+                    children[*current % len].to_start();
+                }
+                NextResult::Current
+            }
+        }
+    }
+
+    fn to_start(&mut self) {
+        match self {
+            SongOpTracker::SinglePlay => {}
+            SongOpTracker::PlayOnce { current, children }
+            | SongOpTracker::LoopNTimes {
+                current, children, ..
+            }
+            | SongOpTracker::Stretch {
+                current, children, ..
+            }
+            | SongOpTracker::InfiniteLoop { current, children } => {
+                *current = 0;
+                start_in(children, 0);
+            }
+            SongOpTracker::LoopUntilDuration {
+                current,
+                played,
+                children,
+                ..
+            } => {
+                *current = 0;
+                *played = 0.0;
+                start_in(children, 0);
+            }
+            SongOpTracker::RandomPlay {
+                current,
+                randomized_indices,
+                children,
+            } => {
+                *current = 0;
+                randomized_indices.shuffle(&mut thread_rng()); // re-randomize the indices
+                if let Some(idx) = randomized_indices.first() {
+                    start_in(children, *idx);
+                }
+            }
+            SongOpTracker::SingleRandom {
+                current: index,
+                children,
+            }
+            | SongOpTracker::InfiniteRandom {
+                current: index,
+                children,
+            } => {
+                *index = pick_random(children.len()); // randomize the index
+                start_in(children, *index);
+            }
+            SongOpTracker::WeightedRandom {
+                current: index,
+                weights,
+                children,
+            } => {
+                *index = pick_weighted(weights);
+                start_in(children, *index);
+            }
+        }
+    }
+
+    fn to_end(&mut self) {
+        match self {
+            SongOpTracker::SinglePlay => {}
+            SongOpTracker::PlayOnce { current, children }
+            | SongOpTracker::InfiniteLoop { current, children } => {
+                *current = children.len().saturating_sub(1);
+                end_in(children, *current);
+            }
+            // Where the time runs out depends on the songs, so it's walked to
+            SongOpTracker::LoopUntilDuration { .. } => {
+                self.to_start();
+                while self.move_next() == NextResult::Current {}
+            }
+            SongOpTracker::LoopNTimes {
+                current,
+                total_loops,
+                children,
+            } => {
+                *current = (*total_loops * children.len()).saturating_sub(1);
+                let idx = current.checked_rem(children.len()).unwrap_or(0);
+                end_in(children, idx);
+            }
+            SongOpTracker::Stretch {
+                current,
+                length,
+                children,
+            } => {
+                *current = (*length * children.len()).saturating_sub(1);
+                let idx = current.checked_div(*length).unwrap_or(0);
+                end_in(children, idx);
+            }
+            SongOpTracker::RandomPlay {
+                current,
+                randomized_indices,
+                children,
+            } => {
+                *current = randomized_indices.len().saturating_sub(1);
+                if let Some(idx) = randomized_indices.get(*current) {
+                    end_in(children, *idx);
+                }
+            }
+            SongOpTracker::SingleRandom {
+                current: index,
+                children,
+            }
+            | SongOpTracker::InfiniteRandom {
+                current: index,
+                children,
+            } => end_in(children, *index),
+            // Stepping back into the group picks again
+            SongOpTracker::WeightedRandom {
+                current: index,
+                weights,
+                children,
+            } => {
+                *index = pick_weighted(weights);
+                end_in(children, *index);
+            }
+        }
+    }
+}
+impl SongOpTracker {
+    fn map<C: FromIterator<SongOpTracker>>(ops: &[RecursiveSongOp]) -> C {
+        ops.iter().map(Self::from).collect::<C>()
+    }
+
+    pub fn from_song_op(song_op: &RecursiveSongOp, indices: VecDeque<usize>) -> Self {
+        let mut s = Self::from(song_op);
+        s.set_current(indices);
+        s
+    }
+
+    /// Whether the tracker could have been made from the op, so it can carry on walking it
+    pub fn fits(&self, song_op: &RecursiveSongOp) -> bool {
+        type Rso = RecursiveSongOp;
+        // `positions` is how many places `current` can be at, none for empty groups
+        let all_fit = |children: &[SongOpTracker], current: usize, positions| {
+            children.len() == song_op.into_iter().count()
+                && (positions == 0 || current < positions)
+                && children
+                    .iter()
+                    .zip(song_op)
+                    .all(|(child, op)| child.fits(op))
+        };
+        match (self, song_op) {
+            (Self::SinglePlay, Rso::SinglePlay(_)) => true,
+            (Self::PlayOnce { current, children }, Rso::PlayOnce(ops))
+            | (Self::InfiniteLoop { current, children }, Rso::InfiniteLoop(ops))
+            | (Self::SingleRandom { current, children }, Rso::SingleRandom(ops))
+            | (Self::InfiniteRandom { current, children }, Rso::InfiniteRandom(ops)) => {
+                all_fit(children, *current, ops.len())
+            }
+            (
+                Self::LoopNTimes {
+                    current,
+                    total_loops,
+                    children,
+                },
+                Rso::LoopNTimes(ops, n),
+            ) => {
+                let positions = ops.len() * *total_loops;
+                *total_loops == *n as usize && all_fit(children, *current, positions)
+            }
+            (
+                Self::Stretch {
+                    current,
+                    length,
+                    children,
+                },
+                Rso::Stretch(ops, n),
+            ) => {
+                let positions = ops.len() * *length;
+                *length == *n as usize && all_fit(children, *current, positions)
+            }
+            (
+                Self::RandomPlay {
+                    current,
+                    randomized_indices,
+                    children,
+                },
+                Rso::RandomPlay(ops),
+            ) => {
+                let mut sorted = randomized_indices.clone();
+                sorted.sort_unstable();
+                sorted.into_iter().eq(0..ops.len()) && all_fit(children, *current, ops.len())
+            }
+            (
+                Self::WeightedRandom {
+                    current,
+                    weights,
+                    children,
+                },
+                Rso::WeightedRandom(weighted),
+            ) => {
+                weights.iter().eq(weighted.iter().map(|(_, weight)| weight))
+                    && all_fit(children, *current, weighted.len())
+            }
+            // `current` counts plays, there's no telling how many there'll be
+            (
+                Self::LoopUntilDuration {
+                    current,
+                    budget,
+                    children,
+                    ..
+                },
+                Rso::LoopUntilDuration(_, secs),
+            ) => budget == secs && all_fit(children, *current, 0),
+            _ => false,
+        }
+    }
+
+    /// Looks up the lengths of the songs in timed groups, songs without one count for
+    /// `unknown` seconds. Run again once more songs are cached.
+    pub fn measure(&mut self, length: &impl Fn(&SongKey) -> Option<f64>, unknown: f64) {
+        if let Self::LoopUntilDuration {
+            songs,
+            lengths,
+            unknown: fallback,
+            ..
+        } = self
+        {
+            *lengths = songs
+                .iter()
+                .filter_map(|(_, key)| Some((key.clone(), length(key)?)))
+                .collect();
+            *fallback = unknown;
+        }
+        for child in self.children_mut() {
+            child.measure(length, unknown);
+        }
+    }
+
+    /// The index of the child that's playing
+    fn child_index(&self) -> usize {
+        match self {
+            Self::SinglePlay => 0,
+            Self::PlayOnce { current, .. }
+            | Self::InfiniteLoop { current, .. }
+            | Self::SingleRandom { current, .. }
+            | Self::InfiniteRandom { current, .. }
+            | Self::WeightedRandom { current, .. } => *current,
+            Self::LoopNTimes {
+                current, children, ..
+            }
+            | Self::LoopUntilDuration {
+                current, children, ..
+            } => current.checked_rem(children.len()).unwrap_or(0),
+            Self::Stretch {
+                current, length, ..
+            } => *current / (*length).max(1),
+            Self::RandomPlay {
+                current,
+                randomized_indices,
+                ..
+            } => randomized_indices.get(*current).copied().unwrap_or(0),
+        }
+    }
+
+    fn children(&self) -> &[SongOpTracker] {
+        match self {
+            Self::SinglePlay => &[],
+            Self::PlayOnce { children, .. }
+            | Self::LoopNTimes { children, .. }
+            | Self::Stretch { children, .. }
+            | Self::InfiniteLoop { children, .. }
+            | Self::RandomPlay { children, .. }
+            | Self::SingleRandom { children, .. }
+            | Self::InfiniteRandom { children, .. }
+            | Self::WeightedRandom { children, .. }
+            | Self::LoopUntilDuration { children, .. } => children,
+        }
+    }
+
+    fn children_mut(&mut self) -> &mut [SongOpTracker] {
+        match self {
+            Self::SinglePlay => &mut [],
+            Self::PlayOnce { children, .. }
+            | Self::LoopNTimes { children, .. }
+            | Self::Stretch { children, .. }
+            | Self::InfiniteLoop { children, .. }
+            | Self::RandomPlay { children, .. }
+            | Self::SingleRandom { children, .. }
+            | Self::InfiniteRandom { children, .. }
+            | Self::WeightedRandom { children, .. }
+            | Self::LoopUntilDuration { children, .. } => children,
+        }
+    }
+
+    /// The tracker to start playing from the path, None if the op can't be played
+    pub fn start(song_op: &RecursiveSongOp, indices: VecDeque<usize>) -> Option<Self> {
+        info!["Starting at {:?}: {}", indices, song_op.summary()];
+        trace!["{:#?}", song_op];
+        match song_op.is_valid() {
+            true => Some(Self::from_song_op(song_op, indices)),
+            false => {
+                info!["Can't play an invalid op"];
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::{
+        playback::{
+            BackResult, NextResult, OperationTracker, RecursiveSongOp as RSO, SongOpTracker,
+        },
+        verbosity::formatted_by,
+    };
+
+    #[test]
+    pub fn tester() {
+        let ops: RSO = RSO::PlayOnce(vec![
+            RSO::SinglePlay("".to_string()),
+            RSO::PlayOnce(vec![
+                RSO::SinglePlay("".to_string()),
+                RSO::RandomPlay(vec![
+                    RSO::SinglePlay("".to_string()),
+                    RSO::SinglePlay("".to_string()),
+                ]),
+            ]),
+        ]);
+
+        let mut tracker = SongOpTracker::from(&ops);
+
+        println!["{:?}", tracker];
+        println!["{:?}", tracker.get_current().collect::<Vec<_>>()];
+
+        let limit = 100;
+        let mut n = 0;
+        while NextResult::Current == tracker.move_next() && n < limit {
+            // println!["{:?}", tracker];
+            println!["{:?}", tracker.get_current().collect::<Vec<_>>()];
+            n += 1;
+        }
+        println!["Finished going forwards"];
+
+        while BackResult::Current == tracker.move_back() {
+            println!["{:?}", tracker.get_current().collect::<Vec<_>>()];
+        }
+    }
+
+    #[test]
+    fn setting_the_current_song_follows_the_path() {
+        let songs = |n: usize| (0..n).map(|i| RSO::SinglePlay(i.to_string())).collect();
+        let ops = RSO::PlayOnce(vec![
+            RSO::Stretch(songs(3), 2),
+            RSO::RandomPlay(songs(4)),
+            RSO::LoopNTimes(songs(2), 3),
+        ]);
+        let mut tracker = SongOpTracker::from(&ops);
+        for path in [[0, 2], [1, 3], [1, 0], [2, 1], [0, 1]] {
+            tracker.set_current(VecDeque::from(path));
+            assert_eq![tracker.get_current().collect::<Vec<_>>(), path];
+        }
+
+        // Stretched songs start over, and loops stay in the same loop
+        tracker.set_current(VecDeque::from([0, 1]));
+        tracker.move_next();
+        assert_eq![tracker.get_current().collect::<Vec<_>>(), vec![0, 1]];
+        tracker.set_current(VecDeque::from([2, 1]));
+        tracker.move_next();
+        assert_eq![tracker.get_current().collect::<Vec<_>>(), vec![2, 0]];
+    }
+
+    /// A tree that ends, up to `depth` groups deep. Random groups are only in it if `random`,
+    /// so that walking back can be checked against walking forward.
+    fn finite_tree(rng: &mut StdRng, depth: usize, random: bool) -> RSO {
+        if depth == 0 || rng.gen_range(0..4) == 0 {
+            return RSO::SinglePlay(rng.gen_range(0..10_u8).to_string());
+        }
+        let width = rng.gen_range(1..=3);
+        let children = (0..width)
+            .map(|_| finite_tree(rng, depth - 1, random))
+            .collect();
+        let kinds = match random {
+            true => 6,
+            false => 4,
+        };
+        match rng.gen_range(0..kinds) {
+            0 => RSO::PlayOnce(children),
+            1 => RSO::LoopNTimes(children, rng.gen_range(1..=3)),
+            2 => RSO::Stretch(children, rng.gen_range(1..=3)),
+            3 => RSO::LoopUntilDuration(children, rng.gen_range(1..=20_u64) * 60),
+            4 => RSO::RandomPlay(children),
+            _ => RSO::SingleRandom(children),
+        }
+    }
+
+    #[test]
+    fn walks_stay_on_songs() {
+        let path = |tracker: &SongOpTracker| tracker.get_current().collect::<Vec<_>>();
+        let mut rng = StdRng::seed_from_u64(4550);
+        for case in 0..300 {
+            let random = case % 2 == 1;
+            let op = finite_tree(&mut rng, 3, random);
+            let on_song = |path: &[usize]| op.song_at(path.iter().copied()).is_some();
+
+            let mut tracker = SongOpTracker::from(&op);
+            let mut forward = vec![path(&tracker)];
+            while tracker.move_next() == NextResult::Current {
+                forward.push(path(&tracker));
+                assert![forward.len() < 100_000, "{op:?} doesn't end"];
+            }
+            let mut back = vec![path(&tracker)];
+            while tracker.move_back() == BackResult::Current {
+                back.push(path(&tracker));
+            }
+            for path in forward.iter().chain(&back) {
+                assert![on_song(path), "{path:?} isn't a song of {op:?}"];
+            }
+            if !random {
+                back.reverse();
+                assert_eq![forward, back, "{op:?}"];
+            }
+
+            tracker.to_end();
+            assert![on_song(&path(&tracker)), "{op:?}"];
+            tracker.to_start();
+            assert![on_song(&path(&tracker)), "{op:?}"];
+        }
+    }
+
+    #[test]
+    fn the_end_is_the_last_song_played() {
+        let path = |tracker: &SongOpTracker| tracker.get_current().collect::<Vec<_>>();
+        let song = |key: &str| RSO::SinglePlay(key.to_string());
+        let ops = RSO::PlayOnce(vec![
+            song("a"),
+            RSO::LoopNTimes(vec![song("b"), song("c")], 2),
+            RSO::Stretch(vec![song("d"), song("e")], 2),
+        ]);
+        let mut walked = SongOpTracker::from(&ops);
+        while walked.move_next() == NextResult::Current {}
+
+        let mut tracker = SongOpTracker::from(&ops);
+        tracker.to_end();
+        assert_eq![path(&tracker), vec![2, 1]];
+        assert_eq![path(&tracker), path(&walked)];
+
+        // Going back from there goes through the songs played before it
+        let mut back = vec![];
+        while tracker.move_back() == BackResult::Current {
+            back.push(path(&tracker));
+        }
+        let played = [[2, 1], [2, 0], [2, 0], [1, 1], [1, 0], [1, 1], [1, 0]].map(Vec::from);
+        let played: Vec<Vec<usize>> = played.into_iter().chain([vec![0]]).collect();
+        assert_eq![back, played];
+    }
+
+    #[test]
+    fn empty_groups_and_zero_counts_dont_panic() {
+        let song = || RSO::SinglePlay("a".to_string());
+        let ops = [
+            RSO::PlayOnce(vec![]),
+            RSO::PlayOnce(vec![
+                RSO::RandomPlay(vec![]),
+                song(),
+                RSO::SingleRandom(vec![]),
+            ]),
+            RSO::Stretch(vec![song()], 0),
+            RSO::LoopNTimes(vec![song()], 0),
+            RSO::LoopNTimes(vec![], 2),
+            RSO::InfiniteLoop(vec![]),
+            RSO::InfiniteRandom(vec![]),
+            RSO::WeightedRandom(vec![]),
+            RSO::LoopUntilDuration(vec![RSO::PlayOnce(vec![])], 60),
+        ];
+        for op in &ops {
+            let mut tracker = SongOpTracker::from(op);
+            for _ in 0..3 {
+                tracker.move_next();
+            }
+            tracker.to_end();
+            for _ in 0..3 {
+                tracker.move_back();
+            }
+            tracker.to_start();
+            tracker.get_current().for_each(drop);
+        }
+    }
+
+    #[test]
+    fn weighted_picks_follow_the_weights() {
+        let song = |key: &str| RSO::SinglePlay(key.to_string());
+        let ops = RSO::WeightedRandom(vec![(song("a"), 1), (song("b"), 3), (song("c"), 0)]);
+        let mut tracker = SongOpTracker::from(&ops);
+        let mut counts = [0; 3];
+        for _ in 0..4000 {
+            assert_eq![tracker.move_next(), NextResult::Current];
+            counts[tracker.get_current().next().unwrap()] += 1;
+        }
+        // 1000 and 3000 are expected, these are many deviations away
+        assert![(800..1200).contains(&counts[0]), "{counts:?}"];
+        assert![(2800..3200).contains(&counts[1]), "{counts:?}"];
+        assert_eq![counts[2], 0];
+    }
+
+    #[test]
+    fn weighted_groups_of_one_can_be_walked() {
+        let ops = RSO::PlayOnce(vec![
+            RSO::SinglePlay("a".to_string()),
+            RSO::WeightedRandom(vec![(
+                RSO::WeightedRandom(vec![(RSO::SinglePlay("b".to_string()), 1)]),
+                1,
+            )]),
+        ]);
+        let mut tracker = SongOpTracker::start(&ops, VecDeque::from([0])).unwrap();
+        for _ in 0..10 {
+            assert_eq![tracker.move_next(), NextResult::Current];
+            assert_eq![tracker.get_current().collect::<Vec<_>>(), vec![1, 0, 0]];
+        }
+        assert_eq![tracker.move_back(), BackResult::Current];
+        assert_eq![tracker.get_current().collect::<Vec<_>>(), vec![0]];
+        tracker.to_end();
+        tracker.to_start();
+        assert![tracker.fits(&ops)];
+    }
+
+    #[test]
+    fn timed_loops_stop_once_the_time_is_up() {
+        let song = |key: &str| RSO::SinglePlay(key.to_string());
+        let ops = RSO::LoopUntilDuration(vec![song("a"), song("b")], 5 * 60);
+        let mut tracker = SongOpTracker::from(&ops);
+        tracker.measure(&|key| (key == "a").then_some(60.0), 180.0);
+
+        // 60 + 180 + 60 reaches 5 minutes on the third song
+        let mut paths = vec![tracker.get_current().collect::<Vec<_>>()];
+        while tracker.move_next() == NextResult::Current {
+            paths.push(tracker.get_current().collect());
+        }
+        assert_eq![paths, vec![vec![0], vec![1], vec![0]]];
+
+        // Going back takes the song back off the count
+        tracker.to_start();
+        tracker.move_next();
+        assert_eq![tracker.move_back(), BackResult::Current];
+        for _ in 0..2 {
+            assert_eq![tracker.move_next(), NextResult::Current];
+        }
+        assert_eq![tracker.move_next(), NextResult::Ended];
+        assert![tracker.fits(&ops)];
+
+        // Once b is cached it counts for its own length, 60 + 120 + 60 + 120
+        tracker.to_start();
+        tracker.measure(&|key| Some(if key == "a" { 60.0 } else { 120.0 }), 180.0);
+        let mut played = 1;
+        while tracker.move_next() == NextResult::Current {
+            played += 1;
+        }
+        assert_eq![played, 4];
+    }
+
+    #[test]
+    fn starting_playback_prints_little_at_default_verbosity() {
+        let big = RSO::PlayOnce(
+            (0..200)
+                .map(|g| {
+                    let songs = (0..50).map(|i| RSO::SinglePlay(format!("song-{g}-{i}")));
+                    RSO::LoopNTimes(songs.collect(), 2)
+                })
+                .collect(),
+        );
+        assert_eq![big.node_count(), 1 + 200 * 51];
+
+        let (tracker, formatted) =
+            formatted_by(|| SongOpTracker::start(&big, VecDeque::from([120, 30])));
+        assert_eq![
+            tracker.unwrap().get_current().collect::<Vec<_>>(),
+            vec![120, 30]
+        ];
+        assert![formatted < 128, "formatted {formatted} bytes"];
+    }
+}
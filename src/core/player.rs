@@ -0,0 +1,209 @@
+//! Steps through a playlist's op the way the tracker bar's buttons do, for scripts and tests.
+//! Players made with [`Player::with_sound`] play the songs they step to from the downloaded
+//! files, the others only pick them.
+
+use std::collections::VecDeque;
+
+use kira::sound::PlaybackState;
+
+use crate::{
+    audio::{YTMRSAudioManager, DEFAULT_STOP_FADE},
+    caching::{readers::FolderBasedReader, SoundData},
+    playback::{BackResult, NextResult, OperationTracker, RecursiveSongOp, SongOpTracker},
+    SongKey,
+};
+
+/// Plays the player's songs from where they were downloaded to
+#[derive(Debug)]
+struct Sound {
+    manager: YTMRSAudioManager,
+    files: FolderBasedReader,
+    volume: f64,
+}
+
+impl Sound {
+    /// Streams the song from its file. Songs that weren't downloaded leave nothing playing.
+    fn play(&mut self, key: Option<&SongKey>, paused: bool) {
+        let key = match key {
+            Some(key) => key,
+            None => return self.manager.stop(DEFAULT_STOP_FADE),
+        };
+        let path = match async_std::task::block_on(self.files.locate(key)) {
+            Ok(Some(path)) => path,
+            Ok(None) => {
+                println!["{key} wasn't downloaded"];
+                return self.manager.stop(DEFAULT_STOP_FADE);
+            }
+            Err(e) => {
+                println!["Can't find {key}: {e}"];
+                return self.manager.stop(DEFAULT_STOP_FADE);
+            }
+        };
+        match SoundData::try_from((key.clone(), path)) {
+            Ok(sound) => {
+                self.manager.play_once(sound, 1.0, DEFAULT_STOP_FADE);
+                self.manager.set_volume(self.volume);
+                if paused {
+                    self.manager.pause();
+                }
+            }
+            Err(e) => {
+                println!["{e}"];
+                self.manager.stop(DEFAULT_STOP_FADE);
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Player {
+    op: RecursiveSongOp,
+    tracker: SongOpTracker,
+    /// Whether the op ran out of songs. The tracker stays at the last one.
+    ended: bool,
+    paused: bool,
+    /// None for players that only pick songs
+    sound: Option<Sound>,
+}
+
+impl Player {
+    /// Starts at the op's first song. None if the op can't be played.
+    pub fn new(op: RecursiveSongOp) -> Option<Self> {
+        let tracker = SongOpTracker::start(&op, VecDeque::new())?;
+        Some(Self {
+            op,
+            tracker,
+            ended: false,
+            paused: false,
+            sound: None,
+        })
+    }
+
+    /// Plays the songs too, from the files they were downloaded to. Fails if the op can't be
+    /// played or there's no output device.
+    pub fn with_sound(op: RecursiveSongOp, files: FolderBasedReader) -> Result<Self, String> {
+        let mut player = match Self::new(op) {
+            Some(player) => player,
+            None => return Err("There's nothing to play".to_string()),
+        };
+        player.sound = Some(Sound {
+            manager: YTMRSAudioManager::new()?,
+            files,
+            volume: 1.0,
+        });
+        player.sound();
+        Ok(player)
+    }
+
+    /// Plays the current song, when the player has sound
+    fn sound(&mut self) {
+        let key = self.current().cloned();
+        if let Some(sound) = &mut self.sound {
+            sound.play(key.as_ref(), self.paused);
+        }
+    }
+
+    /// Whether a song is still heard, so scripts know when to move on. Always false for
+    /// players without sound.
+    pub fn is_playing(&self) -> bool {
+        match &self.sound {
+            Some(sound) => sound.manager.playback_state() == PlaybackState::Playing,
+            None => false,
+        }
+    }
+
+    /// The amplitude songs are played at, 1 by default
+    pub fn set_volume(&mut self, volume: f64) {
+        if let Some(sound) = &mut self.sound {
+            sound.volume = volume;
+            sound.manager.set_volume(volume);
+        }
+    }
+
+    /// None once the op ended
+    pub fn current(&self) -> Option<&SongKey> {
+        match self.ended {
+            true => None,
+            false => self.op.song_at(self.tracker.get_current()),
+        }
+    }
+
+    /// Moves to the next song, None once there are none left
+    pub fn next_song(&mut self) -> Option<&SongKey> {
+        if !self.ended {
+            let mut tracker = self.tracker.clone();
+            match tracker.move_next() {
+                NextResult::Current => self.tracker = tracker,
+                NextResult::Ended => self.ended = true,
+            }
+        }
+        self.sound();
+        self.current()
+    }
+
+    /// Moves to the song before. The first song stays, and after the end it's the last song.
+    pub fn previous_song(&mut self) -> Option<&SongKey> {
+        match self.ended {
+            true => self.ended = false,
+            false => {
+                let mut tracker = self.tracker.clone();
+                if tracker.move_back() == BackResult::Current {
+                    self.tracker = tracker;
+                }
+            }
+        }
+        self.sound();
+        self.current()
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+        if let Some(sound) = &mut self.sound {
+            sound.manager.pause();
+        }
+    }
+
+    pub fn play(&mut self) {
+        self.paused = false;
+        if let Some(sound) = &mut self.sound {
+            sound.manager.play();
+        }
+    }
+
+    pub fn toggle_pause(&mut self) {
+        match self.paused {
+            true => self.play(),
+            false => self.pause(),
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Player;
+    use crate::playback::RecursiveSongOp as RSO;
+
+    #[test]
+    fn songs_are_stepped_through_in_order() {
+        let songs = ["a", "b", "c"].map(|key| RSO::SinglePlay(key.to_string()));
+        let mut player = Player::new(RSO::PlayOnce(songs.to_vec())).unwrap();
+        let key = |key: Option<&String>| key.cloned();
+        assert_eq![key(player.current()), Some("a".into())];
+        assert_eq![key(player.previous_song()), Some("a".into())];
+        assert_eq![key(player.next_song()), Some("b".into())];
+        assert_eq![key(player.next_song()), Some("c".into())];
+        assert_eq![key(player.next_song()), None];
+        assert_eq![key(player.next_song()), None];
+        assert_eq![key(player.previous_song()), Some("c".into())];
+        assert_eq![key(player.previous_song()), Some("b".into())];
+
+        player.pause();
+        player.toggle_pause();
+        assert![!player.is_paused()];
+        assert![Player::new(RSO::LoopNTimes(vec![], 0)).is_none()];
+    }
+}
@@ -0,0 +1,73 @@
+//! Reads the playlists the app saved, without the window. Only what's needed to play them is
+//! read, the rest is left to the app.
+
+use std::path::Path;
+
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    group::GroupPlay,
+    paths::{playlist_path, playlists_directory},
+    playback::RecursiveSongOp,
+    SongKey,
+};
+
+#[derive(Debug, Clone)]
+pub enum LoadError {
+    File,
+    Format,
+}
+
+/// A group of the playlist's tree, as the app saves it
+#[derive(Debug, Clone, Deserialize)]
+pub struct SavedTree {
+    #[serde(flatten)]
+    pub play: GroupPlay,
+    pub list: Vec<SavedItem>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub enum SavedItem {
+    /// The app skips the song's widget id in the same place, so both are read alike
+    Song(SongKey, #[serde(skip)] ()),
+    Operation(SavedTree),
+}
+
+impl SavedTree {
+    /// The op the app would play from the tree
+    pub fn build(&self) -> RecursiveSongOp {
+        self.play
+            .build(self.list.iter().enumerate().filter_map(|(idx, item)| {
+                let op = match item {
+                    SavedItem::Song(key, _) => RecursiveSongOp::SinglePlay(key.clone()),
+                    SavedItem::Operation(op) if op.play.enabled => op.build(),
+                    SavedItem::Operation(_) => return None,
+                };
+                Some((idx, op))
+            }))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SavedPlaylist {
+    pub id: Uuid,
+    pub name: String,
+    pub constructor: SavedTree,
+}
+
+impl SavedPlaylist {
+    pub fn read(path: &Path) -> Result<Self, LoadError> {
+        let json = std::fs::read_to_string(path).map_err(|_| LoadError::File)?;
+        serde_json::from_str(&json).map_err(|_| LoadError::Format)
+    }
+
+    /// A playlist from the playlists directory
+    pub fn load(id: Uuid) -> Result<Self, LoadError> {
+        Self::read(&playlist_path(&playlists_directory(), id))
+    }
+
+    pub fn build(&self) -> RecursiveSongOp {
+        self.constructor.build()
+    }
+}
@@ -0,0 +1,401 @@
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Datelike, Days, Local, LocalResult, TimeDelta, TimeZone, Weekday};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// How late a schedule may still fire, e.g. after the computer was suspended
+pub const GRACE_MINUTES: i64 = 15;
+
+/// A playlist to start at a given time of day, like an alarm.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleEntry {
+    pub enabled: bool,
+    pub hour: u32,
+    pub minute: u32,
+    /// Bit 0 is Monday, bit 6 is Sunday
+    pub days: u8,
+    pub playlist: Uuid,
+    /// The volume to ramp up to
+    pub volume: f32,
+    pub ramp_secs: u64,
+    /// Whether to interrupt a song that is already playing
+    pub interrupt: bool,
+}
+
+impl ScheduleEntry {
+    /// Weekday mornings at 7, fading in over half a minute
+    pub fn new(playlist: Uuid, volume: f32) -> Self {
+        Self {
+            enabled: true,
+            hour: 7,
+            minute: 0,
+            days: 0b001_1111,
+            playlist,
+            volume,
+            ramp_secs: 30,
+            interrupt: false,
+        }
+    }
+
+    pub fn runs_on(&self, day: Weekday) -> bool {
+        self.days & (1 << day.num_days_from_monday()) != 0
+    }
+
+    /// The first time this entry is due strictly after `after`
+    pub fn next_due<Tz: TimeZone>(&self, after: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let tz = after.timezone();
+        let today = after.date_naive();
+
+        // a week and a day covers every weekday, even if today's time already passed
+        (0..=7).find_map(|offset| {
+            let date = today.checked_add_days(Days::new(offset))?;
+            if !self.runs_on(date.weekday()) {
+                return None;
+            }
+            let naive = date.and_hms_opt(self.hour, self.minute, 0)?;
+            let due = match tz.from_local_datetime(&naive) {
+                LocalResult::Single(t) => t,
+                // the clocks went back, so the time happens twice
+                LocalResult::Ambiguous(earliest, _) => earliest,
+                // the clocks skipped over it, so fire once they've jumped
+                LocalResult::None => tz
+                    .from_local_datetime(&(naive + TimeDelta::hours(1)))
+                    .earliest()?,
+            };
+            (due > *after).then_some(due)
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Schedule {
+    pub entries: Vec<ScheduleEntry>,
+}
+
+impl Schedule {
+    /// Finds the entry that became due after `last_check`, up to `now`.
+    /// Overlapping entries pick the earliest, and entries missed by more than
+    /// the grace period are dropped.
+    pub fn due<Tz: TimeZone>(
+        &self,
+        last_check: &DateTime<Tz>,
+        now: &DateTime<Tz>,
+    ) -> Option<(&ScheduleEntry, DateTime<Tz>)> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.enabled)
+            .filter_map(|entry| entry.next_due(last_check).map(|due| (entry, due)))
+            .filter(|(_, due)| {
+                due <= now && now.clone() - due.clone() <= TimeDelta::minutes(GRACE_MINUTES)
+            })
+            .min_by_key(|(_, due)| due.clone())
+    }
+}
+
+/// Where the time is read from, so schedules can be checked without waiting on the clock
+pub trait Clock {
+    type Tz: TimeZone;
+    fn now(&self) -> DateTime<Self::Tz>;
+}
+
+/// The computer's clock, in its time zone
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LocalClock;
+impl Clock for LocalClock {
+    type Tz = Local;
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+}
+
+/// Checks a schedule now and then, firing each entry once between checks
+#[derive(Debug)]
+pub struct ScheduleWatch<Tz: TimeZone> {
+    last_check: Option<DateTime<Tz>>,
+}
+impl<Tz: TimeZone> Default for ScheduleWatch<Tz> {
+    fn default() -> Self {
+        Self { last_check: None }
+    }
+}
+
+impl<Tz: TimeZone> ScheduleWatch<Tz> {
+    /// The entry that became due since the last check. The first check only starts watching.
+    pub fn check<'a>(
+        &mut self,
+        schedule: &'a Schedule,
+        clock: &impl Clock<Tz = Tz>,
+    ) -> Option<(&'a ScheduleEntry, DateTime<Tz>)> {
+        let now = clock.now();
+        let last_check = self.last_check.replace(now.clone()).unwrap_or(now.clone());
+        schedule.due(&last_check, &now)
+    }
+}
+
+/// Raises the volume from silence once playback starts.
+#[derive(Debug, Clone)]
+pub struct VolumeRamp {
+    target: f32,
+    duration: Duration,
+    started: Option<Instant>,
+}
+
+impl VolumeRamp {
+    pub fn new(target: f32, duration: Duration) -> Self {
+        Self {
+            target,
+            duration,
+            started: None,
+        }
+    }
+
+    /// Starts the ramp if it hasn't started yet
+    pub fn start(&mut self, now: Instant) {
+        self.started.get_or_insert(now);
+    }
+
+    pub fn volume(&self, now: Instant) -> f32 {
+        match self.started {
+            None => 0.0,
+            Some(_) if self.duration.is_zero() => self.target,
+            Some(started) => {
+                let progress = now.saturating_duration_since(started).as_secs_f32()
+                    / self.duration.as_secs_f32();
+                self.target * progress.min(1.0)
+            }
+        }
+    }
+
+    pub fn finished(&self, now: Instant) -> bool {
+        self.started
+            .is_some_and(|started| now.saturating_duration_since(started) >= self.duration)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::Cell,
+        time::{Duration, Instant},
+    };
+
+    use chrono::{
+        DateTime, FixedOffset, LocalResult, NaiveDate, NaiveDateTime, TimeDelta, TimeZone, Utc,
+        Weekday,
+    };
+    use uuid::Uuid;
+
+    use super::{Clock, Schedule, ScheduleEntry, ScheduleWatch, VolumeRamp};
+
+    /// Berlin in 2024, when the clocks went forward on March 31st and back on October 27th
+    #[derive(Debug, Clone, Copy)]
+    struct Berlin;
+    impl TimeZone for Berlin {
+        type Offset = FixedOffset;
+
+        fn from_offset(_: &FixedOffset) -> Self {
+            Berlin
+        }
+
+        fn offset_from_local_date(&self, local: &NaiveDate) -> LocalResult<FixedOffset> {
+            self.offset_from_local_datetime(&local.and_hms_opt(12, 0, 0).unwrap())
+        }
+
+        fn offset_from_local_datetime(&self, local: &NaiveDateTime) -> LocalResult<FixedOffset> {
+            // Summer time first, as it's the earlier of two readings of the same time
+            let offsets: Vec<FixedOffset> = [2, 1]
+                .into_iter()
+                .map(|hours| FixedOffset::east_opt(hours * 3600).unwrap())
+                .filter(|offset| {
+                    let utc = *local - TimeDelta::seconds(offset.local_minus_utc() as i64);
+                    self.offset_from_utc_datetime(&utc) == *offset
+                })
+                .collect();
+            match offsets[..] {
+                [] => LocalResult::None,
+                [offset] => LocalResult::Single(offset),
+                [summer, winter, ..] => LocalResult::Ambiguous(summer, winter),
+            }
+        }
+
+        fn offset_from_utc_date(&self, utc: &NaiveDate) -> FixedOffset {
+            self.offset_from_utc_datetime(&utc.and_hms_opt(0, 0, 0).unwrap())
+        }
+
+        fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> FixedOffset {
+            let change = |month, day| {
+                NaiveDate::from_ymd_opt(2024, month, day)
+                    .unwrap()
+                    .and_hms_opt(1, 0, 0)
+                    .unwrap()
+            };
+            let summer = (change(3, 31)..change(10, 27)).contains(utc);
+            FixedOffset::east_opt(if summer { 2 } else { 1 } * 3600).unwrap()
+        }
+    }
+
+    /// A clock that only moves when it's told to
+    struct Mocked<Tz: TimeZone>(Cell<DateTime<Tz>>);
+    impl<Tz: TimeZone> Mocked<Tz> {
+        fn set(&self, now: DateTime<Tz>) {
+            self.0.set(now);
+        }
+    }
+    impl<Tz: TimeZone> Clock for Mocked<Tz>
+    where
+        DateTime<Tz>: Copy,
+    {
+        type Tz = Tz;
+        fn now(&self) -> DateTime<Tz> {
+            self.0.get()
+        }
+    }
+
+    fn entry(hour: u32, minute: u32, days: u8) -> ScheduleEntry {
+        ScheduleEntry {
+            enabled: true,
+            hour,
+            minute,
+            days,
+            playlist: Uuid::nil(),
+            volume: 1.0,
+            ramp_secs: 0,
+            interrupt: false,
+        }
+    }
+
+    /// 2024-05-06 is a Monday
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<FixedOffset> {
+        FixedOffset::east_opt(2 * 3600)
+            .unwrap()
+            .with_ymd_and_hms(2024, 5, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn due_time_crosses_day_boundaries() {
+        let every_day = entry(7, 30, 0b111_1111);
+        assert_eq![every_day.next_due(&at(6, 6, 0)), Some(at(6, 7, 30))];
+        assert_eq![every_day.next_due(&at(6, 7, 30)), Some(at(7, 7, 30))];
+        assert_eq![every_day.next_due(&at(6, 23, 59)), Some(at(7, 7, 30))];
+    }
+
+    #[test]
+    fn due_time_respects_the_day_mask() {
+        let mondays = entry(7, 30, 0b000_0001);
+        assert![mondays.runs_on(Weekday::Mon)];
+        assert![!mondays.runs_on(Weekday::Tue)];
+        assert_eq![mondays.next_due(&at(6, 8, 0)), Some(at(13, 7, 30))];
+
+        let never = entry(7, 30, 0);
+        assert_eq![never.next_due(&at(6, 8, 0)), None];
+    }
+
+    #[test]
+    fn missed_entries_fire_within_the_grace_period() {
+        let schedule = Schedule {
+            entries: vec![entry(7, 30, 0b111_1111)],
+        };
+        // Suspended from 7:00 to 7:40
+        assert![schedule.due(&at(6, 7, 0), &at(6, 7, 40)).is_some()];
+        // Suspended until well after
+        assert![schedule.due(&at(6, 7, 0), &at(6, 9, 0)).is_none()];
+        // Not due yet
+        assert![schedule.due(&at(6, 7, 0), &at(6, 7, 29)).is_none()];
+    }
+
+    #[test]
+    fn overlapping_entries_pick_the_earliest() {
+        let mut disabled = entry(7, 0, 0b111_1111);
+        disabled.enabled = false;
+        let schedule = Schedule {
+            entries: vec![entry(7, 10, 0b111_1111), entry(7, 5, 0b111_1111), disabled],
+        };
+        let (picked, due) = schedule.due(&at(6, 6, 59), &at(6, 7, 11)).unwrap();
+        assert_eq![picked.minute, 5];
+        assert_eq![due, at(6, 7, 5)];
+    }
+
+    #[test]
+    fn watches_fire_each_entry_once() {
+        let schedule = Schedule {
+            entries: vec![entry(7, 30, 0b111_1111)],
+        };
+        let clock = Mocked(Cell::new(at(6, 7, 0)));
+        let mut watch = ScheduleWatch::default();
+        assert![watch.check(&schedule, &clock).is_none()];
+
+        clock.set(at(6, 7, 29));
+        assert![watch.check(&schedule, &clock).is_none()];
+        clock.set(at(6, 7, 31));
+        assert_eq![watch.check(&schedule, &clock).unwrap().1, at(6, 7, 30)];
+        clock.set(at(6, 7, 32));
+        assert![watch.check(&schedule, &clock).is_none()];
+
+        // Started after it was due, so it waits for tomorrow
+        let mut late = ScheduleWatch::default();
+        assert![late.check(&schedule, &clock).is_none()];
+        clock.set(at(7, 7, 30));
+        assert_eq![late.check(&schedule, &clock).unwrap().1, at(7, 7, 30)];
+    }
+
+    #[test]
+    fn clock_changes_neither_skip_nor_repeat_entries() {
+        let local = |month, day, hour, minute| {
+            Berlin
+                .from_local_datetime(
+                    &NaiveDate::from_ymd_opt(2024, month, day)
+                        .unwrap()
+                        .and_hms_opt(hour, minute, 0)
+                        .unwrap(),
+                )
+                .earliest()
+                .unwrap()
+        };
+        let utc = |month, day, hour, minute| {
+            Utc.with_ymd_and_hms(2024, month, day, hour, minute, 0)
+                .unwrap()
+                .with_timezone(&Berlin)
+        };
+        let schedule = Schedule {
+            entries: vec![entry(2, 30, 0b111_1111)],
+        };
+
+        // 2:30 never happened on March 31st, so it's played once the clocks jumped to 3:00
+        let clock = Mocked(Cell::new(local(3, 31, 1, 0)));
+        let mut watch = ScheduleWatch::default();
+        watch.check(&schedule, &clock);
+        clock.set(local(3, 31, 3, 10));
+        assert![watch.check(&schedule, &clock).is_none()];
+        clock.set(local(3, 31, 3, 31));
+        assert_eq![watch.check(&schedule, &clock).unwrap().1, utc(3, 31, 1, 30)];
+
+        // 2:30 happened twice on October 27th, and is only played the first time
+        let clock = Mocked(Cell::new(utc(10, 26, 23, 0)));
+        let mut watch = ScheduleWatch::default();
+        watch.check(&schedule, &clock);
+        clock.set(utc(10, 27, 0, 31));
+        assert_eq![
+            watch.check(&schedule, &clock).unwrap().1,
+            utc(10, 27, 0, 30)
+        ];
+        for (hour, minute) in [(1, 0), (1, 31), (1, 40), (2, 0)] {
+            clock.set(utc(10, 27, hour, minute));
+            assert![watch.check(&schedule, &clock).is_none()];
+        }
+    }
+
+    #[test]
+    fn volume_ramps_from_silence() {
+        let now = Instant::now();
+        let mut ramp = VolumeRamp::new(0.8, Duration::from_secs(10));
+        assert_eq![ramp.volume(now), 0.0];
+
+        ramp.start(now);
+        assert_eq![ramp.volume(now + Duration::from_secs(5)), 0.4];
+        assert![!ramp.finished(now + Duration::from_secs(5))];
+        assert_eq![ramp.volume(now + Duration::from_secs(20)), 0.8];
+        assert![ramp.finished(now + Duration::from_secs(20))];
+    }
+}
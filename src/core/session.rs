@@ -0,0 +1,110 @@
+//! Where playback was when the settings were saved, so a restart carries on from there.
+//! The playlist can change between the save and the restart, so nothing is resumed when the
+//! song isn't where it was anymore.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    playback::{OperationTracker, RecursiveSongOp, SongOpTracker},
+    SongKey,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    /// Keeps the shuffles and loop counts as they were
+    pub tracker: SongOpTracker,
+    /// The song that was playing
+    pub key: SongKey,
+    /// In seconds
+    pub elapsed: f64,
+    /// Kept for older settings files, restored songs always start paused
+    #[serde(default)]
+    pub paused: bool,
+}
+
+impl Session {
+    /// The tracker to carry on with. It's started again at the song when the op changed,
+    /// and None when the song isn't at its path anymore.
+    pub fn restore(&self, op: &RecursiveSongOp) -> Option<SongOpTracker> {
+        let path: Vec<usize> = self.tracker.get_current().collect();
+        match op.song_at(path.iter().copied()) {
+            Some(key) if *key == self.key => {}
+            _ => return None,
+        }
+        match self.tracker.fits(op) {
+            true => Some(self.tracker.clone()),
+            false => SongOpTracker::start(op, path.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::playback::{OperationTracker, RecursiveSongOp, SongOpTracker};
+
+    use super::Session;
+
+    fn songs(keys: &[&str]) -> Vec<RecursiveSongOp> {
+        let song = |key: &&str| RecursiveSongOp::SinglePlay(key.to_string());
+        keys.iter().map(song).collect()
+    }
+
+    fn session(op: &RecursiveSongOp, at: usize) -> Session {
+        let mut tracker = SongOpTracker::start(op, [0].into()).unwrap();
+        for _ in 0..at {
+            tracker.move_next();
+        }
+        let key = op.song_at(tracker.get_current()).unwrap().clone();
+        Session {
+            tracker,
+            key,
+            elapsed: 42.0,
+            paused: true,
+        }
+    }
+
+    #[test]
+    fn sessions_survive_the_settings_file() {
+        let op = RecursiveSongOp::RandomPlay(songs(&["a", "b", "c"]));
+        let saved = session(&op, 0);
+
+        let json = serde_json::to_string(&saved).unwrap();
+        let loaded: Session = serde_json::from_str(&json).unwrap();
+        let tracker = loaded.restore(&op).unwrap();
+        assert_eq![
+            tracker.get_current().collect::<Vec<_>>(),
+            saved.tracker.get_current().collect::<Vec<_>>()
+        ];
+        assert_eq![
+            (loaded.key, loaded.elapsed, loaded.paused),
+            (saved.key, 42.0, true)
+        ];
+
+        // The rest of the shuffle plays in the order it would have
+        let (mut before, mut after) = (saved.tracker.clone(), tracker);
+        before.move_next();
+        after.move_next();
+        assert_eq![
+            before.get_current().collect::<Vec<_>>(),
+            after.get_current().collect::<Vec<_>>()
+        ];
+    }
+
+    #[test]
+    fn changed_ops_restart_at_the_song_or_give_up() {
+        let op = RecursiveSongOp::PlayOnce(songs(&["a", "b"]));
+        let saved = session(&op, 1);
+
+        // A song was added after it, so it's still at its path
+        let longer = RecursiveSongOp::PlayOnce(songs(&["a", "b", "c"]));
+        let tracker = saved.restore(&longer).unwrap();
+        assert_eq![tracker.get_current().collect::<Vec<_>>(), vec![1]];
+
+        // A song was added before it, so another song is at the path
+        let shifted = RecursiveSongOp::PlayOnce(songs(&["c", "a", "b"]));
+        assert![saved.restore(&shifted).is_none()];
+
+        let shorter = RecursiveSongOp::PlayOnce(songs(&["a"]));
+        assert![saved.restore(&shorter).is_none()];
+    }
+}
@@ -0,0 +1,482 @@
+//! The settings file: the open playlist, the user's preferences, the schedule, and where
+//! playback and the window were. The app keeps its own playlist type in it, a headless run reads
+//! the playlist as it was saved.
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    fmt::Display,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use async_std::prelude::*;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    audio::{EqSettings, DEFAULT_STOP_FADE, DEFAULT_STREAM_OVER_MB},
+    backend_settings::BackendSettings,
+    caching::readers::unique_tmp,
+    paths::settings_path,
+    playback::{ActualRecursiveOps, Repeat, SimpleMode, DEFAULT_UNKNOWN_SONG_SECS},
+    saved::{LoadError, SavedPlaylist},
+    scheduler::Schedule,
+    session::Session,
+    window_state::{PinnedModes, WindowState},
+};
+
+/// How many plays are remembered when the user hasn't picked a number
+pub const DEFAULT_HISTORY_LIMIT: usize = 1000;
+
+/// How many songs are asked for at once when the user hasn't picked a number
+pub const DEFAULT_METADATA_REQUESTS: usize = 4;
+
+/// How much of the width the search takes, until the divider is dragged
+pub const DEFAULT_SPLIT: f32 = 0.5;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WrapDefaults {
+    pub operation: ActualRecursiveOps,
+    pub n: u32,
+}
+impl Default for WrapDefaults {
+    fn default() -> Self {
+        Self {
+            operation: ActualRecursiveOps::LoopNTimes,
+            n: 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct YTMRUserSettings {
+    /// The current volume, and the one used for devices that haven't been seen before.
+    /// Kept while muted, so unmuting goes back to it.
+    pub volume: f32,
+    #[serde(default)]
+    pub muted: bool,
+    /// The last volume used on each output device
+    #[serde(default)]
+    pub device_volumes: HashMap<String, f32>,
+    /// Which layout modes keep the window above other windows
+    #[serde(default)]
+    pub pinned: PinnedModes,
+    /// How much of the width the search pane takes, the playlist pane has the rest
+    #[serde(default = "default_pane_split")]
+    pub pane_split: f32,
+    /// The last version whose changelog was shown
+    #[serde(default)]
+    pub last_seen_version: Option<String>,
+    /// The group a song is wrapped in from its row
+    #[serde(default)]
+    pub wrap: WrapDefaults,
+    /// Shows the playlist as a flat queue. Off for settings from before it existed.
+    #[serde(default)]
+    pub simple: SimpleMode,
+    /// Plays songs at the same loudness, using the gain measured for each
+    #[serde(default)]
+    pub normalize: bool,
+    /// Shows a desktop notification when a song starts while the window is in the background
+    #[serde(default = "default_song_notifications")]
+    pub song_notifications: bool,
+    /// Plays the rest of the queue in a random order
+    #[serde(default)]
+    pub shuffle: bool,
+    #[serde(default)]
+    pub repeat: Repeat,
+    /// How much of the disk downloaded songs can take, the least recently played go first
+    #[serde(default = "default_audio_cache_mb")]
+    pub max_audio_cache_mb: u64,
+    /// Songs whose file is bigger than this play from the disk, rather than from memory
+    #[serde(default = "default_stream_over_mb")]
+    pub stream_over_mb: u64,
+    /// What every song is played through
+    #[serde(default)]
+    pub eq: EqSettings,
+    /// How long a song fades out for when another replaces it
+    #[serde(default = "default_stop_fade_ms")]
+    pub stop_fade_ms: u64,
+    /// How many plays the history keeps, the oldest are forgotten first
+    #[serde(default = "default_history_limit")]
+    pub history_limit: usize,
+    /// How many songs' info is asked of the backend at once
+    #[serde(default = "default_metadata_requests")]
+    pub metadata_requests: usize,
+    /// What a song whose length isn't known counts for in "Loop For" groups, in seconds
+    #[serde(default = "default_unknown_song_secs")]
+    pub unknown_song_secs: u64,
+    /// What the backend converts downloads to
+    #[serde(default)]
+    pub download_format: DownloadFormat,
+    /// The tick intervals the user changed, in milliseconds. The rest keep their defaults.
+    #[serde(default)]
+    pub tick_intervals_ms: HashMap<Ticker, u64>,
+    /// How often changes are saved on their own, in seconds. 0 leaves it to the save button.
+    #[serde(default = "default_autosave_secs")]
+    pub autosave_secs: u64,
+    #[cfg(feature = "scrobble")]
+    #[serde(default)]
+    pub scrobble: ScrobbleSettings,
+}
+
+#[cfg(feature = "scrobble")]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScrobbleSettings {
+    pub enabled: bool,
+    /// The ListenBrainz user token. It's kept in its own file, and only read from settings
+    /// saved before it was.
+    #[serde(default, skip_serializing)]
+    pub token: String,
+}
+
+fn default_song_notifications() -> bool {
+    true
+}
+
+fn default_pane_split() -> f32 {
+    DEFAULT_SPLIT
+}
+
+fn default_audio_cache_mb() -> u64 {
+    4096
+}
+
+fn default_stream_over_mb() -> u64 {
+    DEFAULT_STREAM_OVER_MB
+}
+
+fn default_stop_fade_ms() -> u64 {
+    DEFAULT_STOP_FADE.as_millis() as u64
+}
+
+fn default_history_limit() -> usize {
+    DEFAULT_HISTORY_LIMIT
+}
+
+fn default_metadata_requests() -> usize {
+    DEFAULT_METADATA_REQUESTS
+}
+
+fn default_unknown_song_secs() -> u64 {
+    DEFAULT_UNKNOWN_SONG_SECS
+}
+
+fn default_autosave_secs() -> u64 {
+    30
+}
+
+/// The formats the backend can convert downloads to that can be played back.
+/// Wav is the quickest to decode, but by far the biggest.
+/// What the site serves (opus or m4a) isn't decoded by the audio backend, so it's converted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DownloadFormat {
+    #[default]
+    Wav,
+    Flac,
+    Mp3,
+    Vorbis,
+}
+
+impl DownloadFormat {
+    pub const ALL: [DownloadFormat; 4] = [Self::Wav, Self::Flac, Self::Mp3, Self::Vorbis];
+
+    /// The name the backend knows the format by
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Wav => "wav",
+            Self::Flac => "flac",
+            Self::Mp3 => "mp3",
+            Self::Vorbis => "vorbis",
+        }
+    }
+}
+
+impl Display for DownloadFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The periodic ticks, by what they do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Ticker {
+    Cache,
+    BackendStatus,
+    PlayingStatus,
+    Schedule,
+    VolumeRamp,
+    OutputDevice,
+    Maintenance,
+}
+
+impl Ticker {
+    pub const ALL: [Ticker; 7] = [
+        Self::Cache,
+        Self::BackendStatus,
+        Self::PlayingStatus,
+        Self::Schedule,
+        Self::VolumeRamp,
+        Self::OutputDevice,
+        Self::Maintenance,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Cache => "cache upkeep",
+            Self::BackendStatus => "backend check",
+            Self::PlayingStatus => "progress bar",
+            Self::Schedule => "schedule",
+            Self::VolumeRamp => "volume fades",
+            Self::OutputDevice => "output device check",
+            Self::Maintenance => "maintenance",
+        }
+    }
+}
+
+impl Default for YTMRUserSettings {
+    fn default() -> Self {
+        Self {
+            volume: 1.0,
+            muted: false,
+            device_volumes: HashMap::new(),
+            pinned: PinnedModes::default(),
+            pane_split: default_pane_split(),
+            last_seen_version: None,
+            wrap: WrapDefaults::default(),
+            simple: SimpleMode::fresh(),
+            normalize: false,
+            song_notifications: default_song_notifications(),
+            shuffle: false,
+            repeat: Repeat::Off,
+            max_audio_cache_mb: default_audio_cache_mb(),
+            stream_over_mb: default_stream_over_mb(),
+            eq: EqSettings::default(),
+            stop_fade_ms: default_stop_fade_ms(),
+            history_limit: default_history_limit(),
+            metadata_requests: default_metadata_requests(),
+            unknown_song_secs: default_unknown_song_secs(),
+            download_format: DownloadFormat::default(),
+            tick_intervals_ms: HashMap::new(),
+            autosave_secs: default_autosave_secs(),
+            #[cfg(feature = "scrobble")]
+            scrobble: Default::default(),
+        }
+    }
+}
+
+impl YTMRUserSettings {
+    pub fn audio_cache_budget(&self) -> u64 {
+        self.max_audio_cache_mb.saturating_mul(1024 * 1024)
+    }
+
+    /// In bytes
+    pub fn stream_over(&self) -> u64 {
+        self.stream_over_mb.saturating_mul(1024 * 1024)
+    }
+
+    pub fn stop_fade(&self) -> Duration {
+        Duration::from_millis(self.stop_fade_ms)
+    }
+
+    /// The volume to use on the device
+    pub fn volume_for(&self, device: Option<&str>) -> f32 {
+        device
+            .and_then(|device| self.device_volumes.get(device))
+            .copied()
+            .unwrap_or(self.volume)
+    }
+
+    /// The volume, or silence while muted
+    pub fn unless_muted(&self, volume: f32) -> f32 {
+        match self.muted {
+            true => 0.0,
+            false => volume,
+        }
+    }
+
+    /// Sets the volume, remembering it for the device
+    pub fn set_volume(&mut self, device: Option<&str>, volume: f32) {
+        self.volume = volume;
+        if let Some(device) = device {
+            self.device_volumes.insert(device.to_string(), volume);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct YTMRSettings<P = SavedPlaylist> {
+    pub playlist: P,
+    pub user: YTMRUserSettings,
+    #[serde(default)]
+    pub schedule: Schedule,
+    /// Where playback was when the settings were saved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<Session>,
+    /// Where the backend is, and whether to launch it
+    #[serde(default)]
+    pub backend: BackendSettings,
+    /// The main window's size and position, also written on its own when quitting without saving
+    #[serde(default)]
+    pub window: WindowState,
+}
+
+#[derive(Debug, Clone)]
+pub enum SaveError {
+    File,
+    Write,
+    Format,
+}
+
+impl<P: DeserializeOwned> YTMRSettings<P> {
+    pub async fn load_default() -> Result<Self, LoadError> {
+        Self::load(settings_path()).await
+    }
+
+    pub async fn load(path: PathBuf) -> Result<Self, LoadError> {
+        let mut contents = String::new();
+        let mut file = async_std::fs::File::open(path)
+            .await
+            .map_err(|_| LoadError::File)?;
+
+        file.read_to_string(&mut contents)
+            .await
+            .map_err(|_| LoadError::File)?;
+        let settings: Self = serde_json::from_str(&contents).map_err(|_| LoadError::Format)?;
+        Ok(settings)
+    }
+}
+
+impl<P: Serialize> YTMRSettings<P> {
+    /// The same for settings that would be saved the same. Maps are sorted before they're
+    /// hashed, so the order their keys were added in doesn't count.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        serde_json::to_value(self)
+            .map(|json| json.to_string())
+            .unwrap_or_default()
+            .hash(&mut hasher);
+        hasher.finish()
+    }
+
+    pub async fn save(self) -> Result<PathBuf, SaveError> {
+        self.save_to(settings_path()).await
+    }
+
+    /// Writes a file next to the path and renames it over the old one, so a write that fails
+    /// halfway leaves the old settings whole
+    pub async fn save_to(self, path: PathBuf) -> Result<PathBuf, SaveError> {
+        let json = serde_json::to_string_pretty(&self).map_err(|_| SaveError::Format)?;
+        if let Some(dir) = path.parent() {
+            async_std::fs::create_dir_all(dir)
+                .await
+                .map_err(|_| SaveError::File)?;
+        }
+
+        let tempfile = unique_tmp(&path, "json");
+        let written = write_synced(&tempfile, json.as_bytes()).await;
+        let renamed = match written {
+            Ok(()) => async_std::fs::rename(&tempfile, &path)
+                .await
+                .map_err(|_| SaveError::File),
+            Err(e) => Err(e),
+        };
+        if renamed.is_err() {
+            let _ = async_std::fs::remove_file(&tempfile).await;
+        }
+        renamed.map(|()| path)
+    }
+}
+
+/// Written through to the disk, so it can be renamed over a file without losing it in a crash
+async fn write_synced(path: &Path, contents: &[u8]) -> Result<(), SaveError> {
+    let mut file = async_std::fs::File::create(path)
+        .await
+        .map_err(|_| SaveError::File)?;
+    file.write_all(contents)
+        .await
+        .map_err(|_| SaveError::Write)?;
+    file.sync_all().await.map_err(|_| SaveError::Write)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DownloadFormat, Ticker, YTMRUserSettings};
+
+    /// Any playlist does for these
+    type YTMRSettings = super::YTMRSettings<serde_json::Value>;
+
+    #[test]
+    fn volume_is_remembered_per_device() {
+        // Settings from before device volumes existed
+        let mut settings: YTMRUserSettings = serde_json::from_str(r#"{"volume": 0.8}"#).unwrap();
+        assert_eq![settings.volume_for(Some("speakers")), 0.8];
+
+        settings.set_volume(Some("speakers"), 0.8);
+        // Switch to the DAC, which inherits the current volume until it's changed
+        assert_eq![settings.volume_for(Some("dac")), 0.8];
+        settings.set_volume(Some("dac"), 0.25);
+
+        assert_eq![settings.volume_for(Some("speakers")), 0.8];
+        assert_eq![settings.volume_for(Some("dac")), 0.25];
+        // Devices that can't be named use the current volume
+        assert_eq![settings.volume_for(None), 0.25];
+    }
+
+    #[test]
+    fn preferences_are_saved() {
+        let mut settings: YTMRUserSettings = serde_json::from_str(r#"{"volume": 1.0}"#).unwrap();
+        assert_eq![settings.download_format, DownloadFormat::Wav];
+        assert![settings.tick_intervals_ms.is_empty()];
+
+        settings.download_format = DownloadFormat::Flac;
+        let intervals = &mut settings.tick_intervals_ms;
+        intervals.insert(Ticker::OutputDevice, 10_000);
+        let json = serde_json::to_string(&settings).unwrap();
+        assert![json.contains(r#""download_format":"flac""#)];
+        assert![json.contains(r#""output_device":10000"#)];
+        let loaded: YTMRUserSettings = serde_json::from_str(&json).unwrap();
+        assert_eq![loaded.download_format, DownloadFormat::Flac];
+        let interval = loaded.tick_intervals_ms.get(&Ticker::OutputDevice);
+        assert_eq![interval, Some(&10_000)];
+        assert_eq![loaded.autosave_secs, 30];
+    }
+
+    #[test]
+    fn only_changes_change_the_fingerprint() {
+        let mut settings = YTMRSettings::default();
+        let devices = ["speakers", "dac", "headphones", "hdmi"];
+        for (idx, device) in devices.into_iter().enumerate() {
+            settings.user.set_volume(Some(device), idx as f32 / 4.0);
+        }
+        let saved = settings.fingerprint();
+
+        // The same volumes, in a map of their own added the other way around
+        let volumes = devices.into_iter().enumerate().rev();
+        let mut reordered = settings.clone();
+        reordered.user.device_volumes = volumes
+            .map(|(idx, device)| (device.to_string(), idx as f32 / 4.0))
+            .collect();
+        assert_eq![reordered.fingerprint(), saved];
+
+        settings.user.autosave_secs = 0;
+        assert_ne![settings.fingerprint(), saved];
+    }
+
+    #[test]
+    fn saving_replaces_the_file_whole() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.json");
+        std::fs::write(&path, "old").unwrap();
+
+        let mut settings = YTMRSettings::default();
+        settings.user.autosave_secs = 5;
+        let saved = async_std::task::block_on(settings.save_to(path.clone())).unwrap();
+        assert_eq![saved, path];
+        let loaded = async_std::task::block_on(YTMRSettings::load(path)).unwrap();
+        assert_eq![loaded.user.autosave_secs, 5];
+        // The file it was written to first was renamed away
+        assert_eq![std::fs::read_dir(dir.path()).unwrap().count(), 1];
+    }
+}
@@ -0,0 +1,132 @@
+//! How much is printed. Messages above the level are never formatted, so they can dump large
+//! structures without costing anything when nobody asked for them.
+
+use std::{
+    fmt,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Progress and errors
+    Normal,
+    /// What the app is doing, in short
+    Debug,
+    /// Every lock, and whole structures
+    Trace,
+}
+
+impl Verbosity {
+    /// -v for Debug, -vv (or -v twice) for Trace
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Self {
+        let count: usize = args.into_iter().map(|arg| Self::raised_by(&arg)).sum();
+        match count {
+            0 => Self::Normal,
+            1 => Self::Debug,
+            _ => Self::Trace,
+        }
+    }
+
+    /// How many levels the argument raises the verbosity by, 0 for other arguments
+    pub fn raised_by(arg: &str) -> usize {
+        match (arg, arg.strip_prefix('-')) {
+            ("--verbose", _) => 1,
+            (_, Some(vs)) if !vs.is_empty() && vs.chars().all(|c| c == 'v') => vs.len(),
+            _ => 0,
+        }
+    }
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+
+pub fn set(level: Verbosity) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn enabled(level: Verbosity) -> bool {
+    level as u8 <= LEVEL.load(Ordering::Relaxed)
+}
+
+#[cfg(test)]
+thread_local! {
+    static FORMATTED: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+/// Prints the message if the level is enabled. Use the macros instead of calling this.
+pub fn print(level: Verbosity, args: fmt::Arguments) {
+    if !enabled(level) {
+        return;
+    }
+    let line = args.to_string();
+    #[cfg(test)]
+    FORMATTED.with(|formatted| formatted.set(formatted.get() + line.len()));
+    println!["{line}"];
+}
+
+/// How many bytes of messages `f` formatted on this thread
+#[cfg(test)]
+pub fn formatted_by<R>(f: impl FnOnce() -> R) -> (R, usize) {
+    let before = FORMATTED.with(|formatted| formatted.get());
+    let result = f();
+    (result, FORMATTED.with(|formatted| formatted.get()) - before)
+}
+
+/// Printed at every level. Only for short lines, leave the details to `debug!` and `trace!`.
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => {
+        $crate::verbosity::print($crate::verbosity::Verbosity::Normal, format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        $crate::verbosity::print($crate::verbosity::Verbosity::Debug, format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        $crate::verbosity::print($crate::verbosity::Verbosity::Trace, format_args!($($arg)*))
+    };
+}
+
+pub use crate::{debug, info, trace};
+
+#[cfg(test)]
+mod tests {
+    use super::{formatted_by, Verbosity};
+
+    fn args(args: &[&str]) -> Verbosity {
+        Verbosity::from_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn levels_come_from_the_arguments() {
+        assert_eq![args(&["ytm-rs"]), Verbosity::Normal];
+        assert_eq![args(&["ytm-rs", "--demo", "-v"]), Verbosity::Debug];
+        assert_eq![args(&["ytm-rs", "--verbose"]), Verbosity::Debug];
+        assert_eq![args(&["ytm-rs", "-vv"]), Verbosity::Trace];
+        assert_eq![args(&["ytm-rs", "-v", "-v"]), Verbosity::Trace];
+        assert_eq![args(&["ytm-rs", "-", "-x"]), Verbosity::Normal];
+    }
+
+    #[test]
+    fn hidden_messages_are_not_formatted() {
+        struct Expensive;
+        impl std::fmt::Display for Expensive {
+            fn fmt(&self, _: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                panic!["formatted a hidden message"]
+            }
+        }
+
+        let ((), formatted) = formatted_by(|| {
+            super::debug!["{}", Expensive];
+            super::trace!["{}", Expensive];
+            super::info!["shown"];
+        });
+        assert_eq![formatted, "shown".len()];
+    }
+}
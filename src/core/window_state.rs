@@ -0,0 +1,107 @@
+//! Where the main window was and how big it was, so it opens the same way next time, and which
+//! layout modes keep it above the others. The state is read before the rest of the settings,
+//! since the window is made before they load.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{paths::settings_path, settings::SaveError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WindowState {
+    pub width: f32,
+    pub height: f32,
+    /// None until the window is moved, it opens centered until then
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<(i32, i32)>,
+}
+
+impl Default for WindowState {
+    fn default() -> Self {
+        Self {
+            width: 1024.0,
+            height: 512.0,
+            position: None,
+        }
+    }
+}
+
+impl WindowState {
+    /// Reads the state from the settings file, skipping everything else in it
+    pub fn load() -> Self {
+        #[derive(Deserialize)]
+        struct Saved {
+            #[serde(default)]
+            window: WindowState,
+        }
+        let saved = std::fs::read_to_string(settings_path())
+            .ok()
+            .and_then(|contents| serde_json::from_str::<Saved>(&contents).ok());
+        match saved {
+            Some(saved) => saved.window,
+            None => Self::default(),
+        }
+    }
+
+    /// Writes the state into the settings file, leaving the rest of it as it was saved.
+    /// Settings that were never saved aren't written.
+    pub async fn save_into(self, path: PathBuf) -> Result<(), SaveError> {
+        let contents = match async_std::fs::read_to_string(&path).await {
+            Ok(contents) => contents,
+            Err(_) => return Ok(()),
+        };
+        let mut json: serde_json::Value =
+            serde_json::from_str(&contents).map_err(|_| SaveError::Format)?;
+        let window = serde_json::to_value(self).map_err(|_| SaveError::Format)?;
+        match json.as_object_mut() {
+            Some(settings) => settings.insert("window".to_string(), window),
+            None => return Err(SaveError::Format),
+        };
+        let json = serde_json::to_string_pretty(&json).map_err(|_| SaveError::Format)?;
+        async_std::fs::write(&path, json)
+            .await
+            .map_err(|_| SaveError::Write)
+    }
+}
+
+/// The layouts of the playlist, see [`crate::playback::SimpleMode`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayoutMode {
+    Advanced,
+    Simple,
+}
+
+impl LayoutMode {
+    pub fn of(simple: bool) -> Self {
+        match simple {
+            true => Self::Simple,
+            false => Self::Advanced,
+        }
+    }
+}
+
+/// Whether each layout mode keeps the window on top
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PinnedModes {
+    #[serde(default)]
+    pub advanced: bool,
+    #[serde(default)]
+    pub simple: bool,
+}
+
+impl PinnedModes {
+    pub fn get(&self, mode: LayoutMode) -> bool {
+        match mode {
+            LayoutMode::Advanced => self.advanced,
+            LayoutMode::Simple => self.simple,
+        }
+    }
+
+    pub fn set(&mut self, mode: LayoutMode, pinned: bool) {
+        match mode {
+            LayoutMode::Advanced => self.advanced = pinned,
+            LayoutMode::Simple => self.simple = pinned,
+        }
+    }
+}
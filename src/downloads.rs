@@ -0,0 +1,353 @@
+//! Songs the backend is downloading. Each download runs as a subscription that streams the
+//! backend's progress, so cancelling one drops its subscription and the request with it.
+//! Nothing reaches the sounds cache until a download finishes.
+
+use std::collections::{HashSet, VecDeque};
+
+use futures::StreamExt;
+use iced::{
+    widget::{button, column, progress_bar, row, text, Column},
+    Alignment, Element, Length, Subscription,
+};
+use reqwest::{Client, Url};
+
+pub use ytm_rs_core::backend_handler::{DownloadEvent, DownloadProgress};
+
+use crate::{
+    backend_handler::BackendHandler,
+    response_types::UrlString,
+    settings::{DownloadFormat, SongKey},
+};
+
+#[derive(Debug, Clone)]
+pub struct Download {
+    pub key: SongKey,
+    pub title: String,
+    url: UrlString,
+    client: Client,
+    host: Url,
+    format: DownloadFormat,
+    /// Whether the song plays once it's downloaded
+    pub play: bool,
+    pub progress: DownloadProgress,
+}
+
+impl Download {
+    pub fn new(
+        key: SongKey,
+        title: String,
+        url: UrlString,
+        client: Client,
+        host: Url,
+        format: DownloadFormat,
+        play: bool,
+    ) -> Self {
+        Self {
+            key,
+            title,
+            url,
+            client,
+            host,
+            format,
+            play,
+            progress: DownloadProgress::default(),
+        }
+    }
+}
+
+/// How many of a batch's songs download at once
+pub const BATCH_CONCURRENCY: usize = 2;
+
+/// Songs queued to download a few at a time, like every song of a playlist.
+/// A song that fails is counted and the rest go on.
+#[derive(Debug, Clone, Default)]
+pub struct Batch {
+    pending: VecDeque<SongKey>,
+    active: HashSet<SongKey>,
+    total: usize,
+    done: usize,
+    /// The songs that failed, with why
+    pub failed: Vec<(SongKey, String)>,
+    /// Stopped because the sounds cache went over its budget, it goes on once resumed
+    pub paused: bool,
+}
+
+impl Batch {
+    pub fn new(keys: Vec<SongKey>) -> Self {
+        Self {
+            total: keys.len(),
+            pending: keys.into(),
+            ..Default::default()
+        }
+    }
+
+    /// The songs to start so that the most are downloading, none while it's paused
+    pub fn next_keys(&mut self) -> Vec<SongKey> {
+        let mut keys = vec![];
+        while !self.paused && self.active.len() < BATCH_CONCURRENCY {
+            match self.pending.pop_front() {
+                Some(key) => {
+                    self.active.insert(key.clone());
+                    keys.push(key);
+                }
+                None => break,
+            }
+        }
+        keys
+    }
+
+    /// Counts the song as downloaded, or as failed with the error.
+    /// Returns whether it was one of the batch's downloads.
+    pub fn finish(&mut self, key: &str, error: Option<String>) -> bool {
+        if !self.active.remove(key) {
+            return false;
+        }
+        match error {
+            Some(error) => self.failed.push((key.to_string(), error)),
+            None => self.done += 1,
+        }
+        true
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.active.contains(key)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.pending.is_empty() && self.active.is_empty()
+    }
+
+    pub fn describe(&self) -> String {
+        let mut text = format!("{}/{} downloaded", self.done, self.total);
+        if !self.failed.is_empty() {
+            text.push_str(&format!(", {} failed", self.failed.len()));
+        }
+        if self.paused {
+            text.push_str(", paused");
+        }
+        text
+    }
+
+    /// What's said once it's over
+    pub fn summary(&self) -> String {
+        let mut text = format!("Downloaded {} of {} songs", self.done, self.total);
+        if !self.failed.is_empty() {
+            let keys: Vec<&str> = self.failed.iter().map(|(key, _)| key.as_str()).collect();
+            text.push_str(&format!(", {} failed: {}", keys.len(), keys.join(", ")));
+        }
+        text
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum DownloadsMsg {
+    Cancel(SongKey),
+    CancelBatch,
+    ResumeBatch,
+}
+
+/// The downloads in flight, in the order they started
+#[derive(Debug, Default)]
+pub struct Downloads {
+    in_flight: Vec<Download>,
+    pub batch: Option<Batch>,
+}
+
+impl Downloads {
+    /// Starts the download unless the song is already downloading, in which case it's only
+    /// made to play. Returns whether it started.
+    pub fn start(&mut self, download: Download) -> bool {
+        match self.in_flight.iter_mut().find(|d| d.key == download.key) {
+            Some(existing) => {
+                existing.play |= download.play;
+                false
+            }
+            None => {
+                self.in_flight.push(download);
+                true
+            }
+        }
+    }
+
+    /// Records the progress, returning the download it belongs to
+    pub fn progress(&mut self, key: &str, progress: DownloadProgress) -> Option<&Download> {
+        let download = self.in_flight.iter_mut().find(|d| d.key == key)?;
+        download.progress = progress;
+        Some(download)
+    }
+
+    /// Takes the download out, which also stops it if it's still running
+    pub fn remove(&mut self, key: &str) -> Option<Download> {
+        let idx = self.in_flight.iter().position(|d| d.key == key)?;
+        Some(self.in_flight.remove(idx))
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.in_flight.iter().any(|d| d.key == key)
+    }
+
+    /// Drops the batch and stops its downloads, except the ones a song is waiting on to play.
+    /// Returns the songs that were stopped.
+    pub fn cancel_batch(&mut self) -> Vec<SongKey> {
+        let batch = match self.batch.take() {
+            Some(batch) => batch,
+            None => return vec![],
+        };
+        let (stopped, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut self.in_flight)
+            .into_iter()
+            .partition(|d| batch.contains(&d.key) && !d.play);
+        self.in_flight = kept;
+        stopped.into_iter().map(|d| d.key).collect()
+    }
+
+    /// One stream per download, kept alive for as long as the download is in flight
+    pub fn subscription(&self) -> Subscription<(SongKey, DownloadEvent)> {
+        Subscription::batch(self.in_flight.iter().map(|download| {
+            let key = download.key.clone();
+            let (client, host) = (download.client.clone(), download.host.clone());
+            let url = download.url.clone();
+            let stream = BackendHandler::download_song(client, host, url, download.format)
+                .map(move |event| (key.clone(), event));
+            iced::subscription::run_with_id(("download", download.key.clone()), stream)
+        }))
+    }
+
+    fn batch_view(batch: &Batch) -> Element<DownloadsMsg> {
+        let resume = batch.paused.then_some(DownloadsMsg::ResumeBatch);
+        row![
+            text(batch.describe()).width(Length::Fill),
+            button("resume").on_press_maybe(resume),
+            button("cancel all").on_press(DownloadsMsg::CancelBatch),
+        ]
+        .spacing(8)
+        .align_items(Alignment::Center)
+        .into()
+    }
+
+    pub fn view(&self) -> Option<Element<DownloadsMsg>> {
+        if self.in_flight.is_empty() && self.batch.is_none() {
+            return None;
+        }
+        let rows = self.in_flight.iter().map(|download| {
+            row![
+                text(download.title.clone()).width(Length::FillPortion(2)),
+                progress_bar(0.0..=1.0, download.progress.fraction().unwrap_or_default())
+                    .height(8)
+                    .width(Length::FillPortion(3)),
+                text(download.progress.describe()).width(120),
+                button("cancel").on_press(DownloadsMsg::Cancel(download.key.clone())),
+            ]
+            .spacing(8)
+            .align_items(Alignment::Center)
+            .into()
+        });
+        Some(
+            column![
+                text(format!("{} downloading", self.in_flight.len())).size(20),
+                Column::with_children(rows).spacing(4),
+            ]
+            .push_maybe(self.batch.as_ref().map(Self::batch_view))
+            .spacing(4)
+            .padding(10)
+            .into(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::{Client, Url};
+
+    use super::{Batch, Download, DownloadProgress, Downloads, BATCH_CONCURRENCY};
+    use crate::settings::DownloadFormat;
+
+    fn download(key: &str, play: bool) -> Download {
+        let host = Url::parse("http://127.0.0.1:55001/").unwrap();
+        Download::new(
+            key.into(),
+            key.into(),
+            "...".into(),
+            Client::new(),
+            host,
+            DownloadFormat::Wav,
+            play,
+        )
+    }
+
+    #[test]
+    fn downloads_are_started_once() {
+        let mut downloads = Downloads::default();
+        assert![downloads.start(download("a", false))];
+        // Asking to play a song that's being prefetched plays it once it's done
+        assert![!downloads.start(download("a", true))];
+        assert![downloads.start(download("b", false))];
+
+        assert![downloads.remove("a").unwrap().play];
+        assert![downloads.remove("a").is_none()];
+        assert![!downloads.remove("b").unwrap().play];
+    }
+
+    #[test]
+    fn batches_go_on_past_failures() {
+        let keys: Vec<String> = ["a", "b", "c"].map(String::from).into();
+        let mut batch = Batch::new(keys);
+        let started = batch.next_keys();
+        assert_eq![started.len(), BATCH_CONCURRENCY];
+        assert![batch.next_keys().is_empty()];
+
+        assert![batch.finish("a", None)];
+        assert![batch.finish("b", Some("offline".into()))];
+        assert![!batch.finish("b", None)];
+        batch.paused = true;
+        assert![batch.next_keys().is_empty()];
+        assert_eq![batch.describe(), "1/3 downloaded, 1 failed, paused"];
+
+        batch.paused = false;
+        assert_eq![batch.next_keys(), ["c"]];
+        assert![batch.finish("c", None)];
+        assert![batch.is_finished()];
+        assert_eq![batch.summary(), "Downloaded 2 of 3 songs, 1 failed: b"];
+    }
+
+    #[test]
+    fn cancelling_a_batch_keeps_songs_waiting_to_play() {
+        let mut downloads = Downloads::default();
+        let mut batch = Batch::new(vec!["a".into(), "b".into()]);
+        for key in batch.next_keys() {
+            downloads.start(download(&key, false));
+        }
+        downloads.batch = Some(batch);
+        // Clicked while the batch was downloading it
+        downloads.start(download("b", true));
+        downloads.start(download("c", false));
+
+        assert_eq![downloads.cancel_batch(), ["a"]];
+        assert![downloads.batch.is_none()];
+        assert![!downloads.contains("a") && downloads.contains("b") && downloads.contains("c")];
+    }
+
+    #[test]
+    fn progress_is_a_fraction_of_the_total() {
+        let mut downloads = Downloads::default();
+        downloads.start(download("a", false));
+        let progress = DownloadProgress {
+            downloaded_bytes: 250,
+            total_bytes: Some(1000),
+            eta: Some(3.2),
+        };
+        let updated = downloads.progress("a", progress).unwrap();
+        assert_eq![updated.progress.fraction(), Some(0.25)];
+        assert_eq![updated.progress.describe(), "25%, 3s left"];
+        assert![downloads.progress("b", progress).is_none()];
+
+        // Without a total, only the size so far is known
+        let unknown = DownloadProgress {
+            downloaded_bytes: 1_500_000,
+            ..Default::default()
+        };
+        assert_eq![
+            (unknown.fraction(), unknown.describe()),
+            (None, "1.5MB".to_string())
+        ];
+    }
+}
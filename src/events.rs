@@ -0,0 +1,204 @@
+//! Events integrations can listen to, instead of each hooking its own calls into `Ytmrs::update`.
+//!
+//! Events are emitted by `Ytmrs`:
+//! - [`AppEvent::SongStarted`] when a sound starts playing, in `play`
+//! - [`AppEvent::SongEnded`] once for every song that played, when the audio manager reports
+//!   its end or when anything else replaces it, in `song_ended`
+//! - [`AppEvent::SongAdded`] for every song dropped into the playlist, in `handle_zones`
+//! - [`AppEvent::PlaybackPaused`] and [`AppEvent::PlaybackResumed`] from the tracker's buttons
+//! - [`AppEvent::Seeked`] when the progress bar is let go, or a transport key seeks
+//!
+//! Listeners are registered once, when the bus is started, and run on a background thread in the
+//! order the events were emitted. Emitting never waits for them, so a slow listener can't stall
+//! the UI, though it does delay the listeners after it.
+
+use std::{
+    sync::mpsc::{self, Sender},
+    thread,
+};
+
+use crate::song::Song;
+
+#[derive(Debug, Clone)]
+pub enum AppEvent {
+    SongStarted {
+        id: String,
+        /// None if the song's metadata isn't cached
+        meta: Option<Song>,
+    },
+    SongEnded {
+        id: String,
+        /// False if it was skipped
+        completed: bool,
+    },
+    SongAdded {
+        id: String,
+        playlist: String,
+    },
+    PlaybackPaused,
+    PlaybackResumed,
+    Seeked {
+        /// Where the song is now, in seconds
+        secs: f64,
+    },
+}
+
+pub trait Listener: Send {
+    fn handle(&mut self, event: &AppEvent);
+}
+
+/// Delivers events to the listeners registered when it was started
+#[derive(Debug, Default)]
+pub struct EventBus {
+    /// None when nothing listens
+    sender: Option<Sender<AppEvent>>,
+}
+
+impl EventBus {
+    pub fn start(mut listeners: Vec<Box<dyn Listener>>) -> Self {
+        if listeners.is_empty() {
+            return Self::default();
+        }
+        let (sender, receiver) = mpsc::channel::<AppEvent>();
+        // Ends once the bus is dropped
+        thread::spawn(move || {
+            for event in receiver {
+                for listener in listeners.iter_mut() {
+                    listener.handle(&event);
+                }
+            }
+        });
+        Self {
+            sender: Some(sender),
+        }
+    }
+
+    pub fn emit(&self, event: AppEvent) {
+        if let Some(sender) = &self.sender {
+            if sender.send(event).is_err() {
+                println!["Event listeners stopped, dropping event"];
+            }
+        }
+    }
+}
+
+/// Prints what's playing to the console
+pub struct ConsoleListener;
+impl Listener for ConsoleListener {
+    fn handle(&mut self, event: &AppEvent) {
+        match event {
+            AppEvent::SongStarted {
+                meta: Some(song), ..
+            } => println!["Now playing: {} - {}", song.title, song.channel],
+            AppEvent::SongStarted { id, meta: None } => println!["Now playing: {id}"],
+            _ => {}
+        }
+    }
+}
+
+/// The listeners to attach on startup
+pub fn default_listeners() -> Vec<Box<dyn Listener>> {
+    vec![Box::new(ConsoleListener)]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::mpsc::{self, Receiver, Sender},
+        time::{Duration, Instant},
+    };
+
+    use super::{AppEvent, EventBus, Listener};
+
+    /// Forwards the ids of the events it gets, after waiting for its gate if it has one
+    struct Recorder {
+        gate: Option<Receiver<()>>,
+        out: Sender<String>,
+    }
+    impl Listener for Recorder {
+        fn handle(&mut self, event: &AppEvent) {
+            if let Some(gate) = &self.gate {
+                gate.recv().unwrap();
+            }
+            let id = match event {
+                AppEvent::SongStarted { id, .. }
+                | AppEvent::SongEnded { id, .. }
+                | AppEvent::SongAdded { id, .. } => id.clone(),
+                AppEvent::PlaybackPaused => "paused".into(),
+                AppEvent::PlaybackResumed => "resumed".into(),
+                AppEvent::Seeked { .. } => "seeked".into(),
+            };
+            self.out.send(id).unwrap();
+        }
+    }
+
+    fn started(id: &str) -> AppEvent {
+        AppEvent::SongStarted {
+            id: id.into(),
+            meta: None,
+        }
+    }
+
+    fn received(out: &Receiver<String>, count: usize) -> Vec<String> {
+        (0..count)
+            .map(|_| out.recv_timeout(Duration::from_secs(5)).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn listeners_get_events_in_order() {
+        let (first, first_out) = mpsc::channel();
+        let (second, second_out) = mpsc::channel();
+        let bus = EventBus::start(vec![
+            Box::new(Recorder {
+                gate: None,
+                out: first,
+            }),
+            Box::new(Recorder {
+                gate: None,
+                out: second,
+            }),
+        ]);
+
+        bus.emit(started("a"));
+        bus.emit(AppEvent::PlaybackPaused);
+        bus.emit(AppEvent::SongEnded {
+            id: "a".into(),
+            completed: false,
+        });
+        let expected = vec!["a", "paused", "a"];
+        assert_eq![received(&first_out, 3), expected];
+        assert_eq![received(&second_out, 3), expected];
+    }
+
+    #[test]
+    fn emitting_does_not_wait_for_listeners() {
+        let (gate, gate_out) = mpsc::channel();
+        let (out, events) = mpsc::channel();
+        let bus = EventBus::start(vec![Box::new(Recorder {
+            gate: Some(gate_out),
+            out,
+        })]);
+
+        // The listener is stuck on the first event until the gate opens
+        let start = Instant::now();
+        for i in 0..100 {
+            bus.emit(started(&i.to_string()));
+        }
+        assert![start.elapsed() < Duration::from_secs(1)];
+        assert![events.try_recv().is_err()];
+
+        for _ in 0..100 {
+            gate.send(()).unwrap();
+        }
+        let expected: Vec<String> = (0..100).map(|i| i.to_string()).collect();
+        assert_eq![received(&events, 100), expected];
+    }
+
+    #[test]
+    fn nothing_is_sent_without_listeners() {
+        let bus = EventBus::start(vec![]);
+        assert![bus.sender.is_none()];
+        bus.emit(AppEvent::PlaybackResumed);
+    }
+}
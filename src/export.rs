@@ -0,0 +1,208 @@
+//! Writes playlists out for other players, to files in the exports directory that are named
+//! after the playlist. Exporting again replaces the last export.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    backend_handler::BackendHandler,
+    caching::readers::FolderBasedReader,
+    playlist::Playlist,
+    settings::{SaveError, SongKey},
+    song::Song,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Each song's file if it was downloaded, otherwise its webpage
+    M3u8,
+    /// The playlist as it's saved
+    Json,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 2] = [ExportFormat::M3u8, ExportFormat::Json];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ExportFormat::M3u8 => "export m3u8",
+            ExportFormat::Json => "export json",
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::M3u8 => "m3u8",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+/// What's known of a song of the playlist when it's exported
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportEntry {
+    pub key: SongKey,
+    pub title: String,
+    pub duration: Option<f64>,
+    pub webpage_url: String,
+}
+
+impl ExportEntry {
+    /// Songs that aren't in the metadata cache are named with their key
+    pub fn new(key: SongKey, song: Option<&Song>) -> Self {
+        match song {
+            Some(song) => {
+                let artist = match &song.artists {
+                    Some(artists) if !artists.is_empty() => artists.join(", "),
+                    _ => song.channel.clone(),
+                };
+                let title = match artist.is_empty() {
+                    true => song.title.clone(),
+                    false => format!("{artist} - {}", song.title),
+                };
+                Self {
+                    key,
+                    title,
+                    duration: song.song_duration().known(),
+                    webpage_url: song.webpage_url.clone(),
+                }
+            }
+            None => Self {
+                title: key.clone(),
+                duration: None,
+                webpage_url: BackendHandler::request_url_from_id(&key),
+                key,
+            },
+        }
+    }
+}
+
+/// The playlist in the extended M3U format. `files` are the downloaded songs' files by key.
+pub fn m3u8(entries: &[ExportEntry], files: &HashMap<SongKey, PathBuf>) -> String {
+    let mut out = String::from("#EXTM3U\n");
+    for entry in entries {
+        let duration = match entry.duration {
+            Some(secs) => secs.round() as i64,
+            None => -1,
+        };
+        // A line break would end the entry early
+        let title = entry.title.replace(['\r', '\n'], " ");
+        let location = match files.get(&entry.key) {
+            Some(path) => path.to_string_lossy().into_owned(),
+            None => entry.webpage_url.clone(),
+        };
+        out.push_str(&format!("#EXTINF:{duration},{title}\n{location}\n"));
+    }
+    out
+}
+
+/// Where the playlist is exported to, named after it and told apart from others of the same
+/// name by the start of its id
+pub fn export_path(dir: &Path, playlist: &Playlist, format: ExportFormat) -> PathBuf {
+    let name: String = playlist
+        .name
+        .trim()
+        .chars()
+        .map(|c| match c.is_alphanumeric() || " -_".contains(c) {
+            true => c,
+            false => '_',
+        })
+        .collect();
+    let id = playlist.id.simple().to_string();
+    let stem = match name.is_empty() {
+        true => id,
+        false => format!("{name} {}", &id[..8]),
+    };
+    dir.join(format!("{stem}.{}", format.extension()))
+}
+
+/// Writes the playlist to its export file. `entries` are its songs in order, for the M3U8,
+/// which points to the files `sounds` has of them.
+pub async fn export(
+    playlist: Playlist,
+    entries: Vec<ExportEntry>,
+    sounds: FolderBasedReader,
+    format: ExportFormat,
+    dir: PathBuf,
+) -> Result<PathBuf, SaveError> {
+    let contents = match format {
+        ExportFormat::M3u8 => {
+            let files = match sounds.locate_all().await {
+                Ok(files) => files,
+                Err(e) => {
+                    println!["Exporting every song as its webpage, the sound index failed: {e}"];
+                    HashMap::new()
+                }
+            };
+            m3u8(&entries, &files)
+        }
+        ExportFormat::Json => {
+            serde_json::to_string_pretty(&playlist).map_err(|_| SaveError::Format)?
+        }
+    };
+    async_std::fs::create_dir_all(&dir)
+        .await
+        .map_err(|_| SaveError::File)?;
+    let path = export_path(&dir, &playlist, format);
+    async_std::fs::write(&path, contents)
+        .await
+        .map_err(|_| SaveError::Write)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, path::PathBuf};
+
+    use super::{export_path, m3u8, ExportEntry, ExportFormat};
+    use crate::{playlist::Playlist, song::Song};
+
+    #[test]
+    fn songs_export_as_their_file_or_webpage() {
+        let mut song = Song::basic();
+        song.title = "Title\nbroken".into();
+        song.artists = Some(vec!["Artist".into()]);
+        song.duration = 61.6;
+        song.webpage_url = "https://example.com/a".into();
+        let downloaded = ExportEntry::new("a".into(), Some(&song));
+        let streamed = ExportEntry::new("b".into(), Some(&song));
+        let unknown = ExportEntry::new("c".into(), None);
+        let files = HashMap::from([("a".to_string(), PathBuf::from("/songs/a.mp3"))]);
+
+        let lines: Vec<String> = m3u8(&[downloaded, streamed, unknown], &files)
+            .lines()
+            .map(String::from)
+            .collect();
+        assert_eq![
+            lines,
+            [
+                "#EXTM3U",
+                "#EXTINF:62,Artist - Title broken",
+                "/songs/a.mp3",
+                "#EXTINF:62,Artist - Title broken",
+                "https://example.com/a",
+                "#EXTINF:-1,c",
+                "https://music.youtube.com/watch?v=c",
+            ]
+        ];
+    }
+
+    #[test]
+    fn export_names_are_safe_file_names() {
+        let playlist = Playlist {
+            name: " a/b: c ".into(),
+            ..Default::default()
+        };
+        let id = playlist.id.simple().to_string();
+        let expected = format!("exports/a_b_ c {}.m3u8", &id[..8]);
+        let path = export_path(&PathBuf::from("exports"), &playlist, ExportFormat::M3u8);
+        assert_eq![path, PathBuf::from(expected)];
+
+        let untitled = Playlist::default();
+        let expected = format!("exports/{}.json", untitled.id.simple());
+        let path = export_path(&PathBuf::from("exports"), &untitled, ExportFormat::Json);
+        assert_eq![path, PathBuf::from(expected)];
+    }
+}
@@ -0,0 +1,390 @@
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Local};
+use iced::{
+    widget::{
+        button, column, container, row, scrollable, scrollable::RelativeOffset, text, Column, Space,
+    },
+    Alignment, Command, Element, Length,
+};
+
+use crate::{
+    search_window::LOCAL_SEARCH_PREFIX,
+    song::{Song, SongSource},
+    ytmrs::YtmrsMsg,
+};
+
+/// How many failures are remembered, the oldest are forgotten first
+pub const MAX_FAILURES: usize = 300;
+/// How tall the list of failures gets before it scrolls
+const LIST_HEIGHT: f32 = 200.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    Download,
+    MetadataFetch,
+    CacheWrite,
+    BackendRequest,
+}
+
+impl FailureKind {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Self::Download => "download failed",
+            Self::MetadataFetch => "metadata fetch failed",
+            Self::CacheWrite => "cache write failed",
+            Self::BackendRequest => "backend request failed",
+        }
+    }
+}
+
+/// Enough of a failed operation to run it again
+#[derive(Debug, Clone)]
+pub enum Retry {
+    Download { key: String, play: bool },
+    FetchMetadata { id: String, source: SongSource },
+    WriteSong(Box<Song>),
+    Search(String),
+}
+
+impl Retry {
+    /// Whether running it again reaches out to the backend
+    pub fn needs_backend(&self) -> bool {
+        match self {
+            Self::Download { .. } | Self::FetchMetadata { .. } => true,
+            Self::WriteSong(_) => false,
+            Self::Search(query) => !query.starts_with(LOCAL_SEARCH_PREFIX),
+        }
+    }
+
+    /// The message that runs the operation again
+    pub fn message(self) -> YtmrsMsg {
+        match self {
+            Self::Download { key, play } => YtmrsMsg::DownloadSong(key, play),
+            Self::FetchMetadata { id, source } => YtmrsMsg::SearchedKeysReceived {
+                existing: Default::default(),
+                missing: vec![id],
+                source,
+            },
+            Self::WriteSong(song) => YtmrsMsg::WriteSongs(vec![*song]),
+            Self::Search(query) => YtmrsMsg::ReopenSource(query),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Failure {
+    pub kind: FailureKind,
+    /// The song key, id or query that failed
+    pub subject: String,
+    pub error: String,
+    /// None if the operation can't be run again from here
+    pub retry: Option<Retry>,
+}
+
+impl Failure {
+    pub fn new(kind: FailureKind, subject: String, error: String, retry: Option<Retry>) -> Self {
+        Self {
+            kind,
+            subject,
+            error,
+            retry,
+        }
+    }
+}
+
+/// A failure and how often it happened
+#[derive(Debug, Clone)]
+pub struct FailureEntry {
+    pub failure: Failure,
+    pub count: usize,
+    pub last_seen: DateTime<Local>,
+}
+
+#[derive(Debug, Clone)]
+pub enum FailuresMsg {
+    Toggle,
+    Retry(FailureKind, String),
+    ClearAll,
+}
+
+/// The failures seen this session, one entry per kind and subject
+#[derive(Debug, Default)]
+pub struct Failures {
+    /// Oldest first
+    entries: VecDeque<FailureEntry>,
+    expanded: bool,
+    /// The entry a notification's details were asked for
+    highlighted: Option<(FailureKind, String)>,
+}
+
+fn list_id() -> scrollable::Id {
+    scrollable::Id::new("failures")
+}
+
+impl Failures {
+    pub fn record(&mut self, failure: Failure, now: DateTime<Local>) {
+        println![
+            "{}: {} ({})",
+            failure.kind.describe(),
+            failure.subject,
+            failure.error
+        ];
+        let count = match self.position(failure.kind, &failure.subject) {
+            Some(idx) => self.entries.remove(idx).map_or(0, |entry| entry.count),
+            None => 0,
+        };
+        self.entries.push_back(FailureEntry {
+            failure,
+            count: count + 1,
+            last_seen: now,
+        });
+        while self.entries.len() > MAX_FAILURES {
+            self.entries.pop_front();
+        }
+    }
+
+    /// Forgets the failure once the operation succeeded. Returns whether there was one.
+    pub fn resolve(&mut self, kind: FailureKind, subject: &str) -> bool {
+        match self.position(kind, subject) {
+            Some(idx) => {
+                self.entries.remove(idx);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The operation to run again. The entry stays until it succeeds.
+    pub fn retry(&self, kind: FailureKind, subject: &str) -> Option<Retry> {
+        self.position(kind, subject)
+            .and_then(|idx| self.entries[idx].failure.retry.clone())
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn toggle(&mut self) {
+        self.expanded = !self.expanded;
+    }
+
+    /// Opens the panel on the failure, picking it out and scrolling to it
+    pub fn reveal<M: 'static>(&mut self, kind: FailureKind, subject: String) -> Command<M> {
+        self.expanded = true;
+        let newer = self
+            .position(kind, &subject)
+            .map(|idx| self.entries.len() - 1 - idx);
+        self.highlighted = Some((kind, subject));
+        match newer {
+            Some(row) => scrollable::snap_to(
+                list_id(),
+                RelativeOffset {
+                    x: 0.0,
+                    y: row as f32 / (self.entries.len() - 1).max(1) as f32,
+                },
+            ),
+            None => Command::none(),
+        }
+    }
+
+    fn is_highlighted(&self, failure: &Failure) -> bool {
+        self.highlighted
+            .as_ref()
+            .is_some_and(|(kind, subject)| failure.kind == *kind && failure.subject == *subject)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Newest first
+    pub fn entries(&self) -> impl Iterator<Item = &FailureEntry> {
+        self.entries.iter().rev()
+    }
+
+    fn position(&self, kind: FailureKind, subject: &str) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|entry| entry.failure.kind == kind && entry.failure.subject == subject)
+    }
+
+    /// Retries that need the backend are greyed out while it's offline
+    pub fn view(&self, offline: bool) -> Option<Element<FailuresMsg>> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let header = row![
+            text(format!("{} recently failed", self.len())).size(20),
+            Space::with_width(Length::Fill),
+            button(match self.expanded {
+                true => "hide",
+                false => "show",
+            })
+            .on_press(FailuresMsg::Toggle),
+            button("clear all").on_press(FailuresMsg::ClearAll),
+        ]
+        .spacing(4)
+        .align_items(Alignment::Center);
+
+        let mut panel = column![header].spacing(4);
+        if self.expanded {
+            let entries = self.entries().map(|entry| {
+                let failure = &entry.failure;
+                let times = match entry.count {
+                    1 => String::new(),
+                    n => format!(" x{n}"),
+                };
+                let marker = match self.is_highlighted(failure) {
+                    true => "> ",
+                    false => "",
+                };
+                row![text(format!(
+                    "{marker}{} {}: {}{times}, last at {}",
+                    failure.kind.describe(),
+                    failure.subject,
+                    failure.error,
+                    entry.last_seen.format("%H:%M:%S"),
+                ))
+                .width(Length::Fill)]
+                .push_maybe(failure.retry.as_ref().map(|retry| {
+                    let possible = !(offline && retry.needs_backend());
+                    button("retry").on_press_maybe(
+                        possible.then(|| FailuresMsg::Retry(failure.kind, failure.subject.clone())),
+                    )
+                }))
+                .align_items(Alignment::Center)
+                .into()
+            });
+            panel = panel.push(
+                scrollable(Column::with_children(entries).spacing(4))
+                    .id(list_id())
+                    .height(Length::Shrink)
+                    .max_height(LIST_HEIGHT),
+            );
+        }
+
+        Some(container(panel).padding(10).width(Length::Fill).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, TimeDelta};
+
+    use super::{Failure, FailureKind, Failures, Retry, MAX_FAILURES};
+    use crate::{fixtures, search_window::LOCAL_SEARCH_PREFIX, song::SongSource, ytmrs::YtmrsMsg};
+
+    fn failure(kind: FailureKind, subject: &str, error: &str) -> Failure {
+        Failure::new(
+            kind,
+            subject.into(),
+            error.into(),
+            Some(Retry::Search(subject.into())),
+        )
+    }
+
+    #[test]
+    fn failures_are_deduplicated_by_kind_and_subject() {
+        let mut failures = Failures::default();
+        let start = Local::now();
+        failures.record(failure(FailureKind::Download, "a", "first"), start);
+        failures.record(failure(FailureKind::Download, "b", "other"), start);
+        failures.record(failure(FailureKind::CacheWrite, "a", "same subject"), start);
+        let later = start + TimeDelta::seconds(5);
+        failures.record(failure(FailureKind::Download, "a", "second"), later);
+        assert_eq![failures.len(), 3];
+
+        // The repeated one moves to the top with the latest error
+        let newest = failures.entries().next().unwrap();
+        assert_eq![newest.failure.subject, "a"];
+        assert_eq![newest.failure.kind, FailureKind::Download];
+        assert_eq![newest.failure.error, "second"];
+        assert_eq![newest.count, 2];
+        assert_eq![newest.last_seen, later];
+
+        assert![failures.resolve(FailureKind::Download, "a")];
+        assert![!failures.resolve(FailureKind::Download, "a")];
+        assert![failures.retry(FailureKind::CacheWrite, "a").is_some()];
+        assert_eq![failures.len(), 2];
+        failures.clear();
+        assert![failures.view(false).is_none()];
+    }
+
+    #[test]
+    fn only_the_newest_failures_are_kept() {
+        let mut failures = Failures::default();
+        let now = Local::now();
+        for i in 0..MAX_FAILURES + 10 {
+            failures.record(
+                failure(FailureKind::BackendRequest, &i.to_string(), ""),
+                now,
+            );
+        }
+        assert_eq![failures.len(), MAX_FAILURES];
+        assert![failures.retry(FailureKind::BackendRequest, "9").is_none()];
+        assert![failures.retry(FailureKind::BackendRequest, "10").is_some()];
+    }
+
+    #[test]
+    fn retries_send_the_original_operation() {
+        let msg = Retry::Download {
+            key: "k".into(),
+            play: true,
+        }
+        .message();
+        assert![matches!(msg, YtmrsMsg::DownloadSong(key, true) if key == "k")];
+
+        let source = SongSource::Search { query: "q".into() };
+        let msg = Retry::FetchMetadata {
+            id: "id".into(),
+            source: source.clone(),
+        }
+        .message();
+        assert![matches!(
+            msg,
+            YtmrsMsg::SearchedKeysReceived { existing, missing, source: s }
+                if existing.is_empty() && missing == ["id"] && s == source
+        )];
+
+        let song = fixtures::songs(3, 1).remove(0);
+        let msg = Retry::WriteSong(Box::new(song.clone())).message();
+        assert![matches!(msg, YtmrsMsg::WriteSongs(songs) if songs[0].id == song.id)];
+
+        let msg = Retry::Search("query".into()).message();
+        assert![matches!(msg, YtmrsMsg::ReopenSource(query) if query == "query")];
+    }
+
+    #[test]
+    fn only_backend_retries_wait_for_it() {
+        assert![Retry::Search("query".into()).needs_backend()];
+        assert![!Retry::Search(format!("{LOCAL_SEARCH_PREFIX}query")).needs_backend()];
+        let song = fixtures::songs(1, 1).remove(0);
+        assert![!Retry::WriteSong(Box::new(song)).needs_backend()];
+    }
+
+    #[test]
+    fn details_open_the_panel_on_their_failure() {
+        let mut failures = Failures::default();
+        let now = Local::now();
+        failures.record(failure(FailureKind::Download, "a", ""), now);
+        failures.record(failure(FailureKind::Download, "b", ""), now);
+
+        let _: iced::Command<()> = failures.reveal(FailureKind::Download, "a".into());
+        assert![failures.expanded];
+        let highlighted: Vec<&str> = failures
+            .entries()
+            .filter(|entry| failures.is_highlighted(&entry.failure))
+            .map(|entry| entry.failure.subject.as_str())
+            .collect();
+        assert_eq![highlighted, ["a"]];
+        // Other kinds of the same subject aren't picked out
+        let other = failure(FailureKind::CacheWrite, "a", "");
+        assert![!failures.is_highlighted(&other)];
+    }
+}
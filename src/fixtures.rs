@@ -0,0 +1,508 @@
+//! Generated songs, audio and thumbnails, for developing the UI without a real library.
+//!
+//! Everything is derived from a seed, so the same seed always gives the same dataset.
+//! Build with the `demo` feature and launch with `--demo` to run the app on it. The files are
+//! written to a temporary directory that's removed on exit, and the user's data is never read.
+
+use std::{f32::consts::TAU, io::Cursor, path::PathBuf};
+
+use image::{ImageFormat, Rgb, RgbImage};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use crate::{
+    caching::readers::{
+        CacheReader, FileData, FolderBasedReader, LazyFolderBasedReader, LineBasedReader,
+    },
+    song::{Song, SongSource},
+    song_operations::{ActualRecursiveOps, ConstructorItem, SongOpConstructor},
+};
+
+#[cfg(feature = "demo")]
+pub const DEMO_SEED: u64 = 42;
+#[cfg(feature = "demo")]
+pub const DEMO_SONGS: usize = 40;
+
+pub const SAMPLE_RATE: u32 = 22_050;
+/// The clips are short, so their length doesn't match the duration in the metadata
+pub const CLIP_SECONDS: u32 = 6;
+pub const THUMBNAIL_SIZE: u32 = 120;
+
+const ADJECTIVES: &[&str] = &[
+    "Golden", "Silent", "Electric", "Broken", "Velvet", "Midnight", "Hollow", "Crimson", "Distant",
+    "Paper", "Neon", "Wild", "Quiet", "Burning", "Frozen", "Lucky", "Northern", "Glass",
+];
+const NOUNS: &[&str] = &[
+    "Harbor",
+    "Signal",
+    "River",
+    "Lantern",
+    "Echo",
+    "Garden",
+    "Mirror",
+    "Horizon",
+    "Satellite",
+    "Ocean",
+    "Window",
+    "Fever",
+    "Highway",
+    "Shadow",
+    "Ember",
+    "Parade",
+    "Compass",
+    "Tide",
+];
+const VERBS: &[&str] = &[
+    "Chasing",
+    "Waiting for",
+    "Leaving",
+    "Dancing with",
+    "Dreaming of",
+    "Running from",
+    "Finding",
+];
+const VERSIONS: &[&str] = &["Live", "Acoustic", "Remix", "Demo", "Radio Edit"];
+const FIRST_NAMES: &[&str] = &[
+    "Mara", "Theo", "Ines", "Jonah", "Priya", "Kai", "Lena", "Omar", "Sofia", "Ezra", "Noor",
+];
+const LAST_NAMES: &[&str] = &[
+    "Lindqvist",
+    "Okafor",
+    "Reyes",
+    "Hart",
+    "Nakamura",
+    "Castell",
+    "Beaumont",
+    "Moreau",
+    "Sato",
+];
+const GENRES: &[&str] = &[
+    "indie",
+    "synthpop",
+    "folk",
+    "ambient",
+    "jazz",
+    "lo-fi",
+    "rock",
+    "soul",
+    "electronic",
+];
+
+/// Semitones of a pentatonic scale, which sounds fine in any order
+const SCALE: &[i32] = &[0, 2, 4, 7, 9, 12];
+
+fn pick<'a>(rng: &mut StdRng, words: &[&'a str]) -> &'a str {
+    words.choose(rng).unwrap()
+}
+
+fn title(rng: &mut StdRng) -> String {
+    match rng.gen_range(0..4) {
+        0 => format!("{} {}", pick(rng, ADJECTIVES), pick(rng, NOUNS)),
+        1 => format!("{} in the {}", pick(rng, NOUNS), pick(rng, NOUNS)),
+        2 => format!("{} the {}", pick(rng, VERBS), pick(rng, NOUNS)),
+        _ => format!(
+            "{} {} ({})",
+            pick(rng, ADJECTIVES),
+            pick(rng, NOUNS),
+            pick(rng, VERSIONS)
+        ),
+    }
+}
+
+fn artist(rng: &mut StdRng) -> String {
+    match rng.gen_bool(0.5) {
+        true => format!("{} {}", pick(rng, FIRST_NAMES), pick(rng, LAST_NAMES)),
+        false => format!("The {} {}s", pick(rng, ADJECTIVES), pick(rng, NOUNS)),
+    }
+}
+
+/// An id shaped like a Youtube one
+fn video_id(rng: &mut StdRng) -> String {
+    const CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+    (0..11)
+        .map(|_| *CHARS.choose(rng).unwrap() as char)
+        .collect()
+}
+
+/// Songs by a handful of artists, each with an album or two
+pub fn songs(seed: u64, count: usize) -> Vec<Song> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let artists: Vec<(String, Vec<String>)> = (0..count / 4 + 1)
+        .map(|_| {
+            let name = artist(&mut rng);
+            let albums = (0..rng.gen_range(1..=2))
+                .map(|_| format!("{} {}s", pick(&mut rng, ADJECTIVES), pick(&mut rng, NOUNS)))
+                .collect();
+            (name, albums)
+        })
+        .collect();
+
+    (0..count)
+        .map(|_| {
+            let (name, albums) = artists.choose(&mut rng).unwrap();
+            let id = video_id(&mut rng);
+            let mut credited = vec![name.clone()];
+            if rng.gen_bool(0.2) {
+                credited.push(artist(&mut rng));
+            }
+            let tag_count = rng.gen_range(0..=3);
+            let mut tags: Vec<String> = GENRES
+                .choose_multiple(&mut rng, tag_count)
+                .map(|genre| genre.to_string())
+                .collect();
+            tags.sort();
+            Song {
+                id: id.clone(),
+                extractor_key: Some("Youtube".into()),
+                title: title(&mut rng),
+                channel: name.clone(),
+                view_count: Some(rng.gen_range(1_000..50_000_000)),
+                thumbnail: format!("https://i.ytimg.com/vi/{id}/hqdefault.jpg"),
+                album: rng
+                    .gen_bool(0.8)
+                    .then(|| albums.choose(&mut rng).unwrap().clone()),
+                webpage_url: format!("https://music.youtube.com/watch?v={id}"),
+                duration: rng.gen_range(95..420) as f64,
+                artists: Some(credited),
+                tags,
+                rating: rng.gen_bool(0.25).then(|| rng.gen_range(1..=5)),
+                source: SongSource::Search {
+                    query: name.to_lowercase(),
+                },
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+/// A playlist with every kind of group, up to three groups deep.
+/// Songs are reused when there aren't enough of them.
+pub fn playlist(songs: &[Song]) -> SongOpConstructor {
+    let mut keys = songs.iter().map(|song| song.id.clone()).cycle();
+    let mut next = || ConstructorItem::from(keys.next().unwrap());
+    let group = |op, items| ConstructorItem::Operation(SongOpConstructor::new(op, items, None));
+
+    SongOpConstructor::from(vec![
+        next(),
+        next(),
+        ConstructorItem::Operation(
+            SongOpConstructor::new(ActualRecursiveOps::LoopNTimes, vec![next(), next()], None)
+                .with_n(2),
+        ),
+        ConstructorItem::Operation(
+            SongOpConstructor::new(
+                ActualRecursiveOps::Stretch,
+                vec![
+                    next(),
+                    group(ActualRecursiveOps::RandomPlay, vec![next(), next(), next()]),
+                ],
+                None,
+            )
+            .with_n(3),
+        ),
+        group(
+            ActualRecursiveOps::PlayOnce,
+            vec![
+                next(),
+                group(
+                    ActualRecursiveOps::SingleRandom,
+                    vec![
+                        next(),
+                        next(),
+                        group(ActualRecursiveOps::InfiniteRandom, vec![next(), next()]),
+                    ],
+                ),
+            ],
+        ),
+        group(ActualRecursiveOps::InfiniteLoop, vec![next(), next()]),
+        group(ActualRecursiveOps::WeightedRandom, vec![next(), next()]),
+        group(ActualRecursiveOps::LoopUntilDuration, vec![next(), next()]),
+    ])
+}
+
+/// Wraps 16 bit mono samples in a WAV file
+fn wav(samples: &[i16]) -> Vec<u8> {
+    let data_len = (samples.len() * 2) as u32;
+    let mut out = Vec::with_capacity(44 + data_len as usize);
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(36 + data_len).to_le_bytes());
+    out.extend_from_slice(b"WAVEfmt ");
+    out.extend_from_slice(&16u32.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    out.extend_from_slice(&1u16.to_le_bytes()); // mono
+    out.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    out.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes()); // bytes per second
+    out.extend_from_slice(&2u16.to_le_bytes()); // bytes per frame
+    out.extend_from_slice(&16u16.to_le_bytes());
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        out.extend_from_slice(&sample.to_le_bytes());
+    }
+    out
+}
+
+/// A few notes of a scale with a little noise, as a WAV file
+fn clip(rng: &mut StdRng) -> Vec<u8> {
+    const NOTES: u32 = 4;
+    let base = *[196.0, 220.0, 261.63].choose(rng).unwrap();
+    let noise = rng.gen_range(0.0..0.03);
+    let note_len = (SAMPLE_RATE * CLIP_SECONDS / NOTES) as usize;
+
+    let mut samples = Vec::with_capacity(note_len * NOTES as usize);
+    for _ in 0..NOTES {
+        let step = *SCALE.choose(rng).unwrap();
+        let freq = base * 2f32.powf(step as f32 / 12.0);
+        for i in 0..note_len {
+            let t = i as f32 / SAMPLE_RATE as f32;
+            // A quick attack, then fading out until the next note
+            let envelope = (t / 0.02).min(1.0) * (1.0 - i as f32 / note_len as f32);
+            let tone = (TAU * freq * t).sin() * 0.4 * envelope;
+            let value = tone + rng.gen_range(-noise..=noise);
+            samples.push((value.clamp(-1.0, 1.0) * i16::MAX as f32) as i16);
+        }
+    }
+    wav(&samples)
+}
+
+/// A diagonal gradient with a few circles on it, as a PNG file
+fn thumbnail(rng: &mut StdRng) -> Vec<u8> {
+    let mut color = || Rgb([rng.gen::<u8>(), rng.gen::<u8>(), rng.gen::<u8>()]);
+    let (from, to) = (color(), color());
+    let circles: Vec<(f32, f32, f32, Rgb<u8>)> = (0..3)
+        .map(|_| {
+            let size = THUMBNAIL_SIZE as f32;
+            (
+                rng.gen_range(0.0..size),
+                rng.gen_range(0.0..size),
+                rng.gen_range(size / 10.0..size / 3.0),
+                Rgb([rng.gen(), rng.gen(), rng.gen()]),
+            )
+        })
+        .collect();
+
+    let image = RgbImage::from_fn(THUMBNAIL_SIZE, THUMBNAIL_SIZE, |x, y| {
+        let (x, y) = (x as f32, y as f32);
+        let circle = circles
+            .iter()
+            .find(|(cx, cy, r, _)| (x - cx).powi(2) + (y - cy).powi(2) <= r * r);
+        match circle {
+            Some((_, _, _, color)) => *color,
+            None => {
+                let t = (x + y) / (2.0 * THUMBNAIL_SIZE as f32);
+                let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t) as u8;
+                Rgb([0, 1, 2].map(|c| mix(from.0[c], to.0[c])))
+            }
+        }
+    });
+    let mut png = vec![];
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .unwrap();
+    png
+}
+
+/// Everything the app needs to run without a library or a connection
+#[derive(Debug, Clone)]
+pub struct Dataset {
+    pub songs: Vec<Song>,
+    pub playlist: SongOpConstructor,
+    /// The audio of every song
+    pub clips: Vec<FileData<Vec<u8>>>,
+    /// The thumbnail of every song, by id
+    pub thumbnails: Vec<(String, Vec<u8>)>,
+}
+
+impl Dataset {
+    pub fn generate(seed: u64, count: usize) -> Self {
+        let songs = songs(seed, count);
+        // Separate from the songs' generator, so the songs are the same as `songs` gives
+        let mut rng = StdRng::seed_from_u64(seed.wrapping_add(1));
+        let mut clips = vec![];
+        let mut thumbnails = vec![];
+        for song in &songs {
+            clips.push(FileData::new(song.id.clone(), clip(&mut rng)));
+            thumbnails.push((song.id.clone(), thumbnail(&mut rng)));
+        }
+        Self {
+            playlist: playlist(&songs),
+            songs,
+            clips,
+            thumbnails,
+        }
+    }
+
+    /// Writes the dataset through the same readers the app reads it back with
+    pub async fn write(
+        &self,
+        songs: &LineBasedReader,
+        sounds: &FolderBasedReader,
+        thumbnails: &LazyFolderBasedReader,
+    ) -> Result<(), std::io::Error> {
+        if let Some(dir) = songs.filepath.parent() {
+            async_std::fs::create_dir_all(dir).await?;
+        }
+        songs.extend(&self.songs, true).await?;
+        sounds.extend(&self.clips, true).await?;
+
+        let mut index = vec![];
+        for (id, png) in &self.thumbnails {
+            let path = PathBuf::from(id).with_extension("png");
+            async_std::fs::write(thumbnails.convert_path(&path), png).await?;
+            index.push(FileData::new(id.clone(), path));
+        }
+        thumbnails.extend(&index, true).await
+    }
+
+    /// Settings that open the demo playlist
+    #[cfg(feature = "demo")]
+    pub fn settings(&self) -> crate::settings::YTMRSettings {
+        crate::settings::YTMRSettings {
+            playlist: crate::playlist::Playlist {
+                name: "Demo".into(),
+                constructor: self.playlist.clone(),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+}
+
+/// Moves the app onto a generated dataset when run with `--demo`.
+/// Returns the directory holding it, which is removed when dropped.
+#[cfg(feature = "demo")]
+pub fn install_if_requested() -> Option<tempfile::TempDir> {
+    use crate::settings::{
+        redirect_dirs, song_audio_path, song_metadata_path, thumbnails_directory,
+    };
+
+    if !std::env::args().any(|arg| arg == "--demo") {
+        return None;
+    }
+    // Falling back to the real directories would defeat the point
+    let dir = tempfile::tempdir().expect("Failed to create the demo directory");
+    redirect_dirs(dir.path().to_path_buf());
+    println!["Running on demo data in {:?}", dir.path()];
+
+    let dataset = Dataset::generate(DEMO_SEED, DEMO_SONGS);
+    async_std::task::block_on(async {
+        let written = dataset
+            .write(
+                &LineBasedReader::new(song_metadata_path()),
+                &FolderBasedReader::new(song_audio_path()),
+                &LazyFolderBasedReader::new(thumbnails_directory()),
+            )
+            .await;
+        println!["Writing demo data: {written:?}"];
+        println![
+            "Writing demo settings: {:?}",
+            dataset.settings().save().await
+        ];
+    });
+    Some(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, io::Cursor};
+
+    use kira::sound::static_sound::StaticSoundData;
+
+    use crate::{
+        caching::readers::{
+            CacheReader, FileData, FolderBasedReader, LazyFolderBasedReader, LineBasedReader,
+            SourceItemPair,
+        },
+        song::Song,
+        song_operations::{ActualRecursiveOps, ConstructorItem, SongOpConstructor},
+        thumbnails::{resolve, ThumbnailSize},
+    };
+
+    use super::{Dataset, CLIP_SECONDS, THUMBNAIL_SIZE};
+
+    fn json<T: serde::Serialize>(value: &T) -> String {
+        serde_json::to_string(value).unwrap()
+    }
+
+    /// The operations of every group in the tree, and how deep the groups go
+    fn groups(tree: &SongOpConstructor) -> (HashSet<&'static str>, usize) {
+        let mut ops = HashSet::from([tree.play.operation.as_str()]);
+        let mut depth = 0;
+        for item in &tree.list {
+            if let ConstructorItem::Operation(op) = item {
+                let (inner, inner_depth) = groups(op);
+                ops.extend(inner);
+                depth = depth.max(inner_depth + 1);
+            }
+        }
+        (ops, depth)
+    }
+
+    #[test]
+    fn same_seed_same_dataset() {
+        let a = Dataset::generate(7, 6);
+        let b = Dataset::generate(7, 6);
+        assert_eq![json(&a.songs), json(&b.songs)];
+        assert_eq![json(&a.playlist), json(&b.playlist)];
+        assert_eq![json(&a.clips), json(&b.clips)];
+        assert_eq![a.thumbnails, b.thumbnails];
+
+        let other = Dataset::generate(8, 6);
+        assert_ne![json(&a.songs), json(&other.songs)];
+        assert_ne![a.thumbnails, other.thumbnails];
+
+        // The songs don't depend on whether the media is generated alongside them
+        assert_eq![json(&super::songs(7, 6)), json(&a.songs)];
+    }
+
+    #[test]
+    fn playlist_has_every_kind_of_group() {
+        let dataset = Dataset::generate(1, 4);
+        let (ops, depth) = groups(&dataset.playlist);
+        let all = [
+            ActualRecursiveOps::PlayOnce,
+            ActualRecursiveOps::LoopNTimes,
+            ActualRecursiveOps::Stretch,
+            ActualRecursiveOps::InfiniteLoop,
+            ActualRecursiveOps::RandomPlay,
+            ActualRecursiveOps::SingleRandom,
+            ActualRecursiveOps::InfiniteRandom,
+            ActualRecursiveOps::WeightedRandom,
+            ActualRecursiveOps::LoopUntilDuration,
+        ];
+        assert_eq![ops, HashSet::from(all.map(|op| op.as_str()))];
+        assert_eq![depth, 3];
+
+        // With fewer songs than places, every song is still used
+        let keys: HashSet<_> = dataset.playlist.all_song_keys_rec().collect();
+        assert_eq![keys.len(), 4];
+    }
+
+    #[test]
+    fn written_dataset_plays_and_shows() {
+        async_std::task::block_on(async {
+            let dir = tempfile::tempdir().unwrap();
+            let songs = LineBasedReader::new(dir.path().join("data").join("songs.ndjson"));
+            let sounds = FolderBasedReader::new(dir.path().join("songs"));
+            let thumbnails = LazyFolderBasedReader::new(dir.path().join("thumbs"));
+            let dataset = Dataset::generate(3, 5);
+            dataset.write(&songs, &sounds, &thumbnails).await.unwrap();
+
+            let read: Vec<SourceItemPair<String, Song>> = songs.read().await.unwrap();
+            assert_eq![read.len(), 5];
+
+            let clips: Vec<SourceItemPair<String, FileData<Vec<u8>>>> =
+                sounds.read().await.unwrap();
+            assert_eq![clips.len(), 5];
+            let SourceItemPair(_, clip) = clips.into_iter().next().unwrap();
+            let sound = StaticSoundData::from_cursor(Cursor::new(clip.into_data())).unwrap();
+            let seconds = sound.duration().as_secs_f64();
+            assert![(seconds - CLIP_SECONDS as f64).abs() < 0.01];
+
+            let ids = read.iter().map(|pair| pair.1.id.clone()).collect();
+            let thumbs = resolve(&thumbnails, &ids, ThumbnailSize::Large).await;
+            assert_eq![thumbs.len(), 5];
+            let image = image::open(thumbs.values().next().unwrap()).unwrap();
+            assert_eq![image.width(), THUMBNAIL_SIZE];
+        });
+    }
+}
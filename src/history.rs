@@ -0,0 +1,337 @@
+//! The songs that were played, and how they came to play. Each play is added to the end of an
+//! NDJSON file as it starts. Only the newest records are read back in, and the file is cut
+//! down to those as it grows, so it never has to be read whole.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    future::Future,
+};
+
+use chrono::{DateTime, Local};
+use iced::{
+    widget::{button, column, text, Column},
+    Element, Length,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    caching::{readers::LineBasedReader, IDed},
+    settings::{history_path, SongKey, DEFAULT_HISTORY_LIMIT},
+    song_operations::SongOpConstructor,
+};
+
+/// How many rows the history shows
+const HISTORY_SHOWN: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlayTrigger {
+    /// Picked from the playlist, the search or a menu
+    Picked,
+    /// Came up after the song before it ended
+    #[default]
+    Queue,
+    /// Skipped to with the tracker's buttons or from the queue
+    Skipped,
+    /// Picked up where the last session left off
+    Resumed,
+    /// Played again from the history
+    History,
+}
+
+impl PlayTrigger {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Picked => "picked",
+            Self::Queue => "queued",
+            Self::Skipped => "skipped to",
+            Self::Resumed => "resumed",
+            Self::History => "replayed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlayRecord {
+    /// Tells the plays of the same song apart
+    id: String,
+    pub key: SongKey,
+    pub at: DateTime<Local>,
+    pub trigger: PlayTrigger,
+}
+
+impl PlayRecord {
+    pub fn new(key: SongKey, trigger: PlayTrigger, at: DateTime<Local>) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            key,
+            at,
+            trigger,
+        }
+    }
+}
+
+impl IDed<String> for PlayRecord {
+    fn id(&self) -> &String {
+        &self.id
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum HistoryMsg {
+    Replay(SongKey),
+}
+
+#[derive(Debug)]
+pub struct History {
+    reader: LineBasedReader,
+    limit: usize,
+    /// The newest plays, oldest first
+    recent: VecDeque<PlayRecord>,
+    /// How many lines the file has, None until it's been read
+    in_file: Option<usize>,
+    /// Changes whenever a play is added or forgotten
+    revision: u64,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new(LineBasedReader::new(history_path()), DEFAULT_HISTORY_LIMIT)
+    }
+}
+
+impl History {
+    pub fn new(reader: LineBasedReader, limit: usize) -> Self {
+        Self {
+            reader,
+            limit: limit.max(1),
+            recent: VecDeque::new(),
+            in_file: None,
+            revision: 0,
+        }
+    }
+
+    /// Reads the newest plays from the file, for [`History::loaded`]
+    pub fn load(&self) -> impl Future<Output = Result<(Vec<PlayRecord>, bool), String>> {
+        let reader = self.reader.clone();
+        let limit = self.limit;
+        async move { reader.tail(limit).await.map_err(|e| e.to_string()) }
+    }
+
+    /// Takes in the plays read from the file. `more` is whether the file had older ones.
+    /// Songs played while it was read are kept after them. Returns the file's trim, if any.
+    pub fn loaded(&mut self, records: Vec<PlayRecord>, more: bool) -> impl Future<Output = ()> {
+        let played: Vec<PlayRecord> = self
+            .recent
+            .drain(..)
+            .filter(|played| records.iter().all(|record| record.id != played.id))
+            .collect();
+        let in_file = records.len() + played.len();
+        self.recent.extend(records);
+        self.recent.extend(played);
+        self.in_file = Some(in_file);
+        self.revision = self.revision.wrapping_add(1);
+        let trimmed = match more {
+            true => self.trim(),
+            false => self.forget_oldest(),
+        };
+        Self::write(self.reader.clone(), None, trimmed)
+    }
+
+    /// Records that the song started playing. Returns the write to the file, which is run
+    /// away from the UI.
+    pub fn record(
+        &mut self,
+        key: SongKey,
+        trigger: PlayTrigger,
+        at: DateTime<Local>,
+    ) -> impl Future<Output = ()> {
+        let record = PlayRecord::new(key, trigger, at);
+        self.in_file = self.in_file.map(|lines| lines + 1);
+        self.recent.push_back(record.clone());
+        self.revision = self.revision.wrapping_add(1);
+        let trimmed = self.forget_oldest();
+        Self::write(self.reader.clone(), Some(record), trimmed)
+    }
+
+    /// Returns the file's trim, if the new limit needs one
+    pub fn set_limit(&mut self, limit: usize) -> impl Future<Output = ()> {
+        self.limit = limit.max(1);
+        let trimmed = self.forget_oldest();
+        Self::write(self.reader.clone(), None, trimmed)
+    }
+
+    /// Adds the play to the file, then replaces the file with the trimmed plays
+    async fn write(
+        reader: LineBasedReader,
+        played: Option<PlayRecord>,
+        trimmed: Option<Vec<PlayRecord>>,
+    ) {
+        if let Some(played) = played {
+            if let Err(e) = reader.append(&[played]).await {
+                println!["Failed to add the play to the history: {e:?}"];
+            }
+        }
+        if let Some(records) = trimmed {
+            if let Err(e) = reader.replace(&records).await {
+                println!["Failed to trim the history: {e:?}"];
+            }
+        }
+    }
+
+    /// Keeps only the newest plays in memory, and cuts the file down to them once it has
+    /// grown a tenth over the limit, so it isn't rewritten on every play
+    fn forget_oldest(&mut self) -> Option<Vec<PlayRecord>> {
+        while self.recent.len() > self.limit {
+            self.recent.pop_front();
+            self.revision = self.revision.wrapping_add(1);
+        }
+        let slack = (self.limit / 10).max(1);
+        match self.in_file.is_some_and(|lines| lines > self.limit + slack) {
+            true => self.trim(),
+            false => None,
+        }
+    }
+
+    /// The plays in memory, which replace the file's.
+    /// None before the file is read, so plays that weren't read yet aren't lost.
+    fn trim(&mut self) -> Option<Vec<PlayRecord>> {
+        self.in_file?;
+        let records: Vec<PlayRecord> = self.recent.iter().cloned().collect();
+        self.in_file = Some(records.len());
+        Some(records)
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// The songs played, as many times as they were
+    pub fn used_keys(&self) -> impl Iterator<Item = &SongKey> {
+        self.recent.iter().map(|record| &record.key)
+    }
+
+    /// How many times each song was played, as far back as the history goes
+    pub fn counts(&self) -> HashMap<&SongKey, usize> {
+        let mut counts = HashMap::new();
+        for record in &self.recent {
+            *counts.entry(&record.key).or_default() += 1;
+        }
+        counts
+    }
+
+    /// The plays newest first, with plays of the same song in a row counted as one
+    pub fn runs(&self) -> Vec<(&PlayRecord, usize)> {
+        let mut runs: Vec<(&PlayRecord, usize)> = vec![];
+        for record in self.recent.iter().rev() {
+            match runs.last_mut() {
+                Some((last, repeats)) if last.key == record.key => *repeats += 1,
+                _ => runs.push((record, 1)),
+            }
+        }
+        runs
+    }
+
+    pub fn view<'a>(&'a self, tree: &'a SongOpConstructor) -> Element<'a, HistoryMsg> {
+        let counts = self.counts();
+        let rows = self
+            .runs()
+            .into_iter()
+            .take(HISTORY_SHOWN)
+            .map(|(record, repeats)| {
+                let title = tree.cached_song_data(&record.key).title;
+                let title = match repeats {
+                    1 => title,
+                    _ => format!("{title} (x{repeats})"),
+                };
+                let played = counts.get(&record.key).copied().unwrap_or_default();
+                let details = format!(
+                    "{}, {}, played {played} times",
+                    record.at.format("%b %-d %H:%M"),
+                    record.trigger.label(),
+                );
+                button(column![text(title), text(details).size(12)])
+                    .width(Length::Fill)
+                    .on_press(HistoryMsg::Replay(record.key.clone()))
+                    .into()
+            });
+        let empty = self
+            .recent
+            .is_empty()
+            .then(|| text("Nothing was played yet"));
+        column![text("history"), Column::with_children(rows).spacing(2)]
+            .push_maybe(empty)
+            .spacing(4)
+            .width(Length::Fill)
+            .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Local;
+
+    use super::{History, PlayRecord, PlayTrigger};
+    use crate::caching::readers::LineBasedReader;
+
+    fn keys(history: &History) -> Vec<&str> {
+        let records = history.recent.iter();
+        records.map(|record| record.key.as_str()).collect()
+    }
+
+    #[test]
+    fn repeats_are_shown_once_and_counted() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut history = History::new(LineBasedReader::new(dir.path().join("h.ndjson")), 100);
+        for key in ["a", "b", "b", "a", "b"] {
+            let recorded = history.record(key.into(), PlayTrigger::Picked, Local::now());
+            async_std::task::block_on(recorded);
+        }
+        let runs: Vec<(&str, usize)> = history
+            .runs()
+            .into_iter()
+            .map(|(record, repeats)| (record.key.as_str(), repeats))
+            .collect();
+        assert_eq![runs, [("b", 1), ("a", 1), ("b", 2), ("a", 1)]];
+        let counts = history.counts();
+        assert_eq![(counts[&"a".to_string()], counts[&"b".to_string()]), (2, 3)];
+    }
+
+    #[test]
+    fn only_the_newest_plays_are_kept() {
+        async_std::task::block_on(async {
+            let dir = tempfile::tempdir().unwrap();
+            let reader = LineBasedReader::new(dir.path().join("h.ndjson"));
+            let old: Vec<PlayRecord> = (0..30)
+                .map(|i| PlayRecord::new(i.to_string(), PlayTrigger::Queue, Local::now()))
+                .collect();
+            reader.append(&old).await.unwrap();
+
+            let mut history = History::new(reader.clone(), 10);
+            // Played before the file was read, so it goes after what's read
+            history
+                .record("new".into(), PlayTrigger::Picked, Local::now())
+                .await;
+            let (records, more) = history.load().await.unwrap();
+            assert![more];
+            history.loaded(records, more).await;
+
+            let expected = ["22", "23", "24", "25", "26", "27", "28", "29", "new"];
+            assert_eq![keys(&history)[1..], expected];
+            // The file was cut down to what's kept
+            let (all, more) = reader.tail::<PlayRecord>(100).await.unwrap();
+            assert_eq![all.len(), 10];
+            assert![!more];
+
+            for i in 0..11 {
+                history
+                    .record(format!("more{i}"), PlayTrigger::Queue, Local::now())
+                    .await;
+            }
+            assert_eq![history.recent.len(), 10];
+            let (all, _) = reader.tail::<PlayRecord>(100).await.unwrap();
+            assert![all.len() <= 11];
+        });
+    }
+}
@@ -0,0 +1,167 @@
+//! Reads playlists back in, from an M3U or M3U8 of YouTube links, or from an exported playlist.
+//! Each import becomes a new playlist, so nothing already in the app is replaced.
+
+use std::path::Path;
+
+use reqwest::Url;
+
+use crate::{
+    playlist::Playlist,
+    settings::{LoadError, SongKey},
+    song_operations::{ConstructorItem, SongOpConstructor},
+};
+
+/// A playlist made from a file, and how many of the file's entries couldn't be used
+#[derive(Debug, Clone)]
+pub struct Imported {
+    pub playlist: Playlist,
+    pub skipped: usize,
+    /// The file it was imported from
+    pub path: String,
+}
+
+fn is_video_id(id: &str) -> bool {
+    id.len() == 11
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// The YouTube video an M3U entry points to, given as a link or as the bare id
+pub fn youtube_id(entry: &str) -> Option<SongKey> {
+    let entry = entry.trim();
+    if is_video_id(entry) {
+        return Some(entry.to_string());
+    }
+    let url = Url::parse(entry).ok()?;
+    let host = url.host_str()?.to_lowercase();
+    let host = ["www.", "music.", "m."]
+        .iter()
+        .find_map(|prefix| host.strip_prefix(prefix))
+        .unwrap_or(&host)
+        .to_string();
+    let id = match host.as_str() {
+        "youtu.be" => url.path().trim_matches('/').to_string(),
+        "youtube.com" => url
+            .query_pairs()
+            .find(|(k, _)| k == "v")
+            .map(|(_, v)| v.to_string())?,
+        _ => return None,
+    };
+    is_video_id(&id).then_some(id)
+}
+
+/// The songs of the entries in order, and how many entries weren't YouTube videos
+pub fn parse_m3u(text: &str) -> (Vec<SongKey>, usize) {
+    let mut keys = vec![];
+    let mut skipped = 0;
+    let entries = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'));
+    for entry in entries {
+        match youtube_id(entry) {
+            Some(key) => keys.push(key),
+            None => skipped += 1,
+        }
+    }
+    (keys, skipped)
+}
+
+/// Makes the file's contents into a new playlist. Exported playlists keep their groups and
+/// name, M3U files are played once through in order and are named after the file.
+pub fn parse(path: &Path, text: &str) -> Result<Imported, LoadError> {
+    let (playlist, skipped) = match path.extension().is_some_and(|ext| ext == "json") {
+        true => {
+            let exported: Playlist = serde_json::from_str(text).map_err(|_| LoadError::Format)?;
+            // A new id, so it doesn't replace the playlist it was exported from
+            let playlist = Playlist {
+                name: exported.name,
+                constructor: exported.constructor.with_fresh_ids(),
+                ..Default::default()
+            };
+            (playlist, 0)
+        }
+        false => {
+            let (keys, skipped) = parse_m3u(text);
+            let items: Vec<ConstructorItem> = keys.into_iter().map(ConstructorItem::from).collect();
+            let name = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let playlist = Playlist {
+                name,
+                constructor: SongOpConstructor::from(items),
+                ..Default::default()
+            };
+            (playlist, skipped)
+        }
+    };
+    Ok(Imported {
+        playlist,
+        skipped,
+        path: path.display().to_string(),
+    })
+}
+
+pub async fn import(path: String) -> Result<Imported, LoadError> {
+    let path = Path::new(path.trim());
+    let text = async_std::fs::read_to_string(path)
+        .await
+        .map_err(|_| LoadError::File)?;
+    parse(path, &text)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{parse, parse_m3u, youtube_id};
+    use crate::playlist::Playlist;
+
+    #[test]
+    fn youtube_links_and_ids_are_found() {
+        for entry in [
+            "dQw4w9WgXcQ",
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=10",
+            "https://music.youtube.com/watch?v=dQw4w9WgXcQ",
+            "https://youtu.be/dQw4w9WgXcQ",
+        ] {
+            assert_eq![youtube_id(entry).as_deref(), Some("dQw4w9WgXcQ"), "{entry}"];
+        }
+        for entry in [
+            "/music/song.mp3",
+            "https://example.com/watch?v=dQw4w9WgXcQ",
+            "short",
+        ] {
+            assert_eq![youtube_id(entry), None, "{entry}"];
+        }
+    }
+
+    #[test]
+    fn local_files_are_skipped() {
+        let m3u = "#EXTM3U\n#EXTINF:213,A - B\nhttps://youtu.be/dQw4w9WgXcQ\n\n\
+                   #EXTINF:-1,C\n/music/c.mp3\nyQ1m2lEHJ8s\n";
+        let (keys, skipped) = parse_m3u(m3u);
+        assert_eq![keys, ["dQw4w9WgXcQ", "yQ1m2lEHJ8s"]];
+        assert_eq![skipped, 1];
+
+        let imported = parse(Path::new("/lists/mine.m3u8"), m3u).unwrap();
+        assert_eq![imported.playlist.name, "mine"];
+        assert_eq![imported.playlist.constructor.all_song_keys_rec().count(), 2];
+    }
+
+    #[test]
+    fn exported_playlists_are_imported_as_new_ones() {
+        let exported = Playlist {
+            name: "mine".into(),
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&exported).unwrap();
+        let imported = parse(Path::new("mine.json"), &json).unwrap();
+        assert_eq![imported.playlist.name, "mine"];
+        assert_ne![imported.playlist.id, exported.id];
+
+        assert![parse(Path::new("mine.json"), "not json").is_err()];
+    }
+}
@@ -8,49 +8,118 @@ use std::time::{Duration, SystemTime};
 
 use iced::advanced::Application;
 use iced::{alignment::Horizontal, Command as Cm, Element, Length, Subscription};
-use iced::{executor, window, Color, Renderer, Settings, Size};
+use iced::{executor, window, Color, Renderer, Settings};
 use iced::{
     theme::{Palette, Theme},
-    widget::{button, column, container, text},
+    widget::{button, column, container, row, text, tooltip},
 };
 use parking_lot::Mutex;
-use styling::transition_scheme;
 
 mod audio;
 mod backend_handler;
+mod backend_settings;
+mod bulk_edit;
 mod caching;
+mod cli;
+mod context_menu;
+mod downloads;
+mod events;
+mod export;
+mod failures;
+#[cfg(any(test, feature = "demo"))]
+mod fixtures;
+mod history;
+mod import;
+#[cfg(feature = "media-controls")]
+mod media_controls;
+mod metadata_queue;
+mod notifications;
+mod now_playing;
+mod panes;
 mod playlist;
+mod queue;
 mod response_types;
+#[cfg(feature = "scrobble")]
+mod scrobbler;
 mod search_window;
 mod settings;
+mod settings_panel;
+mod shutdown;
+mod single_instance;
+mod sleep_timer;
 mod song;
+mod song_editor;
 mod song_list;
+mod song_notifications;
 mod song_operations;
 mod styling;
+mod subscriptions;
+mod suggestions;
 mod thumbnails;
 mod user_input;
+mod whats_new;
+mod widgets;
+mod window_level;
+mod window_state;
 mod ytmrs;
 
+// The core, in its own library so it can run without the window
+use ytm_rs_core::{scheduler, session, verbosity};
+
 use crate::{
-    backend_handler::{BackendHandler, BackendLaunchStatus, ConnectionMode},
-    settings::{LoadError, SaveError, YTMRSettings},
+    backend_handler::BackendHandler,
+    caching::readers::flush_writes,
+    cli::{StartupOptions, USAGE},
+    notifications::Notification,
+    playlist::load_headers,
+    settings::{
+        playlists_directory, settings_path, use_settings_file, LoadError, SaveError, YTMRSettings,
+    },
+    shutdown::StopRequests,
+    single_instance::{Claim, ShowRequests},
     styling::SchemeState,
+    whats_new::WhatsNewMsg,
+    window_level::{platform_refusal, IcedLevel, LayoutMode, WindowPin},
+    window_state::{WindowChange, WindowPlacement, WindowState},
     ytmrs::{Ytmrs, YtmrsMsg},
 };
 
 pub const BACKGROUND_TRANSITION_DURATION: Duration = Duration::from_millis(1000);
 pub const BACKGROUND_TRANSITION_RATE: Duration = Duration::from_millis(1000 / 40); // ~15fps
 
+/// How far closing the window has gotten
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum Closing {
+    #[default]
+    Open,
+    /// The settings are being written, the window closes once they are
+    Saving,
+    /// They couldn't be written, the window stays open until it's quit without saving
+    Failed,
+}
+
 #[derive(Debug)]
 struct MainState {
     ytmrs: Ytmrs,
+    /// Set while a save is being written
     saving: bool,
+    /// Set once a failed save was shown, so failing autosaves aren't shown again until one works
+    save_failure_shown: bool,
     state: SchemeState,
+    closing: Closing,
+    /// False when the settings file couldn't be read, so it's only replaced with the defaults
+    /// by the save button
+    save_automatically: bool,
+    pin: WindowPin,
 }
 
 #[derive(Debug)]
 struct Main {
     backend: Arc<Mutex<BackendHandler>>,
+    options: StartupOptions,
+    /// From instances that were started while this one runs
+    show_requests: ShowRequests,
+    stop_requests: StopRequests,
     state: Option<MainState>,
 }
 
@@ -62,13 +131,96 @@ struct Main {
 enum MAINMessage {
     Loaded(Result<YTMRSettings, LoadError>),
     Save,
-    Saved(Result<PathBuf, SaveError>),
-    UpdateVisibleBackground(SchemeState),
+    /// Whether it was saved automatically
+    Saved(Result<PathBuf, SaveError>, bool),
+    ToggleAlwaysOnTop,
+    /// Moves the background transition along
+    UpdateVisibleBackground,
+    Window(WindowChange),
+    /// Closes the window, once everything is written
+    Close,
+    /// The system asked the app to end
+    StopRequested,
+    SavedToClose(Result<PathBuf, SaveError>),
+    /// Closes without the settings, after they failed to save
+    QuitWithoutSaving,
+    /// Leaves the window open, after the settings failed to save
+    CancelClose,
+    /// Another instance was started, and asked for this one instead
+    ShowWindow,
     YtmrsMessage(YtmrsMsg),
 }
 
 impl Main {}
 
+fn save(state: &mut MainState, automatic: bool) -> Cm<MAINMessage> {
+    state.saving = true;
+    state.ytmrs.prepare_to_save();
+    Cm::perform(state.ytmrs.settings.clone().save(), move |result| {
+        MAINMessage::Saved(result, automatic)
+    })
+}
+
+/// Writes the settings, then closes the window
+fn save_and_close(state: &mut MainState) -> Cm<MAINMessage> {
+    state.closing = Closing::Saving;
+    // The save being written is waited for, then what changed since is saved with the rest
+    if state.saving {
+        return Cm::none();
+    }
+    if !state.save_automatically {
+        println!["The settings couldn't be read, so they aren't saved over"];
+        return Cm::perform(flush_writes(), |_| MAINMessage::Close);
+    }
+    state.ytmrs.prepare_to_save();
+    let settings = state.ytmrs.settings.clone();
+    Cm::perform(
+        async move {
+            let saved = settings.save().await;
+            flush_writes().await;
+            saved
+        },
+        MAINMessage::SavedToClose,
+    )
+}
+
+/// Brings the window to the front, out of the taskbar if it was minimized
+fn show_window() -> Cm<MAINMessage> {
+    Cm::batch([
+        window::minimize(window::Id::MAIN, false),
+        window::gain_focus(window::Id::MAIN),
+    ])
+}
+
+/// Disabled where the window can't be pinned, saying why when it's hovered
+fn pin_button<'a>(pin: &WindowPin) -> Element<'a, MAINMessage> {
+    let label = match pin.on_top() {
+        true => "unpin",
+        false => "pin",
+    };
+    let refusal = platform_refusal();
+    let pin =
+        button(label).on_press_maybe(refusal.is_none().then_some(MAINMessage::ToggleAlwaysOnTop));
+    match refusal {
+        Some(reason) => tooltip(pin, text(reason), tooltip::Position::Bottom).into(),
+        None => pin.into(),
+    }
+}
+
+fn layout_mode(state: &MainState) -> LayoutMode {
+    LayoutMode::of(state.ytmrs.settings.user.simple.enabled)
+}
+
+/// Puts the window at the level the layout mode it's in wants
+fn apply_level(state: &mut MainState) -> Cm<MAINMessage> {
+    let mut level = IcedLevel::default();
+    let pinned = state.ytmrs.settings.user.pinned.get(layout_mode(state));
+    if let Err(reason) = state.pin.apply(&mut level, pinned) {
+        println!["The window isn't kept on top: {reason}"];
+    }
+    level.into_command()
+}
+
 impl Application for Main {
     type Executor = executor::Default;
 
@@ -78,11 +230,21 @@ impl Application for Main {
 
     type Renderer = Renderer;
 
-    type Flags = Arc<Mutex<BackendHandler>>;
+    type Flags = (
+        Arc<Mutex<BackendHandler>>,
+        StartupOptions,
+        ShowRequests,
+        StopRequests,
+    );
 
-    fn new(backend: Self::Flags) -> (Self, Cm<Self::Message>) {
+    fn new(
+        (backend, options, show_requests, stop_requests): Self::Flags,
+    ) -> (Self, Cm<Self::Message>) {
         let me = Self {
             backend,
+            options,
+            show_requests,
+            stop_requests,
             state: None,
         };
 
@@ -98,7 +260,6 @@ impl Application for Main {
 
     fn theme(&self) -> Theme {
         match &self.state {
-            Some(state) if state.saving => Theme::default(),
             None => Theme::default(),
             Some(state) => {
                 let (primary, danger) = {
@@ -124,86 +285,194 @@ impl Application for Main {
         match &mut self.state {
             None => match message {
                 MAINMessage::Loaded(o) => {
+                    let save_automatically = !matches!(o, Err(LoadError::Format));
                     let mut s = match o {
                         Ok(settings) => Ytmrs::new(settings, self.backend.clone()),
                         Err(_) => Ytmrs::default(),
                     };
 
-                    let commands = s.load().map(MAINMessage::YtmrsMessage);
-
-                    self.state = Some(MainState {
+                    let load = s.load(&self.options).map(MAINMessage::YtmrsMessage);
+                    let mut state = MainState {
                         ytmrs: s,
                         saving: false,
+                        save_failure_shown: false,
                         state: SchemeState::default(),
-                    });
-                    commands
+                        closing: Closing::Open,
+                        save_automatically,
+                        pin: WindowPin::default(),
+                    };
+                    // The window already exists, so the saved level can be applied now
+                    let level = apply_level(&mut state);
+                    self.state = Some(state);
+                    Cm::batch([load, level])
                 }
+                // Nothing was loaded, so there's nothing to save
+                MAINMessage::Window(WindowChange::CloseRequested)
+                | MAINMessage::StopRequested
+                | MAINMessage::Close => window::close(window::Id::MAIN),
+                MAINMessage::ShowWindow => show_window(),
                 _ => Cm::none(),
             },
             Some(ref mut state) => match message {
-                MAINMessage::UpdateVisibleBackground(scheme_state) => {
-                    match scheme_state {
-                        SchemeState::Started(_) => todo!(), // how
-                        SchemeState::Transitioning(_) => {
-                            state.state = scheme_state.clone();
-
-                            Cm::perform(
-                                transition_scheme(scheme_state),
-                                |state: SchemeState| -> MAINMessage {
-                                    MAINMessage::UpdateVisibleBackground(state)
-                                },
-                            )
-                        }
-                        SchemeState::Finished(_) => {
-                            state.state = *Box::new(scheme_state);
-                            Cm::none()
-                        }
-                    }
+                MAINMessage::UpdateVisibleBackground => {
+                    state.state = std::mem::take(&mut state.state).advance(SystemTime::now());
+                    Cm::none()
                 }
-                MAINMessage::YtmrsMessage(YtmrsMsg::SetNewBackground(k, scheme)) => {
-                    let schemestate = SchemeState::Started(Box::new(styling::Started {
+                // The colors of songs that were skipped past are dropped, rather than flashed
+                MAINMessage::YtmrsMessage(YtmrsMsg::SetNewBackground(k, scheme))
+                    if state.ytmrs.is_playing(&k) =>
+                {
+                    // The subscription ticks the transition along from here
+                    state.state = SchemeState::Started(Box::new(styling::Started {
                         from: state.state.first_choice().clone(),
                         to: scheme.clone(),
                         started: SystemTime::now(),
                     }));
-                    state.state = schemestate.clone();
-                    Cm::batch([
-                        Cm::perform(
-                            transition_scheme(schemestate),
-                            MAINMessage::UpdateVisibleBackground,
-                        ),
-                        state
-                            .ytmrs
-                            .update(YtmrsMsg::SetNewBackground(k, scheme))
-                            .map(MAINMessage::YtmrsMessage),
-                    ])
-                    // Cm::perform(, |state| {
-                    //     YTMRSMessage::UpdateVisibleBackground(state)
-                    // })
+                    state
+                        .ytmrs
+                        .update(YtmrsMsg::SetNewBackground(k, scheme))
+                        .map(MAINMessage::YtmrsMessage)
+                }
+                // Saved like the button does, once nothing else is being saved
+                MAINMessage::YtmrsMessage(YtmrsMsg::AutosaveTick) => {
+                    let idle = !state.saving && state.closing == Closing::Open;
+                    match idle && state.save_automatically && state.ytmrs.has_unsaved_changes() {
+                        true => save(state, true),
+                        false => Cm::none(),
+                    }
                 }
                 MAINMessage::YtmrsMessage(msg) => {
-                    state.ytmrs.update(msg).map(MAINMessage::YtmrsMessage)
+                    let mode = layout_mode(state);
+                    let command = state.ytmrs.update(msg).map(MAINMessage::YtmrsMessage);
+                    match layout_mode(state) == mode {
+                        true => command,
+                        // Each mode is put back at its own level
+                        false => Cm::batch([command, apply_level(state)]),
+                    }
                 }
-                MAINMessage::Save => {
-                    state.ytmrs.prepare_to_save();
-                    Cm::perform(state.ytmrs.settings.clone().save(), MAINMessage::Saved)
+                MAINMessage::Save => save(state, false),
+                MAINMessage::Window(WindowChange::CloseRequested) => match state.closing {
+                    Closing::Saving => Cm::none(),
+                    Closing::Open | Closing::Failed => save_and_close(state),
+                },
+                // A second signal doesn't wait for the save
+                MAINMessage::StopRequested => match state.closing {
+                    Closing::Saving => window::close(window::Id::MAIN),
+                    Closing::Open | Closing::Failed => save_and_close(state),
+                },
+                MAINMessage::SavedToClose(Ok(p)) => {
+                    println!["Saved to {p:?}"];
+                    window::close(window::Id::MAIN)
                 }
-                MAINMessage::Saved(success) => {
-                    match success {
-                        Ok(p) => println!["Saved to {p:?}"],
-                        Err(e) => println!["{e:?}"],
-                    }
+                MAINMessage::SavedToClose(Err(e)) => {
+                    state.closing = Closing::Failed;
+                    let notification = Notification::error(format!("Saving failed: {e:?}"));
+                    state
+                        .ytmrs
+                        .update(YtmrsMsg::Notify(notification))
+                        .map(MAINMessage::YtmrsMessage)
+                }
+                MAINMessage::QuitWithoutSaving => {
+                    // The window's state is written on its own, into the settings as they were
+                    let window = state.ytmrs.settings.window;
+                    Cm::perform(
+                        async move {
+                            let saved = window.save_into(settings_path()).await;
+                            flush_writes().await;
+                            saved
+                        },
+                        |result| {
+                            if let Err(e) = result {
+                                println!["Failed to save the window's state: {e:?}"];
+                            }
+                            MAINMessage::Close
+                        },
+                    )
+                }
+                MAINMessage::CancelClose => {
+                    state.closing = Closing::Open;
+                    Cm::none()
+                }
+                MAINMessage::Window(WindowChange::Focused(focused)) => {
+                    state.ytmrs.set_focused(focused);
+                    Cm::none()
+                }
+                MAINMessage::Window(change) => {
+                    state.ytmrs.settings.window.apply(change);
                     Cm::none()
                 }
+                MAINMessage::Close => window::close(window::Id::MAIN),
+                MAINMessage::ShowWindow => show_window(),
+                MAINMessage::ToggleAlwaysOnTop => {
+                    let mode = layout_mode(state);
+                    let mut level = IcedLevel::default();
+                    let pinned = &mut state.ytmrs.settings.user.pinned;
+                    match state.pin.toggle(&mut level, pinned, mode) {
+                        Ok(()) => level.into_command(),
+                        Err(reason) => state
+                            .ytmrs
+                            .update(YtmrsMsg::Notify(Notification::error(reason)))
+                            .map(MAINMessage::YtmrsMessage),
+                    }
+                }
+                MAINMessage::Saved(success, automatic) => {
+                    state.saving = false;
+                    let notice = match success {
+                        Ok(p) => {
+                            println!["Saved to {p:?}"];
+                            state.save_failure_shown = false;
+                            state.ytmrs.saved();
+                            Cm::none()
+                        }
+                        Err(e) if automatic && state.save_failure_shown => {
+                            println!["Autosaving failed again: {e:?}"];
+                            Cm::none()
+                        }
+                        Err(e) => {
+                            state.save_failure_shown = true;
+                            let notification = Notification::error(format!("Saving failed: {e:?}"));
+                            state
+                                .ytmrs
+                                .update(YtmrsMsg::Notify(notification))
+                                .map(MAINMessage::YtmrsMessage)
+                        }
+                    };
+                    match state.closing {
+                        // The window was closed while this was written
+                        Closing::Saving => Cm::batch([notice, save_and_close(state)]),
+                        Closing::Open | Closing::Failed => notice,
+                    }
+                }
                 _ => Cm::none(),
             },
         }
     }
 
     fn subscription(&self) -> Subscription<MAINMessage> {
+        // The window can be closed or asked for while the settings load
+        let window = Subscription::batch([
+            window_state::changes().map(MAINMessage::Window),
+            self.show_requests
+                .subscription()
+                .map(|_| MAINMessage::ShowWindow),
+            self.stop_requests
+                .subscription()
+                .map(|_| MAINMessage::StopRequested),
+        ]);
         match &self.state {
-            Some(state) => state.ytmrs.subscription().map(MAINMessage::YtmrsMessage),
-            None => Subscription::none(),
+            Some(state) => {
+                let transition = match state.state.is_finished() {
+                    true => Subscription::none(),
+                    false => subscriptions::every("background", BACKGROUND_TRANSITION_RATE)
+                        .map(|_| MAINMessage::UpdateVisibleBackground),
+                };
+                Subscription::batch([
+                    state.ytmrs.subscription().map(MAINMessage::YtmrsMessage),
+                    transition,
+                    window,
+                ])
+            }
+            None => window,
         }
     }
 
@@ -220,10 +489,34 @@ impl Application for Main {
             .into(),
 
             Some(state) => {
+                let close_failed = (state.closing == Closing::Failed).then(|| {
+                    row![
+                        text("The settings couldn't be saved."),
+                        button("try again")
+                            .on_press(MAINMessage::Window(WindowChange::CloseRequested)),
+                        button("quit without saving").on_press(MAINMessage::QuitWithoutSaving),
+                        button("keep open").on_press(MAINMessage::CancelClose),
+                    ]
+                    .spacing(4)
+                    .align_items(iced::Alignment::Center)
+                });
                 let contents = {
                     let c = column![
-                        button(if state.saving { "saving..." } else { "save" })
-                            .on_press(MAINMessage::Save),
+                        row![
+                            button(if state.saving { "saving..." } else { "save" })
+                                .on_press_maybe((!state.saving).then_some(MAINMessage::Save)),
+                            pin_button(&state.pin),
+                            button(match state.ytmrs.settings.user.normalize {
+                                true => "normalizing",
+                                false => "normalize",
+                            })
+                            .on_press(MAINMessage::YtmrsMessage(YtmrsMsg::ToggleNormalize)),
+                            button("view changelog").on_press(MAINMessage::YtmrsMessage(
+                                YtmrsMsg::WhatsNew(WhatsNewMsg::Open)
+                            )),
+                        ]
+                        .push_maybe(close_failed)
+                        .spacing(4),
                         state
                             .ytmrs
                             .view(state.state.first_choice().clone())
@@ -251,15 +544,53 @@ impl Application for Main {
 }
 
 pub fn main() -> iced::Result {
+    verbosity::set(verbosity::Verbosity::from_args(std::env::args()));
+    let options = match StartupOptions::parse(std::env::args()) {
+        Ok(options) if options.help => {
+            println!["{USAGE}"];
+            return Ok(());
+        }
+        Ok(options) => options,
+        Err(e) => exit_with(e),
+    };
+    if let Some(path) = &options.settings {
+        use_settings_file(path.clone());
+    }
+
+    // Kept until exit, the dataset is deleted with it
+    #[cfg(feature = "demo")]
+    let _demo = fixtures::install_if_requested();
+
+    // Named playlists are looked for once the demo's library is in place
+    let headers = || async_std::task::block_on(load_headers(&playlists_directory()));
+    let options = match options.resolve(headers) {
+        Ok(options) => options,
+        Err(e) => exit_with(e),
+    };
+
+    // Kept until exit, the lock is removed with it
+    let (_instance, show_requests) = match options.new_instance {
+        true => (None, ShowRequests::default()),
+        false => match single_instance::claim() {
+            Claim::First(guard, requests) => (Some(guard), requests),
+            Claim::Signaled => {
+                println!["The app is running already, its window was brought to the front"];
+                return Ok(());
+            }
+        },
+    };
+
     let backend = Arc::new(Mutex::new(BackendHandler::default()));
+    let window_state = WindowState::load();
+    let stop_requests = shutdown::listen();
 
     let main = Main::run(Settings {
         id: None,
-        flags: backend.clone(),
+        flags: (backend.clone(), options, show_requests, stop_requests),
         antialiasing: true,
         window: window::Settings {
-            size: Size::new(1024.0, 512.0),
-            position: window::Position::Centered,
+            size: window_state.size(),
+            position: window_state.position(),
             min_size: None,
             max_size: None,
             visible: true,
@@ -285,25 +616,25 @@ pub fn main() -> iced::Result {
                 application_id: "YtmRs".to_string(),
             },
 
-            exit_on_close_request: true,
+            // The window's state is written before it closes
+            exit_on_close_request: false,
         },
         ..Default::default()
     });
 
     println!["App exited"];
 
-    // If backend is owned by current process, try to kill it
-    {
-        let mut b = backend.lock();
-        if let BackendLaunchStatus::Launched(ConnectionMode::Child(process, _)) = &mut b.status {
-            println!["Kill result: {:?}", process.kill()];
-            println!["Killed backend"];
-        }
-    }
+    // If backend is owned by current process, ask it to exit, or kill it
+    backend.lock().shut_down_child();
 
     main
 }
 
+fn exit_with(e: cli::ArgError) -> ! {
+    eprintln!["{e}\n\n{USAGE}"];
+    std::process::exit(2)
+}
+
 // pub fn main() {
 //     let sound = Sound::from_path("BeetrootKvass.wav").unwrap();
 //     let sample_rate = sound.sample_rate();
@@ -0,0 +1,384 @@
+//! Shows the playing song in the system's media controls, MPRIS on Linux, the now playing
+//! info on macOS and the SMTC on Windows, and passes their commands to the tracker. Windows'
+//! controls are attached to the window's handle, so they wait for [`MediaCommands::attach`].
+//!
+//! The controls live on their own thread, fed by an [`events::Listener`]. Where they can't be
+//! made, nothing is shown and no commands come.
+
+use std::{
+    ffi::c_void,
+    path::PathBuf,
+    sync::{mpsc, Arc},
+    thread,
+    time::{Duration, Instant},
+};
+
+use futures::{
+    channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
+    future, stream, StreamExt,
+};
+use iced::{window, Command, Subscription};
+use parking_lot::Mutex;
+use raw_window_handle::RawWindowHandle;
+use souvlaki::{
+    MediaControlEvent, MediaControls, MediaMetadata, MediaPlayback, MediaPosition, PlatformConfig,
+    SeekDirection,
+};
+
+use crate::{
+    audio::{AudioProgressTracker, TrackerMsg},
+    events::{AppEvent, Listener},
+    song::{Song, SongDuration},
+    thumbnails::saved_path,
+    verbosity::debug,
+};
+
+/// How far a seek without an amount goes, in seconds
+const SEEK_STEP: f64 = 5.0;
+
+/// What the system asked for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MediaCommand {
+    Play,
+    Pause,
+    Toggle,
+    Next,
+    Previous,
+    /// Moves this many seconds forward, or back when negative
+    SeekBy(f64),
+    /// Moves to this many seconds into the song
+    SeekTo(f64),
+}
+
+impl MediaCommand {
+    fn from_event(event: MediaControlEvent) -> Option<Self> {
+        let seconds = |direction: SeekDirection, secs: f64| match direction {
+            SeekDirection::Forward => secs,
+            SeekDirection::Backward => -secs,
+        };
+        match event {
+            MediaControlEvent::Play => Some(Self::Play),
+            MediaControlEvent::Pause | MediaControlEvent::Stop => Some(Self::Pause),
+            MediaControlEvent::Toggle => Some(Self::Toggle),
+            MediaControlEvent::Next => Some(Self::Next),
+            MediaControlEvent::Previous => Some(Self::Previous),
+            MediaControlEvent::Seek(direction) => Some(Self::SeekBy(seconds(direction, SEEK_STEP))),
+            MediaControlEvent::SeekBy(direction, by) => {
+                Some(Self::SeekBy(seconds(direction, by.as_secs_f64())))
+            }
+            MediaControlEvent::SetPosition(MediaPosition(at)) => {
+                Some(Self::SeekTo(at.as_secs_f64()))
+            }
+            _ => None,
+        }
+    }
+
+    /// What the tracker's buttons would have sent. Seeks go through the progress bar.
+    pub fn tracker_msgs(self, tracker: &AudioProgressTracker) -> Vec<TrackerMsg> {
+        let seek = |target: f64| {
+            vec![
+                TrackerMsg::ProgressSliderChanged(target.max(0.0)),
+                TrackerMsg::ProgressSliderReleased,
+            ]
+        };
+        match self {
+            Self::Play => vec![TrackerMsg::Play],
+            Self::Pause => vec![TrackerMsg::Pause],
+            Self::Toggle => match tracker.paused {
+                true => vec![TrackerMsg::Play],
+                false => vec![TrackerMsg::Pause],
+            },
+            Self::Next => vec![TrackerMsg::Next],
+            Self::Previous => vec![TrackerMsg::Previous],
+            Self::SeekBy(secs) => match tracker.elapsed {
+                Some(elapsed) => seek(elapsed + secs),
+                None => vec![],
+            },
+            Self::SeekTo(secs) => seek(secs),
+        }
+    }
+}
+
+/// The commands from the controls, taken by the first subscription that runs
+#[derive(Debug, Clone, Default)]
+pub struct MediaCommands {
+    commands: Arc<Mutex<Option<UnboundedReceiver<MediaCommand>>>>,
+    /// Passes the window's handle to the controls' thread
+    window: Option<mpsc::Sender<isize>>,
+}
+
+impl MediaCommands {
+    /// Gives the controls the main window's handle. Only Windows' controls wait for it.
+    pub fn attach(&self) -> Command<()> {
+        let window = self.window.clone();
+        window::run_with_handle(window::Id::MAIN, move |handle| {
+            if let (Some(window), RawWindowHandle::Win32(handle)) = (window, handle.as_raw()) {
+                let _ = window.send(handle.hwnd.get());
+            }
+        })
+    }
+
+    pub fn subscription(&self) -> Subscription<MediaCommand> {
+        let receiver = Arc::clone(&self.commands);
+        let commands = stream::once(async move { receiver.lock().take() })
+            .filter_map(future::ready)
+            .flatten();
+        iced::subscription::run_with_id("media_controls", commands)
+    }
+}
+
+/// What the controls show
+enum Update {
+    Song {
+        title: String,
+        artist: String,
+        duration: Option<Duration>,
+        cover: Option<PathBuf>,
+    },
+    Playing(Duration),
+    Paused(Duration),
+}
+
+/// Where playback is, worked out from the events since the song started
+#[derive(Debug, Default)]
+struct Position {
+    offset: Duration,
+    /// When playback last resumed, None while paused
+    since: Option<Instant>,
+}
+
+impl Position {
+    fn at(&self, now: Instant) -> Duration {
+        let playing = self.since.map(|since| now.saturating_duration_since(since));
+        self.offset + playing.unwrap_or_default()
+    }
+
+    fn start(&mut self, now: Instant) {
+        self.offset = Duration::ZERO;
+        self.since = Some(now);
+    }
+
+    fn pause(&mut self, now: Instant) {
+        self.offset = self.at(now);
+        self.since = None;
+    }
+
+    fn resume(&mut self, now: Instant) {
+        self.since = self.since.or(Some(now));
+    }
+
+    fn seek(&mut self, secs: f64, now: Instant) {
+        self.offset = Duration::from_secs_f64(secs.max(0.0));
+        self.since = self.since.map(|_| now);
+    }
+}
+
+/// Passes the events to the controls' thread
+pub struct MediaControlsListener {
+    updates: mpsc::Sender<Update>,
+    position: Position,
+}
+
+impl Listener for MediaControlsListener {
+    fn handle(&mut self, event: &AppEvent) {
+        let now = Instant::now();
+        let update = match event {
+            AppEvent::SongStarted { id, meta } => {
+                self.position.start(now);
+                let update = match meta {
+                    Some(song) => song_update(song),
+                    None => Update::Song {
+                        title: id.clone(),
+                        artist: String::new(),
+                        duration: None,
+                        cover: None,
+                    },
+                };
+                // The song is reported before it's reported as playing
+                let _ = self.updates.send(update);
+                Update::Playing(Duration::ZERO)
+            }
+            AppEvent::PlaybackPaused => {
+                self.position.pause(now);
+                Update::Paused(self.position.at(now))
+            }
+            AppEvent::PlaybackResumed => {
+                self.position.resume(now);
+                Update::Playing(self.position.at(now))
+            }
+            AppEvent::Seeked { secs } => {
+                self.position.seek(*secs, now);
+                match self.position.since {
+                    Some(_) => Update::Playing(self.position.at(now)),
+                    None => Update::Paused(self.position.at(now)),
+                }
+            }
+            AppEvent::SongEnded { .. } | AppEvent::SongAdded { .. } => return,
+        };
+        // The thread is gone if the controls couldn't be made
+        let _ = self.updates.send(update);
+    }
+}
+
+fn song_update(song: &Song) -> Update {
+    let artist = match &song.artists {
+        Some(artists) if !artists.is_empty() => artists.join(", "),
+        _ => song.channel.clone(),
+    };
+    // Only thumbnails that were saved have a path to show
+    let cover = song.thumbnail_handle.as_ref().and_then(saved_path);
+    let duration = match song.song_duration() {
+        SongDuration::Known(secs) => Some(Duration::from_secs_f64(secs)),
+        SongDuration::Unknown | SongDuration::Live => None,
+    };
+    Update::Song {
+        title: song.title.clone(),
+        artist,
+        duration,
+        cover,
+    }
+}
+
+/// Starts the controls' thread. The listener feeds it, and the commands come from it.
+pub fn start() -> (MediaControlsListener, MediaCommands) {
+    let (updates, received) = mpsc::channel();
+    let (commands, receiver) = unbounded();
+    let (window, hwnd) = mpsc::channel();
+    thread::spawn(move || run(received, commands, hwnd));
+    let listener = MediaControlsListener {
+        updates,
+        position: Position::default(),
+    };
+    let commands = MediaCommands {
+        commands: Arc::new(Mutex::new(Some(receiver))),
+        window: Some(window),
+    };
+    (listener, commands)
+}
+
+/// Ends once the listener is dropped, or on Windows if the window's handle never comes
+fn run(
+    updates: mpsc::Receiver<Update>,
+    commands: UnboundedSender<MediaCommand>,
+    window: mpsc::Receiver<isize>,
+) {
+    // The updates sent in the meantime wait in their channel
+    let hwnd = match cfg!(target_os = "windows") {
+        true => match window.recv() {
+            Ok(hwnd) => Some(hwnd as *mut c_void),
+            Err(_) => return,
+        },
+        false => None,
+    };
+    let config = PlatformConfig {
+        dbus_name: "ytm_rs",
+        display_name: "ytm-rs",
+        hwnd,
+    };
+    let mut controls = match MediaControls::new(config) {
+        Ok(controls) => controls,
+        Err(e) => {
+            debug!["Media controls are unavailable: {e:?}"];
+            return;
+        }
+    };
+    let attached = controls.attach(move |event| {
+        if let Some(command) = MediaCommand::from_event(event) {
+            let _ = commands.unbounded_send(command);
+        }
+    });
+    if let Err(e) = attached {
+        debug!["Media controls are unavailable: {e:?}"];
+        return;
+    }
+    for update in updates {
+        let result = match update {
+            Update::Song {
+                title,
+                artist,
+                duration,
+                cover,
+            } => {
+                let cover = cover.map(|path| format!("file://{}", path.display()));
+                controls.set_metadata(MediaMetadata {
+                    title: Some(&title),
+                    artist: Some(&artist),
+                    duration,
+                    cover_url: cover.as_deref(),
+                    ..Default::default()
+                })
+            }
+            Update::Playing(at) => controls.set_playback(MediaPlayback::Playing {
+                progress: Some(MediaPosition(at)),
+            }),
+            Update::Paused(at) => controls.set_playback(MediaPlayback::Paused {
+                progress: Some(MediaPosition(at)),
+            }),
+        };
+        if let Err(e) = result {
+            debug!["Failed to update the media controls: {e:?}"];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use souvlaki::{MediaControlEvent, MediaPosition, SeekDirection};
+
+    use super::{MediaCommand, Position};
+    use crate::audio::{AudioProgressTracker, TrackerMsg};
+
+    #[test]
+    fn commands_go_through_the_tracker() {
+        let mut tracker = AudioProgressTracker::default();
+        tracker.elapsed = Some(3.0);
+        tracker.paused = true;
+        let msgs = |event| {
+            MediaCommand::from_event(event)
+                .unwrap()
+                .tracker_msgs(&tracker)
+        };
+        assert![matches!(
+            msgs(MediaControlEvent::Toggle)[..],
+            [TrackerMsg::Play]
+        )];
+        assert![matches!(
+            msgs(MediaControlEvent::Stop)[..],
+            [TrackerMsg::Pause]
+        )];
+        // Seeking back past the start goes to the start
+        let back = msgs(MediaControlEvent::SeekBy(
+            SeekDirection::Backward,
+            Duration::from_secs(10),
+        ));
+        assert![matches!(
+            back[..],
+            [
+                TrackerMsg::ProgressSliderChanged(at),
+                TrackerMsg::ProgressSliderReleased
+            ] if at == 0.0
+        )];
+        let to = msgs(MediaControlEvent::SetPosition(MediaPosition(
+            Duration::from_secs(42),
+        )));
+        assert![matches!(to[..], [TrackerMsg::ProgressSliderChanged(at), _] if at == 42.0)];
+        assert_eq![MediaCommand::from_event(MediaControlEvent::Raise), None];
+    }
+
+    #[test]
+    fn position_follows_pauses_and_seeks() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut position = Position::default();
+        position.start(start);
+        assert_eq![position.at(at(10)), Duration::from_secs(10)];
+        position.pause(at(10));
+        assert_eq![position.at(at(50)), Duration::from_secs(10)];
+        position.seek(30.0, at(50));
+        assert_eq![position.at(at(60)), Duration::from_secs(30)];
+        position.resume(at(60));
+        assert_eq![position.at(at(65)), Duration::from_secs(35)];
+    }
+}
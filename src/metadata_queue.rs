@@ -0,0 +1,164 @@
+//! Songs whose info is waiting to be asked of the backend. Only a few are asked for at once,
+//! since each one is a yt-dlp call and a big tab would otherwise start dozens of them. Those
+//! that fail are asked for once more before they're given up on. Cached songs can be queued
+//! too, to refresh their info.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::{settings::SongKey, song::SongSource};
+
+/// Why a song's info couldn't be had
+#[derive(Debug, Clone, PartialEq)]
+pub enum FetchError {
+    /// The video was deleted or made private, asking again won't help
+    Unavailable,
+    Failed(String),
+}
+impl FetchError {
+    pub fn describe(&self) -> &str {
+        match self {
+            Self::Unavailable => "The video was deleted or made private",
+            Self::Failed(e) => e,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MetadataQueue {
+    /// In the order they were asked for, with where they were found
+    pending: VecDeque<(SongKey, SongSource)>,
+    in_flight: HashMap<SongKey, SongSource>,
+    /// The songs that failed once already
+    retried: HashSet<SongKey>,
+    /// Cached songs that are asked for again anyway
+    refreshing: HashSet<SongKey>,
+    /// How many songs were given up on since the queue last drained
+    given_up: usize,
+}
+
+impl MetadataQueue {
+    /// Queues the songs that aren't queued or being asked for already
+    pub fn request(&mut self, keys: impl IntoIterator<Item = SongKey>, source: &SongSource) {
+        for key in keys {
+            let queued = self.pending.iter().any(|(k, _)| *k == key);
+            if queued || self.in_flight.contains_key(&key) {
+                continue;
+            }
+            self.retried.remove(&key);
+            self.pending.push_back((key, source.clone()));
+        }
+    }
+
+    /// Queues the songs to be asked for even though they're cached
+    pub fn refresh(&mut self, keys: impl IntoIterator<Item = SongKey>) {
+        let keys: Vec<SongKey> = keys.into_iter().collect();
+        self.refreshing.extend(keys.iter().cloned());
+        self.request(keys, &SongSource::Unknown);
+    }
+
+    /// Takes the next songs to ask for, as many as there's room for. Songs that were cached
+    /// since they were queued are dropped, unless they're being refreshed.
+    pub fn next_batch(&mut self, limit: usize, cached: impl Fn(&SongKey) -> bool) -> Vec<SongKey> {
+        let refreshing = &self.refreshing;
+        self.pending
+            .retain(|(key, _)| refreshing.contains(key) || !cached(key));
+        let room = limit.max(1).saturating_sub(self.in_flight.len());
+        let batch: Vec<(SongKey, SongSource)> =
+            self.pending.drain(..room.min(self.pending.len())).collect();
+        let keys = batch.iter().map(|(key, _)| key.clone()).collect();
+        self.in_flight.extend(batch);
+        keys
+    }
+
+    /// Records how asking for the song went, returning where it was found. None if it
+    /// failed for the first time, it's then queued to be asked for again before the rest.
+    /// Songs whose video is gone aren't asked for again.
+    pub fn finish(&mut self, key: &SongKey, error: Option<&FetchError>) -> Option<SongSource> {
+        let source = self.in_flight.remove(key)?;
+        if let Some(FetchError::Failed(_)) = error {
+            match self.retried.insert(key.clone()) {
+                true => {
+                    self.pending.push_front((key.clone(), source));
+                    return None;
+                }
+                false => self.given_up += 1,
+            }
+        }
+        self.retried.remove(key);
+        self.refreshing.remove(key);
+        Some(source)
+    }
+
+    /// How many songs were given up on, once nothing is left to ask for. The count starts
+    /// over after it's been taken.
+    pub fn take_given_up(&mut self) -> Option<usize> {
+        match self.pending.is_empty() && self.in_flight.is_empty() && self.given_up > 0 {
+            true => Some(std::mem::take(&mut self.given_up)),
+            false => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::song::SongSource;
+
+    use super::{FetchError, MetadataQueue};
+
+    fn keys(keys: &[&str]) -> Vec<String> {
+        keys.iter().map(|key| key.to_string()).collect()
+    }
+
+    #[test]
+    fn only_a_few_songs_are_asked_for_at_once() {
+        let mut queue = MetadataQueue::default();
+        let source = SongSource::Manual;
+        queue.request(keys(&["a", "b", "c", "d", "e"]), &source);
+        queue.request(keys(&["a"]), &source);
+        assert_eq![queue.next_batch(2, |_| false), keys(&["a", "b"])];
+        assert![queue.next_batch(2, |_| false).is_empty()];
+
+        assert_eq![queue.finish(&"a".into(), None), Some(source.clone())];
+        assert_eq![queue.finish(&"b".into(), None), Some(source.clone())];
+        // Songs cached in the meantime aren't asked for
+        assert_eq![queue.next_batch(2, |key| key == "c"), keys(&["d", "e"])];
+    }
+
+    #[test]
+    fn failures_are_retried_once_then_counted() {
+        let mut queue = MetadataQueue::default();
+        let source = SongSource::Manual;
+        let failed = FetchError::Failed("timed out".into());
+        let failed = Some(&failed);
+        queue.request(keys(&["a", "b", "c"]), &source);
+        assert_eq![queue.next_batch(2, |_| false), keys(&["a", "b"])];
+        assert_eq![queue.finish(&"a".into(), failed), None];
+        assert_eq![queue.finish(&"b".into(), None), Some(source.clone())];
+
+        // The retry goes before the songs that weren't asked for yet
+        assert_eq![queue.next_batch(2, |_| false), keys(&["a", "c"])];
+        assert_eq![queue.finish(&"a".into(), failed), Some(source.clone())];
+        assert_eq![queue.take_given_up(), None];
+        queue.finish(&"c".into(), None);
+        assert_eq![queue.take_given_up(), Some(1)];
+        assert_eq![queue.take_given_up(), None];
+    }
+
+    #[test]
+    fn refreshed_songs_are_asked_for_though_cached() {
+        let mut queue = MetadataQueue::default();
+        queue.refresh(keys(&["a", "b"]));
+        queue.request(keys(&["c"]), &SongSource::Manual);
+        assert_eq![queue.next_batch(4, |_| true), keys(&["a", "b"])];
+
+        // Gone videos aren't retried or counted as failures
+        let gone = Some(&FetchError::Unavailable);
+        assert_eq![queue.finish(&"a".into(), gone), Some(SongSource::Unknown)];
+        queue.finish(&"b".into(), None);
+        assert_eq![queue.take_given_up(), None];
+
+        // Once refreshed, they're cached songs like any other
+        queue.request(keys(&["a"]), &SongSource::Manual);
+        assert![queue.next_batch(4, |_| true).is_empty()];
+    }
+}
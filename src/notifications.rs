@@ -0,0 +1,234 @@
+//! Short-lived messages about what just went wrong, or right, shown over the top of the window.
+//! Failures that can be retried are also kept in the failures panel, these only tell of them.
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use iced::{
+    alignment::Horizontal,
+    widget::{button, container, row, text, Column},
+    Alignment, Background, Border, Color, Element, Length, Subscription,
+};
+
+use crate::{failures::FailureKind, subscriptions};
+
+/// How many notifications are shown at once, the oldest are dropped first
+pub const MAX_NOTIFICATIONS: usize = 5;
+/// How long a notification stays up unless it's dismissed
+pub const NOTIFICATION_LIFETIME: Duration = Duration::from_secs(6);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Info,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    pub level: Level,
+    pub text: String,
+    /// The entry of the failures panel that tells more
+    pub details: Option<(FailureKind, String)>,
+}
+
+impl Notification {
+    pub fn info(text: impl Into<String>) -> Self {
+        Self {
+            level: Level::Info,
+            text: text.into(),
+            details: None,
+        }
+    }
+
+    pub fn error(text: impl Into<String>) -> Self {
+        Self {
+            level: Level::Error,
+            text: text.into(),
+            details: None,
+        }
+    }
+
+    /// Links the notification to the failure recorded for the subject
+    pub fn with_details(self, kind: FailureKind, subject: impl Into<String>) -> Self {
+        Self {
+            details: Some((kind, subject.into())),
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum NotificationsMsg {
+    Dismiss(u64),
+    /// Dismisses the notification to show its failure in the failures panel
+    Details {
+        id: u64,
+        kind: FailureKind,
+        subject: String,
+    },
+    /// Drops the notifications that have been up long enough
+    Tick(Instant),
+}
+
+#[derive(Debug, Clone)]
+struct Shown {
+    id: u64,
+    notification: Notification,
+    since: Instant,
+}
+
+/// The notifications being shown, oldest first
+#[derive(Debug, Default)]
+pub struct Notifications {
+    shown: VecDeque<Shown>,
+    next_id: u64,
+}
+
+impl Notifications {
+    pub fn push(&mut self, notification: Notification, now: Instant) {
+        self.shown.push_back(Shown {
+            id: self.next_id,
+            notification,
+            since: now,
+        });
+        self.next_id += 1;
+        while self.shown.len() > MAX_NOTIFICATIONS {
+            self.shown.pop_front();
+        }
+    }
+
+    pub fn update(&mut self, msg: NotificationsMsg) {
+        match msg {
+            NotificationsMsg::Dismiss(id) | NotificationsMsg::Details { id, .. } => {
+                self.shown.retain(|shown| shown.id != id)
+            }
+            NotificationsMsg::Tick(now) => self
+                .shown
+                .retain(|shown| now.saturating_duration_since(shown.since) < NOTIFICATION_LIFETIME),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shown.is_empty()
+    }
+
+    /// Only ticks while something is shown
+    pub fn subscription(&self) -> Subscription<NotificationsMsg> {
+        match self.is_empty() {
+            true => Subscription::none(),
+            false => subscriptions::every("notifications", Duration::from_secs(1))
+                .map(NotificationsMsg::Tick),
+        }
+    }
+
+    /// A layer to stack over the window, with the toasts in its top right corner
+    pub fn view(&self) -> Option<Element<NotificationsMsg>> {
+        if self.is_empty() {
+            return None;
+        }
+        let toasts = self.shown.iter().rev().map(|shown| {
+            let label = match shown.notification.level {
+                Level::Info => "info",
+                Level::Error => "error",
+            };
+            container(
+                row![
+                    text(label).width(50),
+                    text(shown.notification.text.clone()).width(Length::Fill),
+                ]
+                .push_maybe(shown.notification.details.clone().map(|(kind, subject)| {
+                    button("details").on_press(NotificationsMsg::Details {
+                        id: shown.id,
+                        kind,
+                        subject,
+                    })
+                }))
+                .push(button("dismiss").on_press(NotificationsMsg::Dismiss(shown.id)))
+                .spacing(8)
+                .align_items(Alignment::Center),
+            )
+            .padding(6)
+            .style(|_| container::Style {
+                background: Some(Background::Color(Color::BLACK)),
+                border: Border::rounded(4).with_width(1).with_color(Color::WHITE),
+                ..Default::default()
+            })
+            .into()
+        });
+        Some(
+            container(Column::with_children(toasts).spacing(4).max_width(420))
+                .padding(10)
+                .width(Length::Fill)
+                .align_x(Horizontal::Right)
+                .into(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::failures::FailureKind;
+
+    use super::{
+        Notification, Notifications, NotificationsMsg, MAX_NOTIFICATIONS, NOTIFICATION_LIFETIME,
+    };
+
+    fn texts(notifications: &Notifications) -> Vec<&str> {
+        notifications
+            .shown
+            .iter()
+            .map(|shown| shown.notification.text.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn the_oldest_notifications_are_dropped() {
+        let now = Instant::now();
+        let mut notifications = Notifications::default();
+        for n in 0..MAX_NOTIFICATIONS + 2 {
+            notifications.push(Notification::error(n.to_string()), now);
+        }
+        assert_eq![texts(&notifications), ["2", "3", "4", "5", "6"]];
+        assert_eq![texts(&notifications).len(), MAX_NOTIFICATIONS];
+    }
+
+    #[test]
+    fn notifications_expire_or_are_dismissed() {
+        let now = Instant::now();
+        let mut notifications = Notifications::default();
+        notifications.push(Notification::error("old"), now);
+        notifications.push(Notification::info("new"), now + Duration::from_secs(3));
+        notifications.push(
+            Notification::info("dismissed"),
+            now + Duration::from_secs(3),
+        );
+
+        notifications.update(NotificationsMsg::Dismiss(2));
+        notifications.update(NotificationsMsg::Tick(now + NOTIFICATION_LIFETIME));
+        assert_eq![texts(&notifications), ["new"]];
+
+        notifications.update(NotificationsMsg::Tick(now + NOTIFICATION_LIFETIME * 2));
+        assert![notifications.is_empty()];
+    }
+
+    #[test]
+    fn asking_for_details_dismisses_the_notification() {
+        let now = Instant::now();
+        let mut notifications = Notifications::default();
+        let failed = Notification::error("failed").with_details(FailureKind::Download, "a");
+        assert_eq![failed.details, Some((FailureKind::Download, "a".into()))];
+        notifications.push(failed, now);
+        notifications.push(Notification::info("other"), now);
+
+        notifications.update(NotificationsMsg::Details {
+            id: 0,
+            kind: FailureKind::Download,
+            subject: "a".into(),
+        });
+        assert_eq![texts(&notifications), ["other"]];
+    }
+}
@@ -0,0 +1,186 @@
+//! The panel above the tracker bar, with the playing song's art and details, and where in the
+//! playlist it's playing from. Each group on the way to the song can be clicked to show it.
+
+use iced::{
+    advanced::widget::Id as WId,
+    widget::{button, column, row, text, Row},
+    Alignment, Element,
+};
+
+use crate::{
+    settings::SongKey,
+    song::{format_rating, Song, SongData},
+    song_operations::{ConstructorItem, SongOpConstructor, TreeDirected},
+};
+
+/// How big the art is drawn
+const ART_SIZE: u16 = 160;
+
+#[derive(Debug, Clone)]
+pub enum NowPlayingMsg {
+    /// Shows the group with the id in the playlist, opening it
+    OpenGroup(WId),
+}
+
+/// A group on the way from the playlist to the playing song
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupStep {
+    pub label: String,
+    pub id: WId,
+}
+
+#[derive(Debug)]
+pub struct NowPlaying {
+    data: SongData,
+    album: Option<String>,
+    views: Option<usize>,
+    groups: Vec<GroupStep>,
+    /// Starting at 0, among every song in the playlist
+    position: Option<(usize, usize)>,
+}
+
+impl NowPlaying {
+    /// `path` is where the song is in the tree, if it's playing from the playlist.
+    /// Songs that aren't cached are shown as a mystery.
+    pub fn new(
+        key: &SongKey,
+        song: Option<&Song>,
+        root: &SongOpConstructor,
+        name: &str,
+        path: Option<&[usize]>,
+    ) -> Self {
+        let data = match song {
+            Some(song) => song.as_data(),
+            None => SongData::mystery_with_title(key.clone()),
+        };
+        Self {
+            data,
+            album: song.and_then(|song| song.album.clone()),
+            views: song.and_then(|song| song.view_count),
+            groups: path
+                .map(|path| group_steps(root, name, path))
+                .unwrap_or_default(),
+            position: path.and_then(|path| song_position(root, path)),
+        }
+    }
+
+    pub fn view<'a>(self) -> Element<'a, NowPlayingMsg> {
+        let art = SongData::image_or_placeholder(self.data.handle.clone(), ART_SIZE, ART_SIZE);
+        let artists = match &self.data.artists {
+            Some(artists) if !artists.is_empty() => artists.join(", "),
+            _ => self.data.channel.clone(),
+        };
+        let views = self
+            .views
+            .map(|views| format!("{} views", format_views(views)));
+        let rating = self.data.rating.map(format_rating);
+        let position = self
+            .position
+            .map(|(idx, count)| format!("song {} of {}", idx + 1, count));
+
+        // The groups, then the song they end at
+        let from_playlist = !self.groups.is_empty();
+        let mut path = Row::new().spacing(4).align_items(Alignment::Center);
+        for step in self.groups {
+            path = path
+                .push(button(text(step.label)).on_press(NowPlayingMsg::OpenGroup(step.id)))
+                .push(text("▸"));
+        }
+        let path = from_playlist.then(|| path.push(text(self.data.title.clone())));
+
+        let details = column![text(self.data.title).size(24), text(artists)]
+            .push_maybe(self.album.map(text))
+            .push_maybe(views.map(text))
+            .push_maybe(rating.map(text))
+            .push_maybe(position.map(text))
+            .push_maybe(path)
+            .spacing(4);
+        row![art, details]
+            .spacing(12)
+            .align_items(Alignment::Center)
+            .into()
+    }
+}
+
+/// The groups holding the song at the tree path, starting with the playlist itself
+pub fn group_steps(root: &SongOpConstructor, name: &str, path: &[usize]) -> Vec<GroupStep> {
+    let name = match name.is_empty() {
+        true => "playlist",
+        false => name,
+    };
+    (0..path.len())
+        .filter_map(|end| root.group_at_path(&path[..end]).map(|group| (end, group)))
+        .map(|(end, group)| GroupStep {
+            label: match end {
+                0 => name.to_string(),
+                _ => group.label(),
+            },
+            id: group.widget_id(),
+        })
+        .collect()
+}
+
+/// Where the song at the tree path is among every song in the playlist, and how many there are
+pub fn song_position(root: &SongOpConstructor, path: &[usize]) -> Option<(usize, usize)> {
+    let id = match root.item_at_path(path.to_vec().into())? {
+        ConstructorItem::Song(_, sid) => WId::from(sid.0.clone()),
+        ConstructorItem::Operation(_) => return None,
+    };
+    let songs = root.songs_with_ids();
+    let idx = songs.iter().position(|(_, sid)| *sid == id)?;
+    Some((idx, songs.len()))
+}
+
+/// e.g. "1,234,567"
+fn format_views(views: usize) -> String {
+    let digits = views.to_string();
+    let mut formatted = String::new();
+    for (idx, digit) in digits.chars().enumerate() {
+        if idx > 0 && (digits.len() - idx) % 3 == 0 {
+            formatted.push(',');
+        }
+        formatted.push(digit);
+    }
+    formatted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_views, group_steps, song_position};
+    use crate::song_operations::{ActualRecursiveOps, ConstructorItem, SongOpConstructor};
+
+    fn song(key: &str) -> ConstructorItem {
+        ConstructorItem::from(key.to_string())
+    }
+
+    #[test]
+    fn the_path_names_each_group_down_to_the_song() {
+        let group = SongOpConstructor::new(
+            ActualRecursiveOps::LoopNTimes,
+            vec![song("b"), song("c")],
+            None,
+        )
+        .with_n(3);
+        let group_id = group.widget_id();
+        let root = SongOpConstructor::from(vec![song("a"), group.into()]);
+
+        let steps = group_steps(&root, "", &[1, 1]);
+        let labels: Vec<&str> = steps.iter().map(|step| step.label.as_str()).collect();
+        assert_eq![labels, ["playlist", "Loop N Times (3)"]];
+        assert_eq![steps[0].id, root.widget_id()];
+        assert_eq![steps[1].id, group_id];
+        assert_eq![group_steps(&root, "Gym", &[0])[0].label, "Gym"];
+
+        assert_eq![song_position(&root, &[1, 1]), Some((2, 3))];
+        assert_eq![song_position(&root, &[1]), None];
+        assert_eq![song_position(&root, &[5]), None];
+    }
+
+    #[test]
+    fn views_are_grouped_by_thousands() {
+        assert_eq![format_views(0), "0"];
+        assert_eq![format_views(999), "999"];
+        assert_eq![format_views(1000), "1,000"];
+        assert_eq![format_views(1234567), "1,234,567"];
+    }
+}
@@ -0,0 +1,132 @@
+//! The search and the playlist side by side, split where the divider was dragged to.
+//! Either pane can be maximized, which collapses the other one.
+
+use iced::{
+    widget::{
+        button,
+        pane_grid::{self, Axis, Configuration, Content, PaneGrid, TitleBar},
+        text,
+    },
+    Element, Length,
+};
+
+use crate::settings::DEFAULT_SPLIT;
+
+/// Neither pane can be dragged thinner than this part of the width
+const MIN_SPLIT: f32 = 0.15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaneKind {
+    Search,
+    Playlist,
+}
+
+impl PaneKind {
+    fn title(&self) -> &'static str {
+        match self {
+            Self::Search => "search",
+            Self::Playlist => "playlist",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum PanesMsg {
+    Resized(pane_grid::ResizeEvent),
+    /// Maximizes the pane, or restores it if it's maximized
+    ToggleMaximized(pane_grid::Pane),
+}
+
+#[derive(Debug)]
+pub struct Panes {
+    state: pane_grid::State<PaneKind>,
+}
+
+impl Default for Panes {
+    fn default() -> Self {
+        Self::new(DEFAULT_SPLIT)
+    }
+}
+
+impl Panes {
+    pub fn new(split: f32) -> Self {
+        let state = pane_grid::State::with_configuration(Configuration::Split {
+            axis: Axis::Vertical,
+            ratio: clamp_split(split),
+            a: Box::new(Configuration::Pane(PaneKind::Search)),
+            b: Box::new(Configuration::Pane(PaneKind::Playlist)),
+        });
+        Self { state }
+    }
+
+    /// The new split when the divider was dragged, to be saved
+    pub fn update(&mut self, msg: PanesMsg) -> Option<f32> {
+        match msg {
+            PanesMsg::Resized(pane_grid::ResizeEvent { split, ratio }) => {
+                let ratio = clamp_split(ratio);
+                self.state.resize(split, ratio);
+                Some(ratio)
+            }
+            PanesMsg::ToggleMaximized(pane) => {
+                match self.state.maximized() {
+                    Some(_) => self.state.restore(),
+                    None => self.state.maximize(pane),
+                }
+                None
+            }
+        }
+    }
+
+    /// The panes' contents are built by `body`, their messages are passed through as they are
+    pub fn view<'a, M: Clone + 'a>(
+        &'a self,
+        body: impl Fn(PaneKind) -> Element<'a, M>,
+        on_msg: fn(PanesMsg) -> M,
+    ) -> Element<'a, M> {
+        PaneGrid::new(&self.state, |pane, kind, maximized| {
+            let toggle = button(match maximized {
+                true => "restore",
+                false => "maximize",
+            })
+            .on_press(on_msg(PanesMsg::ToggleMaximized(pane)));
+            let title_bar = TitleBar::new(text(kind.title())).controls(toggle);
+            Content::new(body(*kind)).title_bar(title_bar)
+        })
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .spacing(8)
+        .on_resize(10, move |event| on_msg(PanesMsg::Resized(event)))
+        .into()
+    }
+}
+
+/// Splits saved by hand could leave a pane with no room at all
+pub fn clamp_split(split: f32) -> f32 {
+    match split.is_finite() {
+        true => split.clamp(MIN_SPLIT, 1.0 - MIN_SPLIT),
+        false => DEFAULT_SPLIT,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{clamp_split, Panes, PanesMsg, DEFAULT_SPLIT};
+
+    #[test]
+    fn splits_leave_room_for_both_panes() {
+        assert_eq![clamp_split(0.3), 0.3];
+        assert_eq![clamp_split(0.0), 0.15];
+        assert_eq![clamp_split(1.0), 1.0 - clamp_split(0.0)];
+        assert_eq![clamp_split(f32::NAN), DEFAULT_SPLIT];
+    }
+
+    #[test]
+    fn maximizing_toggles() {
+        let mut panes = Panes::default();
+        let pane = *panes.state.panes.keys().next().unwrap();
+        assert_eq![panes.update(PanesMsg::ToggleMaximized(pane)), None];
+        assert_eq![panes.state.maximized(), Some(pane)];
+        panes.update(PanesMsg::ToggleMaximized(pane));
+        assert_eq![panes.state.maximized(), None];
+    }
+}
@@ -1,36 +1,89 @@
 use iced::{
-    widget::{button, column, row, scrollable, text, text_input},
-    Command, Element,
+    advanced::widget::Id as WId,
+    keyboard::{self, key::Named},
+    widget::{
+        button, checkbox, column, pick_list, row, scrollable,
+        scrollable::{RelativeOffset, Viewport},
+        text, text_input, Column,
+    },
+    Command, Element, Rectangle,
 };
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use std::{
+    collections::{HashSet, VecDeque},
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
 use crate::{
-    song_operations::{SongOpConstructor, SongOpMessage},
+    export::ExportFormat,
+    settings::{playlist_path, playlists_directory, LoadError, SaveError, SongKey},
+    song_operations::{
+        tree_diff::TreeDiff, tree_filter::TreeFilter, ConstructorItem, SongOpConstructor,
+        SongOpMessage, TreeDirected, VisibleRow,
+    },
     styling::FullYtmrsScheme,
+    thumbnails::OnScreen,
+    user_input::FocusCursor,
 };
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PlaylistHeader {
     pub id: uuid::Uuid,
     pub name: String,
     pub num_of_songs: usize,
+    /// The songs of the playlist as it was saved, kept in the cache while it's in the library
+    #[serde(skip)]
+    pub keys: Vec<SongKey>,
 }
 impl From<&Playlist> for PlaylistHeader {
     fn from(value: &Playlist) -> Self {
+        let keys: Vec<SongKey> = value.constructor.all_song_keys_rec().cloned().collect();
         Self {
             id: value.id,
             name: value.name.clone(),
-            num_of_songs: value.constructor.all_song_keys_rec().count(),
+            num_of_songs: keys.len(),
+            keys,
         }
     }
 }
+impl Display for PlaylistHeader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self.name.is_empty() {
+            true => "untitled",
+            false => &self.name,
+        };
+        write!(f, "{name} ({} songs)", self.num_of_songs)
+    }
+}
 
 #[derive(Debug, Clone)]
 pub enum PlaylistMessage {
     ConstructorMessage(SongOpMessage),
     NameEdited(String),
     Save,
+    Export(ExportFormat),
+    /// Asks the backend for the info of every song in the playlist again
+    RefreshInfo,
+    /// Downloads every song in the playlist that isn't in the sounds cache
+    DownloadAll,
+    /// Opens the saved playlist with the id
+    Switch(Uuid),
+    New,
+    AskDelete,
+    ConfirmDelete,
+    CancelDelete,
+    ImportPathEdited(String),
+    /// Opens the file at the import path as a new playlist
+    Import,
+    Scrolled(Viewport),
+    AllowDuplicatesToggled(bool),
+    /// Shows only the songs matching what's typed
+    FilterEdited(String),
+    /// Focuses the next song the filter matched, and scrolls to it
+    NextMatch,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,6 +91,22 @@ pub struct Playlist {
     pub id: uuid::Uuid,
     pub name: String,
     pub constructor: SongOpConstructor,
+    /// Adds songs that are in the playlist already without asking first
+    #[serde(default)]
+    pub allow_duplicates: bool,
+    #[serde(skip)]
+    pub focus: FocusCursor,
+    /// The rows on screen, to download their thumbnails first
+    #[serde(skip)]
+    pub on_screen: OnScreen,
+    /// The edits of the tree that can be undone
+    #[serde(skip)]
+    pub history: EditHistory,
+    /// What's typed in the filter box
+    #[serde(skip)]
+    pub filter: String,
+    #[serde(skip, default = "scrollable::Id::unique")]
+    scroll_id: scrollable::Id,
 }
 
 impl Default for Playlist {
@@ -46,21 +115,171 @@ impl Default for Playlist {
             id: Uuid::new_v4(),
             name: Default::default(),
             constructor: Default::default(),
+            allow_duplicates: false,
+            focus: FocusCursor::default(),
+            on_screen: OnScreen::default(),
+            history: EditHistory::default(),
+            filter: String::new(),
+            scroll_id: scrollable::Id::unique(),
         }
     }
 }
 impl Playlist {
-    pub fn view(&self, scheme: &FullYtmrsScheme) -> Element<PlaylistMessage> {
+    /// Writes the playlist to its own file in the playlists directory
+    pub async fn save(self) -> Result<PathBuf, SaveError> {
+        self.save_to(&playlists_directory()).await
+    }
+
+    pub async fn save_to(self, dir: &Path) -> Result<PathBuf, SaveError> {
+        let json = serde_json::to_string_pretty(&self).map_err(|_| SaveError::Format)?;
+        async_std::fs::create_dir_all(dir)
+            .await
+            .map_err(|_| SaveError::File)?;
+
+        let path = playlist_path(dir, self.id);
+        async_std::fs::write(&path, json)
+            .await
+            .map_err(|_| SaveError::Write)?;
+        Ok(path)
+    }
+
+    pub async fn load_from(dir: &Path, id: Uuid) -> Result<Self, LoadError> {
+        Self::read(playlist_path(dir, id)).await
+    }
+
+    /// A playlist's file, which doesn't have to be in the library
+    pub async fn read(path: PathBuf) -> Result<Self, LoadError> {
+        let json = async_std::fs::read_to_string(path)
+            .await
+            .map_err(|_| LoadError::File)?;
+        serde_json::from_str(&json).map_err(|_| LoadError::Format)
+    }
+
+    /// The song row or group header that has keyboard focus
+    pub fn focused_row(&self) -> Option<VisibleRow> {
+        let mut visible = self.constructor.visible_rows();
+        let idx = self.focus.get()?;
+        (idx < visible.len()).then(|| visible.swap_remove(idx))
+    }
+
+    /// The id of the song row that has keyboard focus
+    pub fn focused_song(&self) -> Option<WId> {
+        match self.focused_row()? {
+            VisibleRow::Song(id) => Some(id),
+            VisibleRow::Group(_) => None,
+        }
+    }
+
+    /// Moves the keyboard focus to the song. Returns false if its row isn't visible.
+    pub fn focus_song(&mut self, id: &WId) -> bool {
+        let row = VisibleRow::Song(id.clone());
+        match self
+            .constructor
+            .visible_rows()
+            .iter()
+            .position(|v| *v == row)
+        {
+            Some(idx) => {
+                self.focus.set(idx);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Appends the songs to the focused group, or the group holding the focused row, or to the
+    /// playlist itself when nothing is focused. Returns the ids of their new rows.
+    pub fn add_to_focused_group(&mut self, keys: Vec<SongKey>) -> Vec<WId> {
+        let group = match self.focused_row() {
+            Some(VisibleRow::Song(id)) => self.constructor.path_to_id(&id).map(|mut path| {
+                path.pop();
+                path
+            }),
+            Some(VisibleRow::Group(id)) => self.constructor.path_to_id(&id),
+            None => None,
+        };
+        let group = group.unwrap_or_default();
+        let mut ids = vec![];
+        for key in keys {
+            let item = ConstructorItem::from(key);
+            if let ConstructorItem::Song(_, sid) = &item {
+                ids.push(WId::from(sid.0.clone()));
+            }
+            self.constructor.push_to_path(group.clone().into(), item);
+        }
+        ids
+    }
+
+    /// The keys that are in the playlist already
+    pub fn present(&self, keys: &[SongKey]) -> HashSet<SongKey> {
+        let existing: HashSet<&SongKey> = self.constructor.all_song_keys_rec().collect();
+        keys.iter()
+            .filter(|key| existing.contains(key))
+            .cloned()
+            .collect()
+    }
+
+    /// Describes the row that has keyboard focus
+    pub fn focused_description(&self) -> Option<String> {
+        self.constructor
+            .song_data(&self.focused_song()?)
+            .map(|data| data.describe())
+    }
+
+    /// Moves the keyboard focus. Returns the id of the song to play when Enter was pressed on
+    /// a song, Enter on a group's header collapses or opens it.
+    pub fn navigate(&mut self, key: &keyboard::Key) -> Option<WId> {
+        let len = self.constructor.visible_rows().len();
+        match key {
+            keyboard::Key::Named(Named::ArrowDown) => self.focus.move_down(len),
+            keyboard::Key::Named(Named::ArrowUp) => self.focus.move_up(len),
+            keyboard::Key::Named(Named::Enter) => {
+                self.focus.clamp(len);
+                match self.focused_row()? {
+                    VisibleRow::Song(id) => return Some(id),
+                    VisibleRow::Group(id) => self.constructor.toggle_group(&id),
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// `menu` is the song row whose menu is open
+    pub fn view(&self, scheme: &FullYtmrsScheme, menu: Option<&WId>) -> Element<PlaylistMessage> {
         let name_edit =
             text_input(&self.id.to_string(), &self.name).on_input(PlaylistMessage::NameEdited);
         let save_button = button(text("save")).on_press(PlaylistMessage::Save);
+        let export_button = |format: ExportFormat| {
+            button(text(format.label())).on_press(PlaylistMessage::Export(format))
+        };
 
-        let constructor = scrollable(
-            Element::new(self.constructor.view(scheme)).map(PlaylistMessage::ConstructorMessage),
-        )
-        .style(scheme.scrollable_style.clone().update());
+        let focused = self.focused_row().map(|row| match row {
+            VisibleRow::Song(id) | VisibleRow::Group(id) => id,
+        });
+        let tree = self
+            .constructor
+            .view(scheme, focused.as_ref(), menu, &self.on_screen);
+        let constructor = scrollable(Element::new(tree).map(PlaylistMessage::ConstructorMessage))
+            .id(self.scroll_id.clone())
+            .on_scroll(PlaylistMessage::Scrolled)
+            .style(scheme.scrollable_style.clone().update());
 
-        column![row![name_edit, save_button], constructor].into()
+        let buttons = row![
+            name_edit,
+            save_button,
+            export_button(ExportFormat::M3u8),
+            export_button(ExportFormat::Json),
+            button(text("refresh info")).on_press(PlaylistMessage::RefreshInfo),
+            button(text("download all")).on_press(PlaylistMessage::DownloadAll),
+            checkbox("allow repeats", self.allow_duplicates)
+                .on_toggle(PlaylistMessage::AllowDuplicatesToggled),
+        ]
+        .align_items(iced::Alignment::Center);
+        let filter = text_input("filter songs", &self.filter)
+            .on_input(PlaylistMessage::FilterEdited)
+            .on_submit(PlaylistMessage::NextMatch);
+        column![buttons, filter, constructor].into()
     }
 
     pub fn update(&mut self, message: PlaylistMessage) -> Command<PlaylistMessage> {
@@ -69,7 +288,549 @@ impl Playlist {
                 self.name = value;
                 Command::none()
             }
+            PlaylistMessage::AllowDuplicatesToggled(allow) => {
+                self.allow_duplicates = allow;
+                Command::none()
+            }
+            PlaylistMessage::FilterEdited(value) => {
+                match TreeFilter::new(&value) {
+                    Some(filter) => self.constructor.apply_filter(filter),
+                    None => self.constructor.clear_filter(),
+                }
+                self.filter = value;
+                // The rows under the cursor changed
+                self.focus.clear();
+                Command::none()
+            }
+            PlaylistMessage::NextMatch => self.next_match(),
             _ => Command::none(),
         }
     }
+
+    /// Focuses the song row after the focused row, or the first one past the last
+    fn next_match(&mut self) -> Command<PlaylistMessage> {
+        let visible = self.constructor.visible_rows();
+        let start = self.focus.get().map_or(0, |idx| idx + 1).min(visible.len());
+        let next = (start..visible.len())
+            .chain(0..start)
+            .find_map(|idx| match &visible[idx] {
+                VisibleRow::Song(id) => Some((idx, self.constructor.offset_of(id)?)),
+                VisibleRow::Group(_) => None,
+            });
+        let (next, offset) = match next {
+            Some(found) => found,
+            None => return Command::none(),
+        };
+        self.focus.set(next);
+        self.scroll_to(offset)
+    }
+
+    /// Opens the group with the id and the groups holding it, then focuses its header and
+    /// scrolls to it
+    pub fn reveal_group(&mut self, id: &WId) -> Command<PlaylistMessage> {
+        self.constructor.expand_to(id);
+        let row = VisibleRow::Group(id.clone());
+        let rows = self.constructor.visible_rows();
+        // The playlist's own header isn't focusable
+        if let Some(idx) = rows.iter().position(|v| *v == row) {
+            self.focus.set(idx);
+        }
+        match self.constructor.offset_of(id) {
+            Some(offset) => self.scroll_to(offset),
+            None => Command::none(),
+        }
+    }
+
+    /// Puts the point of the tree at the top of the screen, or as near as scrolling goes
+    fn scroll_to(&self, offset: f32) -> Command<PlaylistMessage> {
+        let seen = match self.on_screen.fractions() {
+            Some((start, end)) => end - start,
+            None => 0.0,
+        };
+        let y = match seen < 1.0 {
+            true => offset / self.constructor.height() / (1.0 - seen),
+            false => 0.0,
+        };
+        scrollable::snap_to(
+            self.scroll_id.clone(),
+            RelativeOffset {
+                x: 0.0,
+                y: y.clamp(0.0, 1.0),
+            },
+        )
+    }
+}
+
+/// The most edits of a playlist that can be undone, the oldest are forgotten first
+pub const MAX_UNDO: usize = 100;
+
+/// The trees from before each edit, and from before each undo.
+/// They're kept whole so undoing brings the same rows back, with the ids they had.
+#[derive(Debug, Clone, Default)]
+pub struct EditHistory {
+    undo: VecDeque<SongOpConstructor>,
+    redo: Vec<SongOpConstructor>,
+}
+
+impl EditHistory {
+    /// Keeps the tree from before an edit, if the edit changed it. Returns whether it did.
+    /// The tree is only kept once when an edit is recorded within another one.
+    pub fn record(&mut self, before: SongOpConstructor, after: &SongOpConstructor) -> bool {
+        let recorded = self.undo.back().is_some_and(|last| last.same_tree(&before));
+        if before.same_tree(after) || recorded {
+            return false;
+        }
+        self.undo.push_back(before);
+        if self.undo.len() > MAX_UNDO {
+            self.undo.pop_front();
+        }
+        self.redo.clear();
+        true
+    }
+
+    /// Puts the tree from before the last edit back. Returns false when there's none.
+    pub fn undo(&mut self, tree: &mut SongOpConstructor) -> bool {
+        match self.undo.pop_back() {
+            Some(previous) => {
+                self.redo.push(restore(tree, previous));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Puts the tree from before the last undo back. Returns false when there's none.
+    pub fn redo(&mut self, tree: &mut SongOpConstructor) -> bool {
+        match self.redo.pop() {
+            Some(next) => {
+                self.undo.push_back(restore(tree, next));
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Replaces the tree, returning the one it replaced
+fn restore(tree: &mut SongOpConstructor, mut other: SongOpConstructor) -> SongOpConstructor {
+    other.edited();
+    std::mem::replace(tree, other)
+}
+
+/// Removes the playlist's file. Playlists that were never saved have nothing to remove.
+pub async fn delete_playlist(dir: &Path, id: Uuid) -> Result<(), SaveError> {
+    match async_std::fs::remove_file(playlist_path(dir, id)).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(SaveError::File),
+        _ => Ok(()),
+    }
+}
+
+/// The headers of every playlist saved in the directory, sorted by name
+pub async fn load_headers(dir: &Path) -> Vec<PlaylistHeader> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        // Nothing was saved yet
+        Err(_) => return vec![],
+    };
+    let mut headers = vec![];
+    for path in entries.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+        if path.extension().is_some_and(|ext| ext == "json") {
+            let json = async_std::fs::read_to_string(&path).await;
+            match json.map(|json| serde_json::from_str::<Playlist>(&json)) {
+                Ok(Ok(playlist)) => headers.push(PlaylistHeader::from(&playlist)),
+                _ => println!["Skipping unreadable playlist {path:?}"],
+            }
+        }
+    }
+    sort_headers(&mut headers);
+    headers
+}
+
+fn sort_headers(headers: &mut [PlaylistHeader]) {
+    headers.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+}
+
+/// The saved playlists the open one can be switched to
+#[derive(Debug, Default)]
+pub struct PlaylistLibrary {
+    headers: Vec<PlaylistHeader>,
+    /// Changes whenever a playlist is added, replaced or forgotten
+    revision: u64,
+    confirming_delete: bool,
+    /// The M3U or exported playlist to import
+    pub import_path: String,
+}
+
+impl PlaylistLibrary {
+    pub fn set_headers(&mut self, headers: Vec<PlaylistHeader>) {
+        self.headers = headers;
+        self.revision = self.revision.wrapping_add(1);
+    }
+
+    /// Adds the playlist, replacing what was known about it
+    pub fn record(&mut self, playlist: &Playlist) {
+        let header = PlaylistHeader::from(playlist);
+        match self.headers.iter().position(|h| h.id == header.id) {
+            Some(idx) => self.headers[idx] = header,
+            None => self.headers.push(header),
+        }
+        sort_headers(&mut self.headers);
+        self.revision = self.revision.wrapping_add(1);
+    }
+
+    pub fn headers(&self) -> &[PlaylistHeader] {
+        &self.headers
+    }
+
+    pub fn forget(&mut self, id: Uuid) {
+        self.headers.retain(|header| header.id != id);
+        self.revision = self.revision.wrapping_add(1);
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// The songs of every saved playlist, as many times as they're in them
+    pub fn used_keys(&self) -> impl Iterator<Item = &SongKey> {
+        self.headers.iter().flat_map(|header| &header.keys)
+    }
+
+    /// The playlist to open in place of the one with the id
+    pub fn other_than(&self, id: Uuid) -> Option<Uuid> {
+        self.headers
+            .iter()
+            .find(|header| header.id != id)
+            .map(|header| header.id)
+    }
+
+    pub fn set_confirming_delete(&mut self, confirming: bool) {
+        self.confirming_delete = confirming;
+    }
+
+    pub fn view(&self, open: &Playlist) -> Element<PlaylistMessage> {
+        // The open playlist may have been renamed or edited since it was saved
+        let current = PlaylistHeader::from(open);
+        let mut options: Vec<PlaylistHeader> = self
+            .headers
+            .iter()
+            .filter(|header| header.id != open.id)
+            .cloned()
+            .collect();
+        options.push(current.clone());
+        sort_headers(&mut options);
+
+        let delete = match self.confirming_delete {
+            false => row![button("delete").on_press(PlaylistMessage::AskDelete)],
+            true => row![
+                text(format!("Delete {current}?")),
+                button("delete").on_press(PlaylistMessage::ConfirmDelete),
+                button("cancel").on_press(PlaylistMessage::CancelDelete),
+            ],
+        };
+        let import = row![
+            text_input("playlist file to import", &self.import_path)
+                .on_input(PlaylistMessage::ImportPathEdited)
+                .on_submit(PlaylistMessage::Import)
+                .width(240),
+            button("import").on_press_maybe(
+                (!self.import_path.trim().is_empty()).then_some(PlaylistMessage::Import)
+            ),
+        ];
+        row![
+            pick_list(options, Some(current), |header| PlaylistMessage::Switch(
+                header.id
+            )),
+            button("new").on_press(PlaylistMessage::New),
+            delete.spacing(4).align_items(iced::Alignment::Center),
+            import.spacing(4).align_items(iced::Alignment::Center),
+        ]
+        .spacing(4)
+        .align_items(iced::Alignment::Center)
+        .into()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ExternalChangeMsg {
+    ToggleDetails,
+    Dismiss,
+    Revert,
+    Jump(WId),
+}
+
+/// A change to the playlist made outside the app, kept until it's dismissed so it can be undone
+#[derive(Debug, Clone)]
+pub struct ExternalChange {
+    pub previous: SongOpConstructor,
+    pub diff: TreeDiff,
+    expanded: bool,
+}
+
+impl ExternalChange {
+    pub fn new(previous: SongOpConstructor, diff: TreeDiff) -> Self {
+        Self {
+            previous,
+            diff,
+            expanded: false,
+        }
+    }
+
+    pub fn toggle_details(&mut self) {
+        self.expanded = !self.expanded;
+    }
+
+    pub fn view(&self) -> Element<ExternalChangeMsg> {
+        let banner = row![
+            text(format!(
+                "The playlist was changed outside ytm-rs: {}",
+                self.diff
+            )),
+            button(if self.expanded { "hide" } else { "details" })
+                .on_press(ExternalChangeMsg::ToggleDetails),
+            button("revert external change").on_press(ExternalChangeMsg::Revert),
+            button("dismiss").on_press(ExternalChangeMsg::Dismiss),
+        ]
+        .spacing(4)
+        .align_items(iced::Alignment::Center);
+
+        let details = self.expanded.then(|| {
+            Column::with_children(self.diff.changes.iter().map(|change| {
+                row![text(change.kind.to_string())]
+                    .push_maybe(
+                        change
+                            .row
+                            .clone()
+                            .map(|row| button("show").on_press(ExternalChangeMsg::Jump(row))),
+                    )
+                    .spacing(4)
+                    .align_items(iced::Alignment::Center)
+                    .into()
+            }))
+            .spacing(2)
+        });
+
+        column![banner].push_maybe(details).spacing(4).into()
+    }
+}
+
+/// Where songs held back by the duplicate check were being added
+#[derive(Debug, Clone)]
+pub enum AddTarget {
+    FocusedGroup,
+    /// Dropped on the zones
+    Dropped(Vec<(WId, Rectangle)>),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum DuplicatePromptMsg {
+    AddAnyway,
+    /// Adds only the songs that aren't in the playlist yet
+    Skip,
+}
+
+/// Songs being added that are in the playlist already, held back until it's known whether to
+/// add them anyway. Adding many asks once for all of them.
+#[derive(Debug, Clone)]
+pub struct DuplicatePrompt {
+    pub keys: Vec<SongKey>,
+    pub present: HashSet<SongKey>,
+    pub target: AddTarget,
+}
+
+impl DuplicatePrompt {
+    /// The keys that aren't in the playlist yet
+    pub fn new_keys(&self) -> Vec<SongKey> {
+        self.keys
+            .iter()
+            .filter(|key| !self.present.contains(*key))
+            .cloned()
+            .collect()
+    }
+
+    pub fn describe(&self) -> String {
+        match self.keys.len() {
+            1 => "The song is already in the playlist".to_string(),
+            total => format!(
+                "{} of {total} songs are already in the playlist",
+                self.present.len()
+            ),
+        }
+    }
+
+    pub fn view(&self) -> Element<DuplicatePromptMsg> {
+        row![
+            text(self.describe()),
+            button("add anyway").on_press(DuplicatePromptMsg::AddAnyway),
+            button("skip").on_press(DuplicatePromptMsg::Skip),
+        ]
+        .spacing(4)
+        .align_items(iced::Alignment::Center)
+        .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::song_operations::{
+        ActualRecursiveOps, ConstructorItem, SongOpConstructor, TreeDirected,
+    };
+
+    use super::{
+        delete_playlist, load_headers, AddTarget, DuplicatePrompt, EditHistory, Playlist,
+        PlaylistLibrary, MAX_UNDO,
+    };
+
+    fn playlist(name: &str, keys: &[&str]) -> Playlist {
+        let items = keys
+            .iter()
+            .map(|key| ConstructorItem::from(key.to_string()));
+        Playlist {
+            name: name.to_string(),
+            constructor: SongOpConstructor::from(items.collect::<Vec<_>>()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn playlists_are_saved_to_their_own_files() {
+        async_std::task::block_on(async {
+            let dir = tempfile::tempdir().unwrap();
+            let focus = playlist("focus", &["a", "b"]);
+            let gym = playlist("Gym", &["b"]);
+            focus.clone().save_to(dir.path()).await.unwrap();
+            gym.clone().save_to(dir.path()).await.unwrap();
+            std::fs::write(dir.path().join("notes.txt"), "not a playlist").unwrap();
+
+            let headers = load_headers(dir.path()).await;
+            let names: Vec<&str> = headers.iter().map(|h| h.name.as_str()).collect();
+            assert_eq![names, vec!["focus", "Gym"]];
+            assert_eq![headers[0].num_of_songs, 2];
+
+            let loaded = Playlist::load_from(dir.path(), gym.id).await.unwrap();
+            assert_eq![loaded.name, "Gym"];
+            assert_eq![loaded.constructor.all_song_keys_rec().count(), 1];
+
+            delete_playlist(dir.path(), gym.id).await.unwrap();
+            // Deleting a playlist that was never saved is fine
+            delete_playlist(dir.path(), gym.id).await.unwrap();
+            assert_eq![load_headers(dir.path()).await.len(), 1];
+            assert![Playlist::load_from(dir.path(), gym.id).await.is_err()];
+        });
+    }
+
+    #[test]
+    fn songs_are_added_to_the_focused_group() {
+        let group = SongOpConstructor::new(
+            ActualRecursiveOps::LoopNTimes,
+            vec![ConstructorItem::from("b".to_string())],
+            None,
+        );
+        let mut list = playlist("focus", &["a"]);
+        list.constructor.list.push(group.into());
+
+        // Nothing focused, so they go at the end
+        list.add_to_focused_group(vec!["c".into()]);
+        assert_eq![
+            list.constructor.all_song_keys_rec().collect::<Vec<_>>(),
+            ["a", "b", "c"]
+        ];
+
+        let b = list.constructor.visible_song_ids()[1].clone();
+        assert![list.focus_song(&b)];
+        let ids = list.add_to_focused_group(vec!["e".into(), "d".into()]);
+        let keys = list.constructor.all_song_keys_rec().collect::<Vec<_>>();
+        assert_eq![keys, ["a", "b", "e", "d", "c"]];
+        assert![matches![
+            list.constructor.item_at_path([1, 2].into()),
+            Some(ConstructorItem::Song(key, _)) if key == "d"
+        ]];
+        assert_eq![list.constructor.path_to_id(&ids[1]), Some(vec![1, 2])];
+    }
+
+    #[test]
+    fn undoing_a_move_brings_back_the_same_rows() {
+        let mut list = playlist("undo", &["a", "b", "c"]);
+        let ids = list.constructor.visible_song_ids();
+        let mut history = EditHistory::default();
+
+        // Dropping b at the end
+        let before = list.constructor.clone();
+        let item = list.constructor.pop_path([1].into()).unwrap();
+        list.constructor.push_to_path([].into(), item);
+        assert![history.record(before, &list.constructor)];
+
+        assert![history.undo(&mut list.constructor)];
+        assert_eq![list.constructor.path_to_id(&ids[1]), Some(vec![1])];
+        assert_eq![list.constructor.visible_song_ids(), ids];
+        assert![!history.undo(&mut list.constructor)];
+
+        assert![history.redo(&mut list.constructor)];
+        assert_eq![list.constructor.path_to_id(&ids[1]), Some(vec![2])];
+        assert![!history.redo(&mut list.constructor)];
+    }
+
+    #[test]
+    fn history_is_bounded_and_edits_clear_redos() {
+        let mut list = playlist("undo", &[]);
+        let mut history = EditHistory::default();
+        // Nothing changed
+        assert![!history.record(list.constructor.clone(), &list.constructor)];
+
+        for n in 0..MAX_UNDO + 5 {
+            let before = list.constructor.clone();
+            list.constructor
+                .push_to_path([].into(), ConstructorItem::from(n.to_string()));
+            history.record(before, &list.constructor);
+        }
+        let mut undone = 0;
+        while history.undo(&mut list.constructor) {
+            undone += 1;
+        }
+        assert_eq![undone, MAX_UNDO];
+        assert_eq![list.constructor.all_song_keys_rec().count(), 5];
+
+        let before = list.constructor.clone();
+        list.constructor
+            .push_to_path([].into(), ConstructorItem::from("new".to_string()));
+        history.record(before, &list.constructor);
+        assert![!history.redo(&mut list.constructor)];
+    }
+
+    #[test]
+    fn the_library_follows_renames_and_deletes() {
+        let mut focus = playlist("focus", &["a"]);
+        let gym = playlist("gym", &[]);
+        let mut library = PlaylistLibrary::default();
+        library.record(&focus);
+        library.record(&gym);
+
+        focus.name = "deep focus".to_string();
+        library.record(&focus);
+        assert_eq![library.headers.len(), 2];
+        assert_eq![library.headers[0].name, "deep focus"];
+        assert_eq![library.headers[0].to_string(), "deep focus (1 songs)"];
+
+        assert_eq![library.other_than(focus.id), Some(gym.id)];
+        library.forget(gym.id);
+        assert_eq![library.other_than(focus.id), None];
+    }
+
+    #[test]
+    fn repeated_songs_are_asked_about_together() {
+        let list = playlist("list", &["a", "b"]);
+        let keys: Vec<String> = ["a", "c", "b", "d"].iter().map(|k| k.to_string()).collect();
+        let present = list.present(&keys);
+        assert_eq![present.len(), 2];
+
+        let prompt = DuplicatePrompt {
+            keys,
+            present,
+            target: AddTarget::FocusedGroup,
+        };
+        let description = "2 of 4 songs are already in the playlist";
+        assert_eq![prompt.describe(), description];
+        assert_eq![prompt.new_keys(), ["c", "d"]];
+        assert![list.present(&["e".to_string()]).is_empty()];
+    }
 }
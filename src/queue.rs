@@ -0,0 +1,383 @@
+//! What plays next, found by stepping a copy of the tracker forward.
+//! Random picks made while stepping the copy aren't the ones the real tracker will make, so
+//! upcoming songs inside shuffles are a guess. Jumping to one still plays it.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use iced::{
+    widget::{button, column, container, text, Column},
+    Element, Length,
+};
+
+use crate::{
+    settings::SongKey,
+    song_operations::{
+        ConstructorItem, NextResult, OperationTracker, RecursiveSongOp, SongOpConstructor,
+        SongOpTracker,
+    },
+    styling::FullYtmrsScheme,
+};
+use rand::{seq::SliceRandom, thread_rng};
+
+/// How many upcoming songs are shown, so ops that never end don't hang the preview
+pub const PREVIEW_LENGTH: usize = 50;
+/// How many upcoming songs are shuffled at once, for the same reason
+pub const SHUFFLE_LENGTH: usize = 500;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueueEntry {
+    /// Where the song is in the tree
+    pub path: Vec<usize>,
+    pub key: SongKey,
+}
+
+#[derive(Debug, Clone)]
+pub enum QueueMsg {
+    /// Jumps to the upcoming entry at the index
+    Jump(usize),
+}
+
+#[derive(Debug, Default)]
+pub struct Queue {
+    current: Option<QueueEntry>,
+    upcoming: Vec<QueueEntry>,
+    /// Whether more songs follow the preview
+    continues: bool,
+    /// Changes whenever the queue is recomputed
+    revision: u64,
+}
+
+/// The paths the tracker moves through after its current song, up to `limit` of them.
+/// Also returns whether it goes on after those.
+pub fn upcoming_paths(tracker: &SongOpTracker, limit: usize) -> (Vec<Vec<usize>>, bool) {
+    let mut tracker = tracker.clone();
+    let mut paths = vec![];
+    while paths.len() < limit {
+        match tracker.move_next() {
+            NextResult::Current => paths.push(tracker.get_current().collect()),
+            NextResult::Ended => return (paths, false),
+        }
+    }
+    (paths, tracker.move_next() == NextResult::Current)
+}
+
+/// What unrated songs are shuffled as, the middle of the stars
+const UNRATED_WEIGHT: u32 = 3;
+
+/// The ratings of the songs being shuffled, higher rated songs are picked sooner
+#[derive(Debug, Clone, Default)]
+pub struct ShuffleRatings {
+    /// Finds the song at a path of the tracker
+    op: Option<RecursiveSongOp>,
+    ratings: HashMap<SongKey, u8>,
+}
+
+impl ShuffleRatings {
+    /// Only keeps the ratings of the songs in the op
+    pub fn new(op: RecursiveSongOp, rating: impl Fn(&SongKey) -> Option<u8>) -> Self {
+        let ratings = op
+            .song_keys()
+            .into_iter()
+            .filter_map(|key| Some((key.clone(), rating(key)?)))
+            .collect();
+        Self {
+            op: Some(op),
+            ratings,
+        }
+    }
+
+    /// Keeps up with a song being rated while it's shuffled
+    pub fn set(&mut self, key: &SongKey, rating: Option<u8>) {
+        match rating {
+            Some(rating) => self.ratings.insert(key.clone(), rating),
+            None => self.ratings.remove(key),
+        };
+    }
+
+    fn weight(&self, path: &[usize]) -> u32 {
+        let key = self
+            .op
+            .as_ref()
+            .and_then(|op| op.song_at(path.iter().copied()));
+        match key.and_then(|key| self.ratings.get(key)) {
+            Some(rating) => (*rating as u32).max(1),
+            None => UNRATED_WEIGHT,
+        }
+    }
+}
+
+/// The songs the tracker had left when shuffling started, played in a random order.
+/// Loops leave the same path in more than once, and it plays once for each time.
+#[derive(Debug, Clone)]
+pub struct Shuffle {
+    /// The paths the tracker would have moved through, in order
+    sequence: Vec<Vec<usize>>,
+    /// Indices into the sequence
+    played: HashSet<usize>,
+    /// Whether the tracker goes on after the sequence
+    continues: bool,
+}
+
+impl Shuffle {
+    pub fn new(tracker: &SongOpTracker) -> Self {
+        let (sequence, continues) = upcoming_paths(tracker, SHUFFLE_LENGTH);
+        Self {
+            sequence,
+            played: HashSet::new(),
+            continues,
+        }
+    }
+
+    /// A random path that hasn't played yet, None once they all have.
+    /// Songs are picked as often as their rating says.
+    pub fn pick(&self, ratings: &ShuffleRatings) -> Option<&Vec<usize>> {
+        let left: Vec<&Vec<usize>> = self
+            .sequence
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| !self.played.contains(idx))
+            .map(|(_, path)| path)
+            .collect();
+        left.choose_weighted(&mut thread_rng(), |path| ratings.weight(path))
+            .ok()
+            .copied()
+    }
+
+    pub fn mark_played(&mut self, path: &[usize]) {
+        let idx = (0..self.sequence.len())
+            .find(|idx| !self.played.contains(idx) && self.sequence[*idx] == path);
+        if let Some(idx) = idx {
+            self.played.insert(idx);
+        }
+    }
+
+    /// Whether there's more to shuffle once all of the sequence played
+    pub fn continues(&self) -> bool {
+        self.continues
+    }
+}
+
+fn entry_at(tree: &SongOpConstructor, path: Vec<usize>) -> Option<QueueEntry> {
+    match tree.played_item(&path) {
+        Some(ConstructorItem::Song(key, _)) => Some(QueueEntry {
+            path,
+            key: key.clone(),
+        }),
+        _ => None,
+    }
+}
+
+impl Queue {
+    /// Recomputes the queue from where the tracker is. None means nothing is playing.
+    pub fn refresh(&mut self, tracker: Option<&SongOpTracker>, tree: &SongOpConstructor) {
+        self.revision = self.revision.wrapping_add(1);
+        let tracker = match tracker {
+            Some(tracker) => tracker,
+            None => {
+                self.current = None;
+                self.upcoming.clear();
+                self.continues = false;
+                return;
+            }
+        };
+        let (paths, continues) = upcoming_paths(tracker, PREVIEW_LENGTH);
+        self.current = entry_at(tree, tracker.get_current().collect());
+        self.upcoming = paths
+            .into_iter()
+            .filter_map(|path| entry_at(tree, path))
+            .collect();
+        self.continues = continues;
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// The playing song and the ones after it
+    pub fn used_keys(&self) -> impl Iterator<Item = &SongKey> {
+        self.current
+            .iter()
+            .chain(&self.upcoming)
+            .map(|entry| &entry.key)
+    }
+
+    /// Moves the tracker to the upcoming entry. Returns whether it's there.
+    pub fn jump(&self, tracker: &mut SongOpTracker, idx: usize) -> bool {
+        match self.upcoming.get(idx) {
+            Some(entry) => {
+                tracker.set_current(VecDeque::from(entry.path.clone()));
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn view<'a>(
+        &'a self,
+        tree: &'a SongOpConstructor,
+        scheme: &FullYtmrsScheme,
+    ) -> Element<'a, QueueMsg> {
+        let title = |entry: &QueueEntry| {
+            let data = tree.cached_song_data(&entry.key);
+            match data.state.badge() {
+                Some(badge) => format!("{badge} {}", data.title),
+                None => data.title,
+            }
+        };
+
+        let current = match &self.current {
+            Some(entry) => {
+                let style = scheme.focus_style.apply(Default::default(), true);
+                container(text(title(entry)))
+                    .padding(4)
+                    .width(Length::Fill)
+                    .style(move |_| style)
+            }
+            None => container(text("Nothing is playing")).padding(4),
+        };
+
+        let upcoming = self.upcoming.iter().enumerate().map(|(idx, entry)| {
+            button(text(title(entry)))
+                .width(Length::Fill)
+                .on_press(QueueMsg::Jump(idx))
+                .into()
+        });
+
+        column![
+            text("queue"),
+            current,
+            Column::with_children(upcoming).spacing(2)
+        ]
+        .push_maybe(self.continues.then(|| text("...")))
+        .spacing(4)
+        .width(Length::Fill)
+        .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::song_operations::{
+        ActualRecursiveOps, ConstructorItem, OperationTracker, SongOpConstructor, SongOpTracker,
+    };
+
+    use super::{upcoming_paths, Queue, Shuffle, ShuffleRatings, PREVIEW_LENGTH};
+
+    fn song(key: &str) -> ConstructorItem {
+        ConstructorItem::from(key.to_string())
+    }
+
+    fn group(op: ActualRecursiveOps, n: u32, items: Vec<ConstructorItem>) -> ConstructorItem {
+        ConstructorItem::Operation(SongOpConstructor::new(op, items, None).with_n(n))
+    }
+
+    fn keys(queue: &Queue) -> Vec<&str> {
+        queue
+            .upcoming
+            .iter()
+            .map(|entry| entry.key.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn nested_ops_are_queued_in_play_order() {
+        let tree = SongOpConstructor::from(vec![
+            song("a"),
+            group(
+                ActualRecursiveOps::LoopNTimes,
+                2,
+                vec![song("b"), song("c")],
+            ),
+            song("d"),
+        ]);
+        let tracker = SongOpTracker::start(&tree.build(), [0].into()).unwrap();
+        let mut queue = Queue::default();
+        queue.refresh(Some(&tracker), &tree);
+        assert_eq![queue.current.as_ref().unwrap().key, "a"];
+        assert_eq![keys(&queue), vec!["b", "c", "b", "c", "d"]];
+        assert_eq![queue.upcoming[4].path, vec![2]];
+
+        queue.refresh(None, &tree);
+        assert![queue.current.is_none() && queue.upcoming.is_empty()];
+    }
+
+    #[test]
+    fn endless_ops_are_previewed() {
+        let mut tree = SongOpConstructor::from(vec![song("a"), song("b")]);
+        tree.play.operation = ActualRecursiveOps::InfiniteLoop;
+        let tracker = SongOpTracker::start(&tree.build(), [0].into()).unwrap();
+        let (paths, continues) = upcoming_paths(&tracker, PREVIEW_LENGTH);
+        assert_eq![paths.len(), PREVIEW_LENGTH];
+        assert![continues];
+        assert_eq![paths[..3], [vec![1], vec![0], vec![1]]];
+    }
+
+    #[test]
+    fn jumping_moves_the_tracker() {
+        let tree = SongOpConstructor::from(vec![
+            song("a"),
+            group(ActualRecursiveOps::Stretch, 2, vec![song("b"), song("c")]),
+            song("d"),
+        ]);
+        let mut tracker = SongOpTracker::start(&tree.build(), [0].into()).unwrap();
+        let mut queue = Queue::default();
+        queue.refresh(Some(&tracker), &tree);
+        assert_eq![keys(&queue), vec!["b", "b", "c", "c", "d"]];
+
+        assert![queue.jump(&mut tracker, 2)];
+        assert_eq![tracker.get_current().collect::<Vec<_>>(), vec![1, 1]];
+        queue.refresh(Some(&tracker), &tree);
+        assert_eq![keys(&queue), vec!["c", "d"]];
+        assert![!queue.jump(&mut tracker, 5)];
+    }
+
+    #[test]
+    fn shuffles_play_each_song_left_once() {
+        let tree = SongOpConstructor::from(vec![
+            song("a"),
+            group(ActualRecursiveOps::LoopNTimes, 2, vec![song("b")]),
+            song("c"),
+        ]);
+        let tracker = SongOpTracker::start(&tree.build(), [0].into()).unwrap();
+        let mut shuffle = Shuffle::new(&tracker);
+        assert![!shuffle.continues()];
+
+        let mut played = vec![];
+        while let Some(path) = shuffle.pick(&ShuffleRatings::default()).cloned() {
+            shuffle.mark_played(&path);
+            played.push(path);
+        }
+        played.sort();
+        assert_eq![played, vec![vec![1, 0], vec![1, 0], vec![2]]];
+    }
+
+    #[test]
+    fn higher_rated_songs_are_shuffled_in_sooner() {
+        let tree = SongOpConstructor::from(vec![song("s"), song("a"), song("b"), song("c")]);
+        let op = tree.build();
+        let tracker = SongOpTracker::start(&op, [0].into()).unwrap();
+        let ratings = ShuffleRatings::new(op, |key| match key.as_str() {
+            "a" => Some(5),
+            "b" => Some(1),
+            _ => None,
+        });
+        let shuffle = Shuffle::new(&tracker);
+
+        let mut picked = [0; 4];
+        for _ in 0..2000 {
+            picked[shuffle.pick(&ratings).unwrap()[0]] += 1;
+        }
+        // 5, 1 and 3 out of 9
+        assert![picked[1] > picked[3] && picked[3] > picked[2]];
+        assert![picked[1] > picked[2] * 3];
+
+        let mut ratings = ratings;
+        ratings.set(&"a".to_string(), None);
+        ratings.set(&"b".to_string(), Some(5));
+        let mut picked = [0; 4];
+        for _ in 0..2000 {
+            picked[shuffle.pick(&ratings).unwrap()[0]] += 1;
+        }
+        assert![picked[2] > picked[1]];
+    }
+}
@@ -2,7 +2,10 @@ use std::borrow::Borrow;
 
 use serde::{Deserialize, Serialize};
 
-use crate::{settings::SongKey, song::Song};
+use crate::{
+    settings::SongKey,
+    song::{Song, SongData, SongDuration, SongSource},
+};
 
 pub type UrlString = String;
 
@@ -19,7 +22,12 @@ pub struct YTabEntry {
     pub url: UrlString,
     pub title: String,
     pub description: Option<String>,
-    pub duration: f64,
+    /// Missing for livestreams and some uploads
+    #[serde(default)]
+    pub duration: Option<f64>,
+    /// One of "is_live", "was_live", "not_live", etc.
+    #[serde(default)]
+    pub live_status: Option<String>,
     pub view_count: Option<usize>,
     pub channel: String,
     pub channel_url: String,
@@ -39,6 +47,36 @@ pub struct YTab {
     pub entries: Vec<YTabEntry>,
 }
 
+impl YTab {
+    pub fn source(&self) -> SongSource {
+        SongSource::Tab {
+            title: self.title.clone(),
+            url: self.webpage_url.clone(),
+        }
+    }
+
+    /// The tab's entries as songs, stamped as coming from this tab
+    pub fn into_songs(self) -> Vec<Song> {
+        let source = self.source();
+        self.entries
+            .into_iter()
+            .map(|entry| Song {
+                id: entry.id,
+                title: entry.title,
+                channel: entry.channel.clone(),
+                view_count: entry.view_count,
+                webpage_url: entry.url,
+                duration: entry.duration.unwrap_or_default(),
+                is_live: entry.live_status.as_deref() == Some("is_live"),
+                thumbnail: entry.thumbnails[0].url.clone(),
+                artists: Some(vec![entry.channel.clone()]),
+                source: source.clone(),
+                ..Default::default()
+            })
+            .collect()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum YTIEKey {
     Youtube,
@@ -52,6 +90,28 @@ pub struct YTSearchEntry {
     pub title: Option<String>,
     pub ie_key: YTIEKey,
     pub url: UrlString,
+    /// Left out of some results
+    #[serde(default)]
+    pub channel: Option<String>,
+    #[serde(default)]
+    pub duration: Option<f64>,
+}
+
+impl YTSearchEntry {
+    /// What the result says of the song, shown in its row until the song is cached
+    pub fn as_data(&self) -> SongData {
+        let mystery = SongData::mystery();
+        SongData {
+            title: self.title.clone().unwrap_or(self.id.clone()),
+            channel: self.channel.clone().unwrap_or(mystery.channel.clone()),
+            artists: self.channel.clone().map(|channel| vec![channel]),
+            duration: match self.duration {
+                Some(duration) => SongDuration::from_parts(duration, false),
+                None => SongDuration::Unknown,
+            },
+            ..mystery
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -110,4 +170,108 @@ impl YTResponseType {
             )?)),
         }
     }
+
+    /// Where the songs of the response were found, `query` being what was asked for.
+    /// Songs asked for by their url are found by that url.
+    pub fn source(&self, query: &str) -> SongSource {
+        match self {
+            Self::Tab(tab) => tab.source(),
+            Self::Search(_) | Self::Song(_) => SongSource::Search {
+                query: query.to_string(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::song::{Song, SongSource};
+
+    use super::{YTMSearch, YTResponseType, YTSearchEntry, YTab};
+
+    #[test]
+    fn tab_songs_remember_the_tab() {
+        let tab: YTab = serde_json::from_value(serde_json::json!({
+            "id": "PLabc",
+            "title": "Morning mix",
+            "channel": null,
+            "view_count": null,
+            "thumbnails": null,
+            "availability": null,
+            "webpage_url": "https://www.youtube.com/playlist?list=PLabc",
+            "modified_date": null,
+            "entries": [{
+                "id": "song1",
+                "url": "https://www.youtube.com/watch?v=song1",
+                "title": "First",
+                "description": null,
+                "view_count": 1,
+                "channel": "Someone",
+                "channel_url": "...",
+                "thumbnails": [{"height": 1, "width": 1, "url": "..."}],
+            }],
+        }))
+        .unwrap();
+
+        let songs = tab.into_songs();
+        assert_eq![songs.len(), 1];
+        assert_eq![
+            songs[0].source,
+            SongSource::Tab {
+                title: "Morning mix".into(),
+                url: "https://www.youtube.com/playlist?list=PLabc".into(),
+            }
+        ];
+        assert_eq![
+            songs[0].source.reopen_query(),
+            Some("https://www.youtube.com/playlist?list=PLabc")
+        ];
+    }
+
+    #[test]
+    fn searches_and_songs_remember_the_query() {
+        let url = "https://music.youtube.com/watch?v=song1";
+        let song = YTResponseType::Song(Song::basic());
+        assert_eq![song.source(url).reopen_query(), Some(url)];
+
+        let search = YTResponseType::Search(YTMSearch {
+            title: "results".into(),
+            webpage_url: "...".into(),
+            entries: vec![],
+        });
+        assert_eq![
+            search.source("morning"),
+            SongSource::Search {
+                query: "morning".into()
+            }
+        ];
+    }
+
+    #[test]
+    fn search_results_fill_their_rows() {
+        let entry: YTSearchEntry = serde_json::from_value(serde_json::json!({
+            "id": "song1",
+            "title": "First",
+            "ie_key": "Youtube",
+            "url": "https://music.youtube.com/watch?v=song1",
+            "channel": "Someone",
+            "duration": 200.0,
+        }))
+        .unwrap();
+        let data = entry.as_data();
+        assert_eq![
+            (data.title, data.channel, data.duration.format()),
+            ("First".into(), "Someone".into(), "3:20".into())
+        ];
+
+        // Results that leave them out are still read
+        let entry: YTSearchEntry = serde_json::from_value(serde_json::json!({
+            "id": "song2",
+            "title": null,
+            "ie_key": "Youtube",
+            "url": "...",
+        }))
+        .unwrap();
+        assert_eq![entry.as_data().title, "song2"];
+    }
 }
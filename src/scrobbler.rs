@@ -0,0 +1,497 @@
+use std::{
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+
+use async_std::io::WriteExt;
+use parking_lot::Mutex;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use crate::{
+    events::{AppEvent, Listener},
+    settings::project_data_dir,
+    song::Song,
+};
+
+pub const SUBMIT_URL: &str = "https://api.listenbrainz.org/1/submit-listens";
+/// The most listens sent in a single request
+pub const MAX_BATCH: usize = 100;
+/// A listen counts after half the song, or this many seconds, whichever comes first
+pub const THRESHOLD_SECS: f64 = 4.0 * 60.0;
+/// Position jumps bigger than this between ticks are seeks, and don't count as listening
+const MAX_TICK_GAP: f64 = 3.0;
+
+pub fn token_path() -> PathBuf {
+    let mut path = project_data_dir();
+    path.push("listenbrainz_token");
+    path
+}
+
+/// Reads the token from its file. A token left in the settings is moved there.
+pub async fn load_token(path: PathBuf, from_settings: String) -> Result<String, String> {
+    match async_std::fs::read_to_string(&path).await {
+        Ok(token) => Ok(token.trim().to_string()),
+        Err(_) if !from_settings.is_empty() => {
+            save_token(path, from_settings.clone()).await?;
+            Ok(from_settings)
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+        Err(e) => Err(format!("{e:?}")),
+    }
+}
+
+/// Writes the token to a file only the user can read
+pub async fn save_token(path: PathBuf, token: String) -> Result<(), String> {
+    let write = async {
+        if let Some(dir) = path.parent() {
+            async_std::fs::create_dir_all(dir).await?;
+        }
+        let mut options = async_std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use async_std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&path).await?;
+        // The mode only applies to new files, older ones may be readable by others
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(std::fs::Permissions::from_mode(0o600))
+                .await?;
+        }
+        file.write_all(token.as_bytes()).await?;
+        file.sync_all().await
+    };
+    write.await.map_err(|e: std::io::Error| format!("{e:?}"))
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Listen {
+    pub artist: String,
+    pub title: String,
+    pub album: Option<String>,
+    pub duration: f64,
+    /// Unix timestamp of when the song started
+    pub listened_at: u64,
+}
+
+impl Listen {
+    pub fn from_song(song: &Song, started: SystemTime) -> Self {
+        Self {
+            artist: match &song.artists {
+                Some(artists) if !artists.is_empty() => artists.join(", "),
+                _ => song.channel.clone(),
+            },
+            title: song.title.clone(),
+            album: song.album.clone(),
+            duration: song.duration,
+            listened_at: started
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+        }
+    }
+
+    fn track_metadata(&self) -> Value {
+        let mut metadata = Map::new();
+        metadata.insert("artist_name".into(), self.artist.clone().into());
+        metadata.insert("track_name".into(), self.title.clone().into());
+        if let Some(album) = &self.album {
+            metadata.insert("release_name".into(), album.clone().into());
+        }
+        metadata.insert(
+            "additional_info".into(),
+            json!({
+                "duration_ms": (self.duration * 1000.0) as u64,
+                "submission_client": "ytm-rs",
+            }),
+        );
+        Value::Object(metadata)
+    }
+}
+
+pub fn listens_payload(listens: &[Listen]) -> Value {
+    json!({
+        "listen_type": if listens.len() == 1 { "single" } else { "import" },
+        "payload": listens
+            .iter()
+            .map(|listen| json!({
+                "listened_at": listen.listened_at,
+                "track_metadata": listen.track_metadata(),
+            }))
+            .collect::<Vec<_>>(),
+    })
+}
+
+pub fn now_playing_payload(listen: &Listen) -> Value {
+    json!({
+        "listen_type": "playing_now",
+        "payload": [{ "track_metadata": listen.track_metadata() }],
+    })
+}
+
+pub async fn submit(token: String, body: Value) -> Result<(), String> {
+    submit_to(SUBMIT_URL, token, body).await
+}
+
+async fn submit_to(url: &str, token: String, body: Value) -> Result<(), String> {
+    match Client::new()
+        .post(url)
+        .header("Authorization", format!("Token {token}"))
+        .json(&body)
+        .send()
+        .await
+    {
+        Ok(response) if response.status().is_success() => Ok(()),
+        Ok(response) => Err(format!("ListenBrainz responded with {}", response.status())),
+        Err(e) => Err(format!("{e:?}")),
+    }
+}
+
+/// Counts how long a song was actually listened to, ignoring seeks and pauses.
+#[derive(Debug, Clone)]
+pub struct ListenProgress {
+    duration: f64,
+    listened: f64,
+    last_position: Option<f64>,
+    counted: bool,
+}
+
+impl ListenProgress {
+    pub fn new(duration: f64) -> Self {
+        Self {
+            duration,
+            listened: 0.0,
+            last_position: None,
+            counted: false,
+        }
+    }
+
+    pub fn threshold(&self) -> f64 {
+        (self.duration / 2.0).min(THRESHOLD_SECS)
+    }
+
+    /// Feeds the playback position. Returns true the first time the listen counts.
+    pub fn tick(&mut self, position: f64) -> bool {
+        if let Some(last) = self.last_position {
+            let delta = position - last;
+            if delta > 0.0 && delta <= MAX_TICK_GAP {
+                self.listened += delta;
+            }
+        }
+        self.last_position = Some(position);
+
+        if !self.counted && self.duration > 0.0 && self.listened >= self.threshold() {
+            self.counted = true;
+            return true;
+        }
+        false
+    }
+}
+
+pub fn backoff(failures: u32) -> Duration {
+    Duration::from_secs(30 * 2_u64.pow(failures.min(7))).min(Duration::from_secs(60 * 60))
+}
+
+/// Listens that haven't been submitted yet, kept on disk so they survive restarts.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Outbox {
+    pub pending: Vec<Listen>,
+    #[serde(skip)]
+    failures: u32,
+    #[serde(skip)]
+    retry_at: Option<Instant>,
+    #[serde(skip)]
+    in_flight: bool,
+}
+
+impl Outbox {
+    pub fn path() -> PathBuf {
+        let mut path = project_data_dir();
+        path.push("scrobbles.json");
+        path
+    }
+
+    pub fn load() -> Self {
+        std::fs::read(Self::path())
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        let result = serde_json::to_vec(self)
+            .map_err(std::io::Error::from)
+            .and_then(|json| std::fs::write(Self::path(), json));
+        if let Err(e) = result {
+            println!["Failed to save scrobbles: {e:?}"];
+        }
+    }
+
+    /// Takes the next batch to submit, unless one is already being sent
+    /// or the last attempt failed too recently.
+    pub fn take_batch(&mut self, now: Instant) -> Option<Vec<Listen>> {
+        if self.in_flight || self.pending.is_empty() || self.retry_at.is_some_and(|t| t > now) {
+            return None;
+        }
+        self.in_flight = true;
+        Some(self.pending.iter().take(MAX_BATCH).cloned().collect())
+    }
+
+    /// Records the result of submitting a batch of `count` listens
+    pub fn submitted(&mut self, count: usize, success: bool, now: Instant) {
+        self.in_flight = false;
+        match success {
+            true => {
+                self.pending.drain(..count.min(self.pending.len()));
+                self.failures = 0;
+                self.retry_at = None;
+            }
+            false => {
+                self.failures += 1;
+                self.retry_at = Some(now + backoff(self.failures));
+            }
+        }
+    }
+}
+
+/// Tracks the playing song and queues it once it's been listened to.
+#[derive(Debug, Default)]
+pub struct Scrobbler {
+    current: Option<(Listen, ListenProgress)>,
+    now_playing: Option<Listen>,
+    pub outbox: Outbox,
+}
+
+impl Scrobbler {
+    pub fn start(&mut self, listen: Listen) {
+        let progress = ListenProgress::new(listen.duration);
+        self.now_playing = Some(listen.clone());
+        self.current = Some((listen, progress));
+    }
+
+    /// Stops counting the song, it's listened to up to where it ended
+    pub fn end(&mut self) {
+        self.current = None;
+    }
+
+    /// The song that started since the last call, to send as "now playing"
+    pub fn take_now_playing(&mut self) -> Option<Listen> {
+        self.now_playing.take()
+    }
+
+    /// Feeds the playback position. Returns true when a listen was queued.
+    pub fn tick(&mut self, position: f64) -> bool {
+        match &mut self.current {
+            Some((listen, progress)) => match progress.tick(position) {
+                true => {
+                    self.outbox.pending.push(listen.clone());
+                    true
+                }
+                false => false,
+            },
+            None => false,
+        }
+    }
+}
+
+/// Follows the songs through the app's events, the ticks feed it their positions
+pub struct ScrobbleListener(pub Arc<Mutex<Scrobbler>>);
+impl Listener for ScrobbleListener {
+    fn handle(&mut self, event: &AppEvent) {
+        match event {
+            AppEvent::SongStarted {
+                meta: Some(song), ..
+            } => self
+                .0
+                .lock()
+                .start(Listen::from_song(song, SystemTime::now())),
+            // Without metadata there's nothing to submit
+            AppEvent::SongStarted { meta: None, .. } | AppEvent::SongEnded { .. } => {
+                self.0.lock().end()
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::{Ipv4Addr, TcpListener},
+        sync::mpsc,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use super::{
+        listens_payload, load_token, now_playing_payload, save_token, submit_to, Listen,
+        ListenProgress, Outbox,
+    };
+
+    /// Answers one request with the status, sending back what was asked
+    fn serve(status: &'static str) -> (String, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let url = format!("http://{}/1/submit-listens", listener.local_addr().unwrap());
+        let (requests, received) = mpsc::channel();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_millis(50)))
+                .unwrap();
+            let mut request = vec![];
+            let mut buffer = [0; 1024];
+            while let Ok(read @ 1..) = stream.read(&mut buffer) {
+                request.extend_from_slice(&buffer[..read]);
+            }
+            requests
+                .send(String::from_utf8_lossy(&request).into_owned())
+                .unwrap();
+            let reply =
+                format!("HTTP/1.1 {status}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+            stream.write_all(reply.as_bytes()).unwrap();
+        });
+        (url, received)
+    }
+
+    fn run<T>(future: impl std::future::Future<Output = T>) -> T {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    fn listen(title: &str) -> Listen {
+        Listen {
+            artist: "Someone".into(),
+            title: title.into(),
+            album: None,
+            duration: 200.0,
+            listened_at: 1_700_000_000,
+        }
+    }
+
+    #[test]
+    fn seeks_and_pauses_dont_count() {
+        let mut progress = ListenProgress::new(200.0);
+        assert_eq![progress.threshold(), 100.0];
+
+        // Seeking straight past the threshold doesn't count
+        assert![!progress.tick(0.0)];
+        assert![!progress.tick(150.0)];
+        // Neither does staying paused
+        for _ in 0..10 {
+            assert![!progress.tick(150.0)];
+        }
+
+        let counted = (1..=100)
+            .filter(|s| progress.tick(150.0 + *s as f64))
+            .count();
+        assert_eq![counted, 1];
+        // Doesn't count twice
+        assert![!progress.tick(251.0)];
+    }
+
+    #[test]
+    fn long_songs_count_after_four_minutes() {
+        let mut progress = ListenProgress::new(60.0 * 60.0);
+        assert_eq![progress.threshold(), 240.0];
+        let first = (0..=300).position(|s| progress.tick(s as f64));
+        assert_eq![first, Some(240)];
+    }
+
+    #[test]
+    fn outbox_retries_with_backoff() {
+        let now = Instant::now();
+        let mut outbox = Outbox::default();
+        assert_eq![outbox.take_batch(now), None];
+
+        outbox.pending = vec![listen("a"), listen("b")];
+        let batch = outbox.take_batch(now).unwrap();
+        assert_eq![batch.len(), 2];
+        // Only one batch in flight at a time
+        assert_eq![outbox.take_batch(now), None];
+
+        outbox.submitted(batch.len(), false, now);
+        assert_eq![outbox.take_batch(now + Duration::from_secs(10)), None];
+        let batch = outbox.take_batch(now + Duration::from_secs(61)).unwrap();
+
+        // A listen queued while the batch was sent stays queued
+        outbox.pending.push(listen("c"));
+        outbox.submitted(batch.len(), true, now);
+        assert_eq![outbox.pending, vec![listen("c")]];
+        assert![outbox.take_batch(now).is_some()];
+    }
+
+    #[test]
+    fn listens_are_submitted_with_the_token() {
+        let (url, received) = serve("200 OK");
+        let body = listens_payload(&[listen("a")]);
+        assert_eq![run(submit_to(&url, "secret".into(), body)), Ok(())];
+        let request = received.recv().unwrap();
+        assert![request.starts_with("POST /1/submit-listens"), "{request}"];
+        assert![request.contains("authorization: Token secret"), "{request}"];
+        assert![request.contains(r#""track_name":"a""#), "{request}"];
+
+        let (url, _received) = serve("401 Unauthorized");
+        let result = run(submit_to(&url, "wrong".into(), listens_payload(&[])));
+        assert![result.unwrap_err().contains("401"), "rejected tokens fail"];
+    }
+
+    #[test]
+    fn tokens_are_moved_out_of_the_settings_and_kept_private() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("token");
+        let load = |from_settings: &str| {
+            async_std::task::block_on(load_token(path.clone(), from_settings.into()))
+        };
+        assert_eq![load(""), Ok(String::new())];
+        assert_eq![load("from settings"), Ok("from settings".into())];
+        // The file wins once it's there
+        assert_eq![load("stale"), Ok("from settings".into())];
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = |path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+            assert_eq![mode(&path), 0o600];
+
+            let shared = dir.path().join("shared");
+            std::fs::write(&shared, "old").unwrap();
+            std::fs::set_permissions(&shared, std::fs::Permissions::from_mode(0o644)).unwrap();
+            async_std::task::block_on(save_token(shared.clone(), "new".into())).unwrap();
+            assert_eq![mode(&shared), 0o600];
+            assert_eq![std::fs::read_to_string(&shared).unwrap(), "new"];
+        }
+    }
+
+    #[test]
+    fn payloads_follow_the_listenbrainz_format() {
+        let mut with_album = listen("b");
+        with_album.album = Some("Album".into());
+
+        let payload = listens_payload(&[listen("a"), with_album]);
+        assert_eq![payload["listen_type"], "import"];
+        assert_eq![payload["payload"][0]["listened_at"], 1_700_000_000];
+        let metadata = &payload["payload"][0]["track_metadata"];
+        assert_eq![metadata["artist_name"], "Someone"];
+        assert_eq![metadata["track_name"], "a"];
+        assert![metadata.get("release_name").is_none()];
+        assert_eq![metadata["additional_info"]["duration_ms"], 200_000];
+        assert_eq![
+            payload["payload"][1]["track_metadata"]["release_name"],
+            "Album"
+        ];
+
+        let payload = now_playing_payload(&listen("a"));
+        assert_eq![payload["listen_type"], "playing_now"];
+        assert![payload["payload"][0].get("listened_at").is_none()];
+    }
+}
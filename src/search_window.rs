@@ -1,20 +1,32 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::HashMap,
+    fmt::{Debug, Display},
+};
 
 use iced::{
     alignment::Horizontal,
-    keyboard::Modifiers,
-    widget::{column, scrollable, text, text_input, Column, Container},
-    Command as Cm, Element, Length,
+    keyboard::{self, key::Named, Modifiers},
+    widget::{
+        button, column, pick_list, row, scrollable, scrollable::Viewport, text, text_input, Column,
+        Container, Space,
+    },
+    Command as Cm, Element, Length, Subscription,
 };
 use iced_drop::{droppable, zones_on_point};
+use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    caching::{BufferedCache, NDJsonCache, RwArc, RwMap},
+    caching::{song_data_read, BufferedCache, NDJsonCache, RwArc, RwMap},
+    context_menu::{with_menu, SongAction},
     response_types::{YTIEKey, YTSearchEntry},
     song::{Song, SongData},
+    song_list::{windowed, Span, ROW_HEIGHT},
     styling::FullYtmrsScheme,
-    user_input::SelectionMode,
+    subscriptions,
+    suggestions::{Suggestions, SUGGEST_INTERVAL},
+    thumbnails::OnScreen,
+    user_input::{FocusCursor, SelectionMode},
 };
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -52,29 +64,178 @@ impl SearchEntry {
     }
 }
 
+/// What a tab's songs are sorted by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TabSort {
+    /// The order of the tab itself
+    #[default]
+    Original,
+    Title,
+    Duration,
+    Channel,
+    /// The most viewed first
+    Views,
+    /// The highest rated first
+    Rating,
+}
+
+impl TabSort {
+    pub const ALL: [TabSort; 6] = [
+        Self::Original,
+        Self::Title,
+        Self::Duration,
+        Self::Channel,
+        Self::Views,
+        Self::Rating,
+    ];
+}
+
+impl Display for TabSort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Original => "playlist order",
+            Self::Title => "title",
+            Self::Duration => "duration",
+            Self::Channel => "channel",
+            Self::Views => "views",
+            Self::Rating => "rating",
+        })
+    }
+}
+
+/// The rows a tab shows, by the index of their song in the tab
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TabOrder {
+    rows: Vec<usize>,
+    /// How many of the rows are of cached songs, the ones that aren't cached come after them
+    known: usize,
+}
+
+/// What a cached song of a tab is sorted by
+struct SortFacts {
+    idx: usize,
+    title: String,
+    channel: String,
+    duration: Option<f64>,
+    views: Option<usize>,
+    rating: Option<u8>,
+}
+
+/// How a tab's songs are filtered and sorted
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TabView {
+    pub filter: String,
+    pub sort: TabSort,
+}
+
+impl TabView {
+    /// Songs that aren't cached can't be filtered or sorted, so they're kept after the rest in
+    /// the tab's order. None when every song is shown as it is in the tab.
+    pub fn arrange(&self, keys: &[String], songs: &RwMap<String, Song>) -> Option<TabOrder> {
+        if *self == Self::default() {
+            return None;
+        }
+        let mut known = vec![];
+        let mut unknown = vec![];
+        for (idx, key) in keys.iter().enumerate() {
+            match songs.get(key) {
+                Some(song) => {
+                    let song = song.read();
+                    if song.matches(&self.filter) {
+                        known.push(SortFacts {
+                            idx,
+                            title: song.title.to_lowercase(),
+                            channel: song.channel.to_lowercase(),
+                            duration: song.song_duration().known(),
+                            views: song.view_count,
+                            rating: song.rating,
+                        });
+                    }
+                }
+                None => unknown.push(idx),
+            }
+        }
+        // Sorting is stable, so songs that compare equal stay in the tab's order
+        match self.sort {
+            TabSort::Original => {}
+            TabSort::Title => known.sort_by(|a, b| a.title.cmp(&b.title)),
+            TabSort::Channel => {
+                known.sort_by(|a, b| a.channel.cmp(&b.channel).then(a.title.cmp(&b.title)))
+            }
+            // Unknown durations and views last
+            TabSort::Duration => known.sort_by(|a, b| match (a.duration, b.duration) {
+                (Some(a), Some(b)) => a.total_cmp(&b),
+                (a, b) => b.is_some().cmp(&a.is_some()),
+            }),
+            TabSort::Views => known.sort_by(|a, b| b.views.cmp(&a.views)),
+            // Unrated songs last
+            TabSort::Rating => known.sort_by(|a, b| b.rating.cmp(&a.rating)),
+        }
+        let mut rows: Vec<usize> = known.into_iter().map(|facts| facts.idx).collect();
+        let known = rows.len();
+        rows.extend(unknown);
+        Some(TabOrder { rows, known })
+    }
+
+    fn view(&self) -> Element<SWMessage> {
+        row![
+            text_input("Filter the songs...", &self.filter).on_input(SWMessage::TabFilterEdited),
+            pick_list(TabSort::ALL, Some(self.sort), SWMessage::TabSortPicked),
+        ]
+        .spacing(4)
+        .into()
+    }
+}
+
+/// The index in the tab of the song shown at the row
+fn tab_index(order: &Option<TabOrder>, row: usize) -> Option<usize> {
+    match order {
+        Some(order) => order.rows.get(row).copied(),
+        None => Some(row),
+    }
+}
+
+/// The row the song at the index in the tab is shown at, if it's shown
+fn tab_position(order: &Option<TabOrder>, idx: usize) -> Option<usize> {
+    match order {
+        Some(order) => order.rows.iter().position(|i| *i == idx),
+        None => Some(idx),
+    }
+}
+
+/// The song of the tab's row, in the order they're shown
+fn tab_row<'a>(keys: &'a [String], order: &Option<TabOrder>, row: usize) -> Option<&'a String> {
+    keys.get(tab_index(order, row)?)
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum SearchType {
     Song(String),
-    Tab(Vec<String>, #[serde(skip)] SelectionMode),
+    /// The selection is of the rows as they're shown
+    Tab(
+        Vec<String>,
+        #[serde(skip)] SelectionMode,
+        #[serde(skip)] Option<TabOrder>,
+    ),
     Search(Vec<SearchEntry>),
 }
 
 impl SearchType {
     pub fn new_tab(songs: Vec<String>) -> Self {
-        Self::Tab(songs, SelectionMode::None)
+        Self::Tab(songs, SelectionMode::None, None)
     }
 
     pub fn selected_keys(&self) -> Option<Vec<&String>> {
         match self {
             Self::Song(s) => Some(vec![s]),
-            Self::Tab(s, mode) => match mode {
+            Self::Tab(s, mode, order) => match mode {
                 SelectionMode::None => None,
-                SelectionMode::Single(idx) => s.get(*idx).map(|k| vec![k]),
+                SelectionMode::Single(idx) => tab_row(s, order, *idx).map(|k| vec![k]),
                 SelectionMode::Multiple(v) => {
-                    Some(v.iter().filter_map(|idx| s.get(*idx)).collect())
+                    Some(v.iter().filter_map(|idx| tab_row(s, order, *idx)).collect())
                 }
                 SelectionMode::Range { first: _, r } => {
-                    Some(r.clone().filter_map(|idx| s.get(idx)).collect())
+                    Some(r.clone().filter_map(|idx| tab_row(s, order, idx)).collect())
                 }
             },
             Self::Search(_) => None,
@@ -84,7 +245,7 @@ impl SearchType {
     pub fn used_keys(&self) -> Vec<&String> {
         match self {
             SearchType::Song(ref song) => vec![song],
-            SearchType::Tab(ref v, _) => v.iter().collect(),
+            SearchType::Tab(ref v, ..) => v.iter().collect(),
             SearchType::Search(ref v) => v
                 .iter()
                 .filter_map(|e| match e {
@@ -103,27 +264,108 @@ impl SearchType {
         }
     }
 
+    /// The link of the tab at the row, to open it with
+    pub fn tab_url(&self, idx: usize) -> Option<&String> {
+        match self {
+            SearchType::Search(v) => match v.get(idx)? {
+                SearchEntry::Tab { url, .. } => Some(url),
+                SearchEntry::Song { .. } => None,
+            },
+            SearchType::Song(_) | SearchType::Tab(..) => None,
+        }
+    }
+
+    /// The key of the song at the row, None for tabs
+    pub fn song_key(&self, idx: usize) -> Option<&String> {
+        match self {
+            SearchType::Song(key) => (idx == 0).then_some(key),
+            SearchType::Tab(v, _, order) => tab_row(v, order, idx),
+            SearchType::Search(v) => match v.get(idx)? {
+                SearchEntry::Song { id, .. } => Some(id),
+                SearchEntry::Tab { .. } => None,
+            },
+        }
+    }
+
+    /// The number of rows shown for this search type
+    pub fn row_count(&self) -> usize {
+        match self {
+            SearchType::Song(_) => 1,
+            SearchType::Tab(v, _, order) => order.as_ref().map_or(v.len(), |o| o.rows.len()),
+            SearchType::Search(v) => v.len(),
+        }
+    }
+
+    /// Gets the display data of the row at `idx`
+    fn row_data(&self, idx: usize, data: &HashMap<String, SongData>) -> Option<SongData> {
+        let (key, title) = match self {
+            SearchType::Song(key) => (key, None),
+            SearchType::Tab(v, _, order) => (tab_row(v, order, idx)?, None),
+            SearchType::Search(v) => match v.get(idx)? {
+                SearchEntry::Song { id, title, url: _ } => (id, title.clone()),
+                SearchEntry::Tab { id, title, url: _ } => {
+                    return Some(SongData::mystery_with_title(
+                        title.clone().unwrap_or(id.clone()),
+                    ))
+                }
+            },
+        };
+        Some(match data.get(key) {
+            Some(data) => data.clone(),
+            None => SongData::mystery_with_title(title.unwrap_or(key.clone())).into_provisional(),
+        })
+    }
+
     pub fn view(
         &self,
         scheme: &FullYtmrsScheme,
-        cached_map: RwMap<String, Song>,
+        data: &HashMap<String, SongData>,
+        focused: Option<usize>,
+        menu: Option<usize>,
+        on_screen: &OnScreen,
     ) -> Element<SWMessage> {
         match &self {
-            SearchType::Song(_) => {
-                todo!()
+            SearchType::Song(key) => {
+                let style = scheme
+                    .focus_style
+                    .apply(scheme.song_appearance.update(false), focused == Some(0));
+                let data = match data.get(key) {
+                    Some(data) => data.clone(),
+                    None => SongData::mystery_with_title(key.clone()).into_provisional(),
+                };
+                let row = droppable(
+                    Container::new(
+                        Element::new(data.row(true, false)).map(|_| SWMessage::SelectSong(0)),
+                    )
+                    .style(move |_| style),
+                )
+                .on_drop(move |pt, rec| SWMessage::Drop(key.clone(), pt, rec))
+                .on_click(SWMessage::SimpleSelectSong(0))
+                .on_single_click(SWMessage::SelectSong(0));
+                Container::new(song_menu(row.into(), 0, menu))
+                    .max_width(400)
+                    .into()
             }
-            SearchType::Tab(v, mode) => {
-                let songs = v.iter().enumerate().map(|(idx, key)| {
+            SearchType::Tab(v, mode, order) => {
+                let heights = vec![ROW_HEIGHT; self.row_count()];
+                let span = Span::of(on_screen, ROW_HEIGHT * heights.len() as f32);
+                let songs = windowed(&heights, span, |idx, _| {
+                    let key = match tab_row(v, order, idx) {
+                        Some(key) => key,
+                        None => return Space::with_height(ROW_HEIGHT).into(),
+                    };
                     let selected = mode.contains(idx);
-                    let style = scheme.song_appearance.update(selected);
-                    droppable(
+                    let style = scheme.focus_style.apply(
+                        scheme.song_appearance.update(selected),
+                        focused == Some(idx),
+                    );
+                    let row = droppable(
                         Container::new(
-                            Element::new(match cached_map.get(key) {
-                                Some(songc) => {
-                                    let song = songc.read();
-                                    song.as_data().row(true, false)
-                                }
-                                None => SongData::mystery_with_title(key.clone()).row(true, false),
+                            Element::new(match data.get(key) {
+                                Some(data) => data.clone().row(true, false),
+                                None => SongData::mystery_with_title(key.clone())
+                                    .into_provisional()
+                                    .row(true, false),
                             })
                             .map(move |_| SWMessage::SelectSong(idx)),
                         )
@@ -131,48 +373,76 @@ impl SearchType {
                     )
                     .on_drop(move |pt, rec| SWMessage::Drop(key.clone(), pt, rec))
                     .on_click(SWMessage::SimpleSelectSong(idx))
-                    .on_single_click(SWMessage::SelectSong(idx))
-                    .into()
+                    .on_single_click(SWMessage::SelectSong(idx));
+                    song_menu(row.into(), idx, menu)
                 });
+                // Above the list rather than in it, so every row in it is as tall
+                let note = match order {
+                    Some(order) if order.rows.is_empty() => Some(text("No songs match the filter")),
+                    Some(order) if order.known < order.rows.len() => {
+                        let unloaded = order.rows.len() - order.known;
+                        let note =
+                            format!("{unloaded} songs aren't loaded, so they're left at the end");
+                        Some(text(note).size(12))
+                    }
+                    _ => None,
+                };
 
-                Element::new(
-                    scrollable(
-                        Container::new(Column::with_children(songs).width(Length::Fill))
-                            .align_x(Horizontal::Left)
-                            .max_width(400)
-                            .padding(0),
+                column![]
+                    .push_maybe(note)
+                    .push(
+                        scrollable(
+                            Container::new(songs.width(Length::Fill))
+                                .align_x(Horizontal::Left)
+                                .max_width(400)
+                                .padding(0),
+                        )
+                        .width(Length::Fill)
+                        .on_scroll(SWMessage::Scrolled)
+                        .style(scheme.scrollable_style.clone().update()),
                     )
-                    .width(Length::Fill)
-                    .style(scheme.scrollable_style.clone().update()),
-                )
+                    .into()
             }
             SearchType::Search(v) => {
-                let items = v.iter().enumerate().map(|(idx, entry)| match entry {
-                    SearchEntry::Song { id, title, url: _ } => droppable(
-                        Element::new(match cached_map.get(id) {
-                            Some(song) => {
-                                let song = song.read();
-                                song.as_data().row(false, false)
-                            }
-                            None => {
-                                SongData::mystery_with_title(title.clone().unwrap_or(id.clone()))
-                                    .row(false, false)
-                            }
-                        })
-                        .map(move |_| SWMessage::SelectSong(idx)),
-                    )
-                    .on_drop(move |pt, rec| SWMessage::Drop(id.clone(), pt, rec))
-                    .on_click(SWMessage::SimpleSelectSong(idx))
-                    .on_single_click(SWMessage::SelectSong(idx))
-                    .into(),
-                    SearchEntry::Tab { id, title, url: _ } => {
-                        Element::new(text(title.clone().unwrap_or(id.clone())))
-                    }
+                let items = v.iter().enumerate().map(|(idx, entry)| {
+                    let style = scheme
+                        .focus_style
+                        .apply(scheme.song_appearance.update(false), focused == Some(idx));
+                    let item: Element<SWMessage> = match entry {
+                        SearchEntry::Song { id, title, url: _ } => song_menu(
+                            droppable(
+                                Element::new(match data.get(id) {
+                                    Some(data) => data.clone().row(false, false),
+                                    None => SongData::mystery_with_title(
+                                        title.clone().unwrap_or(id.clone()),
+                                    )
+                                    .into_provisional()
+                                    .row(false, false),
+                                })
+                                .map(move |_| SWMessage::SelectSong(idx)),
+                            )
+                            .on_drop(move |pt, rec| SWMessage::Drop(id.clone(), pt, rec))
+                            .on_click(SWMessage::SimpleSelectSong(idx))
+                            .on_single_click(SWMessage::SelectSong(idx))
+                            .into(),
+                            idx,
+                            menu,
+                            data.get(id)
+                                .is_some_and(|data| data.state.removable_download()),
+                        ),
+                        SearchEntry::Tab { id, title, url: _ } => Element::new(
+                            button(text(format!("{} ›", title.clone().unwrap_or(id.clone()))))
+                                .width(Length::Fill)
+                                .on_press(SWMessage::OpenTab(idx)),
+                        ),
+                    };
+                    Container::new(item).style(move |_| style).into()
                 });
 
                 Element::new(
                     scrollable(Column::with_children(items))
                         .width(Length::Fill)
+                        .on_scroll(SWMessage::Scrolled)
                         .style(scheme.scrollable_style.clone().update()),
                 )
             }
@@ -180,6 +450,23 @@ impl SearchType {
     }
 }
 
+/// The row of the song at `idx`, with its menu open if it's the one at `menu`
+fn song_menu(
+    row: Element<SWMessage>,
+    idx: usize,
+    menu: Option<usize>,
+    downloaded: bool,
+) -> Element<SWMessage> {
+    with_menu(
+        row,
+        menu == Some(idx),
+        SWMessage::OpenMenu(idx),
+        &SongAction::SEARCH,
+        downloaded,
+        move |action| SWMessage::Menu(idx, action),
+    )
+}
+
 #[derive(Debug, Clone)]
 pub enum SWMessage {
     Drop(String, iced::Point, iced::Rectangle),
@@ -187,8 +474,65 @@ pub enum SWMessage {
 
     SearchQueryChanged(String),
     SearchQuerySubmitted,
+    Retry,
     SimpleSelectSong(usize),
     SelectSong(usize),
+    /// Opens the tab of a search result, like pasting its link would
+    OpenTab(usize),
+    /// Goes back to the results the last request replaced
+    Back,
+    /// Opens the menu of the song at the row
+    OpenMenu(usize),
+    Menu(usize, SongAction),
+    Scrolled(Viewport),
+    TabFilterEdited(String),
+    TabSortPicked(TabSort),
+    /// Catches the suggestions up to the query
+    Suggest,
+    /// Plays the suggested song at the row, or adds it to the playlist
+    Suggestion(usize, SongAction),
+}
+
+/// Queries starting with this search the songs saved locally instead of Youtube
+pub const LOCAL_SEARCH_PREFIX: &str = "local:";
+
+/// How many replaced results can be gone back to
+pub const HISTORY_LENGTH: usize = 10;
+
+/// Results that a newer request replaced
+#[derive(Debug, Clone)]
+struct Page {
+    query: String,
+    last_query: String,
+    search_type: SearchType,
+    provisional: HashMap<String, SongData>,
+}
+
+/// Display data of the shown songs, reused between views until the window's revision or the song
+/// cache's generation changes.
+#[derive(Default)]
+struct RowDataCache(Mutex<Option<((u64, u64), HashMap<String, SongData>)>>);
+impl Clone for RowDataCache {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+impl Debug for RowDataCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("RowDataCache")
+    }
+}
+
+/// What the pane shows in place of the results
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum PaneState {
+    #[default]
+    Ready,
+    /// Waiting for the request with this id
+    Loading(u64),
+    /// The request for this query succeeded but had no results
+    Empty(String),
+    Error(String),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -197,13 +541,57 @@ pub struct SearchWindow {
     pub search_type: SearchType,
     #[serde(skip)]
     pub cache: Option<RwArc<NDJsonCache<Song>>>,
+    #[serde(skip)]
+    pub focus: FocusCursor,
+    #[serde(skip)]
+    pub state: PaneState,
+    /// The query of the last request, used to retry it
+    #[serde(skip)]
+    pub last_query: String,
+    #[serde(skip)]
+    next_request: u64,
+    // bumped whenever the results or the songs in them may have changed
+    #[serde(skip)]
+    revision: u64,
+    #[serde(skip)]
+    data_cache: RowDataCache,
+    /// What the results themselves say about songs that aren't cached yet
+    #[serde(skip)]
+    provisional: HashMap<String, SongData>,
+    /// The results requests replaced, the latest last
+    #[serde(skip)]
+    history: Vec<Page>,
+    /// Only saved songs can be searched while the backend is offline
+    #[serde(skip)]
+    pub offline: bool,
+    /// The results on screen, to download their thumbnails first
+    #[serde(skip)]
+    pub on_screen: OnScreen,
+    /// Saved songs that match the query as it's typed
+    #[serde(skip)]
+    pub suggestions: Suggestions,
+    /// How the songs of tabs are filtered and sorted
+    #[serde(skip)]
+    pub tab_view: TabView,
 }
 impl Default for SearchWindow {
     fn default() -> Self {
         SearchWindow {
             query: String::new(),
-            search_type: SearchType::Tab(vec![], SelectionMode::None),
+            search_type: SearchType::new_tab(vec![]),
             cache: None,
+            focus: FocusCursor::default(),
+            state: PaneState::default(),
+            last_query: String::new(),
+            next_request: 0,
+            revision: 0,
+            data_cache: RowDataCache::default(),
+            provisional: HashMap::new(),
+            history: vec![],
+            offline: false,
+            on_screen: OnScreen::default(),
+            suggestions: Suggestions::default(),
+            tab_view: TabView::default(),
         }
     }
 }
@@ -216,28 +604,277 @@ impl SearchWindow {
         self.search_type.selected_keys()
     }
 
-    pub fn view(&self, scheme: &FullYtmrsScheme) -> Element<SWMessage> {
-        let keys: HashSet<String> = self.used_keys().into_iter().cloned().collect();
+    /// Marks a request for the query as dispatched. Returns the id to resolve it with.
+    /// Shown results are kept so they can be gone back to.
+    pub fn begin_request(&mut self, query: String) -> u64 {
+        if self.state == PaneState::Ready && self.search_type.row_count() > 0 {
+            if self.history.len() == HISTORY_LENGTH {
+                self.history.remove(0);
+            }
+            self.history.push(Page {
+                query: self.query.clone(),
+                last_query: self.last_query.clone(),
+                search_type: self.search_type.clone(),
+                provisional: self.provisional.clone(),
+            });
+        }
+        self.suggestions.close();
+        self.next_request += 1;
+        self.last_query = query;
+        self.state = PaneState::Loading(self.next_request);
+        self.next_request
+    }
 
-        let cached_map: HashMap<_, _> = match &self.cache {
-            Some(lock) => {
-                let c = lock.read();
-                c.fetch_existing(&keys)
+    /// Whether the request is the one the pane is waiting for.
+    /// Responses to older requests should be ignored.
+    pub fn is_current(&self, request: u64) -> bool {
+        self.state == PaneState::Loading(request)
+    }
+
+    /// Resolves the request after its response was parsed into the search type
+    pub fn resolve(&mut self, request: u64, result: Result<(), String>) {
+        if !self.is_current(request) {
+            return;
+        }
+        self.state = match result {
+            Ok(()) if self.search_type.row_count() == 0 => {
+                PaneState::Empty(self.last_query.clone())
             }
+            Ok(()) => PaneState::Ready,
+            Err(e) => PaneState::Error(e),
+        };
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Shows the results the last request replaced, without asking for them again.
+    /// A request still on its way is ignored once it arrives.
+    pub fn back(&mut self) {
+        if let Some(page) = self.history.pop() {
+            self.query = page.query;
+            self.last_query = page.last_query;
+            self.search_type = page.search_type;
+            self.provisional = page.provisional;
+            self.state = PaneState::Ready;
+            self.focus = FocusCursor::default();
+            self.arrange();
+            self.touch();
+        }
+    }
+
+    /// Shows new results. Rows of songs that aren't cached use their provisional data
+    /// until the songs are. A new tab isn't filtered, but is sorted like the last one.
+    pub fn set_results(&mut self, search_type: SearchType, provisional: HashMap<String, SongData>) {
+        self.search_type = search_type;
+        self.provisional = provisional
+            .into_iter()
+            .map(|(key, data)| (key, data.into_provisional()))
+            .collect();
+        self.tab_view.filter.clear();
+        self.arrange();
+        self.touch();
+    }
+
+    /// Filters and sorts the tab's songs by what the cache has of them, again after songs
+    /// were cached. The selection stays on the songs it was on while they're shown.
+    pub fn arrange(&mut self) {
+        let (keys, mode, order) = match &mut self.search_type {
+            SearchType::Tab(keys, mode, order) => (keys, mode, order),
+            SearchType::Song(_) | SearchType::Search(_) => return,
+        };
+        let songs = match &self.cache {
+            Some(lock) => lock.read().fetch_existing(keys.iter()),
+            None => HashMap::new(),
+        };
+        let arranged = self.tab_view.arrange(keys, &songs);
+        if arranged == *order {
+            return;
+        }
+        let mut rows: Vec<usize> = mode
+            .indices()
+            .into_iter()
+            .filter_map(|row| tab_index(order, row))
+            .filter_map(|idx| tab_position(&arranged, idx))
+            .collect();
+        rows.sort_unstable();
+        *mode = match rows.len() {
+            0 => SelectionMode::None,
+            1 => SelectionMode::Single(rows[0]),
+            _ => SelectionMode::Multiple(rows),
+        };
+        *order = arranged;
+    }
+
+    /// Works out the suggestions for the query typed since they were last
+    pub fn suggest(&mut self) {
+        if let Some(cache) = &self.cache {
+            self.suggestions.refresh(cache);
+        }
+    }
+
+    /// Only ticks while the suggestions are behind the query
+    pub fn subscription(&self) -> Subscription<SWMessage> {
+        match self.suggestions.is_pending() {
+            true => {
+                subscriptions::every("suggestions", SUGGEST_INTERVAL).map(|_| SWMessage::Suggest)
+            }
+            false => Subscription::none(),
+        }
+    }
+
+    /// Marks the view data as stale, so the next view reads the song cache again
+    pub fn touch(&mut self) {
+        self.revision = self.revision.wrapping_add(1);
+    }
+
+    /// Gets the display data of the shown songs, from the cache if they're in it
+    fn collect_song_data(&self) -> HashMap<String, SongData> {
+        let keys = self.used_keys();
+        let map = match &self.cache {
+            Some(lock) => lock.read().fetch_existing(keys.iter().copied()),
             None => HashMap::new(),
         };
+        let mut data: HashMap<String, SongData> = map
+            .into_iter()
+            .map(|(key, song)| {
+                let data = song.read().as_data();
+                (key, data)
+            })
+            .collect();
+        for key in keys {
+            if !data.contains_key(key) {
+                if let Some(provisional) = self.provisional.get(key) {
+                    data.insert(key.clone(), provisional.clone());
+                }
+            }
+        }
+        data
+    }
+
+    /// Runs `f` with the display data, collecting it again only if the results or the songs
+    /// changed
+    fn with_song_data<R>(&self, f: impl FnOnce(&HashMap<String, SongData>) -> R) -> R {
+        let generation = match &self.cache {
+            Some(lock) => lock.read().generation(),
+            None => 0,
+        };
+        let at = (self.revision, generation);
+        let mut cache = self.data_cache.0.lock();
+        if !matches!(*cache, Some((read_at, _)) if read_at == at) {
+            song_data_read();
+            *cache = Some((at, self.collect_song_data()));
+        }
+        f(cache.as_ref().map(|(_, data)| data).unwrap())
+    }
 
-        let search_query = text_input("Enter query...", &self.query)
+    /// `menu` is the row whose menu is open
+    pub fn view(&self, scheme: &FullYtmrsScheme, menu: Option<usize>) -> Element<SWMessage> {
+        let placeholder = match self.offline {
+            true => format!("Offline, search your saved songs with \"{LOCAL_SEARCH_PREFIX}\""),
+            false => "Enter query...".to_string(),
+        };
+        let search_query = text_input(&placeholder, &self.query)
             .on_input(SWMessage::SearchQueryChanged)
             .on_submit(SWMessage::SearchQuerySubmitted);
+        let search_query = row![]
+            .push_maybe(
+                (!self.history.is_empty()).then(|| button("back").on_press(SWMessage::Back)),
+            )
+            .push(search_query)
+            .spacing(4);
+
+        let focused = self
+            .focus
+            .get()
+            .filter(|idx| *idx < self.search_type.row_count());
+
+        let contents: Element<SWMessage> = match &self.state {
+            PaneState::Ready => {
+                let results = self.with_song_data(|data| {
+                    self.search_type
+                        .view(scheme, data, focused, menu, &self.on_screen)
+                });
+                match &self.search_type {
+                    SearchType::Tab(keys, ..) if !keys.is_empty() => {
+                        column![self.tab_view.view(), results].spacing(4).into()
+                    }
+                    _ => results,
+                }
+            }
+            PaneState::Loading(_) => text("Searching...")
+                .horizontal_alignment(Horizontal::Center)
+                .width(Length::Fill)
+                .into(),
+            PaneState::Empty(query) => column![
+                text(format!("No results for \"{query}\"")),
+                text(format!(
+                    "Try a different query, paste a link to a song or playlist, \
+                     or search your saved songs with \"{LOCAL_SEARCH_PREFIX}\""
+                ))
+                .size(12),
+            ]
+            .align_items(iced::Alignment::Center)
+            .width(Length::Fill)
+            .into(),
+            PaneState::Error(e) => column![text(e), button("retry").on_press(SWMessage::Retry)]
+                .align_items(iced::Alignment::Center)
+                .width(Length::Fill)
+                .into(),
+        };
+
+        let suggestions = self
+            .suggestions
+            .view(scheme)
+            .map(|e| e.map(|(idx, action)| SWMessage::Suggestion(idx, action)));
+        column![search_query]
+            .push_maybe(suggestions)
+            .push(contents)
+            .into()
+    }
+
+    /// Describes the row that has keyboard focus
+    pub fn focused_description(&self) -> Option<String> {
+        let idx = self.focus.get()?;
+        self.with_song_data(|data| self.search_type.row_data(idx, data))
+            .map(|data| data.describe())
+    }
 
-        column![search_query, self.search_type.view(scheme, cached_map)].into()
+    /// Moves the keyboard focus. Returns the message to run for keys with an action.
+    pub fn navigate(&mut self, key: &keyboard::Key) -> Option<SWMessage> {
+        let len = self.search_type.row_count();
+        match key {
+            keyboard::Key::Named(Named::ArrowDown) => self.focus.move_down(len),
+            keyboard::Key::Named(Named::ArrowUp) => self.focus.move_up(len),
+            keyboard::Key::Named(Named::Enter) => {
+                self.focus.clamp(len);
+                return self
+                    .focus
+                    .get()
+                    .map(|idx| match self.search_type.tab_url(idx) {
+                        Some(_) => SWMessage::OpenTab(idx),
+                        None => SWMessage::SelectSong(idx),
+                    });
+            }
+            keyboard::Key::Named(Named::Space) => {
+                self.focus.clamp(len);
+                if let (Some(idx), SearchType::Tab(_, mode, _)) =
+                    (self.focus.get(), &mut self.search_type)
+                {
+                    *mode = mode.clone().update_selection(idx, &Modifiers::CTRL);
+                }
+            }
+            _ => {}
+        }
+        None
     }
 
     pub fn update(&mut self, msg: SWMessage, mods: &Modifiers) -> Cm<SWMessage> {
+        self.touch();
         match msg {
             SWMessage::SimpleSelectSong(idx) => {
-                if let SearchType::Tab(_, ref mut mode) = self.search_type {
+                if let SearchType::Tab(_, ref mut mode, _) = self.search_type {
                     if let SelectionMode::None | SelectionMode::Single(_) = mode {
                         *mode = SelectionMode::Single(idx);
                     }
@@ -245,7 +882,7 @@ impl SearchWindow {
                 Cm::none()
             }
             SWMessage::SelectSong(idx) => {
-                if let SearchType::Tab(_, ref mut mode) = self.search_type {
+                if let SearchType::Tab(_, ref mut mode, _) = self.search_type {
                     *mode = mode.clone().update_selection(idx, mods);
                 }
                 Cm::none()
@@ -260,10 +897,297 @@ impl SearchWindow {
             ),
             SWMessage::HandleZones(_, _) => unreachable!(),
             SWMessage::SearchQueryChanged(s) => {
+                self.suggestions.typed(&s);
                 self.query = s;
                 Cm::none()
             }
-            SWMessage::SearchQuerySubmitted => Cm::none(),
+            SWMessage::Back => {
+                self.back();
+                Cm::none()
+            }
+            SWMessage::TabFilterEdited(filter) => {
+                self.tab_view.filter = filter;
+                self.arrange();
+                Cm::none()
+            }
+            SWMessage::TabSortPicked(sort) => {
+                self.tab_view.sort = sort;
+                self.arrange();
+                Cm::none()
+            }
+            SWMessage::SearchQuerySubmitted
+            | SWMessage::Retry
+            | SWMessage::OpenTab(_)
+            | SWMessage::OpenMenu(_)
+            | SWMessage::Menu(..)
+            | SWMessage::Scrolled(_)
+            | SWMessage::Suggest
+            | SWMessage::Suggestion(..) => Cm::none(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc};
+
+    use parking_lot::RwLock;
+
+    use crate::{
+        caching::{readers::LineBasedReader, BufferedCache, NDJsonCache, ToRwMapExt},
+        fixtures,
+        song::{Song, SongData},
+        user_input::SelectionMode,
+    };
+
+    use super::{PaneState, SearchEntry, SearchType, SearchWindow, TabSort, HISTORY_LENGTH};
+
+    fn title(window: &SearchWindow, key: &str) -> Option<String> {
+        window.with_song_data(|data| data.get(key).map(|d| d.title.clone()))
+    }
+
+    #[test]
+    fn requests_resolve_to_pane_states() {
+        let mut window = SearchWindow::default();
+
+        let request = window.begin_request("found".to_string());
+        assert_eq![window.state, PaneState::Loading(request)];
+        window.search_type = SearchType::new_tab(vec!["a".to_string()]);
+        window.resolve(request, Ok(()));
+        assert_eq![window.state, PaneState::Ready];
+
+        let request = window.begin_request("nothing".to_string());
+        window.search_type = SearchType::new_tab(vec![]);
+        window.resolve(request, Ok(()));
+        assert_eq![window.state, PaneState::Empty("nothing".to_string())];
+
+        let request = window.begin_request("broken".to_string());
+        window.resolve(request, Err("failed".to_string()));
+        assert_eq![window.state, PaneState::Error("failed".to_string())];
+        assert_eq![window.last_query, "broken"];
+    }
+
+    #[test]
+    fn stale_responses_are_ignored() {
+        let mut window = SearchWindow::default();
+        let old = window.begin_request("old".to_string());
+        let new = window.begin_request("new".to_string());
+
+        assert![!window.is_current(old)];
+        window.resolve(old, Err("failed".to_string()));
+        assert_eq![window.state, PaneState::Loading(new)];
+
+        window.resolve(new, Ok(()));
+        assert_eq![window.state, PaneState::Empty("new".to_string())];
+    }
+
+    #[test]
+    fn opened_tabs_go_back_to_the_search() {
+        let mut window = SearchWindow {
+            query: "lofi playlist".into(),
+            ..Default::default()
+        };
+        let request = window.begin_request(window.query.clone());
+        window.search_type = SearchType::Search(vec![SearchEntry::Tab {
+            id: "PL1".into(),
+            title: Some("lofi".into()),
+            url: "https://www.youtube.com/playlist?list=PL1".into(),
+        }]);
+        window.resolve(request, Ok(()));
+        // Nothing was shown before the search, so there's nothing to go back to
+        assert![window.history.is_empty()];
+
+        let url = window.search_type.tab_url(0).unwrap().clone();
+        let request = window.begin_request(url.clone());
+        window.query = url;
+        window.set_results(
+            SearchType::new_tab(vec!["a".into(), "b".into()]),
+            HashMap::new(),
+        );
+        window.resolve(request, Ok(()));
+        assert_eq![window.used_keys(), vec!["a", "b"]];
+
+        window.back();
+        assert_eq![window.query, "lofi playlist"];
+        let tab = window.search_type.tab_url(0).map(String::as_str);
+        assert_eq![tab, Some("https://www.youtube.com/playlist?list=PL1")];
+        assert![window.history.is_empty()];
+    }
+
+    #[test]
+    fn history_is_bounded_and_skips_failed_requests() {
+        let mut window = SearchWindow::default();
+        for i in 0..HISTORY_LENGTH + 5 {
+            let request = window.begin_request(i.to_string());
+            window.search_type = SearchType::new_tab(vec![i.to_string()]);
+            window.resolve(request, Ok(()));
+        }
+        assert_eq![window.history.len(), HISTORY_LENGTH];
+
+        // An error isn't worth going back to
+        let request = window.begin_request("broken".into());
+        window.resolve(request, Err("failed".into()));
+        window.begin_request("retry".into());
+        assert_eq![window.history.len(), HISTORY_LENGTH];
+
+        // Going back ignores the request on its way
+        window.back();
+        assert_eq![window.state, PaneState::Ready];
+        assert_eq![window.used_keys(), vec![&(HISTORY_LENGTH + 4).to_string()]];
+    }
+
+    #[test]
+    fn view_data_is_reused_until_touched() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = Arc::new(RwLock::new(NDJsonCache::<Song>::new(LineBasedReader::new(
+            dir.path().join("songs.ndjson"),
+        ))));
+        let song = |title: &str| Song {
+            id: "a".into(),
+            title: title.into(),
+            ..Song::basic()
+        };
+        cache
+            .write()
+            .items_mut()
+            .extend([("a".to_string(), song("first"))].to_rwmap());
+
+        let mut window = SearchWindow {
+            cache: Some(Arc::clone(&cache)),
+            search_type: SearchType::new_tab(vec!["a".into(), "b".into()]),
+            ..Default::default()
+        };
+        assert_eq![title(&window, "a").as_deref(), Some("first")];
+        // Uncached songs aren't collected, the rows fall back to their key
+        assert_eq![title(&window, "b"), None];
+
+        // A change to the cache alone isn't seen until the window is touched
+        *cache.read().items()["a"].write() = song("second");
+        assert_eq![title(&window, "a").as_deref(), Some("first")];
+        window.touch();
+        assert_eq![title(&window, "a").as_deref(), Some("second")];
+
+        // Neither is a change to the results
+        window.search_type = SearchType::new_tab(vec!["b".into()]);
+        assert_eq![title(&window, "a").as_deref(), Some("second")];
+        window.touch();
+        assert_eq![title(&window, "a"), None];
+    }
+
+    #[test]
+    fn provisional_rows_upgrade_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("songs.ndjson");
+        let cache = Arc::new(RwLock::new(NDJsonCache::<Song>::new(LineBasedReader::new(
+            path.clone(),
+        ))));
+        let songs = fixtures::songs(2, 2);
+        let keys: Vec<String> = songs.iter().map(|s| s.id.clone()).collect();
+        let (a, b) = (&keys[0], &keys[1]);
+        let provisional: HashMap<String, SongData> = songs
+            .iter()
+            .map(|song| {
+                (
+                    song.id.clone(),
+                    SongData::mystery_with_title(song.title.clone()),
+                )
+            })
+            .collect();
+
+        let mut window = SearchWindow {
+            cache: Some(Arc::clone(&cache)),
+            ..Default::default()
+        };
+        window.set_results(
+            SearchType::Tab(keys.clone(), SelectionMode::Single(1), None),
+            provisional,
+        );
+        let row = |window: &SearchWindow, key: &str| {
+            window.with_song_data(|data| data.get(key).map(|d| (d.channel.clone(), d.provisional)))
+        };
+        assert_eq![title(&window, a), Some(songs[0].title.clone())];
+        assert_eq![row(&window, a), Some(("???".to_string(), true))];
+        assert_eq![row(&window, b), Some(("???".to_string(), true))];
+
+        // The full song arrives
+        cache
+            .write()
+            .items_mut()
+            .extend([(a.clone(), songs[0].clone())].to_rwmap());
+        window.touch();
+        assert_eq![row(&window, a), Some((songs[0].channel.clone(), false))];
+        assert_eq![row(&window, b), Some(("???".to_string(), true))];
+
+        // The rows and the selection are where they were
+        assert_eq![window.used_keys(), vec![a, b]];
+        assert_eq![window.selected_keys(), Some(vec![b])];
+
+        // The placeholders only ever lived in the window
+        assert_eq![cache.read().items().len(), 1];
+        assert![!path.exists()];
+    }
+
+    #[test]
+    fn tabs_are_filtered_and_sorted_with_their_selection() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = Arc::new(RwLock::new(NDJsonCache::<Song>::new(LineBasedReader::new(
+            dir.path().join("songs.ndjson"),
+        ))));
+        let songs = [
+            ("a", "Beta", 10, Some(4)),
+            ("b", "Alpha", 30, None),
+            ("c", "Gamma", 20, Some(5)),
+        ]
+        .map(|(key, title, views, rating)| {
+            let mut song = Song::basic();
+            song.id = key.into();
+            song.title = title.into();
+            song.view_count = Some(views);
+            song.rating = rating;
+            (key.to_string(), song)
+        });
+        cache.write().items_mut().extend(songs.to_rwmap());
+        let rows = |window: &SearchWindow| {
+            let search_type = &window.search_type;
+            let keys = (0..search_type.row_count()).filter_map(|row| search_type.song_key(row));
+            keys.cloned().collect::<Vec<String>>()
+        };
+        let selected = |window: &SearchWindow| {
+            let keys = window.selected_keys().unwrap_or_default().into_iter();
+            keys.cloned().collect::<Vec<String>>()
+        };
+        let mut window = SearchWindow {
+            cache: Some(Arc::clone(&cache)),
+            ..Default::default()
+        };
+        // "d" isn't cached
+        let keys = ["d", "a", "b", "c"].map(String::from).to_vec();
+        window.set_results(SearchType::new_tab(keys), HashMap::new());
+        if let SearchType::Tab(_, mode, _) = &mut window.search_type {
+            *mode = SelectionMode::Range { first: 0, r: 0..=1 };
+        }
+        assert_eq![selected(&window), ["d", "a"]];
+
+        window.tab_view.sort = TabSort::Title;
+        window.arrange();
+        assert_eq![rows(&window), ["b", "a", "c", "d"]];
+        // The selection is of the same songs, not the same rows
+        assert_eq![selected(&window), ["a", "d"]];
+
+        window.tab_view.filter = "gamma".into();
+        window.arrange();
+        assert_eq![rows(&window), ["c", "d"]];
+        // Songs that were filtered out are no longer selected
+        assert_eq![selected(&window), ["d"]];
+
+        window.tab_view.filter.clear();
+        window.tab_view.sort = TabSort::Views;
+        window.arrange();
+        assert_eq![rows(&window), ["b", "c", "a", "d"]];
+
+        window.tab_view.sort = TabSort::Rating;
+        window.arrange();
+        assert_eq![rows(&window), ["c", "a", "b", "d"]];
+        assert_eq![window.used_keys(), vec!["d", "a", "b", "c"]];
+    }
+}
@@ -0,0 +1,726 @@
+//! The user's preferences, opened from the settings button. Edits apply as they're typed and
+//! are saved with the rest of the settings. Text that doesn't parse is pointed out next to its
+//! field, and the value it would replace is kept until it does.
+
+use std::time::Duration;
+
+use iced::{
+    widget::{button, checkbox, column, pick_list, row, text, text_input, Column, Row},
+    Alignment, Element,
+};
+use uuid::Uuid;
+
+use crate::{
+    backend_settings::{BackendForm, BackendFormMsg},
+    playlist::PlaylistHeader,
+    scheduler::{Schedule, ScheduleEntry},
+    settings::{DownloadFormat, Ticker, YTMRUserSettings},
+    ytmrs::Tickers,
+};
+
+/// Ticks any faster than this would keep the app busy
+pub const MIN_TICK_INTERVAL: Duration = Duration::from_millis(50);
+/// Any longer and the next song would play over the last one for a while
+pub const MAX_STOP_FADE: Duration = Duration::from_secs(5);
+
+/// A cache folder that can be emptied from the panel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheFolder {
+    Sounds,
+    Thumbnails,
+}
+impl CacheFolder {
+    pub fn label(&self) -> &'static str {
+        match self {
+            CacheFolder::Sounds => "audio",
+            CacheFolder::Thumbnails => "thumbnail",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum SettingsPanelMsg {
+    FormatPicked(DownloadFormat),
+    CacheSizeEdited(String),
+    StreamOverEdited(String),
+    StopFadeEdited(String),
+    HistoryLimitEdited(String),
+    MetadataRequestsEdited(String),
+    UnknownLengthEdited(String),
+    AutosaveEdited(String),
+    IntervalEdited(Ticker, String),
+    ToggleNormalize,
+    ToggleSongNotifications,
+    /// Asks to confirm emptying the folder
+    AskClear(CacheFolder),
+    ConfirmClear(CacheFolder),
+    CancelClear,
+    Backend(BackendFormMsg),
+    Schedule(ScheduleMsg),
+    #[cfg(feature = "scrobble")]
+    ToggleScrobbling,
+    #[cfg(feature = "scrobble")]
+    TokenEdited(String),
+    /// Keeps the typed token, which the caller writes to its file
+    #[cfg(feature = "scrobble")]
+    SaveToken,
+    Close,
+}
+impl SettingsPanelMsg {
+    pub fn is_text_edit(&self) -> bool {
+        match self {
+            Self::CacheSizeEdited(_)
+            | Self::StreamOverEdited(_)
+            | Self::StopFadeEdited(_)
+            | Self::HistoryLimitEdited(_)
+            | Self::MetadataRequestsEdited(_)
+            | Self::UnknownLengthEdited(_)
+            | Self::AutosaveEdited(_)
+            | Self::IntervalEdited(..) => true,
+            Self::Backend(msg) => msg.is_text_edit(),
+            Self::Schedule(msg) => msg.is_text_edit(),
+            #[cfg(feature = "scrobble")]
+            Self::TokenEdited(_) => true,
+            _ => false,
+        }
+    }
+}
+
+/// An edit to the entry at the index of the schedule
+#[derive(Debug, Clone)]
+pub enum ScheduleMsg {
+    /// Adds an entry that plays the playlist
+    Add(Uuid),
+    Remove(usize),
+    ToggleEnabled(usize),
+    TimeEdited(usize, String),
+    /// Flips the day, 0 is Monday
+    DayToggled(usize, u32),
+    PlaylistPicked(usize, Uuid),
+    VolumeEdited(usize, String),
+    RampEdited(usize, String),
+    ToggleInterrupt(usize),
+}
+impl ScheduleMsg {
+    pub fn is_text_edit(&self) -> bool {
+        matches!(
+            self,
+            Self::TimeEdited(..) | Self::VolumeEdited(..) | Self::RampEdited(..)
+        )
+    }
+}
+
+const DAY_LETTERS: [&str; 7] = ["M", "T", "W", "T", "F", "S", "S"];
+
+/// Text being edited, and what's wrong with it
+#[derive(Debug)]
+struct Field {
+    text: String,
+    error: Option<String>,
+}
+impl Field {
+    fn new(text: String) -> Self {
+        Self { text, error: None }
+    }
+
+    /// Keeps the text, returning what it parses to
+    fn edit<T>(&mut self, text: String, parse: fn(&str) -> Result<T, String>) -> Option<T> {
+        let parsed = parse(&text);
+        self.text = text;
+        match parsed {
+            Ok(value) => {
+                self.error = None;
+                Some(value)
+            }
+            Err(e) => {
+                self.error = Some(e);
+                None
+            }
+        }
+    }
+
+    /// The text box alone, for fields in a row of their own
+    fn input<'a>(
+        &'a self,
+        width: u16,
+        on_input: impl Fn(String) -> SettingsPanelMsg + 'a,
+    ) -> Element<'a, SettingsPanelMsg> {
+        text_input("", &self.text)
+            .on_input(on_input)
+            .width(width)
+            .into()
+    }
+
+    fn view<'a>(
+        &'a self,
+        label: &'static str,
+        on_input: impl Fn(String) -> SettingsPanelMsg + 'a,
+    ) -> Element<'a, SettingsPanelMsg> {
+        row![
+            text(label).width(160),
+            text_input("", &self.text).on_input(on_input).width(100),
+        ]
+        .push_maybe(self.error.as_deref().map(text))
+        .spacing(8)
+        .align_items(Alignment::Center)
+        .into()
+    }
+}
+
+fn parse_megabytes(text: &str) -> Result<u64, String> {
+    match text.trim().parse::<u64>() {
+        Ok(mb) if mb > 0 => Ok(mb),
+        _ => Err(format!("{text:?} is not a size in MB")),
+    }
+}
+
+fn parse_count(text: &str) -> Result<usize, String> {
+    match text.trim().parse::<usize>() {
+        Ok(count) if count > 0 => Ok(count),
+        _ => Err(format!("{text:?} is not a number of songs")),
+    }
+}
+
+fn parse_length(text: &str) -> Result<u64, String> {
+    match text.trim().parse::<u64>() {
+        Ok(secs) if secs > 0 => Ok(secs),
+        _ => Err(format!("{text:?} is not a number of seconds")),
+    }
+}
+
+/// 0 turns it off
+fn parse_autosave(text: &str) -> Result<u64, String> {
+    text.trim()
+        .parse::<u64>()
+        .map_err(|_| format!("{text:?} is not a number of seconds"))
+}
+
+/// 0 starts at the full volume right away
+fn parse_ramp(text: &str) -> Result<u64, String> {
+    text.trim()
+        .parse::<u64>()
+        .map_err(|_| format!("{text:?} is not a number of seconds to fade in over"))
+}
+
+fn parse_seconds(text: &str) -> Option<Duration> {
+    text.trim()
+        .parse::<f64>()
+        .ok()
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+}
+
+fn parse_fade(text: &str) -> Result<Duration, String> {
+    match parse_seconds(text) {
+        None => Err(format!("{text:?} is not a number of seconds")),
+        Some(fade) if fade > MAX_STOP_FADE => {
+            Err(format!("at most {}s", MAX_STOP_FADE.as_secs_f64()))
+        }
+        Some(fade) => Ok(fade),
+    }
+}
+
+fn parse_time(text: &str) -> Result<(u32, u32), String> {
+    let parsed = text
+        .trim()
+        .split_once(':')
+        .and_then(|(hour, minute)| Some((hour.parse().ok()?, minute.parse().ok()?)));
+    match parsed {
+        Some((hour, minute)) if hour < 24 && minute < 60 => Ok((hour, minute)),
+        _ => Err(format!("{text:?} is not a time like 7:30")),
+    }
+}
+
+fn parse_percent(text: &str) -> Result<f32, String> {
+    match text.trim().trim_end_matches('%').parse::<f32>() {
+        Ok(percent) if (0.0..=100.0).contains(&percent) => Ok(percent / 100.0),
+        _ => Err(format!("{text:?} is not a volume from 0 to 100")),
+    }
+}
+
+fn parse_interval(text: &str) -> Result<Duration, String> {
+    match parse_seconds(text) {
+        None => Err(format!("{text:?} is not a number of seconds")),
+        Some(interval) if interval < MIN_TICK_INTERVAL => {
+            Err(format!("at least {}s", MIN_TICK_INTERVAL.as_secs_f64()))
+        }
+        Some(interval) => Ok(interval),
+    }
+}
+
+#[derive(Debug)]
+pub struct SettingsPanel {
+    cache_size: Field,
+    stream_over: Field,
+    stop_fade: Field,
+    history_limit: Field,
+    metadata_requests: Field,
+    unknown_length: Field,
+    autosave: Field,
+    intervals: Vec<(Ticker, Field)>,
+    /// The folder waiting on a confirmation to be emptied
+    clearing: Option<CacheFolder>,
+    /// The text of each schedule entry, in the schedule's order
+    schedule: Vec<EntryFields>,
+    /// The ListenBrainz token being typed, kept until it's saved
+    #[cfg(feature = "scrobble")]
+    token: String,
+}
+
+/// The text boxes of a schedule entry
+#[derive(Debug)]
+struct EntryFields {
+    time: Field,
+    volume: Field,
+    ramp: Field,
+}
+impl EntryFields {
+    fn new(entry: &ScheduleEntry) -> Self {
+        Self {
+            time: Field::new(format!("{}:{:02}", entry.hour, entry.minute)),
+            volume: Field::new(format!("{}", (entry.volume * 100.0).round())),
+            ramp: Field::new(entry.ramp_secs.to_string()),
+        }
+    }
+
+    fn errors(&self) -> impl Iterator<Item = &str> {
+        [&self.time, &self.volume, &self.ramp]
+            .into_iter()
+            .filter_map(|field| field.error.as_deref())
+    }
+}
+
+impl SettingsPanel {
+    pub fn new(user: &YTMRUserSettings, schedule: &Schedule, tickers: &Tickers) -> Self {
+        Self {
+            cache_size: Field::new(user.max_audio_cache_mb.to_string()),
+            stream_over: Field::new(user.stream_over_mb.to_string()),
+            stop_fade: Field::new(user.stop_fade().as_secs_f64().to_string()),
+            history_limit: Field::new(user.history_limit.to_string()),
+            metadata_requests: Field::new(user.metadata_requests.to_string()),
+            unknown_length: Field::new(user.unknown_song_secs.to_string()),
+            autosave: Field::new(user.autosave_secs.to_string()),
+            intervals: Ticker::ALL
+                .into_iter()
+                .map(|ticker| {
+                    // The ticker keeps its old interval until the new one settles
+                    let interval = match user.tick_intervals_ms.get(&ticker) {
+                        Some(ms) => Duration::from_millis(*ms),
+                        None => tickers.interval(ticker),
+                    };
+                    (ticker, Field::new(interval.as_secs_f64().to_string()))
+                })
+                .collect(),
+            clearing: None,
+            schedule: schedule.entries.iter().map(EntryFields::new).collect(),
+            #[cfg(feature = "scrobble")]
+            token: user.scrobble.token.clone(),
+        }
+    }
+
+    /// Applies the edit to the schedule, which is saved with the settings
+    pub fn update_schedule(&mut self, msg: ScheduleMsg, schedule: &mut Schedule, volume: f32) {
+        if let ScheduleMsg::Add(playlist) = msg {
+            let entry = ScheduleEntry::new(playlist, volume);
+            self.schedule.push(EntryFields::new(&entry));
+            schedule.entries.push(entry);
+            return;
+        }
+        if let ScheduleMsg::Remove(idx) = msg {
+            if idx < schedule.entries.len() {
+                schedule.entries.remove(idx);
+                self.schedule.remove(idx);
+            }
+            return;
+        }
+
+        let idx = match &msg {
+            ScheduleMsg::ToggleEnabled(idx)
+            | ScheduleMsg::TimeEdited(idx, _)
+            | ScheduleMsg::DayToggled(idx, _)
+            | ScheduleMsg::PlaylistPicked(idx, _)
+            | ScheduleMsg::VolumeEdited(idx, _)
+            | ScheduleMsg::RampEdited(idx, _)
+            | ScheduleMsg::ToggleInterrupt(idx) => *idx,
+            ScheduleMsg::Add(_) | ScheduleMsg::Remove(_) => return,
+        };
+        let (entry, fields) = match (schedule.entries.get_mut(idx), self.schedule.get_mut(idx)) {
+            (Some(entry), Some(fields)) => (entry, fields),
+            _ => return,
+        };
+        match msg {
+            ScheduleMsg::ToggleEnabled(_) => entry.enabled = !entry.enabled,
+            ScheduleMsg::TimeEdited(_, time) => {
+                if let Some((hour, minute)) = fields.time.edit(time, parse_time) {
+                    (entry.hour, entry.minute) = (hour, minute);
+                }
+            }
+            ScheduleMsg::DayToggled(_, day) => entry.days ^= 1 << day,
+            ScheduleMsg::PlaylistPicked(_, playlist) => entry.playlist = playlist,
+            ScheduleMsg::VolumeEdited(_, volume) => {
+                if let Some(volume) = fields.volume.edit(volume, parse_percent) {
+                    entry.volume = volume;
+                }
+            }
+            ScheduleMsg::RampEdited(_, secs) => {
+                if let Some(secs) = fields.ramp.edit(secs, parse_ramp) {
+                    entry.ramp_secs = secs;
+                }
+            }
+            ScheduleMsg::ToggleInterrupt(_) => entry.interrupt = !entry.interrupt,
+            ScheduleMsg::Add(_) | ScheduleMsg::Remove(_) => {}
+        }
+    }
+
+    fn schedule_view<'a>(
+        &'a self,
+        schedule: &Schedule,
+        playlists: &[PlaylistHeader],
+        open: Uuid,
+    ) -> Element<'a, SettingsPanelMsg> {
+        let msg = |msg| SettingsPanelMsg::Schedule(msg);
+        let entries = schedule.entries.iter().zip(&self.schedule).enumerate().map(
+            |(idx, (entry, fields))| {
+                let days = (0..7).map(|day| {
+                    checkbox(DAY_LETTERS[day as usize], entry.days & (1 << day) != 0)
+                        .on_toggle(move |_| msg(ScheduleMsg::DayToggled(idx, day)))
+                        .into()
+                });
+                let picked = playlists
+                    .iter()
+                    .find(|header| header.id == entry.playlist)
+                    .cloned();
+                let controls = row![
+                    checkbox("", entry.enabled)
+                        .on_toggle(move |_| msg(ScheduleMsg::ToggleEnabled(idx))),
+                    fields
+                        .time
+                        .input(60, move |time| msg(ScheduleMsg::TimeEdited(idx, time))),
+                    Row::with_children(days).spacing(2),
+                    pick_list(playlists.to_vec(), picked, move |header| {
+                        msg(ScheduleMsg::PlaylistPicked(idx, header.id))
+                    })
+                    .placeholder("missing playlist"),
+                    text("volume %"),
+                    fields
+                        .volume
+                        .input(50, move |volume| msg(ScheduleMsg::VolumeEdited(
+                            idx, volume
+                        ))),
+                    text("fade in (s)"),
+                    fields
+                        .ramp
+                        .input(50, move |secs| msg(ScheduleMsg::RampEdited(idx, secs))),
+                    checkbox("interrupt", entry.interrupt)
+                        .on_toggle(move |_| msg(ScheduleMsg::ToggleInterrupt(idx))),
+                    button("remove").on_press(msg(ScheduleMsg::Remove(idx))),
+                ]
+                .spacing(8)
+                .align_items(Alignment::Center);
+                let errors = fields.errors().map(|error| text(error).into());
+                Column::with_children(std::iter::once(controls.into()).chain(errors))
+                    .spacing(2)
+                    .into()
+            },
+        );
+        column![
+            row![
+                text("schedule"),
+                button("add").on_press(msg(ScheduleMsg::Add(open))),
+            ]
+            .spacing(8)
+            .align_items(Alignment::Center),
+            Column::with_children(entries).spacing(4),
+        ]
+        .spacing(4)
+        .into()
+    }
+
+    /// Applies the edit to the settings, returning the interval to give the ticker if one
+    /// changed. Normalizing and the backend are left to the caller.
+    pub fn update(
+        &mut self,
+        msg: SettingsPanelMsg,
+        user: &mut YTMRUserSettings,
+    ) -> Option<(Ticker, Duration)> {
+        match msg {
+            SettingsPanelMsg::FormatPicked(format) => user.download_format = format,
+            SettingsPanelMsg::CacheSizeEdited(size) => {
+                if let Some(mb) = self.cache_size.edit(size, parse_megabytes) {
+                    user.max_audio_cache_mb = mb;
+                }
+            }
+            SettingsPanelMsg::StreamOverEdited(size) => {
+                if let Some(mb) = self.stream_over.edit(size, parse_megabytes) {
+                    user.stream_over_mb = mb;
+                }
+            }
+            SettingsPanelMsg::StopFadeEdited(secs) => {
+                if let Some(fade) = self.stop_fade.edit(secs, parse_fade) {
+                    user.stop_fade_ms = fade.as_millis() as u64;
+                }
+            }
+            SettingsPanelMsg::HistoryLimitEdited(count) => {
+                if let Some(count) = self.history_limit.edit(count, parse_count) {
+                    user.history_limit = count;
+                }
+            }
+            SettingsPanelMsg::MetadataRequestsEdited(count) => {
+                if let Some(count) = self.metadata_requests.edit(count, parse_count) {
+                    user.metadata_requests = count;
+                }
+            }
+            SettingsPanelMsg::UnknownLengthEdited(secs) => {
+                if let Some(secs) = self.unknown_length.edit(secs, parse_length) {
+                    user.unknown_song_secs = secs;
+                }
+            }
+            SettingsPanelMsg::AutosaveEdited(secs) => {
+                if let Some(secs) = self.autosave.edit(secs, parse_autosave) {
+                    user.autosave_secs = secs;
+                }
+            }
+            SettingsPanelMsg::IntervalEdited(ticker, secs) => {
+                let field = self.intervals.iter_mut().find(|(t, _)| *t == ticker)?;
+                let interval = field.1.edit(secs, parse_interval)?;
+                user.tick_intervals_ms
+                    .insert(ticker, interval.as_millis() as u64);
+                return Some((ticker, interval));
+            }
+            SettingsPanelMsg::ToggleSongNotifications => {
+                user.song_notifications = !user.song_notifications
+            }
+            #[cfg(feature = "scrobble")]
+            SettingsPanelMsg::ToggleScrobbling => user.scrobble.enabled = !user.scrobble.enabled,
+            #[cfg(feature = "scrobble")]
+            SettingsPanelMsg::TokenEdited(token) => self.token = token,
+            #[cfg(feature = "scrobble")]
+            SettingsPanelMsg::SaveToken => user.scrobble.token = self.token.trim().to_string(),
+            SettingsPanelMsg::AskClear(folder) => self.clearing = Some(folder),
+            // The caller empties it
+            SettingsPanelMsg::ConfirmClear(_) | SettingsPanelMsg::CancelClear => {
+                self.clearing = None
+            }
+            SettingsPanelMsg::ToggleNormalize
+            | SettingsPanelMsg::Backend(_)
+            | SettingsPanelMsg::Schedule(_)
+            | SettingsPanelMsg::Close => {}
+        }
+        None
+    }
+
+    pub fn view<'a>(
+        &'a self,
+        user: &YTMRUserSettings,
+        backend: &'a BackendForm,
+        status: &'static str,
+        schedule: &Schedule,
+        playlists: &[PlaylistHeader],
+        open: Uuid,
+    ) -> Element<'a, SettingsPanelMsg> {
+        let intervals = self.intervals.iter().map(|(ticker, field)| {
+            let ticker = *ticker;
+            field.view(ticker.label(), move |secs| {
+                SettingsPanelMsg::IntervalEdited(ticker, secs)
+            })
+        });
+        let clear_button = |folder: CacheFolder| {
+            button(text(format!("clear {} cache", folder.label())))
+                .on_press(SettingsPanelMsg::AskClear(folder))
+        };
+        let confirm_clear = self.clearing.map(|folder| {
+            row![
+                text(format!("Delete the whole {} cache?", folder.label())),
+                button("delete").on_press(SettingsPanelMsg::ConfirmClear(folder)),
+                button("cancel").on_press(SettingsPanelMsg::CancelClear),
+            ]
+            .spacing(8)
+            .align_items(Alignment::Center)
+        });
+        #[cfg(feature = "scrobble")]
+        let scrobbling = Some(
+            row![
+                checkbox("submit listens to ListenBrainz", user.scrobble.enabled)
+                    .on_toggle(|_| SettingsPanelMsg::ToggleScrobbling),
+                text_input("user token", &self.token)
+                    .secure(true)
+                    .on_input(SettingsPanelMsg::TokenEdited)
+                    .on_submit(SettingsPanelMsg::SaveToken)
+                    .width(240),
+                button("save token").on_press_maybe(
+                    (self.token.trim() != user.scrobble.token)
+                        .then_some(SettingsPanelMsg::SaveToken)
+                ),
+            ]
+            .spacing(8)
+            .align_items(Alignment::Center),
+        );
+        #[cfg(not(feature = "scrobble"))]
+        let scrobbling: Option<Element<SettingsPanelMsg>> = None;
+        #[cfg(feature = "song-notifications")]
+        let song_notifications = Some(
+            checkbox("notify when songs change", user.song_notifications)
+                .on_toggle(|_| SettingsPanelMsg::ToggleSongNotifications),
+        );
+        #[cfg(not(feature = "song-notifications"))]
+        let song_notifications: Option<Element<SettingsPanelMsg>> = None;
+        column![
+            row![
+                text("Settings").size(20),
+                button("close").on_press(SettingsPanelMsg::Close),
+            ]
+            .spacing(8)
+            .align_items(Alignment::Center),
+            row![
+                text("download as").width(160),
+                pick_list(
+                    DownloadFormat::ALL,
+                    Some(user.download_format),
+                    SettingsPanelMsg::FormatPicked
+                ),
+            ]
+            .spacing(8)
+            .align_items(Alignment::Center),
+            self.cache_size
+                .view("song cache (MB)", SettingsPanelMsg::CacheSizeEdited),
+            self.stream_over
+                .view("stream songs over (MB)", SettingsPanelMsg::StreamOverEdited),
+            self.stop_fade
+                .view("fade out songs (s)", SettingsPanelMsg::StopFadeEdited),
+            self.history_limit
+                .view("songs in history", SettingsPanelMsg::HistoryLimitEdited),
+            self.metadata_requests.view(
+                "songs fetched at once",
+                SettingsPanelMsg::MetadataRequestsEdited
+            ),
+            self.unknown_length.view(
+                "unknown song length (s)",
+                SettingsPanelMsg::UnknownLengthEdited
+            ),
+            self.autosave
+                .view("save every (s, 0 = off)", SettingsPanelMsg::AutosaveEdited),
+            column![checkbox("normalize volume", user.normalize)
+                .on_toggle(|_| SettingsPanelMsg::ToggleNormalize)]
+            .push_maybe(song_notifications)
+            .spacing(8),
+            column![row![
+                clear_button(CacheFolder::Sounds),
+                clear_button(CacheFolder::Thumbnails)
+            ]
+            .spacing(8)]
+            .push_maybe(confirm_clear)
+            .spacing(8),
+            text("seconds between ticks"),
+            Column::with_children(intervals).spacing(4),
+            self.schedule_view(schedule, playlists, open),
+            backend.view(status).map(SettingsPanelMsg::Backend),
+        ]
+        .push_maybe(scrobbling)
+        .spacing(8)
+        .padding(10)
+        .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use uuid::Uuid;
+
+    use super::{CacheFolder, ScheduleMsg, SettingsPanel, SettingsPanelMsg};
+    use crate::{
+        scheduler::Schedule,
+        settings::{Ticker, YTMRUserSettings},
+        ytmrs::Tickers,
+    };
+
+    #[test]
+    fn invalid_edits_keep_the_stored_values() {
+        let mut user = YTMRUserSettings::default();
+        let mut panel = SettingsPanel::new(&user, &Schedule::default(), &Tickers::default());
+
+        panel.update(SettingsPanelMsg::CacheSizeEdited("512".into()), &mut user);
+        assert_eq![user.max_audio_cache_mb, 512];
+        for size in ["", "0", "-3", "lots"] {
+            panel.update(SettingsPanelMsg::CacheSizeEdited(size.into()), &mut user);
+            assert_eq![user.max_audio_cache_mb, 512];
+            assert![panel.cache_size.error.is_some()];
+        }
+        assert_eq![panel.cache_size.text, "lots"];
+
+        let edit = |secs: &str| SettingsPanelMsg::IntervalEdited(Ticker::Cache, secs.into());
+        let changed = panel.update(edit("2.5"), &mut user);
+        assert_eq![changed, Some((Ticker::Cache, Duration::from_millis(2500)))];
+        assert_eq![user.tick_intervals_ms.get(&Ticker::Cache), Some(&2500)];
+        for secs in ["0", "0.01", "NaN", "often"] {
+            assert_eq![panel.update(edit(secs), &mut user), None];
+            assert_eq![user.tick_intervals_ms.get(&Ticker::Cache), Some(&2500)];
+        }
+
+        panel.update(SettingsPanelMsg::StopFadeEdited("0".into()), &mut user);
+        assert_eq![user.stop_fade_ms, 0];
+        panel.update(SettingsPanelMsg::StopFadeEdited("10".into()), &mut user);
+        assert_eq![user.stop_fade_ms, 0];
+
+        panel.update(SettingsPanelMsg::HistoryLimitEdited("20".into()), &mut user);
+        panel.update(SettingsPanelMsg::HistoryLimitEdited("0".into()), &mut user);
+        assert_eq![user.history_limit, 20];
+
+        // Autosaving can be turned off, but not made negative
+        panel.update(SettingsPanelMsg::AutosaveEdited("0".into()), &mut user);
+        panel.update(SettingsPanelMsg::AutosaveEdited("-5".into()), &mut user);
+        assert_eq![user.autosave_secs, 0];
+    }
+
+    #[test]
+    fn clearing_a_cache_is_confirmed_first() {
+        let mut user = YTMRUserSettings::default();
+        let mut panel = SettingsPanel::new(&user, &Schedule::default(), &Tickers::default());
+        panel.update(SettingsPanelMsg::AskClear(CacheFolder::Sounds), &mut user);
+        assert_eq![panel.clearing, Some(CacheFolder::Sounds)];
+        panel.update(SettingsPanelMsg::CancelClear, &mut user);
+        assert_eq![panel.clearing, None];
+
+        let folder = CacheFolder::Thumbnails;
+        panel.update(SettingsPanelMsg::AskClear(folder), &mut user);
+        panel.update(SettingsPanelMsg::ConfirmClear(folder), &mut user);
+        assert_eq![panel.clearing, None];
+    }
+
+    #[test]
+    fn schedules_are_edited_in_place() {
+        let mut schedule = Schedule::default();
+        let mut panel =
+            SettingsPanel::new(&YTMRUserSettings::default(), &schedule, &Tickers::default());
+        let mut edit = |msg| panel.update_schedule(msg, &mut schedule, 0.5);
+        let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+        edit(ScheduleMsg::Add(first));
+        edit(ScheduleMsg::Add(second));
+
+        edit(ScheduleMsg::TimeEdited(1, "21:05".into()));
+        for time in ["24:00", "7", "noon"] {
+            edit(ScheduleMsg::TimeEdited(1, time.into()));
+        }
+        // Sunday on, Monday off
+        edit(ScheduleMsg::DayToggled(1, 6));
+        edit(ScheduleMsg::DayToggled(1, 0));
+        edit(ScheduleMsg::VolumeEdited(1, "80".into()));
+        edit(ScheduleMsg::VolumeEdited(1, "120".into()));
+        edit(ScheduleMsg::PlaylistPicked(1, first));
+        edit(ScheduleMsg::RampEdited(1, "0".into()));
+        let entry = &schedule.entries[1];
+        assert_eq![(entry.hour, entry.minute), (21, 5)];
+        assert_eq![entry.days, 0b101_1110];
+        assert_eq![(entry.volume, entry.playlist), (0.8, first)];
+        assert_eq![entry.ramp_secs, 0];
+        assert![panel.schedule[1].time.error.is_some()];
+
+        // The text follows its entry once the one before it is gone
+        panel.update_schedule(ScheduleMsg::Remove(0), &mut schedule, 0.5);
+        assert_eq![schedule.entries.len(), 1];
+        assert_eq![schedule.entries[0].playlist, first];
+        assert_eq![panel.schedule[0].time.text, "noon"];
+        panel.update_schedule(ScheduleMsg::RampEdited(1, "5".into()), &mut schedule, 0.5);
+        assert_eq![schedule.entries[0].ramp_secs, 0];
+    }
+}
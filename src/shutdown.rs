@@ -0,0 +1,56 @@
+//! Lets the app save before the system ends it. On unix, SIGTERM, SIGINT and SIGHUP are taken
+//! as a close request. Elsewhere, nothing is caught.
+
+use std::sync::Arc;
+
+use futures::{channel::mpsc::UnboundedReceiver, future, stream, StreamExt};
+use iced::Subscription;
+use parking_lot::Mutex;
+
+/// The signals that were caught, taken by the first subscription that runs
+#[derive(Debug, Clone, Default)]
+pub struct StopRequests(Arc<Mutex<Option<UnboundedReceiver<()>>>>);
+
+impl StopRequests {
+    pub fn subscription(&self) -> Subscription<()> {
+        let receiver = Arc::clone(&self.0);
+        let requests = stream::once(async move { receiver.lock().take() })
+            .filter_map(future::ready)
+            .flatten();
+        iced::subscription::run_with_id("stop_requests", requests)
+    }
+}
+
+/// Starts catching the signals, on a thread of its own
+#[cfg(unix)]
+pub fn listen() -> StopRequests {
+    use signal_hook::{
+        consts::{SIGHUP, SIGINT, SIGTERM},
+        iterator::Signals,
+    };
+
+    use crate::verbosity::debug;
+
+    let mut signals = match Signals::new([SIGTERM, SIGINT, SIGHUP]) {
+        Ok(signals) => signals,
+        Err(e) => {
+            println!["Signals won't be caught, the app won't save when it's ended: {e}"];
+            return StopRequests::default();
+        }
+    };
+    let (requests, receiver) = futures::channel::mpsc::unbounded();
+    std::thread::spawn(move || {
+        for signal in signals.forever() {
+            debug!["Caught signal {signal}"];
+            if requests.unbounded_send(()).is_err() {
+                return;
+            }
+        }
+    });
+    StopRequests(Arc::new(Mutex::new(Some(receiver))))
+}
+
+#[cfg(not(unix))]
+pub fn listen() -> StopRequests {
+    StopRequests::default()
+}
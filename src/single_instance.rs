@@ -0,0 +1,233 @@
+//! Keeps one app running at a time, since two would each launch a backend and write the same
+//! caches. The running instance writes its pid and the port it listens on to a lock file, and
+//! instances started after it ask it to show its window, then exit.
+//!
+//! A lock whose pid isn't running, or whose port doesn't answer like the app, is left from a
+//! crash and is replaced.
+
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    process,
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+use futures::{
+    channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
+    future, stream, StreamExt,
+};
+use iced::Subscription;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::{settings::instance_lock_path, verbosity::debug};
+
+/// Sent by the new instance, on a line of its own
+const SHOW_REQUEST: &str = "show";
+/// What the running instance answers, so a reused pid isn't taken for it
+const SHOW_REPLY: &str = "ytm-rs";
+/// How long the running instance has to answer
+const REPLY_TIMEOUT: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct LockInfo {
+    pid: u32,
+    port: u16,
+}
+
+/// Removes the lock file when the app exits
+#[derive(Debug)]
+pub struct InstanceGuard {
+    lock: Option<PathBuf>,
+}
+
+impl Drop for InstanceGuard {
+    fn drop(&mut self) {
+        if let Some(path) = &self.lock {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// The other instances asking for the window, taken by the first subscription that runs
+#[derive(Debug, Clone, Default)]
+pub struct ShowRequests(Arc<Mutex<Option<UnboundedReceiver<()>>>>);
+
+impl ShowRequests {
+    pub fn subscription(&self) -> Subscription<()> {
+        let receiver = Arc::clone(&self.0);
+        let requests = stream::once(async move { receiver.lock().take() })
+            .filter_map(future::ready)
+            .flatten();
+        iced::subscription::run_with_id("show_requests", requests)
+    }
+}
+
+pub enum Claim {
+    /// No other instance is running
+    First(InstanceGuard, ShowRequests),
+    /// The running instance was asked to show its window
+    Signaled,
+}
+
+impl Claim {
+    /// Runs without a lock, for when it can't be made
+    fn unlocked() -> Self {
+        Self::First(InstanceGuard { lock: None }, ShowRequests::default())
+    }
+}
+
+pub fn claim() -> Claim {
+    claim_at(&instance_lock_path())
+}
+
+fn claim_at(path: &Path) -> Claim {
+    let listener = match TcpListener::bind((Ipv4Addr::LOCALHOST, 0)) {
+        Ok(listener) => listener,
+        Err(e) => {
+            println!["Other instances won't find this one, it can't listen: {e}"];
+            return Claim::unlocked();
+        }
+    };
+    let own = LockInfo {
+        pid: process::id(),
+        port: match listener.local_addr() {
+            Ok(addr) => addr.port(),
+            Err(_) => return Claim::unlocked(),
+        },
+    };
+    // A stale lock is removed, then claimed once more
+    for _ in 0..2 {
+        match write_new(path, own) {
+            Ok(()) => {
+                let (requests, receiver) = unbounded();
+                thread::spawn(move || listen(listener, requests));
+                let guard = InstanceGuard {
+                    lock: Some(path.to_path_buf()),
+                };
+                return Claim::First(guard, ShowRequests(Arc::new(Mutex::new(Some(receiver)))));
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(e) => {
+                println!["Failed to write the instance lock: {e}"];
+                return Claim::unlocked();
+            }
+        }
+        let other = fs::read_to_string(path)
+            .ok()
+            .and_then(|json| serde_json::from_str::<LockInfo>(&json).ok());
+        match other {
+            Some(other) if is_running(other.pid) && ask_to_show(other.port) => {
+                return Claim::Signaled
+            }
+            _ => {
+                println!["Removing the lock of an instance that isn't running"];
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+    Claim::unlocked()
+}
+
+/// Fails if the lock exists, so two instances starting at once can't both claim it
+fn write_new(path: &Path, info: LockInfo) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)?;
+    file.write_all(serde_json::to_string(&info)?.as_bytes())
+}
+
+#[cfg(unix)]
+fn is_running(pid: u32) -> bool {
+    process::Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stderr(process::Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+#[cfg(windows)]
+fn is_running(pid: u32) -> bool {
+    process::Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {pid}"), "/NH"])
+        .output()
+        .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).contains(&pid.to_string()))
+}
+
+/// Whether the app answered at the port
+fn ask_to_show(port: u16) -> bool {
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    let exchange = || -> io::Result<String> {
+        let mut stream = TcpStream::connect_timeout(&addr, REPLY_TIMEOUT)?;
+        stream.set_read_timeout(Some(REPLY_TIMEOUT))?;
+        writeln!(stream, "{SHOW_REQUEST}")?;
+        let mut reply = String::new();
+        BufReader::new(stream).read_line(&mut reply)?;
+        Ok(reply)
+    };
+    match exchange() {
+        Ok(reply) => reply.trim() == SHOW_REPLY,
+        Err(e) => {
+            debug!["No instance answered at port {port}: {e}"];
+            false
+        }
+    }
+}
+
+/// Ends once the app stops taking requests
+fn listen(listener: TcpListener, requests: UnboundedSender<()>) {
+    for stream in listener.incoming().flatten() {
+        if matches!(answer(stream), Ok(true)) && requests.unbounded_send(()).is_err() {
+            return;
+        }
+    }
+}
+
+/// Whether the connection asked for the window. Anything else that connects is ignored.
+fn answer(mut stream: TcpStream) -> io::Result<bool> {
+    stream.set_read_timeout(Some(REPLY_TIMEOUT))?;
+    let mut request = String::new();
+    BufReader::new(&stream).read_line(&mut request)?;
+    match request.trim() == SHOW_REQUEST {
+        true => writeln!(stream, "{SHOW_REPLY}").map(|_| true),
+        false => Ok(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+
+    use super::{claim_at, write_new, Claim, LockInfo};
+
+    #[test]
+    fn later_instances_signal_the_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("instance.lock");
+        // Left by a crash, nothing answers at the port
+        let crashed = LockInfo {
+            pid: u32::MAX,
+            port: 9,
+        };
+        write_new(&path, crashed).unwrap();
+
+        let (guard, requests) = match claim_at(&path) {
+            Claim::First(guard, requests) => (guard, requests),
+            Claim::Signaled => panic!["signaled an instance that isn't running"],
+        };
+        assert![matches!(claim_at(&path), Claim::Signaled)];
+        let mut received = requests.0.lock().take().unwrap();
+        assert_eq![futures::executor::block_on(received.next()), Some(())];
+
+        drop(guard);
+        assert![!path.exists()];
+    }
+}
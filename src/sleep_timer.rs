@@ -0,0 +1,95 @@
+//! Pauses playback once a set time is up, fading the song out first. The timer isn't saved,
+//! closing the app ends it.
+
+use std::time::{Duration, Instant};
+
+use crate::song::parse_duration;
+
+/// The times offered in the tracker's menu, in minutes
+pub const SLEEP_CHOICES: [u64; 3] = [15, 30, 60];
+/// How long the song fades out for before playback pauses
+pub const SLEEP_FADE: Duration = Duration::from_secs(10);
+
+/// What the timer asks for on a tick
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SleepStep {
+    Wait,
+    /// Fades the song out over the time that's left
+    Fade(Duration),
+    Pause,
+}
+
+#[derive(Debug, Clone)]
+pub struct SleepTimer {
+    ends: Instant,
+    fading: bool,
+}
+
+impl SleepTimer {
+    pub fn new(duration: Duration, now: Instant) -> Self {
+        Self {
+            ends: now + duration,
+            fading: false,
+        }
+    }
+
+    pub fn remaining(&self, now: Instant) -> Duration {
+        self.ends.saturating_duration_since(now)
+    }
+
+    /// Whether the song is being faded out
+    pub fn fading(&self) -> bool {
+        self.fading
+    }
+
+    /// The fade starts once, on the first tick of the last seconds
+    pub fn tick(&mut self, now: Instant) -> SleepStep {
+        let remaining = self.remaining(now);
+        if remaining.is_zero() {
+            return SleepStep::Pause;
+        }
+        match !self.fading && remaining <= SLEEP_FADE {
+            true => {
+                self.fading = true;
+                SleepStep::Fade(remaining)
+            }
+            false => SleepStep::Wait,
+        }
+    }
+}
+
+/// Reads the custom time typed in the menu, as minutes or "mm:ss"
+pub fn parse_sleep(text: &str) -> Option<Duration> {
+    parse_duration(text)
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{parse_sleep, SleepStep, SleepTimer};
+
+    #[test]
+    fn fades_once_then_pauses() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut timer = SleepTimer::new(Duration::from_secs(60), start);
+        assert_eq![timer.tick(at(30)), SleepStep::Wait];
+        assert![!timer.fading()];
+        assert_eq![timer.tick(at(52)), SleepStep::Fade(Duration::from_secs(8))];
+        assert_eq![timer.tick(at(55)), SleepStep::Wait];
+        assert![timer.fading()];
+        assert_eq![timer.tick(at(61)), SleepStep::Pause];
+        assert_eq![timer.remaining(at(61)), Duration::ZERO];
+    }
+
+    #[test]
+    fn custom_times_are_minutes() {
+        assert_eq![parse_sleep("45"), Some(Duration::from_secs(45 * 60))];
+        assert_eq![parse_sleep("1:30"), Some(Duration::from_secs(90))];
+        assert_eq![parse_sleep("0"), None];
+        assert_eq![parse_sleep(""), None];
+    }
+}
@@ -1,11 +1,13 @@
+use std::fmt::Display;
+
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
 use iced::{
     advanced::image as iced_image,
     alignment::{Horizontal, Vertical},
-    widget::{self, button, column, container, hover, row, text, Image, Row, Text},
+    widget::{self, button, column, container, hover, row, text, tooltip, Image, Row, Text},
     Alignment, Background, Border, Color, Element, Length, Shadow, Vector,
 };
 
@@ -16,11 +18,18 @@ use iced::widget::Svg;
 use crate::audio::PLAY_SVG;
 
 use crate::{
+    audio::{LoadFacts, PlaybackMode},
     caching::IDed,
     response_types::{RequestedDownload, UrlString},
     settings::SongKey,
+    song_operations::tree_filter::TreeFilter,
 };
 
+/// The text of songs whose video is gone
+const UNAVAILABLE_COLOR: Color = Color::from_rgb(0.85, 0.25, 0.25);
+/// The part of a row the playlist's filter matched
+const HIGHLIGHT_COLOR: Color = Color::from_rgb(1.0, 0.8, 0.2);
+
 fn r(len: usize) -> String {
     thread_rng()
         .sample_iter(&Alphanumeric)
@@ -32,6 +41,13 @@ fn r(len: usize) -> String {
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Song {
     pub id: SongKey,
+    /// The id given by the extractor, if `id` had to be namespaced because of a collision
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub raw_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub extractor_key: Option<String>,
     pub title: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
@@ -42,13 +58,53 @@ pub struct Song {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub album: Option<String>,
     pub webpage_url: UrlString,
+    /// Zero when the extractor doesn't know it
+    #[serde(default, deserialize_with = "null_as_default")]
     pub duration: f64,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub is_live: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub artists: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     #[serde(default)]
     pub tags: Vec<String>,
 
+    /// User rating from 1 to 5
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub rating: Option<u8>,
+    /// Personal notes about the song
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub notes: Option<String>,
+    /// Whether the user edited the title, tags, album or artists, which then survive refreshes
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    #[serde(default)]
+    pub user_edited: bool,
+    /// Where the song was first found
+    #[serde(skip_serializing_if = "SongSource::is_unknown")]
+    #[serde(default)]
+    pub source: SongSource,
+    /// Whether the song is decoded into memory or streamed when it plays
+    #[serde(skip_serializing_if = "PlaybackMode::is_auto")]
+    #[serde(default)]
+    pub playback_mode: PlaybackMode,
+    /// The amplitude that brings the song to the others' loudness, measured when it's decoded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub gain: Option<f64>,
+    /// The thumbnail's most prominent color as RGBA, which the background is made from.
+    /// Picked the first time the song plays.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub primary_color: Option<[u8; 4]>,
+    /// Whether the video was deleted or made private since the song was cached.
+    /// Found out when the song's info is refreshed, and cleared by the next refresh that works.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    #[serde(default)]
+    pub unavailable: bool,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub requested_downloads: Option<Vec<RequestedDownload>>,
     #[serde(skip)]
@@ -63,6 +119,8 @@ impl Song {
     pub fn basic() -> Self {
         Self {
             id: r(11),
+            raw_id: None,
+            extractor_key: None,
             title: r(14),
             description: None,
             channel: r(10),
@@ -71,6 +129,7 @@ impl Song {
             album: None,
             webpage_url: "...".to_string(),
             duration: thread_rng().gen_range(0.0..(12.0 * 60.0 * 60.0)),
+            is_live: false,
             artists: Some(
                 ["Me!!".into()]
                     .into_iter()
@@ -83,6 +142,14 @@ impl Song {
                 .cycle()
                 .take(thread_rng().gen_range(0..=5))
                 .collect(),
+            rating: None,
+            notes: None,
+            user_edited: false,
+            source: SongSource::Unknown,
+            playback_mode: PlaybackMode::Auto,
+            gain: None,
+            primary_color: None,
+            unavailable: false,
             requested_downloads: None,
             thumbnail_handle: None,
             ui_state: SongState::default(),
@@ -94,11 +161,83 @@ impl Song {
             title: self.title.clone(),
             channel: self.channel.clone(),
             artists: self.artists.clone(),
-            duration: self.duration,
+            duration: self.song_duration(),
             handle: self.thumbnail_handle.clone(),
             state: self.ui_state.clone(),
+            rating: self.rating,
+            notes: self.notes.clone(),
+            provisional: false,
+            unavailable: self.unavailable,
         }
     }
+
+    pub fn song_duration(&self) -> SongDuration {
+        SongDuration::from_parts(self.duration, self.is_live)
+    }
+
+    /// What the song's metadata says about its size, the file's own size is better when known
+    pub fn load_facts(&self, headroom: Option<u64>) -> LoadFacts {
+        LoadFacts {
+            file_size: self
+                .requested_downloads
+                .iter()
+                .flatten()
+                .map(|download| download.filesize.max(download.filesize_approx) as u64)
+                .find(|size| *size > 0),
+            duration: (self.duration > 0.0).then_some(self.duration),
+            headroom,
+        }
+    }
+
+    /// Whether the two would be written to the cache the same way
+    pub fn saves_as(&self, other: &Song) -> bool {
+        serde_json::to_value(self).ok() == serde_json::to_value(other).ok()
+    }
+
+    /// Copies the fields only the user can set from the previously cached version of this song,
+    /// so refreshing metadata from the backend doesn't wipe them.
+    pub fn preserve_user_fields(&mut self, previous: &Song) {
+        self.rating = previous.rating;
+        self.notes = previous.notes.clone();
+        self.playback_mode = previous.playback_mode;
+        // Downloads don't measure the song again
+        if self.gain.is_none() {
+            self.gain = previous.gain;
+        }
+        if self.primary_color.is_none() {
+            self.primary_color = previous.primary_color;
+        }
+        if !previous.source.is_unknown() {
+            self.source = previous.source.clone();
+        }
+        if previous.user_edited {
+            self.title = previous.title.clone();
+            self.tags = previous.tags.clone();
+            self.album = previous.album.clone();
+            self.artists = previous.artists.clone();
+            self.user_edited = true;
+        }
+    }
+
+    /// Whether every word of the query appears somewhere in the song's text, ignoring case.
+    /// This includes the user's notes, so songs can be found by what they were annotated with.
+    pub fn matches(&self, query: &str) -> bool {
+        let mut haystack = vec![self.title.as_str(), self.channel.as_str()];
+        haystack.extend(self.artists.iter().flatten().map(String::as_str));
+        haystack.extend(self.album.as_deref());
+        haystack.extend(self.tags.iter().map(String::as_str));
+        haystack.extend(self.notes.as_deref());
+        let haystack = haystack.join("\n").to_lowercase();
+
+        query
+            .split_whitespace()
+            .all(|word| haystack.contains(&word.to_lowercase()))
+    }
+
+    /// Whether the song was found in the same place as the source
+    pub fn came_from(&self, source: &SongSource) -> bool {
+        self.source.same_origin(source)
+    }
 }
 impl AsRef<Song> for Song {
     #[inline]
@@ -123,6 +262,153 @@ pub fn format_duration(d: &f32) -> String {
     }
 }
 
+/// Reads "mm:ss" or a number of minutes into seconds
+pub fn parse_duration(text: &str) -> Option<u64> {
+    let text = text.trim();
+    match text.split_once(':') {
+        Some((minutes, seconds)) => {
+            let seconds = seconds.parse::<u64>().ok().filter(|s| *s < 60)?;
+            Some(minutes.parse::<u64>().ok()? * 60 + seconds)
+        }
+        None => {
+            let minutes = text
+                .parse::<f64>()
+                .ok()
+                .filter(|m| m.is_finite() && *m >= 0.0)?;
+            Some((minutes * 60.0).round() as u64)
+        }
+    }
+}
+
+/// Seconds as "mm:ss", however many minutes there are
+pub fn format_minutes(seconds: u64) -> String {
+    format!("{}:{:0>2}", seconds / 60, seconds % 60)
+}
+
+/// The extractor sends `null` for fields it doesn't know, like the duration of livestreams
+fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+/// Where a song was found. Songs cached before this was recorded are `Unknown`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum SongSource {
+    #[default]
+    Unknown,
+    Search {
+        query: String,
+    },
+    Tab {
+        title: String,
+        url: UrlString,
+    },
+    LocalImport {
+        path: String,
+    },
+    Manual,
+}
+impl SongSource {
+    pub fn is_unknown(&self) -> bool {
+        *self == Self::Unknown
+    }
+
+    /// What to search for to open the source again, if it can be
+    pub fn reopen_query(&self) -> Option<&str> {
+        match self {
+            Self::Search { query } => Some(query),
+            Self::Tab { url, .. } => Some(url),
+            Self::Unknown | Self::LocalImport { .. } | Self::Manual => None,
+        }
+    }
+
+    /// Whether both are the same place. Tabs are compared by url, since their titles can change.
+    /// Unknown sources aren't the same as anything.
+    pub fn same_origin(&self, other: &SongSource) -> bool {
+        match (self, other) {
+            (Self::Search { query: a }, Self::Search { query: b }) => a == b,
+            (Self::Tab { url: a, .. }, Self::Tab { url: b, .. }) => a == b,
+            (Self::LocalImport { path: a }, Self::LocalImport { path: b }) => a == b,
+            (Self::Manual, Self::Manual) => true,
+            _ => false,
+        }
+    }
+}
+impl Display for SongSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unknown => f.write_str("unknown source"),
+            Self::Search { query } => write!(f, "search \"{query}\""),
+            Self::Tab { title, .. } => write!(f, "tab \"{title}\""),
+            Self::LocalImport { path } => write!(f, "import from {path}"),
+            Self::Manual => f.write_str("added manually"),
+        }
+    }
+}
+
+/// How long a song is, if that can be known at all
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SongDuration {
+    Known(f64),
+    Unknown,
+    Live,
+}
+impl SongDuration {
+    /// Zero, negative and non-finite durations are treated as unknown
+    pub fn from_parts(duration: f64, is_live: bool) -> Self {
+        match (is_live, duration.is_finite() && duration > 0.0) {
+            (true, _) => Self::Live,
+            (false, true) => Self::Known(duration),
+            (false, false) => Self::Unknown,
+        }
+    }
+
+    pub fn known(&self) -> Option<f64> {
+        match self {
+            Self::Known(d) => Some(*d),
+            Self::Unknown | Self::Live => None,
+        }
+    }
+
+    pub fn format(&self) -> String {
+        match self {
+            Self::Known(d) => format_duration(&(*d as f32)),
+            Self::Unknown => "--:--".to_string(),
+            Self::Live => "LIVE".to_string(),
+        }
+    }
+}
+
+/// Adds up the known durations, and counts the ones that couldn't be added
+pub fn sum_durations(durations: impl IntoIterator<Item = SongDuration>) -> (f64, usize) {
+    durations
+        .into_iter()
+        .fold((0.0, 0), |(total, unknown), d| match d.known() {
+            Some(d) => (total + d, unknown),
+            None => (total, unknown + 1),
+        })
+}
+
+/// e.g. "12:34 (+2 unknown)"
+pub fn format_total_duration(total: f64, unknown: usize) -> String {
+    let total = format_duration(&(total as f32));
+    match unknown {
+        0 => total,
+        n => format!("{total} (+{n} unknown)"),
+    }
+}
+
+pub const MAX_RATING: u8 = 5;
+
+/// Renders a rating as a row of stars, e.g. "★★★☆☆"
+pub fn format_rating(rating: u8) -> String {
+    let rating = rating.min(MAX_RATING) as usize;
+    "★".repeat(rating) + &"☆".repeat(MAX_RATING as usize - rating)
+}
+
 #[derive(Debug, Clone)]
 pub enum SongMessage {
     ThumbnailClicked,
@@ -132,9 +418,14 @@ pub struct SongData {
     pub title: String,
     pub channel: String,
     pub artists: Option<Vec<String>>,
-    pub duration: f64,
+    pub duration: SongDuration,
     pub handle: Option<iced_image::Handle>,
     pub state: SongState,
+    pub rating: Option<u8>,
+    pub notes: Option<String>,
+    /// Taken from a search result while the song's full info is still being fetched
+    pub provisional: bool,
+    pub unavailable: bool,
 }
 impl SongData {
     /// Used for placeholders of songs that are not cached yet
@@ -143,9 +434,13 @@ impl SongData {
             title: "?????".to_string(),
             channel: "???".to_string(),
             artists: None,
-            duration: -1.0,
+            duration: SongDuration::Unknown,
             handle: None,
             state: SongState::default(),
+            rating: None,
+            notes: None,
+            provisional: false,
+            unavailable: false,
         }
     }
 
@@ -156,6 +451,14 @@ impl SongData {
         }
     }
 
+    /// Marks the data as standing in for the full info
+    pub fn into_provisional(self) -> Self {
+        Self {
+            provisional: true,
+            ..self
+        }
+    }
+
     fn format_artists(&self) -> String {
         match &self.artists {
             None => self.channel.clone(),
@@ -163,6 +466,44 @@ impl SongData {
         }
     }
 
+    /// A plain-text description of the row, for assistive tech and the focus status line.
+    /// Every focusable song row gets its label from here.
+    pub fn describe(&self) -> String {
+        let mut description = format!(
+            "{}, by {}, {}",
+            self.title,
+            self.format_artists(),
+            self.duration.format()
+        );
+        if let Some(rating) = self.rating {
+            description.push_str(&format!(", rated {rating} of {MAX_RATING}"));
+        }
+        if let Some(notes) = &self.notes {
+            description.push_str(&format!(", noted \"{notes}\""));
+        }
+        if let Some(state) = self.state.describe() {
+            description.push_str(", ");
+            description.push_str(&state);
+        }
+        if self.provisional {
+            description.push_str(", details loading");
+        }
+        description
+    }
+
+    fn format_duration_and_rating(&self) -> String {
+        let duration = self.duration.format();
+        let text = match self.rating {
+            None => duration,
+            Some(rating) => format!("{}  {}", duration, format_rating(rating)),
+        };
+        // The row follows a download as it goes
+        match (&self.state, self.state.describe()) {
+            (SongState::Downloading(_), Some(state)) => format!("{text}  {state}"),
+            _ => text,
+        }
+    }
+
     fn img(h: Option<iced_image::Handle>, x: u16, y: u16) -> Option<Image<iced_image::Handle>> {
         h.map(|h| {
             Image::new(h)
@@ -181,7 +522,7 @@ impl SongData {
     }
 
     /// Creates the thumbnail of the song data, replacing with "???" text upon missing data.
-    fn image_or_placeholder<'a, M>(
+    pub fn image_or_placeholder<'a, M>(
         h: Option<iced_image::Handle>,
         width: u16,
         height: u16,
@@ -193,7 +534,41 @@ impl SongData {
     }
 
     pub fn row<'a>(self, clickable: bool, hover_play_button: bool) -> Row<'a, SongMessage> {
+        self.row_highlighting(clickable, hover_play_button, None)
+    }
+
+    /// The row, with what the filter matched in its title and artists picked out
+    pub fn row_highlighting<'a>(
+        self,
+        clickable: bool,
+        hover_play_button: bool,
+        filter: Option<&TreeFilter>,
+    ) -> Row<'a, SongMessage> {
         let img = Self::image_or_placeholder(self.handle.clone(), 80, 80);
+        let lines = [
+            format!(
+                "{}{}",
+                self.title,
+                match (self.provisional, self.unavailable) {
+                    (_, true) => " (unavailable)",
+                    // Upgraded in place once the full info arrives
+                    (true, false) => " *",
+                    (false, false) => "",
+                }
+            ),
+            self.format_duration_and_rating(),
+            self.format_artists(),
+        ];
+        let color = self.unavailable.then_some(UNAVAILABLE_COLOR);
+        let details: Element<'a, SongMessage> = match filter {
+            None => text(lines.join("\n"))
+                .style(move |_| widget::text::Style { color })
+                .into(),
+            Some(filter) => widget::Column::with_children(
+                lines.iter().map(|line| highlighted(line, filter, color)),
+            )
+            .into(),
+        };
 
         let c = match clickable {
             false => img,
@@ -217,6 +592,13 @@ impl SongData {
                 .into(),
         };
 
+        let badge = self
+            .state
+            .badge()
+            .zip(self.state.describe())
+            .map(|(badge, state)| {
+                tooltip(text(badge).width(16), text(state), tooltip::Position::Left)
+            });
         let row = row![
             match hover_play_button {
                 false => c,
@@ -254,27 +636,350 @@ impl SongData {
                     )
                 }
             },
-            column![text(format!(
-                "{}\n{}\n{}",
-                self.title.clone(),
-                format_duration(&(self.duration as f32)),
-                self.format_artists()
-            )),]
-            .width(Length::Fill),
-        ];
+            column![details].width(Length::Fill),
+        ]
+        // Small fixed-width icons at the end, so the rest of the row doesn't move
+        .push_maybe(badge)
+        .push_maybe(
+            self.notes
+                .map(|notes| tooltip(text("✎").width(16), text(notes), tooltip::Position::Left)),
+        );
         row.spacing(8).padding(0).align_items(Alignment::Center)
     }
 }
 
+/// The line, with the part the filter matched in the highlight color
+fn highlighted<'a, M: 'a>(line: &str, filter: &TreeFilter, color: Option<Color>) -> Element<'a, M> {
+    let style = move |_: &_| widget::text::Style { color };
+    match filter.highlight(line) {
+        Some(range) => row![
+            text(line[..range.start].to_string()).style(style),
+            text(line[range.clone()].to_string()).style(|_| widget::text::Style {
+                color: Some(HIGHLIGHT_COLOR),
+            }),
+            text(line[range.end..].to_string()).style(style),
+        ]
+        .into(),
+        None => text(line.to_string()).style(style).into(),
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub enum SongState {
     #[default]
     None,
 
     Fetching,
-    Downloading,
+    /// How much is done, from 0 to 1, once its size is known
+    Downloading(Option<f32>),
     Downloaded,
 
     Cached,
     Playing,
 }
+impl SongState {
+    pub fn describe(&self) -> Option<String> {
+        let state = match self {
+            SongState::None => return None,
+            SongState::Fetching => "fetching",
+            SongState::Downloading(Some(done)) => {
+                return Some(format!("downloading {:.0}%", done * 100.0))
+            }
+            SongState::Downloading(None) => "downloading",
+            SongState::Downloaded => "downloaded",
+            SongState::Cached => "cached",
+            SongState::Playing => "playing",
+        };
+        Some(state.to_string())
+    }
+
+    /// Whether the song's download can be removed, which the playing song's can't
+    pub fn removable_download(&self) -> bool {
+        matches!(self, SongState::Downloaded | SongState::Cached)
+    }
+
+    /// The icon shown at the end of the song's rows, its progress is shown by the duration
+    pub fn badge(&self) -> Option<&'static str> {
+        match self {
+            SongState::None => None,
+            SongState::Fetching => Some("☁"),
+            SongState::Downloading(_) => Some("⟳"),
+            SongState::Downloaded | SongState::Cached => Some("✓"),
+            SongState::Playing => Some("♪"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::PlaybackMode;
+
+    use super::{
+        format_minutes, format_rating, format_total_duration, parse_duration, sum_durations, Song,
+        SongData, SongDuration, SongSource, SongState,
+    };
+
+    #[test]
+    fn refresh_preserves_rating() {
+        let previous = Song {
+            rating: Some(4),
+            ..Song::basic()
+        };
+        let mut refreshed = Song {
+            id: previous.id.clone(),
+            ..Song::basic()
+        };
+        refreshed.preserve_user_fields(&previous);
+
+        assert_eq![refreshed.rating, Some(4)];
+    }
+
+    #[test]
+    fn durations_are_read_as_minutes() {
+        assert_eq![parse_duration("25:30"), Some(25 * 60 + 30)];
+        assert_eq![parse_duration(" 90 "), Some(90 * 60)];
+        assert_eq![parse_duration("1.5"), Some(90)];
+        assert_eq![parse_duration("2:75"), None];
+        assert_eq![parse_duration("-3"), None];
+        assert_eq![parse_duration("soon"), None];
+        assert_eq![format_minutes(90 * 60 + 5), "90:05"];
+    }
+
+    #[test]
+    fn downloads_show_their_progress() {
+        let mut data = SongData::mystery();
+        data.state = SongState::Downloading(None);
+        assert![data.describe().ends_with(", downloading")];
+        data.state = SongState::Downloading(Some(0.42));
+        assert![data.describe().ends_with(", downloading 42%")];
+        assert![data
+            .format_duration_and_rating()
+            .ends_with("downloading 42%")];
+    }
+
+    #[test]
+    fn only_songs_on_their_way_have_badges() {
+        assert_eq![SongState::None.badge(), None];
+        assert_eq![SongState::Downloading(Some(0.5)).badge(), Some("⟳")];
+        assert_eq![SongState::Downloaded.badge(), SongState::Cached.badge()];
+        assert_eq![SongState::Playing.describe().as_deref(), Some("playing")];
+    }
+
+    #[test]
+    fn measured_gain_survives_refreshes() {
+        let previous = Song {
+            gain: Some(0.5),
+            primary_color: Some([200, 40, 40, 255]),
+            ..Song::basic()
+        };
+        let mut refreshed = Song::basic();
+        refreshed.preserve_user_fields(&previous);
+        assert_eq![refreshed.gain, Some(0.5)];
+        assert_eq![refreshed.primary_color, Some([200, 40, 40, 255])];
+
+        // Songs without one are read as unmeasured
+        let json = serde_json::to_string(&Song::basic()).unwrap();
+        assert![!json.contains("\"gain\"")];
+        assert_eq![serde_json::from_str::<Song>(&json).unwrap().gain, None];
+    }
+
+    #[test]
+    fn refreshes_that_work_make_songs_available_again() {
+        let previous = Song {
+            unavailable: true,
+            ..Song::basic()
+        };
+        let json = serde_json::to_string(&previous).unwrap();
+        assert![serde_json::from_str::<Song>(&json).unwrap().unavailable];
+
+        let mut refreshed = Song::basic();
+        refreshed.preserve_user_fields(&previous);
+        assert![!refreshed.unavailable];
+        let json = serde_json::to_string(&refreshed).unwrap();
+        assert![!json.contains("unavailable")];
+    }
+
+    #[test]
+    fn refresh_preserves_edited_metadata() {
+        let previous = Song {
+            tags: vec!["mine".into()],
+            album: Some("Renamed".into()),
+            ..Song::basic()
+        };
+        let mut refreshed = Song::basic();
+        refreshed.preserve_user_fields(&previous);
+        // Not edited by the user, so the backend's metadata wins
+        assert_eq![refreshed.album, None];
+
+        let previous = Song {
+            user_edited: true,
+            ..previous
+        };
+        refreshed.preserve_user_fields(&previous);
+        assert_eq![refreshed.tags, vec!["mine".to_string()]];
+        assert_eq![refreshed.album.as_deref(), Some("Renamed")];
+        assert![refreshed.user_edited];
+    }
+
+    #[test]
+    fn first_source_is_kept() {
+        let tab = SongSource::Tab {
+            title: "Mix".into(),
+            url: "https://www.youtube.com/playlist?list=abc".into(),
+        };
+        let previous = Song {
+            source: tab.clone(),
+            ..Song::basic()
+        };
+        let mut refreshed = Song {
+            source: SongSource::Search {
+                query: "later".into(),
+            },
+            ..Song::basic()
+        };
+        refreshed.preserve_user_fields(&previous);
+        assert_eq![refreshed.source, tab];
+
+        // Nothing recorded yet, so the new source is the first one
+        let mut refreshed = Song {
+            source: SongSource::Manual,
+            ..Song::basic()
+        };
+        refreshed.preserve_user_fields(&Song::basic());
+        assert_eq![refreshed.source, SongSource::Manual];
+    }
+
+    #[test]
+    fn old_lines_have_an_unknown_source() {
+        let mut song = Song::basic();
+        let json = serde_json::to_string(&song).unwrap();
+        assert![!json.contains("source")];
+        let loaded: Song = serde_json::from_str(&json).unwrap();
+        assert_eq![loaded.source, SongSource::Unknown];
+
+        song.source = SongSource::Search {
+            query: "lofi".into(),
+        };
+        let loaded: Song = serde_json::from_str(&serde_json::to_string(&song).unwrap()).unwrap();
+        assert_eq![loaded.source, song.source];
+    }
+
+    #[test]
+    fn playback_modes_default_to_auto_and_survive_refreshes() {
+        let json = serde_json::to_string(&Song::basic()).unwrap();
+        assert![!json.contains("playback_mode")];
+        let loaded: Song = serde_json::from_str(&json).unwrap();
+        assert_eq![loaded.playback_mode, PlaybackMode::Auto];
+
+        let previous = Song {
+            playback_mode: PlaybackMode::Stream,
+            ..Song::basic()
+        };
+        let mut refreshed = Song::basic();
+        refreshed.preserve_user_fields(&previous);
+        assert_eq![refreshed.playback_mode, PlaybackMode::Stream];
+    }
+
+    #[test]
+    fn songs_can_be_filtered_by_source() {
+        let tab = |title: &str, url: &str| SongSource::Tab {
+            title: title.into(),
+            url: url.into(),
+        };
+        let song = Song {
+            source: tab("Old title", "https://a"),
+            ..Song::basic()
+        };
+        assert![song.came_from(&tab("New title", "https://a"))];
+        assert![!song.came_from(&tab("Old title", "https://b"))];
+        assert![!song.came_from(&SongSource::Search {
+            query: "https://a".into()
+        })];
+        assert![!Song::basic().came_from(&SongSource::Unknown)];
+        assert_eq![song.source.reopen_query(), Some("https://a")];
+    }
+
+    #[test]
+    fn refresh_preserves_notes() {
+        let previous = Song {
+            notes: Some("skip the first verse".into()),
+            ..Song::basic()
+        };
+        let mut refreshed = Song::basic();
+        refreshed.preserve_user_fields(&previous);
+
+        assert_eq![refreshed.notes.as_deref(), Some("skip the first verse")];
+    }
+
+    #[test]
+    fn search_includes_notes() {
+        let song = Song {
+            title: "Clair de Lune".into(),
+            notes: Some("Good for STUDYING".into()),
+            ..Song::basic()
+        };
+        assert![song.matches("studying")];
+        assert![song.matches("clair studying")];
+        assert![!song.matches("clair sleeping")];
+        assert![!Song::basic().matches("studying")];
+    }
+
+    #[test]
+    fn ratings_render_as_stars() {
+        assert_eq![format_rating(3), "★★★☆☆"];
+        assert_eq![format_rating(9), "★★★★★"];
+    }
+
+    #[test]
+    fn durations_are_derived_from_song_fields() {
+        assert_eq![
+            SongDuration::from_parts(90.0, false),
+            SongDuration::Known(90.0)
+        ];
+        assert_eq![SongDuration::from_parts(0.0, false), SongDuration::Unknown];
+        assert_eq![SongDuration::from_parts(-1.0, false), SongDuration::Unknown];
+        assert_eq![
+            SongDuration::from_parts(f64::NAN, false),
+            SongDuration::Unknown
+        ];
+        assert_eq![SongDuration::from_parts(90.0, true), SongDuration::Live];
+
+        let song: Song = serde_json::from_value(serde_json::json!({
+            "id": "a",
+            "title": "a",
+            "channel": "a",
+            "thumbnail": "",
+            "webpage_url": "",
+            "duration": null,
+            "is_live": true,
+        }))
+        .unwrap();
+        assert_eq![song.song_duration(), SongDuration::Live];
+    }
+
+    #[test]
+    fn unknown_durations_are_formatted_and_left_out_of_totals() {
+        assert_eq![SongDuration::Known(75.0).format(), "1:15"];
+        assert_eq![SongDuration::Unknown.format(), "--:--"];
+        assert_eq![SongDuration::Live.format(), "LIVE"];
+
+        let (total, unknown) = sum_durations([
+            SongDuration::Known(60.0),
+            SongDuration::Unknown,
+            SongDuration::Known(30.0),
+            SongDuration::Live,
+        ]);
+        assert_eq![(total, unknown), (90.0, 2)];
+        assert_eq![format_total_duration(total, unknown), "1:30 (+2 unknown)"];
+        assert_eq![format_total_duration(90.0, 0), "1:30"];
+    }
+
+    #[test]
+    fn only_saved_fields_tell_songs_apart() {
+        let mut song = Song::basic();
+        song.ui_state = SongState::Fetching;
+        assert![song.saves_as(&Song::basic())];
+        song.rating = Some(3);
+        assert![!song.saves_as(&Song::basic())];
+    }
+}
@@ -0,0 +1,226 @@
+//! Fixing one song's title, artists and album. The song is only changed when the edit is saved,
+//! so cancelling leaves the cache and its file as they were.
+
+use iced::{
+    widget::{button, column, container, row, text, text_input, Space},
+    Alignment, Command, Element, Length,
+};
+
+use crate::{
+    bulk_edit::split_list,
+    settings::SongKey,
+    song::{Song, SongSource},
+};
+
+/// The fields as they are typed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SongEdit {
+    pub title: String,
+    /// Comma separated, cleared when empty
+    pub artists: String,
+    /// Cleared when empty
+    pub album: String,
+}
+
+impl SongEdit {
+    pub fn of(song: &Song) -> Self {
+        Self {
+            title: song.title.clone(),
+            artists: song.artists.clone().unwrap_or_default().join(", "),
+            album: song.album.clone().unwrap_or_default(),
+        }
+    }
+
+    /// Applies the fields to the song. An empty title keeps the old one, songs need a title.
+    /// Returns whether the song changed.
+    pub fn apply(&self, song: &mut Song) -> bool {
+        let title = match self.title.trim() {
+            "" => song.title.clone(),
+            title => title.to_string(),
+        };
+        let artists = Some(split_list(&self.artists)).filter(|a| !a.is_empty());
+        let album = Some(self.album.trim())
+            .filter(|album| !album.is_empty())
+            .map(str::to_string);
+
+        let changed = title != song.title || artists != song.artists || album != song.album;
+        if changed {
+            song.title = title;
+            song.artists = artists;
+            song.album = album;
+            song.user_edited = true;
+        }
+        changed
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum SongEditMsg {
+    Title(String),
+    Artists(String),
+    Album(String),
+    /// Searches for the query the song was found with again
+    Reopen(String),
+    /// Shows the saved songs found in the same place
+    MoreFromSource(SongSource),
+    Save,
+    Cancel,
+}
+
+/// The editor of a single song
+#[derive(Debug, Clone)]
+pub struct SongEditor {
+    pub key: SongKey,
+    pub edit: SongEdit,
+    /// Where the song was found, which can't be edited
+    pub source: SongSource,
+}
+
+fn title_input_id() -> text_input::Id {
+    text_input::Id::new("song_editor_title")
+}
+
+impl SongEditor {
+    /// Opens the editor on the song's current values, focusing the title
+    pub fn open<M: 'static>(song: &Song) -> (Self, Command<M>) {
+        let editor = Self {
+            key: song.id.clone(),
+            edit: SongEdit::of(song),
+            source: song.source.clone(),
+        };
+        (editor, text_input::focus(title_input_id()))
+    }
+
+    pub fn update(&mut self, msg: SongEditMsg) {
+        match msg {
+            SongEditMsg::Title(title) => self.edit.title = title,
+            SongEditMsg::Artists(artists) => self.edit.artists = artists,
+            SongEditMsg::Album(album) => self.edit.album = album,
+            SongEditMsg::Reopen(_)
+            | SongEditMsg::MoreFromSource(_)
+            | SongEditMsg::Save
+            | SongEditMsg::Cancel => {}
+        }
+    }
+
+    pub fn view(&self) -> Element<SongEditMsg> {
+        let field = |label: &'static str,
+                     placeholder: &'static str,
+                     value: &str,
+                     on_input: fn(String) -> SongEditMsg| {
+            row![
+                text(label).width(80),
+                text_input(placeholder, value)
+                    .on_input(on_input)
+                    .on_submit(SongEditMsg::Save),
+            ]
+            .spacing(4)
+            .align_items(Alignment::Center)
+        };
+
+        container(
+            column![
+                row![
+                    text(format!("Edit {}", self.key)).size(20),
+                    Space::with_width(Length::Fill),
+                    button("cancel").on_press(SongEditMsg::Cancel),
+                ]
+                .align_items(Alignment::Center),
+                row![
+                    text("title").width(80),
+                    text_input("title", &self.edit.title)
+                        .id(title_input_id())
+                        .on_input(SongEditMsg::Title)
+                        .on_submit(SongEditMsg::Save),
+                ]
+                .spacing(4)
+                .align_items(Alignment::Center),
+                field(
+                    "artists",
+                    "comma separated",
+                    &self.edit.artists,
+                    SongEditMsg::Artists
+                ),
+                field("album", "none", &self.edit.album, SongEditMsg::Album),
+                row![text("found").width(80), text(self.source.to_string())]
+                    .push_maybe(self.source.reopen_query().map(|query| {
+                        button("open").on_press(SongEditMsg::Reopen(query.to_string()))
+                    }))
+                    .push_maybe((!self.source.is_unknown()).then(|| {
+                        button("more from here")
+                            .on_press(SongEditMsg::MoreFromSource(self.source.clone()))
+                    }))
+                    .spacing(4)
+                    .align_items(Alignment::Center),
+                button("save").on_press(SongEditMsg::Save),
+            ]
+            .spacing(4),
+        )
+        .padding(10)
+        .width(Length::Fill)
+        .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::song::Song;
+
+    use super::{SongEdit, SongEditMsg, SongEditor};
+
+    fn song() -> Song {
+        Song {
+            title: "Song (Official Video) [HD]".into(),
+            channel: "ArtistVEVO".into(),
+            ..Song::basic()
+        }
+    }
+
+    #[test]
+    fn edits_are_applied_on_save_only() {
+        let original = song();
+        let (mut editor, _) = SongEditor::open::<()>(&original);
+        assert_eq![editor.edit.title, original.title];
+        assert_eq![editor.edit.artists, ""];
+
+        editor.update(SongEditMsg::Title(" Song ".into()));
+        editor.update(SongEditMsg::Artists("Artist, Feature,".into()));
+        editor.update(SongEditMsg::Album("Album".into()));
+
+        let mut edited = original.clone();
+        assert![editor.edit.apply(&mut edited)];
+        assert_eq![edited.title, "Song"];
+        assert_eq![
+            edited.artists,
+            Some(vec!["Artist".into(), "Feature".into()])
+        ];
+        assert_eq![edited.album.as_deref(), Some("Album")];
+        assert![edited.user_edited];
+
+        // Opening the editor on the edited song and saving it as-is changes nothing
+        assert![!SongEdit::of(&edited).apply(&mut edited)];
+
+        // An empty title is kept, the other fields are cleared
+        let cleared = SongEdit::default();
+        assert![cleared.apply(&mut edited)];
+        assert_eq![edited.title, "Song"];
+        assert_eq![(edited.artists, edited.album), (None, None)];
+    }
+
+    #[test]
+    fn edited_titles_survive_refreshes() {
+        let mut edited = song();
+        SongEdit {
+            title: "Song".into(),
+            ..Default::default()
+        }
+        .apply(&mut edited);
+
+        let mut refreshed = Song {
+            id: edited.id.clone(),
+            ..song()
+        };
+        refreshed.preserve_user_fields(&edited);
+        assert_eq![refreshed.title, "Song"];
+    }
+}
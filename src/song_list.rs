@@ -1,44 +1,169 @@
-use serde::{Deserialize, Serialize};
+//! Lists that only build the rows around the part of them that's on screen.
+//!
+//! Iced's scrollables build every row of their content on every view, so a list of a thousand
+//! songs makes the whole app crawl. These lists build the rows near the screen, and stand in
+//! for the rest with empty space of their height, so the scrollbar still measures the whole
+//! list. The rows keep their index in the full list, for the messages they send.
 
-// Since Iced's Scrollable component gets *real* laggy with big lists,
-// we use one with pages to maintain performance.
+use std::{
+    ops::Range,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
-// pub enum PSLMessage {
-//     NextPage,
-//     PrevPage,
-// }
+use iced::{
+    widget::{Column, Space},
+    Element,
+};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct PagedSongList<T> {
-    pub songs: Vec<T>,
-    song_per_page: usize,
-    current_index: usize,
+use crate::thumbnails::{OnScreen, UNSCROLLED_ROWS};
+
+/// The height of a song's row, that of its thumbnail
+pub const ROW_HEIGHT: f32 = 80.0;
+/// What a group's header is taken to be, it's only ever guessed
+pub const HEADER_HEIGHT: f32 = 40.0;
+/// How far past the screen rows are built, so they're there by the time they scroll in
+pub const MARGIN: f32 = 10.0 * ROW_HEIGHT;
+/// How much is taken to be on screen before the list was first scrolled
+const UNSCROLLED_HEIGHT: f32 = UNSCROLLED_ROWS as f32 * ROW_HEIGHT;
+
+/// Counts the rows built since the count was last taken
+static BUILT_ROWS: AtomicUsize = AtomicUsize::new(0);
+
+/// How many rows were built since this was last called, to see what a view costs
+pub fn take_built_rows() -> usize {
+    BUILT_ROWS.swap(0, Ordering::Relaxed)
+}
+
+/// The part of a list that rows are built for, in pixels from its top
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub start: f32,
+    pub end: f32,
+}
+
+impl Span {
+    /// What's on screen of a list that's `height` tall, and the margin around it
+    pub fn of(on_screen: &OnScreen, height: f32) -> Self {
+        match on_screen.fractions() {
+            Some((start, end)) => Self {
+                start: start * height - MARGIN,
+                end: end * height + MARGIN,
+            },
+            None => Self {
+                start: 0.0,
+                end: UNSCROLLED_HEIGHT + MARGIN,
+            },
+        }
+    }
+
+    /// The span from the point of view of something that starts `top` pixels down
+    pub fn below(&self, top: f32) -> Self {
+        Self {
+            start: self.start - top,
+            end: self.end - top,
+        }
+    }
+}
+
+/// The rows of a list to build, and the space taken by those before and after them
+#[derive(Debug, Clone, PartialEq)]
+pub struct Window {
+    pub rows: Range<usize>,
+    pub before: f32,
+    pub after: f32,
+}
+
+/// Which rows of a list with these heights are in the span
+pub fn window(heights: &[f32], span: Span) -> Window {
+    let mut top = 0.0;
+    let mut rows = heights.len()..heights.len();
+    let mut before = 0.0;
+    for (idx, height) in heights.iter().enumerate() {
+        let bottom = top + height;
+        if bottom > span.start && top < span.end {
+            if rows.start == heights.len() {
+                rows.start = idx;
+                before = top;
+            }
+            rows.end = idx + 1;
+        }
+        top = bottom;
+    }
+    if rows.is_empty() {
+        // Nothing is in the span, it's all space
+        return Window {
+            rows: 0..0,
+            before: top,
+            after: 0.0,
+        };
+    }
+    let built: f32 = heights[rows.clone()].iter().sum();
+    Window {
+        after: top - before - built,
+        rows,
+        before,
+    }
+}
+
+/// A column of rows, where only those in the span are built with `row`. It's given each
+/// row's index and the span as seen from the row, so rows that are lists can window theirs.
+pub fn windowed<'a, M: 'a>(
+    heights: &[f32],
+    span: Span,
+    mut row: impl FnMut(usize, Span) -> Element<'a, M>,
+) -> Column<'a, M> {
+    let window = window(heights, span);
+    BUILT_ROWS.fetch_add(window.rows.len(), Ordering::Relaxed);
+    let mut top = window.before;
+    let mut column = Column::new().push(Space::with_height(window.before));
+    for idx in window.rows {
+        column = column.push(row(idx, span.below(top)));
+        top += heights[idx];
+    }
+    column.push(Space::with_height(window.after))
 }
 
-// impl<T> Default for PagedSongList<T> {
-//     fn default() -> Self {
-//         Self {
-//             songs: Default::default(),
-//             song_per_page: 25,
-//             current_index: 0,
-//         }
-//     }
-// }
-
-// impl<T> PagedSongList<T> {
-//     pub fn get_current_page(&self) -> std::iter::Take<std::slice::Iter<'_, T>> {
-//         self.songs[(self.current_index * self.song_per_page)..]
-//             .into_iter()
-//             .take(self.song_per_page)
-//     }
-
-//     pub fn clear(&mut self) {
-//         self.songs.clear();
-//     }
-
-//     pub fn push(&mut self, obj: T) {
-//         self.songs.push(obj);
-//     }
-
-//     pub fn view(&self, scheme: &FullYtmrsScheme) -> Element {}
-// }
+#[cfg(test)]
+mod tests {
+    use iced::{widget::Space, Element};
+
+    use super::{window, windowed, Span, Window, ROW_HEIGHT};
+
+    #[test]
+    fn only_rows_in_the_span_are_built() {
+        let heights = [ROW_HEIGHT; 1000];
+        let span = Span {
+            start: 500.0 * ROW_HEIGHT,
+            end: 510.0 * ROW_HEIGHT,
+        };
+        let mut built = vec![];
+        let _: Element<()> = windowed(&heights, span, |idx, span| {
+            built.push((idx, span.start));
+            Space::with_height(ROW_HEIGHT).into()
+        })
+        .into();
+        assert_eq![built.len(), 10];
+        // Rows keep their index in the list, and see the span from their own top
+        assert_eq![built[0], (500, 0.0)];
+        assert_eq![built[9], (509, -9.0 * ROW_HEIGHT)];
+    }
+
+    #[test]
+    fn the_space_around_the_rows_adds_up_to_the_list() {
+        let heights = [10.0, 20.0, 30.0, 40.0];
+        let window_of = |start, end| window(&heights, Span { start, end });
+        assert_eq![
+            window_of(15.0, 35.0),
+            Window {
+                rows: 1..3,
+                before: 10.0,
+                after: 40.0,
+            }
+        ];
+        // Rows only touching the span aren't built
+        assert_eq![window_of(30.0, 60.0).rows, 2..3];
+        assert_eq![window_of(-100.0, 1000.0).rows, 0..4];
+        let past = window_of(200.0, 300.0);
+        assert_eq![(past.rows, past.before, past.after), (0..0, 100.0, 0.0)];
+    }
+}
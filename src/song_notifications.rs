@@ -0,0 +1,121 @@
+//! A desktop notification for the song that started while the window was in the background.
+//! Each waits a moment first, so only the song skipping stopped at is shown. Built without the
+//! `song-notifications` feature, nothing is shown.
+
+use std::{path::PathBuf, time::Duration};
+
+#[cfg(feature = "song-notifications")]
+use notify_rust::Notification;
+
+use crate::{song::Song, thumbnails::saved_path};
+
+/// How long a song plays before it's notified
+pub const NOTIFY_DELAY: Duration = Duration::from_millis(1500);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SongNotice {
+    pub title: String,
+    pub artist: String,
+    /// The thumbnail, if it was saved
+    pub cover: Option<PathBuf>,
+}
+
+impl SongNotice {
+    pub fn new(song: &Song) -> Self {
+        Self {
+            title: song.title.clone(),
+            artist: match &song.artists {
+                Some(artists) if !artists.is_empty() => artists.join(", "),
+                _ => song.channel.clone(),
+            },
+            cover: song.thumbnail_handle.as_ref().and_then(saved_path),
+        }
+    }
+}
+
+/// The notice waiting for its song to have played for a moment
+#[derive(Debug, Default)]
+pub struct SongNotifier {
+    pending: Option<(u64, SongNotice)>,
+    next_id: u64,
+}
+
+impl SongNotifier {
+    /// Replaces the waiting notice. The id is brought back to [`SongNotifier::take`] once the
+    /// delay is over.
+    pub fn queue(&mut self, notice: SongNotice) -> u64 {
+        self.next_id += 1;
+        self.pending = Some((self.next_id, notice));
+        self.next_id
+    }
+
+    /// The notice, unless another song started since it was queued
+    pub fn take(&mut self, id: u64) -> Option<SongNotice> {
+        match self.pending.take() {
+            Some((pending, notice)) if pending == id => Some(notice),
+            pending => {
+                self.pending = pending;
+                None
+            }
+        }
+    }
+
+    pub fn cancel(&mut self) {
+        self.pending = None;
+    }
+}
+
+pub async fn wait(id: u64) -> u64 {
+    async_std::task::sleep(NOTIFY_DELAY).await;
+    id
+}
+
+/// Shows the notice. The notification daemon is called on a blocking thread, since it can be
+/// slow to answer.
+#[cfg(feature = "song-notifications")]
+pub async fn show(notice: SongNotice) -> Result<(), String> {
+    async_std::task::spawn_blocking(move || {
+        let mut notification = Notification::new();
+        notification
+            .appname("ytm-rs")
+            .summary(&notice.title)
+            .body(&notice.artist);
+        if let Some(cover) = &notice.cover {
+            notification.icon(&cover.to_string_lossy());
+        }
+        notification.show().map(|_| ()).map_err(|e| e.to_string())
+    })
+    .await
+}
+
+#[cfg(not(feature = "song-notifications"))]
+pub async fn show(_notice: SongNotice) -> Result<(), String> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SongNotice, SongNotifier};
+
+    fn notice(title: &str) -> SongNotice {
+        SongNotice {
+            title: title.into(),
+            artist: "Someone".into(),
+            cover: None,
+        }
+    }
+
+    #[test]
+    fn only_the_last_song_is_notified() {
+        let mut notifier = SongNotifier::default();
+        let first = notifier.queue(notice("first"));
+        let second = notifier.queue(notice("second"));
+        assert_eq![notifier.take(first), None];
+        assert_eq![notifier.take(second), Some(notice("second"))];
+        assert_eq![notifier.take(second), None];
+
+        let third = notifier.queue(notice("third"));
+        notifier.cancel();
+        assert_eq![notifier.take(third), None];
+    }
+}
@@ -1,7 +1,8 @@
-mod recursive_song_op;
+pub mod album;
+pub mod simple;
 mod song_op_constructor;
-mod song_op_tracker;
+pub mod tree_diff;
+pub mod tree_filter;
 
-pub use recursive_song_op::*;
 pub use song_op_constructor::*;
-pub use song_op_tracker::*;
+pub use ytm_rs_core::playback::*;
@@ -0,0 +1,296 @@
+//! Treats the group holding the playing song as an album: where the song is in it, and jumping
+//! between groups by stepping the tracker, so loops and shuffles are respected on the way.
+
+use crate::{
+    settings::SongKey,
+    song::{format_total_duration, sum_durations, SongDuration},
+};
+
+use super::{BackResult, ConstructorItem, NextResult, OperationTracker, SongOpConstructor};
+
+/// How many songs a jump may step over before giving up, for groups that never end
+const MAX_STEPS: usize = 10_000;
+
+/// Where the playing song is in its group
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlbumPosition {
+    /// Starting at 0
+    pub track: usize,
+    pub count: usize,
+    /// The known lengths of the songs before this one, plus how far into this one it is
+    pub elapsed: f64,
+    /// How many of the songs before this one have an unknown length
+    pub unknown: usize,
+}
+impl AlbumPosition {
+    /// e.g. "track 4 of 12, 12:34 in"
+    pub fn describe(&self) -> String {
+        format!(
+            "track {} of {}, {} in",
+            self.track + 1,
+            self.count,
+            format_total_duration(self.elapsed, self.unknown)
+        )
+    }
+}
+
+/// The path of the group the song at the path is in.
+/// Songs at the top of the playlist aren't in one.
+pub fn parent_group(path: &[usize]) -> Option<&[usize]> {
+    match path.len() {
+        0 | 1 => None,
+        len => Some(&path[..len - 1]),
+    }
+}
+
+/// What a group skip moves away from: the song's group, or the song itself at the top
+pub fn current_unit(path: &[usize]) -> &[usize] {
+    parent_group(path).unwrap_or(path)
+}
+
+/// Where the song at the path is in its group, if the group holds more than one song
+pub fn album_position(
+    root: &SongOpConstructor,
+    path: &[usize],
+    elapsed: f64,
+    duration: impl Fn(&SongKey) -> SongDuration,
+) -> Option<AlbumPosition> {
+    let group = root.group_at_path(parent_group(path)?)?;
+    let count = group.all_song_keys_rec().count();
+    if count < 2 {
+        return None;
+    }
+    let before: Vec<&SongKey> = group.song_keys_before(*path.last()?).collect();
+    let (total, unknown) = sum_durations(before.iter().map(|key| duration(key)));
+    Some(AlbumPosition {
+        track: before.len(),
+        count,
+        elapsed: total + elapsed,
+        unknown,
+    })
+}
+
+/// The path of the closest group with songs after (or before) the unit, next to it in its parent.
+/// Groups that are off are passed over.
+pub fn sibling_group(
+    root: &SongOpConstructor,
+    unit: &[usize],
+    forward: bool,
+) -> Option<Vec<usize>> {
+    let (idx, parent_path) = unit.split_last()?;
+    let parent = root.group_at_path(parent_path)?;
+    let has_songs = |(_, item): &(usize, &ConstructorItem)| match item {
+        ConstructorItem::Operation(op) => {
+            op.is_enabled() && op.all_song_keys_rec().next().is_some()
+        }
+        ConstructorItem::Song(_, _) => false,
+    };
+    let items = parent.list.iter().enumerate();
+    let (found, _) = match forward {
+        true => items.skip(idx + 1).find(has_songs),
+        false => items.take(*idx).rev().find(has_songs),
+    }?;
+    let mut path = parent_path.to_vec();
+    path.push(found);
+    Some(path)
+}
+
+fn current<T: OperationTracker>(tracker: &T) -> Vec<usize> {
+    tracker.get_current().collect()
+}
+
+/// Steps forward until the tracker is inside the group.
+/// It is left where it was if the queue ends or it gives up first.
+pub fn forward_into<T: OperationTracker + Clone>(tracker: &mut T, group: &[usize]) -> bool {
+    let mut probe = tracker.clone();
+    for _ in 0..MAX_STEPS {
+        if probe.move_next() == NextResult::Ended {
+            return false;
+        }
+        if current(&probe).starts_with(group) {
+            *tracker = probe;
+            return true;
+        }
+    }
+    false
+}
+
+/// Steps back until the tracker is at the start of the group.
+/// It is left where it was if the queue can't go back that far.
+pub fn back_into<T: OperationTracker + Clone>(tracker: &mut T, group: &[usize]) -> bool {
+    let mut probe = tracker.clone();
+    for _ in 0..MAX_STEPS {
+        if probe.move_back() == BackResult::Rewound {
+            return false;
+        }
+        if current(&probe).starts_with(group) {
+            rewind_to_start(&mut probe, group);
+            *tracker = probe;
+            return true;
+        }
+    }
+    false
+}
+
+/// Steps back for as long as the previous song is still inside the group
+pub fn rewind_to_start<T: OperationTracker + Clone>(tracker: &mut T, group: &[usize]) {
+    for _ in 0..MAX_STEPS {
+        let mut probe = tracker.clone();
+        if probe.move_back() == BackResult::Rewound || !current(&probe).starts_with(group) {
+            return;
+        }
+        *tracker = probe;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{
+        settings::SongKey,
+        song::SongDuration,
+        song_operations::{
+            ActualRecursiveOps, ConstructorItem, OperationTracker, SongOpConstructor, SongOpTracker,
+        },
+    };
+
+    use super::{
+        album_position, back_into, current, current_unit, forward_into, parent_group,
+        rewind_to_start, sibling_group, AlbumPosition,
+    };
+
+    fn song(key: &str) -> ConstructorItem {
+        ConstructorItem::from(key.to_string())
+    }
+
+    fn group(op: ActualRecursiveOps, items: Vec<ConstructorItem>) -> ConstructorItem {
+        ConstructorItem::Operation(SongOpConstructor::new(op, items, None))
+    }
+
+    /// a, [b, c, [d, e], f], g, [h]
+    fn tree() -> SongOpConstructor {
+        SongOpConstructor::from(vec![
+            song("a"),
+            group(
+                ActualRecursiveOps::PlayOnce,
+                vec![
+                    song("b"),
+                    song("c"),
+                    group(ActualRecursiveOps::PlayOnce, vec![song("d"), song("e")]),
+                    song("f"),
+                ],
+            ),
+            song("g"),
+            group(ActualRecursiveOps::PlayOnce, vec![song("h")]),
+        ])
+    }
+
+    fn tracker_at(tree: &SongOpConstructor, path: Vec<usize>) -> SongOpTracker {
+        SongOpTracker::from_song_op(&tree.build(), path.into())
+    }
+
+    #[test]
+    fn positions_come_from_the_nearest_group() {
+        assert_eq![parent_group(&[1, 2, 0]), Some(&[1, 2][..])];
+        assert_eq![parent_group(&[2]), None];
+        assert_eq![current_unit(&[1, 3]), &[1]];
+        assert_eq![current_unit(&[2]), &[2]];
+
+        let durations: HashMap<SongKey, SongDuration> = [
+            ("b", SongDuration::Known(100.0)),
+            ("c", SongDuration::Live),
+            ("d", SongDuration::Known(50.0)),
+            ("e", SongDuration::Known(25.0)),
+        ]
+        .into_iter()
+        .map(|(k, d)| (k.to_string(), d))
+        .collect();
+        let duration = |key: &SongKey| durations.get(key).copied().unwrap_or(SongDuration::Unknown);
+        let tree = tree();
+
+        // Songs in nested groups count towards the outer group
+        assert_eq![
+            album_position(&tree, &[1, 3], 10.0, duration),
+            Some(AlbumPosition {
+                track: 4,
+                count: 5,
+                elapsed: 185.0,
+                unknown: 1,
+            })
+        ];
+        assert_eq![
+            album_position(&tree, &[1, 2, 1], 10.0, duration),
+            Some(AlbumPosition {
+                track: 1,
+                count: 2,
+                elapsed: 60.0,
+                unknown: 0,
+            })
+        ];
+        let first = album_position(&tree, &[1, 0], 0.0, duration).unwrap();
+        assert_eq![first.describe(), "track 1 of 5, 0:00 in"];
+
+        // The top of the playlist and groups of one aren't albums
+        assert_eq![album_position(&tree, &[2], 10.0, duration), None];
+        assert_eq![album_position(&tree, &[3, 0], 10.0, duration), None];
+        // Nor are paths that don't exist
+        assert_eq![album_position(&tree, &[0, 0], 10.0, duration), None];
+        assert_eq![album_position(&tree, &[9, 0], 10.0, duration), None];
+    }
+
+    #[test]
+    fn jumps_land_on_the_first_song_of_the_next_group() {
+        let tree = tree();
+        assert_eq![sibling_group(&tree, &[0], true), Some(vec![1])];
+        assert_eq![sibling_group(&tree, &[1], true), Some(vec![3])];
+        assert_eq![sibling_group(&tree, &[3], true), None];
+        assert_eq![sibling_group(&tree, &[2], false), Some(vec![1])];
+        assert_eq![sibling_group(&tree, &[1, 1], true), Some(vec![1, 2])];
+
+        let mut tracker = tracker_at(&tree, vec![1, 1]);
+        assert![forward_into(&mut tracker, &[3])];
+        assert_eq![current(&tracker), vec![3, 0]];
+
+        // Stepping back into a group ends on its first song, even through a nested group
+        assert![back_into(&mut tracker, &[1])];
+        assert_eq![current(&tracker), vec![1, 0]];
+
+        let mut tracker = tracker_at(&tree, vec![1, 2, 1]);
+        rewind_to_start(&mut tracker, &[1]);
+        assert_eq![current(&tracker), vec![1, 0]];
+        // Nothing before the first song
+        assert![!back_into(&mut tracker, &[3])];
+        assert_eq![current(&tracker), vec![1, 0]];
+    }
+
+    #[test]
+    fn loops_are_stepped_through_and_never_ending_groups_give_up() {
+        let tree = SongOpConstructor::from(vec![
+            ConstructorItem::Operation(
+                SongOpConstructor::new(
+                    ActualRecursiveOps::LoopNTimes,
+                    vec![song("a"), song("b")],
+                    None,
+                )
+                .with_n(2),
+            ),
+            group(ActualRecursiveOps::PlayOnce, vec![song("c"), song("d")]),
+        ]);
+        let mut tracker = tracker_at(&tree, vec![0, 0]);
+        let mut stepped = tracker.clone();
+        for _ in 0..4 {
+            stepped.move_next();
+        }
+        assert![forward_into(&mut tracker, &[1])];
+        assert_eq![current(&tracker), current(&stepped)];
+
+        let tree = SongOpConstructor::from(vec![
+            group(ActualRecursiveOps::InfiniteLoop, vec![song("a"), song("b")]),
+            group(ActualRecursiveOps::PlayOnce, vec![song("c"), song("d")]),
+        ]);
+        let mut tracker = tracker_at(&tree, vec![0, 1]);
+        assert![!forward_into(&mut tracker, &[1])];
+        assert_eq![current(&tracker), vec![0, 1]];
+    }
+}
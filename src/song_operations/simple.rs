@@ -0,0 +1,399 @@
+//! Simple mode: the playlist as a flat queue of songs, with a shuffle and a repeat toggle
+//! instead of groups. It's a view over the root of the tree, so switching to advanced mode
+//! shows the same tree, and the toggles only change how the queue is built when it plays.
+
+use iced::{
+    advanced::widget::Id as WId,
+    widget::{button, column, container, row, text, Column},
+    Alignment, Element, Length,
+};
+
+use crate::{song::SongMessage, styling::FullYtmrsScheme};
+
+use super::{ActualRecursiveOps, ConstructorItem, SimpleMode, SongOpConstructor, TreeDirected};
+
+/// Whether simple mode can edit the tree: only songs, played once, at the root
+pub fn is_flat(tree: &SongOpConstructor) -> bool {
+    tree.play.operation == ActualRecursiveOps::PlayOnce
+        && tree
+            .list
+            .iter()
+            .all(|item| matches!(item, ConstructorItem::Song(_, _)))
+}
+
+/// Replaces every group with its songs, keeping their order
+pub fn flatten(tree: &mut SongOpConstructor) {
+    while let Some(idx) = tree
+        .list
+        .iter()
+        .position(|item| matches!(item, ConstructorItem::Operation(_)))
+    {
+        tree.flatten_group(vec![idx]);
+    }
+    tree.play.operation = ActualRecursiveOps::PlayOnce;
+    tree.edited();
+}
+
+#[derive(Debug, Clone)]
+pub enum SimpleMsg {
+    ToggleMode,
+    Play(WId),
+    Remove(usize),
+    MoveUp(usize),
+    MoveDown(usize),
+    ToggleShuffle,
+    ToggleRepeat,
+    AskFlatten,
+    ConfirmFlatten,
+    CancelFlatten,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SimpleResult {
+    Play(WId),
+    /// The queue changed under the playing song, so its tracker has to be rebuilt at this path
+    Requeue(Vec<usize>),
+}
+
+/// The state of the simple view that isn't saved
+#[derive(Debug, Default)]
+pub struct SimpleQueue {
+    confirming_flatten: bool,
+}
+
+impl SimpleQueue {
+    pub fn update(
+        &mut self,
+        tree: &mut SongOpConstructor,
+        mode: &mut SimpleMode,
+        playing: Option<Vec<usize>>,
+        msg: SimpleMsg,
+    ) -> Option<SimpleResult> {
+        // Edits are only made to flat trees, the rest are shown read-only
+        let editable = is_flat(tree);
+        match msg {
+            SimpleMsg::ToggleMode => {
+                mode.enabled = !mode.enabled;
+                self.confirming_flatten = false;
+                playing.map(SimpleResult::Requeue)
+            }
+            SimpleMsg::Play(wid) => Some(SimpleResult::Play(wid)),
+            SimpleMsg::Remove(idx) if editable && idx < tree.list.len() => {
+                tree.pop_path([idx].into());
+                match playing?.as_slice() {
+                    [current] if *current > idx => Some(SimpleResult::Requeue(vec![current - 1])),
+                    _ => None,
+                }
+            }
+            SimpleMsg::MoveUp(idx) if editable && idx > 0 && idx < tree.list.len() => {
+                Self::swap(tree, idx - 1, playing)
+            }
+            SimpleMsg::MoveDown(idx) if editable && idx + 1 < tree.list.len() => {
+                Self::swap(tree, idx, playing)
+            }
+            SimpleMsg::Remove(_) | SimpleMsg::MoveUp(_) | SimpleMsg::MoveDown(_) => None,
+            SimpleMsg::ToggleShuffle => {
+                mode.shuffle = !mode.shuffle;
+                playing.map(SimpleResult::Requeue)
+            }
+            SimpleMsg::ToggleRepeat => {
+                mode.repeat = !mode.repeat;
+                playing.map(SimpleResult::Requeue)
+            }
+            SimpleMsg::AskFlatten => {
+                self.confirming_flatten = !editable;
+                None
+            }
+            SimpleMsg::CancelFlatten => {
+                self.confirming_flatten = false;
+                None
+            }
+            SimpleMsg::ConfirmFlatten => {
+                if !self.confirming_flatten {
+                    return None;
+                }
+                self.confirming_flatten = false;
+                // The playing song keeps its place among the others
+                let flat_index = playing.and_then(|path| {
+                    let key = match tree.item_at_path(path.into())? {
+                        ConstructorItem::Song(_, sid) => WId::from(sid.0.clone()),
+                        ConstructorItem::Operation(_) => return None,
+                    };
+                    tree.songs_with_ids()
+                        .iter()
+                        .position(|(_, wid)| *wid == key)
+                });
+                flatten(tree);
+                flat_index.map(|idx| SimpleResult::Requeue(vec![idx]))
+            }
+        }
+    }
+
+    /// Swaps the song at `first` with the one after it
+    fn swap(
+        tree: &mut SongOpConstructor,
+        first: usize,
+        playing: Option<Vec<usize>>,
+    ) -> Option<SimpleResult> {
+        if let Some(item) = tree.pop_path([first + 1].into()) {
+            tree.push_to_path([first].into(), item);
+        }
+        match playing?.as_slice() {
+            [current] if *current == first => Some(SimpleResult::Requeue(vec![first + 1])),
+            [current] if *current == first + 1 => Some(SimpleResult::Requeue(vec![first])),
+            _ => None,
+        }
+    }
+
+    pub fn view<'a>(
+        &'a self,
+        tree: &'a SongOpConstructor,
+        mode: &SimpleMode,
+        scheme: &FullYtmrsScheme,
+        focused: Option<&WId>,
+    ) -> Element<'a, SimpleMsg> {
+        let toggle = |label: &str, on: bool, msg: SimpleMsg| {
+            let state = match on {
+                true => "on",
+                false => "off",
+            };
+            button(text(format!("{label}: {state}"))).on_press(msg)
+        };
+        let controls = row![
+            toggle("shuffle", mode.shuffle, SimpleMsg::ToggleShuffle),
+            toggle("repeat", mode.repeat, SimpleMsg::ToggleRepeat),
+        ]
+        .spacing(4);
+
+        let editable = is_flat(tree);
+        let notice = (!editable).then(|| match self.confirming_flatten {
+            false => column![
+                text("This playlist has groups. It's shown flat here, but can't be edited."),
+                row![
+                    button("flatten it").on_press(SimpleMsg::AskFlatten),
+                    button("switch to advanced mode").on_press(SimpleMsg::ToggleMode),
+                ]
+                .spacing(4),
+            ]
+            .spacing(4),
+            true => column![
+                text("Every group will be removed, keeping the songs in order."),
+                row![
+                    button("flatten").on_press(SimpleMsg::ConfirmFlatten),
+                    button("cancel").on_press(SimpleMsg::CancelFlatten),
+                ]
+                .spacing(4),
+            ]
+            .spacing(4),
+        });
+
+        let songs = tree.songs_with_ids();
+        let last = songs.len().saturating_sub(1);
+        let rows = songs.into_iter().enumerate().map(|(idx, (key, wid))| {
+            let play = wid.clone();
+            let song = Element::new(tree.cached_song_data(key).row(true, true))
+                .map(move |_: SongMessage| SimpleMsg::Play(play.clone()));
+            let edits = editable.then(|| {
+                row![
+                    button("^").on_press_maybe((idx > 0).then_some(SimpleMsg::MoveUp(idx))),
+                    button("v").on_press_maybe((idx < last).then_some(SimpleMsg::MoveDown(idx))),
+                    button("x").on_press(SimpleMsg::Remove(idx)),
+                ]
+            });
+            let style = scheme
+                .focus_style
+                .apply(Default::default(), focused == Some(&wid));
+            let item = match &tree.list.get(idx) {
+                Some(ConstructorItem::Song(_, sid)) if editable => Some(sid.0.clone()),
+                _ => None,
+            };
+            // Rows of editable songs are drop targets, like they are in the tree
+            let row = container(row![song].push_maybe(edits).align_items(Alignment::Center))
+                .style(move |_| style);
+            match item {
+                Some(id) => row.id(id).into(),
+                None => row.into(),
+            }
+        });
+
+        column![controls]
+            .push_maybe(notice)
+            .push(Column::with_children(rows))
+            .spacing(8)
+            .width(Length::Fill)
+            .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use crate::song_operations::{
+        ActualRecursiveOps, ConstructorItem, OperationTracker, RecursiveSongOp, SongOpConstructor,
+        SongOpTracker, TreeDirected,
+    };
+
+    use super::{flatten, is_flat, SimpleMode, SimpleMsg, SimpleQueue, SimpleResult};
+
+    fn song(key: &str) -> ConstructorItem {
+        ConstructorItem::from(key.to_string())
+    }
+
+    fn keys(tree: &SongOpConstructor) -> Vec<&str> {
+        tree.all_song_keys_rec().map(String::as_str).collect()
+    }
+
+    fn flat(keys: &[&str]) -> SongOpConstructor {
+        SongOpConstructor::from(keys.iter().map(|key| song(key)).collect::<Vec<_>>())
+    }
+
+    fn nested() -> SongOpConstructor {
+        SongOpConstructor::from(vec![
+            song("a"),
+            ConstructorItem::Operation(SongOpConstructor::new(
+                ActualRecursiveOps::LoopNTimes,
+                vec![
+                    song("b"),
+                    ConstructorItem::Operation(SongOpConstructor::new(
+                        ActualRecursiveOps::PlayOnce,
+                        vec![song("c")],
+                        None,
+                    )),
+                ],
+                None,
+            )),
+            song("d"),
+        ])
+    }
+
+    #[test]
+    fn toggles_change_the_queue_but_not_the_tree() {
+        let tree = flat(&["a", "b", "c"]);
+        let before = serde_json::to_string(&tree).unwrap();
+        let mut mode = SimpleMode::fresh();
+        let mut queue = SimpleQueue::default();
+        let mut tree2 = tree.clone();
+
+        assert![
+            matches!(mode.build(tree.build()), RecursiveSongOp::PlayOnce(ops) if ops.len() == 3)
+        ];
+        queue.update(&mut tree2, &mut mode, None, SimpleMsg::ToggleShuffle);
+        assert![matches!(
+            mode.build(tree.build()),
+            RecursiveSongOp::RandomPlay(_)
+        )];
+        let result = queue.update(
+            &mut tree2,
+            &mut mode,
+            Some(vec![1]),
+            SimpleMsg::ToggleRepeat,
+        );
+        assert_eq![result, Some(SimpleResult::Requeue(vec![1]))];
+        assert![matches!(
+            mode.build(tree.build()),
+            RecursiveSongOp::InfiniteRandom(_)
+        )];
+        queue.update(&mut tree2, &mut mode, None, SimpleMsg::ToggleShuffle);
+        assert![matches!(
+            mode.build(tree.build()),
+            RecursiveSongOp::InfiniteLoop(_)
+        )];
+        assert_eq![serde_json::to_string(&tree2).unwrap(), before];
+
+        // A shuffled queue still plays from the clicked song
+        mode.shuffle = true;
+        let tracker = SongOpTracker::from_song_op(&mode.build(tree.build()), VecDeque::from([2]));
+        assert_eq![tracker.get_current().collect::<Vec<_>>(), vec![2]];
+
+        // Advanced mode plays the tree as it is, and the tree is untouched by the switch
+        queue.update(&mut tree2, &mut mode, None, SimpleMsg::ToggleMode);
+        assert![!mode.enabled];
+        assert![matches!(
+            mode.build(tree.build()),
+            RecursiveSongOp::PlayOnce(_)
+        )];
+        assert_eq![serde_json::to_string(&tree2).unwrap(), before];
+
+        // Fresh installs start in simple mode, settings from before it stay advanced
+        assert![SimpleMode::fresh().enabled];
+        let old: crate::settings::YTMRUserSettings =
+            serde_json::from_str(r#"{"volume": 0.5}"#).unwrap();
+        assert![!old.simple.enabled];
+        assert![crate::settings::YTMRUserSettings::default().simple.enabled];
+    }
+
+    #[test]
+    fn edits_map_onto_the_tree() {
+        let mut tree = flat(&["a", "b", "c", "d"]);
+        let mut mode = SimpleMode::fresh();
+        let mut queue = SimpleQueue::default();
+        let mut update = |tree: &mut SongOpConstructor, playing: Option<usize>, msg| {
+            queue.update(tree, &mut mode, playing.map(|idx| vec![idx]), msg)
+        };
+
+        // The playing song is followed when it moves, or when a song before it does
+        let result = update(&mut tree, Some(1), SimpleMsg::MoveDown(1));
+        assert_eq![result, Some(SimpleResult::Requeue(vec![2]))];
+        assert_eq![keys(&tree), ["a", "c", "b", "d"]];
+        let result = update(&mut tree, Some(0), SimpleMsg::MoveUp(1));
+        assert_eq![result, Some(SimpleResult::Requeue(vec![1]))];
+        assert_eq![keys(&tree), ["c", "a", "b", "d"]];
+        let result = update(&mut tree, Some(3), SimpleMsg::Remove(0));
+        assert_eq![result, Some(SimpleResult::Requeue(vec![2]))];
+        assert_eq![keys(&tree), ["a", "b", "d"]];
+        assert_eq![update(&mut tree, Some(0), SimpleMsg::Remove(2)), None];
+        assert_eq![keys(&tree), ["a", "b"]];
+
+        // Nothing moves past the ends
+        assert_eq![update(&mut tree, None, SimpleMsg::MoveUp(0)), None];
+        assert_eq![update(&mut tree, None, SimpleMsg::MoveDown(1)), None];
+        assert_eq![update(&mut tree, None, SimpleMsg::Remove(2)), None];
+        assert_eq![keys(&tree), ["a", "b"]];
+
+        // Trees with groups are read-only
+        let mut tree = nested();
+        assert![!is_flat(&tree)];
+        update(&mut tree, None, SimpleMsg::Remove(0));
+        update(&mut tree, None, SimpleMsg::MoveDown(0));
+        assert_eq![keys(&tree), ["a", "b", "c", "d"]];
+    }
+
+    #[test]
+    fn flattening_asks_first() {
+        let mut tree = nested();
+        let mut mode = SimpleMode::fresh();
+        let mut queue = SimpleQueue::default();
+
+        // Not without asking, and not after cancelling
+        assert_eq![
+            queue.update(&mut tree, &mut mode, None, SimpleMsg::ConfirmFlatten),
+            None
+        ];
+        queue.update(&mut tree, &mut mode, None, SimpleMsg::AskFlatten);
+        assert![queue.confirming_flatten];
+        queue.update(&mut tree, &mut mode, None, SimpleMsg::CancelFlatten);
+        queue.update(&mut tree, &mut mode, None, SimpleMsg::ConfirmFlatten);
+        assert![!is_flat(&tree)];
+
+        // The playing song is followed to its place in the flat list
+        queue.update(&mut tree, &mut mode, None, SimpleMsg::AskFlatten);
+        let playing = Some(vec![1, 1, 0]);
+        let result = queue.update(&mut tree, &mut mode, playing, SimpleMsg::ConfirmFlatten);
+        assert_eq![result, Some(SimpleResult::Requeue(vec![2]))];
+        assert![is_flat(&tree)];
+        assert_eq![keys(&tree), ["a", "b", "c", "d"]];
+        assert![!queue.confirming_flatten];
+
+        // Flat trees have nothing to flatten
+        queue.update(&mut tree, &mut mode, None, SimpleMsg::AskFlatten);
+        assert![!queue.confirming_flatten];
+
+        // Looping roots count as structure too
+        let mut looped = flat(&["a"]);
+        looped.play.operation = ActualRecursiveOps::InfiniteLoop;
+        assert![!is_flat(&looped)];
+        flatten(&mut looped);
+        assert![is_flat(&looped)];
+    }
+}
@@ -1,65 +1,40 @@
 use std::{
     collections::{HashMap, HashSet, VecDeque},
-    sync::Arc,
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
 };
 
 use iced::{
     advanced::widget::Id as WId,
     alignment::Vertical,
     widget::{
-        button, column, container, pick_list, row, text, text_input, Column, Container, Row, Space,
+        button, checkbox, column, container, pick_list, row, text, text_input, Container, Row,
+        Space,
     },
-    Command as Cm, Element, Length, Renderer, Theme,
+    Color, Command as Cm, Element, Length, Renderer, Theme,
 };
 use iced_drop::{droppable, zones_on_point};
-use parking_lot::RwLock;
+use parking_lot::{Mutex, MutexGuard, RwLock};
 use serde::{Deserialize, Serialize};
+use ytm_rs_core::group::GroupPlay;
 
 use crate::{
-    caching::{BufferedCache, NDJsonCache},
+    caching::{song_data_read, BufferedCache, NDJsonCache},
+    context_menu::{with_menu, SongAction},
     settings::SongKey,
-    song::{Song, SongData},
+    song::{format_minutes, format_total_duration, parse_duration, Song, SongData},
+    song_list::{windowed, Span, HEADER_HEIGHT, ROW_HEIGHT},
+    song_operations::tree_filter::{AppliedFilter, TreeFilter},
     styling::FullYtmrsScheme,
+    thumbnails::OnScreen,
+    verbosity::{debug, trace},
+    widgets::{Stepper, StepperMsg},
 };
 
-use super::RecursiveSongOp;
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum ActualRecursiveOps {
-    PlayOnce,
-    LoopNTimes,
-    Stretch,
-    InfiniteLoop,
-    RandomPlay,
-    SingleRandom,
-    InfiniteRandom,
-}
-impl ActualRecursiveOps {
-    fn as_str(&self) -> &'static str {
-        match &self {
-            ActualRecursiveOps::PlayOnce => CONSTRUCTOR_CHOICES[0],
-            ActualRecursiveOps::LoopNTimes => CONSTRUCTOR_CHOICES[1],
-            ActualRecursiveOps::Stretch => CONSTRUCTOR_CHOICES[2],
-            ActualRecursiveOps::InfiniteLoop => CONSTRUCTOR_CHOICES[3],
-            ActualRecursiveOps::RandomPlay => CONSTRUCTOR_CHOICES[4],
-            ActualRecursiveOps::SingleRandom => CONSTRUCTOR_CHOICES[5],
-            ActualRecursiveOps::InfiniteRandom => CONSTRUCTOR_CHOICES[6],
-        }
-    }
-
-    fn from_str(s: &'static str) -> Option<ActualRecursiveOps> {
-        match s {
-            "Play Once" => Some(ActualRecursiveOps::PlayOnce),
-            "Loop N Times" => Some(ActualRecursiveOps::LoopNTimes),
-            "Stretch" => Some(ActualRecursiveOps::Stretch),
-            "Infinite Loop" => Some(ActualRecursiveOps::InfiniteLoop),
-            "Random Play" => Some(ActualRecursiveOps::RandomPlay),
-            "Single Random" => Some(ActualRecursiveOps::SingleRandom),
-            "Infinite Random" => Some(ActualRecursiveOps::InfiniteRandom),
-            _ => None,
-        }
-    }
-}
+use super::{ActualRecursiveOps, RecursiveSongOp, CONSTRUCTOR_CHOICES};
 
 pub trait TreeDirected {
     // TODO: These methods are not as ass but they could be better probably
@@ -84,11 +59,22 @@ pub enum ConstructorItem {
     Song(SongKey, #[serde(skip)] ItemId),
     Operation(SongOpConstructor),
 }
-impl ConstructorItem {
-    pub fn all_song_keys(&self) -> Vec<&SongKey> {
-        match *self {
-            ConstructorItem::Song(ref key, _) => vec![key],
-            ConstructorItem::Operation(ref op) => op.all_song_keys_rec().collect(),
+/// The song keys of a tree in order, walked without collecting every level first
+pub struct SongKeys<'a> {
+    stack: Vec<std::slice::Iter<'a, ConstructorItem>>,
+}
+impl<'a> Iterator for SongKeys<'a> {
+    type Item = &'a SongKey;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.stack.last_mut()?.next() {
+                Some(ConstructorItem::Song(key, _)) => return Some(key),
+                Some(ConstructorItem::Operation(op)) => self.stack.push(op.list.iter()),
+                None => {
+                    self.stack.pop();
+                }
+            }
         }
     }
 }
@@ -154,9 +140,24 @@ impl TreeDirected for ConstructorItem {
 
 #[cfg(test)]
 mod tests {
+    use std::{collections::VecDeque, sync::Arc};
+
     use iced::advanced::widget::Id as WId;
+    use parking_lot::RwLock;
+    use ytm_rs_core::saved::SavedTree;
 
-    use crate::song_operations::{ConstructorItem, ItemId, SongOpConstructor, TreeDirected};
+    use crate::{
+        caching::{readers::LineBasedReader, BufferedCache, NDJsonCache, ToRwMapExt},
+        song::Song,
+        song_list::HEADER_HEIGHT,
+        song_operations::{
+            path_after_flatten, path_after_removal, path_after_wrap, tree_filter::TreeFilter,
+            ActualRecursiveOps, CItemMessage, ConstructorItem, ItemId, NextResult,
+            OperationTracker, RecursiveSongOp, SongOpConstructor, SongOpMessage, SongOpTracker,
+            TreeDirected, VisibleRow,
+        },
+        widgets::StepperMsg,
+    };
 
     #[test]
     fn test_path_to_id() {
@@ -178,6 +179,515 @@ mod tests {
         assert_eq![Some(vec![1, 0]), tree.path_to_id(&WId::from(song_id2.0))];
         assert_eq![None, tree.path_to_id(&WId::from(unused_id.0))];
     }
+
+    fn nested_tree() -> (SongOpConstructor, WId) {
+        let group = SongOpConstructor::from(vec![
+            ConstructorItem::Song("a".to_string(), ItemId::default()),
+            ConstructorItem::Song("b".to_string(), ItemId::default()),
+        ]);
+        let group_id = WId::from(group.id.0.clone());
+        let tree = SongOpConstructor::from(vec![
+            ConstructorItem::Song("c".to_string(), ItemId::default()),
+            ConstructorItem::Operation(group),
+        ]);
+        (tree, group_id)
+    }
+
+    #[test]
+    fn promoted_groups_get_fresh_ids() {
+        let (mut tree, group_id) = nested_tree();
+        let path = tree.path_to_id(&group_id).unwrap();
+
+        let copy = tree.promote(path.clone(), false).unwrap();
+        assert_eq![copy.all_song_keys_rec().collect::<Vec<_>>(), vec!["a", "b"]];
+        // None of the copy's ids exist in the original tree
+        assert_eq![tree.path_to_id(&WId::from(copy.id.0.clone())), None];
+        for id in copy.visible_song_ids() {
+            assert_eq![tree.path_to_id(&id), None];
+        }
+        // Copying leaves the tree untouched
+        assert_eq![tree.all_song_keys_rec().count(), 3];
+        // Songs can't be promoted, and neither can the root
+        assert![tree.promote(vec![0], false).is_none()];
+        assert![tree.promote(vec![], false).is_none()];
+    }
+
+    #[test]
+    fn moving_a_group_removes_it() {
+        let (mut tree, group_id) = nested_tree();
+        let path = tree.path_to_id(&group_id).unwrap();
+
+        let moved = tree.promote(path, true).unwrap();
+        assert_eq![moved.all_song_keys_rec().count(), 2];
+        assert_eq![tree.all_song_keys_rec().collect::<Vec<_>>(), vec!["c"]];
+        assert_eq![tree.path_to_id(&group_id), None];
+    }
+
+    #[test]
+    fn paths_after_a_moved_group_follow_their_song() {
+        // [c, [a, b], d, [e, [f]]]
+        let (mut tree, group_id) = nested_tree();
+        tree.list
+            .push(ConstructorItem::Song("d".to_string(), ItemId::default()));
+        let inner = SongOpConstructor::from(vec![ConstructorItem::Song(
+            "f".to_string(),
+            ItemId::default(),
+        )]);
+        tree.list
+            .push(ConstructorItem::Operation(SongOpConstructor::from(vec![
+                ConstructorItem::Song("e".to_string(), ItemId::default()),
+                ConstructorItem::Operation(inner),
+            ])));
+        let moved = tree.path_to_id(&group_id).unwrap();
+        let paths = [vec![0], vec![2], vec![3, 0], vec![3, 1, 0]];
+        let keys: Vec<_> = paths.iter().map(|path| key_at(&tree, path)).collect();
+
+        tree.promote(moved.clone(), true).unwrap();
+        for (path, key) in paths.iter().zip(keys) {
+            let after = path_after_removal(path, &moved).unwrap();
+            assert_eq![key_at(&tree, &after), key, "{path:?} moved to {after:?}"];
+        }
+        // Songs inside the group leave with it
+        assert_eq![path_after_removal(&[1, 0], &[1]), None];
+        assert_eq![path_after_removal(&[1], &[1]), None];
+        // Deeper removals only move their siblings
+        assert_eq![path_after_removal(&[3, 1, 0], &[3, 0]), Some(vec![3, 0, 0])];
+        assert_eq![path_after_removal(&[2], &[3, 0]), Some(vec![2])];
+        assert_eq![path_after_removal(&[3], &[3, 0]), Some(vec![3])];
+    }
+
+    /// The tree's shape, with groups as their operation and songs as their key
+    fn shape(tree: &SongOpConstructor) -> String {
+        let items: Vec<String> = tree
+            .list
+            .iter()
+            .map(|item| match item {
+                ConstructorItem::Song(key, _) => key.clone(),
+                ConstructorItem::Operation(op) => shape(op),
+            })
+            .collect();
+        format!("{}[{}]", tree.play.operation.as_str(), items.join(" "))
+    }
+
+    fn key_at(tree: &SongOpConstructor, path: &[usize]) -> Option<String> {
+        match tree.item_at_path(path.to_vec().into())? {
+            ConstructorItem::Song(key, _) => Some(key.clone()),
+            ConstructorItem::Operation(_) => None,
+        }
+    }
+
+    #[test]
+    fn wrapping_and_flattening_round_trip() {
+        let (tree, _) = nested_tree();
+        let original = shape(&tree);
+        // The root level, and inside the group
+        for path in [vec![0], vec![1, 0], vec![1, 1]] {
+            let mut tree = tree.clone();
+            let key = key_at(&tree, &path);
+            assert![tree
+                .wrap_song(path.clone(), ActualRecursiveOps::LoopNTimes, 2)
+                .is_some()];
+            assert_eq![key_at(&tree, &path_after_wrap(&path, &path)), key];
+            assert_ne![shape(&tree), original];
+
+            assert_eq![tree.flatten_group(path.clone()), Some(1)];
+            assert_eq![shape(&tree), original];
+        }
+
+        // Deeper, where the siblings of the wrapped song have to stay put
+        let mut tree = tree.clone();
+        tree.wrap_song(vec![1, 1], ActualRecursiveOps::PlayOnce, 1);
+        let deeper = shape(&tree);
+        tree.wrap_song(vec![1, 1, 0], ActualRecursiveOps::InfiniteLoop, 1);
+        assert_eq![
+            shape(&tree),
+            "Play Once[c Play Once[a Play Once[Infinite Loop[b]]]]"
+        ];
+        tree.flatten_group(vec![1, 1, 0]);
+        assert_eq![shape(&tree), deeper];
+
+        // Only songs can be wrapped, and only groups flattened
+        assert![tree
+            .wrap_song(vec![1], ActualRecursiveOps::PlayOnce, 1)
+            .is_none()];
+        assert_eq![tree.flatten_group(vec![0]), None];
+        assert_eq![tree.flatten_group(vec![]), None];
+    }
+
+    #[test]
+    fn wrapped_groups_start_with_the_given_operation() {
+        let (mut tree, _) = nested_tree();
+        tree.wrap_song(vec![0], ActualRecursiveOps::LoopNTimes, 3);
+        match &tree.list[0] {
+            ConstructorItem::Operation(group) => {
+                assert_eq![group.play.operation, ActualRecursiveOps::LoopNTimes];
+                assert_eq![group.play.n, 3];
+            }
+            ConstructorItem::Song(_, _) => panic!("not wrapped"),
+        }
+    }
+
+    #[test]
+    fn playing_paths_follow_the_song() {
+        assert_eq![path_after_wrap(&[1, 0], &[1, 0]), vec![1, 0, 0]];
+        assert_eq![path_after_wrap(&[1, 1], &[1, 0]), vec![1, 1]];
+
+        // Flattening a group of 3 at [1] in [x, [a, b, c], y]
+        assert_eq![path_after_flatten(&[0], &[1], 3), vec![0]];
+        assert_eq![path_after_flatten(&[1, 2], &[1], 3), vec![3]];
+        assert_eq![path_after_flatten(&[2], &[1], 3), vec![4]];
+        // Nested inside the flattened group, and in an unrelated group
+        assert_eq![path_after_flatten(&[1, 1, 4], &[1], 3), vec![2, 4]];
+        assert_eq![path_after_flatten(&[0, 5], &[1], 3), vec![0, 5]];
+        assert_eq![path_after_flatten(&[2, 1, 0], &[2, 1], 1), vec![2, 1]];
+
+        // The remapped path points at the same song as before
+        let (mut tree, _) = nested_tree();
+        let before = key_at(&tree, &[1, 1]);
+        tree.flatten_group(vec![1]);
+        assert_eq![key_at(&tree, &path_after_flatten(&[1, 1], &[1], 2)), before];
+    }
+
+    #[test]
+    fn edits_bump_the_revision() {
+        let mut tree = SongOpConstructor::from(vec![]);
+        let revision = tree.revision();
+
+        tree.push_to_path(
+            VecDeque::new(),
+            ConstructorItem::Song("a".to_string(), ItemId::default()),
+        );
+        assert_ne![revision, tree.revision()];
+
+        let revision = tree.revision();
+        tree.update(SongOpMessage::Remove(0));
+        assert_ne![revision, tree.revision()];
+    }
+
+    #[test]
+    fn song_data_is_only_read_again_once_the_songs_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = Arc::new(RwLock::new(NDJsonCache::<Song>::new(LineBasedReader::new(
+            dir.path().join("songs.ndjson"),
+        ))));
+        let mut tree = SongOpConstructor::from(vec![ConstructorItem::from("a".to_string())]);
+        tree.set_cache(Arc::clone(&cache));
+        // Not cached yet
+        assert_eq![tree.cached_song_data(&"a".to_string()).title, "a"];
+
+        let mut song = Song::basic();
+        song.id = "a".into();
+        song.title = "Alpha".into();
+        cache
+            .write()
+            .items_mut()
+            .extend([("a".to_string(), song)].to_rwmap());
+        assert_eq![tree.cached_song_data(&"a".to_string()).title, "Alpha"];
+
+        // Edits made through the song's own lock are seen once they're marked
+        cache.read().items()["a"].write().title = "Aleph".into();
+        assert_eq![tree.cached_song_data(&"a".to_string()).title, "Alpha"];
+        cache.read().touch();
+        assert_eq![tree.cached_song_data(&"a".to_string()).title, "Aleph"];
+    }
+
+    #[test]
+    fn songs_can_be_pushed_into_empty_groups() {
+        let mut tree = SongOpConstructor::from(vec![ConstructorItem::Operation(
+            SongOpConstructor::from(vec![]),
+        )]);
+        // Dropping on the empty group, then on the spot of its first song
+        tree.push_to_path([0, 0].into(), ConstructorItem::from("a".to_string()));
+        tree.push_to_path([0, 0].into(), ConstructorItem::from("b".to_string()));
+        assert_eq![key_at(&tree, &[0, 0]), Some("b".to_string())];
+
+        let mut empty = SongOpConstructor::from(vec![]);
+        empty.push_to_path([3].into(), ConstructorItem::from("a".to_string()));
+        assert_eq![key_at(&empty, &[0]), Some("a".to_string())];
+    }
+
+    #[test]
+    fn copies_are_the_same_tree_until_edited() {
+        let (tree, _) = nested_tree();
+        let mut copy = tree.clone();
+        copy.update(SongOpMessage::Collapse);
+        assert![tree.same_tree(&copy)];
+
+        copy.update(SongOpMessage::StepN(StepperMsg::Increment));
+        assert![!tree.same_tree(&copy)];
+        // Equal keys in new rows aren't the same tree
+        assert![!tree.same_tree(&tree.with_fresh_ids())];
+    }
+
+    #[test]
+    fn only_edits_of_the_tree_are_kept_for_undo() {
+        let nested = |msg| SongOpMessage::ItemMessage(0, CItemMessage::Operation(Box::new(msg)));
+        assert![!nested(SongOpMessage::Collapse).edits_tree()];
+        assert![!SongOpMessage::CollapseGroups(true).edits_tree()];
+        assert![nested(SongOpMessage::Remove(0)).edits_tree()];
+        assert![SongOpMessage::StepN(StepperMsg::Increment).edits_tree()];
+    }
+
+    fn toggle(idx: usize, enabled: bool) -> SongOpMessage {
+        let msg = Box::new(SongOpMessage::Enable(enabled));
+        SongOpMessage::ItemMessage(idx, CItemMessage::Operation(msg))
+    }
+
+    #[test]
+    fn groups_that_are_off_are_skipped() {
+        let group = |keys: &[&str]| {
+            let mut group = SongOpConstructor::from(vec![]);
+            for key in keys {
+                group.list.push(ConstructorItem::from(key.to_string()));
+            }
+            ConstructorItem::Operation(group)
+        };
+        // a, [b, c], [d, e], f
+        let mut tree = SongOpConstructor::from(vec![
+            ConstructorItem::from("a".to_string()),
+            group(&["b", "c"]),
+            group(&["d", "e"]),
+            ConstructorItem::from("f".to_string()),
+        ]);
+        assert![toggle(1, false).toggles_group()];
+        tree.update(toggle(1, false));
+        assert![matches!(tree.build(), RecursiveSongOp::PlayOnce(ops) if ops.len() == 3)];
+
+        // Paths skip the group that's off, both ways
+        assert_eq![tree.played_path(&[2, 1]), Some(vec![1, 1])];
+        assert_eq![tree.played_path(&[1, 0]), None];
+        assert_eq![tree.tree_path(&[1, 1]), Some(vec![2, 1])];
+        assert_eq![tree.tree_path(&[2]), Some(vec![3])];
+        let last = tree.tree_path(&[2]).unwrap();
+        assert_eq![key_at(&tree, &last), Some("f".to_string())];
+
+        let mut tracker = SongOpTracker::start(&tree.build(), [0].into()).unwrap();
+        let mut played = vec![];
+        while tracker.move_next() == NextResult::Current {
+            let path: Vec<usize> = tracker.get_current().collect();
+            match tree.played_item(&path) {
+                Some(ConstructorItem::Song(key, _)) => played.push(key.clone()),
+                _ => panic!["{path:?} isn't a song"],
+            }
+        }
+        assert_eq![played, vec!["d", "e", "f"]];
+
+        // Turned back on, it plays again
+        tree.update(toggle(1, true));
+        assert_eq![tree.played_path(&[1, 0]), Some(vec![1, 0])];
+        assert![tree.is_valid()];
+
+        // A group whose items are all off can't play
+        let mut outer = SongOpConstructor::from(vec![group(&["a"]), group(&["b"])]);
+        let mut tree = SongOpConstructor::from(vec![ConstructorItem::from("c".to_string())]);
+        outer.update(toggle(0, false));
+        outer.update(toggle(1, false));
+        tree.insert(1, ConstructorItem::Operation(outer));
+        assert![tree.build().is_valid()];
+        assert![tree.problem().unwrap().contains("turned off")];
+    }
+
+    #[test]
+    fn weights_stay_with_their_items() {
+        let song = |key: &str| ConstructorItem::from(key.to_string());
+        let mut tree = SongOpConstructor::new(
+            ActualRecursiveOps::WeightedRandom,
+            vec![song("a"), song("b"), song("c")],
+            None,
+        );
+        tree.update(SongOpMessage::Weigh(1, "5".into()));
+        tree.update(SongOpMessage::Weigh(2, "x".into()));
+        tree.update(SongOpMessage::Weigh(0, "".into()));
+        let weights = |tree: &SongOpConstructor| match tree.build() {
+            RecursiveSongOp::WeightedRandom(weighted) => weighted
+                .into_iter()
+                .map(|(_, weight)| weight)
+                .collect::<Vec<_>>(),
+            op => panic!["{op:?} isn't weighted"],
+        };
+        assert_eq![weights(&tree), vec![0, 5, 1]];
+
+        tree.insert(0, song("d"));
+        tree.update(SongOpMessage::Remove(2));
+        assert_eq![key_at(&tree, &[2]), Some("c".to_string())];
+        assert_eq![weights(&tree), vec![1, 0, 1]];
+        assert![tree.is_valid()];
+
+        tree.update(SongOpMessage::Weigh(0, "0".into()));
+        tree.update(SongOpMessage::Weigh(2, "0".into()));
+        assert![!tree.is_valid()];
+    }
+
+    #[test]
+    fn moved_items_keep_their_weights() {
+        let song = |key: &str| ConstructorItem::from(key.to_string());
+        let mut tree = SongOpConstructor::new(
+            ActualRecursiveOps::WeightedRandom,
+            vec![song("a"), song("b"), song("c")],
+            None,
+        );
+        tree.update(SongOpMessage::Weigh(0, "4".into()));
+        tree.update(SongOpMessage::Weigh(2, "0".into()));
+
+        let (item, weight) = tree.take_path(&[0]).unwrap();
+        assert_eq![weight, 4];
+        tree.put_at_path([1].into(), item, weight);
+        assert_eq![key_at(&tree, &[1]), Some("a".to_string())];
+        let weights: Vec<u32> = (0..3).map(|idx| tree.weight(idx)).collect();
+        assert_eq![weights, [1, 4, 0]];
+    }
+
+    #[test]
+    fn durations_are_typed_as_minutes() {
+        let song = ConstructorItem::from("a".to_string());
+        let mut tree =
+            SongOpConstructor::new(ActualRecursiveOps::LoopUntilDuration, vec![song], None);
+        let seconds = |tree: &SongOpConstructor| match tree.build() {
+            RecursiveSongOp::LoopUntilDuration(_, seconds) => seconds,
+            op => panic!["{op:?} isn't timed"],
+        };
+        assert_eq![seconds(&tree), 30 * 60];
+        tree.update(SongOpMessage::TypeDuration("45".into()));
+        assert_eq![seconds(&tree), 45 * 60];
+        // Half typed durations keep the last one that read
+        tree.update(SongOpMessage::TypeDuration("12:".into()));
+        assert_eq![seconds(&tree), 45 * 60];
+        tree.update(SongOpMessage::TypeDuration("12:30".into()));
+        assert_eq![seconds(&tree), 12 * 60 + 30];
+        assert![tree.is_valid()];
+
+        tree.update(SongOpMessage::TypeDuration("0".into()));
+        assert![!tree.is_valid()];
+    }
+
+    #[test]
+    fn song_data_is_not_stale_after_edits() {
+        let mut tree = SongOpConstructor::from(vec![]);
+        assert![tree.collect_song_data().is_empty()];
+
+        tree.push_to_path(
+            VecDeque::new(),
+            ConstructorItem::Song("a".to_string(), ItemId::default()),
+        );
+        let data = tree.collect_song_data();
+        assert_eq![data.len(), 1];
+        assert_eq![data["a"].title, "a"];
+    }
+
+    #[test]
+    fn filters_open_the_groups_they_match_in() {
+        let (mut tree, group_id) = nested_tree();
+        let path = tree.path_to_id(&group_id).unwrap();
+        if let Some(ConstructorItem::Operation(group)) = tree.item_at_path_mut(path.into()) {
+            group.collapsed = true;
+        }
+        assert_eq![tree.visible_song_keys(), ["c"]];
+
+        tree.apply_filter(TreeFilter::new("B").unwrap());
+        assert_eq![tree.visible_song_keys(), ["b"]];
+        let b = tree.visible_song_ids().remove(0);
+        // Under the tree's and the group's headers, with nothing shown above it
+        assert_eq![tree.offset_of(&b), Some(2.0 * HEADER_HEIGHT)];
+
+        tree.clear_filter();
+        assert_eq![tree.visible_song_keys(), ["c"]];
+        assert_eq![tree.offset_of(&b), None];
+    }
+
+    #[test]
+    fn groups_stay_collapsed_after_saving() {
+        let (mut tree, group_id) = nested_tree();
+        let c = tree.visible_song_ids().remove(0);
+        tree.update(SongOpMessage::CollapseGroups(true));
+        let rows = [VisibleRow::Song(c), VisibleRow::Group(group_id.clone())];
+        assert_eq![tree.visible_rows(), rows];
+
+        let json = serde_json::to_string(&tree).unwrap();
+        let mut loaded: SongOpConstructor = serde_json::from_str(&json).unwrap();
+        assert_eq![loaded.visible_song_keys(), ["c"]];
+        loaded.update(SongOpMessage::CollapseGroups(false));
+        assert_eq![loaded.visible_song_keys(), ["c", "a", "b"]];
+
+        tree.toggle_group(&group_id);
+        assert_eq![tree.visible_song_keys(), ["c", "a", "b"]];
+    }
+
+    #[test]
+    fn saved_trees_build_the_same_op_without_the_window() {
+        let song = |key: &str| ConstructorItem::from(key.to_string());
+        let group = SongOpConstructor::new(
+            ActualRecursiveOps::LoopNTimes,
+            vec![song("a"), song("b")],
+            None,
+        )
+        .with_n(2);
+        let mut off = SongOpConstructor::from(vec![song("d")]);
+        off.play.enabled = false;
+        let items = vec![song("c"), group.into(), off.into()];
+        let mut tree = SongOpConstructor::new(ActualRecursiveOps::WeightedRandom, items, None);
+        tree.set_weight(0, 3);
+
+        let json = serde_json::to_string(&tree).unwrap();
+        let saved: SavedTree = serde_json::from_str(&json).unwrap();
+        let built = |op: RecursiveSongOp| format!("{op:?}");
+        assert_eq![built(saved.build()), built(tree.build())];
+    }
+
+    #[test]
+    fn summaries_follow_what_groups_play() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = Arc::new(RwLock::new(NDJsonCache::<Song>::new(LineBasedReader::new(
+            dir.path().join("songs.ndjson"),
+        ))));
+        let arrive = |key: &str, secs: f64| {
+            let mut song = Song::basic();
+            song.id = key.into();
+            song.duration = secs;
+            song.is_live = false;
+            let songs = [(key.to_string(), song)].to_rwmap();
+            cache.write().items_mut().extend(songs);
+        };
+        arrive("a", 60.0);
+        arrive("b", 30.0);
+
+        let song = |key: &str| ConstructorItem::from(key.to_string());
+        let looped = SongOpConstructor::new(
+            ActualRecursiveOps::LoopNTimes,
+            vec![song("a"), song("b")],
+            None,
+        )
+        .with_n(2);
+        let picked = SongOpConstructor::new(
+            ActualRecursiveOps::SingleRandom,
+            vec![song("a"), song("b"), song("c")],
+            None,
+        );
+        let mut tree = SongOpConstructor::from(vec![looped.into(), picked.into()]);
+        tree.set_cache(Arc::clone(&cache));
+        let summary = |tree: &SongOpConstructor, path: &[usize]| {
+            let data = tree.fresh_song_data();
+            let ((_, generation), data) = data.as_ref().unwrap();
+            let group = tree.group_at_path(path).unwrap();
+            group.summary_text((tree.structure, *generation), data)
+        };
+        assert_eq![summary(&tree, &[0]), "2 songs, 3:00"];
+        assert_eq![summary(&tree, &[1]), "3 songs, ~0:30 (+1 unknown)"];
+        assert_eq![summary(&tree, &[]), "5 songs, ~3:30 (+1 unknown)"];
+
+        // Kept while only how the tree is shown changes
+        tree.update(SongOpMessage::CollapseGroups(true));
+        assert_eq![summary(&tree, &[1]), "3 songs, ~0:30 (+1 unknown)"];
+        // Made again once the unknown song's metadata arrives
+        arrive("c", 90.0);
+        assert_eq![summary(&tree, &[1]), "3 songs, ~1:00"];
+
+        let endless = SongOpMessage::ChangeOperation(ActualRecursiveOps::InfiniteRandom);
+        tree.update(SongOpMessage::ItemMessage(
+            1,
+            CItemMessage::Operation(Box::new(endless)),
+        ));
+        assert_eq![summary(&tree, &[]), "5 songs, ∞"];
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -189,11 +699,28 @@ pub enum CItemMessage {
 pub enum SongOpMessage {
     // User input
     NewGroup,
-    ChangeN(u32),
+    /// Edits N, for the operations that use it
+    StepN(StepperMsg),
     Collapse,
     Uncollapse,
+    /// Collapses every group in the tree when true, opens them all when false
+    CollapseGroups(bool),
+    /// Turns the group on or off, groups that are off don't play
+    Enable(bool),
+    /// Types the weight of the item at the index, for "Weighted Random" groups
+    Weigh(usize, String),
+    /// Types how long a "Loop For" group plays, as "mm:ss" or minutes
+    TypeDuration(String),
     ChangeOperation(ActualRecursiveOps),
     CloseSelf,
+    /// Saves the group as its own playlist, removing it from this one if true
+    Promote(bool),
+    /// Puts the song in a group of its own
+    Wrap(WId),
+    /// Opens the song's metadata editor
+    EditSong(SongKey),
+    /// Replaces the group with its items
+    Flatten,
 
     Remove(usize),
 
@@ -201,33 +728,74 @@ pub enum SongOpMessage {
     Dropped(WId, iced::Point, iced::Rectangle),
     HandleZones(WId, Vec<(iced::advanced::widget::Id, iced::Rectangle)>),
     SongClicked(WId),
+    /// Opens the menu of the song at the row
+    OpenMenu(WId),
+    Menu(WId, SongKey, SongAction),
 
     ItemMessage(usize, CItemMessage),
+}
+
+impl SongOpMessage {
+    /// Whether the message comes from typing in a group's count, duration or weights
+    pub fn is_text_edit(&self) -> bool {
+        match self {
+            Self::StepN(StepperMsg::Typed(_)) | Self::Weigh(..) | Self::TypeDuration(_) => true,
+            Self::ItemMessage(_, CItemMessage::Operation(msg)) => msg.is_text_edit(),
+            _ => false,
+        }
+    }
+
+    /// Whether the message can change the tree, rather than only how it's shown
+    pub fn edits_tree(&self) -> bool {
+        match self {
+            Self::Collapse
+            | Self::Uncollapse
+            | Self::CollapseGroups(_)
+            | Self::EditSong(_)
+            | Self::SongClicked(_)
+            | Self::OpenMenu(_) => false,
+            Self::ItemMessage(_, CItemMessage::Operation(msg)) => msg.edits_tree(),
+            _ => true,
+        }
+    }
+
+    /// Whether the message turns a group on or off
+    pub fn toggles_group(&self) -> bool {
+        match self {
+            Self::Enable(_) => true,
+            Self::ItemMessage(_, CItemMessage::Operation(msg)) => msg.toggles_group(),
+            _ => false,
+        }
+    }
+}
 
-    Null,
+/// A row the keyboard focus can be on
+#[derive(Debug, Clone, PartialEq)]
+pub enum VisibleRow {
+    Song(WId),
+    /// The header of a group in the tree
+    Group(WId),
 }
 
 pub enum UpdateResult {
     Cm(Cm<SongOpMessage>),
     SongClicked(WId),
-    Move(WId, WId), // from, to
+    Move(WId, WId),     // from, to
+    Promote(WId, bool), // group, moved
+    Wrap(WId),          // song
+    Flatten(WId),       // group
+    EditSong(SongKey),
+    OpenMenu(WId),
+    Menu(WId, SongKey, SongAction),
 }
 
-fn verify_n(txt: String) -> SongOpMessage {
-    txt.parse::<u32>()
-        .map(SongOpMessage::ChangeN)
-        .unwrap_or(SongOpMessage::Null)
+/// The counts N can be set to from the group's header
+pub const MIN_N: u32 = 1;
+pub const MAX_N: u32 = 9999;
+
+fn n_stepper() -> Stepper {
+    Stepper::new(MIN_N, MAX_N)
 }
-// A wrapper made for recursive song operations
-const CONSTRUCTOR_CHOICES: [&str; 7] = [
-    "Play Once",
-    "Loop N Times",
-    "Stretch",
-    "Infinite Loop",
-    "Random Play",
-    "Single Random",
-    "Infinite Random",
-];
 
 #[derive(Debug, Clone)]
 pub struct ItemId(pub container::Id);
@@ -237,29 +805,206 @@ impl Default for ItemId {
     }
 }
 
+/// The id of a group's N field, so it can be focused
+#[derive(Debug, Clone)]
+pub struct NInputId(pub text_input::Id);
+impl Default for NInputId {
+    fn default() -> Self {
+        Self(text_input::Id::unique())
+    }
+}
+
+/// Revisions are unique across trees, so a tree that replaced another never looks unchanged
+static NEXT_REVISION: AtomicU64 = AtomicU64::new(1);
+
+fn next_revision() -> u64 {
+    NEXT_REVISION.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Whether the item is built into the op
+fn plays(item: &ConstructorItem) -> bool {
+    match item {
+        ConstructorItem::Song(..) => true,
+        ConstructorItem::Operation(op) => op.play.enabled,
+    }
+}
+
+/// Display data of the songs in a tree, reused between views until the tree's revision or the
+/// song cache's generation changes.
+#[derive(Default)]
+struct SongDataCache(Mutex<Option<((u64, u64), HashMap<SongKey, SongData>)>>);
+impl Clone for SongDataCache {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+impl Debug for SongDataCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SongDataCache")
+    }
+}
+
+/// How long one pass of a group plays, with how many songs' lengths aren't known
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PassLength {
+    Exact(f64, usize),
+    /// What a random pick plays on average
+    About(f64, usize),
+    Endless,
+}
+
+impl PassLength {
+    fn of_song(data: Option<&SongData>) -> Self {
+        match data.and_then(|data| data.duration.known()) {
+            Some(secs) => Self::Exact(secs, 0),
+            None => Self::Exact(0.0, 1),
+        }
+    }
+
+    fn parts(self) -> Option<(f64, usize, bool)> {
+        match self {
+            Self::Exact(secs, unknown) => Some((secs, unknown, false)),
+            Self::About(secs, unknown) => Some((secs, unknown, true)),
+            Self::Endless => None,
+        }
+    }
+
+    fn from_parts(secs: f64, unknown: usize, about: bool) -> Self {
+        match about {
+            true => Self::About(secs, unknown),
+            false => Self::Exact(secs, unknown),
+        }
+    }
+
+    /// The items played one after another
+    fn sum(lengths: impl IntoIterator<Item = Self>) -> Self {
+        let mut total = (0.0, 0, false);
+        for length in lengths {
+            match length.parts() {
+                Some((secs, unknown, about)) => {
+                    total = (total.0 + secs, total.1 + unknown, total.2 || about)
+                }
+                None => return Self::Endless,
+            }
+        }
+        Self::from_parts(total.0, total.1, total.2)
+    }
+
+    /// One of the items, picked at random. Any of them that's endless might be picked.
+    fn average(lengths: &[Self]) -> Self {
+        let mut total = (0.0, 0);
+        for length in lengths {
+            match length.parts() {
+                Some((secs, unknown, _)) => total = (total.0 + secs, total.1 + unknown),
+                None => return Self::Endless,
+            }
+        }
+        match lengths.len() {
+            0 => Self::Exact(0.0, 0),
+            picks => Self::About(total.0 / picks as f64, total.1),
+        }
+    }
+
+    fn times(self, n: u32) -> Self {
+        match (self.parts(), n) {
+            (_, 0) => Self::Exact(0.0, 0),
+            (Some((secs, unknown, about)), n) => Self::from_parts(secs * n as f64, unknown, about),
+            (None, _) => Self::Endless,
+        }
+    }
+
+    /// e.g. "~3:20 (+1 unknown)"
+    fn format(self) -> String {
+        match self {
+            Self::Exact(secs, unknown) => format_total_duration(secs, unknown),
+            Self::About(secs, unknown) => format!("~{}", format_total_duration(secs, unknown)),
+            Self::Endless => "∞".to_string(),
+        }
+    }
+}
+
+/// The songs in a group and how long one pass of it plays
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct GroupSummary {
+    songs: usize,
+    length: PassLength,
+}
+
+/// What summaries are kept by: the structure of the tree they're shown in, and the generation of
+/// the song cache. Other changes to the tree, like collapsing groups, don't count.
+type SummaryKey = (u64, u64);
+
+/// A group's summary, kept until its [`SummaryKey`] changes
+#[derive(Default)]
+struct SummaryCache(Mutex<Option<(SummaryKey, GroupSummary)>>);
+impl Clone for SummaryCache {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+impl Debug for SummaryCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SummaryCache")
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SongOpConstructor {
     #[serde(skip)]
     id: ItemId,
-    pub operation: ActualRecursiveOps,
+    /// What the group plays, the rest is how it's shown
+    #[serde(flatten)]
+    pub play: GroupPlay,
     pub list: Vec<ConstructorItem>,
     #[serde(skip)]
     cache: Option<Arc<RwLock<NDJsonCache<Song>>>>,
     pub collapsible: bool,
+    /// Kept in the playlist's file, so groups stay collapsed after a restart
+    #[serde(default)]
     collapsed: bool,
-    // used for certain operations, like LoopNTimes and Stretch
-    n: u32,
+    /// The playlist's filter, while one is applied
+    #[serde(skip)]
+    filter: Option<Arc<AppliedFilter>>,
+    /// Whether the filter collapsed the group. It's shown this way instead of `collapsed`
+    /// while the filter is applied, so clearing it brings back how the group was.
+    #[serde(skip)]
+    filter_collapsed: Option<bool>,
+    /// What's typed in the duration field, kept while it doesn't read as one
+    #[serde(skip)]
+    seconds_draft: Option<String>,
+    // bumped whenever the tree or the songs in it may have changed
+    #[serde(skip, default = "next_revision")]
+    revision: u64,
+    /// Bumped only by edits to what the tree holds or plays, see [`SummaryKey`]
+    #[serde(skip, default = "next_revision")]
+    structure: u64,
+    #[serde(skip)]
+    data_cache: SongDataCache,
+    #[serde(skip)]
+    summary_cache: SummaryCache,
+    #[serde(skip)]
+    n_input: NInputId,
+    #[serde(skip, default = "n_stepper")]
+    n_stepper: Stepper,
 }
 impl Default for SongOpConstructor {
     fn default() -> Self {
         Self {
             id: Default::default(),
-            operation: ActualRecursiveOps::PlayOnce,
+            play: GroupPlay::default(),
             list: vec![],
             cache: None,
             collapsible: true,
             collapsed: false,
-            n: 1,
+            filter: None,
+            filter_collapsed: None,
+            seconds_draft: None,
+            revision: next_revision(),
+            structure: next_revision(),
+            data_cache: SongDataCache::default(),
+            summary_cache: SummaryCache::default(),
+            n_input: NInputId::default(),
+            n_stepper: n_stepper(),
         }
     }
 }
@@ -271,16 +1016,32 @@ impl SongOpConstructor {
     ) -> Self {
         Self {
             id: ItemId::default(),
-            operation,
+            play: GroupPlay::new(operation),
             list,
             cache,
             collapsible: true,
             collapsed: false,
-            n: 1,
+            filter: None,
+            filter_collapsed: None,
+            seconds_draft: None,
+            revision: next_revision(),
+            structure: next_revision(),
+            data_cache: SongDataCache::default(),
+            summary_cache: SummaryCache::default(),
+            n_input: NInputId::default(),
+            n_stepper: n_stepper(),
         }
     }
 
+    /// Sets N, for the operations that use it
+    #[cfg(any(test, feature = "demo"))]
+    pub fn with_n(mut self, n: u32) -> Self {
+        self.play.n = n;
+        self
+    }
+
     pub fn set_cache(&mut self, cache: Arc<RwLock<NDJsonCache<Song>>>) {
+        self.edited();
         self.cache = Some(cache.clone());
         for item in &mut self.list {
             if let ConstructorItem::Operation(op) = item {
@@ -289,46 +1050,444 @@ impl SongOpConstructor {
         }
     }
 
+    pub fn is_enabled(&self) -> bool {
+        self.play.enabled
+    }
+
+    /// How often the item at the index is picked, in "Weighted Random" groups
+    pub fn weight(&self, idx: usize) -> u32 {
+        self.play.weight(idx)
+    }
+
+    pub fn set_weight(&mut self, idx: usize, weight: u32) {
+        self.edited();
+        if self.play.weights.len() <= idx {
+            self.play.weights.resize(idx + 1, 1);
+        }
+        self.play.weights[idx] = weight;
+    }
+
+    /// Removes the item along with its weight
+    fn remove_item(&mut self, idx: usize) -> ConstructorItem {
+        if idx < self.play.weights.len() {
+            self.play.weights.remove(idx);
+        }
+        self.list.remove(idx)
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Marks the view data as stale, so the next view reads the song cache again
+    pub fn touch(&mut self) {
+        self.revision = next_revision();
+    }
+
+    /// Marks what the tree holds or plays as changed, so its summaries are made again too
+    pub fn edited(&mut self) {
+        self.touch();
+        self.structure = next_revision();
+    }
+
+    /// Gets the display data of every song in the tree
+    fn collect_song_data(&self) -> HashMap<SongKey, SongData> {
+        let songs: HashSet<String> = self.all_song_keys_rec().cloned().collect();
+        let map: HashMap<String, _> = match &self.cache {
+            Some(lock) => lock.read().fetch_existing(&songs),
+            None => HashMap::new(),
+        };
+
+        songs
+            .into_iter()
+            .map(|key| {
+                let data = match map.get(&key) {
+                    Some(arc) => arc.read().as_data(),
+                    None => SongData::mystery_with_title(key.clone()),
+                };
+                (key, data)
+            })
+            .collect()
+    }
+
+    /// A deep copy of the tree with new widget ids, so it can exist next to the original
+    pub fn with_fresh_ids(&self) -> Self {
+        Self {
+            id: ItemId::default(),
+            play: self.play.clone(),
+            list: self
+                .list
+                .iter()
+                .map(|item| match item {
+                    ConstructorItem::Song(key, _) => {
+                        ConstructorItem::Song(key.clone(), ItemId::default())
+                    }
+                    ConstructorItem::Operation(op) => {
+                        ConstructorItem::Operation(op.with_fresh_ids())
+                    }
+                })
+                .collect(),
+            cache: self.cache.clone(),
+            collapsible: self.collapsible,
+            collapsed: false,
+            filter: None,
+            filter_collapsed: None,
+            seconds_draft: None,
+            revision: next_revision(),
+            structure: next_revision(),
+            data_cache: SongDataCache::default(),
+            summary_cache: SummaryCache::default(),
+            n_input: NInputId::default(),
+            n_stepper: n_stepper(),
+        }
+    }
+
+    /// Whether the trees have the same items and ids, with the same operations, counts,
+    /// durations, weights and groups turned on. What's collapsed isn't compared.
+    pub fn same_tree(&self, other: &SongOpConstructor) -> bool {
+        WId::from(self.id.0.clone()) == WId::from(other.id.0.clone())
+            && self.play.operation == other.play.operation
+            && self.play.n == other.play.n
+            && self.play.seconds == other.play.seconds
+            && self.play.enabled == other.play.enabled
+            && (0..self.list.len()).all(|idx| self.weight(idx) == other.weight(idx))
+            && self.list.len() == other.list.len()
+            && self.list.iter().zip(&other.list).all(|pair| match pair {
+                (ConstructorItem::Song(a, a_id), ConstructorItem::Song(b, b_id)) => {
+                    a == b && WId::from(a_id.0.clone()) == WId::from(b_id.0.clone())
+                }
+                (ConstructorItem::Operation(a), ConstructorItem::Operation(b)) => a.same_tree(b),
+                _ => false,
+            })
+    }
+
+    /// Copies the group at the path out of the tree, removing it from the tree if `moved`.
+    /// Returns None if the path isn't a group.
+    pub fn promote(&mut self, path: Vec<usize>, moved: bool) -> Option<SongOpConstructor> {
+        let group = match self.item_at_path(path.clone().into())? {
+            ConstructorItem::Operation(op) => op.with_fresh_ids(),
+            ConstructorItem::Song(_, _) => return None,
+        };
+        if moved {
+            self.pop_path(path.into());
+        }
+        Some(group)
+    }
+
+    /// Where the item at the path is in the built op, which leaves out the groups that are off.
+    /// None if the item is in one of them, or isn't there.
+    pub fn played_path(&self, path: &[usize]) -> Option<Vec<usize>> {
+        let (idx, rest) = match path.split_first() {
+            Some(split) => split,
+            None => return Some(vec![]),
+        };
+        let item = self.list.get(*idx)?;
+        if !plays(item) {
+            return None;
+        }
+        let skipped = self.list[..*idx].iter().filter(|item| !plays(item)).count();
+        let mut played = vec![idx - skipped];
+        if let ConstructorItem::Operation(op) = item {
+            played.extend(op.played_path(rest)?);
+        }
+        Some(played)
+    }
+
+    /// Where the item at the path of the built op is in the tree, the reverse of `played_path`
+    pub fn tree_path(&self, played: &[usize]) -> Option<Vec<usize>> {
+        let (idx, rest) = match played.split_first() {
+            Some(split) => split,
+            None => return Some(vec![]),
+        };
+        let (found, item) = self
+            .list
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| plays(item))
+            .nth(*idx)?;
+        let mut path = vec![found];
+        if let ConstructorItem::Operation(op) = item {
+            path.extend(op.tree_path(rest)?);
+        }
+        Some(path)
+    }
+
+    /// The item at the path of the built op
+    pub fn played_item(&self, played: &[usize]) -> Option<&ConstructorItem> {
+        self.item_at_path(self.tree_path(played)?.into())
+    }
+
+    /// Why the tree can't be played, the first problem found in it
+    pub fn problem(&self) -> Option<String> {
+        let all_off = !self.list.is_empty() && !self.list.iter().any(plays);
+        match all_off {
+            true => Some("A group has all of its items turned off, turn one on".into()),
+            false => self
+                .list
+                .iter()
+                .filter_map(|item| match item {
+                    ConstructorItem::Operation(op) if op.play.enabled => Some(op),
+                    _ => None,
+                })
+                .find_map(SongOpConstructor::problem)
+                .or_else(|| self.build().problem()),
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.problem().is_none()
+    }
+
+    /// The group at the path, with the empty path being this one
+    pub fn group_at_path(&self, path: &[usize]) -> Option<&SongOpConstructor> {
+        if path.is_empty() {
+            return Some(self);
+        }
+        match self.item_at_path(path.to_vec().into())? {
+            ConstructorItem::Operation(op) => Some(op),
+            ConstructorItem::Song(_, _) => None,
+        }
+    }
+
+    fn group_at_path_mut(&mut self, path: &[usize]) -> Option<&mut SongOpConstructor> {
+        if path.is_empty() {
+            return Some(self);
+        }
+        match self.item_at_path_mut(path.to_vec().into())? {
+            ConstructorItem::Operation(op) => Some(op),
+            ConstructorItem::Song(_, _) => None,
+        }
+    }
+
+    /// Takes the item at the path out along with its weight, so it can be moved with it
+    pub fn take_path(&mut self, path: &[usize]) -> Option<(ConstructorItem, u32)> {
+        let (idx, parent) = path.split_last()?;
+        self.edited();
+        let group = self.group_at_path_mut(parent)?;
+        let weight = group.weight(*idx);
+        let item = group.pop_path([*idx].into())?;
+        Some((item, weight))
+    }
+
+    /// Puts an item taken with [`Self::take_path`] at the path, with its weight
+    pub fn put_at_path(&mut self, path: VecDeque<usize>, item: ConstructorItem, weight: u32) {
+        let id = match &item {
+            ConstructorItem::Song(_, sid) => WId::from(sid.0.clone()),
+            ConstructorItem::Operation(op) => op.widget_id(),
+        };
+        self.push_to_path(path, item);
+        let placed = self.path_to_id(&id);
+        if let Some((idx, parent)) = placed.as_ref().and_then(|path| path.split_last()) {
+            if let Some(group) = self.group_at_path_mut(parent) {
+                group.set_weight(*idx, weight);
+            }
+        }
+    }
+
+    /// Replaces the song at the path with a group holding only that song, leaving its siblings
+    /// where they are. Returns the id of the group's N field, or None if the path isn't a song.
+    pub fn wrap_song(
+        &mut self,
+        path: Vec<usize>,
+        operation: ActualRecursiveOps,
+        n: u32,
+    ) -> Option<text_input::Id> {
+        self.edited();
+        let cache = self.cache.clone();
+        let item = self.item_at_path_mut(path.into())?;
+        if let ConstructorItem::Operation(_) = item {
+            return None;
+        }
+        let mut group = SongOpConstructor {
+            play: GroupPlay {
+                n: n.clamp(MIN_N, MAX_N),
+                ..GroupPlay::new(operation)
+            },
+            ..Default::default()
+        };
+        if let Some(cache) = cache {
+            group.set_cache(cache);
+        }
+        let n_input = group.n_input.0.clone();
+        let song = std::mem::replace(item, ConstructorItem::Operation(group));
+        if let ConstructorItem::Operation(group) = item {
+            group.list.push(song);
+        }
+        Some(n_input)
+    }
+
+    /// Replaces the group at the path with its items. Returns how many items took its place,
+    /// or None if the path isn't a group.
+    pub fn flatten_group(&mut self, path: Vec<usize>) -> Option<usize> {
+        self.edited();
+        let (idx, parent) = path.split_last()?;
+        let parent = self.group_at_path_mut(parent)?;
+        let items = match parent.list.get_mut(*idx)? {
+            ConstructorItem::Operation(group) => std::mem::take(&mut group.list),
+            ConstructorItem::Song(_, _) => return None,
+        };
+        parent.remove_item(*idx);
+        let count = items.len();
+        let after = parent.list.split_off(*idx);
+        parent.list.extend(items);
+        parent.list.extend(after);
+        if *idx < parent.play.weights.len() {
+            parent.play.weights.splice(*idx..*idx, vec![1; count]);
+        }
+        Some(count)
+    }
+
     /// Returns all the song keys found in this constructor recursively
-    pub fn all_song_keys_rec(&self) -> impl Iterator<Item = &SongKey> {
-        self.list.iter().flat_map(|item| item.all_song_keys())
+    pub fn all_song_keys_rec(&self) -> SongKeys {
+        SongKeys {
+            stack: vec![self.list.iter()],
+        }
+    }
+
+    /// The song keys of the first `count` items, recursively
+    pub fn song_keys_before(&self, count: usize) -> SongKeys {
+        SongKeys {
+            stack: vec![self.list[..count.min(self.list.len())].iter()],
+        }
+    }
+
+    /// The songs in the group and how long one pass of it plays. `at` is taken from the tree
+    /// it's shown in, which is edited along with any group in it.
+    fn summary(&self, at: SummaryKey, data: &HashMap<SongKey, SongData>) -> GroupSummary {
+        let mut cache = self.summary_cache.0.lock();
+        if let Some((kept_at, summary)) = *cache {
+            if kept_at == at {
+                return summary;
+            }
+        }
+        let summary = GroupSummary {
+            songs: self.all_song_keys_rec().count(),
+            length: self.pass_length(at, data),
+        };
+        *cache = Some((at, summary));
+        summary
+    }
+
+    fn pass_length(&self, at: SummaryKey, data: &HashMap<SongKey, SongData>) -> PassLength {
+        let played: Vec<PassLength> = self
+            .list
+            .iter()
+            .filter(|item| plays(item))
+            .map(|item| match item {
+                ConstructorItem::Song(key, _) => PassLength::of_song(data.get(key)),
+                ConstructorItem::Operation(op) => op.summary(at, data).length,
+            })
+            .collect();
+        if played.is_empty() {
+            return PassLength::Exact(0.0, 0);
+        }
+        match self.play.operation {
+            ActualRecursiveOps::PlayOnce | ActualRecursiveOps::RandomPlay => {
+                PassLength::sum(played)
+            }
+            // Stretching plays each song N times, which takes as long as looping them
+            ActualRecursiveOps::LoopNTimes | ActualRecursiveOps::Stretch => {
+                PassLength::sum(played).times(self.play.n)
+            }
+            ActualRecursiveOps::SingleRandom => PassLength::average(&played),
+            ActualRecursiveOps::InfiniteLoop
+            | ActualRecursiveOps::InfiniteRandom
+            | ActualRecursiveOps::WeightedRandom => PassLength::Endless,
+            ActualRecursiveOps::LoopUntilDuration => PassLength::Exact(self.play.seconds as f64, 0),
+        }
+    }
+
+    /// e.g. "12 songs, ~3:20"
+    fn summary_text(&self, at: SummaryKey, data: &HashMap<SongKey, SongData>) -> String {
+        let summary = self.summary(at, data);
+        format!("{} songs, {}", summary.songs, summary.length.format())
+    }
+
+    /// A note shown next to the operation, on what the group will play.
+    /// It only looks at the group's own fields, so the tree isn't built on every view. Endless
+    /// groups are told by the summary, which is cached.
+    fn hint(&self) -> Option<&'static str> {
+        let counted = matches![
+            self.play.operation,
+            ActualRecursiveOps::LoopNTimes | ActualRecursiveOps::Stretch
+        ];
+        if counted && self.play.n == 0 {
+            return Some("  plays nothing, N has to be 1 or more");
+        }
+        if self.play.operation == ActualRecursiveOps::LoopUntilDuration && self.play.seconds == 0 {
+            return Some("  plays nothing, give it a length");
+        }
+        if self.list.is_empty() {
+            return Some("  add songs or groups");
+        }
+        if !self.list.iter().any(plays) {
+            return Some("  everything in it is off");
+        }
+        let weighed =
+            (0..self.list.len()).any(|idx| plays(&self.list[idx]) && self.weight(idx) > 0);
+        if self.play.operation == ActualRecursiveOps::WeightedRandom && !weighed {
+            return Some("  plays nothing, give an item a weight of 1 or more");
+        }
+        None
     }
 
+    /// `at` is taken from the tree the group is shown in
     fn header(
         &self,
         scheme: &FullYtmrsScheme,
         closable: bool,
+        at: SummaryKey,
+        data: &HashMap<SongKey, SongData>,
     ) -> Row<'_, SongOpMessage, Theme, Renderer> {
         let pick_style = scheme.pick_list_style.clone();
-        let child: Element<SongOpMessage> = match self.collapsed {
+        let child: Element<SongOpMessage> = match self.is_collapsed() {
             // show the operation controls
             false => row![pick_list(
                 CONSTRUCTOR_CHOICES,
-                Some(self.operation.as_str()),
+                Some(self.play.operation.as_str()),
                 |selection| {
-                    let op = ActualRecursiveOps::from_str(selection).unwrap();
+                    let op = ActualRecursiveOps::from_choice(selection).unwrap();
                     SongOpMessage::ChangeOperation(op)
                 },
             )
             .style(pick_style.update()),]
-            .push_maybe(match self.operation {
+            .push_maybe(match self.play.operation {
                 ActualRecursiveOps::LoopNTimes | ActualRecursiveOps::Stretch => Some(
-                    text_input("1", &(format!("{}", self.n)))
-                        .on_input(verify_n)
-                        .on_paste(verify_n),
+                    self.n_stepper
+                        .view(self.play.n, self.n_input.0.clone())
+                        .map(SongOpMessage::StepN),
                 ),
+                ActualRecursiveOps::LoopUntilDuration => Some(self.duration_input()),
                 _ => None,
             })
+            .push_maybe(
+                self.hint()
+                    .map(|hint| text(hint).vertical_alignment(Vertical::Center)),
+            )
+            .push(
+                text(format!("  {}", self.summary_text(at, data)))
+                    .vertical_alignment(Vertical::Center),
+            )
             .push(Space::with_width(Length::Fill))
+            .push_maybe(match closable {
+                true => None,
+                false => Some(
+                    row![
+                        button("collapse all").on_press(SongOpMessage::CollapseGroups(true)),
+                        button("expand all").on_press(SongOpMessage::CollapseGroups(false)),
+                    ]
+                    .spacing(2),
+                ),
+            })
             .push(button("+").on_press(SongOpMessage::NewGroup))
             .into(),
 
             // Show a basic view of data
             true => row![
                 text(format!(
-                    "  {} - {} songs",
-                    self.operation.as_str(),
-                    self.list.len()
+                    "  {} - {}",
+                    self.play.operation.as_str(),
+                    self.summary_text(at, data)
                 ))
                 .vertical_alignment(Vertical::Center),
                 Space::with_width(Length::Fill)
@@ -338,13 +1497,29 @@ impl SongOpConstructor {
 
         row![]
             .push_maybe(match self.collapsible {
-                true => match self.collapsed {
+                true => match self.is_collapsed() {
                     true => Some(button(">").on_press(SongOpMessage::Uncollapse).width(30)),
                     false => Some(button("v").on_press(SongOpMessage::Collapse).width(30)),
                 },
                 false => None,
             })
             .push(child)
+            // The root can't be promoted, it's already a playlist
+            .push_maybe(match closable {
+                false => None,
+                true => Some(checkbox("on", self.play.enabled).on_toggle(SongOpMessage::Enable)),
+            })
+            .push_maybe(match closable {
+                false => None,
+                true => Some(
+                    row![
+                        button("flatten").on_press(SongOpMessage::Flatten),
+                        button("copy out").on_press(SongOpMessage::Promote(false)),
+                        button("move out").on_press(SongOpMessage::Promote(true)),
+                    ]
+                    .spacing(2),
+                ),
+            })
             .push_maybe(match closable {
                 false => None,
                 true => Some(button("x").on_press(SongOpMessage::CloseSelf)),
@@ -353,57 +1528,166 @@ impl SongOpConstructor {
             .align_items(iced::Alignment::Center)
     }
 
-    fn get_children(&self, scheme: &FullYtmrsScheme) -> Row<'_, SongOpMessage, Theme, Renderer> {
-        let songs: HashSet<String> = self
-            .list
-            .iter()
-            .filter_map(|item| match item {
-                ConstructorItem::Song(key, _) => Some(key),
-                ConstructorItem::Operation(_) => None,
+    /// The ids of the song rows currently shown, in display order
+    pub fn visible_song_ids(&self) -> Vec<WId> {
+        self.visible_rows()
+            .into_iter()
+            .filter_map(|row| match row {
+                VisibleRow::Song(id) => Some(id),
+                VisibleRow::Group(_) => None,
             })
-            .cloned()
-            .collect();
+            .collect()
+    }
 
-        let map: HashMap<String, _> = match &self.cache {
-            Some(lock) => {
-                let c = lock.read();
-                c.fetch_existing(&songs)
+    /// The song rows and the headers of the groups in the tree currently shown, in display
+    /// order. The tree's own header isn't one of them.
+    pub fn visible_rows(&self) -> Vec<VisibleRow> {
+        let mut rows = vec![];
+        self.collect_visible_rows(&mut rows);
+        rows
+    }
+
+    fn collect_visible_rows(&self, rows: &mut Vec<VisibleRow>) {
+        if self.is_collapsed() {
+            return;
+        }
+        for item in &self.list {
+            match item {
+                ConstructorItem::Song(key, _) if self.hides(key) => {}
+                ConstructorItem::Song(_, sid) => {
+                    rows.push(VisibleRow::Song(WId::from(sid.0.clone())))
+                }
+                ConstructorItem::Operation(op) => {
+                    rows.push(VisibleRow::Group(op.widget_id()));
+                    op.collect_visible_rows(rows);
+                }
             }
-            None => HashMap::new(),
-        };
+        }
+    }
+
+    /// The keys of the songs whose rows are shown, in the order they're shown
+    pub fn visible_song_keys(&self) -> Vec<&SongKey> {
+        let mut keys = vec![];
+        self.collect_visible_song_keys(&mut keys);
+        keys
+    }
+
+    fn collect_visible_song_keys<'a>(&'a self, keys: &mut Vec<&'a SongKey>) {
+        if self.is_collapsed() {
+            return;
+        }
+        for item in &self.list {
+            match item {
+                ConstructorItem::Song(key, _) if self.hides(key) => {}
+                ConstructorItem::Song(key, _) => keys.push(key),
+                ConstructorItem::Operation(op) => op.collect_visible_song_keys(keys),
+            }
+        }
+    }
+
+    /// The widget id of the first song in the tree, including collapsed groups
+    pub fn first_song_id(&self) -> Option<WId> {
+        self.list.iter().find_map(|item| match item {
+            ConstructorItem::Song(_, sid) => Some(WId::from(sid.0.clone())),
+            ConstructorItem::Operation(op) => op.first_song_id(),
+        })
+    }
+
+    /// Gets the display data of the song with the given widget id
+    pub fn song_data(&self, id: &WId) -> Option<SongData> {
+        let path = self.path_to_id(id)?;
+        match self.item_at_path(path.into())? {
+            ConstructorItem::Song(key, _) => Some(
+                match self
+                    .cache
+                    .as_ref()
+                    .and_then(|c| c.read().items().get(key).cloned())
+                {
+                    Some(song) => song.read().as_data(),
+                    None => SongData::mystery_with_title(key.clone()),
+                },
+            ),
+            ConstructorItem::Operation(_) => None,
+        }
+    }
+
+    /// How tall the view of the group is taken to be, to know which of its rows are on screen
+    pub fn height(&self) -> f32 {
+        HEADER_HEIGHT + self.child_heights().iter().sum::<f32>()
+    }
+
+    fn child_heights(&self) -> Vec<f32> {
+        match self.is_collapsed() {
+            true => vec![],
+            false => self
+                .list
+                .iter()
+                .map(|item| match item {
+                    ConstructorItem::Song(key, _) if self.hides(key) => 0.0,
+                    ConstructorItem::Song(..) => ROW_HEIGHT,
+                    ConstructorItem::Operation(op) => op.height(),
+                })
+                .collect(),
+        }
+    }
 
-        let items = self.list.iter().enumerate().map(|(idx, item)| match item {
+    /// Only the children in the span are built, the span being seen from the top of the list
+    fn get_children<'a>(
+        &'a self,
+        scheme: &FullYtmrsScheme,
+        focused: Option<&WId>,
+        menu: Option<&WId>,
+        at: SummaryKey,
+        data: &HashMap<SongKey, SongData>,
+        span: Span,
+    ) -> Row<'a, SongOpMessage, Theme, Renderer> {
+        let row_of = |idx: usize, span: Span| match &self.list[idx] {
             ConstructorItem::Song(key, sid) => {
-                let data = {
-                    match map.get(key) {
-                        Some(arc) => {
-                            let x = arc.read();
-                            x.as_data()
-                        }
-                        None => SongData::mystery_with_title(key.clone()),
-                    }
+                let data = match data.get(key) {
+                    Some(data) => data.clone(),
+                    None => SongData::mystery_with_title(key.clone()),
                 };
                 let wid = WId::from(sid.0.clone());
                 let swid = WId::from(sid.0.clone());
-                let song = Element::new(data.row(true, true))
+                let wrap_id = WId::from(sid.0.clone());
+                let menu_id = WId::from(sid.0.clone());
+                let downloaded = data.state.removable_download();
+                let style = scheme
+                    .focus_style
+                    .apply(Default::default(), focused == Some(&wid));
+                let filter = self.filter.as_ref().map(|applied| &applied.filter);
+                let song = Element::new(data.row_highlighting(true, true, filter))
                     .map(move |_| SongOpMessage::SongClicked(swid.clone()));
 
-                container(
+                let row = container(
                     row![
                         droppable(song)
                             .drag_mode(false, true)
                             .drag_hide(true)
                             .on_single_click(SongOpMessage::SongClicked(wid.clone()))
                             .on_drop(move |pt, rec| SongOpMessage::Dropped(wid.clone(), pt, rec)),
+                        button("wrap").on_press(SongOpMessage::Wrap(wrap_id)),
+                        button("edit").on_press(SongOpMessage::EditSong(key.clone())),
                         button("x").on_press(SongOpMessage::Remove(idx))
                     ]
                     .align_items(iced::Alignment::Center),
                 )
-                .id(sid.0.clone())
-                .into()
+                .style(move |_| style)
+                .id(sid.0.clone());
+                with_menu(
+                    row,
+                    menu == Some(&menu_id),
+                    SongOpMessage::OpenMenu(menu_id.clone()),
+                    &SongAction::PLAYLIST,
+                    downloaded,
+                    move |action| match action {
+                        SongAction::Remove => SongOpMessage::Remove(idx),
+                        action => SongOpMessage::Menu(menu_id.clone(), key.clone(), action),
+                    },
+                )
             }
             ConstructorItem::Operation(constructor) => Element::new(
-                droppable(constructor.view_nested(scheme))
+                droppable(constructor.view_nested(scheme, focused, menu, at, data, span))
                     .drag_mode(false, true)
                     .drag_hide(true)
                     .on_drag(move |_, _| SongOpMessage::Collapse)
@@ -414,48 +1698,298 @@ impl SongOpConstructor {
             .map(move |msg| {
                 SongOpMessage::ItemMessage(idx, CItemMessage::Operation(Box::new(msg)))
             }),
-        });
+        };
+        let item = |idx: usize, span: Span| match (&self.list[idx], &self.play.operation) {
+            (ConstructorItem::Song(key, _), _) if self.hides(key) => Space::with_height(0).into(),
+            (_, ActualRecursiveOps::WeightedRandom) => {
+                row![self.weight_input(idx), row_of(idx, span)]
+                    .align_items(iced::Alignment::Center)
+                    .into()
+            }
+            _ => row_of(idx, span),
+        };
+        let items = windowed(&self.child_heights(), span, item);
 
-        row![
-            Space::with_width(Length::Fixed(8.0)),
-            Column::with_children(items)
-        ]
-        .width(Length::Fill)
+        row![Space::with_width(Length::Fixed(8.0)), items].width(Length::Fill)
     }
 
-    pub fn view(&self, scheme: &FullYtmrsScheme) -> Container<SongOpMessage> {
-        container(
-            column![self.header(scheme, false).width(Length::Fill)]
-                .push_maybe(match self.collapsed {
-                    true => None,
-                    false => Some(self.get_children(scheme)),
-                })
-                .width(Length::Fill),
-        )
-        .id(self.id.0.clone())
+    /// How far the song's row or the group's header is from the top of this group, by the
+    /// heights the view takes. None if it isn't shown.
+    pub fn offset_of(&self, id: &WId) -> Option<f32> {
+        if self.widget_id() == *id {
+            return Some(0.0);
+        }
+        let mut offset = HEADER_HEIGHT;
+        for (item, height) in self.list.iter().zip(self.child_heights()) {
+            match item {
+                ConstructorItem::Song(_, sid)
+                    if height > 0.0 && WId::from(sid.0.clone()) == *id =>
+                {
+                    return Some(offset)
+                }
+                ConstructorItem::Operation(op) => {
+                    if let Some(inner) = op.offset_of(id) {
+                        return Some(offset + inner);
+                    }
+                }
+                ConstructorItem::Song(..) => {}
+            }
+            offset += height;
+        }
+        None
+    }
+
+    fn is_collapsed(&self) -> bool {
+        self.filter_collapsed.unwrap_or(self.collapsed)
+    }
+
+    /// The id of the group's container, which its header is focused by
+    pub fn widget_id(&self) -> WId {
+        WId::from(self.id.0.clone())
+    }
+
+    /// The operation, with the count of the ones that repeat, e.g. "Loop N Times (3)"
+    pub fn label(&self) -> String {
+        match self.play.operation {
+            ActualRecursiveOps::LoopNTimes | ActualRecursiveOps::Stretch => {
+                format!("{} ({})", self.play.operation.as_str(), self.play.n)
+            }
+            ActualRecursiveOps::LoopUntilDuration => {
+                let length = format_minutes(self.play.seconds);
+                format!("{} {length}", self.play.operation.as_str())
+            }
+            _ => self.play.operation.as_str().to_string(),
+        }
+    }
+
+    /// Opens the group with the id, along with every group holding it
+    pub fn expand_to(&mut self, id: &WId) {
+        let path = match self.path_to_id(id) {
+            Some(path) => path,
+            None => return,
+        };
+        for end in 0..=path.len() {
+            if let Some(group) = self.group_at_path_mut(&path[..end]) {
+                group.set_collapsed(false);
+            }
+        }
+    }
+
+    /// While a filter is applied, only how the filter left the group is changed
+    fn set_collapsed(&mut self, collapsed: bool) {
+        match &mut self.filter_collapsed {
+            Some(filtered) => *filtered = collapsed,
+            None => self.collapsed = collapsed,
+        }
+    }
+
+    /// Collapses the group with the id, or opens it if it's collapsed
+    pub fn toggle_group(&mut self, id: &WId) {
+        let path = match self.path_to_id(id) {
+            Some(path) => path,
+            None => return,
+        };
+        if let Some(group) = self.group_at_path_mut(&path) {
+            group.set_collapsed(!group.is_collapsed());
+        }
+    }
+
+    /// Collapses or opens every group inside this one
+    fn collapse_groups(&mut self, collapsed: bool) {
+        for item in &mut self.list {
+            if let ConstructorItem::Operation(op) = item {
+                if op.collapsible {
+                    op.set_collapsed(collapsed);
+                }
+                op.collapse_groups(collapsed);
+            }
+        }
+    }
+
+    /// Whether the song is left out by the filter
+    fn hides(&self, key: &SongKey) -> bool {
+        self.filter
+            .as_ref()
+            .is_some_and(|applied| applied.hides(key))
+    }
+
+    /// Hides the songs that don't match the filter. Groups with songs that match are opened,
+    /// the rest are collapsed.
+    pub fn apply_filter(&mut self, filter: TreeFilter) {
+        let applied = {
+            let cache = self.fresh_song_data();
+            let data = cache.as_ref().map(|(_, data)| data).unwrap();
+            Arc::new(AppliedFilter::new(filter, data))
+        };
+        self.spread_filter(&applied);
+    }
+
+    /// Returns whether anything in the group matched
+    fn spread_filter(&mut self, applied: &Arc<AppliedFilter>) -> bool {
+        let mut found = false;
+        for item in &mut self.list {
+            found |= match item {
+                ConstructorItem::Song(key, _) => !applied.hides(key),
+                ConstructorItem::Operation(op) => op.spread_filter(applied),
+            };
+        }
+        self.filter = Some(applied.clone());
+        self.filter_collapsed = self.collapsible.then_some(!found);
+        found
     }
 
-    pub fn view_nested(&self, scheme: &FullYtmrsScheme) -> Container<SongOpMessage> {
+    /// Shows every song again, with the groups collapsed as they were before the filter
+    pub fn clear_filter(&mut self) {
+        self.filter = None;
+        self.filter_collapsed = None;
+        for item in &mut self.list {
+            if let ConstructorItem::Operation(op) = item {
+                op.clear_filter();
+            }
+        }
+    }
+
+    /// How long a "Loop For" group plays
+    fn duration_input(&self) -> Element<SongOpMessage> {
+        let value = match &self.seconds_draft {
+            Some(draft) => draft.clone(),
+            None => format_minutes(self.play.seconds),
+        };
+        text_input("mm:ss", &value)
+            .on_input(SongOpMessage::TypeDuration)
+            .width(70)
+            .into()
+    }
+
+    /// The weight of the item at the index, for "Weighted Random" groups
+    fn weight_input(&self, idx: usize) -> Element<SongOpMessage> {
+        text_input("0", &self.weight(idx).to_string())
+            .on_input(move |text| SongOpMessage::Weigh(idx, text))
+            .width(40)
+            .into()
+    }
+
+    /// The display data of the songs in the tree, read again only once the tree or the songs
+    /// changed
+    fn fresh_song_data(&self) -> MutexGuard<'_, Option<((u64, u64), HashMap<SongKey, SongData>)>> {
+        let generation = match &self.cache {
+            Some(lock) => lock.read().generation(),
+            None => 0,
+        };
+        let at = (self.revision, generation);
+        let mut cache = self.data_cache.0.lock();
+        if !matches!(*cache, Some((read_at, _)) if read_at == at) {
+            song_data_read();
+            *cache = Some((at, self.collect_song_data()));
+        }
+        cache
+    }
+
+    /// The display data of the song with the key, for views other than the tree's own
+    pub fn cached_song_data(&self, key: &SongKey) -> SongData {
+        let cache = self.fresh_song_data();
+        match cache.as_ref().and_then(|(_, data)| data.get(key)) {
+            Some(data) => data.clone(),
+            None => SongData::mystery_with_title(key.clone()),
+        }
+    }
+
+    /// Every song in the tree in order, with the widget id of its row
+    pub fn songs_with_ids(&self) -> Vec<(&SongKey, WId)> {
+        let mut songs = vec![];
+        for item in &self.list {
+            match item {
+                ConstructorItem::Song(key, sid) => songs.push((key, WId::from(sid.0.clone()))),
+                ConstructorItem::Operation(op) => songs.extend(op.songs_with_ids()),
+            }
+        }
+        songs
+    }
+
+    /// `menu` is the song row whose menu is open
+    pub fn view(
+        &self,
+        scheme: &FullYtmrsScheme,
+        focused: Option<&WId>,
+        menu: Option<&WId>,
+        on_screen: &OnScreen,
+    ) -> Container<SongOpMessage> {
+        let cache = self.fresh_song_data();
+        let ((_, generation), data) = cache.as_ref().unwrap();
+        let span = Span::of(on_screen, self.height()).below(HEADER_HEIGHT);
+        let at = (self.structure, *generation);
+        let children = match self.is_collapsed() {
+            true => None,
+            false => Some(self.get_children(scheme, focused, menu, at, data, span)),
+        };
+
         container(
-            column![self.header(scheme, true).width(Length::Fill)]
-                .push_maybe(match self.collapsed {
-                    true => None,
-                    false => Some(self.get_children(scheme)),
-                })
+            column![self.header(scheme, false, at, data).width(Length::Fill)]
+                .push_maybe(children)
                 .width(Length::Fill),
         )
         .id(self.id.0.clone())
     }
 
+    /// `span` is what's to be built, seen from the top of the group
+    pub fn view_nested<'a>(
+        &'a self,
+        scheme: &FullYtmrsScheme,
+        focused: Option<&WId>,
+        menu: Option<&WId>,
+        at: SummaryKey,
+        data: &HashMap<SongKey, SongData>,
+        span: Span,
+    ) -> Container<'a, SongOpMessage> {
+        let span = span.below(HEADER_HEIGHT);
+        let style = match self.play.enabled {
+            true => container::Style::default(),
+            // Dimmed, so it's clear the group won't play
+            false => container::Style {
+                text_color: Some(Color::from_rgba(1.0, 1.0, 1.0, 0.4)),
+                background: Some(Color::from_rgba(0.0, 0.0, 0.0, 0.35).into()),
+                ..Default::default()
+            },
+        };
+        // The header is focused from the keyboard to collapse the group
+        let header_style = scheme
+            .focus_style
+            .apply(Default::default(), focused == Some(&self.widget_id()));
+        let header = self.header(scheme, true, at, data).width(Length::Fill);
+        let header = container(header).style(move |_| header_style);
+        let children = match self.is_collapsed() {
+            true => None,
+            false => Some(self.get_children(scheme, focused, menu, at, data, span)),
+        };
+        container(column![header].push_maybe(children).width(Length::Fill))
+            .style(move |_| style)
+            .id(self.id.0.clone())
+    }
+
     pub fn insert(&mut self, idx: usize, item: ConstructorItem) {
+        if idx < self.play.weights.len() {
+            self.play.weights.insert(idx, 1);
+        }
         self.list.insert(idx, item)
     }
 
     pub fn update(&mut self, msg: SongOpMessage) -> Option<UpdateResult> {
+        match msg.edits_tree() {
+            true => self.edited(),
+            false => self.touch(),
+        }
         match msg {
             SongOpMessage::CloseSelf => None,
+            SongOpMessage::Promote(moved) => {
+                Some(UpdateResult::Promote(self.id.0.clone().into(), moved))
+            }
+            SongOpMessage::Flatten => Some(UpdateResult::Flatten(self.id.0.clone().into())),
+            SongOpMessage::Wrap(wid) => Some(UpdateResult::Wrap(wid)),
+            SongOpMessage::EditSong(key) => Some(UpdateResult::EditSong(key)),
+            SongOpMessage::OpenMenu(wid) => Some(UpdateResult::OpenMenu(wid)),
+            SongOpMessage::Menu(wid, key, action) => Some(UpdateResult::Menu(wid, key, action)),
             SongOpMessage::Remove(idx) => {
-                self.list.remove(idx);
+                self.remove_item(idx);
                 None
             }
             SongOpMessage::ItemMessage(idx, msg) => {
@@ -467,27 +2001,20 @@ impl SongOpConstructor {
                     ConstructorItem::Operation(op) => match msg {
                         CItemMessage::Operation(somsg) => match *somsg {
                             SongOpMessage::CloseSelf => {
-                                self.list.remove(idx);
+                                self.remove_item(idx);
                                 None
                             }
                             _ => match op.update(*somsg) {
-                                Some(result) => match result {
-                                    UpdateResult::Cm(cm) => {
-                                        Some(UpdateResult::Cm(cm.map(move |msg| {
-                                            SongOpMessage::ItemMessage(
-                                                idx,
-                                                CItemMessage::Operation(Box::new(msg)),
-                                            )
-                                        })))
-                                    }
-                                    UpdateResult::SongClicked(id) => {
-                                        Some(UpdateResult::SongClicked(id))
-                                    }
-                                    UpdateResult::Move(from, to) => {
-                                        Some(UpdateResult::Move(from, to))
-                                    }
-                                },
-                                None => None,
+                                Some(UpdateResult::Cm(cm)) => {
+                                    Some(UpdateResult::Cm(cm.map(move |msg| {
+                                        SongOpMessage::ItemMessage(
+                                            idx,
+                                            CItemMessage::Operation(Box::new(msg)),
+                                        )
+                                    })))
+                                }
+                                // The rest are handled by the app, wherever they came from
+                                result => result,
                             },
                         },
                     },
@@ -499,12 +2026,12 @@ impl SongOpConstructor {
                     ..Default::default()
                 };
                 self.list.push(ConstructorItem::Operation(constructor));
-                println!["Group added"];
-                println!["{:#?}", self.list];
+                debug!["Group added, {} items", self.list.len()];
+                trace!["{:#?}", self.list];
                 None
             }
             SongOpMessage::ChangeOperation(op) => {
-                self.operation = op;
+                self.play.operation = op;
                 None
             }
             SongOpMessage::Dropped(original_id, point, _rec) => {
@@ -529,45 +2056,112 @@ impl SongOpConstructor {
                 }
             }
             SongOpMessage::Collapse => {
-                self.collapsed = true;
+                self.set_collapsed(true);
                 None
             }
             SongOpMessage::Uncollapse => {
-                self.collapsed = false;
+                self.set_collapsed(false);
                 None
             }
-            SongOpMessage::ChangeN(n) => {
-                self.n = n;
+            SongOpMessage::CollapseGroups(collapsed) => {
+                self.collapse_groups(collapsed);
+                None
+            }
+            SongOpMessage::Enable(enabled) => {
+                self.play.enabled = enabled;
+                None
+            }
+            SongOpMessage::Weigh(idx, text) => {
+                // Anything but a number is ignored, clearing the field weighs the item 0
+                match text.parse::<u32>() {
+                    Ok(weight) => self.set_weight(idx, weight),
+                    Err(_) if text.is_empty() => self.set_weight(idx, 0),
+                    Err(_) => {}
+                }
+                None
+            }
+            SongOpMessage::TypeDuration(text) => {
+                if let Some(seconds) = parse_duration(&text) {
+                    self.play.seconds = seconds;
+                }
+                self.seconds_draft = Some(text);
+                None
+            }
+            SongOpMessage::StepN(msg) => {
+                self.n_stepper.update(&mut self.play.n, msg);
                 None
             }
-
-            // Pointer for things like inputting a non-integer value into the "N" field.
-            SongOpMessage::Null => None,
             SongOpMessage::SongClicked(wid) => Some(UpdateResult::SongClicked(wid)),
         }
     }
 
     pub fn build(&self) -> RecursiveSongOp {
-        let children: Vec<RecursiveSongOp> = self
-            .list
-            .iter()
-            .map(|item| match item {
-                ConstructorItem::Song(key, _) => RecursiveSongOp::SinglePlay(key.clone()),
-                ConstructorItem::Operation(op) => op.build(),
-            })
-            .collect();
+        let children = self.list.iter().enumerate().filter(|(_, item)| plays(item));
+        self.play.build(children.map(|(idx, item)| match item {
+            ConstructorItem::Song(key, _) => (idx, RecursiveSongOp::SinglePlay(key.clone())),
+            ConstructorItem::Operation(op) => (idx, op.build()),
+        }))
+    }
+}
+/// Where a path is after the song at `wrapped` was put in a group
+pub fn path_after_wrap(path: &[usize], wrapped: &[usize]) -> Vec<usize> {
+    let mut path = path.to_vec();
+    if path == wrapped {
+        path.push(0);
+    }
+    path
+}
 
-        match &self.operation {
-            ActualRecursiveOps::PlayOnce => RecursiveSongOp::PlayOnce(children),
-            ActualRecursiveOps::LoopNTimes => RecursiveSongOp::LoopNTimes(children, self.n),
-            ActualRecursiveOps::Stretch => RecursiveSongOp::Stretch(children, self.n),
-            ActualRecursiveOps::InfiniteLoop => RecursiveSongOp::InfiniteLoop(children),
-            ActualRecursiveOps::RandomPlay => RecursiveSongOp::RandomPlay(children),
-            ActualRecursiveOps::SingleRandom => RecursiveSongOp::SingleRandom(children),
-            ActualRecursiveOps::InfiniteRandom => RecursiveSongOp::InfiniteRandom(children),
+/// Where a path is after the group at `flattened`, holding `count` items, was flattened
+pub fn path_after_flatten(path: &[usize], flattened: &[usize], count: usize) -> Vec<usize> {
+    let (idx, parent) = match flattened.split_last() {
+        Some(split) => split,
+        None => return path.to_vec(),
+    };
+    let depth = parent.len();
+    if path.len() <= depth || !path.starts_with(parent) {
+        return path.to_vec();
+    }
+    let mut new = path[..depth].to_vec();
+    match path[depth].cmp(idx) {
+        // Before the group
+        std::cmp::Ordering::Less => new.extend(&path[depth..]),
+        // Inside it, moved up a level
+        std::cmp::Ordering::Equal => match path.get(depth + 1) {
+            Some(inner) => {
+                new.push(idx + inner);
+                new.extend(&path[depth + 2..]);
+            }
+            None => new.push(*idx),
+        },
+        // After it, shifted by the items that took its place
+        std::cmp::Ordering::Greater => {
+            new.push(path[depth] + count - 1);
+            new.extend(&path[depth + 1..]);
         }
     }
+    new
 }
+
+/// Where a path is after the item at `removed` was taken out of the tree.
+/// None if the path was inside it.
+pub fn path_after_removal(path: &[usize], removed: &[usize]) -> Option<Vec<usize>> {
+    if path.starts_with(removed) {
+        return None;
+    }
+    let (idx, parent) = match removed.split_last() {
+        Some(split) => split,
+        None => return Some(path.to_vec()),
+    };
+    let depth = parent.len();
+    let mut path = path.to_vec();
+    // Items after it in the same group move up into its place
+    if path.len() > depth && path.starts_with(parent) && path[depth] > *idx {
+        path[depth] -= 1;
+    }
+    Some(path)
+}
+
 impl From<Vec<ConstructorItem>> for SongOpConstructor {
     fn from(value: Vec<ConstructorItem>) -> Self {
         Self::new(ActualRecursiveOps::PlayOnce, value, None)
@@ -575,11 +2169,13 @@ impl From<Vec<ConstructorItem>> for SongOpConstructor {
 }
 impl TreeDirected for SongOpConstructor {
     fn push_to_path(&mut self, mut pth: VecDeque<usize>, item: ConstructorItem) {
+        self.edited();
         let next_idx = pth.pop_front();
         match next_idx {
             None => {
                 self.list.push(item);
             }
+            Some(_) if self.list.is_empty() => self.list.push(item),
             Some(next_idx) => {
                 let list_len = self.list.len();
                 let subitem = &mut self.list[next_idx.min(list_len - 1)];
@@ -592,14 +2188,15 @@ impl TreeDirected for SongOpConstructor {
     }
 
     fn pop_path(&mut self, mut pth: VecDeque<usize>) -> Option<ConstructorItem> {
+        self.edited();
         let next_idx = pth.pop_front()?;
         let subitem = &mut self.list[next_idx];
         match subitem {
-            ConstructorItem::Song(_, _) => Some(self.list.remove(next_idx)),
+            ConstructorItem::Song(_, _) => Some(self.remove_item(next_idx)),
             ConstructorItem::Operation(_) => {
                 println!["PATH:{:?}", pth];
                 if pth.is_empty() {
-                    Some(self.list.remove(next_idx))
+                    Some(self.remove_item(next_idx))
                 } else {
                     subitem.pop_path(pth)
                 }
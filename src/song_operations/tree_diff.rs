@@ -0,0 +1,392 @@
+use std::fmt::Display;
+
+use iced::advanced::widget::Id as WId;
+
+use crate::settings::SongKey;
+
+use super::{ActualRecursiveOps, ConstructorItem, SongOpConstructor};
+
+/// Where a group is in its tree, as the indices of the items leading to it
+pub type GroupPath = Vec<usize>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TreeChange {
+    Added {
+        key: SongKey,
+        group: GroupPath,
+    },
+    Removed {
+        key: SongKey,
+        group: GroupPath,
+    },
+    /// The song left one group and showed up in another
+    Moved {
+        key: SongKey,
+        from: GroupPath,
+        to: GroupPath,
+    },
+    /// The group's operation changed
+    Retyped {
+        group: GroupPath,
+        from: ActualRecursiveOps,
+        to: ActualRecursiveOps,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct Change {
+    pub kind: TreeChange,
+    /// The row in the new tree the change can be seen at
+    pub row: Option<WId>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TreeDiff {
+    pub changes: Vec<Change>,
+}
+
+struct Group<'a> {
+    path: GroupPath,
+    op: &'a ActualRecursiveOps,
+    songs: Vec<(&'a SongKey, WId)>,
+}
+
+/// Lists the groups of the tree, parents before their children
+fn groups(tree: &SongOpConstructor) -> Vec<Group> {
+    fn visit<'a>(tree: &'a SongOpConstructor, path: GroupPath, out: &mut Vec<Group<'a>>) {
+        let idx = out.len();
+        out.push(Group {
+            path: path.clone(),
+            op: &tree.play.operation,
+            songs: vec![],
+        });
+        for (i, item) in tree.list.iter().enumerate() {
+            match item {
+                ConstructorItem::Song(key, sid) => {
+                    out[idx].songs.push((key, WId::from(sid.0.clone())));
+                }
+                ConstructorItem::Operation(op) => {
+                    let mut path = path.clone();
+                    path.push(i);
+                    visit(op, path, out);
+                }
+            }
+        }
+    }
+    let mut out = vec![];
+    visit(tree, vec![], &mut out);
+    out
+}
+
+fn keys<'a>(group: &Group<'a>) -> Vec<&'a SongKey> {
+    group.songs.iter().map(|(key, _)| *key).collect()
+}
+
+/// How many songs two groups share, counting duplicates
+fn shared(a: &Group, b: &Group) -> usize {
+    let mut b = keys(b);
+    keys(a)
+        .into_iter()
+        .filter(|key| match b.iter().position(|k| k == key) {
+            Some(idx) => {
+                b.swap_remove(idx);
+                true
+            }
+            None => false,
+        })
+        .count()
+}
+
+/// Pairs every new group with the old group it most likely is.
+/// Roots always match. Then identical groups match regardless of where they moved,
+/// and the rest match the old group they share the most songs with, preferring the same place.
+fn match_groups(old: &[Group], new: &[Group]) -> Vec<Option<usize>> {
+    let mut matches = vec![None; new.len()];
+    let mut taken = vec![false; old.len()];
+    matches[0] = Some(0);
+    taken[0] = true;
+
+    for (n, group) in new.iter().enumerate().skip(1) {
+        let identical = old
+            .iter()
+            .enumerate()
+            .position(|(o, old)| !taken[o] && old.op == group.op && keys(old) == keys(group));
+        if let Some(o) = identical {
+            matches[n] = Some(o);
+            taken[o] = true;
+        }
+    }
+
+    for (n, group) in new.iter().enumerate() {
+        if matches[n].is_some() {
+            continue;
+        }
+        let best = old
+            .iter()
+            .enumerate()
+            .filter(|(o, _)| !taken[*o])
+            .map(|(o, old)| (o, shared(old, group), old.path == group.path))
+            .filter(|(_, shared, same_place)| *shared > 0 || *same_place)
+            .max_by_key(|(o, shared, same_place)| (*shared, *same_place, usize::MAX - o));
+        if let Some((o, _, _)) = best {
+            matches[n] = Some(o);
+            taken[o] = true;
+        }
+    }
+    matches
+}
+
+/// Compares the structure of two trees, matching songs by key.
+/// Duplicates of a key are paired up in order, so only the extra copies count as changes.
+pub fn diff(old: &SongOpConstructor, new: &SongOpConstructor) -> TreeDiff {
+    let old_groups = groups(old);
+    let new_groups = groups(new);
+    let matches = match_groups(&old_groups, &new_groups);
+
+    let mut changes = vec![];
+    let mut old_left: Vec<Vec<&SongKey>> = old_groups.iter().map(keys).collect();
+    let mut new_left: Vec<(&SongKey, usize, WId)> = vec![];
+
+    for (n, group) in new_groups.iter().enumerate() {
+        match matches[n] {
+            Some(o) => {
+                if old_groups[o].op != group.op {
+                    changes.push(Change {
+                        kind: TreeChange::Retyped {
+                            group: group.path.clone(),
+                            from: old_groups[o].op.clone(),
+                            to: group.op.clone(),
+                        },
+                        row: None,
+                    });
+                }
+                for (key, row) in &group.songs {
+                    match old_left[o].iter().position(|k| k == key) {
+                        Some(idx) => {
+                            old_left[o].remove(idx);
+                        }
+                        None => new_left.push((*key, n, row.clone())),
+                    }
+                }
+            }
+            None => {
+                new_left.extend(group.songs.iter().map(|(key, row)| (*key, n, row.clone())));
+            }
+        }
+    }
+
+    for (key, n, row) in new_left {
+        let from = old_left.iter_mut().enumerate().find_map(|(o, left)| {
+            let idx = left.iter().position(|k| *k == key)?;
+            left.remove(idx);
+            Some(o)
+        });
+        let to = new_groups[n].path.clone();
+        let kind = match from {
+            Some(o) => TreeChange::Moved {
+                key: key.clone(),
+                from: old_groups[o].path.clone(),
+                to,
+            },
+            None => TreeChange::Added {
+                key: key.clone(),
+                group: to,
+            },
+        };
+        changes.push(Change {
+            kind,
+            row: Some(row),
+        });
+    }
+
+    for (o, left) in old_left.into_iter().enumerate() {
+        changes.extend(left.into_iter().map(|key| Change {
+            kind: TreeChange::Removed {
+                key: key.clone(),
+                group: old_groups[o].path.clone(),
+            },
+            row: None,
+        }));
+    }
+
+    TreeDiff { changes }
+}
+
+impl TreeDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// A one line summary, e.g. "+4 songs, -1, 2 moved"
+impl Display for TreeDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let count =
+            |kind: fn(&TreeChange) -> bool| self.changes.iter().filter(|c| kind(&c.kind)).count();
+        let added = count(|c| matches!(c, TreeChange::Added { .. }));
+        let removed = count(|c| matches!(c, TreeChange::Removed { .. }));
+        let moved = count(|c| matches!(c, TreeChange::Moved { .. }));
+        let retyped = count(|c| matches!(c, TreeChange::Retyped { .. }));
+
+        let mut parts = vec![];
+        if added > 0 {
+            parts.push(format!("+{added} songs"));
+        }
+        if removed > 0 {
+            parts.push(format!("-{removed}"));
+        }
+        if moved > 0 {
+            parts.push(format!("{moved} moved"));
+        }
+        if retyped > 0 {
+            parts.push(format!("{retyped} groups changed"));
+        }
+        match parts.is_empty() {
+            true => f.write_str("no changes"),
+            false => f.write_str(&parts.join(", ")),
+        }
+    }
+}
+
+impl Display for TreeChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TreeChange::Added { key, group } => write!(f, "+ {key} in group {group:?}"),
+            TreeChange::Removed { key, group } => write!(f, "- {key} from group {group:?}"),
+            TreeChange::Moved { key, from, to } => {
+                write!(f, "{key} moved from group {from:?} to {to:?}")
+            }
+            TreeChange::Retyped { group, from, to } => {
+                write!(f, "group {group:?}: {} -> {}", from.as_str(), to.as_str())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::song_operations::{ActualRecursiveOps, ConstructorItem, SongOpConstructor};
+
+    use super::{diff, TreeChange};
+
+    fn song(key: &str) -> ConstructorItem {
+        ConstructorItem::from(key.to_string())
+    }
+
+    fn group(op: ActualRecursiveOps, items: Vec<ConstructorItem>) -> ConstructorItem {
+        ConstructorItem::Operation(SongOpConstructor::new(op, items, None))
+    }
+
+    fn tree(items: Vec<ConstructorItem>) -> SongOpConstructor {
+        SongOpConstructor::from(items)
+    }
+
+    fn kinds(old: &SongOpConstructor, new: &SongOpConstructor) -> Vec<TreeChange> {
+        diff(old, new).changes.into_iter().map(|c| c.kind).collect()
+    }
+
+    #[test]
+    fn additions_and_removals() {
+        let old = tree(vec![song("a"), song("b")]);
+        let new = tree(vec![song("b"), song("c"), song("d")]);
+        assert_eq![
+            kinds(&old, &new),
+            vec![
+                TreeChange::Added {
+                    key: "c".into(),
+                    group: vec![]
+                },
+                TreeChange::Added {
+                    key: "d".into(),
+                    group: vec![]
+                },
+                TreeChange::Removed {
+                    key: "a".into(),
+                    group: vec![]
+                },
+            ]
+        ];
+        assert_eq![diff(&old, &new).to_string(), "+2 songs, -1"];
+        assert![diff(&old, &old.clone()).is_empty()];
+    }
+
+    #[test]
+    fn duplicate_keys_pair_up_in_order() {
+        let old = tree(vec![song("a"), song("a"), song("b")]);
+        let new = tree(vec![song("a"), song("b")]);
+        assert_eq![
+            kinds(&old, &new),
+            vec![TreeChange::Removed {
+                key: "a".into(),
+                group: vec![]
+            }]
+        ];
+
+        // One of the copies moving into the group isn't an addition and a removal
+        let old = tree(vec![
+            song("a"),
+            song("a"),
+            group(ActualRecursiveOps::PlayOnce, vec![]),
+        ]);
+        let new = tree(vec![
+            song("a"),
+            group(ActualRecursiveOps::PlayOnce, vec![song("a")]),
+        ]);
+        assert_eq![
+            kinds(&old, &new),
+            vec![TreeChange::Moved {
+                key: "a".into(),
+                from: vec![],
+                to: vec![1],
+            }]
+        ];
+    }
+
+    #[test]
+    fn reordered_groups_are_not_changes() {
+        let first = || group(ActualRecursiveOps::PlayOnce, vec![song("x"), song("y")]);
+        let second = |items| group(ActualRecursiveOps::RandomPlay, items);
+
+        let old = tree(vec![first(), second(vec![song("z")])]);
+        let new = tree(vec![second(vec![song("z")]), first()]);
+        assert![diff(&old, &new).is_empty()];
+
+        // A reordered group that was also edited still matches the group it came from
+        let new = tree(vec![second(vec![song("z"), song("w")]), first()]);
+        assert_eq![
+            kinds(&old, &new),
+            vec![TreeChange::Added {
+                key: "w".into(),
+                group: vec![0]
+            }]
+        ];
+    }
+
+    #[test]
+    fn retyped_groups_and_moves_between_groups() {
+        let old = tree(vec![
+            group(ActualRecursiveOps::PlayOnce, vec![song("a"), song("b")]),
+            group(ActualRecursiveOps::PlayOnce, vec![song("c")]),
+        ]);
+        let new = tree(vec![
+            group(ActualRecursiveOps::InfiniteLoop, vec![song("a")]),
+            group(ActualRecursiveOps::PlayOnce, vec![song("c"), song("b")]),
+        ]);
+        assert_eq![
+            kinds(&old, &new),
+            vec![
+                TreeChange::Retyped {
+                    group: vec![0],
+                    from: ActualRecursiveOps::PlayOnce,
+                    to: ActualRecursiveOps::InfiniteLoop,
+                },
+                TreeChange::Moved {
+                    key: "b".into(),
+                    from: vec![0],
+                    to: vec![1],
+                },
+            ]
+        ];
+        assert_eq![diff(&old, &new).to_string(), "1 moved, 1 groups changed"];
+    }
+}
@@ -0,0 +1,105 @@
+//! Narrows the playlist's tree down to the songs matching what's typed in its filter box.
+//! Songs are matched on their key and their cached title, channel and artists.
+
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+};
+
+use crate::{settings::SongKey, song::SongData};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TreeFilter {
+    /// Lowercased
+    words: Vec<String>,
+}
+
+impl TreeFilter {
+    /// None when there's nothing to filter by
+    pub fn new(query: &str) -> Option<Self> {
+        let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        match words.is_empty() {
+            true => None,
+            false => Some(Self { words }),
+        }
+    }
+
+    /// Whether every word appears in the song's key or text, ignoring case
+    pub fn matches(&self, key: &SongKey, data: Option<&SongData>) -> bool {
+        let mut haystack = vec![key.as_str()];
+        if let Some(data) = data {
+            haystack.extend([data.title.as_str(), data.channel.as_str()]);
+            haystack.extend(data.artists.iter().flatten().map(String::as_str));
+        }
+        let haystack = haystack.join("\n").to_lowercase();
+        self.words.iter().all(|word| haystack.contains(word))
+    }
+
+    /// Where the first of the words is in the line, to highlight it
+    pub fn highlight(&self, line: &str) -> Option<Range<usize>> {
+        let lower = line.to_lowercase();
+        // Lowercasing changed where the characters are, so the match can't be placed
+        if lower.len() != line.len() {
+            return None;
+        }
+        self.words
+            .iter()
+            .filter_map(|word| {
+                lower
+                    .find(word.as_str())
+                    .map(|start| start..start + word.len())
+            })
+            .filter(|range| line.is_char_boundary(range.start) && line.is_char_boundary(range.end))
+            .min_by_key(|range| range.start)
+    }
+}
+
+/// The filter along with the songs it left out, shared by every group of the tree.
+/// Songs added since it was applied aren't left out.
+#[derive(Debug)]
+pub struct AppliedFilter {
+    pub filter: TreeFilter,
+    hidden: HashSet<SongKey>,
+}
+
+impl AppliedFilter {
+    pub fn new(filter: TreeFilter, data: &HashMap<SongKey, SongData>) -> Self {
+        let hidden = data
+            .iter()
+            .filter(|(key, data)| !filter.matches(key, Some(data)))
+            .map(|(key, _)| key.clone())
+            .collect();
+        Self { filter, hidden }
+    }
+
+    pub fn hides(&self, key: &SongKey) -> bool {
+        self.hidden.contains(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TreeFilter;
+    use crate::song::SongData;
+
+    #[test]
+    fn every_word_has_to_match() {
+        let mut data = SongData::mystery_with_title("Blue Harbor".into());
+        data.artists = Some(vec!["The Lights".into()]);
+        let key = "abc".to_string();
+        let filter = |query| TreeFilter::new(query).unwrap();
+        assert![filter("harbor LIGHTS").matches(&key, Some(&data))];
+        assert![!filter("harbor night").matches(&key, Some(&data))];
+        // Songs that aren't cached can still be found by their key
+        assert![filter("ab").matches(&key, None)];
+        assert_eq![TreeFilter::new("  "), None];
+    }
+
+    #[test]
+    fn the_first_word_found_is_highlighted() {
+        let filter = TreeFilter::new("lights blue").unwrap();
+        assert_eq![filter.highlight("Blue Harbor Lights"), Some(0..4)];
+        assert_eq![filter.highlight("Harbor Lights"), Some(7..13)];
+        assert_eq![filter.highlight("Harbor"), None];
+    }
+}
@@ -11,6 +11,10 @@ pub fn argb_to_color(argb: Argb) -> Color {
     }
 }
 
+pub fn argb_to_rgba(argb: Argb) -> [u8; 4] {
+    [argb.red, argb.green, argb.blue, argb.alpha]
+}
+
 pub fn pixel_to_argb(pixel: image::Rgba<u8>) -> Argb {
     Argb {
         red: pixel.0[0],
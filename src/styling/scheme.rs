@@ -1,17 +1,16 @@
-use std::{thread, time::SystemTime};
+use std::time::SystemTime;
 
 use iced::{
     gradient::{ColorStop, Linear},
-    widget::image::Handle,
     Background, Color, Degrees, Gradient,
 };
 
 use crate::{
     styling::{
-        ease_out_cubic, interpolate_color, PickListStyle, PickMenuStyle, PlaybackButtonStyle,
-        ScrollableStyle, SongStyle,
+        ease_out_cubic, interpolate_color, FocusStyle, PickListStyle, PickMenuStyle,
+        PlaybackButtonStyle, ScrollableStyle, SongStyle,
     },
-    BACKGROUND_TRANSITION_DURATION, BACKGROUND_TRANSITION_RATE,
+    BACKGROUND_TRANSITION_DURATION,
 };
 
 use ::{
@@ -20,7 +19,7 @@ use ::{
     std::path::PathBuf,
 };
 
-use crate::styling::{argb_to_color, pixel_to_argb};
+use crate::styling::{argb_to_color, argb_to_rgba, pixel_to_argb};
 
 pub trait Interpolable {
     /// Interpolates between two colors, with a transition rate.
@@ -59,21 +58,20 @@ impl BasicYtmrsScheme {
         ])))
     }
 
-    pub async fn from_handle(handle: Handle) -> Self {
-        match handle {
-            Handle::Path(_, p) => Self::from_image(p).await,
-            _ => Self::default(),
-        }
-    }
-
-    pub async fn from_image(path: PathBuf) -> Self {
-        // I dont even know if wrapping this in a thread does anything but it
-        // doesnt seem to block the UI so I'm happy
-        let thread = thread::spawn(move || {
-            let mut image = Reader::open(path)
-                .expect("Failed to open")
-                .decode()
-                .expect("Failed to decode image");
+    /// The image's most prominent color as RGBA, None if it can't be read
+    pub async fn primary_color_of(path: PathBuf) -> Option<[u8; 4]> {
+        // Decoding and quantizing take a while, so they're kept off the executor
+        async_std::task::spawn_blocking(move || {
+            let decoded = Reader::open(&path)
+                .map_err(image::ImageError::IoError)
+                .and_then(|reader| reader.decode());
+            let mut image = match decoded {
+                Ok(image) => image,
+                Err(e) => {
+                    println!["Failed to read the thumbnail at {path:?}: {e:?}"];
+                    return None;
+                }
+            };
 
             // Resizing the image speeds up the process. Little benefit keeping it large
             if image.dimensions() > (128, 128) {
@@ -98,10 +96,13 @@ impl BasicYtmrsScheme {
                 None,
             );
 
-            scores[0]
-        });
+            Some(argb_to_rgba(scores[0]))
+        })
+        .await
+    }
 
-        Self::from_argb(thread.join().unwrap()).await
+    pub async fn from_rgba(rgba: [u8; 4]) -> Self {
+        Self::from_argb(pixel_to_argb(image::Rgba(rgba))).await
     }
 
     pub async fn from_argb(argb: Argb) -> Self {
@@ -115,15 +116,6 @@ impl BasicYtmrsScheme {
             back_end_color: argb_to_color(scheme.surface_container_lowest),
         }
     }
-
-    #[inline]
-    pub fn into_full(self) -> FullYtmrsScheme {
-        FullYtmrsScheme {
-            pick_list_style: self.primary_color.into(),
-            colors: self,
-            ..Default::default()
-        }
-    }
 }
 
 impl Interpolable for BasicYtmrsScheme {
@@ -144,6 +136,7 @@ impl Interpolable for BasicYtmrsScheme {
 pub struct FullYtmrsScheme {
     pub colors: BasicYtmrsScheme,
     pub song_appearance: SongStyle,
+    pub focus_style: FocusStyle,
     pub scrollable_style: ScrollableStyle,
     pub pick_list_style: PickListStyle,
     pub pick_menu_style: PickMenuStyle,
@@ -189,54 +182,76 @@ impl SchemeState {
             SchemeState::Finished(f) => &f.0,
         }
     }
-}
 
-pub async fn transition_scheme(state: SchemeState) -> SchemeState {
-    match state {
-        SchemeState::Started(s) => {
-            let now = SystemTime::now();
-
-            let progress = now.duration_since(s.started).unwrap();
-            thread::sleep(BACKGROUND_TRANSITION_RATE);
-
-            SchemeState::Transitioning(Box::new(Transitioning {
-                value: s
-                    .from
-                    .colors
-                    .interpolate(
-                        &s.to,
-                        progress.as_millis() as f32 / BACKGROUND_TRANSITION_RATE.as_millis() as f32,
-                    )
-                    .into_full(),
-                from: s.from.colors,
-                to: s.to,
-                started: s.started,
-            }))
-        }
-        SchemeState::Transitioning(t) => {
-            let now = SystemTime::now();
-            let progress = now.duration_since(t.started).unwrap();
-            if progress < BACKGROUND_TRANSITION_DURATION {
-                thread::sleep(BACKGROUND_TRANSITION_RATE);
-                let actual_progress = (progress.as_millis() as f32
-                    / BACKGROUND_TRANSITION_DURATION.as_millis() as f32)
-                    .clamp(0.0, 1.0);
-                let transitioned = t.from.interpolate(&t.to, actual_progress);
-                SchemeState::Transitioning(Box::new(Transitioning {
-                    value: FullYtmrsScheme {
-                        pick_list_style: transitioned.primary_color.into(),
-                        colors: transitioned,
-                        ..t.value
-                    },
-                    ..*t
-                }))
-            } else {
-                SchemeState::Finished(Box::new(Finished(FullYtmrsScheme {
-                    colors: t.to,
-                    ..t.value
-                })))
-            }
+    pub fn is_finished(&self) -> bool {
+        matches!(self, SchemeState::Finished(_))
+    }
+
+    /// Moves the transition to where it should be at `now`, going only by how long ago it
+    /// started, so late or skipped ticks don't slow it down. Finished ones stay as they are.
+    pub fn advance(self, now: SystemTime) -> SchemeState {
+        let (from, to, value, started) = match self {
+            SchemeState::Started(s) => (s.from.colors.clone(), s.to, s.from, s.started),
+            SchemeState::Transitioning(t) => (t.from, t.to, t.value, t.started),
+            finished @ SchemeState::Finished(_) => return finished,
+        };
+        let progress = now.duration_since(started).unwrap_or_default();
+        if progress >= BACKGROUND_TRANSITION_DURATION {
+            return SchemeState::Finished(Box::new(Finished(FullYtmrsScheme {
+                pick_list_style: to.primary_color.into(),
+                colors: to,
+                ..value
+            })));
         }
-        SchemeState::Finished(_) => todo!(), // Hmmm... we're done. What now?
+        let transitioned = from.interpolate(
+            &to,
+            progress.as_secs_f32() / BACKGROUND_TRANSITION_DURATION.as_secs_f32(),
+        );
+        SchemeState::Transitioning(Box::new(Transitioning {
+            value: FullYtmrsScheme {
+                pick_list_style: transitioned.primary_color.into(),
+                colors: transitioned,
+                ..value
+            },
+            from,
+            to,
+            started,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use iced::Color;
+
+    use super::{BasicYtmrsScheme, FullYtmrsScheme, SchemeState, Started};
+    use crate::BACKGROUND_TRANSITION_DURATION;
+
+    #[test]
+    fn transitions_follow_the_time_since_they_started() {
+        let started = SystemTime::now();
+        let to = BasicYtmrsScheme {
+            primary_color: Color::new(0.0, 0.0, 1.0, 1.0),
+            ..Default::default()
+        };
+        let state = SchemeState::Started(Box::new(Started {
+            from: FullYtmrsScheme::default(),
+            to: to.clone(),
+            started,
+        }));
+
+        let halfway = state.advance(started + BACKGROUND_TRANSITION_DURATION / 2);
+        assert![!halfway.is_finished()];
+        let primary = halfway.first_choice().colors.primary_color;
+        assert![primary != Color::WHITE && primary != to.primary_color];
+
+        let done = halfway.advance(started + BACKGROUND_TRANSITION_DURATION);
+        assert![done.is_finished()];
+        assert_eq![done.first_choice().colors, to];
+        // There's nothing left to do once it's finished
+        let later = done.advance(started + BACKGROUND_TRANSITION_DURATION * 2);
+        assert_eq![later.first_choice().colors, to];
     }
 }
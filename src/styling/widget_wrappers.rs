@@ -30,6 +30,23 @@ impl SongStyle {
     }
 }
 
+/// The high-contrast outline drawn around the row that has keyboard focus
+#[derive(Debug, Clone)]
+pub struct FocusStyle(pub Border);
+impl Default for FocusStyle {
+    fn default() -> Self {
+        Self(Border::rounded(2).with_width(2).with_color(Color::WHITE))
+    }
+}
+impl FocusStyle {
+    pub fn apply(&self, mut style: container::Style, focused: bool) -> container::Style {
+        if focused {
+            style.border = self.0;
+        }
+        style
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ScrollableStyle(pub scrollable::Style);
 impl Default for ScrollableStyle {
@@ -124,6 +141,15 @@ impl PlaybackButtonStyle {
         }
         style
     }
+
+    /// For buttons that turn a mode on and off, outlined while it's on
+    pub fn toggle(self, enabled: bool, status: button::Status) -> button::Style {
+        let mut style = self.update(status);
+        if enabled {
+            style.border = style.border.with_width(1).with_color(Color::WHITE);
+        }
+        style
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -0,0 +1,173 @@
+//! Periodic subscriptions with stable identities.
+//!
+//! iced keeps a subscription alive across `subscription()` calls for as long as its identity
+//! stays the same, and restarts it when the identity changes. A timer whose identity changes
+//! needlessly loses its progress and fires late, and two timers sharing an identity are merged.
+//!
+//! So every periodic subscription should:
+//! - be created with [`every`], with a purpose that's unique to it,
+//! - take its interval from a [`Debounced`] value when that can change at runtime,
+//!   so a value that changes many times in a row only restarts the timer once it settles.
+
+use std::time::{Duration, Instant};
+
+use futures::stream;
+use iced::Subscription;
+
+/// How long a value has to stay the same before it's applied
+pub const SETTLE_TIME: Duration = Duration::from_millis(500);
+
+/// The identity of a periodic subscription: what it's for, and the parameters it runs with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TickerId {
+    pub purpose: &'static str,
+    pub interval: Duration,
+}
+
+/// A subscription that fires every `interval`, identified by its purpose and interval
+pub fn every(purpose: &'static str, interval: Duration) -> Subscription<Instant> {
+    let id = TickerId { purpose, interval };
+    iced::subscription::run_with_id(
+        id,
+        stream::unfold((), move |()| async move {
+            async_std::task::sleep(interval).await;
+            Some((Instant::now(), ()))
+        }),
+    )
+}
+
+/// A value that only takes effect once it stops changing.
+#[derive(Debug, Clone)]
+pub struct Debounced<T> {
+    applied: T,
+    pending: Option<(T, Instant)>,
+}
+
+impl<T: PartialEq> Debounced<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            applied: value,
+            pending: None,
+        }
+    }
+
+    /// The value currently in effect
+    pub fn applied(&self) -> &T {
+        &self.applied
+    }
+
+    /// Requests a new value. A different value restarts the wait, the same one doesn't.
+    pub fn set(&mut self, value: T, now: Instant) {
+        if value == self.applied {
+            self.pending = None;
+            return;
+        }
+        match &self.pending {
+            Some((pending, _)) if *pending == value => {}
+            _ => self.pending = Some((value, now)),
+        }
+    }
+
+    /// Applies the requested value once it has settled. Returns true if the value changed.
+    pub fn settle(&mut self, now: Instant) -> bool {
+        match self.pending.take() {
+            Some((value, since)) if now.saturating_duration_since(since) >= SETTLE_TIME => {
+                self.applied = value;
+                true
+            }
+            pending => {
+                self.pending = pending;
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashSet,
+        time::{Duration, Instant},
+    };
+
+    use super::{Debounced, TickerId, SETTLE_TIME};
+
+    fn id(interval: &Debounced<Duration>) -> TickerId {
+        TickerId {
+            purpose: "cache",
+            interval: *interval.applied(),
+        }
+    }
+
+    #[test]
+    fn identities_follow_purpose_and_parameters() {
+        let a = TickerId {
+            purpose: "cache",
+            interval: Duration::from_secs(20),
+        };
+        assert_eq![
+            a,
+            TickerId {
+                purpose: "cache",
+                interval: Duration::from_secs(20),
+            }
+        ];
+        assert_ne![
+            a,
+            TickerId {
+                purpose: "schedule",
+                ..a
+            }
+        ];
+        assert_ne![
+            a,
+            TickerId {
+                interval: Duration::from_secs(21),
+                ..a
+            }
+        ];
+    }
+
+    #[test]
+    fn dragging_an_interval_rebuilds_once() {
+        let start = Instant::now();
+        let mut interval = Debounced::new(Duration::from_secs(20));
+        let mut ids = HashSet::from([id(&interval)]);
+
+        // Dragging a slider through many values, settling after every step
+        for step in 0..30 {
+            let now = start + Duration::from_millis(step * 50);
+            interval.set(Duration::from_secs(21 + step), now);
+            assert![!interval.settle(now)];
+            ids.insert(id(&interval));
+        }
+        let last = start + Duration::from_millis(29 * 50);
+        assert![interval.settle(last + SETTLE_TIME)];
+        let settled = id(&interval);
+        ids.insert(settled);
+        assert_eq![*interval.applied(), Duration::from_secs(50)];
+
+        // Exactly one new identity
+        assert_eq![ids.len(), 2];
+
+        // Asking for the value in effect keeps the identity
+        interval.set(Duration::from_secs(50), last + SETTLE_TIME * 2);
+        assert![!interval.settle(last + SETTLE_TIME * 4)];
+        assert_eq![id(&interval), settled];
+    }
+
+    #[test]
+    fn repeating_a_pending_value_does_not_restart_the_wait() {
+        let start = Instant::now();
+        let mut interval = Debounced::new(Duration::from_secs(10));
+        interval.set(Duration::from_secs(60), start);
+        interval.set(Duration::from_secs(60), start + SETTLE_TIME / 2);
+        assert![interval.settle(start + SETTLE_TIME)];
+
+        // Going back before it settles cancels the change
+        interval.set(Duration::from_secs(10), start + SETTLE_TIME);
+        interval.set(Duration::from_secs(60), start + SETTLE_TIME);
+        assert![!interval.settle(start + SETTLE_TIME * 3)];
+        assert_eq![*interval.applied(), Duration::from_secs(60)];
+    }
+}
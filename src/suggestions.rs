@@ -0,0 +1,309 @@
+//! Saved songs that match what's being typed into the search, shown under the query before
+//! it's sent. They're matched against an index of the metadata cache, so typing doesn't lock
+//! the cache, and worked out at most once a tick, so typing quickly doesn't redo them for
+//! every character.
+
+use std::time::Duration;
+
+use iced::{
+    keyboard::{self, key::Named},
+    widget::{button, column, row, text, Column, Container},
+    Alignment, Element, Length,
+};
+use reqwest::Url;
+
+use crate::{
+    caching::{BufferedCache, NDJsonCache, RwArc},
+    context_menu::SongAction,
+    search_window::LOCAL_SEARCH_PREFIX,
+    settings::SongKey,
+    song::Song,
+    styling::FullYtmrsScheme,
+    user_input::FocusCursor,
+};
+
+/// How many songs are suggested at most
+pub const MAX_SUGGESTIONS: usize = 8;
+/// How often the suggestions catch up to the query while it's being typed
+pub const SUGGEST_INTERVAL: Duration = Duration::from_millis(150);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suggestion {
+    pub key: SongKey,
+    pub title: String,
+    /// The artists, or the channel when there are none
+    pub detail: String,
+}
+
+/// A song as it's matched, lowercased once when the index is built
+#[derive(Debug, Clone)]
+struct IndexEntry {
+    suggestion: Suggestion,
+    title: String,
+    /// The channel and the artists
+    others: Vec<String>,
+}
+
+impl IndexEntry {
+    fn new(song: &Song) -> Self {
+        let artists = song.artists.iter().flatten();
+        let detail = match &song.artists {
+            Some(artists) if !artists.is_empty() => artists.join(", "),
+            _ => song.channel.clone(),
+        };
+        let mut others = vec![song.channel.to_lowercase()];
+        others.extend(artists.map(|artist| artist.to_lowercase()));
+        Self {
+            suggestion: Suggestion {
+                key: song.id.clone(),
+                title: song.title.clone(),
+                detail,
+            },
+            title: song.title.to_lowercase(),
+            others,
+        }
+    }
+}
+
+/// 3 when the text starts with the word, 2 when a word of the text does, 1 when it only
+/// appears inside one
+fn field_score(text: &str, word: &str) -> Option<u32> {
+    text.match_indices(word)
+        .map(|(at, _)| match text[..at].chars().last() {
+            None => 3,
+            Some(c) if !c.is_alphanumeric() => 2,
+            Some(_) => 1,
+        })
+        .max()
+}
+
+/// How well the song matches the lowercased words of the query, None if one of them isn't
+/// in it. Words found in the title count for more than ones in the channel or artists.
+fn score(entry: &IndexEntry, words: &[String]) -> Option<u32> {
+    words
+        .iter()
+        .map(|word| {
+            let title = field_score(&entry.title, word).map(|score| score * 4);
+            let others = entry
+                .others
+                .iter()
+                .filter_map(|field| field_score(field, word))
+                .max();
+            title.max(others)
+        })
+        .sum()
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Suggestions {
+    index: Vec<IndexEntry>,
+    /// How many songs the cache had when the index was built, None until it's built
+    indexed: Option<usize>,
+    /// The query typed since the suggestions were last worked out
+    pending: Option<String>,
+    shown: Vec<Suggestion>,
+    pub focus: FocusCursor,
+}
+
+impl Suggestions {
+    /// Asks for suggestions for the query, worked out on the next [`Suggestions::refresh`].
+    /// Links and local searches aren't suggested for.
+    pub fn typed(&mut self, query: &str) {
+        let query = query.trim();
+        match query.is_empty()
+            || query.starts_with(LOCAL_SEARCH_PREFIX)
+            || Url::parse(query).is_ok()
+        {
+            true => self.close(),
+            false => self.pending = Some(query.to_string()),
+        }
+    }
+
+    /// Whether the query changed since the suggestions were worked out
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Works out the suggestions for the query typed last. The cache is only locked to build
+    /// the index, which happens when it gained or lost songs since.
+    pub fn refresh(&mut self, cache: &RwArc<NDJsonCache<Song>>) {
+        let query = match self.pending.take() {
+            Some(query) => query,
+            None => return,
+        };
+        {
+            let cache = cache.read();
+            let items = cache.items();
+            if self.indexed != Some(items.len()) {
+                self.index = items
+                    .values()
+                    .map(|song| IndexEntry::new(&song.read()))
+                    .collect();
+                self.indexed = Some(items.len());
+            }
+        }
+
+        let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        let mut scored: Vec<(u32, &IndexEntry)> = self
+            .index
+            .iter()
+            .filter_map(|entry| Some((score(entry, &words)?, entry)))
+            .collect();
+        // The closest titles first
+        scored.sort_by(|(a, x), (b, y)| {
+            b.cmp(a)
+                .then(x.title.len().cmp(&y.title.len()))
+                .then_with(|| x.title.cmp(&y.title))
+        });
+        self.shown = scored
+            .into_iter()
+            .take(MAX_SUGGESTIONS)
+            .map(|(_, entry)| entry.suggestion.clone())
+            .collect();
+        self.focus.clear();
+    }
+
+    /// Hides the suggestions. The index is built again when they're next asked for, to see
+    /// songs that changed in the meantime.
+    pub fn close(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn is_open(&self) -> bool {
+        !self.shown.is_empty()
+    }
+
+    pub fn key(&self, idx: usize) -> Option<&SongKey> {
+        self.shown.get(idx).map(|suggestion| &suggestion.key)
+    }
+
+    /// The key of the suggestion picked with the arrow keys
+    pub fn focused(&mut self) -> Option<&SongKey> {
+        self.focus.clamp(self.shown.len());
+        self.focus.get().and_then(|idx| self.key(idx))
+    }
+
+    /// Moves the focus with the arrow keys
+    pub fn navigate(&mut self, key: &keyboard::Key) {
+        let len = self.shown.len();
+        match key {
+            keyboard::Key::Named(Named::ArrowDown) => self.focus.move_down(len),
+            keyboard::Key::Named(Named::ArrowUp) => self.focus.move_up(len),
+            _ => {}
+        }
+    }
+
+    /// Clicking a suggestion plays it, its button adds it to the playlist
+    pub fn view(&self, scheme: &FullYtmrsScheme) -> Option<Element<(usize, SongAction)>> {
+        if self.shown.is_empty() {
+            return None;
+        }
+        let focused = self.focus.get();
+        let rows = self.shown.iter().enumerate().map(|(idx, suggestion)| {
+            let style = scheme
+                .focus_style
+                .apply(scheme.song_appearance.update(false), focused == Some(idx));
+            let info = column![
+                text(suggestion.title.as_str()),
+                text(suggestion.detail.as_str()).size(12)
+            ];
+            let play = button(info)
+                .width(Length::Fill)
+                .on_press((idx, SongAction::PlayNow));
+            let add = button("+").on_press((idx, SongAction::AddToPlaylist));
+            Container::new(row![play, add].spacing(4).align_items(Alignment::Center))
+                .style(move |_| style)
+                .into()
+        });
+        Some(Column::with_children(rows).spacing(2).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use parking_lot::RwLock;
+
+    use crate::{
+        caching::{readers::LineBasedReader, BufferedCache, NDJsonCache, RwArc, ToRwMapExt},
+        song::Song,
+    };
+
+    use super::Suggestions;
+
+    fn song(id: &str, title: &str, channel: &str, artists: &[&str]) -> (String, Song) {
+        let mut song = Song::basic();
+        song.id = id.into();
+        song.title = title.into();
+        song.channel = channel.into();
+        song.artists = Some(artists.iter().map(|a| a.to_string()).collect());
+        (id.to_string(), song)
+    }
+
+    fn keys(suggestions: &Suggestions) -> Vec<&str> {
+        let shown = suggestions.shown.iter();
+        shown.map(|suggestion| suggestion.key.as_str()).collect()
+    }
+
+    fn cache(dir: &tempfile::TempDir) -> RwArc<NDJsonCache<Song>> {
+        let reader = LineBasedReader::new(dir.path().join("songs.ndjson"));
+        Arc::new(RwLock::new(NDJsonCache::new(reader)))
+    }
+
+    #[test]
+    fn title_matches_come_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = cache(&dir);
+        cache.write().items_mut().extend(
+            [
+                song("channel", "Blue", "Harbor Lights", &[]),
+                song("inside", "Starharbor", "Mara", &[]),
+                song("start", "Harbor Nights", "Theo", &[]),
+                song("word", "The Harbor", "Theo", &["Ines"]),
+                song("other", "Golden River", "Kai", &[]),
+            ]
+            .to_rwmap(),
+        );
+        let mut suggestions = Suggestions::default();
+        suggestions.typed("HARBOR");
+        suggestions.refresh(&cache);
+        assert_eq![keys(&suggestions), ["start", "word", "inside", "channel"]];
+
+        // Every word has to match, in the title or elsewhere
+        suggestions.typed("harbor ines");
+        suggestions.refresh(&cache);
+        assert_eq![keys(&suggestions), ["word"]];
+    }
+
+    #[test]
+    fn only_the_last_query_is_worked_out() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = cache(&dir);
+        let songs = [
+            song("a", "River", "Kai", &[]),
+            song("b", "Riverside", "Kai", &[]),
+        ];
+        cache.write().items_mut().extend(songs.to_rwmap());
+        let mut suggestions = Suggestions::default();
+        for query in ["r", "ri", "riversi"] {
+            suggestions.typed(query);
+        }
+        assert![suggestions.is_pending()];
+        suggestions.refresh(&cache);
+        assert![!suggestions.is_pending()];
+        assert_eq![keys(&suggestions), ["b"]];
+
+        // Songs cached since are indexed on the next refresh
+        let later = [song("c", "Riversong", "Kai", &[])];
+        cache.write().items_mut().extend(later.to_rwmap());
+        suggestions.typed("river");
+        suggestions.refresh(&cache);
+        assert_eq![keys(&suggestions), ["a", "b", "c"]];
+
+        // Links are sent as they are
+        suggestions.typed("https://youtu.be/dQw4w9WgXcQ");
+        assert![!suggestions.is_pending()];
+        assert![!suggestions.is_open()];
+    }
+}
@@ -1,29 +1,195 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
 
-use iced::widget::image::Handle;
-use image::{self, GenericImageView};
+use iced::{
+    advanced::image::Data,
+    widget::{image::Handle, scrollable::Viewport},
+};
+use image::{self, imageops::FilterType, DynamicImage, GenericImageView};
 use reqwest::Url;
+use serde::{Deserialize, Serialize};
 
 use crate::caching::{
-    readers::{CacheReader, FileData, LazyFolderBasedReader},
+    readers::{CacheReader, FileData, FolderFiles, LazyFolderBasedReader},
     IDed,
 };
 
+/// The sizes thumbnails are stored in, smallest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThumbnailSize {
+    /// For song rows
+    Small,
+    /// For the now playing artwork and picking the background's colors
+    Large,
+}
+impl ThumbnailSize {
+    pub const ALL: [ThumbnailSize; 2] = [ThumbnailSize::Small, ThumbnailSize::Large];
+
+    /// The length of a side, images are never scaled up to it
+    pub fn pixels(self) -> u32 {
+        match self {
+            Self::Small => 96,
+            Self::Large => 512,
+        }
+    }
+
+    fn tag(self) -> &'static str {
+        match self {
+            Self::Small => "small",
+            Self::Large => "large",
+        }
+    }
+}
+
+/// The files of a thumbnail's variants, relative to the thumbnails folder
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(from = "StoredFiles")]
+pub struct ThumbnailFiles {
+    pub small: Option<PathBuf>,
+    pub large: Option<PathBuf>,
+}
+
+/// Entries from before there were variants point at a single file, which counts as large
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredFiles {
+    Single(PathBuf),
+    Variants {
+        small: Option<PathBuf>,
+        large: Option<PathBuf>,
+    },
+}
+impl From<StoredFiles> for ThumbnailFiles {
+    fn from(value: StoredFiles) -> Self {
+        match value {
+            StoredFiles::Single(large) => Self {
+                small: None,
+                large: Some(large),
+            },
+            StoredFiles::Variants { small, large } => Self { small, large },
+        }
+    }
+}
+
+impl FolderFiles for ThumbnailFiles {
+    fn files(&self) -> Vec<&PathBuf> {
+        self.small.iter().chain(&self.large).collect()
+    }
+    fn files_mut(&mut self) -> Vec<&mut PathBuf> {
+        self.small.iter_mut().chain(&mut self.large).collect()
+    }
+}
+
+/// Where the variant for a size comes from
+#[derive(Debug, PartialEq)]
+pub enum Pick<'a> {
+    Stored(&'a PathBuf),
+    /// Scaled down from a larger variant, then stored
+    Generate {
+        from: &'a PathBuf,
+    },
+    /// Only a smaller variant exists, which is better than nothing
+    Smaller(&'a PathBuf),
+    Missing,
+}
+
+impl ThumbnailFiles {
+    pub fn get(&self, size: ThumbnailSize) -> Option<&PathBuf> {
+        match size {
+            ThumbnailSize::Small => self.small.as_ref(),
+            ThumbnailSize::Large => self.large.as_ref(),
+        }
+    }
+
+    fn set(&mut self, size: ThumbnailSize, path: PathBuf) {
+        match size {
+            ThumbnailSize::Small => self.small = Some(path),
+            ThumbnailSize::Large => self.large = Some(path),
+        }
+    }
+
+    pub fn pick(&self, size: ThumbnailSize) -> Pick {
+        if let Some(path) = self.get(size) {
+            return Pick::Stored(path);
+        }
+        let (smaller, larger) = ThumbnailSize::ALL.split_at(size as usize);
+        // Scaling down looks right, scaling up doesn't
+        if let Some(from) = larger.iter().skip(1).find_map(|s| self.get(*s)) {
+            return Pick::Generate { from };
+        }
+        match smaller.iter().rev().find_map(|s| self.get(*s)) {
+            Some(path) => Pick::Smaller(path),
+            None => Pick::Missing,
+        }
+    }
+}
+
+/// Where a variant made from `base` is stored, e.g. "id.png" -> "id.small.png"
+pub fn variant_path(base: &Path, size: ThumbnailSize) -> PathBuf {
+    let stem = base.file_stem().unwrap_or_default().to_string_lossy();
+    let stem = stem.split('.').next().unwrap_or_default();
+    base.with_file_name(format!("{stem}.{}.png", size.tag()))
+}
+
+/// Scales the square image down to the size, leaving images that are already small enough alone
+fn scaled(image: &DynamicImage, size: ThumbnailSize) -> DynamicImage {
+    let pixels = size.pixels();
+    match image.width() > pixels {
+        true => image.resize_exact(pixels, pixels, FilterType::Lanczos3),
+        false => image.clone(),
+    }
+}
+
+/// Makes the variant from the stored file at `from`, and returns where it was saved
+fn generate_variant(
+    folder: &Path,
+    from: &Path,
+    size: ThumbnailSize,
+) -> Result<PathBuf, image::ImageError> {
+    let image = image::open(folder.join(from))?;
+    let path = variant_path(from, size);
+    scaled(&image, size).save(folder.join(&path))?;
+    Ok(path)
+}
+
+/// Fetches the image at the url. Error statuses like 404 fail too.
+async fn download(url: Url) -> Result<DynamicImage, String> {
+    let response = reqwest::get(url)
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())?;
+    let bytes = response.bytes().await.map_err(|e| e.to_string())?;
+    image::load_from_memory(&bytes).map_err(|e| e.to_string())
+}
+
+/// Downloads the thumbnails and stores their variants.
+/// Thumbnails that fail to download are left out.
 pub async fn get_images(
     reader: LazyFolderBasedReader,
     urls: Vec<(String, Url)>,
+    size: ThumbnailSize,
 ) -> HashMap<String, Handle> {
     // Generate paths to add to the reader.
     let filepaths = reader
         .generate_paths(urls.len())
         .map(|p| p.with_extension("png"));
 
+    let reader = &reader;
     let futures = urls
         .into_iter()
         .zip(filepaths)
-        .map(|((id, url), fpath)| async {
-            let imgbytes = reqwest::get(url).await.unwrap().bytes().await.unwrap();
-            let mut thumbnail = image::load_from_memory(&imgbytes).unwrap();
+        .map(|((id, url), fpath)| async move {
+            let mut thumbnail = match download(url).await {
+                Ok(thumbnail) => thumbnail,
+                Err(e) => {
+                    println!["Failed to download the thumbnail of {id}: {e}"];
+                    return None;
+                }
+            };
             let (w, h) = thumbnail.dimensions();
 
             // crop it to a square
@@ -32,31 +198,372 @@ pub async fn get_images(
             let top = (h - smaller) / 2;
 
             thumbnail = thumbnail.crop(left, top, smaller, smaller);
-            let full_path = reader.convert_path(&fpath);
 
-            let _ = thumbnail.save(&full_path);
-            (full_path, FileData::new(id, fpath))
+            let mut files = ThumbnailFiles::default();
+            for variant in ThumbnailSize::ALL {
+                let path = variant_path(&fpath, variant);
+                let _ = scaled(&thumbnail, variant).save(reader.convert_path(&path));
+                files.set(variant, path);
+            }
+            Some(FileData::new(id, files))
         });
 
-    let paths: Vec<_> = futures::future::join_all(futures)
+    let filedatas: Vec<FileData<ThumbnailFiles>> = futures::future::join_all(futures)
         .await
         .into_iter()
+        .flatten()
         .collect();
-    let filedatas: Vec<_> = paths.iter().map(|(_, fd)| fd).collect();
-    let index_reader = reader.index_reader;
 
     println![
         "Extending index: {:?}",
-        index_reader.extend(&filedatas, true).await
+        reader.extend(&filedatas, true).await
     ];
 
-    paths
+    filedatas
         .into_iter()
-        .map(|(fp, fdata)| {
-            let path = fp;
-            let handle = Handle::from_path(path);
-
-            (fdata.id().to_string(), handle)
+        .filter_map(|fdata| {
+            let path = fdata.clone().into_data().get(size)?.clone();
+            Some((
+                fdata.id().to_string(),
+                Handle::from_path(reader.convert_path(&path)),
+            ))
         })
         .collect()
 }
+
+/// The files of the ids' thumbnails at the size, making missing variants from larger ones.
+/// Ids without a thumbnail are left out.
+pub async fn resolve(
+    reader: &LazyFolderBasedReader,
+    ids: &HashSet<String>,
+    size: ThumbnailSize,
+) -> HashMap<String, PathBuf> {
+    let items = match reader.read_filter(ids).await {
+        Ok(items) => items,
+        Err(e) => {
+            println!["Failed to read the thumbnail index: {e:?}"];
+            return HashMap::new();
+        }
+    };
+    let entries: Vec<FileData<ThumbnailFiles>> =
+        futures::future::join_all(items.into_iter().map(|(_, f)| f))
+            .await
+            .into_iter()
+            .flatten()
+            .map(|pair| pair.1)
+            .collect();
+
+    reader.counters().hit(entries.len());
+    reader
+        .counters()
+        .miss(ids.len().saturating_sub(entries.len()));
+    let mut paths = HashMap::new();
+    let mut generated = vec![];
+    for entry in entries {
+        let id = entry.id().clone();
+        let mut files = entry.into_data();
+        let path = match files.pick(size) {
+            Pick::Stored(path) | Pick::Smaller(path) => path.clone(),
+            Pick::Generate { from } => {
+                let from = from.clone();
+                match generate_variant(&reader.filepath, &from, size) {
+                    Ok(path) => {
+                        files.set(size, path.clone());
+                        generated.push(FileData::new(id.clone(), files));
+                        path
+                    }
+                    Err(e) => {
+                        println!["Failed to make the {size:?} thumbnail of {id}: {e:?}"];
+                        from
+                    }
+                }
+            }
+            Pick::Missing => continue,
+        };
+        paths.insert(id, reader.convert_path(&path));
+    }
+
+    if !generated.is_empty() {
+        println![
+            "Recording generated thumbnails: {:?}",
+            reader.extend(&generated, true).await
+        ];
+    }
+    paths
+}
+
+/// The handles of the ids' thumbnails at the size
+pub async fn get_handles(
+    reader: LazyFolderBasedReader,
+    ids: HashSet<String>,
+    size: ThumbnailSize,
+) -> HashMap<String, Handle> {
+    resolve(&reader, &ids, size)
+        .await
+        .into_iter()
+        .map(|(id, path)| (id, Handle::from_path(path)))
+        .collect()
+}
+
+/// Where the thumbnail was read from, for handles made from a saved file
+pub fn saved_path(handle: &Handle) -> Option<PathBuf> {
+    match handle.data() {
+        Data::Path(path) => Some(path.clone()),
+        _ => None,
+    }
+}
+/// How many thumbnails download at once
+pub const MAX_THUMBNAIL_DOWNLOADS: usize = 4;
+/// How long a thumbnail that failed to download waits to be tried again, doubling each time
+pub const THUMBNAIL_RETRY_DELAY: Duration = Duration::from_secs(60);
+/// The longest a failed thumbnail waits, broken links are still tried now and then
+pub const MAX_THUMBNAIL_RETRY_DELAY: Duration = Duration::from_secs(60 * 60);
+/// Until a list is scrolled, this many of its first rows are taken to be on screen
+pub const UNSCROLLED_ROWS: usize = 20;
+
+/// The part of a list that's on screen, as fractions of its height
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct OnScreen(Option<(f32, f32)>);
+impl OnScreen {
+    pub fn of(viewport: &Viewport) -> Self {
+        let height = viewport.content_bounds().height;
+        if height <= 0.0 {
+            return Self::default();
+        }
+        let start = viewport.absolute_offset().y / height;
+        let end = start + viewport.bounds().height / height;
+        Self(Some((start.clamp(0.0, 1.0), end.clamp(0.0, 1.0))))
+    }
+
+    /// Where the screen starts and ends, as fractions of the list's height. None before the
+    /// list was scrolled.
+    pub fn fractions(&self) -> Option<(f32, f32)> {
+        self.0
+    }
+
+    /// The rows of a list of `len` rows that are on screen, taking them to be the same height
+    pub fn rows(&self, len: usize) -> Range<usize> {
+        match self.0 {
+            Some((start, end)) => {
+                let row = |fraction: f32| ((fraction * len as f32) as usize).min(len);
+                row(start)..(row(end) + 1).min(len)
+            }
+            None => 0..len.min(UNSCROLLED_ROWS),
+        }
+    }
+}
+
+/// Thumbnails waiting to be downloaded. Those on screen download first, and those that failed
+/// wait before they're tried again, so broken links aren't fetched over and over.
+/// Downloads that started are never cancelled, their thumbnails are kept for when the rows
+/// come back.
+#[derive(Debug, Default)]
+pub struct ThumbnailQueue {
+    /// In the order they were asked for
+    pending: Vec<String>,
+    in_flight: HashSet<String>,
+    /// When each failed thumbnail can be tried again, and how many times in a row it failed
+    failed: HashMap<String, (Instant, u32)>,
+}
+
+impl ThumbnailQueue {
+    /// Queues the thumbnails that aren't queued, downloading, or waiting to be tried again
+    pub fn request(&mut self, ids: impl IntoIterator<Item = String>, now: Instant) {
+        let waiting: HashSet<String> = self.pending.iter().cloned().collect();
+        for id in ids {
+            let retry_later = self
+                .failed
+                .get(&id)
+                .is_some_and(|(retry_at, _)| now < *retry_at);
+            if retry_later || waiting.contains(&id) || self.in_flight.contains(&id) {
+                continue;
+            }
+            self.pending.push(id);
+        }
+    }
+
+    /// Takes the next thumbnails to download, as many as there's room for
+    pub fn next_batch(&mut self, on_screen: &HashSet<String>) -> HashSet<String> {
+        let room = MAX_THUMBNAIL_DOWNLOADS.saturating_sub(self.in_flight.len());
+        let (mut first, rest): (Vec<String>, Vec<String>) = self
+            .pending
+            .drain(..)
+            .partition(|id| on_screen.contains(id));
+        first.extend(rest);
+        self.pending = first.split_off(room.min(first.len()));
+
+        self.in_flight.extend(first.iter().cloned());
+        first.into_iter().collect()
+    }
+
+    /// Records the downloads of the batch that finished. The ones that aren't in `downloaded`
+    /// failed.
+    pub fn finish(&mut self, batch: &HashSet<String>, downloaded: &HashSet<String>, now: Instant) {
+        for id in batch {
+            self.in_flight.remove(id);
+            if downloaded.contains(id) {
+                self.failed.remove(id);
+                continue;
+            }
+            let failures = self.failed.get(id).map_or(0, |(_, failures)| *failures) + 1;
+            let delay = THUMBNAIL_RETRY_DELAY
+                .saturating_mul(2_u32.saturating_pow(failures - 1))
+                .min(MAX_THUMBNAIL_RETRY_DELAY);
+            self.failed.insert(id.clone(), (now + delay, failures));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, path::PathBuf, time::Instant};
+
+    use image::{DynamicImage, GenericImageView};
+
+    use crate::caching::readers::{CacheReader, FileData, LazyFolderBasedReader, SourceItemPair};
+
+    use super::{
+        resolve, variant_path, OnScreen, Pick, ThumbnailFiles, ThumbnailQueue, ThumbnailSize,
+        MAX_THUMBNAIL_DOWNLOADS, THUMBNAIL_RETRY_DELAY, UNSCROLLED_ROWS,
+    };
+
+    fn files(small: Option<&str>, large: Option<&str>) -> ThumbnailFiles {
+        ThumbnailFiles {
+            small: small.map(PathBuf::from),
+            large: large.map(PathBuf::from),
+        }
+    }
+
+    #[test]
+    fn variants_are_picked_by_size() {
+        let both = files(Some("s.png"), Some("l.png"));
+        assert_eq![
+            both.pick(ThumbnailSize::Small),
+            Pick::Stored(&"s.png".into())
+        ];
+        assert_eq![
+            both.pick(ThumbnailSize::Large),
+            Pick::Stored(&"l.png".into())
+        ];
+
+        // Small ones are made from large ones, but not the other way around
+        let large = files(None, Some("l.png"));
+        assert_eq![
+            large.pick(ThumbnailSize::Small),
+            Pick::Generate {
+                from: &"l.png".into()
+            }
+        ];
+        let small = files(Some("s.png"), None);
+        assert_eq![
+            small.pick(ThumbnailSize::Large),
+            Pick::Smaller(&"s.png".into())
+        ];
+        assert_eq![files(None, None).pick(ThumbnailSize::Small), Pick::Missing];
+
+        assert_eq![
+            variant_path(&PathBuf::from("id.png"), ThumbnailSize::Small),
+            PathBuf::from("id.small.png")
+        ];
+        assert_eq![
+            variant_path(&PathBuf::from("id.large.png"), ThumbnailSize::Small),
+            PathBuf::from("id.small.png")
+        ];
+    }
+
+    #[test]
+    fn index_entries_round_trip() {
+        let entry = FileData::new("id".to_string(), files(Some("a.small.png"), Some("a.png")));
+        let line = serde_json::to_string(&entry).unwrap();
+        let read: FileData<ThumbnailFiles> = serde_json::from_str(&line).unwrap();
+        assert_eq![read.into_data(), entry.into_data()];
+
+        // Entries from before variants are the large one
+        let legacy: FileData<ThumbnailFiles> = serde_json::from_str(r#"["id","a.png"]"#).unwrap();
+        assert_eq![legacy.into_data(), files(None, Some("a.png"))];
+    }
+
+    #[test]
+    fn missing_small_variants_are_made_on_first_use() {
+        async_std::task::block_on(async {
+            let dir = tempfile::tempdir().unwrap();
+            let reader = LazyFolderBasedReader::new(dir.path().to_path_buf());
+            DynamicImage::new_rgb8(600, 600)
+                .save(dir.path().join("old.png"))
+                .unwrap();
+            let legacy = vec![FileData::new("old".to_string(), PathBuf::from("old.png"))];
+            reader.index_reader.extend(&legacy, true).await.unwrap();
+
+            let ids = HashSet::from(["old".to_string(), "unknown".to_string()]);
+            let small = resolve(&reader, &ids, ThumbnailSize::Small).await;
+            assert_eq![small.len(), 1];
+            assert_eq![small["old"], dir.path().join("old.small.png")];
+            assert_eq![image::open(&small["old"]).unwrap().dimensions(), (96, 96)];
+
+            // The new variant is recorded, and the original is still the large one
+            let index: Vec<SourceItemPair<String, FileData<ThumbnailFiles>>> =
+                reader.index_reader.read().await.unwrap();
+            assert_eq![
+                index[0].1.clone().into_data(),
+                files(Some("old.small.png"), Some("old.png"))
+            ];
+            let large = resolve(&reader, &ids, ThumbnailSize::Large).await;
+            assert_eq![large["old"], dir.path().join("old.png")];
+        });
+    }
+
+    fn ids(ids: &[&str]) -> HashSet<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn thumbnails_on_screen_download_first() {
+        let now = Instant::now();
+        let mut queue = ThumbnailQueue::default();
+        queue.request(["a", "b", "c", "d", "e", "f"].map(String::from), now);
+
+        let batch = queue.next_batch(&ids(&["e"]));
+        assert_eq![batch.len(), MAX_THUMBNAIL_DOWNLOADS];
+        assert_eq![batch, ids(&["e", "a", "b", "c"])];
+        // There's no room until some finish, and downloading ones aren't queued again
+        queue.request(["a".to_string()], now);
+        assert![queue.next_batch(&ids(&["f"])).is_empty()];
+
+        queue.finish(&batch, &ids(&["b", "c", "e"]), now);
+        assert_eq![queue.next_batch(&HashSet::new()), ids(&["d", "f"])];
+    }
+
+    #[test]
+    fn failed_thumbnails_wait_to_be_tried_again() {
+        let now = Instant::now();
+        let mut queue = ThumbnailQueue::default();
+        queue.request(["broken".to_string()], now);
+        let batch = queue.next_batch(&HashSet::new());
+        queue.finish(&batch, &HashSet::new(), now);
+
+        queue.request(["broken".to_string()], now + THUMBNAIL_RETRY_DELAY / 2);
+        assert![queue.next_batch(&HashSet::new()).is_empty()];
+        queue.request(["broken".to_string()], now + THUMBNAIL_RETRY_DELAY);
+        let batch = queue.next_batch(&HashSet::new());
+        assert_eq![batch, ids(&["broken"])];
+
+        // Failing again doubles the wait
+        let later = now + THUMBNAIL_RETRY_DELAY;
+        queue.finish(&batch, &HashSet::new(), later);
+        queue.request(["broken".to_string()], later + THUMBNAIL_RETRY_DELAY);
+        assert![queue.next_batch(&HashSet::new()).is_empty()];
+        queue.request(["broken".to_string()], later + THUMBNAIL_RETRY_DELAY * 2);
+        assert_eq![queue.next_batch(&HashSet::new()), ids(&["broken"])];
+    }
+
+    #[test]
+    fn rows_on_screen_follow_the_scroll_position() {
+        assert_eq![OnScreen::default().rows(100), 0..UNSCROLLED_ROWS];
+        assert_eq![OnScreen::default().rows(3), 0..3];
+        // A quarter of the list is shown, halfway down
+        let halfway = OnScreen(Some((0.5, 0.75)));
+        assert_eq![halfway.rows(100), 50..76];
+        assert_eq![OnScreen(Some((0.5, 1.0))).rows(10), 5..10];
+        assert_eq![halfway.rows(0), 0..0];
+    }
+}
@@ -1,6 +1,6 @@
 use std::{cmp::Ordering, fmt::Debug, ops::RangeInclusive};
 
-use iced::keyboard::{self, Modifiers};
+use iced::keyboard::{self, key::Named, Modifiers};
 
 #[derive(Debug, Clone, Default)]
 pub enum SelectionMode {
@@ -97,9 +97,365 @@ impl SelectionMode {
             Self::Range { first: _, r } => r.contains(&idx),
         }
     }
+
+    /// Every selected index
+    pub fn indices(&self) -> Vec<usize> {
+        match self {
+            Self::None => vec![],
+            Self::Single(idx) => vec![*idx],
+            Self::Multiple(v) => v.clone(),
+            Self::Range { first: _, r } => r.clone().collect(),
+        }
+    }
+}
+
+/// A keyboard focus cursor over a list. This is separate from the selection;
+/// it only marks the row the keyboard actions apply to.
+#[derive(Debug, Clone, Default)]
+pub struct FocusCursor(Option<usize>);
+impl FocusCursor {
+    pub fn get(&self) -> Option<usize> {
+        self.0
+    }
+
+    pub fn clear(&mut self) {
+        self.0 = None;
+    }
+
+    pub fn set(&mut self, idx: usize) {
+        self.0 = Some(idx);
+    }
+
+    /// Keeps the cursor inside a list of `len` items.
+    /// Lists can change under the cursor at any time, so this is called before every use.
+    pub fn clamp(&mut self, len: usize) {
+        self.0 = match (self.0, len) {
+            (_, 0) | (None, _) => None,
+            (Some(idx), _) => Some(idx.min(len - 1)),
+        };
+    }
+
+    pub fn move_down(&mut self, len: usize) {
+        self.clamp(len);
+        self.0 = match (self.0, len) {
+            (_, 0) => None,
+            (None, _) => Some(0),
+            (Some(idx), _) => Some((idx + 1).min(len - 1)),
+        };
+    }
+
+    pub fn move_up(&mut self, len: usize) {
+        self.clamp(len);
+        self.0 = match (self.0, len) {
+            (_, 0) => None,
+            (None, _) => Some(len - 1),
+            (Some(idx), _) => Some(idx.saturating_sub(1)),
+        };
+    }
+}
+
+/// The list that receives keyboard navigation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FocusedList {
+    #[default]
+    None,
+    Search,
+    Constructor,
+}
+impl FocusedList {
+    pub fn next(self) -> Self {
+        match self {
+            Self::None => Self::Search,
+            Self::Search => Self::Constructor,
+            Self::Constructor => Self::None,
+        }
+    }
 }
 
 #[derive(Debug, Default, Clone)]
 pub struct UserInputs {
     pub modifiers: keyboard::Modifiers,
+    pub focused_list: FocusedList,
+    pub transport: TransportBindings,
+    /// A text field was typed in and may still have the keyboard focus,
+    /// so the transport keys are left to it
+    pub typing: bool,
+}
+
+/// What a transport key does
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transport {
+    TogglePause,
+    Next,
+    Previous,
+    /// Changes the volume by this much, out of 1000 like the volume slider
+    Volume(f64),
+    /// Moves this many seconds forward, or back when negative
+    Seek(f64),
+}
+
+/// The keys that control playback from anywhere in the window.
+/// They're kept together so they can be made configurable.
+#[derive(Debug, Clone)]
+pub struct TransportBindings {
+    pub toggle_pause: Named,
+    pub next: Named,
+    pub previous: Named,
+    pub volume_up: Named,
+    pub volume_down: Named,
+    /// Held with next or previous, seeks instead of skipping
+    pub seek_modifier: Modifiers,
+    /// Out of 1000, like the volume slider
+    pub volume_step: f64,
+    /// In seconds
+    pub seek_step: f64,
+}
+impl Default for TransportBindings {
+    fn default() -> Self {
+        Self {
+            toggle_pause: Named::Space,
+            next: Named::ArrowRight,
+            previous: Named::ArrowLeft,
+            volume_up: Named::ArrowUp,
+            volume_down: Named::ArrowDown,
+            seek_modifier: Modifiers::CTRL,
+            volume_step: 50.0,
+            seek_step: 5.0,
+        }
+    }
+}
+impl TransportBindings {
+    /// The action of the key. While typing, only the media keys are used, the rest are left
+    /// to the text field.
+    pub fn action(
+        &self,
+        key: &keyboard::Key,
+        modifiers: &Modifiers,
+        typing: bool,
+    ) -> Option<Transport> {
+        let key = match key {
+            keyboard::Key::Named(key) => *key,
+            _ => return None,
+        };
+        match key {
+            Named::MediaPlayPause => return Some(Transport::TogglePause),
+            Named::MediaTrackNext => return Some(Transport::Next),
+            Named::MediaTrackPrevious => return Some(Transport::Previous),
+            _ if typing => return None,
+            _ => {}
+        }
+        match (*modifiers == self.seek_modifier, modifiers.is_empty()) {
+            (true, _) if key == self.next => Some(Transport::Seek(self.seek_step)),
+            (true, _) if key == self.previous => Some(Transport::Seek(-self.seek_step)),
+            (_, true) if key == self.toggle_pause => Some(Transport::TogglePause),
+            (_, true) if key == self.next => Some(Transport::Next),
+            (_, true) if key == self.previous => Some(Transport::Previous),
+            (_, true) if key == self.volume_up => Some(Transport::Volume(self.volume_step)),
+            (_, true) if key == self.volume_down => Some(Transport::Volume(-self.volume_step)),
+            _ => None,
+        }
+    }
+}
+
+/// Routes the number keys to a rating of the now-playing song.
+/// `1`-`5` set the rating and `0` clears it. Nothing happens when no song is playing.
+pub fn route_rating<'a>(
+    key: &keyboard::Key,
+    modifiers: &Modifiers,
+    now_playing: Option<&'a String>,
+) -> Option<(&'a String, Option<u8>)> {
+    let song = now_playing?;
+    if !modifiers.is_empty() {
+        return None;
+    }
+    match key {
+        keyboard::Key::Character(c) => match c.as_str().parse::<u8>() {
+            Ok(0) => Some((song, None)),
+            Ok(n) if n <= crate::song::MAX_RATING => Some((song, Some(n))),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HistoryStep {
+    Undo,
+    Redo,
+}
+
+/// Routes ctrl+Z to undoing the last edit of the playlist, and ctrl+shift+Z to redoing it.
+/// While typing they're left to the text field.
+pub fn route_history(
+    key: &keyboard::Key,
+    modifiers: &Modifiers,
+    typing: bool,
+) -> Option<HistoryStep> {
+    match key {
+        keyboard::Key::Character(c) if !typing && c.as_str().eq_ignore_ascii_case("z") => {
+            match *modifiers {
+                m if m == Modifiers::COMMAND => Some(HistoryStep::Undo),
+                m if m == Modifiers::COMMAND | Modifiers::SHIFT => Some(HistoryStep::Redo),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Whether the key toggles the cache stats overlay, which is F12 on its own
+pub fn route_debug_overlay(key: &keyboard::Key, modifiers: &Modifiers) -> bool {
+    *key == keyboard::Key::Named(Named::F12) && modifiers.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use iced::keyboard::{key::Named, Key, Modifiers};
+
+    use super::{
+        route_debug_overlay, route_history, route_rating, FocusCursor, HistoryStep, Transport,
+        TransportBindings,
+    };
+
+    #[test]
+    fn focus_moves_within_bounds() {
+        let mut cursor = FocusCursor::default();
+        cursor.move_down(3);
+        assert_eq![cursor.get(), Some(0)];
+        cursor.move_up(3);
+        assert_eq![cursor.get(), Some(0)];
+        cursor.move_down(3);
+        cursor.move_down(3);
+        cursor.move_down(3);
+        assert_eq![cursor.get(), Some(2)];
+
+        let mut cursor = FocusCursor::default();
+        cursor.move_up(3);
+        assert_eq![cursor.get(), Some(2)];
+    }
+
+    #[test]
+    fn focus_survives_list_mutation() {
+        let mut cursor = FocusCursor::default();
+        for _ in 0..5 {
+            cursor.move_down(10);
+        }
+        assert_eq![cursor.get(), Some(4)];
+
+        // Items removed from under the cursor
+        cursor.clamp(2);
+        assert_eq![cursor.get(), Some(1)];
+        cursor.move_down(2);
+        assert_eq![cursor.get(), Some(1)];
+
+        // The list was emptied
+        cursor.move_up(0);
+        assert_eq![cursor.get(), None];
+        cursor.move_down(0);
+        assert_eq![cursor.get(), None];
+
+        // Items added back
+        cursor.move_down(4);
+        assert_eq![cursor.get(), Some(0)];
+        cursor.clamp(100);
+        assert_eq![cursor.get(), Some(0)];
+    }
+
+    #[test]
+    fn number_keys_rate_the_playing_song() {
+        let playing = "song".to_string();
+        let key = |c: &str| Key::Character(c.into());
+
+        assert_eq![
+            route_rating(&key("4"), &Modifiers::empty(), Some(&playing)),
+            Some((&playing, Some(4)))
+        ];
+        assert_eq![
+            route_rating(&key("0"), &Modifiers::empty(), Some(&playing)),
+            Some((&playing, None))
+        ];
+        assert_eq![
+            route_rating(&key("6"), &Modifiers::empty(), Some(&playing)),
+            None
+        ];
+        assert_eq![
+            route_rating(&key("a"), &Modifiers::empty(), Some(&playing)),
+            None
+        ];
+        assert_eq![
+            route_rating(
+                &Key::Named(Named::Space),
+                &Modifiers::empty(),
+                Some(&playing)
+            ),
+            None
+        ];
+    }
+
+    #[test]
+    fn rating_needs_a_playing_song_and_no_modifiers() {
+        let playing = "song".to_string();
+        let key = Key::Character("3".into());
+
+        assert_eq![route_rating(&key, &Modifiers::empty(), None), None];
+        assert_eq![route_rating(&key, &Modifiers::CTRL, Some(&playing)), None];
+    }
+
+    #[test]
+    fn transport_keys_are_left_to_text_fields() {
+        let bindings = TransportBindings::default();
+        let none = Modifiers::empty();
+        let action = |key, modifiers: &Modifiers, typing| {
+            bindings.action(&Key::Named(key), modifiers, typing)
+        };
+
+        assert_eq![
+            action(Named::Space, &none, false),
+            Some(Transport::TogglePause)
+        ];
+        assert_eq![
+            action(Named::ArrowDown, &none, false),
+            Some(Transport::Volume(-50.0))
+        ];
+        assert_eq![
+            action(Named::ArrowLeft, &none, false),
+            Some(Transport::Previous)
+        ];
+        assert_eq![
+            action(Named::ArrowRight, &Modifiers::CTRL, false),
+            Some(Transport::Seek(5.0))
+        ];
+        assert_eq![action(Named::ArrowUp, &Modifiers::CTRL, false), None];
+        assert_eq![action(Named::Space, &Modifiers::SHIFT, false), None];
+
+        assert_eq![action(Named::ArrowUp, &none, true), None];
+        assert_eq![
+            action(Named::MediaTrackNext, &none, true),
+            Some(Transport::Next)
+        ];
+    }
+
+    #[test]
+    fn shift_turns_undo_into_redo() {
+        let redo = Modifiers::COMMAND | Modifiers::SHIFT;
+        let step = |c: &str, modifiers: &Modifiers, typing| {
+            route_history(&Key::Character(c.into()), modifiers, typing)
+        };
+        assert_eq![
+            step("z", &Modifiers::COMMAND, false),
+            Some(HistoryStep::Undo)
+        ];
+        assert_eq![step("Z", &redo, false), Some(HistoryStep::Redo)];
+        assert_eq![step("z", &Modifiers::empty(), false), None];
+        assert_eq![step("y", &Modifiers::COMMAND, false), None];
+        assert_eq![step("z", &Modifiers::COMMAND, true), None];
+    }
+
+    #[test]
+    fn only_a_bare_f12_toggles_the_overlay() {
+        let toggles = |key, modifiers| route_debug_overlay(&Key::Named(key), &modifiers);
+        assert![toggles(Named::F12, Modifiers::empty())];
+        assert![!toggles(Named::F12, Modifiers::CTRL)];
+        assert![!toggles(Named::F11, Modifiers::empty())];
+    }
 }
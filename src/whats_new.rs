@@ -0,0 +1,215 @@
+use iced::{
+    widget::{button, column, container, row, text, Column, Space},
+    Alignment, Element, Length,
+};
+
+use crate::user_input::{FocusedList, UserInputs};
+
+/// The version of the running binary
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Debug, Clone, Copy)]
+pub struct ChangelogEntry {
+    /// The version the change first shipped in
+    pub version: &'static str,
+    pub title: &'static str,
+    /// A key in [`NAVIGATION`] that shows the new feature, if it has its own spot in the UI
+    pub target: Option<&'static str>,
+}
+
+/// Newest first
+pub const CHANGELOG: &[ChangelogEntry] = &[
+    ChangelogEntry {
+        version: "0.1.0",
+        title: "Groups can be copied or moved out into their own playlist",
+        target: Some("playlist"),
+    },
+    ChangelogEntry {
+        version: "0.1.0",
+        title: "Each output device remembers its own volume",
+        target: None,
+    },
+    ChangelogEntry {
+        version: "0.1.0",
+        title: "Playlists can be started on a schedule, with a volume ramp",
+        target: None,
+    },
+    ChangelogEntry {
+        version: "0.1.0",
+        title: "The window can be pinned above other windows",
+        target: None,
+    },
+    ChangelogEntry {
+        version: "0.1.0",
+        title: "Lists can be navigated with the keyboard, Tab switches between them",
+        target: Some("search"),
+    },
+    ChangelogEntry {
+        version: "0.1.0",
+        title: "Songs can be rated with the number keys",
+        target: Some("playlist"),
+    },
+];
+
+/// Parses "major.minor.patch", ignoring any pre-release or build suffix
+pub fn parse_version(version: &str) -> Option<[u64; 3]> {
+    let core = version.split(['-', '+']).next()?;
+    let mut parts = core.split('.').map(|p| p.parse::<u64>().ok());
+    let version = [parts.next()??, parts.next()??, parts.next()??];
+    parts.next().is_none().then_some(version)
+}
+
+/// Whether `version` is newer than `than`. Nothing having been seen counts as older than
+/// everything, while an unreadable version is never newer.
+pub fn is_newer(version: &str, than: Option<&str>) -> bool {
+    match (parse_version(version), than.map(parse_version)) {
+        (None, _) => false,
+        (Some(_), None | Some(None)) => true,
+        (Some(version), Some(Some(than))) => version > than,
+    }
+}
+
+/// The entries to show on startup, given the last version whose notes were shown
+pub fn unseen_entries(last_seen: Option<&str>, current: &str) -> Vec<&'static ChangelogEntry> {
+    if !is_newer(current, last_seen) {
+        return vec![];
+    }
+    CHANGELOG
+        .iter()
+        .filter(|entry| {
+            is_newer(entry.version, last_seen) && !is_newer(entry.version, Some(current))
+        })
+        .collect()
+}
+
+type Navigation = fn(&mut UserInputs);
+
+fn focus_search(inputs: &mut UserInputs) {
+    inputs.focused_list = FocusedList::Search;
+}
+
+fn focus_playlist(inputs: &mut UserInputs) {
+    inputs.focused_list = FocusedList::Constructor;
+}
+
+/// The places in the UI changelog entries can jump to
+pub const NAVIGATION: &[(&str, Navigation)] =
+    &[("search", focus_search), ("playlist", focus_playlist)];
+
+/// Applies the navigation registered for the key. Returns false for unknown keys.
+pub fn navigate(key: &str, inputs: &mut UserInputs) -> bool {
+    match NAVIGATION.iter().find(|(k, _)| *k == key) {
+        Some((_, navigation)) => {
+            navigation(inputs);
+            true
+        }
+        None => false,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum WhatsNewMsg {
+    /// Shows the whole changelog
+    Open,
+    Dismiss,
+    Jump(&'static str),
+}
+
+/// The dismissible panel listing changes
+#[derive(Debug, Clone, Default)]
+pub struct WhatsNew {
+    entries: Vec<&'static ChangelogEntry>,
+}
+
+impl WhatsNew {
+    pub fn on_startup(last_seen: Option<&str>) -> Self {
+        Self {
+            entries: unseen_entries(last_seen, CURRENT_VERSION),
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        !self.entries.is_empty()
+    }
+
+    pub fn open(&mut self) {
+        self.entries = unseen_entries(None, CURRENT_VERSION);
+    }
+
+    pub fn close(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn view(&self) -> Option<Element<WhatsNewMsg>> {
+        if !self.is_open() {
+            return None;
+        }
+
+        let entries = self.entries.iter().map(|entry| {
+            row![text(format!("{}  {}", entry.version, entry.title)).width(Length::Fill)]
+                .push_maybe(
+                    entry
+                        .target
+                        .map(|target| button("show me").on_press(WhatsNewMsg::Jump(target))),
+                )
+                .align_items(Alignment::Center)
+                .into()
+        });
+
+        Some(
+            container(
+                column![row![
+                    text("What's new").size(20),
+                    Space::with_width(Length::Fill),
+                    button("dismiss").on_press(WhatsNewMsg::Dismiss),
+                ]
+                .align_items(Alignment::Center)]
+                .push(Column::with_children(entries).spacing(4))
+                .spacing(4),
+            )
+            .padding(10)
+            .width(Length::Fill)
+            .into(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_newer, navigate, parse_version, unseen_entries, CHANGELOG, NAVIGATION};
+    use crate::user_input::{FocusedList, UserInputs};
+
+    #[test]
+    fn notes_are_shown_once_per_version() {
+        assert_eq![parse_version("1.2.3-beta"), Some([1, 2, 3])];
+        assert_eq![parse_version("1.2"), None];
+
+        assert![is_newer("0.2.0", Some("0.1.9"))];
+        assert![is_newer("0.10.0", Some("0.9.0"))];
+        assert![!is_newer("0.1.0", Some("0.1.0"))];
+        assert![!is_newer("0.1.0", Some("0.2.0"))];
+        assert![is_newer("0.1.0", None)];
+
+        let current = CHANGELOG[0].version;
+        assert![!unseen_entries(None, current).is_empty()];
+        assert![unseen_entries(Some(current), current).is_empty()];
+        // A downgrade doesn't show anything either
+        assert![unseen_entries(Some("999.0.0"), current).is_empty()];
+    }
+
+    #[test]
+    fn navigation_targets_are_registered() {
+        let mut inputs = UserInputs::default();
+        assert![navigate("search", &mut inputs)];
+        assert_eq![inputs.focused_list, FocusedList::Search];
+        assert![navigate("playlist", &mut inputs)];
+        assert_eq![inputs.focused_list, FocusedList::Constructor];
+        assert![!navigate("nowhere", &mut inputs)];
+
+        for entry in CHANGELOG {
+            if let Some(target) = entry.target {
+                assert![NAVIGATION.iter().any(|(key, _)| *key == target), "{target}"];
+            }
+        }
+    }
+}
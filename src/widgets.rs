@@ -0,0 +1,134 @@
+//! Input widgets shared by the forms of the app.
+
+use iced::{
+    widget::{button, row, text_input},
+    Alignment, Color, Element,
+};
+
+/// The border of a field whose text doesn't take effect
+const INVALID_COLOR: Color = Color::from_rgb(0.85, 0.25, 0.25);
+
+#[derive(Debug, Clone)]
+pub enum StepperMsg {
+    Typed(String),
+    /// Enter was pressed, so the text goes back to the value in effect
+    Submitted,
+    Increment,
+    Decrement,
+}
+
+/// A whole number between `min` and `max`, typed in or stepped with the buttons around it.
+/// The value is kept by the owner. Typed text only changes it when it's a number in range,
+/// anything else stays in the field, marked, until it's fixed or Enter is pressed.
+#[derive(Debug, Clone)]
+pub struct Stepper {
+    min: u32,
+    max: u32,
+    /// What was typed since the value was last stepped or submitted
+    draft: Option<String>,
+}
+
+impl Stepper {
+    pub fn new(min: u32, max: u32) -> Self {
+        Self {
+            min,
+            max,
+            draft: None,
+        }
+    }
+
+    fn parse(&self, text: &str) -> Option<u32> {
+        text.trim()
+            .parse::<u32>()
+            .ok()
+            .filter(|n| (self.min..=self.max).contains(n))
+    }
+
+    /// Whether the typed text isn't a number in range
+    pub fn is_invalid(&self) -> bool {
+        self.draft
+            .as_deref()
+            .is_some_and(|draft| self.parse(draft).is_none())
+    }
+
+    /// Applies the message to the value. It only ever changes to a number in range.
+    pub fn update(&mut self, value: &mut u32, msg: StepperMsg) {
+        match msg {
+            StepperMsg::Typed(text) => {
+                if let Some(n) = self.parse(&text) {
+                    *value = n;
+                }
+                self.draft = Some(text);
+            }
+            StepperMsg::Submitted => self.draft = None,
+            StepperMsg::Increment => {
+                *value = value.saturating_add(1).clamp(self.min, self.max);
+                self.draft = None;
+            }
+            StepperMsg::Decrement => {
+                *value = value.saturating_sub(1).clamp(self.min, self.max);
+                self.draft = None;
+            }
+        }
+    }
+
+    pub fn view(&self, value: u32, id: text_input::Id) -> Element<StepperMsg> {
+        let text = match &self.draft {
+            Some(draft) => draft.clone(),
+            None => value.to_string(),
+        };
+        let invalid = self.is_invalid();
+        let field = text_input(&format!("{}-{}", self.min, self.max), &text)
+            .id(id)
+            .on_input(StepperMsg::Typed)
+            .on_submit(StepperMsg::Submitted)
+            .width(60)
+            .style(move |theme, status| {
+                let mut style = text_input::default(theme, status);
+                if invalid {
+                    style.border = style.border.with_width(2).with_color(INVALID_COLOR);
+                }
+                style
+            });
+        row![
+            button("-").on_press_maybe((value > self.min).then_some(StepperMsg::Decrement)),
+            field,
+            button("+").on_press_maybe((value < self.max).then_some(StepperMsg::Increment)),
+        ]
+        .align_items(Alignment::Center)
+        .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Stepper, StepperMsg};
+
+    #[test]
+    fn only_numbers_in_range_take_effect() {
+        let mut stepper = Stepper::new(1, 10);
+        let mut value = 5;
+
+        stepper.update(&mut value, StepperMsg::Typed("07".into()));
+        assert_eq![value, 7];
+        assert![!stepper.is_invalid()];
+        for text in ["0", "11", "7x", ""] {
+            stepper.update(&mut value, StepperMsg::Typed(text.into()));
+            assert_eq![value, 7];
+            assert![stepper.is_invalid()];
+        }
+        stepper.update(&mut value, StepperMsg::Submitted);
+        assert![!stepper.is_invalid()];
+
+        value = 10;
+        stepper.update(&mut value, StepperMsg::Increment);
+        assert_eq![value, 10];
+        value = 1;
+        stepper.update(&mut value, StepperMsg::Decrement);
+        assert_eq![value, 1];
+        // Values from before the range existed are brought into it
+        value = 0;
+        stepper.update(&mut value, StepperMsg::Increment);
+        assert_eq![value, 1];
+    }
+}
@@ -0,0 +1,160 @@
+//! Keeping the window above the others. Each layout mode remembers whether it's pinned, so
+//! switching modes puts the window back where that mode had it.
+
+use iced::{window, Command};
+
+pub use ytm_rs_core::window_state::{LayoutMode, PinnedModes};
+
+/// Changes the window's level, so the pin can be tried without a window
+pub trait LevelControl {
+    /// Why the window can't be kept on top here, None when it can
+    fn unsupported(&self) -> Option<&'static str>;
+    /// Asks for the window to be kept on top, or not
+    fn request(&mut self, on_top: bool);
+}
+
+/// Wayland leaves stacking to the compositor, so apps can't ask to stay on top
+const WAYLAND_REFUSAL: &str =
+    "Wayland doesn't let apps keep their window on top, use your compositor's window rules";
+
+/// Why the window can't be pinned in this session, None when it can
+pub fn platform_refusal() -> Option<&'static str> {
+    let wayland = cfg!(target_os = "linux") && std::env::var_os("WAYLAND_DISPLAY").is_some();
+    wayland.then_some(WAYLAND_REFUSAL)
+}
+
+/// Asks iced for the level, which it doesn't answer
+#[derive(Debug, Default)]
+pub struct IcedLevel {
+    requested: Option<bool>,
+}
+
+impl IcedLevel {
+    pub fn into_command<M>(self) -> Command<M> {
+        match self.requested {
+            Some(true) => window::change_level(window::Id::MAIN, window::Level::AlwaysOnTop),
+            Some(false) => window::change_level(window::Id::MAIN, window::Level::Normal),
+            None => Command::none(),
+        }
+    }
+}
+
+impl LevelControl for IcedLevel {
+    fn unsupported(&self) -> Option<&'static str> {
+        platform_refusal()
+    }
+
+    fn request(&mut self, on_top: bool) {
+        self.requested = Some(on_top);
+    }
+}
+
+/// The level that was asked of the window, which isn't always the one that's wanted
+#[derive(Debug, Default)]
+pub struct WindowPin {
+    on_top: bool,
+}
+
+impl WindowPin {
+    pub fn on_top(&self) -> bool {
+        self.on_top
+    }
+
+    /// Puts the window on top or back among the others. When the platform refuses, the
+    /// window is left where it was and the reason is returned.
+    pub fn apply(
+        &mut self,
+        control: &mut impl LevelControl,
+        on_top: bool,
+    ) -> Result<(), &'static str> {
+        if on_top == self.on_top {
+            return Ok(());
+        }
+        if let (true, Some(reason)) = (on_top, control.unsupported()) {
+            return Err(reason);
+        }
+        control.request(on_top);
+        self.on_top = on_top;
+        Ok(())
+    }
+
+    /// Flips the pin of the mode, remembering it only if the window could follow
+    pub fn toggle(
+        &mut self,
+        control: &mut impl LevelControl,
+        pinned: &mut PinnedModes,
+        mode: LayoutMode,
+    ) -> Result<(), &'static str> {
+        let on_top = !self.on_top;
+        self.apply(control, on_top)?;
+        pinned.set(mode, on_top);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LayoutMode, LevelControl, PinnedModes, WindowPin};
+
+    /// Records the levels asked for
+    #[derive(Default)]
+    struct Recorded {
+        refusal: Option<&'static str>,
+        requests: Vec<bool>,
+    }
+    impl LevelControl for Recorded {
+        fn unsupported(&self) -> Option<&'static str> {
+            self.refusal
+        }
+        fn request(&mut self, on_top: bool) {
+            self.requests.push(on_top);
+        }
+    }
+
+    #[test]
+    fn switching_modes_puts_back_each_modes_level() {
+        let mut control = Recorded::default();
+        let mut pinned = PinnedModes::default();
+        let mut pin = WindowPin::default();
+
+        pin.toggle(&mut control, &mut pinned, LayoutMode::Simple)
+            .unwrap();
+        assert![pinned.simple && !pinned.advanced];
+
+        pin.apply(&mut control, pinned.get(LayoutMode::Advanced))
+            .unwrap();
+        assert![!pin.on_top()];
+        pin.apply(&mut control, pinned.get(LayoutMode::Simple))
+            .unwrap();
+        assert![pin.on_top()];
+        // Already on top, so nothing more is asked
+        pin.apply(&mut control, true).unwrap();
+        assert_eq![control.requests, [true, false, true]];
+    }
+
+    #[test]
+    fn refused_pins_leave_the_window_and_the_setting_alone() {
+        let mut control = Recorded {
+            refusal: Some("no"),
+            ..Default::default()
+        };
+        let mut pinned = PinnedModes::default();
+        let mut pin = WindowPin::default();
+
+        assert_eq![
+            pin.toggle(&mut control, &mut pinned, LayoutMode::Advanced),
+            Err("no")
+        ];
+        assert![!pin.on_top()];
+        assert_eq![pinned, PinnedModes::default()];
+
+        // A mode pinned where it could be still starts unpinned
+        pinned.advanced = true;
+        assert_eq![
+            pin.apply(&mut control, pinned.get(LayoutMode::Advanced)),
+            Err("no")
+        ];
+        assert![!pin.on_top()];
+        assert![control.requests.is_empty()];
+    }
+}
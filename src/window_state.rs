@@ -0,0 +1,124 @@
+//! Opening the main window where it was saved, and following its moves and resizes
+
+use iced::{event, window, Event, Point, Size, Subscription};
+use once_cell::sync::OnceCell;
+
+pub use ytm_rs_core::window_state::WindowState;
+
+/// Smaller windows are taken to be mistakes, they're opened at this size instead
+pub const MIN_WINDOW_SIZE: f32 = 200.0;
+
+/// Where the window was, read once the monitor is known
+static RESTORED_POSITION: OnceCell<(i32, i32)> = OnceCell::new();
+
+#[derive(Debug, Clone, Copy)]
+pub enum WindowChange {
+    Resized(Size),
+    Moved(i32, i32),
+    /// Whether the window was brought to the front, or left for another
+    Focused(bool),
+    CloseRequested,
+}
+
+/// The state as the window takes it
+pub trait WindowPlacement {
+    fn apply(&mut self, change: WindowChange);
+    fn size(&self) -> Size;
+    /// The saved position, moved onto the monitor once it's known
+    fn position(&self) -> window::Position;
+}
+
+impl WindowPlacement for WindowState {
+    fn apply(&mut self, change: WindowChange) {
+        match change {
+            WindowChange::Resized(size) => {
+                self.width = size.width;
+                self.height = size.height;
+            }
+            WindowChange::Moved(x, y) => self.position = Some((x, y)),
+            WindowChange::Focused(_) | WindowChange::CloseRequested => {}
+        }
+    }
+
+    fn size(&self) -> Size {
+        match self.width >= MIN_WINDOW_SIZE && self.height >= MIN_WINDOW_SIZE {
+            true => Size::new(self.width, self.height),
+            false => Self::default().size(),
+        }
+    }
+
+    fn position(&self) -> window::Position {
+        match self.position {
+            Some(position) => {
+                let _ = RESTORED_POSITION.set(position);
+                window::Position::SpecificWith(restored_position)
+            }
+            None => window::Position::Centered,
+        }
+    }
+}
+
+fn restored_position(window: Size, monitor: Size) -> Point {
+    let position = RESTORED_POSITION.get().copied().unwrap_or_default();
+    clamp_position(position, window, monitor)
+}
+
+/// Keeps the window on the monitor, in case the one it was on was unplugged
+pub fn clamp_position((x, y): (i32, i32), window: Size, monitor: Size) -> Point {
+    let max_x = (monitor.width - window.width).max(0.0);
+    let max_y = (monitor.height - window.height).max(0.0);
+    Point::new((x as f32).clamp(0.0, max_x), (y as f32).clamp(0.0, max_y))
+}
+
+/// The main window's moves, resizes and focus, and its close button
+pub fn changes() -> Subscription<WindowChange> {
+    event::listen_with(|event, _| match event {
+        Event::Window(id, change) if id == window::Id::MAIN => match change {
+            window::Event::Resized { width, height } => {
+                let size = Size::new(width as f32, height as f32);
+                Some(WindowChange::Resized(size))
+            }
+            window::Event::Moved { x, y } => Some(WindowChange::Moved(x, y)),
+            window::Event::Focused => Some(WindowChange::Focused(true)),
+            window::Event::Unfocused => Some(WindowChange::Focused(false)),
+            window::Event::CloseRequested => Some(WindowChange::CloseRequested),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use iced::{Point, Size};
+
+    use super::{clamp_position, WindowChange, WindowPlacement, WindowState};
+
+    #[test]
+    fn windows_are_kept_on_the_monitor() {
+        let window = Size::new(800.0, 600.0);
+        let monitor = Size::new(1920.0, 1080.0);
+        let at = |x, y| clamp_position((x, y), window, monitor);
+        assert_eq![at(100, 50), Point::new(100.0, 50.0)];
+        // It was on a second monitor to the right
+        assert_eq![at(2500, 200), Point::new(1120.0, 200.0)];
+        assert_eq![at(-300, -20), Point::ORIGIN];
+        // Bigger than the monitor, so it's put in the corner
+        let huge = Size::new(3000.0, 2000.0);
+        assert_eq![clamp_position((40, 40), huge, monitor), Point::ORIGIN];
+    }
+
+    #[test]
+    fn changes_are_remembered() {
+        let mut state = WindowState::default();
+        state.apply(WindowChange::Resized(Size::new(1200.0, 700.0)));
+        state.apply(WindowChange::Moved(30, 40));
+        let json = serde_json::to_string(&state).unwrap();
+        let loaded: WindowState = serde_json::from_str(&json).unwrap();
+        assert_eq![loaded, state];
+        assert_eq![loaded.size(), Size::new(1200.0, 700.0)];
+
+        state.apply(WindowChange::Resized(Size::new(0.0, 0.0)));
+        assert_eq![state.size(), WindowState::default().size()];
+    }
+}
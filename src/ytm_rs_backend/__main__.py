@@ -1,7 +1,8 @@
+import os
 import sys
 from copy import deepcopy
 from queue import Queue
-from threading import Thread
+from threading import Event, Thread, Timer
 from typing import TypedDict
 from urllib import parse as urlparse
 
@@ -109,8 +110,8 @@ class DownloadDict(TypedDict):
 
 # POSTPROCESSORS:
 FORMAT_POSTPROCESSORS: dict[str, tuple[str, dict[str, str]]] = {
-    # Hmm vorbis doesnt work??
-    "vorbis": ("ogg", {"key": "FFmpegVideoConvertor", "preferedformat": "vorbis"}),
+    # The convertor only knows containers, vorbis is a codec
+    "vorbis": ("ogg", {"key": "FFmpegExtractAudio", "preferredcodec": "vorbis"}),
     "aac": ("aac", {"key": "FFmpegVideoConvertor", "preferedformat": "aac"}),
     "flac": ("flac", {"key": "FFmpegVideoConvertor", "preferedformat": "flac"}),
     "mp3": ("mp3", {"key": "FFmpegVideoConvertor", "preferedformat": "mp3"}),
@@ -118,41 +119,57 @@ FORMAT_POSTPROCESSORS: dict[str, tuple[str, dict[str, str]]] = {
 }
 
 
-# STREAMING DOWNLOAD PROGRESS:
-def __download():
-    ...
-    # q = Queue()
-
-    # def progress():
-    #     idx = 0
-    #     while True:
-    #         item = q.get()
-    #         if item is StopIteration:
-    #             break
-    #         js = orjson.dumps(item)
-    #         with open(f"tmp_{idx}.json", "wb") as f:
-    #             f.write(js)
-    #             idx += 1
-
-    #         yield orjson.dumps(item)
-    #         yield "\n"
-
-    # def add_p2q(progress):
-    #     q.put(progress)
-
-    # def request_info(json):
-    #     try:
-    #         opts_ = opts.copy()
-    #         opts_["progress_hooks"] = [add_p2q]
-    #             q.put(StopIteration)
-    #     except Exception as e:
-    #         q.put(repr(e))
-    #         q.put(StopIteration)
-
-    # t = Thread(target=request_info, args=(json,))
-    # t.daemon = True
-    # t.start()
-    # return (progress(), {"Content-Type": "application/x-ndjson"})
+class DownloadCancelled(Exception):
+    """Raised in the progress hook once the client stops listening"""
+
+
+def stream_download(opts_: dict, url: str):
+    """Downloads in a thread, yielding newline-delimited progress objects and then the info"""
+    q: Queue = Queue()
+    cancelled = Event()
+
+    def hook(progress):
+        if cancelled.is_set():
+            raise DownloadCancelled()
+        q.put(
+            {
+                "progress": {
+                    "downloaded_bytes": progress.get("downloaded_bytes") or 0,
+                    "total_bytes": progress.get("total_bytes")
+                    or progress.get("total_bytes_estimate"),
+                    "eta": progress.get("eta"),
+                }
+            }
+        )
+
+    def run():
+        try:
+            opts_["progress_hooks"] = [hook]
+            with YoutubeDL(opts_) as ytdl:
+                info = ytdl.extract_info(download=True, url=url)
+                assert info is not None
+                if "heatmap" in info:
+                    info.pop("heatmap")
+                q.put({"info": info})
+        except DownloadCancelled:
+            print(f"Cancelled download of {url}")
+        except Exception as e:
+            q.put({"error": repr(e)})
+        finally:
+            q.put(StopIteration)
+
+    t = Thread(target=run, daemon=True)
+    t.start()
+
+    def lines():
+        try:
+            while (item := q.get()) is not StopIteration:
+                yield orjson.dumps(item) + b"\n"
+        finally:
+            # The client went away, so the download stops at its next progress report
+            cancelled.set()
+
+    return (lines(), {"Content-Type": "application/x-ndjson"})
 
 
 @app.route("/download", methods=["POST"])
@@ -169,6 +186,8 @@ def download():
             opts_["postprocessors"].insert(0, postprocessor[1])
             opts_["final_ext"] = postprocessor[0]
         print(opts_)
+        if json.get("stream"):
+            return stream_download(opts_, json["url"])
         with YoutubeDL(opts_) as ytdl:
             info = ytdl.extract_info(
                 download=True,
@@ -186,6 +205,13 @@ def download():
         return str(e)
 
 
+@app.route("/shutdown", methods=["POST"])
+def shutdown():
+    # Exits once the answer is sent, downloads that are running are dropped
+    Timer(0.1, os._exit, [0]).start()
+    return "OK"
+
+
 if __name__ == "__main__":
     print(sys.argv)
     port = 55001
@@ -6,97 +6,556 @@ use std::{
     time,
 };
 
-use futures::future::join_all;
+use chrono::Local;
+use futures::{future::join_all, FutureExt};
 use iced::{
     advanced::widget::Id as WId,
+    alignment::Horizontal,
     keyboard,
     widget::{
-        column,
+        button, column,
         container::{Container, Id as CId},
         image::Handle,
-        row, Space,
+        pick_list, row, scrollable, stack, text, text_editor, text_input, Column, Space,
     },
     Alignment, Command as Cm, Element, Length, Subscription,
 };
-use kira::sound::{static_sound::StaticSoundData, PlaybackState};
+use kira::{sound::PlaybackState, tween::Tween};
 use parking_lot::Mutex;
 use reqwest::Url;
 
+#[cfg(feature = "media-controls")]
+use crate::media_controls::{self, MediaCommand, MediaCommands};
+#[cfg(feature = "scrobble")]
+use crate::scrobbler::{self, listens_payload, now_playing_payload, ScrobbleListener, Scrobbler};
 use crate::{
-    audio::{AudioProgressTracker, ChangeSong, TrackerMsg, YTMRSAudioManager},
-    backend_handler::{BackendHandler, BackendLaunchStatus, RequestResult},
+    audio::{
+        self, amplitude_to_slider, available_memory, slider_to_amplitude, AudioProgressTracker,
+        ChangeSong, DeviceWatch, LoadFacts, Loading, PlaybackMode, TrackerMsg, YTMRSAudioManager,
+        VOLUME_SLIDER_MAX,
+    },
+    backend_handler::{
+        self, is_unavailable, BackendHandler, BackendLaunchStatus, BackendReqErr, RequestResult,
+    },
+    backend_settings::{BackendForm, BackendFormMsg},
+    bulk_edit::{self, BulkEditMsg, BulkEditor},
     caching::{
-        readers::{folder_based_reader::read_file, CacheReader, FileData},
-        BasicSoundData, BufferedCache, IDed, RwMap, SoundData, ToRwMapExt, YtmrsCache,
+        readers::{
+            folder_based_reader::{
+                read_file, EvictionReport, MaintenanceReport, RemovalReport, ValidationReport,
+                ORPHAN_AGE, STALE_TMP_AGE,
+            },
+            CacheReader, FileData, SourceItemPair,
+        },
+        view_built, BasicSoundData, BufferedCache, CacheStats, IDed, KeySource, RwMap, SoundData,
+        ToRwMapExt, UsedKeys, YtmrsCache,
+    },
+    cli::StartupOptions,
+    context_menu::{closes_menus, MenuTarget, SongAction},
+    downloads::{Batch, Download, DownloadEvent, DownloadProgress, Downloads, DownloadsMsg},
+    events::{self, AppEvent, EventBus},
+    export::{self, ExportEntry, ExportFormat},
+    failures::{Failure, FailureKind, Failures, FailuresMsg, Retry},
+    history::{History, HistoryMsg, PlayRecord, PlayTrigger},
+    import::{self, Imported},
+    metadata_queue::{FetchError, MetadataQueue},
+    notifications::{Notification, Notifications, NotificationsMsg},
+    now_playing::{NowPlaying, NowPlayingMsg},
+    panes::{PaneKind, Panes, PanesMsg},
+    playlist::{
+        self, AddTarget, DuplicatePrompt, DuplicatePromptMsg, ExternalChange, ExternalChangeMsg,
+        Playlist, PlaylistHeader, PlaylistLibrary, PlaylistMessage,
     },
-    playlist::PlaylistMessage,
+    queue::{Queue, QueueMsg, Shuffle, ShuffleRatings},
     response_types::YTResponseType,
-    search_window::{SWMessage, SearchEntry, SearchType, SearchWindow},
-    settings::YTMRSettings,
-    song::{Song, SongState},
+    scheduler::{LocalClock, ScheduleEntry, ScheduleWatch, VolumeRamp},
+    search_window::{SWMessage, SearchEntry, SearchType, SearchWindow, LOCAL_SEARCH_PREFIX},
+    session::Session,
+    settings::{
+        exports_directory, playlists_directory, settings_path, LoadError, SaveError, SongKey,
+        Ticker, YTMRSettings, YTMRUserSettings,
+    },
+    settings_panel::{CacheFolder, SettingsPanel, SettingsPanelMsg},
+    sleep_timer::{SleepStep, SleepTimer},
+    song::{Song, SongData, SongDuration, SongSource, SongState},
+    song_editor::{SongEditMsg, SongEditor},
+    song_list,
+    song_notifications::{self, SongNotice, SongNotifier},
     song_operations::{
-        self, ConstructorItem, OperationTracker, RecursiveSongOp, SongOpTracker, TreeDirected,
-        UpdateResult,
+        self,
+        album::{self, AlbumPosition},
+        simple::{SimpleMsg, SimpleQueue, SimpleResult},
+        tree_diff, ConstructorItem, InfLoopType, NextResult, OperationTracker, RecursiveSongOp,
+        Repeat, SongOpMessage, SongOpTracker, TreeDirected, UpdateResult,
     },
     styling::{BasicYtmrsScheme, FullYtmrsScheme},
-    thumbnails::get_images,
-    user_input::UserInputs,
+    subscriptions::{self, Debounced},
+    thumbnails::{self, get_images, OnScreen, ThumbnailFiles, ThumbnailQueue, ThumbnailSize},
+    user_input::{
+        route_debug_overlay, route_history, route_rating, FocusedList, HistoryStep, Transport,
+        UserInputs,
+    },
+    verbosity::{debug, info, trace},
+    whats_new::{self, WhatsNew, WhatsNewMsg},
 };
 
+const BACKEND_POLL_INTERVAL: time::Duration = time::Duration::from_secs(10);
+/// The longest wait between attempts to reach a backend that isn't running
+const BACKEND_MAX_RETRY_INTERVAL: time::Duration = time::Duration::from_secs(5 * 60);
+
+/// How long to wait before trying the backend again, doubling with each failure in a row
+fn backend_retry_interval(failures: u32) -> time::Duration {
+    BACKEND_POLL_INTERVAL
+        .saturating_mul(2_u32.saturating_pow(failures))
+        .min(BACKEND_MAX_RETRY_INTERVAL)
+}
+
+/// How long until the backend is polled again. The failures in a row are counted until it
+/// answers, so a server that keeps being launched but never answers is tried less and less.
+fn next_backend_poll(online: bool, failures: &mut u32) -> time::Duration {
+    match online {
+        true => {
+            *failures = 0;
+            BACKEND_POLL_INTERVAL
+        }
+        false => {
+            let interval = backend_retry_interval(*failures);
+            *failures = failures.saturating_add(1);
+            interval
+        }
+    }
+}
+
+/// How often the progress bar moves while it can be seen, unless it was set to move faster
+const SMOOTH_PROGRESS_INTERVAL: time::Duration = time::Duration::from_millis(250);
+
+/// How often the playing song's progress is ticked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressTicks {
+    Off,
+    /// At the interval set for the progress bar
+    Background,
+    /// Faster, for a smoother progress bar
+    Smooth,
+}
+impl ProgressTicks {
+    /// Songs that are fading out still tick, so the bar follows them to the end
+    fn of(playback: PlaybackState, seen: bool) -> Self {
+        match (playback, seen) {
+            (PlaybackState::Paused | PlaybackState::Stopped, _) => Self::Off,
+            (PlaybackState::Playing, true) => Self::Smooth,
+            (PlaybackState::Playing | PlaybackState::Pausing | PlaybackState::Stopping, _) => {
+                Self::Background
+            }
+        }
+    }
+}
+
+/// Whether each periodic tick is running, and how often.
+/// New tickers go through [`subscriptions::every`], see that module for why.
 #[derive(Debug)]
 pub struct Tickers {
-    cache: (bool, time::Duration),
-    backend_status: (bool, time::Duration),
-    playing_status: (bool, time::Duration),
+    cache: (bool, Debounced<time::Duration>),
+    backend_status: (bool, Debounced<time::Duration>),
+    /// Runs as playback goes, see [`ProgressTicks`]
+    playing_status: Debounced<time::Duration>,
+    schedule: (bool, Debounced<time::Duration>),
+    volume_ramp: (bool, Debounced<time::Duration>),
+    output_device: (bool, Debounced<time::Duration>),
+    maintenance: (bool, Debounced<time::Duration>),
+    /// Counts the sleep timer down each second, it isn't one of the intervals the user sets
+    sleep_timer: (bool, Debounced<time::Duration>),
+    /// Set from the user's settings rather than with the other intervals, zero when it's off
+    autosave: Debounced<time::Duration>,
 }
 impl Default for Tickers {
     fn default() -> Self {
+        let every = |enabled, interval| (enabled, Debounced::new(interval));
         Self {
-            cache: (true, time::Duration::from_secs(20)),
-            backend_status: (true, time::Duration::from_secs(10)),
-            playing_status: (false, time::Duration::from_secs(1)),
+            cache: every(true, time::Duration::from_secs(20)),
+            backend_status: every(true, BACKEND_POLL_INTERVAL),
+            playing_status: Debounced::new(time::Duration::from_secs(1)),
+            schedule: every(true, time::Duration::from_secs(60)),
+            volume_ramp: every(false, time::Duration::from_millis(250)),
+            output_device: every(true, time::Duration::from_secs(5)),
+            maintenance: every(true, time::Duration::from_secs(30 * 60)),
+            sleep_timer: every(false, time::Duration::from_secs(1)),
+            autosave: Debounced::new(time::Duration::from_secs(30)),
         }
     }
 }
 impl Tickers {
-    pub fn subscription(&self) -> Subscription<YtmrsMsg> {
-        let mut subs = Vec::with_capacity(3);
-        if self.cache.0 {
-            subs.push(iced::time::every(self.cache.1).map(|_| YtmrsMsg::CacheTick));
+    /// The default intervals, except for the ones the user changed
+    pub fn new(user: &YTMRUserSettings) -> Self {
+        let mut tickers = Self::default();
+        for (ticker, ms) in &user.tick_intervals_ms {
+            *tickers.get_mut(*ticker) = Debounced::new(time::Duration::from_millis(*ms));
         }
-        if self.backend_status.0 {
-            subs.push(
-                iced::time::every(self.backend_status.1).map(|_| YtmrsMsg::BackendStatusTick),
-            );
+        tickers.autosave = Debounced::new(time::Duration::from_secs(user.autosave_secs));
+        tickers
+    }
+
+    fn get(&self, ticker: Ticker) -> &Debounced<time::Duration> {
+        match ticker {
+            Ticker::Cache => &self.cache.1,
+            Ticker::BackendStatus => &self.backend_status.1,
+            Ticker::PlayingStatus => &self.playing_status,
+            Ticker::Schedule => &self.schedule.1,
+            Ticker::VolumeRamp => &self.volume_ramp.1,
+            Ticker::OutputDevice => &self.output_device.1,
+            Ticker::Maintenance => &self.maintenance.1,
         }
-        if self.playing_status.0 {
-            subs.push(
-                iced::time::every(self.playing_status.1).map(|_| YtmrsMsg::PlayingStatusTick),
-            );
+    }
+
+    fn get_mut(&mut self, ticker: Ticker) -> &mut Debounced<time::Duration> {
+        match ticker {
+            Ticker::Cache => &mut self.cache.1,
+            Ticker::BackendStatus => &mut self.backend_status.1,
+            Ticker::PlayingStatus => &mut self.playing_status,
+            Ticker::Schedule => &mut self.schedule.1,
+            Ticker::VolumeRamp => &mut self.volume_ramp.1,
+            Ticker::OutputDevice => &mut self.output_device.1,
+            Ticker::Maintenance => &mut self.maintenance.1,
+        }
+    }
+
+    pub fn interval(&self, ticker: Ticker) -> time::Duration {
+        *self.get(ticker).applied()
+    }
+
+    /// Changes how often the tick fires, once the interval stops changing
+    pub fn set_interval(&mut self, ticker: Ticker, interval: time::Duration, now: time::Instant) {
+        self.get_mut(ticker).set(interval, now);
+    }
+
+    /// Changes how often the settings are saved, 0 stops it
+    pub fn set_autosave(&mut self, secs: u64, now: time::Instant) {
+        self.autosave.set(time::Duration::from_secs(secs), now);
+    }
+
+    /// Applies interval changes that have settled
+    pub fn settle(&mut self, now: time::Instant) {
+        for interval in [
+            &mut self.cache.1,
+            &mut self.backend_status.1,
+            &mut self.playing_status,
+            &mut self.schedule.1,
+            &mut self.volume_ramp.1,
+            &mut self.output_device.1,
+            &mut self.maintenance.1,
+            &mut self.sleep_timer.1,
+            &mut self.autosave,
+        ] {
+            interval.settle(now);
+        }
+    }
+
+    /// How often the progress ticks, None when it doesn't
+    fn progress_interval(&self, progress: ProgressTicks) -> Option<time::Duration> {
+        let interval = *self.playing_status.applied();
+        match progress {
+            ProgressTicks::Off => None,
+            ProgressTicks::Background => Some(interval),
+            ProgressTicks::Smooth => Some(interval.min(SMOOTH_PROGRESS_INTERVAL)),
         }
-        Subscription::batch(subs)
     }
+
+    pub fn subscription(&self, progress: ProgressTicks) -> Subscription<YtmrsMsg> {
+        let every = |purpose, (enabled, interval): &(bool, Debounced<time::Duration>)| {
+            enabled.then(|| subscriptions::every(purpose, *interval.applied()))
+        };
+        let subs = [
+            every("cache", &self.cache).map(|s| s.map(|_| YtmrsMsg::CacheTick)),
+            every("backend_status", &self.backend_status)
+                .map(|s| s.map(|_| YtmrsMsg::BackendStatusTick)),
+            self.progress_interval(progress).map(|interval| {
+                subscriptions::every("playing_status", interval)
+                    .map(|_| YtmrsMsg::PlayingStatusTick)
+            }),
+            every("schedule", &self.schedule).map(|s| s.map(|_| YtmrsMsg::ScheduleTick)),
+            every("volume_ramp", &self.volume_ramp).map(|s| s.map(|_| YtmrsMsg::VolumeRampTick)),
+            every("output_device", &self.output_device)
+                .map(|s| s.map(|_| YtmrsMsg::OutputDeviceTick)),
+            every("maintenance", &self.maintenance).map(|s| s.map(|_| YtmrsMsg::MaintenanceTick)),
+            every("sleep_timer", &self.sleep_timer).map(|s| s.map(|_| YtmrsMsg::SleepTimerTick)),
+            (!self.autosave.applied().is_zero()).then(|| {
+                subscriptions::every("autosave", *self.autosave.applied())
+                    .map(|_| YtmrsMsg::AutosaveTick)
+            }),
+        ];
+        Subscription::batch(subs.into_iter().flatten())
+    }
+}
+
+/// What reading a song from the sound cache found
+#[derive(Debug, Clone)]
+pub enum CachedSound {
+    /// Too big to read into memory, so it streams from the file
+    Located(PathBuf),
+    Read(Box<BasicSoundData>),
+    /// It was downloaded in a format that can't be decoded
+    Undecodable(String),
+    /// It hasn't been downloaded
+    Missing,
+}
+
+/// A downloaded song, ready to play
+enum Downloaded {
+    Decoded(Box<BasicSoundData>),
+    /// Too big to decode, so it streams from the cache
+    Stream(PathBuf),
 }
 
 #[derive(Debug)]
 pub struct PlayerState {
     tracker: SongOpTracker,
+    /// The tracker already moved to the next song, so the song that was prefetched is the one
+    /// that plays. Random ops pick as they move, moving the tracker again could pick another.
+    next: Option<(SongOpTracker, NextResult)>,
+    /// The songs left to shuffle through, when shuffling
+    shuffle: Option<Shuffle>,
+    ratings: ShuffleRatings,
+    repeat: Repeat,
+    /// Songs asked to play after the current one, before the queue goes on
+    up_next: VecDeque<Vec<usize>>,
+    /// Where the tracker was when the songs asked for cut in, the queue goes on from there
+    interrupted: Option<SongOpTracker>,
+}
+impl PlayerState {
+    fn new(tracker: SongOpTracker) -> Self {
+        Self {
+            tracker,
+            next: None,
+            shuffle: None,
+            ratings: ShuffleRatings::default(),
+            repeat: Repeat::Off,
+            up_next: VecDeque::new(),
+            interrupted: None,
+        }
+    }
+
+    fn with_shuffle(mut self, enabled: bool) -> Self {
+        self.set_shuffle(enabled);
+        self
+    }
+
+    fn with_ratings(mut self, ratings: ShuffleRatings) -> Self {
+        self.ratings = ratings;
+        self
+    }
+
+    fn with_repeat(mut self, repeat: Repeat) -> Self {
+        self.set_repeat(repeat);
+        self
+    }
+
+    /// Looks the lengths of the songs in timed groups up again, on every tracker kept
+    fn measure(&mut self, length: &impl Fn(&SongKey) -> Option<f64>, unknown: f64) {
+        let next = self.next.as_mut().map(|(tracker, _)| tracker);
+        for tracker in [Some(&mut self.tracker), next, self.interrupted.as_mut()]
+            .into_iter()
+            .flatten()
+        {
+            tracker.measure(length, unknown);
+        }
+    }
+
+    fn set_repeat(&mut self, repeat: Repeat) {
+        self.next = None;
+        self.repeat = repeat;
+    }
+
+    /// Shuffles the songs after the current one, or goes back to the tracker's order from it
+    fn set_shuffle(&mut self, enabled: bool) {
+        self.next = None;
+        let from = self.interrupted.as_ref().unwrap_or(&self.tracker);
+        self.shuffle = enabled.then(|| Shuffle::new(from));
+    }
+
+    /// Plays the song at the path after the current one, and after those asked for before it
+    fn play_next(&mut self, path: Vec<usize>) {
+        self.next = None;
+        self.up_next.push_back(path);
+    }
+
+    /// Where the tracker goes next, leaving it where it is.
+    /// Repeating the queue starts it over from the first song, shuffling the rest again.
+    fn step(&mut self) -> (SongOpTracker, NextResult) {
+        if let Some(path) = self.up_next.front() {
+            let mut tracker = self.tracker.clone();
+            tracker.set_current(VecDeque::from(path.clone()));
+            return (tracker, NextResult::Current);
+        }
+        let (mut tracker, result) = self.step_in_queue();
+        match (result, self.repeat) {
+            (NextResult::Ended, Repeat::All) => {
+                tracker.to_start();
+                if self.shuffle.is_some() {
+                    self.shuffle = Some(Shuffle::new(&tracker));
+                }
+                (tracker, NextResult::Current)
+            }
+            (result, _) => (tracker, result),
+        }
+    }
+
+    fn step_in_queue(&mut self) -> (SongOpTracker, NextResult) {
+        let from = self.interrupted.as_ref().unwrap_or(&self.tracker);
+        let mut tracker = from.clone();
+        let shuffle = match &mut self.shuffle {
+            Some(shuffle) => shuffle,
+            None => {
+                let result = tracker.move_next();
+                return (tracker, result);
+            }
+        };
+        if shuffle.pick(&self.ratings).is_none() && shuffle.continues() {
+            *shuffle = Shuffle::new(from);
+        }
+        match shuffle.pick(&self.ratings) {
+            Some(path) => {
+                tracker.set_current(VecDeque::from(path.clone()));
+                (tracker, NextResult::Current)
+            }
+            None => (tracker, NextResult::Ended),
+        }
+    }
+
+    /// The tracker, for moves other than to the next song. The next song is worked out again,
+    /// and the queue goes on from wherever the tracker is moved to.
+    fn tracker_mut(&mut self) -> &mut SongOpTracker {
+        self.next = None;
+        self.interrupted = None;
+        &mut self.tracker
+    }
+
+    /// The path of the song after this one, None if the queue ends
+    fn peek_next(&mut self) -> Option<Vec<usize>> {
+        if self.next.is_none() {
+            self.next = Some(self.step());
+        }
+        let (tracker, result) = self.next.as_ref()?;
+        match result {
+            NextResult::Current => Some(tracker.get_current().collect()),
+            NextResult::Ended => None,
+        }
+    }
+
+    fn move_next(&mut self) -> NextResult {
+        let cut_in = !self.up_next.is_empty();
+        let (tracker, result) = match self.next.take() {
+            Some(next) => next,
+            None => self.step(),
+        };
+        let previous = std::mem::replace(&mut self.tracker, tracker);
+        match cut_in {
+            true => {
+                self.up_next.pop_front();
+                self.interrupted.get_or_insert(previous);
+            }
+            false => {
+                self.interrupted = None;
+                if let (NextResult::Current, Some(shuffle)) = (&result, &mut self.shuffle) {
+                    shuffle.mark_played(&self.tracker.get_current().collect::<Vec<_>>());
+                }
+            }
+        }
+        result
+    }
 }
 
 #[derive(Debug, Default)]
 pub struct Ytmrs {
     inputs: UserInputs,
     search: SearchWindow,
+    whats_new: WhatsNew,
 
     audio_manager: YTMRSAudioManager,
     audio_tracker: AudioProgressTracker,
+    output_device: DeviceWatch,
     player_state: Option<PlayerState>,
+    /// The song after the playing one, being loaded ahead of time
+    prefetching: Option<String>,
+    /// The songs with a file in the audio cache, found in its index at startup. Songs whose
+    /// metadata is read later are shown as cached from it.
+    downloaded: HashSet<String>,
+    /// The songs being read from the sound cache, and whether they play once they're read
+    fetching: HashMap<SongKey, bool>,
+    /// The thumbnails that weren't saved, waiting to be downloaded
+    thumbnail_queue: ThumbnailQueue,
+    /// The songs found by searches that weren't cached, waiting to be asked for
+    metadata_queue: MetadataQueue,
+    /// The session restored from the settings, until its song plays
+    resuming: Option<Session>,
+    now_playing: Option<String>,
+    /// Set once listeners were told the playing song ended
+    song_over: bool,
+    /// The notes of the playing song, being edited
+    notes: text_editor::Content,
+    /// Where the playing song was found
+    now_playing_source: SongSource,
+    /// The songs that were played
+    history: History,
+    /// How the song being loaded came to play, until it plays
+    play_trigger: Option<PlayTrigger>,
+    /// Starts playing once the playlist from `--playlist` is open
+    play_when_opened: bool,
+    /// Shows the history in place of the queue
+    history_open: bool,
+    /// The search and the playlist, split by a divider
+    panes: Panes,
+    /// Shown over the view while the debug overlay is open, refreshed on cache ticks
+    cache_stats: Option<CacheStats>,
+    volume_ramp: Option<VolumeRamp>,
+    /// Pauses playback once it's up, it isn't saved
+    sleep_timer: Option<SleepTimer>,
+    /// The sleep timer faded the song out, so it gets its volume back when it plays again
+    slept: bool,
+    song_notifier: SongNotifier,
+    /// Set while another window has the focus, songs are only notified then
+    in_background: bool,
+    schedule_watch: ScheduleWatch<Local>,
+    /// The scheduled entry whose playlist is being opened, it starts once it's open
+    scheduled: Option<ScheduleEntry>,
+    /// Also held by its listener, which follows the songs
+    #[cfg(feature = "scrobble")]
+    scrobbler: Arc<Mutex<Scrobbler>>,
+    #[cfg(feature = "media-controls")]
+    media_commands: MediaCommands,
 
     tickers: Tickers,
     backend_handler: Arc<Mutex<BackendHandler>>,
+    backend_form: BackendForm,
+    /// Set while the backend can't be reached
+    offline: bool,
+    /// The attempts to reach the backend that failed in a row
+    backend_failures: u32,
     pub settings: YTMRSettings,
+    /// When the settings file was last loaded or saved by us, to notice changes made elsewhere
+    settings_modified: Option<time::SystemTime>,
+    /// The fingerprint of the settings as they were last loaded or saved
+    saved_fingerprint: u64,
+    /// The fingerprint of the settings being saved, until they are
+    saving_fingerprint: Option<u64>,
+    external_change: Option<ExternalChange>,
+    /// Songs being added that are in the playlist already, until it's known what to do
+    duplicate_prompt: Option<DuplicatePrompt>,
+    bulk_edit: Option<BulkEditor>,
+    song_editor: Option<SongEditor>,
+    settings_panel: Option<SettingsPanel>,
+    /// The song row whose menu is open
+    context_menu: Option<MenuTarget>,
+    events: EventBus,
+    failures: Failures,
+    notifications: Notifications,
+    simple: SimpleQueue,
+    queue: Queue,
+    library: PlaylistLibrary,
+    downloads: Downloads,
 
     cache: YtmrsCache,
+    /// The songs the playlist and the search use, which are kept in the cache
+    used_keys: UsedKeys,
 }
 
 #[derive(Debug, Clone)]
@@ -104,50 +563,179 @@ pub enum YtmrsMsg {
     // User input
     HandleZones(String, Vec<(iced::advanced::widget::Id, iced::Rectangle)>),
     KeysChanged(keyboard::Key, keyboard::Modifiers),
+    KeyPressed(keyboard::Key, keyboard::Modifiers),
 
     // Ticks
     CacheTick,
     BackendStatusTick,
     BackendStatusPollSuccess,
     BackendStatusPollFailure(String),
+    /// An attempt to connect to the backend ended, see [`BackendHandler::connect_with`]
+    BackendConnected,
+    BackendForm(BackendFormMsg),
     PlayingStatusTick,
+    ScheduleTick,
+    VolumeRampTick,
+    SleepTimerTick,
+    /// The song's notification waited long enough, if no other song started since
+    SongNoticeDue(u64),
+    OutputDeviceTick,
+    OutputDevicePolled(Option<String>),
+    MaintenanceTick,
+    /// Saves the settings if they changed, which the window does
+    AutosaveTick,
+    /// None if the pass was skipped because the folder was busy
+    MaintenanceFinished(Result<Option<MaintenanceReport>, String>),
+    /// None if the pass was skipped because the folder was busy
+    ValidationFinished(Result<Option<ValidationReport>, String>),
+    /// The songs with a file in the audio cache
+    DownloadsLocated(Result<HashSet<String>, String>),
+    /// None if the pass was skipped because the folder was busy
+    EvictionFinished(Result<Option<EvictionReport>, String>),
+    /// Files deleted from a cache folder by hand
+    FilesRemoved(CacheFolder, Result<RemovalReport, String>),
+    /// The playlist's songs that aren't in the sounds cache, to download as a batch
+    BatchChecked(Result<Vec<SongKey>, String>),
+    /// How many bytes the sounds cache takes up, checked between the batch's downloads
+    BatchUsageChecked(Result<u64, String>),
 
-    RequestRecieved(RequestResult),
+    /// The response to the search request with the given id
+    RequestRecieved(u64, RequestResult),
+    /// The saved songs matching a local search request
+    LocalSearchFinished(u64, Result<Vec<Song>, String>),
 
     // Searching
     SearchedKeysReceived {
         existing: RwMap<String, Song>,
         missing: Vec<String>,
+        /// Where the missing songs are being found
+        source: SongSource,
     },
 
     ManagerMsg(ChangeSong),
     SearchWindowMessage(SWMessage),
     PlaylistMsg(PlaylistMessage),
+    Simple(SimpleMsg),
+    Queue(QueueMsg),
     AudioTrackerMessage(TrackerMsg),
+    WhatsNew(WhatsNewMsg),
+    ExternalChange(ExternalChangeMsg),
+    DuplicatePrompt(DuplicatePromptMsg),
+    /// Something went wrong that the user may want to retry
+    Failed(Failure),
+    Failures(FailuresMsg),
+    /// Tells the user something happened, without keeping it around
+    Notify(Notification),
+    /// A click that nothing under the cursor handled
+    CloseContextMenu,
+    Notifications(NotificationsMsg),
+    /// Opens the bulk editor on the selected songs
+    OpenBulkEdit,
+    /// Adds the selected songs to the playlist, in the group of the focused row
+    AddSelected,
+    BulkEdit(BulkEditMsg),
+    OpenSongEditor(String),
+    SongEditor(SongEditMsg),
+    /// Opens the settings panel, or closes it
+    ToggleSettings,
+    SettingsPanel(SettingsPanelMsg),
+    /// The settings file after it was changed outside the app
+    ExternalSettingsLoaded(Result<YTMRSettings, LoadError>),
+    SettingsSaved(Result<PathBuf, SaveError>),
+    PlaylistSaved(Result<PathBuf, SaveError>),
+    PlaylistExported(Result<PathBuf, SaveError>),
+    /// The playlist to open in place of the current one
+    PlaylistLoaded(Result<Playlist, LoadError>),
+    /// The playlist with the id had its file removed, or not
+    PlaylistDeleted(uuid::Uuid, Result<(), SaveError>),
+    /// The playlist made from a file, to open in place of the current one
+    PlaylistImported(Result<Imported, LoadError>),
+    /// The newest plays of the history, and whether there were older ones
+    HistoryLoaded(Result<(Vec<PlayRecord>, bool), String>),
+    History(HistoryMsg),
+    ToggleHistory,
+    Panes(PanesMsg),
+    NowPlaying(NowPlayingMsg),
+    LibraryLoaded(Vec<PlaylistHeader>),
+    NotesEdited(text_editor::Action),
+    /// Searches for the source again
+    ReopenSource(String),
+    /// Shows the saved songs that were found in the same place
+    ShowFromSource(SongSource),
+    SaveNotes,
 
     ImagesFetched {
         map: HashMap<String, Handle>,
         missing: Option<HashSet<String>>,
     },
+    /// The thumbnails of the batch that could be downloaded
+    ThumbnailsDownloaded {
+        batch: HashSet<String>,
+        map: HashMap<String, Handle>,
+    },
     SongsFetched {
         map: RwMap<String, Song>,
         get_existing_thumbnails: bool,
     },
-    SoundsFetched {
-        map: HashMap<String, StaticSoundData>,
-        play: Option<String>,
-    },
+    SoundFetched(String, CachedSound),
     DownloadSong(String, bool),
+    DownloadProgress {
+        id: String,
+        downloaded_bytes: u64,
+        total_bytes: Option<u64>,
+        /// In seconds
+        eta: Option<f64>,
+    },
+    /// The backend finished the download, with the song's info
+    DownloadEnded {
+        id: String,
+        result: RequestResult,
+    },
+    Downloads(DownloadsMsg),
+    /// The songs of a batch of the metadata queue, by the ids they were asked for.
+    /// Those fetched still need keys assigned.
+    SongsReceived(Vec<(String, Result<Song, FetchError>)>),
+    /// Writes the songs to the metadata cache, as a retry of a failed write
+    WriteSongs(Vec<Song>),
+    SongsWritten {
+        map: RwMap<String, Song>,
+        result: Result<(), String>,
+    },
     SongDownloaded {
+        /// The key the download was requested for
+        key: String,
         song: Song,
         play: bool,
     },
+    /// A song was downloaded to the cache without being played
+    SongCached(String),
     SongDownloadFinished {
         id: String,
         data: Box<BasicSoundData>,
     },
+    /// A cached song to stream from its file
+    SoundLocated {
+        id: String,
+        path: PathBuf,
+    },
+    SetPlaybackMode(PlaybackMode),
+    /// Turns loudness normalization on or off
+    ToggleNormalize,
 
     SetNewBackground(String, BasicYtmrsScheme),
+    /// The color picked from the song's thumbnail, to make its background from
+    PrimaryColorPicked(String, [u8; 4]),
+    /// The result of submitting this many listens
+    #[cfg(feature = "scrobble")]
+    ScrobblesSubmitted(usize, Result<(), String>),
+    /// The ListenBrainz token read from its file
+    #[cfg(feature = "scrobble")]
+    TokenLoaded(Result<String, String>),
+    #[cfg(feature = "scrobble")]
+    TokenSaved(Result<(), String>),
+    /// A command from the system's media controls
+    #[cfg(feature = "media-controls")]
+    MediaControl(MediaCommand),
     Null,
 }
 
@@ -155,28 +743,169 @@ impl Ytmrs {
     pub fn new(settings: YTMRSettings, backend_handler: Arc<Mutex<BackendHandler>>) -> Self {
         Self {
             audio_tracker: AudioProgressTracker::new(&settings.user),
+            whats_new: WhatsNew::on_startup(settings.user.last_seen_version.as_deref()),
+            backend_form: BackendForm::new(&settings.backend),
+            tickers: Tickers::new(&settings.user),
+            panes: Panes::new(settings.user.pane_split),
             settings,
             backend_handler,
             ..Self::default()
         }
     }
 
-    pub fn load(&mut self) -> Cm<YtmrsMsg> {
+    pub fn load(&mut self, options: &StartupOptions) -> Cm<YtmrsMsg> {
         // Add the cache to required places
         self.settings
             .playlist
             .constructor
             .set_cache(Arc::clone(&self.cache.song_metadata));
         self.search.cache = Some(Arc::clone(&self.cache.song_metadata));
+        self.settings_modified = settings_modified();
+        self.saved_fingerprint = self.settings.fingerprint();
+        #[allow(unused_mut)]
+        let mut listeners = events::default_listeners();
+        #[cfg(feature = "scrobble")]
+        {
+            self.scrobbler.lock().outbox = scrobbler::Outbox::load();
+            listeners.push(Box::new(ScrobbleListener(Arc::clone(&self.scrobbler))));
+        }
+        #[cfg(feature = "media-controls")]
+        let attach = {
+            let (listener, commands) = media_controls::start();
+            listeners.push(Box::new(listener));
+            self.media_commands = commands;
+            self.media_commands.attach().map(|()| YtmrsMsg::Null)
+        };
+        self.events = EventBus::start(listeners);
+        self.audio_manager.apply_eq(&self.settings.user.eq);
+        self.sync_used_keys();
+        let trimmed = self.history.set_limit(self.settings.user.history_limit);
+
+        let unknown = matches!(
+            self.backend_handler.lock().status,
+            BackendLaunchStatus::Unknown
+        );
+        let connect = match unknown {
+            true => backend_handler::connect_with(
+                &self.backend_handler,
+                &options.backend(&self.settings.backend),
+            ),
+            false => {
+                self.schedule_backend_poll();
+                Cm::none()
+            }
+        };
+        self.library.record(&self.settings.playlist);
+        if let Some(session) = self.settings.session.take() {
+            self.restore_session(session);
+        }
+        let startup = match options.playlist_file() {
+            Some(path) => {
+                self.play_when_opened = options.play;
+                self.replace_playlist(|_| Playlist::read(path), YtmrsMsg::PlaylistLoaded)
+            }
+            None if options.play => self.start_playing(),
+            None => Cm::none(),
+        };
+        let sounds = self.cache.sounds.reader.clone();
+
+        Cm::batch([
+            self.fetch_missing_metadata(),
+            Cm::perform(
+                async move {
+                    sounds
+                        .validate(Some(ORPHAN_AGE))
+                        .await
+                        .map_err(|e| format!("{e:?}"))
+                },
+                YtmrsMsg::ValidationFinished,
+            ),
+            self.locate_downloads(),
+            Cm::perform(
+                async { playlist::load_headers(&playlists_directory()).await },
+                YtmrsMsg::LibraryLoaded,
+            ),
+            Cm::perform(self.history.load(), YtmrsMsg::HistoryLoaded),
+            self.load_scrobble_token(),
+            Cm::perform(trimmed, |()| YtmrsMsg::Null),
+            connect,
+            startup,
+            #[cfg(feature = "media-controls")]
+            attach,
+        ])
+    }
 
-        let mut backend = self.backend_handler.lock();
+    #[cfg(feature = "scrobble")]
+    fn load_scrobble_token(&mut self) -> Cm<YtmrsMsg> {
+        let from_settings = std::mem::take(&mut self.settings.user.scrobble.token);
+        Cm::perform(
+            scrobbler::load_token(scrobbler::token_path(), from_settings),
+            YtmrsMsg::TokenLoaded,
+        )
+    }
+    #[cfg(not(feature = "scrobble"))]
+    fn load_scrobble_token(&mut self) -> Cm<YtmrsMsg> {
+        Cm::none()
+    }
+
+    fn locate_downloads(&self) -> Cm<YtmrsMsg> {
+        let sounds = self.cache.sounds.reader.clone();
+        Cm::perform(
+            async move {
+                let located = sounds.locate_all().await.map_err(|e| e.to_string())?;
+                Ok(located.into_keys().collect())
+            },
+            YtmrsMsg::DownloadsLocated,
+        )
+    }
 
-        if let BackendLaunchStatus::Unknown = backend.status {
-            *backend = BackendHandler::load(None);
+    /// Shows the songs with a download as cached, unless they're shown as something else
+    fn mark_downloaded(&self, songs: &RwMap<String, Song>) {
+        for (key, song) in songs {
+            let mut song = song.write();
+            if self.downloaded.contains(key) && matches!(song.ui_state, SongState::None) {
+                song.ui_state = SongState::Cached;
+            }
         }
-        let cache = self.cache.song_metadata.write();
+    }
+
+    /// Reads the metadata of the songs from the metadata file, and asks the backend for the
+    /// ones it doesn't have. Those found by the backend are marked as found in `source`.
+    fn request_metadata(&self, keys: HashSet<String>, source: SongSource) -> Cm<YtmrsMsg> {
+        let reader = self.cache.song_metadata.write().reader.clone();
+        Cm::perform(
+            async move {
+                let new_songs: RwMap<String, Song> = join_all(reader.read_from_ids(&keys).await)
+                    .await
+                    .into_iter()
+                    .collect();
+                let ids: HashSet<String> = new_songs.keys().cloned().collect();
+
+                // Find keys that are still missing after fetching
+                let missing = keys.difference(&ids);
+
+                (new_songs, missing.cloned().collect())
+            },
+            move |(existing, missing)| YtmrsMsg::SearchedKeysReceived {
+                existing,
+                missing,
+                source,
+            },
+        )
+    }
+
+    /// Reads the metadata of the used songs that aren't in the cache yet.
+    /// Songs shared with the previous playlist are already there, and are left alone.
+    fn fetch_missing_metadata(&self) -> Cm<YtmrsMsg> {
+        let cache = self.cache.song_metadata.read();
         let metadata_reader = cache.reader.clone();
-        let keys = self.all_used_keys();
+        let existing = cache.fetch_existing(self.used_keys.keys());
+        let keys: HashSet<String> = self
+            .used_keys
+            .keys()
+            .filter(|key| !existing.contains_key(*key))
+            .cloned()
+            .collect();
 
         Cm::perform(
             async move {
@@ -192,113 +921,423 @@ impl Ytmrs {
         )
     }
 
-    pub fn prepare_to_save(&mut self) {}
+    pub fn prepare_to_save(&mut self) {
+        self.settings.session = self.session();
+        self.saving_fingerprint = Some(self.settings.fingerprint());
+    }
+
+    /// Whether the settings changed since they were last saved. Playback moving along doesn't
+    /// count, it's only written along with other changes.
+    pub fn has_unsaved_changes(&self) -> bool {
+        self.settings.fingerprint() != self.saved_fingerprint
+    }
+
+    /// Where playback is, or the restored session when its song hasn't started yet
+    fn session(&self) -> Option<Session> {
+        let current = match (&self.player_state, &self.now_playing) {
+            (Some(state), Some(key)) => Some(Session {
+                tracker: state.tracker.clone(),
+                key: key.clone(),
+                elapsed: self.audio_manager.elapsed().unwrap_or_default(),
+                paused: self.audio_tracker.paused,
+            }),
+            _ => None,
+        };
+        current.or_else(|| self.resuming.clone())
+    }
+
+    /// Picks the tracker back up, the song plays once its metadata is read
+    fn restore_session(&mut self, session: Session) {
+        let op = self
+            .settings
+            .user
+            .simple
+            .build(self.settings.playlist.constructor.build());
+        match session.restore(&op) {
+            Some(tracker) => {
+                info!["Resuming {} at {:.1}s", session.key, session.elapsed];
+                self.player_state = Some(self.player_state_for(tracker, op));
+                self.resuming = Some(session);
+            }
+            None => println!["The playlist changed, {} isn't resumed", session.key],
+        }
+    }
+
+    /// Records that the settings file was just written by us
+    pub fn saved(&mut self) {
+        self.settings_modified = settings_modified();
+        if let Some(fingerprint) = self.saving_fingerprint.take() {
+            self.saved_fingerprint = fingerprint;
+        }
+    }
+
+    /// Replaces the playlist with the externally changed one, keeping what it replaced
+    fn apply_external_playlist(&mut self, mut constructor: song_operations::SongOpConstructor) {
+        let diff = tree_diff::diff(&self.settings.playlist.constructor, &constructor);
+        if diff.is_empty() {
+            return;
+        }
+        println!["Playlist changed externally: {diff}"];
+        constructor.set_cache(Arc::clone(&self.cache.song_metadata));
+        let playlist = &mut self.settings.playlist;
+        let previous = std::mem::replace(&mut playlist.constructor, constructor);
+        playlist
+            .history
+            .record(previous.clone(), &playlist.constructor);
+        // The tracker walks the old tree, which may not match the new one anymore
+        self.player_state = None;
+        self.external_change = Some(ExternalChange::new(previous, diff));
+    }
 
     pub fn subscription(&self) -> Subscription<YtmrsMsg> {
         Subscription::batch([
-            self.tickers.subscription(),
+            self.tickers.subscription(self.progress_ticks()),
             // Handle tracking modifiers
-            keyboard::on_key_press(|k, m| Some(YtmrsMsg::KeysChanged(k, m))),
+            keyboard::on_key_press(|k, m| Some(YtmrsMsg::KeyPressed(k, m))),
             keyboard::on_key_release(|k, m| Some(YtmrsMsg::KeysChanged(k, m))),
             // Checking when songs finish
-            self.audio_manager.subscription().map(YtmrsMsg::ManagerMsg),
+            audio::song_end_subscription(&self.audio_manager).map(YtmrsMsg::ManagerMsg),
+            self.notifications
+                .subscription()
+                .map(YtmrsMsg::Notifications),
+            self.search
+                .subscription()
+                .map(YtmrsMsg::SearchWindowMessage),
+            self.downloads
+                .subscription()
+                .map(|(id, event)| match event {
+                    DownloadEvent::Progress(progress) => YtmrsMsg::DownloadProgress {
+                        id,
+                        downloaded_bytes: progress.downloaded_bytes,
+                        total_bytes: progress.total_bytes,
+                        eta: progress.eta,
+                    },
+                    DownloadEvent::Finished(result) => YtmrsMsg::DownloadEnded { id, result },
+                }),
+            #[cfg(feature = "media-controls")]
+            self.media_commands
+                .subscription()
+                .map(YtmrsMsg::MediaControl),
         ])
     }
 
     pub fn view(&self, scheme: FullYtmrsScheme) -> Element<YtmrsMsg> {
-        let backend = self.backend_handler.lock();
-        let backend_status = backend.status.as_string();
-
-        let search = self.search.view(&scheme).map(YtmrsMsg::SearchWindowMessage);
+        let started = time::Instant::now();
+        let status = self.backend_handler.lock().status.as_string();
+        let backend_status = row![
+            text(status),
+            button("settings").on_press(YtmrsMsg::ToggleSettings),
+        ]
+        .spacing(8)
+        .align_items(Alignment::Center);
+        let settings_panel = self.settings_panel.as_ref().map(|panel| {
+            panel
+                .view(
+                    &self.settings.user,
+                    &self.backend_form,
+                    status,
+                    &self.settings.schedule,
+                    self.library.headers(),
+                    self.settings.playlist.id,
+                )
+                .map(YtmrsMsg::SettingsPanel)
+        });
 
-        let current_playlist = self
-            .settings
-            .playlist
-            .view(&scheme)
-            .map(YtmrsMsg::PlaylistMsg);
+        let (search_menu, playlist_menu) = match &self.context_menu {
+            Some(MenuTarget::Search(idx)) => (Some(*idx), None),
+            Some(MenuTarget::Playlist(wid)) => (None, Some(wid)),
+            None => (None, None),
+        };
+        let panes = self.panes.view(
+            |kind| match kind {
+                PaneKind::Search => self
+                    .search
+                    .view(&scheme, search_menu)
+                    .map(YtmrsMsg::SearchWindowMessage),
+                PaneKind::Playlist => self.playlist_pane(&scheme, playlist_menu),
+            },
+            YtmrsMsg::Panes,
+        );
+        let rows = song_list::take_built_rows();
+        trace!["Built {} song rows", rows];
 
-        let base_drop_target = Container::new(Space::with_height(Length::Fill))
-            .width(Length::Fill)
-            .id(CId::new("base_drop_target"));
+        let (side, toggle) = match self.history_open {
+            true => (
+                self.history
+                    .view(&self.settings.playlist.constructor)
+                    .map(YtmrsMsg::History),
+                "queue",
+            ),
+            false => (
+                self.queue
+                    .view(&self.settings.playlist.constructor, &scheme)
+                    .map(YtmrsMsg::Queue),
+                "history",
+            ),
+        };
+        let queue = column![
+            button(toggle).on_press(YtmrsMsg::ToggleHistory),
+            scrollable(side).style(scheme.scrollable_style.clone().update()),
+        ];
 
         let tracker = self
             .audio_tracker
             .view(&scheme)
             .map(YtmrsMsg::AudioTrackerMessage);
 
-        Element::new(
-            column![
-                column![
-                    backend_status,
-                    row![search, column![current_playlist, base_drop_target]],
-                ]
-                .spacing(20),
-                tracker
+        // Announces the focused row, standing in for proper accessibility labels
+        let focus_description = match self.inputs.focused_list {
+            FocusedList::None => None,
+            FocusedList::Search => self.search.focused_description(),
+            FocusedList::Constructor => self.settings.playlist.focused_description(),
+        };
+
+        let whats_new = self.whats_new.view().map(|e| e.map(YtmrsMsg::WhatsNew));
+        let failures = self
+            .failures
+            .view(self.offline)
+            .map(|e| e.map(YtmrsMsg::Failures));
+        let notifications = self
+            .notifications
+            .view()
+            .map(|e| e.map(YtmrsMsg::Notifications));
+        let downloads = self.downloads.view().map(|e| e.map(YtmrsMsg::Downloads));
+        let external_change = self
+            .external_change
+            .as_ref()
+            .map(|change| change.view().map(YtmrsMsg::ExternalChange));
+        let duplicate_prompt = self
+            .duplicate_prompt
+            .as_ref()
+            .map(|prompt| prompt.view().map(YtmrsMsg::DuplicatePrompt));
+
+        let bulk_edit: Option<Element<YtmrsMsg>> = match &self.bulk_edit {
+            Some(editor) => Some(editor.view().map(YtmrsMsg::BulkEdit)),
+            None => self
+                .search
+                .selected_keys()
+                .and_then(|keys| match keys.as_slice() {
+                    [] => None,
+                    [key] => Some(
+                        row![
+                            button("edit song").on_press(YtmrsMsg::OpenSongEditor(key.to_string())),
+                            button("add song").on_press(YtmrsMsg::AddSelected),
+                        ]
+                        .spacing(4)
+                        .into(),
+                    ),
+                    keys => Some(
+                        row![
+                            button(text(format!("edit {} selected", keys.len())))
+                                .on_press(YtmrsMsg::OpenBulkEdit),
+                            button(text(format!("add {} selected", keys.len())))
+                                .on_press(YtmrsMsg::AddSelected),
+                        ]
+                        .spacing(4)
+                        .into(),
+                    ),
+                }),
+        };
+        let song_editor = self
+            .song_editor
+            .as_ref()
+            .map(|editor| editor.view().map(YtmrsMsg::SongEditor));
+
+        let notes = self.now_playing.as_ref().map(|key| {
+            row![
+                text_editor(&self.notes)
+                    .on_action(YtmrsMsg::NotesEdited)
+                    .height(60),
+                button("save notes").on_press(YtmrsMsg::SaveNotes),
+                button("edit").on_press(YtmrsMsg::OpenSongEditor(key.clone())),
+                // Changes take effect the next time the song plays
+                pick_list(
+                    PlaybackMode::ALL,
+                    Some(self.playback_mode(key)),
+                    YtmrsMsg::SetPlaybackMode
+                ),
             ]
-            .align_items(Alignment::Center),
-        )
+            .spacing(4)
+            .align_items(Alignment::Center)
+        });
+
+        let now_playing = self.now_playing.as_ref().map(|key| {
+            let song = self
+                .cache
+                .song_metadata
+                .read()
+                .items()
+                .get(key)
+                .map(|song| song.read().clone());
+            let playlist = &self.settings.playlist;
+            NowPlaying::new(
+                key,
+                song.as_ref(),
+                &playlist.constructor,
+                &playlist.name,
+                self.playing_tree_path().as_deref(),
+            )
+            .view()
+            .map(YtmrsMsg::NowPlaying)
+        });
+
+        let album = self.album_position().map(|position| {
+            row![
+                text(position.describe()),
+                button("restart album")
+                    .on_press(YtmrsMsg::AudioTrackerMessage(TrackerMsg::RestartGroup)),
+            ]
+            .spacing(4)
+            .align_items(Alignment::Center)
+        });
+
+        let source = self.now_playing.as_ref().map(|_| {
+            let source = &self.now_playing_source;
+            row![text(format!("from {source}"))]
+                .push_maybe(source.reopen_query().map(|query| {
+                    button("open").on_press(YtmrsMsg::ReopenSource(query.to_string()))
+                }))
+                .push_maybe((!source.is_unknown()).then(|| {
+                    button("more from here").on_press(YtmrsMsg::ShowFromSource(source.clone()))
+                }))
+                .spacing(4)
+                .align_items(Alignment::Center)
+        });
+
+        let cache_stats = self.cache_stats.map(|stats| {
+            let lines = stats.lines().map(|line| text(line).size(12).into());
+            Container::new(Column::with_children(lines))
+                .width(Length::Fill)
+                .align_x(Horizontal::Right)
+        });
+
+        let close_menu = self
+            .context_menu
+            .as_ref()
+            .map(|_| YtmrsMsg::CloseContextMenu);
+        view_built(started.elapsed(), rows);
+        let window = closes_menus(
+            column![]
+                .push_maybe(cache_stats)
+                .push_maybe(whats_new)
+                .push_maybe(external_change)
+                .push_maybe(duplicate_prompt)
+                .push_maybe(failures)
+                .push_maybe(downloads)
+                .push_maybe(bulk_edit)
+                .push_maybe(song_editor)
+                .push_maybe(settings_panel)
+                .push(column![backend_status, row![panes, queue]].spacing(20))
+                .push_maybe(focus_description.map(text))
+                .push_maybe(now_playing)
+                .push_maybe(album)
+                .push_maybe(source)
+                .push_maybe(notes)
+                .push(tracker)
+                .align_items(Alignment::Center),
+            close_menu,
+        );
+        match notifications {
+            Some(notifications) => stack![window, notifications].into(),
+            None => window,
+        }
+    }
+
+    /// The library, the playlist being edited, and the space songs can be dropped on below it
+    fn playlist_pane(&self, scheme: &FullYtmrsScheme, menu: Option<&WId>) -> Element<YtmrsMsg> {
+        let library = self
+            .library
+            .view(&self.settings.playlist)
+            .map(YtmrsMsg::PlaylistMsg);
+
+        let mode = &self.settings.user.simple;
+        let mode_toggle = button(match mode.enabled {
+            true => "advanced mode",
+            false => "simple mode",
+        })
+        .on_press(YtmrsMsg::Simple(SimpleMsg::ToggleMode));
+        let current_playlist: Element<YtmrsMsg> = match mode.enabled {
+            true => {
+                let focused = self.settings.playlist.focused_song();
+                let queue = self
+                    .simple
+                    .view(
+                        &self.settings.playlist.constructor,
+                        mode,
+                        scheme,
+                        focused.as_ref(),
+                    )
+                    .map(YtmrsMsg::Simple);
+                scrollable(queue)
+                    .style(scheme.scrollable_style.clone().update())
+                    .into()
+            }
+            false => self
+                .settings
+                .playlist
+                .view(scheme, menu)
+                .map(YtmrsMsg::PlaylistMsg),
+        };
+
+        let base_drop_target = Container::new(Space::with_height(Length::Fill))
+            .width(Length::Fill)
+            .id(CId::new("base_drop_target"));
+
+        column![library, mode_toggle, current_playlist, base_drop_target].into()
     }
 
     pub fn parse_search_request(&mut self, response_type: YTResponseType) -> Cm<YtmrsMsg> {
+        let source = response_type.source(&self.search.last_query);
         match response_type {
-            YTResponseType::Song(_song) => {
+            YTResponseType::Song(mut song) => {
                 println!["Request is a song"];
-                Cm::none()
+
+                song.source = source;
+                let provisional = song.as_data();
+                let (keys, command) = self.ingest_songs(vec![song]);
+                let key = keys[0].clone();
+
+                let ids = HashSet::from([key.clone()]);
+                let provisional = HashMap::from([(key.clone(), provisional)]);
+                self.search.set_results(SearchType::Song(key), provisional);
+
+                Cm::batch([self.download_images_for_ids(ids), command])
             }
             YTResponseType::Tab(t) => {
                 println!["Request is a 'tab'"];
 
-                let songs: Vec<Song> = t
-                    .entries
-                    .into_iter()
-                    .map(|entry| Song {
-                        id: entry.id,
-                        title: entry.title,
-                        channel: entry.channel.clone(),
-                        view_count: entry.view_count,
-                        webpage_url: entry.url,
-                        duration: entry.duration,
-                        thumbnail: entry.thumbnails[0].url.clone(),
-                        artists: Some(vec![entry.channel.clone()]),
-                        ..Default::default()
-                    })
+                // The tab's entries are only shown until the songs are cached, they're missing
+                // too much to be saved as songs
+                let songs = t.into_songs();
+                let keys: Vec<String> = songs.iter().map(|song| song.id.clone()).collect();
+                let provisional = songs
+                    .iter()
+                    .map(|song| (song.id.clone(), song.as_data()))
                     .collect();
 
-                let keys: Vec<_> = songs.iter().map(|s| s.id.clone()).collect();
-
-                self.search.search_type = SearchType::new_tab(keys.clone());
-
-                let map = songs.iter().map(|s| (s.id.clone(), s.clone())).to_rwmap();
-
-                let ids: HashSet<String> = songs.iter().map(|s| s.id.clone()).collect();
+                self.search
+                    .set_results(SearchType::new_tab(keys.clone()), provisional);
 
-                // Add the songs to the file cache
-                let reader = { self.cache.song_metadata.write().reader.clone() };
-
-                Cm::batch([
-                    self.download_images_for_ids(ids),
-                    Cm::perform(
-                        async move {
-                            println!("extend songs {:?}", reader.extend(songs, true).await);
-                        },
-                        move |_| YtmrsMsg::SongsFetched {
-                            map,
-                            get_existing_thumbnails: true,
-                        },
-                    ),
-                ])
+                self.request_metadata(keys.into_iter().collect(), source)
             }
             YTResponseType::Search(s) => {
                 println!["Request is a search"];
                 // println!["{:?}", s];
 
                 let mut song_keys: HashSet<String> = HashSet::new();
+                let mut provisional: HashMap<String, SongData> = HashMap::new();
                 let mut entries: Vec<SearchEntry> = vec![];
                 // Get links for each entry
                 for entry in s.entries {
-                    let search_entry: SearchEntry = SearchEntry::new(entry).unwrap();
+                    let data = entry.as_data();
+                    // Skip entries that can't be shown
+                    let search_entry: SearchEntry = match SearchEntry::new(entry) {
+                        Ok(e) => e,
+                        Err(_) => continue,
+                    };
                     if let SearchEntry::Song { id, .. } = &search_entry {
                         song_keys.insert(id.clone());
+                        provisional.insert(id.clone(), data);
                     }
 
                     entries.push(search_entry);
@@ -313,32 +1352,15 @@ impl Ytmrs {
                     .cloned()
                     .collect();
 
-                self.search.search_type = SearchType::Search(entries);
+                self.search
+                    .set_results(SearchType::Search(entries), provisional);
 
                 println!["{:?}, {:?}", existing_keys.len(), song_keys.len()];
                 if existing_keys.len() != song_keys.len() {
                     // We need to fetch the metadata for these songs.
                     let missing: HashSet<String> =
                         song_keys.difference(&existing_keys).cloned().collect();
-
-                    let reader = self.cache.song_metadata.write().reader.clone();
-
-                    Cm::perform(
-                        async move {
-                            let new_songs: RwMap<String, Song> =
-                                join_all(reader.read_from_ids(&missing).await)
-                                    .await
-                                    .into_iter()
-                                    .collect();
-                            let ids: HashSet<String> = new_songs.keys().cloned().collect();
-
-                            // Find keys that are still missing after fetching
-                            let missing = missing.difference(&ids);
-
-                            (new_songs, missing.cloned().collect())
-                        },
-                        |(existing, missing)| YtmrsMsg::SearchedKeysReceived { existing, missing },
-                    )
+                    self.request_metadata(missing, source)
                 } else {
                     self.download_images_for_ids(existing_keys)
                 }
@@ -347,154 +1369,725 @@ impl Ytmrs {
     }
 
     pub fn update(&mut self, message: YtmrsMsg) -> Cm<YtmrsMsg> {
+        self.tickers.settle(time::Instant::now());
+
         match message {
             // * User input
             YtmrsMsg::HandleZones(song_key, zones) => {
-                if !zones.is_empty() {
-                    self.handle_zones(song_key, zones);
+                let keys = match self.search.selected_keys() {
+                    Some(keys) => keys.into_iter().cloned().collect(),
+                    None => vec![song_key],
+                };
+                match self.drop_path(&zones).is_some() {
+                    true => self.add_checked(keys, AddTarget::Dropped(zones)),
+                    false => Cm::none(),
                 }
-
-                Cm::none()
             }
             YtmrsMsg::KeysChanged(_, m) => {
-                println!["{:?}", m];
+                trace!["{:?}", m];
                 self.inputs.modifiers = m;
 
                 Cm::none()
             }
+            YtmrsMsg::KeyPressed(k, m) => {
+                self.inputs.modifiers = m;
+
+                if route_debug_overlay(&k, &m) {
+                    self.cache_stats = match self.cache_stats {
+                        Some(_) => None,
+                        None => Some(self.cache.stats()),
+                    };
+                    return Cm::none();
+                }
+
+                if let Some(command) = self.navigate(&k) {
+                    return command;
+                }
+
+                // The editors focus their first field when they open
+                let typing =
+                    self.inputs.typing || self.bulk_edit.is_some() || self.song_editor.is_some();
+                if let Some(step) = route_history(&k, &m, typing) {
+                    return self.step_history(step);
+                }
+                if let Some(action) = self.inputs.transport.action(&k, &m, typing) {
+                    return self.transport(action);
+                }
+
+                match route_rating(&k, &m, self.now_playing.as_ref()) {
+                    Some((key, rating)) => self.rate_song(key.clone(), rating),
+                    None => Cm::none(),
+                }
+            }
 
             // * Ticks
             YtmrsMsg::CacheTick => {
-                let used_meta_keys: HashSet<String> = self.all_used_keys();
+                self.sync_used_keys();
+                if self.cache_stats.is_some() {
+                    self.cache_stats = Some(self.cache.stats());
+                }
                 {
                     let mut metadata = self.cache.song_metadata.write();
-                    let available_keys: HashSet<String> =
-                        metadata.items().keys().cloned().collect();
-
-                    let unused_keys = available_keys
-                        .difference(&used_meta_keys)
+                    let unused_keys: Vec<String> = metadata
+                        .items()
+                        .keys()
+                        .filter(|key| !self.used_keys.contains(key))
                         .cloned()
-                        .collect::<Vec<String>>();
+                        .collect();
 
                     metadata.drop_from_cache(unused_keys);
                 }
-                Cm::none()
+
+                let modified = settings_modified();
+                let reload = match modified.is_some() && modified != self.settings_modified {
+                    true => {
+                        self.settings_modified = modified;
+                        Cm::perform(
+                            YTMRSettings::load_default(),
+                            YtmrsMsg::ExternalSettingsLoaded,
+                        )
+                    }
+                    false => Cm::none(),
+                };
+                Cm::batch([reload, self.evict_sounds()])
             }
-            YtmrsMsg::BackendStatusTick => {
-                self.backend_handler.lock().poll();
-                Cm::none()
+            // The window saves, see main
+            YtmrsMsg::AutosaveTick => Cm::none(),
+            YtmrsMsg::MaintenanceTick => {
+                let reader = self.cache.sounds.reader.clone();
+                Cm::perform(
+                    async move {
+                        reader
+                            .maintain(STALE_TMP_AGE)
+                            .await
+                            .map_err(|e| format!("{e:?}"))
+                    },
+                    YtmrsMsg::MaintenanceFinished,
+                )
+            }
+            YtmrsMsg::MaintenanceFinished(result) => {
+                match result {
+                    Ok(Some(report)) => println![
+                        "Sound folder maintenance: {} pruned, {} quarantined, {} bytes reclaimed",
+                        report.pruned, report.quarantined, report.bytes_reclaimed
+                    ],
+                    Ok(None) => println!["Sound folder is busy, skipping maintenance"],
+                    Err(e) => println!["Sound folder maintenance failed: {e}"],
+                }
+                Cm::none()
+            }
+            YtmrsMsg::ValidationFinished(result) => {
+                match result {
+                    Ok(Some(report)) => {
+                        println![
+                            "Sound folder validation: {} pruned, {} orphans removed",
+                            report.pruned, report.orphans_removed
+                        ];
+                        for orphan in report.orphans {
+                            println!["Sound file without an index entry: {:?}", orphan];
+                        }
+                    }
+                    Ok(None) => println!["Sound folder is busy, skipping validation"],
+                    Err(e) => println!["Sound folder validation failed: {e}"],
+                }
+                Cm::none()
+            }
+            YtmrsMsg::DownloadsLocated(result) => {
+                match result {
+                    Ok(ids) => {
+                        self.downloaded.extend(ids);
+                        let metadata = self.cache.song_metadata.read();
+                        self.mark_downloaded(metadata.items());
+                    }
+                    Err(e) => println!["Failed to read the audio cache's index: {e}"],
+                }
+                Cm::none()
+            }
+            YtmrsMsg::EvictionFinished(result) => {
+                match result {
+                    Ok(Some(report)) if report.evicted > 0 => {
+                        self.notify(Notification::info(format!(
+                            "Removed {} songs from the cache, freeing {:.1}MB",
+                            report.evicted,
+                            report.bytes_freed as f64 / 1_000_000.0
+                        )))
+                    }
+                    Ok(Some(report)) => debug!["Sound cache is {} bytes", report.bytes_left],
+                    Ok(None) => debug!["Sound folder is busy, skipping eviction"],
+                    Err(e) => println!["Sound cache eviction failed: {e}"],
+                }
+                Cm::none()
+            }
+            YtmrsMsg::FilesRemoved(folder, result) => {
+                let report = match result {
+                    Ok(report) => report,
+                    Err(e) => {
+                        self.notify(Notification::error(format!(
+                            "Failed to remove the {} files: {e}",
+                            folder.label()
+                        )));
+                        return Cm::none();
+                    }
+                };
+                self.notify(Notification::info(format!(
+                    "Removed {} {} files, freeing {:.1}MB",
+                    report.removed.len(),
+                    folder.label(),
+                    report.bytes_freed as f64 / 1_000_000.0
+                )));
+                match folder {
+                    CacheFolder::Sounds => {
+                        self.cache.sounds.policy.forget(&report.removed);
+                        for id in &report.removed {
+                            self.downloaded.remove(id);
+                            self.show_song_state(id, SongState::None);
+                        }
+                        self.cache.sounds.drop_from_cache(report.removed);
+                        Cm::none()
+                    }
+                    CacheFolder::Thumbnails => {
+                        // Their files are gone, so the rows on screen download them again
+                        let metadata = self.cache.song_metadata.read();
+                        for song in metadata.items().values() {
+                            song.write().thumbnail_handle = None;
+                        }
+                        metadata.touch();
+                        drop(metadata);
+                        self.download_images_for_ids(self.songs_on_screen())
+                    }
+                }
+            }
+            YtmrsMsg::BackendStatusTick => backend_handler::poll(&self.backend_handler),
+            YtmrsMsg::BackendStatusPollSuccess => {
+                self.backend_handler.lock().answered();
+                self.schedule_backend_poll();
+                Cm::none()
+            }
+            YtmrsMsg::BackendConnected => {
+                self.schedule_backend_poll();
+                Cm::none()
             }
-            YtmrsMsg::BackendStatusPollSuccess => Cm::none(),
             YtmrsMsg::BackendStatusPollFailure(e) => {
-                println!["Polling failure: {:?}", e];
-                let mut backend = self.backend_handler.lock();
-                backend.status = BackendLaunchStatus::Unknown;
-                todo!()
+                println!["The backend stopped responding: {e}"];
+                self.backend_handler.lock().lost();
+                self.schedule_backend_poll();
+                Cm::none()
+            }
+            YtmrsMsg::BackendForm(BackendFormMsg::Reconnect) => {
+                self.inputs.typing = false;
+                match self.backend_form.parse() {
+                    Ok(settings) => {
+                        self.settings.backend = settings;
+                        self.backend_failures = 0;
+                        backend_handler::connect_with(&self.backend_handler, &self.settings.backend)
+                    }
+                    Err(e) => {
+                        self.notify(Notification::error(e));
+                        Cm::none()
+                    }
+                }
+            }
+            YtmrsMsg::BackendForm(msg) => {
+                self.inputs.typing = msg.is_text_edit();
+                self.backend_form.update(msg);
+                Cm::none()
+            }
+            YtmrsMsg::ToggleSettings => {
+                self.settings_panel = match self.settings_panel {
+                    Some(_) => None,
+                    None => Some(SettingsPanel::new(
+                        &self.settings.user,
+                        &self.settings.schedule,
+                        &self.tickers,
+                    )),
+                };
+                Cm::none()
+            }
+            YtmrsMsg::SettingsPanel(SettingsPanelMsg::Close) => {
+                self.settings_panel = None;
+                Cm::none()
+            }
+            YtmrsMsg::SettingsPanel(SettingsPanelMsg::ToggleNormalize) => {
+                self.update(YtmrsMsg::ToggleNormalize)
+            }
+            YtmrsMsg::SettingsPanel(SettingsPanelMsg::Backend(msg)) => {
+                // Caught before the panel's other messages, so the typing is set here too
+                self.inputs.typing = msg.is_text_edit();
+                self.update(YtmrsMsg::BackendForm(msg))
+            }
+            YtmrsMsg::SettingsPanel(SettingsPanelMsg::Schedule(msg)) => {
+                self.inputs.typing = msg.is_text_edit();
+                if let Some(panel) = &mut self.settings_panel {
+                    let volume = self.settings.user.volume;
+                    panel.update_schedule(msg, &mut self.settings.schedule, volume);
+                }
+                Cm::none()
+            }
+            #[cfg(feature = "scrobble")]
+            YtmrsMsg::SettingsPanel(SettingsPanelMsg::SaveToken) => {
+                if let Some(panel) = &mut self.settings_panel {
+                    panel.update(SettingsPanelMsg::SaveToken, &mut self.settings.user);
+                }
+                let token = self.settings.user.scrobble.token.clone();
+                Cm::perform(
+                    scrobbler::save_token(scrobbler::token_path(), token),
+                    YtmrsMsg::TokenSaved,
+                )
+            }
+            YtmrsMsg::SettingsPanel(msg @ SettingsPanelMsg::ConfirmClear(folder)) => {
+                if let Some(panel) = &mut self.settings_panel {
+                    panel.update(msg, &mut self.settings.user);
+                }
+                match folder {
+                    CacheFolder::Sounds => self.clear_sounds(),
+                    CacheFolder::Thumbnails => {
+                        let reader = self.cache.thumbnails.clone();
+                        Cm::perform(
+                            async move {
+                                reader
+                                    .clear::<ThumbnailFiles>()
+                                    .await
+                                    .map_err(|e| e.to_string())
+                            },
+                            |result| YtmrsMsg::FilesRemoved(CacheFolder::Thumbnails, result),
+                        )
+                    }
+                }
+            }
+            YtmrsMsg::SettingsPanel(msg) => {
+                self.inputs.typing = msg.is_text_edit();
+                let panel = match &mut self.settings_panel {
+                    Some(panel) => panel,
+                    None => return Cm::none(),
+                };
+                // The new interval restarts the ticker's subscription once it settles
+                if let Some((ticker, interval)) = panel.update(msg, &mut self.settings.user) {
+                    self.tickers
+                        .set_interval(ticker, interval, time::Instant::now());
+                }
+                self.tickers
+                    .set_autosave(self.settings.user.autosave_secs, time::Instant::now());
+                let trimmed = self.history.set_limit(self.settings.user.history_limit);
+                Cm::perform(trimmed, |()| YtmrsMsg::Null)
             }
             YtmrsMsg::PlayingStatusTick => {
                 self.audio_tracker.update_from_manager(&self.audio_manager);
+                self.scrobble_tick()
+            }
+            #[cfg(feature = "scrobble")]
+            YtmrsMsg::TokenLoaded(result) => {
+                match result {
+                    Ok(token) => self.settings.user.scrobble.token = token,
+                    Err(e) => println!["Failed to read the ListenBrainz token: {e}"],
+                }
                 Cm::none()
             }
-
-            YtmrsMsg::RequestRecieved(response) => match response {
-                Ok(s) => {
-                    let response_type = YTResponseType::new(s);
-                    match response_type {
-                        Ok(response_type) => self.parse_search_request(response_type),
-                        Err(e) => {
-                            println!["Error: {:?}", e];
-                            Cm::none()
+            #[cfg(feature = "scrobble")]
+            YtmrsMsg::TokenSaved(result) => {
+                match result {
+                    Ok(()) => self.notify(Notification::info("Saved the ListenBrainz token")),
+                    Err(e) => self.notify(Notification::error(format!(
+                        "Failed to save the ListenBrainz token: {e}"
+                    ))),
+                }
+                Cm::none()
+            }
+            #[cfg(feature = "scrobble")]
+            YtmrsMsg::ScrobblesSubmitted(count, result) => {
+                if let Err(e) = &result {
+                    println!["Failed to submit {count} listens: {e}"];
+                }
+                let mut scrobbler = self.scrobbler.lock();
+                scrobbler
+                    .outbox
+                    .submitted(count, result.is_ok(), time::Instant::now());
+                scrobbler.outbox.save();
+                Cm::none()
+            }
+            #[cfg(feature = "media-controls")]
+            YtmrsMsg::MediaControl(command) => {
+                if self.now_playing.is_none() {
+                    return Cm::none();
+                }
+                let msgs = command.tracker_msgs(&self.audio_tracker);
+                let commands: Vec<_> = msgs
+                    .into_iter()
+                    .map(|msg| self.update(YtmrsMsg::AudioTrackerMessage(msg)))
+                    .collect();
+                Cm::batch(commands)
+            }
+            YtmrsMsg::SongNoticeDue(id) => match self.song_notifier.take(id) {
+                // The window shows what's playing when it's in front
+                Some(notice) if self.in_background => {
+                    Cm::perform(song_notifications::show(notice), |result| {
+                        if let Err(e) = result {
+                            println!["Failed to show the song's notification: {e}"];
                         }
+                        YtmrsMsg::Null
+                    })
+                }
+                _ => Cm::none(),
+            },
+            YtmrsMsg::ScheduleTick => {
+                match self
+                    .schedule_watch
+                    .check(&self.settings.schedule, &LocalClock)
+                {
+                    Some((entry, due)) => {
+                        println!["Scheduled playback due at {due}"];
+                        let entry = entry.clone();
+                        self.start_scheduled(entry)
+                    }
+                    None => Cm::none(),
+                }
+            }
+            YtmrsMsg::OutputDeviceTick => {
+                Cm::perform(audio::poll_output_device(), YtmrsMsg::OutputDevicePolled)
+            }
+            YtmrsMsg::OutputDevicePolled(device) => {
+                let switched = self.output_device.started();
+                let device = match self.output_device.observe(device) {
+                    Some(device) => device,
+                    None => return Cm::none(),
+                };
+                println!["Output device: {device:?}"];
+                let volume = self.settings.user.volume_for(device.as_deref());
+                self.settings.user.volume = volume;
+                self.audio_tracker.volume = amplitude_to_slider(volume as f64);
+                match switched {
+                    true => self.switch_output(),
+                    false => {
+                        self.audio_manager.fade_volume(
+                            self.settings.user.unless_muted(volume) as f64,
+                            Tween {
+                                duration: time::Duration::from_millis(300),
+                                ..Default::default()
+                            },
+                        );
+                        Cm::none()
                     }
                 }
-                _ => {
-                    println!["{:?}", response];
-                    Cm::none()
+            }
+            YtmrsMsg::VolumeRampTick => {
+                let now = time::Instant::now();
+                let sleeping = self.sleep_timer.as_ref().is_some_and(SleepTimer::fading);
+                if let Some(ramp) = &self.volume_ramp {
+                    let (volume, finished) = (ramp.volume(now), ramp.finished(now));
+                    self.follow_ramp(volume);
+                    // The sleep timer's fade goes on top of the ramp
+                    if !sleeping {
+                        let volume = self.settings.user.unless_muted(volume);
+                        self.audio_manager.set_volume(volume as f64);
+                    }
+                    if finished {
+                        self.volume_ramp = None;
+                        self.tickers.volume_ramp.0 = false;
+                    }
                 }
-            },
+                Cm::none()
+            }
+            YtmrsMsg::SleepTimerTick => {
+                let now = time::Instant::now();
+                let step = match &mut self.sleep_timer {
+                    Some(timer) => timer.tick(now),
+                    None => return Cm::none(),
+                };
+                self.audio_tracker.sleep_left = self
+                    .sleep_timer
+                    .as_ref()
+                    .map(|timer| timer.remaining(now).as_secs());
+                match step {
+                    SleepStep::Wait => {}
+                    SleepStep::Fade(left) => self.fade_for_sleep(left),
+                    SleepStep::Pause => {
+                        println!["The sleep timer is up, pausing"];
+                        self.cancel_sleep_timer();
+                        // A song that's being read doesn't start either
+                        self.fetching.values_mut().for_each(|plays| *plays = false);
+                        self.pause_playback();
+                    }
+                }
+                Cm::none()
+            }
+
+            YtmrsMsg::LocalSearchFinished(request, songs) => {
+                if !self.search.is_current(request) {
+                    return Cm::none();
+                }
+                match songs {
+                    Ok(songs) => {
+                        let keys: Vec<String> = songs.iter().map(|s| s.id.clone()).collect();
+                        // Songs already in memory may have their thumbnails loaded
+                        let map: RwMap<String, Song> = {
+                            let metadata = self.cache.song_metadata.read();
+                            songs
+                                .into_iter()
+                                .filter(|song| !metadata.items().contains_key(&song.id))
+                                .map(|song| (song.id.clone(), song))
+                                .to_rwmap()
+                        };
+                        self.search
+                            .set_results(SearchType::new_tab(keys.clone()), HashMap::new());
+                        self.search.resolve(request, Ok(()));
+                        Cm::batch([
+                            self.update(YtmrsMsg::SongsFetched {
+                                map,
+                                get_existing_thumbnails: false,
+                            }),
+                            self.download_images_for_ids(keys.into_iter().collect()),
+                        ])
+                    }
+                    Err(e) => {
+                        self.search.resolve(request, Err(e));
+                        Cm::none()
+                    }
+                }
+            }
+            YtmrsMsg::RequestRecieved(request, response) => {
+                if !self.search.is_current(request) {
+                    println!["Ignoring stale response to request {request}"];
+                    return Cm::none();
+                }
+                match response {
+                    Ok(s) => {
+                        let response_type = YTResponseType::new(s);
+                        match response_type {
+                            Ok(response_type) => {
+                                self.failures
+                                    .resolve(FailureKind::BackendRequest, &self.search.last_query);
+                                let command = self.parse_search_request(response_type);
+                                self.search.resolve(request, Ok(()));
+                                command
+                            }
+                            Err(e) => {
+                                debug!["{:?}", e];
+                                self.notify(Notification::error(
+                                    "The backend's response couldn't be read",
+                                ));
+                                self.search.resolve(
+                                    request,
+                                    Err("The response wasn't a song, playlist or search".into()),
+                                );
+                                Cm::none()
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        let query = self.search.last_query.clone();
+                        self.notify(
+                            Notification::error(format!("Searching failed: {}", e.describe()))
+                                .with_details(FailureKind::BackendRequest, query.clone()),
+                        );
+                        self.failures.record(
+                            Failure::new(
+                                FailureKind::BackendRequest,
+                                query.clone(),
+                                e.describe(),
+                                Some(Retry::Search(query)),
+                            ),
+                            Local::now(),
+                        );
+                        self.search.resolve(request, Err(e.describe()));
+                        Cm::none()
+                    }
+                }
+            }
 
             // * Searching
-            YtmrsMsg::SearchedKeysReceived { existing, missing } => {
+            YtmrsMsg::SearchedKeysReceived {
+                existing,
+                missing,
+                source,
+            } => {
                 let ids = existing.keys().cloned().collect();
-                let reader = {
+                self.mark_downloaded(&existing);
+                {
                     let mut metadata = self.cache.song_metadata.write();
                     metadata.items_mut().extend(existing);
-                    metadata.reader.clone()
-                };
-                // if missing is not empty, then we need to fetch the missing songs
-                let backend_handler = self.backend_handler.clone();
-
-                let request_command = async move {
-                    let urls = missing.into_iter().map(BackendHandler::request_url_from_id);
-
-                    let requests =
-                        join_all(urls.map(|url| backend_handler.lock().request_info(url).unwrap()))
-                            .await;
-                    let songs: Vec<Song> = requests
-                        .into_iter()
-                        .filter_map(Result::ok)
-                        .filter_map(|s| serde_json::from_str(&s).ok())
-                        .collect();
-
-                    println!["485: {:?}", reader.extend(&songs, true).await];
-
-                    songs
+                }
+                self.search.arrange();
+                self.metadata_queue.request(missing, &source);
+                Cm::batch([self.fetch_metadata(), self.download_images_for_ids(ids)])
+            }
+            YtmrsMsg::SongsReceived(fetched) => {
+                let mut songs = vec![];
+                let mut gone = vec![];
+                for (id, song) in fetched {
+                    let source = match self.metadata_queue.finish(&id, song.as_ref().err()) {
+                        Some(source) => source,
+                        // It's asked for again
+                        None => continue,
+                    };
+                    // Cached songs whose video is gone are kept, marked as such
+                    let cached = match &song {
+                        Err(FetchError::Unavailable) => {
+                            let metadata = self.cache.song_metadata.read();
+                            metadata.items().get(&id).map(|song| song.read().clone())
+                        }
+                        _ => None,
+                    };
+                    match (song, cached) {
+                        (Ok(mut song), _) => {
+                            self.failures.resolve(FailureKind::MetadataFetch, &id);
+                            song.source = source;
+                            songs.push(song);
+                        }
+                        (Err(_), Some(mut song)) => {
+                            self.failures.resolve(FailureKind::MetadataFetch, &id);
+                            song.unavailable = true;
+                            gone.push(song);
+                        }
+                        (Err(error), None) => {
+                            let retry = Retry::FetchMetadata {
+                                id: id.clone(),
+                                source,
+                            };
+                            let error = error.describe().to_string();
+                            self.failures.record(
+                                Failure::new(FailureKind::MetadataFetch, id, error, Some(retry)),
+                                Local::now(),
+                            );
+                        }
+                    }
+                }
+                if let Some(given_up) = self.metadata_queue.take_given_up() {
+                    self.notify(Notification::error(format!(
+                        "Couldn't fetch the info of {given_up} songs, they can be retried \
+                         from the failures"
+                    )));
+                }
+                let ingest = self.ingest_songs(songs).1;
+                let gone = match gone.is_empty() {
+                    true => Cm::none(),
+                    false => self.write_songs(gone),
                 };
-
-                Cm::batch([
-                    Cm::perform(request_command, |songs| {
-                        let map: HashMap<String, _> =
-                            songs.into_iter().map(|s| (s.id().clone(), s)).to_rwmap();
-
-                        YtmrsMsg::SongsFetched {
-                            map,
-                            get_existing_thumbnails: true,
+                Cm::batch([ingest, gone, self.fetch_metadata()])
+            }
+            YtmrsMsg::WriteSongs(songs) => self.write_songs(songs),
+            YtmrsMsg::SongsWritten { map, result } => {
+                for (key, song) in map.iter() {
+                    match &result {
+                        Ok(()) => {
+                            self.failures.resolve(FailureKind::CacheWrite, key);
                         }
-                    }),
-                    self.download_images_for_ids(ids),
-                ])
+                        Err(e) => {
+                            let retry = Retry::WriteSong(Box::new(song.read().clone()));
+                            self.failures.record(
+                                Failure::new(
+                                    FailureKind::CacheWrite,
+                                    key.clone(),
+                                    e.clone(),
+                                    Some(retry),
+                                ),
+                                Local::now(),
+                            );
+                        }
+                    }
+                }
+                self.update(YtmrsMsg::SongsFetched {
+                    map,
+                    get_existing_thumbnails: true,
+                })
             }
 
+            // Timers of a song that was replaced can still fire once
+            YtmrsMsg::ManagerMsg(change) if !self.audio_manager.is_current(&change) => Cm::none(),
+            // A poll only means the song ended if the handle actually stopped
+            YtmrsMsg::ManagerMsg(ChangeSong { poll: true, .. })
+                if self.audio_manager.playback_state() != PlaybackState::Stopped =>
+            {
+                Cm::none()
+            }
             YtmrsMsg::ManagerMsg(_) => {
-                println!["{:?}", self.audio_manager.playback_state()];
+                debug!["{:?}", self.audio_manager.playback_state()];
                 if let PlaybackState::Playing | PlaybackState::Stopped | PlaybackState::Stopping =
                     self.audio_manager.playback_state()
                 {
-                    println!["CHANGE SONG!"];
-                    println!["STATE: {:#?}", self.player_state];
-                    self.play_next_song()
+                    debug![
+                        "Song ended at {:?}",
+                        self.player_state
+                            .as_ref()
+                            .map(|state| state.tracker.get_current().collect::<Vec<_>>())
+                    ];
+                    trace!["STATE: {:#?}", self.player_state];
+                    self.song_ended(true);
+                    // The song ended while the sleep timer faded it out, so nothing follows it. The
+                    // volume is put back for whatever plays next.
+                    if self.sleep_timer.as_ref().is_some_and(SleepTimer::fading) {
+                        println!["The sleep timer is almost up, the next song doesn't start"];
+                        self.cancel_sleep_timer();
+                        self.restore_slept_volume();
+                        self.pause_playback();
+                        return Cm::none();
+                    }
+                    match self.repeats_song() {
+                        true => self.replay(),
+                        false => self.play_next_song(),
+                    }
                 } else {
                     Cm::none()
                 }
             }
+            YtmrsMsg::SearchWindowMessage(SWMessage::Suggest) => {
+                self.search.suggest();
+                Cm::none()
+            }
+            YtmrsMsg::SearchWindowMessage(SWMessage::Scrolled(viewport)) => {
+                self.search.on_screen = OnScreen::of(&viewport);
+                self.download_thumbnails()
+            }
+            YtmrsMsg::PlaylistMsg(PlaylistMessage::Scrolled(viewport)) => {
+                self.settings.playlist.on_screen = OnScreen::of(&viewport);
+                self.download_thumbnails()
+            }
             YtmrsMsg::SearchWindowMessage(msg) => {
+                // Anything else done in the search window moves the focus off the query
+                self.inputs.typing = matches![
+                    msg,
+                    SWMessage::SearchQueryChanged(_) | SWMessage::TabFilterEdited(_)
+                ];
+                self.context_menu = None;
                 match msg {
-                    SWMessage::SearchQuerySubmitted => {
-                        // Check if URL is valid
-                        match Url::parse(&self.search.query) {
-                            Ok(_) => Cm::perform(
-                                self.backend_handler
-                                    .lock()
-                                    .request_info(self.search.query.clone())
-                                    .unwrap(),
-                                YtmrsMsg::RequestRecieved,
-                            ),
-                            // URL failed to parse, try to search Youtube
-                            Err(e) => {
-                                println![
-                                    "Failed to parse: \"{}\". assuming it's a search query",
-                                    e
-                                ];
-                                Cm::perform(
-                                    self.backend_handler
-                                        .lock()
-                                        .request_search(self.search.query.clone())
-                                        .unwrap(),
-                                    YtmrsMsg::RequestRecieved,
-                                )
-                            }
-                        }
+                    SWMessage::OpenMenu(idx) => {
+                        self.context_menu = Some(MenuTarget::Search(idx));
+                        Cm::none()
                     }
+                    SWMessage::Menu(idx, action) => match self.search.search_type.song_key(idx) {
+                        Some(key) => self.song_action(key.clone(), None, action),
+                        None => Cm::none(),
+                    },
+                    SWMessage::Suggestion(idx, action) => match self.search.suggestions.key(idx) {
+                        Some(key) => {
+                            let key = key.clone();
+                            self.search.suggestions.close();
+                            self.song_action(key, None, action)
+                        }
+                        None => Cm::none(),
+                    },
+                    // Enter on a suggestion plays it, or adds it with ctrl held
+                    SWMessage::SearchQuerySubmitted => match self.search.suggestions.focused() {
+                        Some(key) => {
+                            let key = key.clone();
+                            let action = match self.inputs.modifiers.control() {
+                                true => SongAction::AddToPlaylist,
+                                false => SongAction::PlayNow,
+                            };
+                            self.search.suggestions.close();
+                            self.song_action(key, None, action)
+                        }
+                        None => self.submit_search(self.search.query.clone()),
+                    },
+                    SWMessage::Retry => self.submit_search(self.search.last_query.clone()),
+                    SWMessage::OpenTab(idx) => match self.search.search_type.tab_url(idx) {
+                        Some(url) => {
+                            let url = url.clone();
+                            // The results are kept with the query that found them
+                            let command = self.submit_search(url.clone());
+                            self.search.query = url;
+                            command
+                        }
+                        None => Cm::none(),
+                    },
                     _ => self
                         .search
                         .update(msg, &self.inputs.modifiers)
@@ -505,58 +2098,104 @@ impl Ytmrs {
                 }
             }
             YtmrsMsg::PlaylistMsg(msg) => {
+                self.inputs.typing = match &msg {
+                    PlaylistMessage::NameEdited(_)
+                    | PlaylistMessage::ImportPathEdited(_)
+                    | PlaylistMessage::FilterEdited(_)
+                    | PlaylistMessage::NextMatch => true,
+                    PlaylistMessage::ConstructorMessage(msg) => msg.is_text_edit(),
+                    _ => false,
+                };
+                self.context_menu = None;
+                // So the matched row is described, and the arrow keys go on from it
+                if let PlaylistMessage::NextMatch = msg {
+                    self.inputs.focused_list = FocusedList::Constructor;
+                }
                 match msg {
-                    PlaylistMessage::ConstructorMessage(msg) => {
-                        match self.settings.playlist.constructor.update(msg) {
-                            Some(msg) => match msg {
-                                UpdateResult::Cm(cm) => cm.map(|m| {
-                                    YtmrsMsg::PlaylistMsg(PlaylistMessage::ConstructorMessage(m))
-                                }),
-                                UpdateResult::SongClicked(wid) => self.song_clicked(wid),
-                                UpdateResult::Move(from, to) => {
-                                    // Remove item at `from` and place it to `to`
-                                    println!["MOVE FROM {:?} TO {:?}", from, to];
-                                    let from_path =
-                                        self.settings.playlist.constructor.path_to_id(&from);
-
-                                    if from_path.is_none() {
-                                        return Cm::none();
-                                    }
-                                    let from_path = from_path.unwrap();
-                                    println!["FROM:{:?}", from_path];
-
-                                    let item = self
-                                        .settings
-                                        .playlist
-                                        .constructor
-                                        .pop_path(from_path.into());
-
-                                    if item.is_none() {
-                                        return Cm::none();
-                                    }
-                                    let item = item.unwrap();
-
-                                    let to_path =
-                                        self.settings.playlist.constructor.path_to_id(&to);
-
-                                    if to_path.is_none() {
-                                        return Cm::none();
-                                    }
-
-                                    let to_path = to_path.unwrap();
-                                    println!["TO:{:?}", to_path];
-
-                                    self.settings
-                                        .playlist
-                                        .constructor
-                                        .push_to_path(to_path.into(), item);
-
-                                    Cm::none()
-                                }
-                            },
-                            None => Cm::none(),
+                    // Only edits of the tree are kept, folding and clicking rows aren't undone
+                    PlaylistMessage::ConstructorMessage(msg) => match msg.edits_tree() {
+                        true => self.edit_playlist(|ytmrs| ytmrs.constructor_message(msg)),
+                        false => self.constructor_message(msg),
+                    },
+                    PlaylistMessage::Save => Cm::perform(
+                        self.settings.playlist.clone().save(),
+                        YtmrsMsg::PlaylistSaved,
+                    ),
+                    PlaylistMessage::Export(format) => self.export_playlist(format),
+                    PlaylistMessage::RefreshInfo => {
+                        let keys: Vec<SongKey> = self
+                            .settings
+                            .playlist
+                            .constructor
+                            .all_song_keys_rec()
+                            .cloned()
+                            .collect();
+                        self.notify(Notification::info(format!(
+                            "Refreshing the info of {} songs",
+                            keys.len()
+                        )));
+                        self.refresh_songs(keys)
+                    }
+                    PlaylistMessage::DownloadAll => {
+                        if self.downloads.batch.is_some() {
+                            self.notify(Notification::info("The playlist is already downloading"));
+                            return Cm::none();
                         }
+                        let mut seen = HashSet::new();
+                        let keys: Vec<SongKey> = self
+                            .settings
+                            .playlist
+                            .constructor
+                            .all_song_keys_rec()
+                            .filter(|key| seen.insert(*key))
+                            .cloned()
+                            .collect();
+                        let reader = self.cache.sounds.reader.clone();
+                        Cm::perform(
+                            async move {
+                                let cached =
+                                    reader.locate_all().await.map_err(|e| e.to_string())?;
+                                Ok(keys
+                                    .into_iter()
+                                    .filter(|key| !cached.contains_key(key))
+                                    .collect())
+                            },
+                            YtmrsMsg::BatchChecked,
+                        )
+                    }
+                    PlaylistMessage::Switch(id) => match id == self.settings.playlist.id {
+                        true => Cm::none(),
+                        false => self.replace_playlist(
+                            move |dir| async move { Playlist::load_from(&dir, id).await },
+                            YtmrsMsg::PlaylistLoaded,
+                        ),
+                    },
+                    PlaylistMessage::New => self.replace_playlist(
+                        |_| async {
+                            Ok(Playlist {
+                                name: "New playlist".to_string(),
+                                ..Default::default()
+                            })
+                        },
+                        YtmrsMsg::PlaylistLoaded,
+                    ),
+                    PlaylistMessage::ImportPathEdited(path) => {
+                        self.library.import_path = path;
+                        Cm::none()
+                    }
+                    PlaylistMessage::Import => {
+                        let path = self.library.import_path.clone();
+                        self.replace_playlist(|_| import::import(path), YtmrsMsg::PlaylistImported)
+                    }
+                    PlaylistMessage::AskDelete => {
+                        self.library.set_confirming_delete(true);
+                        Cm::none()
+                    }
+                    PlaylistMessage::CancelDelete => {
+                        self.library.set_confirming_delete(false);
+                        Cm::none()
                     }
+                    PlaylistMessage::ConfirmDelete => self.delete_playlist(),
                     _ => self
                         .settings
                         .playlist
@@ -564,229 +2203,1681 @@ impl Ytmrs {
                         .map(YtmrsMsg::PlaylistMsg),
                 }
             }
-            YtmrsMsg::AudioTrackerMessage(msg) => match &msg {
-                TrackerMsg::Pause => {
-                    self.audio_manager.pause();
-                    self.audio_tracker.paused = true;
-                    self.tickers.playing_status.0 = false;
+            YtmrsMsg::Simple(msg) => {
+                let playing = self
+                    .player_state
+                    .as_ref()
+                    .map(|state| state.tracker.get_current().collect());
+                let result = self.edit_playlist(|ytmrs| {
+                    ytmrs.simple.update(
+                        &mut ytmrs.settings.playlist.constructor,
+                        &mut ytmrs.settings.user.simple,
+                        playing,
+                        msg,
+                    )
+                });
+                match result {
+                    Some(SimpleResult::Play(wid)) => self.song_clicked(wid),
+                    Some(SimpleResult::Requeue(path)) => {
+                        self.remap_playing_path(path);
+                        Cm::none()
+                    }
+                    None => Cm::none(),
+                }
+            }
+            YtmrsMsg::Queue(QueueMsg::Jump(idx)) => self.jump_in_queue(idx),
+            YtmrsMsg::NotesEdited(action) => {
+                self.inputs.typing = true;
+                self.notes.perform(action);
+                Cm::none()
+            }
+            YtmrsMsg::ReopenSource(query) => {
+                self.search.query = query.clone();
+                self.submit_search(query)
+            }
+            YtmrsMsg::ShowFromSource(source) => {
+                let request = self.search.begin_request(format!("songs from {source}"));
+                self.search_local(request, move |song| song.came_from(&source))
+            }
+            YtmrsMsg::SetPlaybackMode(mode) => match self.now_playing.clone() {
+                Some(key) => self.update_song(key, |song| song.playback_mode = mode),
+                None => Cm::none(),
+            },
+            YtmrsMsg::ToggleNormalize => {
+                let user = &mut self.settings.user;
+                user.normalize = !user.normalize;
+                if let Some(key) = &self.now_playing {
+                    let gain = self.gain_for(key);
+                    let volume = self.settings.user.unless_muted(self.settings.user.volume);
+                    self.audio_manager.set_gain(volume as f64, gain);
+                }
+                Cm::none()
+            }
+            YtmrsMsg::SaveNotes => {
+                self.inputs.typing = false;
+                match self.now_playing.clone() {
+                    Some(key) => {
+                        let notes = self.notes.text();
+                        let notes = notes.trim();
+                        let notes = (!notes.is_empty()).then(|| notes.to_string());
+                        self.update_song(key, |song| song.notes = notes)
+                    }
+                    None => Cm::none(),
+                }
+            }
+            YtmrsMsg::ExternalSettingsLoaded(result) => {
+                match result {
+                    // Another playlist was opened there, which leaves this one alone
+                    Ok(settings) if settings.playlist.id != self.settings.playlist.id => {}
+                    Ok(settings) => self.apply_external_playlist(settings.playlist.constructor),
+                    Err(e) => println!["Failed to reload the changed settings: {e:?}"],
+                }
+                Cm::none()
+            }
+            YtmrsMsg::OpenBulkEdit => match self.search.selected_keys() {
+                Some(keys) => {
+                    let (editor, command) = BulkEditor::open(keys.into_iter().cloned().collect());
+                    self.bulk_edit = Some(editor);
+                    command
+                }
+                None => Cm::none(),
+            },
+            YtmrsMsg::AddSelected => match self.search.selected_keys() {
+                Some(keys) => {
+                    let keys = keys.into_iter().cloned().collect();
+                    self.add_checked(keys, AddTarget::FocusedGroup)
+                }
+                None => Cm::none(),
+            },
+            YtmrsMsg::BulkEdit(msg) => match msg {
+                BulkEditMsg::Cancel => {
+                    self.bulk_edit = None;
                     Cm::none()
                 }
-                TrackerMsg::Play => {
-                    self.audio_manager.play();
-                    self.audio_tracker.paused = false;
-                    self.tickers.playing_status.0 = true;
+                BulkEditMsg::Apply => self.apply_bulk_edit(),
+                msg => {
+                    if let Some(editor) = &mut self.bulk_edit {
+                        editor.update(&msg);
+                    }
                     Cm::none()
                 }
-                TrackerMsg::Next => self.play_next_song(),
-                TrackerMsg::Previous => self.rewind(),
-                TrackerMsg::UpdateVolume(v) => {
-                    println!["{:?}", v];
-                    let float_vol = v / 1000_f64;
-                    self.audio_manager.set_volume(float_vol);
-                    self.audio_tracker.volume = *v;
-                    self.settings.user.volume = float_vol as f32;
+            },
+            YtmrsMsg::OpenSongEditor(key) => self.open_song_editor(key),
+            YtmrsMsg::SongEditor(msg) => match msg {
+                SongEditMsg::Cancel => {
+                    // Nothing was written, the cached song still has its old values
+                    self.song_editor = None;
                     Cm::none()
                 }
-                TrackerMsg::ProgressSliderChanged(_) => self
-                    .audio_tracker
-                    .update(msg)
-                    .map(YtmrsMsg::AudioTrackerMessage),
-                TrackerMsg::ProgressSliderReleased(v) => {
-                    self.audio_manager.seek(*v);
-                    self.audio_tracker
-                        .update(msg)
-                        .map(YtmrsMsg::AudioTrackerMessage)
+                SongEditMsg::Save => match self.song_editor.take() {
+                    // Only the metadata changes, so a playing song keeps playing
+                    Some(editor) => self.update_song(editor.key, |song| {
+                        editor.edit.apply(song);
+                    }),
+                    None => Cm::none(),
+                },
+                SongEditMsg::Reopen(query) => self.update(YtmrsMsg::ReopenSource(query)),
+                SongEditMsg::MoreFromSource(source) => {
+                    self.update(YtmrsMsg::ShowFromSource(source))
+                }
+                msg => {
+                    if let Some(editor) = &mut self.song_editor {
+                        editor.update(msg);
+                    }
+                    Cm::none()
                 }
             },
-
-            YtmrsMsg::ImagesFetched { map, missing } => {
-                self.push_image_handles(map);
-
-                match missing {
-                    None => Cm::none(),
-                    Some(missing) => {
-                        let thumb_reader = self.cache.thumbnails.clone();
-                        let thumb_urls: Vec<(String, Url)> = self
-                            .cache
-                            .song_metadata
-                            .read()
-                            .fetch_existing(&missing)
-                            .into_iter()
-                            .map(|(id, s)| (id, Url::parse(&s.read().thumbnail).unwrap()))
-                            .collect();
-
-                        Cm::perform(
-                            async move {
-                                let thumbnails = get_images(thumb_reader, thumb_urls).await;
-
-                                thumbnails.into_iter().collect()
-                            },
-                            |map| YtmrsMsg::ImagesFetched { map, missing: None },
-                        )
+            YtmrsMsg::ExternalChange(msg) => match msg {
+                ExternalChangeMsg::ToggleDetails => {
+                    if let Some(change) = &mut self.external_change {
+                        change.toggle_details();
                     }
+                    Cm::none()
                 }
-            }
-            YtmrsMsg::SongsFetched {
-                map,
-                get_existing_thumbnails,
-            } => {
+                ExternalChangeMsg::Dismiss => {
+                    self.external_change = None;
+                    Cm::none()
+                }
+                ExternalChangeMsg::Revert => match self.external_change.take() {
+                    Some(change) => {
+                        self.edit_playlist(|ytmrs| {
+                            ytmrs.settings.playlist.constructor = change.previous;
+                        });
+                        self.player_state = None;
+                        Cm::perform(self.settings.clone().save(), YtmrsMsg::SettingsSaved)
+                    }
+                    None => Cm::none(),
+                },
+                ExternalChangeMsg::Jump(row) => {
+                    match self.settings.playlist.focus_song(&row) {
+                        true => self.inputs.focused_list = FocusedList::Constructor,
+                        false => println!["That song is in a collapsed group"],
+                    }
+                    Cm::none()
+                }
+            },
+            YtmrsMsg::DuplicatePrompt(msg) => match self.duplicate_prompt.take() {
+                Some(prompt) => {
+                    let keys = match msg {
+                        DuplicatePromptMsg::AddAnyway => prompt.keys,
+                        DuplicatePromptMsg::Skip => prompt.new_keys(),
+                    };
+                    match keys.is_empty() {
+                        true => Cm::none(),
+                        false => self.add_to(keys, prompt.target),
+                    }
+                }
+                None => Cm::none(),
+            },
+            YtmrsMsg::PlaylistSaved(result) => {
+                match result {
+                    Ok(path) => {
+                        println!["Saved the playlist to {path:?}"];
+                        self.library.record(&self.settings.playlist);
+                        self.notify(Notification::info("Saved the playlist"));
+                    }
+                    Err(e) => self.notify(Notification::error(format!(
+                        "Failed to save the playlist: {e:?}"
+                    ))),
+                }
+                Cm::none()
+            }
+            YtmrsMsg::PlaylistExported(result) => {
+                match result {
+                    Ok(path) => {
+                        println!["Exported the playlist to {path:?}"];
+                        let exported = format!("Exported to {}", path.display());
+                        self.notify(Notification::info(exported));
+                    }
+                    Err(e) => self.notify(Notification::error(format!(
+                        "Failed to export the playlist: {e:?}"
+                    ))),
+                }
+                Cm::none()
+            }
+            YtmrsMsg::PlaylistImported(result) => match result {
+                Ok(Imported {
+                    playlist,
+                    skipped,
+                    path,
+                }) => {
+                    let keys: HashSet<String> =
+                        playlist.constructor.all_song_keys_rec().cloned().collect();
+                    let found = playlist.constructor.all_song_keys_rec().count();
+                    self.notify(Notification::info(format!(
+                        "Imported {found}, skipped {skipped}"
+                    )));
+                    self.library.import_path.clear();
+                    let open = self.open_playlist(playlist);
+                    let source = SongSource::LocalImport { path };
+                    Cm::batch([open, self.request_metadata(keys, source)])
+                }
+                Err(e) => {
+                    self.notify(Notification::error(format!("Failed to import: {e:?}")));
+                    Cm::none()
+                }
+            },
+            YtmrsMsg::HistoryLoaded(result) => match result {
+                Ok((records, more)) => {
+                    Cm::perform(self.history.loaded(records, more), |()| YtmrsMsg::Null)
+                }
+                Err(e) => {
+                    println!["Failed to read the history: {e}"];
+                    Cm::none()
+                }
+            },
+            YtmrsMsg::History(HistoryMsg::Replay(key)) => {
+                let row = self
+                    .settings
+                    .playlist
+                    .constructor
+                    .songs_with_ids()
+                    .into_iter()
+                    .find(|(k, _)| **k == key)
+                    .map(|(_, id)| id);
+                self.play_trigger = Some(PlayTrigger::History);
+                self.song_action(key, row, SongAction::PlayNow)
+            }
+            YtmrsMsg::ToggleHistory => {
+                self.history_open = !self.history_open;
+                Cm::none()
+            }
+            YtmrsMsg::Panes(msg) => {
+                // Saved with the rest of the settings
+                if let Some(split) = self.panes.update(msg) {
+                    self.settings.user.pane_split = split;
+                }
+                Cm::none()
+            }
+            YtmrsMsg::NowPlaying(NowPlayingMsg::OpenGroup(id)) => {
+                self.inputs.focused_list = FocusedList::Constructor;
+                self.settings
+                    .playlist
+                    .reveal_group(&id)
+                    .map(YtmrsMsg::PlaylistMsg)
+            }
+            YtmrsMsg::PlaylistDeleted(id, result) => match result {
+                Ok(()) => {
+                    // Only forgotten now, so a failed delete leaves it in the library
+                    self.library.forget(id);
+                    match self.library.other_than(id) {
+                        Some(next) => Cm::perform(
+                            async move { Playlist::load_from(&playlists_directory(), next).await },
+                            YtmrsMsg::PlaylistLoaded,
+                        ),
+                        None => self.update(YtmrsMsg::PlaylistLoaded(Ok(Playlist::default()))),
+                    }
+                }
+                Err(e) => {
+                    self.notify(Notification::error(format!(
+                        "Failed to delete the playlist: {e:?}"
+                    )));
+                    Cm::none()
+                }
+            },
+            YtmrsMsg::PlaylistLoaded(result) => {
+                let play = std::mem::take(&mut self.play_when_opened);
+                let scheduled = self.scheduled.take();
+                match result {
+                    Ok(playlist) => {
+                        let scheduled = scheduled.filter(|entry| entry.playlist == playlist.id);
+                        let opened = self.open_playlist(playlist);
+                        match (scheduled, play) {
+                            (Some(entry), _) => Cm::batch([opened, self.start_scheduled(entry)]),
+                            (None, true) => Cm::batch([opened, self.start_playing()]),
+                            (None, false) => opened,
+                        }
+                    }
+                    Err(e) => {
+                        println!["Failed to open the playlist: {e:?}"];
+                        if scheduled.is_some() {
+                            self.notify(Notification::error(
+                                "The scheduled playlist couldn't be opened",
+                            ));
+                        }
+                        Cm::none()
+                    }
+                }
+            }
+            YtmrsMsg::LibraryLoaded(headers) => {
+                self.library.set_headers(headers);
+                // The open playlist may never have been saved to its own file
+                self.library.record(&self.settings.playlist);
+                Cm::none()
+            }
+            YtmrsMsg::SettingsSaved(result) => {
+                match result {
+                    Ok(path) => {
+                        println!["Saved to {path:?}"];
+                        self.saved();
+                    }
+                    Err(e) => self.notify(Notification::error(format!("Saving failed: {e:?}"))),
+                }
+                Cm::none()
+            }
+            YtmrsMsg::Failed(failure) => {
+                let batch = match failure.kind == FailureKind::Download {
+                    true => {
+                        // The download's reading of the file failed after the download ended
+                        self.show_song_state(&failure.subject, SongState::None);
+                        self.batch_finished(&failure.subject, Some(failure.error.clone()))
+                    }
+                    false => Cm::none(),
+                };
+                // A batch's failures are told of once it's over
+                let in_batch = self.downloads.batch.as_ref().is_some_and(|batch| {
+                    batch.failed.iter().any(|(key, _)| *key == failure.subject)
+                });
+                if !in_batch {
+                    self.notify(
+                        Notification::error(format!(
+                            "{}: {}",
+                            failure.kind.describe(),
+                            failure.subject
+                        ))
+                        .with_details(failure.kind, failure.subject.clone()),
+                    );
+                }
+                self.failures.record(failure, Local::now());
+                batch
+            }
+            YtmrsMsg::Notify(notification) => {
+                self.notify(notification);
+                Cm::none()
+            }
+            YtmrsMsg::CloseContextMenu => {
+                self.context_menu = None;
+                Cm::none()
+            }
+            YtmrsMsg::Notifications(msg) => {
+                let reveal = match &msg {
+                    NotificationsMsg::Details { kind, subject, .. } => {
+                        self.failures.reveal(*kind, subject.clone())
+                    }
+                    _ => Cm::none(),
+                };
+                self.notifications.update(msg);
+                reveal
+            }
+            YtmrsMsg::Failures(msg) => match msg {
+                FailuresMsg::Toggle => {
+                    self.failures.toggle();
+                    Cm::none()
+                }
+                FailuresMsg::Retry(kind, subject) => match self.failures.retry(kind, &subject) {
+                    Some(retry) => self.update(retry.message()),
+                    None => Cm::none(),
+                },
+                FailuresMsg::ClearAll => {
+                    self.failures.clear();
+                    Cm::none()
+                }
+            },
+            YtmrsMsg::WhatsNew(msg) => {
+                match msg {
+                    WhatsNewMsg::Open => {
+                        self.whats_new.open();
+                        return Cm::none();
+                    }
+                    WhatsNewMsg::Dismiss => {}
+                    WhatsNewMsg::Jump(target) => {
+                        if !whats_new::navigate(target, &mut self.inputs) {
+                            println!["No navigation registered for {target:?}"];
+                        }
+                    }
+                }
+                self.whats_new.close();
+                self.settings.user.last_seen_version = Some(whats_new::CURRENT_VERSION.into());
+                Cm::none()
+            }
+            YtmrsMsg::AudioTrackerMessage(msg) => match &msg {
+                TrackerMsg::Pause => {
+                    self.cancel_sleep_timer();
+                    self.pause_playback();
+                    Cm::none()
+                }
+                TrackerMsg::Play => {
+                    self.restore_slept_volume();
+                    self.audio_manager.play();
+                    self.audio_tracker.paused = false;
+                    self.events.emit(AppEvent::PlaybackResumed);
+                    Cm::none()
+                }
+                TrackerMsg::Next if self.inputs.modifiers.control() => self.skip_group(true),
+                TrackerMsg::Previous if self.inputs.modifiers.control() => self.skip_group(false),
+                TrackerMsg::Next => {
+                    self.song_ended(false);
+                    self.play_trigger = Some(PlayTrigger::Skipped);
+                    self.play_next_song()
+                }
+                TrackerMsg::Previous => self.rewind(),
+                TrackerMsg::ToggleShuffle => {
+                    let user = &mut self.settings.user;
+                    user.shuffle = !user.shuffle;
+                    self.audio_tracker.shuffle = user.shuffle;
+                    if let Some(state) = &mut self.player_state {
+                        state.set_shuffle(user.shuffle);
+                    }
+                    Cm::none()
+                }
+                TrackerMsg::CycleRepeat => {
+                    let user = &mut self.settings.user;
+                    user.repeat = user.repeat.next();
+                    self.audio_tracker.repeat = user.repeat;
+                    if let Some(state) = &mut self.player_state {
+                        state.set_repeat(user.repeat);
+                    }
+                    Cm::none()
+                }
+                TrackerMsg::NextGroup => self.skip_group(true),
+                TrackerMsg::PreviousGroup => self.skip_group(false),
+                TrackerMsg::RestartGroup => self.restart_group(),
+                TrackerMsg::SkipPressed(_) => self
+                    .audio_tracker
+                    .update(msg)
+                    .map(YtmrsMsg::AudioTrackerMessage),
+                TrackerMsg::SkipReleased(skip) => {
+                    let msg = self.audio_tracker.release(*skip, time::Instant::now());
+                    self.update(YtmrsMsg::AudioTrackerMessage(msg))
+                }
+                TrackerMsg::UpdateVolume(position) => {
+                    let volume = slider_to_amplitude(*position);
+                    // The user's volume wins over a scheduled fade
+                    self.volume_ramp = None;
+                    self.tickers.volume_ramp.0 = false;
+                    self.audio_manager.set_volume(volume);
+                    self.audio_tracker.volume = *position;
+                    self.audio_tracker.muted = false;
+                    let user = &mut self.settings.user;
+                    user.muted = false;
+                    user.set_volume(self.output_device.active(), volume as f32);
+                    Cm::none()
+                }
+                TrackerMsg::ScrollVolume(delta) => {
+                    let position = self.audio_tracker.scrolled_volume(*delta);
+                    self.update(YtmrsMsg::AudioTrackerMessage(TrackerMsg::UpdateVolume(
+                        position,
+                    )))
+                }
+                TrackerMsg::ToggleMute => {
+                    let user = &mut self.settings.user;
+                    user.muted = !user.muted;
+                    self.audio_tracker.muted = user.muted;
+                    self.audio_manager.fade_volume(
+                        self.playing_volume() as f64,
+                        Tween {
+                            duration: time::Duration::from_millis(100),
+                            ..Default::default()
+                        },
+                    );
+                    Cm::none()
+                }
+                TrackerMsg::ProgressSliderChanged(_) => self
+                    .audio_tracker
+                    .update(msg)
+                    .map(YtmrsMsg::AudioTrackerMessage),
+                TrackerMsg::ProgressSliderReleased => {
+                    if let Some(target) = self.audio_tracker.finish_seek() {
+                        self.audio_manager.seek(target);
+                        self.events.emit(AppEvent::Seeked { secs: target });
+                    }
+                    Cm::none()
+                }
+                TrackerMsg::ToggleEqualizer
+                | TrackerMsg::ToggleSleepMenu
+                | TrackerMsg::SleepTextEdited(_) => self
+                    .audio_tracker
+                    .update(msg)
+                    .map(YtmrsMsg::AudioTrackerMessage),
+                TrackerMsg::SetSleepTimer(time) => {
+                    let time = *time;
+                    let _ = self.audio_tracker.update(msg);
+                    self.cancel_sleep_timer();
+                    if !self.audio_tracker.paused {
+                        self.restore_slept_volume();
+                    }
+                    if let Some(time) = time {
+                        let now = time::Instant::now();
+                        self.sleep_timer = Some(SleepTimer::new(time, now));
+                        self.tickers.sleep_timer.0 = true;
+                        self.audio_tracker.sleep_left = Some(time.as_secs());
+                    }
+                    Cm::none()
+                }
+                TrackerMsg::EqualizerChanged(band, gain) => {
+                    let eq = &mut self.settings.user.eq;
+                    let gain = eq.set_gain(*band, *gain);
+                    self.audio_tracker.eq = *eq;
+                    self.audio_manager.set_eq(*band, gain);
+                    Cm::none()
+                }
+                TrackerMsg::ToggleLimiter => {
+                    let eq = &mut self.settings.user.eq;
+                    eq.limiter = !eq.limiter;
+                    self.audio_tracker.eq = *eq;
+                    self.audio_manager.set_limiter(eq.limiter);
+                    Cm::none()
+                }
+            },
+
+            YtmrsMsg::ImagesFetched { map, missing } => {
+                self.push_image_handles(map);
+
+                match missing {
+                    None => Cm::none(),
+                    Some(missing) => {
+                        self.thumbnail_queue.request(missing, time::Instant::now());
+                        self.download_thumbnails()
+                    }
+                }
+            }
+            YtmrsMsg::ThumbnailsDownloaded { batch, map } => {
+                let downloaded: HashSet<String> = map.keys().cloned().collect();
+                self.thumbnail_queue
+                    .finish(&batch, &downloaded, time::Instant::now());
+                self.push_image_handles(map);
+                self.download_thumbnails()
+            }
+            YtmrsMsg::SongsFetched {
+                map,
+                get_existing_thumbnails,
+            } => {
                 let keys: HashSet<String> = map.keys().cloned().collect();
+                self.mark_downloaded(&map);
                 {
                     let mut lock = self.cache.song_metadata.write();
                     lock.items_mut().extend(map);
                 }
-                match get_existing_thumbnails {
+                self.remeasure_playing();
+                self.search.arrange();
+                // The resumed song can only be downloaded once its metadata is known
+                let resume = match &self.resuming {
+                    Some(session) if self.now_playing.is_none() && keys.contains(&session.key) => {
+                        Some(session.tracker.get_current().collect())
+                    }
+                    _ => None,
+                };
+                let resume = match resume {
+                    Some(path) => self.play_at_path(path),
+                    None => Cm::none(),
+                };
+                let thumbnails = match get_existing_thumbnails {
                     false => Cm::none(),
                     true => self.download_images_for_ids(keys),
+                };
+                Cm::batch([resume, thumbnails])
+            }
+            YtmrsMsg::SoundFetched(id, sound) => {
+                // The song plays if it was asked to at any point while it was being read
+                let play = self.fetching.remove(&id).unwrap_or_default();
+                // Playing it marks it as playing instead
+                if self.now_playing.as_ref() != Some(&id) {
+                    let state = match sound {
+                        CachedSound::Located(_) | CachedSound::Read(_) => SongState::Cached,
+                        CachedSound::Undecodable(_) => SongState::None,
+                        CachedSound::Missing => {
+                            self.downloaded.remove(&id);
+                            SongState::None
+                        }
+                    };
+                    self.show_song_state(&id, state);
                 }
+                match sound {
+                    CachedSound::Located(path) => match play {
+                        true => self.update(YtmrsMsg::SoundLocated { id, path }),
+                        false => Cm::none(),
+                    },
+                    CachedSound::Read(sound) => {
+                        println!["Sound fetched."];
+
+                        let gain = self.record_gain(&sound);
+                        let map: HashMap<_, _> = HashMap::from([(id.clone(), *sound)]).to_rwmap();
+
+                        self.cache.sounds.insert_items(map.clone());
+
+                        match play {
+                            true => {
+                                let sound = map[&id].read();
+                                let notice = self.play(SoundData::from(sound.clone()));
+                                Cm::batch([gain, self.set_background(id), notice])
+                            }
+                            false => gain,
+                        }
+                    }
+                    CachedSound::Undecodable(e) => {
+                        self.notify(Notification::error(e));
+                        Cm::none()
+                    }
+                    CachedSound::Missing => self.download_song(id, play),
+                }
+            }
+            YtmrsMsg::DownloadSong(s, play) => self.download_song(s, play),
+            YtmrsMsg::DownloadProgress {
+                id,
+                downloaded_bytes,
+                total_bytes,
+                eta,
+            } => {
+                let progress = DownloadProgress {
+                    downloaded_bytes,
+                    total_bytes,
+                    eta,
+                };
+                if let Some(download) = self.downloads.progress(&id, progress) {
+                    let state = SongState::Downloading(download.progress.fraction());
+                    self.show_song_state(&id, state);
+                }
+                Cm::none()
+            }
+            YtmrsMsg::DownloadEnded { id, result } => {
+                let play = match self.downloads.remove(&id) {
+                    Some(download) => download.play,
+                    // Cancelled while its last message was on the way
+                    None => return Cm::none(),
+                };
+                self.show_song_state(&id, SongState::None);
+                let song = result.and_then(|s| {
+                    trace!["{:?}", s];
+                    serde_json::from_str::<Song>(&s).map_err(|_| BackendReqErr::Decode)
+                });
+                match song {
+                    Ok(song) => self.update(YtmrsMsg::SongDownloaded {
+                        key: id,
+                        song,
+                        play,
+                    }),
+                    Err(e) => self.update(YtmrsMsg::Failed(Failure::new(
+                        FailureKind::Download,
+                        id.clone(),
+                        e.describe(),
+                        Some(Retry::Download { key: id, play }),
+                    ))),
+                }
+            }
+            YtmrsMsg::Downloads(DownloadsMsg::Cancel(id)) => {
+                // Dropping it drops its subscription, and the request with it
+                if self.downloads.remove(&id).is_some() {
+                    println!["Cancelled the download of {id}"];
+                    self.show_song_state(&id, SongState::None);
+                }
+                if self.prefetching.as_ref() == Some(&id) {
+                    self.prefetching = None;
+                }
+                self.batch_finished(&id, Some("cancelled".to_string()))
+            }
+            YtmrsMsg::Downloads(DownloadsMsg::CancelBatch) => {
+                for key in self.downloads.cancel_batch() {
+                    self.show_song_state(&key, SongState::None);
+                }
+                self.notify(Notification::info("Cancelled the playlist's download"));
+                Cm::none()
+            }
+            YtmrsMsg::Downloads(DownloadsMsg::ResumeBatch) => {
+                if let Some(batch) = &mut self.downloads.batch {
+                    batch.paused = false;
+                }
+                self.check_batch_usage()
+            }
+            YtmrsMsg::BatchChecked(result) => match result {
+                Ok(keys) if keys.is_empty() => {
+                    self.notify(Notification::info(
+                        "Every song in the playlist is downloaded",
+                    ));
+                    Cm::none()
+                }
+                Ok(keys) => {
+                    self.notify(Notification::info(format!(
+                        "Downloading {} songs",
+                        keys.len()
+                    )));
+                    self.downloads.batch = Some(Batch::new(keys));
+                    self.advance_batch()
+                }
+                Err(e) => {
+                    self.notify(Notification::error(format!(
+                        "Couldn't read the sounds cache: {e}"
+                    )));
+                    Cm::none()
+                }
+            },
+            YtmrsMsg::BatchUsageChecked(result) => {
+                let budget = self.settings.user.audio_cache_budget();
+                match result {
+                    Ok(bytes) if bytes > budget => {
+                        if let Some(batch) = &mut self.downloads.batch {
+                            batch.paused = true;
+                        }
+                        self.notify(Notification::error(format!(
+                            "The sounds cache is over its budget of {}MB, the playlist's download \
+                            is paused",
+                            self.settings.user.max_audio_cache_mb
+                        )));
+                        Cm::none()
+                    }
+                    Ok(_) => self.advance_batch(),
+                    Err(e) => {
+                        println!["Failed to measure the sounds cache: {e}"];
+                        self.advance_batch()
+                    }
+                }
+            }
+            YtmrsMsg::SongDownloaded {
+                key,
+                mut song,
+                play,
+            } => {
+                self.failures.resolve(FailureKind::Download, &key);
+                // The backend only knows the raw id, so keep the key the download was requested for
+                if song.id != key {
+                    song.raw_id = Some(std::mem::replace(&mut song.id, key));
+                }
+
+                if let Some(recdown) = &song.requested_downloads {
+                    let recdown = recdown[0].clone();
+                    let filepath = PathBuf::from(recdown.filepath);
+                    let id = song.id.clone();
+                    let id2 = id.clone();
+                    let reader = self.cache.sounds.reader.clone();
+                    let loading = audio::resolve(
+                        self.playback_mode(&id),
+                        &song.load_facts(available_memory()),
+                        self.settings.user.stream_over(),
+                    );
+                    Cm::perform(
+                        async move {
+                            let data = read_file(&filepath).await;
+                            // Named after the downloaded file's format
+                            let extension = filepath
+                                .extension()
+                                .and_then(|ext| ext.to_str())
+                                .unwrap_or_default();
+
+                            match data {
+                                Ok(data) => {
+                                    let file_data = FileData::new(id2.clone(), data);
+                                    {
+                                        println![
+                                            "{:?}",
+                                            reader
+                                                .extend_as(vec![&file_data], extension, true)
+                                                .await
+                                        ];
+                                        println!["531 Extended."];
+                                    }
+                                    if play {
+                                        let sound = match loading {
+                                            // It plays from the cached copy, never decoded whole
+                                            Loading::Stream => match reader.locate(&id2).await {
+                                                Ok(Some(path)) => Downloaded::Stream(path),
+                                                Ok(None) => {
+                                                    return Err("not in the cache".to_string())
+                                                }
+                                                Err(e) => return Err(e.to_string()),
+                                            },
+                                            Loading::Static => {
+                                                println!["Creating sound from bytes..."];
+                                                let bsd = BasicSoundData::try_from((
+                                                    id2,
+                                                    file_data.into_data(),
+                                                ))?;
+                                                println!["Created sound from bytes."];
+                                                Downloaded::Decoded(Box::new(bsd))
+                                            }
+                                        };
+
+                                        // Delete the original file
+                                        println!["Deleting {:?}...", filepath];
+                                        match async_std::fs::remove_file(&filepath).await {
+                                            Ok(_) => {
+                                                println!["Deleted {:?}.", filepath];
+                                            }
+                                            Err(e) => {
+                                                println!["Error deleting {:?}: {:?}", filepath, e];
+                                            }
+                                        }
+
+                                        Ok(Some(sound))
+                                    } else {
+                                        Ok(None)
+                                    }
+                                }
+                                Err(e) => {
+                                    println!["{:?}", e];
+                                    Err(e.to_string())
+                                }
+                            }
+                        },
+                        move |data| match data {
+                            Ok(Some(Downloaded::Decoded(data))) => {
+                                YtmrsMsg::SongDownloadFinished { id, data }
+                            }
+                            Ok(Some(Downloaded::Stream(path))) => {
+                                YtmrsMsg::SoundLocated { id, path }
+                            }
+                            Ok(None) => YtmrsMsg::SongCached(id),
+                            Err(e) => YtmrsMsg::Failed(Failure::new(
+                                FailureKind::Download,
+                                id.clone(),
+                                e,
+                                Some(Retry::Download { key: id, play }),
+                            )),
+                        },
+                    )
+                } else {
+                    let batch = self.batch_finished(&song.id, Some("no file was sent".to_string()));
+                    // Add the song to the filecache
+                    let mut metadata = self.cache.song_metadata.write();
+                    if let Some(previous) = metadata.items().get(&song.id) {
+                        song.preserve_user_fields(&previous.read());
+                    }
+                    let map = [(song.id.clone(), song.clone())].to_rwmap();
+                    metadata.items_mut().extend(map);
+
+                    let reader = metadata.reader.clone();
+
+                    let save = Cm::perform(
+                        async move {
+                            println![
+                                "Adding song to cache: {:?}",
+                                reader.extend(Vec::from([song]), true).await
+                            ];
+                        },
+                        |_| YtmrsMsg::Null,
+                    );
+                    Cm::batch([batch, save])
+                }
+            }
+            YtmrsMsg::SongCached(id) => {
+                self.downloaded.insert(id.clone());
+                self.show_song_state(&id, SongState::Downloaded);
+                self.remeasure_playing();
+                let batch = self.batch_finished(&id, None);
+                let prefetch = match self.prefetching.as_ref() == Some(&id) {
+                    // Read into memory now, rather than when it's needed
+                    true => self.fetch_song(id, false),
+                    false => Cm::none(),
+                };
+                Cm::batch([batch, prefetch])
+            }
+            YtmrsMsg::SongDownloadFinished { id, data } => {
+                self.downloaded.insert(id.clone());
+                let gain = self.record_gain(&data);
+                let notice = self.play(SoundData::from(*data));
+                let batch = self.batch_finished(&id, None);
+                Cm::batch([gain, batch, self.set_background(id), notice])
+            }
+            YtmrsMsg::SoundLocated { id, path } => match SoundData::try_from((id.clone(), path)) {
+                Ok(sound) => {
+                    self.downloaded.insert(id.clone());
+                    let notice = self.play(sound);
+                    Cm::batch([self.set_background(id), notice])
+                }
+                Err(e) => {
+                    self.notify(Notification::error(e));
+                    Cm::none()
+                }
+            },
+
+            YtmrsMsg::PrimaryColorPicked(key, rgba) => {
+                let show = self.show_background(key.clone(), rgba);
+                // So the next time it plays, the thumbnail isn't picked through again
+                let save = self.update_song(key, |song| song.primary_color = Some(rgba));
+                Cm::batch([save, show])
+            }
+            YtmrsMsg::SetNewBackground(_, _) => Cm::none(),
+            YtmrsMsg::Null => Cm::none(),
+        }
+    }
+
+    /// Assigns collision-free keys to freshly ingested songs, adds them to the
+    /// metadata cache and writes them to disk.
+    /// Returns the assigned keys in the same order as `songs`.
+    fn ingest_songs(&mut self, mut songs: Vec<Song>) -> (Vec<String>, Cm<YtmrsMsg>) {
+        {
+            let metadata = self.cache.song_metadata.read();
+            let items = metadata.items();
+            self.cache.keys.assign_batch(&mut songs, |k| {
+                items.get(k).map(|s| s.read().webpage_url.clone())
+            });
+            for song in songs.iter_mut() {
+                if let Some(previous) = items.get(&song.id) {
+                    song.preserve_user_fields(&previous.read());
+                }
+            }
+        }
+
+        let keys: Vec<String> = songs.iter().map(|s| s.id.clone()).collect();
+        (keys, self.write_songs(songs))
+    }
+
+    /// Writes the songs to disk, then adds them to the metadata cache either way
+    fn write_songs(&self, songs: Vec<Song>) -> Cm<YtmrsMsg> {
+        let map: HashMap<String, _> = songs.iter().map(|s| (s.id().clone(), s.clone())).to_rwmap();
+        let reader = self.cache.song_metadata.read().reader.clone();
+
+        Cm::perform(
+            async move {
+                let result = reader.extend(&songs, true).await;
+                println!["Extending songs: {:?}", result];
+                result.map_err(|e| e.to_string())
+            },
+            move |result| YtmrsMsg::SongsWritten { map, result },
+        )
+    }
+
+    /// Sends the query to the backend, as a url if it is one or as a search otherwise
+    fn submit_search(&mut self, query: String) -> Cm<YtmrsMsg> {
+        let request = self.search.begin_request(query.clone());
+        if let Some(query) = query.strip_prefix(LOCAL_SEARCH_PREFIX) {
+            let query = query.to_string();
+            return self.search_local(request, move |song| song.matches(&query));
+        }
+        let backend = self.backend_handler.lock();
+
+        // Check if URL is valid
+        let future = match Url::parse(&query) {
+            Ok(_) => backend.request_info(query).map(|f| f.boxed()),
+            // URL failed to parse, try to search Youtube
+            Err(e) => {
+                println!["Failed to parse: \"{}\". assuming it's a search query", e];
+                backend.request_search(query).map(|f| f.boxed())
+            }
+        };
+        match future {
+            Some(future) => Cm::perform(future, move |response| {
+                YtmrsMsg::RequestRecieved(request, response)
+            }),
+            None => {
+                drop(backend);
+                let error = BackendReqErr::Offline.describe();
+                let query = self.search.last_query.clone();
+                self.failures.record(
+                    Failure::new(
+                        FailureKind::BackendRequest,
+                        query.clone(),
+                        error.clone(),
+                        Some(Retry::Search(query)),
+                    ),
+                    Local::now(),
+                );
+                self.search.resolve(request, Err(error));
+                Cm::none()
+            }
+        }
+    }
+
+    /// Searches every saved song, including their notes
+    fn search_local(
+        &mut self,
+        request: u64,
+        filter: impl Fn(&Song) -> bool + Send + 'static,
+    ) -> Cm<YtmrsMsg> {
+        let reader = self.cache.song_metadata.read().reader.clone();
+        Cm::perform(
+            async move {
+                reader
+                    .read()
+                    .await
+                    .map(|songs: Vec<SourceItemPair<String, Song>>| {
+                        songs
+                            .into_iter()
+                            .map(|SourceItemPair(_, song)| song)
+                            .filter(filter)
+                            .collect()
+                    })
+                    .map_err(|e| format!("{e:?}"))
+            },
+            move |songs| YtmrsMsg::LocalSearchFinished(request, songs),
+        )
+    }
+
+    /// Handles keyboard navigation of the lists.
+    /// Returns None when the key isn't used for navigation.
+    fn navigate(&mut self, key: &keyboard::Key) -> Option<Cm<YtmrsMsg>> {
+        match key {
+            keyboard::Key::Named(keyboard::key::Named::Tab) => {
+                self.inputs.typing = false;
+                self.inputs.focused_list = self.inputs.focused_list.next();
+                Some(Cm::none())
+            }
+            keyboard::Key::Named(keyboard::key::Named::Escape) if self.context_menu.is_some() => {
+                self.context_menu = None;
+                Some(Cm::none())
+            }
+            keyboard::Key::Named(keyboard::key::Named::Escape) if self.bulk_edit.is_some() => {
+                self.bulk_edit = None;
+                Some(Cm::none())
+            }
+            keyboard::Key::Named(keyboard::key::Named::Escape) if self.song_editor.is_some() => {
+                self.song_editor = None;
+                Some(Cm::none())
+            }
+            keyboard::Key::Named(keyboard::key::Named::Enter)
+                if self.inputs.modifiers.control()
+                    && self.inputs.focused_list == FocusedList::Search =>
+            {
+                Some(self.update(YtmrsMsg::AddSelected))
+            }
+            keyboard::Key::Named(keyboard::key::Named::Escape)
+                if self.search.suggestions.is_open() =>
+            {
+                self.search.suggestions.close();
+                Some(Cm::none())
+            }
+            keyboard::Key::Named(
+                keyboard::key::Named::ArrowUp | keyboard::key::Named::ArrowDown,
+            ) if self.inputs.typing && self.search.suggestions.is_open() => {
+                self.search.suggestions.navigate(key);
+                Some(Cm::none())
+            }
+            keyboard::Key::Named(keyboard::key::Named::Escape) => {
+                self.inputs.typing = false;
+                self.inputs.focused_list = FocusedList::None;
+                self.search.focus.clear();
+                self.settings.playlist.focus.clear();
+                Some(Cm::none())
+            }
+            keyboard::Key::Named(
+                keyboard::key::Named::ArrowUp
+                | keyboard::key::Named::ArrowDown
+                | keyboard::key::Named::Enter
+                | keyboard::key::Named::Space,
+            ) => match self.inputs.focused_list {
+                FocusedList::None => None,
+                FocusedList::Search => Some(match self.search.navigate(key) {
+                    Some(msg) => self.update(YtmrsMsg::SearchWindowMessage(msg)),
+                    None => Cm::none(),
+                }),
+                FocusedList::Constructor => Some(match self.settings.playlist.navigate(key) {
+                    Some(wid) => self.song_clicked(wid),
+                    None => Cm::none(),
+                }),
+            },
+            _ => None,
+        }
+    }
+
+    /// Sends the "now playing" song and any listens that are due
+    #[cfg(feature = "scrobble")]
+    fn scrobble_tick(&mut self) -> Cm<YtmrsMsg> {
+        let settings = &self.settings.user.scrobble;
+        if !settings.enabled || settings.token.is_empty() {
+            return Cm::none();
+        }
+        let token = settings.token.clone();
+
+        let mut commands = vec![];
+        let mut scrobbler = self.scrobbler.lock();
+        if let Some(listen) = scrobbler.take_now_playing() {
+            let submission = scrobbler::submit(token.clone(), now_playing_payload(&listen));
+            commands.push(Cm::perform(submission, |result| {
+                if let Err(e) = result {
+                    println!["Failed to submit now playing: {e}"];
+                }
+                YtmrsMsg::Null
+            }));
+        }
+        if let Some(elapsed) = self.audio_tracker.elapsed {
+            if scrobbler.tick(elapsed) {
+                scrobbler.outbox.save();
+            }
+        }
+        if let Some(batch) = scrobbler.outbox.take_batch(time::Instant::now()) {
+            let count = batch.len();
+            commands.push(Cm::perform(
+                scrobbler::submit(token, listens_payload(&batch)),
+                move |result| YtmrsMsg::ScrobblesSubmitted(count, result),
+            ));
+        }
+        Cm::batch(commands)
+    }
+    #[cfg(not(feature = "scrobble"))]
+    fn scrobble_tick(&mut self) -> Cm<YtmrsMsg> {
+        Cm::none()
+    }
+
+    /// Saves the open playlist, then loads the next one, which `loaded` opens.
+    /// Nothing is loaded if the open one couldn't be saved, so it isn't lost.
+    fn replace_playlist<F, Fut, T>(
+        &self,
+        next: F,
+        loaded: fn(Result<T, LoadError>) -> YtmrsMsg,
+    ) -> Cm<YtmrsMsg>
+    where
+        F: FnOnce(PathBuf) -> Fut + Send + 'static,
+        Fut: std::future::Future<Output = Result<T, LoadError>> + Send + 'static,
+        T: Send + 'static,
+    {
+        let current = self.settings.playlist.clone();
+        Cm::perform(
+            async move {
+                let dir = playlists_directory();
+                if let Err(e) = current.save_to(&dir).await {
+                    println!["Not switching, the open playlist couldn't be saved: {e:?}"];
+                    return Err(LoadError::File);
+                }
+                next(dir).await
+            },
+            loaded,
+        )
+    }
+
+    fn open_playlist(&mut self, mut playlist: Playlist) -> Cm<YtmrsMsg> {
+        playlist
+            .constructor
+            .set_cache(Arc::clone(&self.cache.song_metadata));
+        self.library.record(&playlist);
+        self.settings.playlist = playlist;
+        // The tracker walks the old tree
+        self.player_state = None;
+        self.external_change = None;
+        self.refresh_queue();
+        self.sync_used_keys();
+        Cm::batch([
+            self.fetch_missing_metadata(),
+            Cm::perform(self.settings.clone().save(), YtmrsMsg::SettingsSaved),
+        ])
+    }
+
+    /// Deletes the open playlist's file and opens another one, or a new one if it was the last
+    fn delete_playlist(&mut self) -> Cm<YtmrsMsg> {
+        self.library.set_confirming_delete(false);
+        let id = self.settings.playlist.id;
+        Cm::perform(
+            async move { playlist::delete_playlist(&playlists_directory(), id).await },
+            move |result| YtmrsMsg::PlaylistDeleted(id, result),
+        )
+    }
+
+    /// Saves the group as its own playlist, removing it from the current one if `moved`
+    fn promote_group(&mut self, wid: WId, moved: bool) -> Cm<YtmrsMsg> {
+        let path = match self.settings.playlist.constructor.path_to_id(&wid) {
+            Some(path) => path,
+            None => return Cm::none(),
+        };
+        let playing = self.playing_tree_path();
+        let constructor = &mut self.settings.playlist.constructor;
+        let mut group = match constructor.promote(path.clone(), moved) {
+            Some(group) => group,
+            None => return Cm::none(),
+        };
+        group.set_cache(Arc::clone(&self.cache.song_metadata));
+        let name = format!("{} ({})", self.settings.playlist.name, group.label());
+
+        if let (true, Some(current)) = (moved, playing) {
+            match song_operations::path_after_removal(&current, &path) {
+                Some(current) => self.remap_playing_path(current),
+                None => {
+                    println!["The playing song was moved to another playlist, stopping the queue"];
+                    self.player_state = None;
+                }
+            }
+        }
+
+        let playlist = Playlist {
+            name,
+            constructor: group,
+            ..Default::default()
+        };
+        Cm::perform(playlist.save(), |result| {
+            match result {
+                Ok(path) => println!["Saved group to {path:?}"],
+                Err(e) => println!["{e:?}"],
+            }
+            YtmrsMsg::Null
+        })
+    }
+
+    /// Puts the song in a group of its own, focusing the group's N field so it can be changed
+    fn wrap_song(&mut self, wid: WId) -> Cm<YtmrsMsg> {
+        let playing = self.playing_tree_path();
+        let constructor = &mut self.settings.playlist.constructor;
+        let path = match constructor.path_to_id(&wid) {
+            Some(path) => path,
+            None => return Cm::none(),
+        };
+        let wrap = &self.settings.user.wrap;
+        let n_input = match constructor.wrap_song(path.clone(), wrap.operation.clone(), wrap.n) {
+            Some(n_input) => n_input,
+            None => return Cm::none(),
+        };
+        if let Some(current) = playing {
+            self.remap_playing_path(song_operations::path_after_wrap(&current, &path));
+        }
+        text_input::focus(n_input)
+    }
+
+    /// Replaces the group with its items
+    fn flatten_group(&mut self, wid: WId) -> Cm<YtmrsMsg> {
+        let playing = self.playing_tree_path();
+        let constructor = &mut self.settings.playlist.constructor;
+        let path = match constructor.path_to_id(&wid) {
+            Some(path) => path,
+            None => return Cm::none(),
+        };
+        if let (Some(count), Some(current)) = (constructor.flatten_group(path.clone()), playing) {
+            self.remap_playing_path(song_operations::path_after_flatten(&current, &path, count));
+        }
+        Cm::none()
+    }
+
+    /// Runs an edit of the playlist's tree, keeping the tree from before it so it can be undone
+    fn edit_playlist<T>(&mut self, edit: impl FnOnce(&mut Self) -> T) -> T {
+        let before = self.settings.playlist.constructor.clone();
+        let result = edit(self);
+        let playlist = &mut self.settings.playlist;
+        playlist.history.record(before, &playlist.constructor);
+        result
+    }
+
+    /// Undoes or redoes an edit of the playlist. The playing song keeps playing if its row
+    /// is still in the tree.
+    fn step_history(&mut self, step: HistoryStep) -> Cm<YtmrsMsg> {
+        let playing = self.player_state.as_ref().and_then(|state| {
+            let path: Vec<usize> = state.tracker.get_current().collect();
+            match self.settings.playlist.constructor.played_item(&path) {
+                Some(ConstructorItem::Song(_, sid)) => Some(WId::from(sid.0.clone())),
+                _ => None,
+            }
+        });
+        let playlist = &mut self.settings.playlist;
+        let stepped = match step {
+            HistoryStep::Undo => playlist.history.undo(&mut playlist.constructor),
+            HistoryStep::Redo => playlist.history.redo(&mut playlist.constructor),
+        };
+        if !stepped {
+            return Cm::none();
+        }
+        playlist
+            .constructor
+            .set_cache(Arc::clone(&self.cache.song_metadata));
+        match playing.and_then(|id| playlist.constructor.path_to_id(&id)) {
+            Some(path) => self.remap_playing_path(path),
+            None if self.player_state.is_some() => {
+                println!["The playing song isn't in the playlist anymore, stopping the queue"];
+                self.player_state = None;
+            }
+            None => {}
+        }
+        self.sync_used_keys();
+        self.fetch_missing_metadata()
+    }
+
+    fn constructor_message(&mut self, msg: SongOpMessage) -> Cm<YtmrsMsg> {
+        // The tracker's paths skip the groups that are off, so they move when one is toggled
+        let playing = match msg.toggles_group() {
+            true => self.playing_tree_path(),
+            false => None,
+        };
+        let result = self.settings.playlist.constructor.update(msg);
+        if let Some(path) = playing {
+            self.remap_playing_path(path);
+        }
+        match result {
+            Some(msg) => match msg {
+                UpdateResult::Cm(cm) => {
+                    cm.map(|m| YtmrsMsg::PlaylistMsg(PlaylistMessage::ConstructorMessage(m)))
+                }
+                UpdateResult::SongClicked(wid) => self.song_clicked(wid),
+                UpdateResult::Promote(wid, moved) => self.promote_group(wid, moved),
+                UpdateResult::Wrap(wid) => self.wrap_song(wid),
+                UpdateResult::Flatten(wid) => self.flatten_group(wid),
+                UpdateResult::EditSong(key) => self.open_song_editor(key),
+                UpdateResult::OpenMenu(wid) => {
+                    self.context_menu = Some(MenuTarget::Playlist(wid));
+                    Cm::none()
+                }
+                UpdateResult::Menu(wid, key, action) => self.song_action(key, Some(wid), action),
+                UpdateResult::Move(from, to) => {
+                    // Remove item at `from` and place it to `to`
+                    println!["MOVE FROM {:?} TO {:?}", from, to];
+                    let from_path = self.settings.playlist.constructor.path_to_id(&from);
+
+                    if from_path.is_none() {
+                        return Cm::none();
+                    }
+                    let from_path = from_path.unwrap();
+                    println!["FROM:{:?}", from_path];
+
+                    let taken = self.settings.playlist.constructor.take_path(&from_path);
+
+                    if taken.is_none() {
+                        return Cm::none();
+                    }
+                    let (item, weight) = taken.unwrap();
+
+                    let to_path = self.settings.playlist.constructor.path_to_id(&to);
+
+                    if to_path.is_none() {
+                        return Cm::none();
+                    }
+
+                    let to_path = to_path.unwrap();
+                    println!["TO:{:?}", to_path];
+
+                    self.settings
+                        .playlist
+                        .constructor
+                        .put_at_path(to_path.into(), item, weight);
+
+                    Cm::none()
+                }
+            },
+            None => Cm::none(),
+        }
+    }
+
+    /// Where the playing song is in the tree, None if nothing is playing
+    fn playing_tree_path(&self) -> Option<Vec<usize>> {
+        let current: Vec<usize> = self.player_state.as_ref()?.tracker.get_current().collect();
+        self.settings.playlist.constructor.tree_path(&current)
+    }
+
+    /// Moves the queue to the playing song's path in the tree after the tree changed shape.
+    /// The tracker is rebuilt from the new tree, so loop counts start over.
+    fn remap_playing_path(&mut self, path: Vec<usize>) {
+        if self.player_state.is_none() {
+            return;
+        }
+        let constructor = &self.settings.playlist.constructor;
+        let played = match constructor.played_path(&path) {
+            Some(played) => played,
+            None => {
+                self.stop_playing("The playing song's group was turned off, so playback stopped");
+                return;
+            }
+        };
+        let song_op = self.settings.user.simple.build(constructor.build());
+        let tracker = SongOpTracker::from_song_op(&song_op, played.into());
+        self.player_state = Some(self.player_state_for(tracker, song_op));
+        self.refresh_queue();
+    }
+
+    /// Measures the timed groups being played again, as more songs' lengths are known
+    fn remeasure_playing(&mut self) {
+        let state = match self.player_state.as_mut() {
+            Some(state) => state,
+            None => return,
+        };
+        let metadata = self.cache.song_metadata.read();
+        let items = metadata.items();
+        let length = |key: &SongKey| items.get(key)?.read().song_duration().known();
+        state.measure(&length, self.settings.user.unknown_song_secs as f64);
+    }
+
+    /// The state to play the tracker of the op with, shuffled and repeated as the user has it.
+    /// Timed groups count the lengths of the songs that are cached, and shuffles go by the
+    /// songs' ratings.
+    fn player_state_for(&self, mut tracker: SongOpTracker, op: RecursiveSongOp) -> PlayerState {
+        let user = &self.settings.user;
+        let metadata = self.cache.song_metadata.read();
+        let items = metadata.items();
+        let length = |key: &SongKey| items.get(key)?.read().song_duration().known();
+        tracker.measure(&length, user.unknown_song_secs as f64);
+        let ratings = ShuffleRatings::new(op, |key| items.get(key)?.read().rating);
+        PlayerState::new(tracker)
+            .with_ratings(ratings)
+            .with_shuffle(user.shuffle)
+            .with_repeat(user.repeat)
+    }
+
+    fn refresh_queue(&mut self) {
+        let tracker = self.player_state.as_ref().map(|state| &state.tracker);
+        self.queue
+            .refresh(tracker, &self.settings.playlist.constructor);
+    }
+
+    /// Plays the upcoming song at the index of the queue
+    fn jump_in_queue(&mut self, idx: usize) -> Cm<YtmrsMsg> {
+        let state = match &mut self.player_state {
+            Some(state) => state,
+            None => return Cm::none(),
+        };
+        if !self.queue.jump(state.tracker_mut(), idx) {
+            return Cm::none();
+        }
+        let path: VecDeque<usize> = state.tracker.get_current().collect();
+        self.song_ended(false);
+        self.play_trigger = Some(PlayTrigger::Skipped);
+        self.play_at_path(path)
+    }
+
+    /// Opens the new default device, carrying on with the song where it was
+    fn switch_output(&mut self) -> Cm<YtmrsMsg> {
+        let playing = !matches![
+            self.audio_manager.playback_state(),
+            PlaybackState::Stopping | PlaybackState::Stopped
+        ];
+        let resume = match (&self.player_state, &self.now_playing, playing) {
+            (Some(state), Some(key), true) => Some(Session {
+                tracker: state.tracker.clone(),
+                key: key.clone(),
+                elapsed: self.audio_manager.elapsed().unwrap_or_default(),
+                paused: self.audio_tracker.paused,
+            }),
+            _ => None,
+        };
+        if let Err(e) = self.audio_manager.rebuild(&self.settings.user.eq) {
+            self.notify(Notification::error(format!(
+                "Couldn't switch to the new output device: {e}"
+            )));
+            self.audio_manager.set_volume(self.playing_volume() as f64);
+            return Cm::none();
+        }
+        match resume {
+            Some(session) => {
+                let path = session.tracker.get_current().collect();
+                self.resuming = Some(session);
+                self.play_at_path(path)
+            }
+            None => Cm::none(),
+        }
+    }
+
+    /// Starts the scheduled playlist from the top, ramping up the volume.
+    /// Another playlist is opened first, and started once it's open.
+    fn start_scheduled(&mut self, entry: ScheduleEntry) -> Cm<YtmrsMsg> {
+        if !entry.interrupt && self.audio_manager.playback_state() == PlaybackState::Playing {
+            println!["Skipping scheduled playback, something is already playing"];
+            return Cm::none();
+        }
+        if entry.playlist != self.settings.playlist.id {
+            let id = entry.playlist;
+            self.scheduled = Some(entry);
+            return self.replace_playlist(
+                move |dir| async move { Playlist::load_from(&dir, id).await },
+                YtmrsMsg::PlaylistLoaded,
+            );
+        }
+
+        match self.settings.playlist.constructor.first_song_id() {
+            Some(wid) => {
+                self.volume_ramp = Some(VolumeRamp::new(
+                    entry.volume,
+                    time::Duration::from_secs(entry.ramp_secs),
+                ));
+                self.song_clicked(wid)
             }
-            YtmrsMsg::SoundsFetched { map, play } => {
-                println!["Sounds fetched."];
+            None => Cm::none(),
+        }
+    }
 
-                let map: HashMap<_, _> = map
-                    .into_iter()
-                    .map(|(k, v)| (k.clone(), BasicSoundData::from((k, v))))
-                    .to_rwmap();
+    /// Sets the rating of a cached song and writes it back to the metadata file
+    fn rate_song(&mut self, key: String, rating: Option<u8>) -> Cm<YtmrsMsg> {
+        if let Some(state) = &mut self.player_state {
+            state.ratings.set(&key, rating);
+        }
+        self.update_song(key, |song| song.rating = rating)
+    }
 
-                self.cache.sounds.items_mut().extend(map.clone());
+    /// Edits a cached song and writes it back to the metadata file
+    fn update_song(&mut self, key: String, edit: impl FnOnce(&mut Song)) -> Cm<YtmrsMsg> {
+        let metadata = self.cache.song_metadata.read();
+        let song = metadata.items().get(&key).and_then(|song| {
+            let mut song = song.write();
+            let before = song.clone();
+            edit(&mut song);
+            match song.saves_as(&before) {
+                true => None,
+                false => Some(song.clone()),
+            }
+        });
+        if song.is_none() {
+            return Cm::none();
+        }
+        metadata.touch();
+        let reader = metadata.reader.clone();
 
-                if let Some(k) = play {
-                    if map.contains_key(&k) {
-                        let sound = map[&k].clone();
-                        let sound = sound.read();
-                        self.play(SoundData::from(sound.clone()));
+        match song {
+            Some(song) => Cm::perform(
+                async move {
+                    println![
+                        "Saving song: {:?}",
+                        reader.extend(Vec::from([song]), true).await
+                    ];
+                },
+                |_| YtmrsMsg::Null,
+            ),
+            None => Cm::none(),
+        }
+    }
 
-                        return self.set_background(k);
-                    }
-                }
+    /// Saves the gain measured when the song was decoded, if the song doesn't have one yet
+    fn record_gain(&mut self, sound: &BasicSoundData) -> Cm<YtmrsMsg> {
+        let gain = match sound.gain() {
+            Some(gain) => gain,
+            None => return Cm::none(),
+        };
+        let known = match self.cache.song_metadata.read().items().get(sound.id()) {
+            Some(song) => song.read().gain.is_some(),
+            None => true,
+        };
+        match known {
+            true => Cm::none(),
+            false => self.update_song(sound.id().clone(), |song| song.gain = Some(gain)),
+        }
+    }
 
-                Cm::none()
-            }
-            YtmrsMsg::DownloadSong(s, play) => self.download_song(s, play),
-            YtmrsMsg::SongDownloaded { song, play } => {
-                if let Some(recdown) = song.requested_downloads {
-                    let recdown = recdown[0].clone();
-                    let filepath = PathBuf::from(recdown.filepath);
-                    let id = song.id.clone();
-                    let id2 = id.clone();
-                    let reader = self.cache.sounds.reader.clone();
-                    Cm::perform(
-                        async move {
-                            let data = read_file(&filepath).await;
+    /// The gain the playing song is normalized by, unity when it's off or unmeasured
+    fn gain_for(&self, key: &str) -> f64 {
+        let gain = match self.settings.user.normalize {
+            true => self
+                .cache
+                .song_metadata
+                .read()
+                .items()
+                .get(key)
+                .map(|s| s.read().gain),
+            false => None,
+        };
+        gain.flatten().unwrap_or(1.0)
+    }
 
-                            match data {
-                                Ok(data) => {
-                                    let file_data = FileData::new(id2.clone(), data);
-                                    {
-                                        println![
-                                            "{:?}",
-                                            reader.extend(vec![&file_data], true).await
-                                        ];
-                                        println!["531 Extended."];
-                                    }
-                                    if play {
-                                        println!["Creating sound from bytes..."];
-                                        let bsd =
-                                            BasicSoundData::from((id2, file_data.into_data()));
-                                        println!["Created sound from bytes."];
+    fn open_song_editor(&mut self, key: String) -> Cm<YtmrsMsg> {
+        let song = match self.cache.song_metadata.read().items().get(&key) {
+            Some(song) => song.read().clone(),
+            None => {
+                println!["Can't edit {key}, its metadata isn't cached"];
+                return Cm::none();
+            }
+        };
+        let (editor, command) = SongEditor::open(&song);
+        self.song_editor = Some(editor);
+        command
+    }
 
-                                        // Delete the original file
-                                        println!["Deleting {:?}...", filepath];
-                                        match async_std::fs::remove_file(&filepath).await {
-                                            Ok(_) => {
-                                                println!["Deleted {:?}.", filepath];
-                                            }
-                                            Err(e) => {
-                                                println!["Error deleting {:?}: {:?}", filepath, e];
-                                            }
-                                        }
+    /// Applies the bulk editor to its songs, writing the ones that changed in one batch
+    fn apply_bulk_edit(&mut self) -> Cm<YtmrsMsg> {
+        let editor = match &mut self.bulk_edit {
+            Some(editor) if !editor.edit.is_empty() => editor,
+            _ => return Cm::none(),
+        };
+        let metadata = self.cache.song_metadata.read();
+        let (songs, summary) = bulk_edit::apply_to(&editor.edit, metadata.items(), &editor.keys);
+        metadata.touch();
+        println!["Bulk edit: {summary}"];
+        editor.summary = Some(summary);
+        let reader = metadata.reader.clone();
 
-                                        Some(Box::new(bsd))
-                                    } else {
-                                        None
-                                    }
-                                }
-                                Err(e) => {
-                                    println!["{:?}", e];
-                                    None
-                                }
-                            }
-                        },
-                        move |data| match data {
-                            Some(data) => YtmrsMsg::SongDownloadFinished { id, data },
-                            None => YtmrsMsg::Null,
-                        },
-                    )
-                } else {
-                    // Add the song to the filecache
-                    let map = [(song.id.clone(), song.clone())].to_rwmap();
-                    let mut metadata = self.cache.song_metadata.write();
-                    metadata.items_mut().extend(map);
+        match songs.is_empty() {
+            true => Cm::none(),
+            false => Cm::perform(
+                async move {
+                    println!["Saving songs: {:?}", reader.extend(songs, true).await];
+                },
+                |_| YtmrsMsg::Null,
+            ),
+        }
+    }
 
-                    let reader = metadata.reader.clone();
+    /// Asks the backend for the songs' info again, even though they're cached
+    fn refresh_songs(&mut self, keys: impl IntoIterator<Item = SongKey>) -> Cm<YtmrsMsg> {
+        self.metadata_queue.refresh(keys);
+        self.fetch_metadata()
+    }
 
-                    Cm::perform(
-                        async move {
-                            println![
-                                "Adding song to cache: {:?}",
-                                reader.extend(Vec::from([song]), true).await
-                            ];
-                        },
-                        |_| YtmrsMsg::Null,
-                    )
+    /// Asks the backend for as many of the queued songs as there's room for.
+    /// Offline, every song fails without being asked for.
+    fn fetch_metadata(&mut self) -> Cm<YtmrsMsg> {
+        let batch = {
+            let metadata = self.cache.song_metadata.read();
+            let items = metadata.items();
+            let limit = self.settings.user.metadata_requests;
+            self.metadata_queue
+                .next_batch(limit, |key| items.contains_key(key))
+        };
+        if batch.is_empty() {
+            return Cm::none();
+        }
+        let backend_handler = self.backend_handler.clone();
+        let requests = async move {
+            let responses = join_all(batch.iter().map(|id| {
+                let url = BackendHandler::request_url_from_id(id);
+                match backend_handler.lock().request_info(url) {
+                    Some(request) => request.boxed(),
+                    None => async { RequestResult::Err(BackendReqErr::Offline) }.boxed(),
                 }
-            }
-            YtmrsMsg::SongDownloadFinished { id, data } => {
-                self.play(SoundData::from(*data));
-                self.set_background(id)
-            }
+            }))
+            .await;
+            batch
+                .into_iter()
+                .zip(responses)
+                .map(|(id, response)| {
+                    let song = match response {
+                        Ok(s) => match serde_json::from_str::<Song>(&s) {
+                            Ok(song) => Ok(song),
+                            Err(_) if is_unavailable(&s) => Err(FetchError::Unavailable),
+                            Err(e) => Err(FetchError::Failed(e.to_string())),
+                        },
+                        Err(e) => Err(FetchError::Failed(e.describe())),
+                    };
+                    (id, song)
+                })
+                .collect()
+        };
+        Cm::perform(requests, YtmrsMsg::SongsReceived)
+    }
 
-            YtmrsMsg::SetNewBackground(_, _) => Cm::none(),
-            YtmrsMsg::Null => Cm::none(),
+    /// Starts downloading as many of the queued thumbnails as there's room for.
+    /// Links that can't be read fail like downloads do, so they aren't tried right away again.
+    fn download_thumbnails(&mut self) -> Cm<YtmrsMsg> {
+        let batch = self.thumbnail_queue.next_batch(&self.songs_on_screen());
+        if batch.is_empty() {
+            return Cm::none();
         }
+        let urls: Vec<(String, Url)> = self
+            .cache
+            .song_metadata
+            .read()
+            .fetch_existing(&batch)
+            .into_iter()
+            .filter_map(|(id, song)| Some((id, Url::parse(&song.read().thumbnail).ok()?)))
+            .collect();
+        let reader = self.cache.thumbnails.clone();
+        Cm::perform(get_images(reader, urls, ThumbnailSize::Small), move |map| {
+            YtmrsMsg::ThumbnailsDownloaded { batch, map }
+        })
+    }
+
+    /// The songs whose rows are on screen, going by how far the lists are scrolled
+    fn songs_on_screen(&self) -> HashSet<String> {
+        let results = &self.search.search_type;
+        let shown_results = self.search.on_screen.rows(results.row_count());
+        let rows = self.settings.playlist.constructor.visible_song_keys();
+        let shown_rows = self.settings.playlist.on_screen.rows(rows.len());
+        shown_results
+            .filter_map(|idx| results.song_key(idx))
+            .chain(rows[shown_rows].iter().copied())
+            .cloned()
+            .collect()
     }
 
+    /// Loads the saved thumbnails of the songs that don't have one yet.
+    /// Those that weren't saved are queued to be downloaded.
     fn download_images_for_ids(&self, ids: HashSet<String>) -> Cm<YtmrsMsg> {
-        // get existing songs which already have thumbnails
-        let songs: HashMap<_, _> = self
+        let needed: HashSet<String> = self
             .cache
             .song_metadata
             .read()
             .fetch_existing(&ids)
             .into_iter()
-            .filter_map(|(id, song)| {
-                let s = song.read();
-                match s.thumbnail_handle {
-                    Some(_) => None,
-                    None => Some((id, song.clone())),
-                }
-            })
+            .filter(|(_, song)| song.read().thumbnail_handle.is_none())
+            .map(|(id, _)| id)
             .collect();
-        let song_ids: HashSet<String> = songs.keys().cloned().collect();
-
-        let missing_ids: HashSet<String> = ids.difference(&song_ids).cloned().collect();
+        if needed.is_empty() {
+            return Cm::none();
+        }
 
         let thumb_reader = self.cache.thumbnails.clone();
 
         Cm::perform(
-            async move {
-                let items = thumb_reader.read_filter(&missing_ids).await.unwrap();
-                let ids: Vec<_> = items.iter().map(|(k, _)| k.clone()).collect();
-                let futures = items.into_iter().map(|(_, f)| f);
-                let items: HashMap<String, _> =
-                    ids.into_iter().zip(join_all(futures).await).collect();
-
-                items
-                    .into_values()
-                    .map(|x| (x.1.id().clone(), Handle::from_path(x.1.into_data())))
-                    .collect()
-            },
+            thumbnails::get_handles(thumb_reader, needed.clone(), ThumbnailSize::Small),
             move |map: HashMap<String, Handle>| YtmrsMsg::ImagesFetched {
                 missing: {
                     let collected_ids: HashSet<_> = map.keys().cloned().collect();
 
                     let actually_missing: HashSet<String> =
-                        ids.difference(&collected_ids).cloned().collect();
+                        needed.difference(&collected_ids).cloned().collect();
                     match actually_missing.len() {
                         0 => None,
                         _ => Some(actually_missing),
@@ -797,68 +3888,120 @@ impl Ytmrs {
         )
     }
 
-    fn all_used_keys(&self) -> HashSet<String> {
-        let from_search: HashSet<&String> = self.search.used_keys().into_iter().collect();
-        let from_constr: HashSet<&String> = self
-            .settings
-            .playlist
-            .constructor
-            .all_song_keys_rec()
-            .collect();
+    /// Brings the used keys up to date with the playlist, the search, the saved playlists,
+    /// the history and the queue. Only sources whose revision changed since the last sync
+    /// are walked.
+    fn sync_used_keys(&mut self) {
+        let constructor = &self.settings.playlist.constructor;
+        self.used_keys.sync(
+            KeySource::Playlist,
+            constructor.revision(),
+            constructor.all_song_keys_rec(),
+        );
+        self.used_keys.sync(
+            KeySource::Search,
+            self.search.revision(),
+            self.search.used_keys(),
+        );
+        self.used_keys.sync(
+            KeySource::Library,
+            self.library.revision(),
+            self.library.used_keys(),
+        );
+        self.used_keys.sync(
+            KeySource::History,
+            self.history.revision(),
+            self.history.used_keys(),
+        );
+        self.used_keys.sync(
+            KeySource::Queue,
+            self.queue.revision(),
+            self.queue.used_keys(),
+        );
+    }
 
-        from_search.union(&from_constr).cloned().cloned().collect()
+    /// Shows the notification at the top of the window
+    fn notify(&mut self, notification: Notification) {
+        self.notifications.push(notification, time::Instant::now());
     }
 
-    /// Handles zones
-    fn handle_zones(
-        &mut self,
-        key: String,
-        zones: Vec<(iced::advanced::widget::Id, iced::Rectangle)>,
-    ) {
-        let top = &mut self.settings.playlist.constructor;
-        println!["KEY: {:?}", key];
+    /// Searches and downloads are turned off while the backend is offline,
+    /// saved songs keep playing
+    fn set_offline(&mut self, offline: bool) {
+        if self.offline == offline {
+            return;
+        }
+        self.offline = offline;
+        self.search.offline = offline;
+        self.notify(match offline {
+            true => Notification::error(BackendReqErr::Offline.describe()),
+            false => Notification::info("Connected to the backend"),
+        });
+    }
 
-        let targets = if let Some(v) = self.search.selected_keys() {
-            v
-        } else {
-            vec![&key]
+    /// Polls the backend regularly while it's up. While it's down, the attempts to reach it
+    /// are spread further and further apart.
+    fn schedule_backend_poll(&mut self) {
+        let (online, launched) = {
+            let backend = self.backend_handler.lock();
+            (backend.is_online(), backend.status.is_online())
         };
+        // A server that was just launched is only told of once it answers
+        if online || !launched {
+            self.set_offline(!online);
+        }
+        let interval = next_backend_poll(online, &mut self.backend_failures);
+        self.tickers
+            .backend_status
+            .1
+            .set(interval, time::Instant::now());
+    }
 
+    /// Where songs dropped on the zones go: the path of the row they were dropped on, or the
+    /// end of the playlist. None if they weren't dropped on the playlist.
+    fn drop_path(&self, zones: &[(WId, iced::Rectangle)]) -> Option<Vec<usize>> {
+        let top = &self.settings.playlist.constructor;
         if let Some((id, _)) = zones.iter().rev().find(|(id, _)| top.item_has_id(id)) {
             println!["Target: {:#?}", id];
+            return top.path_to_id(id);
+        }
+        match zones.last() {
+            Some((id, _)) if *id == WId::new("base_drop_target") => Some(vec![top.list.len()]),
+            _ => None,
+        }
+    }
 
-            let mut path = top.path_to_id(id).unwrap();
-            println!["{:?}", path];
+    /// Handles zones
+    fn handle_zones(&mut self, keys: Vec<String>, zones: &[(WId, iced::Rectangle)]) {
+        println!["KEYS: {:?}", keys];
+        let mut path = match self.drop_path(zones) {
+            Some(path) => path,
+            None => return,
+        };
+        println!["{:?}", path];
 
-            let mut idx = path.pop().unwrap_or(0);
-            for item in targets
-                .into_iter()
-                .map(|k| ConstructorItem::from(k.clone()))
-            {
-                path.push(idx);
-                top.push_to_path(VecDeque::from(path.clone()), item);
-                path.pop();
-                idx += 1;
-            }
-        } else if let Some((id, _)) = zones.last() {
-            if *id == WId::new("base_drop_target") {
-                let mut idx = top.list.len();
+        let top = &mut self.settings.playlist.constructor;
+        let mut idx = path.pop().unwrap_or(0);
+        for key in &keys {
+            let item = ConstructorItem::from(key.clone());
+            path.push(idx);
+            top.push_to_path(VecDeque::from(path.clone()), item);
+            path.pop();
+            idx += 1;
+        }
 
-                for item in targets
-                    .into_iter()
-                    .map(|k| ConstructorItem::from(k.clone()))
-                {
-                    top.push_to_path(VecDeque::from([idx]), item);
-                    idx += 1;
-                }
-            }
+        for id in keys {
+            self.events.emit(AppEvent::SongAdded {
+                id,
+                playlist: self.settings.playlist.name.clone(),
+            });
         }
     }
 
     fn push_image_handles(&mut self, map: HashMap<String, Handle>) {
-        let used_keys = self.all_used_keys();
         let lock = self.cache.song_metadata.write();
-        let mut song_cache = lock.fetch_existing(&used_keys);
+        lock.touch();
+        let mut song_cache = lock.fetch_existing(map.keys());
         for (key, song) in song_cache.iter_mut() {
             let mut lock = song.write();
             if lock.thumbnail_handle.is_none() {
@@ -869,215 +4012,1068 @@ impl Ytmrs {
         }
     }
 
-    /// Restarts the current song if it is playing after 2s, otherwise plays the previous song
-    fn rewind(&mut self) -> Cm<YtmrsMsg> {
-        match self.audio_manager.elapsed() {
-            Some(elapsed) => {
-                if elapsed > 2.0 {
-                    self.audio_manager.seek_to_start();
-                    Cm::none()
-                } else {
-                    self.play_previous_song()
-                }
+    /// Runs the action of a transport key
+    fn transport(&mut self, action: Transport) -> Cm<YtmrsMsg> {
+        let msg = match action {
+            Transport::TogglePause | Transport::Seek(_) if self.now_playing.is_none() => {
+                return Cm::none()
+            }
+            Transport::TogglePause => match self.audio_tracker.paused {
+                true => TrackerMsg::Play,
+                false => TrackerMsg::Pause,
+            },
+            Transport::Next => TrackerMsg::Next,
+            Transport::Previous => TrackerMsg::Previous,
+            Transport::Volume(step) => TrackerMsg::UpdateVolume(
+                (self.audio_tracker.volume + step).clamp(0.0, VOLUME_SLIDER_MAX),
+            ),
+            Transport::Seek(secs) => {
+                if let Some(elapsed) = self.audio_manager.elapsed() {
+                    let target = (elapsed + secs).max(0.0);
+                    self.audio_manager.seek(target);
+                    self.audio_tracker.update_from_manager(&self.audio_manager);
+                    self.events.emit(AppEvent::Seeked { secs: target });
+                }
+                return Cm::none();
+            }
+        };
+        self.update(YtmrsMsg::AudioTrackerMessage(msg))
+    }
+
+    /// Restarts the current song if it is playing after 2s, otherwise plays the previous song
+    fn rewind(&mut self) -> Cm<YtmrsMsg> {
+        match self.audio_manager.elapsed() {
+            Some(elapsed) => {
+                if elapsed > 2.0 {
+                    self.audio_manager.seek_to_start();
+                    Cm::none()
+                } else {
+                    self.play_trigger = Some(PlayTrigger::Skipped);
+                    self.play_previous_song()
+                }
+            }
+            None => Cm::none(),
+        }
+    }
+
+    fn play_previous_song(&mut self) -> Cm<YtmrsMsg> {
+        if let Some(state) = &mut self.player_state {
+            match state.tracker_mut().move_back() {
+                song_operations::BackResult::Rewound => {
+                    self.audio_manager.seek_to_start();
+                    self.audio_manager.play();
+                    self.audio_tracker.paused = false;
+                    Cm::none()
+                }
+                song_operations::BackResult::Current => {
+                    let path: VecDeque<usize> = state.tracker.get_current().collect();
+                    self.play_at_path(path)
+                }
+            }
+        } else {
+            Cm::none()
+        }
+    }
+
+    /// Whether the song that ended plays again. Ops that loop forever already pick what's
+    /// next, so repeating doesn't change them.
+    fn repeats_song(&self) -> bool {
+        self.settings.user.repeat == Repeat::One
+            && self.player_state.is_some()
+            && self
+                .settings
+                .user
+                .simple
+                .build(self.settings.playlist.constructor.build())
+                .loop_type()
+                != InfLoopType::Always
+    }
+
+    /// Plays the current song again from the start
+    fn replay(&mut self) -> Cm<YtmrsMsg> {
+        match &self.player_state {
+            Some(state) => {
+                let path: VecDeque<usize> = state.tracker.get_current().collect();
+                self.play_at_path(path)
+            }
+            None => Cm::none(),
+        }
+    }
+
+    fn play_next_song(&mut self) -> Cm<YtmrsMsg> {
+        if let Some(state) = &mut self.player_state {
+            match state.move_next() {
+                song_operations::NextResult::Current => {
+                    let path: VecDeque<usize> = state.tracker.get_current().collect();
+                    self.play_at_path(path)
+                }
+                song_operations::NextResult::Ended => {
+                    // Pause
+                    Cm::none()
+                }
+            }
+        } else {
+            Cm::none()
+        }
+    }
+
+    /// Where the playing song is in its group, if the group holds more than one song
+    fn album_position(&self) -> Option<AlbumPosition> {
+        let path = self.playing_tree_path()?;
+        let metadata = self.cache.song_metadata.read();
+        let items = metadata.items();
+        album::album_position(
+            &self.settings.playlist.constructor,
+            &path,
+            self.audio_tracker.elapsed.unwrap_or(0.0),
+            |key| match items.get(key) {
+                Some(song) => song.read().song_duration(),
+                None => SongDuration::Unknown,
+            },
+        )
+    }
+
+    /// Plays the first song of the group after or before the playing song's group
+    fn skip_group(&mut self, forward: bool) -> Cm<YtmrsMsg> {
+        let state = match &mut self.player_state {
+            Some(state) => state,
+            None => return Cm::none(),
+        };
+        let current: Vec<usize> = state.tracker.get_current().collect();
+        let constructor = &self.settings.playlist.constructor;
+        let current = match constructor.tree_path(&current) {
+            Some(current) => current,
+            None => return Cm::none(),
+        };
+        let unit = album::current_unit(&current);
+        let target = album::sibling_group(constructor, unit, forward)
+            .and_then(|target| constructor.played_path(&target));
+        let target = match target {
+            Some(target) => target,
+            None => return Cm::none(),
+        };
+        let moved = match forward {
+            true => album::forward_into(state.tracker_mut(), &target),
+            false => album::back_into(state.tracker_mut(), &target),
+        };
+        if !moved {
+            return Cm::none();
+        }
+        let path: VecDeque<usize> = state.tracker.get_current().collect();
+        self.song_ended(false);
+        self.play_trigger = Some(PlayTrigger::Skipped);
+        self.play_at_path(path)
+    }
+
+    /// Plays the playing song's group from its first song
+    fn restart_group(&mut self) -> Cm<YtmrsMsg> {
+        let state = match &mut self.player_state {
+            Some(state) => state,
+            None => return Cm::none(),
+        };
+        let current: Vec<usize> = state.tracker.get_current().collect();
+        let group = match album::parent_group(&current) {
+            Some(group) => group,
+            None => return Cm::none(),
+        };
+        album::rewind_to_start(state.tracker_mut(), group);
+        let path: VecDeque<usize> = state.tracker.get_current().collect();
+        self.song_ended(false);
+        self.play_trigger = Some(PlayTrigger::Skipped);
+        self.play_at_path(path)
+    }
+
+    /// Shows the song's colors in the background. They're picked from its thumbnail the first
+    /// time it plays, which is downloaded if it wasn't saved.
+    fn set_background(&self, key: String) -> Cm<YtmrsMsg> {
+        let (picked, url) = match self.cache.song_metadata.read().items().get(&key) {
+            Some(song) => {
+                let song = song.read();
+                (song.primary_color, Url::parse(&song.thumbnail).ok())
+            }
+            None => (None, None),
+        };
+        if let Some(rgba) = picked {
+            return self.show_background(key, rgba);
+        }
+        let reader = self.cache.thumbnails.clone();
+        Cm::perform(
+            async move {
+                let hashset = HashSet::from([key.clone()]);
+                // The colors come out better from the large thumbnail
+                let mut paths = thumbnails::resolve(&reader, &hashset, ThumbnailSize::Large).await;
+                let path = match (paths.remove(&key), url) {
+                    (Some(path), _) => path,
+                    (None, Some(url)) => {
+                        let urls = vec![(key.clone(), url)];
+                        let mut handles = get_images(reader, urls, ThumbnailSize::Large).await;
+                        match handles.remove(&key) {
+                            Some(Handle::Path(_, path)) => path,
+                            _ => return (key, None),
+                        }
+                    }
+                    (None, None) => return (key, None),
+                };
+                let rgba = BasicYtmrsScheme::primary_color_of(path).await;
+                (key, rgba)
+            },
+            |(key, rgba)| match rgba {
+                Some(rgba) => YtmrsMsg::PrimaryColorPicked(key, rgba),
+                None => YtmrsMsg::Null,
+            },
+        )
+    }
+
+    /// Fades the background to the colors, unless another song started playing in the meantime
+    fn show_background(&self, key: String, rgba: [u8; 4]) -> Cm<YtmrsMsg> {
+        if !self.is_playing(&key) {
+            return Cm::none();
+        }
+        Cm::perform(BasicYtmrsScheme::from_rgba(rgba), move |scheme| {
+            YtmrsMsg::SetNewBackground(key, scheme)
+        })
+    }
+
+    /// Whether the song is the one playing
+    pub fn is_playing(&self, key: &str) -> bool {
+        self.now_playing.as_deref() == Some(key)
+    }
+
+    /// Adds the songs to the playlist, unless some are in it already and it doesn't allow
+    /// repeats. Then they're held back until it's known whether to add them anyway.
+    fn add_checked(&mut self, keys: Vec<SongKey>, target: AddTarget) -> Cm<YtmrsMsg> {
+        let playlist = &self.settings.playlist;
+        let present = match playlist.allow_duplicates {
+            true => HashSet::new(),
+            false => playlist.present(&keys),
+        };
+        match present.is_empty() {
+            true => self.add_to(keys, target),
+            false => {
+                self.duplicate_prompt = Some(DuplicatePrompt {
+                    keys,
+                    present,
+                    target,
+                });
+                Cm::none()
+            }
+        }
+    }
+
+    fn add_to(&mut self, keys: Vec<SongKey>, target: AddTarget) -> Cm<YtmrsMsg> {
+        match target {
+            AddTarget::FocusedGroup => self.add_songs(keys).1,
+            AddTarget::Dropped(zones) => {
+                self.edit_playlist(|ytmrs| ytmrs.handle_zones(keys, &zones));
+                Cm::none()
             }
-            None => Cm::none(),
         }
     }
 
-    fn play_previous_song(&mut self) -> Cm<YtmrsMsg> {
-        if let Some(state) = &mut self.player_state {
-            match state.tracker.move_back() {
-                song_operations::BackResult::Rewound => {
-                    self.audio_manager.seek_to_start();
-                    self.audio_manager.play();
-                    self.audio_tracker.paused = false;
-                    self.tickers.playing_status.0 = true;
-                    Cm::none()
-                }
-                song_operations::BackResult::Current => {
-                    let path: VecDeque<usize> = state.tracker.get_current().collect();
-                    self.play_at_path(path)
-                }
-            }
-        } else {
-            Cm::none()
+    /// Adds the songs to the playlist, returning their new rows
+    fn add_songs(&mut self, keys: Vec<SongKey>) -> (Vec<WId>, Cm<YtmrsMsg>) {
+        let rows =
+            self.edit_playlist(|ytmrs| ytmrs.settings.playlist.add_to_focused_group(keys.clone()));
+        for id in keys {
+            self.events.emit(AppEvent::SongAdded {
+                id,
+                playlist: self.settings.playlist.name.clone(),
+            });
         }
+        // So the new rows don't wait for the next cache tick to show their songs
+        self.sync_used_keys();
+        (rows, self.fetch_missing_metadata())
     }
 
-    fn play_next_song(&mut self) -> Cm<YtmrsMsg> {
-        if let Some(state) = &mut self.player_state {
-            match state.tracker.move_next() {
-                song_operations::NextResult::Current => {
-                    let path: VecDeque<usize> = state.tracker.get_current().collect();
-                    self.play_at_path(path)
-                }
-                song_operations::NextResult::Ended => {
-                    // Pause
+    /// Runs the action picked from a song row's menu.
+    /// `row` is None for search results, which are added to the playlist to be played.
+    fn song_action(&mut self, key: SongKey, row: Option<WId>, action: SongAction) -> Cm<YtmrsMsg> {
+        self.context_menu = None;
+        match action {
+            SongAction::CopyUrl => self.copy_url(&key),
+            SongAction::RefreshInfo => self.refresh_songs([key]),
+            SongAction::Rate(rating) => self.rate_song(key, rating),
+            SongAction::RemoveDownload => match self.now_playing.as_ref() == Some(&key) {
+                true => {
+                    self.notify(Notification::error(
+                        "The playing song's download can't be removed",
+                    ));
                     Cm::none()
                 }
+                false => {
+                    let reader = self.cache.sounds.reader.clone();
+                    Cm::perform(
+                        async move {
+                            let ids = HashSet::from([key]);
+                            reader.remove(&ids).await.map_err(|e| e.to_string())
+                        },
+                        |result| YtmrsMsg::FilesRemoved(CacheFolder::Sounds, result),
+                    )
+                }
+            },
+            // Playlist rows remove themselves, search results can't be removed
+            SongAction::Remove => Cm::none(),
+            SongAction::AddToPlaylist => self.add_checked(vec![key], AddTarget::FocusedGroup),
+            SongAction::PlayNow | SongAction::PlayNext => {
+                let (row, added) = match row {
+                    Some(row) => (row, Cm::none()),
+                    None => {
+                        let (rows, added) = self.add_songs(vec![key]);
+                        match rows.into_iter().next() {
+                            Some(row) => (row, added),
+                            None => return added,
+                        }
+                    }
+                };
+                let play = match action {
+                    SongAction::PlayNext => self.play_after_current(row),
+                    _ => self.song_clicked(row),
+                };
+                Cm::batch([added, play])
             }
-        } else {
-            Cm::none()
         }
     }
 
-    fn set_background(&self, key: String) -> Cm<YtmrsMsg> {
-        let hashset = HashSet::from([key.clone()]);
-        let reader = self.cache.thumbnails.clone();
-        let key2 = key.clone();
+    /// Writes the open playlist to the exports directory, with every song it has, in order
+    fn export_playlist(&self, format: ExportFormat) -> Cm<YtmrsMsg> {
+        let playlist = self.settings.playlist.clone();
+        let entries = {
+            let metadata = self.cache.song_metadata.read();
+            let items = metadata.items();
+            playlist
+                .constructor
+                .all_song_keys_rec()
+                .map(|key| {
+                    let song = items.get(key).map(|song| song.read());
+                    ExportEntry::new(key.clone(), song.as_deref())
+                })
+                .collect()
+        };
+        let sounds = self.cache.sounds.reader.clone();
         Cm::perform(
-            async move {
-                let hashset = hashset;
-                let thumbnails: HashMap<_, _> = join_all(reader.read_from_ids(&hashset).await)
-                    .await
-                    .into_iter()
-                    .collect();
+            export::export(playlist, entries, sounds, format, exports_directory()),
+            YtmrsMsg::PlaylistExported,
+        )
+    }
 
-                match thumbnails.is_empty() {
-                    true => None,
-                    false => {
-                        let data = {
-                            let thumbnail = thumbnails[&key].read();
-                            thumbnail.clone().into_data().clone()
-                        };
-                        Some(BasicYtmrsScheme::from_handle(Handle::from_path(data)).await)
-                    }
+    /// Copies the song's webpage, made from its id when its info isn't saved
+    fn copy_url(&mut self, key: &SongKey) -> Cm<YtmrsMsg> {
+        let url = match self.cache.song_metadata.read().items().get(key) {
+            Some(song) => song.read().webpage_url.clone(),
+            None => BackendHandler::request_url_from_id(key),
+        };
+        self.notify(Notification::info(format!("Copied {url}")));
+        iced::clipboard::write(url)
+    }
+
+    /// Plays the song at the row once the current one ends, or now if nothing is playing
+    fn play_after_current(&mut self, row: WId) -> Cm<YtmrsMsg> {
+        let constructor = &self.settings.playlist.constructor;
+        let path = match constructor.path_to_id(&row) {
+            Some(path) => path,
+            None => return Cm::none(),
+        };
+        let path = match constructor.played_path(&path) {
+            Some(path) => path,
+            None => {
+                let note = "The song is in a group that's turned off";
+                self.notify(Notification::info(note));
+                return Cm::none();
+            }
+        };
+        match &mut self.player_state {
+            Some(state) => {
+                state.play_next(path);
+                self.refresh_queue();
+                self.prefetch_next()
+            }
+            None => self.song_clicked(row),
+        }
+    }
+
+    /// Plays from where the restored session was, or else from the playlist's first song
+    fn start_playing(&mut self) -> Cm<YtmrsMsg> {
+        let path: VecDeque<usize> = match &self.player_state {
+            Some(state) => state.tracker.get_current().collect(),
+            None => {
+                let constructor = &self.settings.playlist.constructor;
+                let song_op = self.settings.user.simple.build(constructor.build());
+                if let Some(problem) = constructor.problem().or_else(|| song_op.problem()) {
+                    let problem = format!("Can't play the playlist: {problem}");
+                    self.notify(Notification::error(problem));
+                    return Cm::none();
                 }
-            },
-            |ms| match ms {
-                Some(scheme) => YtmrsMsg::SetNewBackground(key2, scheme),
-                None => YtmrsMsg::Null,
-            },
-        )
+                let tracker = match SongOpTracker::start(&song_op, VecDeque::new()) {
+                    Some(tracker) => tracker,
+                    None => return Cm::none(),
+                };
+                let path = tracker.get_current().collect();
+                self.player_state = Some(self.player_state_for(tracker, song_op));
+                self.play_trigger = Some(PlayTrigger::Picked);
+                path
+            }
+        };
+        self.play_at_path(path)
     }
 
     /// Generate the song tracker when a song is clicked
     fn song_clicked(&mut self, wid: WId) -> Cm<YtmrsMsg> {
-        let path = self.settings.playlist.constructor.path_to_id(&wid).unwrap();
-
-        println!["Given path: {:?}", path];
-        let song_op = self.settings.playlist.constructor.build();
-        println!["Song op: {:?}", song_op];
-        println!["Is valid: {:?}", song_op.is_valid()];
-        println!["Loop type: {:?}", song_op.loop_type()];
-        if !song_op.is_valid() {
+        // Replaying from the history sets its own trigger first
+        let trigger = self.play_trigger.take().unwrap_or(PlayTrigger::Picked);
+        let constructor = &self.settings.playlist.constructor;
+        let path = constructor.path_to_id(&wid).unwrap();
+
+        let song_op = self.settings.user.simple.build(constructor.build());
+        if let Some(problem) = constructor.problem().or_else(|| song_op.problem()) {
+            let problem = format!("Can't play the playlist: {problem}");
+            self.notify(Notification::error(problem));
             return Cm::none();
         }
-        let tracker = SongOpTracker::from_song_op(&song_op, path.into());
+        // The tracker's paths skip the groups that are off
+        let path = match constructor.played_path(&path) {
+            Some(path) => path,
+            None => {
+                let note = "The song is in a group that's turned off";
+                self.notify(Notification::info(note));
+                return Cm::none();
+            }
+        };
+        let tracker = match SongOpTracker::start(&song_op, path.into()) {
+            Some(tracker) => tracker,
+            None => return Cm::none(),
+        };
         let generated_path: VecDeque<usize> = tracker.get_current().collect();
-        self.player_state = Some(PlayerState { tracker });
+        // Picking a song replaces the session that was going to resume
+        self.resuming = None;
+        self.inputs.typing = false;
+        self.player_state = Some(self.player_state_for(tracker, song_op));
+        self.play_trigger = Some(trigger);
         self.play_at_path(generated_path)
     }
 
     fn play_at_path(&mut self, pth: VecDeque<usize>) -> Cm<YtmrsMsg> {
-        let item = self.settings.playlist.constructor.item_at_path(pth);
+        // Everything that moves the tracker plays the song it moved to
+        self.refresh_queue();
+        let pth: Vec<usize> = pth.into();
+        let item = self.settings.playlist.constructor.played_item(&pth);
         if let Some(ConstructorItem::Song(k, _)) = item {
-            println!["Estimated item at path: {:?}", item];
+            debug!["Estimated item at path: {:?}", item];
 
             let hashset = HashSet::from([k.clone()]);
 
             let key = k.clone();
+            let touch = self.touch_sound(key.clone());
 
             let sounds = self.cache.sounds.fetch_existing(&hashset);
+            // A decoded copy isn't played if the user asked for the song to stream
+            let decoded = !sounds.is_empty() && self.playback_mode(&key) != PlaybackMode::Stream;
 
-            match sounds.is_empty() {
-                false => {
+            let command = match decoded {
+                true => {
                     // Song exists in the cache, just play it
                     let item = sounds[&key].read();
-                    self.play(SoundData::from(item.clone()));
+                    let notice = self.play(SoundData::from(item.clone()));
 
-                    self.set_background(key)
+                    Cm::batch([self.set_background(key), notice])
                 }
-                true => {
+                false => {
                     // Song does not exist in the cache, add it to the cache and play it
                     self.fetch_song(key, true)
                 }
-            }
+            };
+            Cm::batch([command, self.prefetch_next(), touch])
         } else {
             Cm::none()
         }
     }
 
-    fn fetch_song(&self, id: String, play: bool) -> Cm<YtmrsMsg> {
+    /// Marks the song as just played, so its download is the last to be evicted
+    fn touch_sound(&self, key: SongKey) -> Cm<YtmrsMsg> {
+        let reader = self.cache.sounds.reader.clone();
+        Cm::perform(
+            async move {
+                reader
+                    .touch(&HashSet::from([key]), time::SystemTime::now())
+                    .await
+            },
+            |result| {
+                if let Err(e) = result {
+                    println!["Failed to mark the song as played: {e:?}"];
+                }
+                YtmrsMsg::Null
+            },
+        )
+    }
+
+    /// Deletes every downloaded song except the one playing
+    fn clear_sounds(&self) -> Cm<YtmrsMsg> {
+        let keep: HashSet<SongKey> = self.now_playing.iter().cloned().collect();
+        let reader = self.cache.sounds.reader.clone();
+        Cm::perform(
+            async move { reader.clear(&keep).await.map_err(|e| e.to_string()) },
+            |result| YtmrsMsg::FilesRemoved(CacheFolder::Sounds, result),
+        )
+    }
+
+    /// Keeps the downloaded songs within their budget, except the one playing and the next
+    fn evict_sounds(&mut self) -> Cm<YtmrsMsg> {
+        let keep: HashSet<SongKey> = [self.now_playing.clone(), self.next_song_key()]
+            .into_iter()
+            .flatten()
+            .collect();
+        let budget = self.settings.user.audio_cache_budget();
+        let reader = self.cache.sounds.reader.clone();
+        Cm::perform(
+            async move {
+                reader
+                    .evict(budget, &keep)
+                    .await
+                    .map_err(|e| format!("{e:?}"))
+            },
+            YtmrsMsg::EvictionFinished,
+        )
+    }
+
+    /// The song after this one, None if the queue ends there or nothing is playing
+    fn next_song_key(&mut self) -> Option<SongKey> {
+        let path = self
+            .player_state
+            .as_mut()
+            .and_then(PlayerState::peek_next)?;
+        match self.settings.playlist.constructor.played_item(&path) {
+            Some(ConstructorItem::Song(key, _)) => Some(key.clone()),
+            _ => None,
+        }
+    }
+
+    /// Loads the next song into the cache while this one plays, so it starts without a gap
+    fn prefetch_next(&mut self) -> Cm<YtmrsMsg> {
+        let key = match self.next_song_key() {
+            Some(key) => key,
+            None => return Cm::none(),
+        };
+        // A song followed by itself is already being loaded
+        let loaded = !self.cache.sounds.fetch_existing([&key]).is_empty();
+        if loaded || self.now_playing.as_ref() == Some(&key) {
+            return Cm::none();
+        }
+        debug!["Prefetching {key}"];
+        self.prefetching = Some(key.clone());
+        self.fetch_song(key, false)
+    }
+
+    /// The playback mode the user picked for the song
+    fn playback_mode(&self, key: &str) -> PlaybackMode {
+        match self.cache.song_metadata.read().items().get(key) {
+            Some(song) => song.read().playback_mode,
+            None => PlaybackMode::Auto,
+        }
+    }
+
+    /// Reads the song from the sound cache, downloading it if it isn't there.
+    /// A song that's already being read isn't read again.
+    fn fetch_song(&mut self, id: String, play: bool) -> Cm<YtmrsMsg> {
+        match self.fetching.get_mut(&id) {
+            Some(pending) => {
+                *pending |= play;
+                return Cm::none();
+            }
+            None => {
+                self.fetching.insert(id.clone(), play);
+            }
+        }
+        // A song followed by itself is read again while it plays
+        if self.now_playing.as_ref() != Some(&id) {
+            self.show_song_state(&id, SongState::Fetching);
+        }
         let set = HashSet::from([id.clone()]);
         let reader = self.cache.sounds.reader.clone();
+        let mode = self.playback_mode(&id);
+        let stream_over = self.settings.user.stream_over();
+        let facts = match self.cache.song_metadata.read().items().get(&id) {
+            Some(song) => song.read().load_facts(available_memory()),
+            None => LoadFacts {
+                headroom: available_memory(),
+                ..Default::default()
+            },
+        };
 
         Cm::perform(
             async move {
+                // Streamed songs are never read into memory, not even to have them ready
+                if let Ok(Some(path)) = reader.locate(&id).await {
+                    let file_size = async_std::fs::metadata(&path).await.map(|m| m.len());
+                    let facts = LoadFacts {
+                        file_size: file_size.ok().or(facts.file_size),
+                        ..facts
+                    };
+                    if audio::resolve(mode, &facts, stream_over) == Loading::Stream {
+                        return YtmrsMsg::SoundFetched(id, CachedSound::Located(path));
+                    }
+                }
+
                 let futures = reader.read_from_ids(&set).await;
                 // there should only be one future in the list
                 let future = futures.into_iter().take(1).next();
-                match future {
+                let sound = match future {
                     Some(item) => {
-                        let actual_item = {
-                            let item = item.await;
-                            let l = item.1.read();
-                            (
-                                item.0.clone(),
-                                BasicSoundData::from((item.0, l.clone().into_data())),
-                            )
-                        };
-                        let arc = Some(Arc::new([actual_item].to_rwmap()));
-                        println!["Created song arc."];
-                        arc
+                        let item = item.await;
+                        let l = item.1.read();
+                        match BasicSoundData::try_from((item.0.clone(), l.clone().into_data())) {
+                            Ok(sound) => CachedSound::Read(Box::new(sound)),
+                            Err(e) => CachedSound::Undecodable(e),
+                        }
                     }
-                    None => None,
-                }
-            },
-            move |map| match map {
-                Some(map) => YtmrsMsg::SoundsFetched {
-                    map: {
-                        map.iter()
-                            .map(|(k, v)| {
-                                let data = {
-                                    let item = v.read();
-                                    item.data().clone()
-                                };
-
-                                (k.clone(), data)
-                            })
-                            .collect()
-                    },
-                    play: play.then_some(id),
-                },
-                None => YtmrsMsg::DownloadSong(id, true),
+                    None => CachedSound::Missing,
+                };
+                YtmrsMsg::SoundFetched(id, sound)
             },
+            |msg| msg,
         )
     }
 
-    fn download_song(&self, id: String, play: bool) -> Cm<YtmrsMsg> {
+    /// Adds the song to the downloads, which run as subscriptions
+    fn download_song(&mut self, id: String, play: bool) -> Cm<YtmrsMsg> {
+        let (host, client) = {
+            let backend = self.backend_handler.lock();
+            (backend.host(), backend.client())
+        };
+        let host = match host {
+            Some(host) => host,
+            None => {
+                let error = BackendReqErr::Offline.describe();
+                let retry = Some(Retry::Download {
+                    key: id.clone(),
+                    play,
+                });
+                // A batch's failures are told of once it's over
+                let in_batch = self
+                    .downloads
+                    .batch
+                    .as_ref()
+                    .is_some_and(|batch| batch.contains(&id));
+                if !in_batch {
+                    self.notify(
+                        Notification::error(format!(
+                            "{}: {}",
+                            FailureKind::Download.describe(),
+                            id
+                        ))
+                        .with_details(FailureKind::Download, id.clone()),
+                    );
+                }
+                let failure = Failure::new(FailureKind::Download, id, error, retry);
+                self.failures.record(failure, Local::now());
+                return Cm::none();
+            }
+        };
         let metadata = self.cache.song_metadata.read();
-        let songs = metadata.fetch_existing(&HashSet::from([id.clone()]));
-        if songs.is_empty() {
-            return Cm::none();
+        let song = match metadata.items().get(&id) {
+            Some(song) => song,
+            None => return Cm::none(),
+        };
+        let mut song = song.write();
+        let download = Download::new(
+            id,
+            song.title.clone(),
+            song.webpage_url.clone(),
+            client,
+            host,
+            self.settings.user.download_format,
+            play,
+        );
+        if self.downloads.start(download) {
+            song.ui_state = SongState::Downloading(None);
+            metadata.touch();
         }
-        let song = songs[&id].write();
-        let backend = self.backend_handler.lock();
-        let url = song.webpage_url.clone();
+        Cm::none()
+    }
 
-        Cm::perform(
-            backend.request_download_song(url).unwrap(),
-            move |result| match result {
-                Ok(s) => {
-                    println!["{:?}", s];
-                    let song: Song = serde_json::from_str(&s).unwrap();
-                    YtmrsMsg::SongDownloaded { song, play }
-                }
-                Err(e) => {
-                    println!["{:?}", e];
-                    YtmrsMsg::Null
+    /// Starts the batch's next downloads. Songs that can't be started count as failed.
+    fn advance_batch(&mut self) -> Cm<YtmrsMsg> {
+        let mut commands = vec![];
+        loop {
+            let keys = match &mut self.downloads.batch {
+                Some(batch) => batch.next_keys(),
+                None => break,
+            };
+            if keys.is_empty() {
+                break;
+            }
+            for key in keys {
+                commands.push(self.download_song(key.clone(), false));
+                if !self.downloads.contains(&key) {
+                    if let Some(batch) = &mut self.downloads.batch {
+                        batch.finish(&key, Some("it couldn't be started".to_string()));
+                    }
                 }
-            },
+            }
+        }
+        let done = matches!(&self.downloads.batch, Some(batch) if batch.is_finished());
+        let finished = match done {
+            true => self.downloads.batch.take(),
+            false => None,
+        };
+        if let Some(batch) = finished {
+            let summary = batch.summary();
+            match batch.failed.first() {
+                None => self.notify(Notification::info(summary)),
+                Some((key, _)) => self.notify(
+                    Notification::error(summary).with_details(FailureKind::Download, key.clone()),
+                ),
+            }
+        }
+        Cm::batch(commands)
+    }
+
+    /// Counts the batch's song as done, then checks the cache's size before going on
+    fn batch_finished(&mut self, key: &str, error: Option<String>) -> Cm<YtmrsMsg> {
+        match &mut self.downloads.batch {
+            Some(batch) if batch.finish(key, error) => self.check_batch_usage(),
+            _ => Cm::none(),
+        }
+    }
+
+    fn check_batch_usage(&self) -> Cm<YtmrsMsg> {
+        let reader = self.cache.sounds.reader.clone();
+        Cm::perform(
+            async move { reader.usage().await.map_err(|e| e.to_string()) },
+            YtmrsMsg::BatchUsageChecked,
         )
     }
 
-    fn play(&mut self, sd: SoundData) {
-        println!["Playing sound."];
-        self.audio_manager.play_once(sd);
-        self.audio_manager
-            .set_volume(self.settings.user.volume as f64);
+    /// Shows where the song is on its way to playing on its rows
+    fn show_song_state(&self, id: &str, state: SongState) {
+        let metadata = self.cache.song_metadata.read();
+        if let Some(song) = metadata.items().get(id) {
+            song.write().ui_state = state;
+            metadata.touch();
+        }
+    }
+
+    /// Stops the playing song and the queue, for when the tree doesn't play the song anymore
+    fn stop_playing(&mut self, why: &str) {
+        self.song_ended(false);
+        self.audio_manager.stop(self.settings.user.stop_fade());
+        self.audio_tracker.update_from_manager(&self.audio_manager);
+        self.player_state = None;
+        self.refresh_queue();
+        self.notify(Notification::info(why));
+    }
+
+    fn pause_playback(&mut self) {
+        self.audio_manager.pause();
+        self.audio_tracker.paused = true;
+        self.events.emit(AppEvent::PlaybackPaused);
+    }
+
+    /// Fades the playing song out over the time the sleep timer has left
+    fn fade_for_sleep(&mut self, left: time::Duration) {
+        let tween = Tween {
+            duration: left,
+            ..Default::default()
+        };
+        self.audio_manager.fade_volume(0.0, tween);
+    }
+
+    /// Turns the sleep timer off. A song it was fading out stays quiet until it's played.
+    fn cancel_sleep_timer(&mut self) {
+        if let Some(timer) = self.sleep_timer.take() {
+            self.slept |= timer.fading();
+        }
+        self.tickers.sleep_timer.0 = false;
+        self.audio_tracker.sleep_left = None;
+    }
+
+    fn restore_slept_volume(&mut self) {
+        if std::mem::take(&mut self.slept) {
+            self.audio_manager.set_volume(self.playing_volume() as f64);
+        }
+    }
+
+    /// The user's volume, which follows the ramp while one is running. Silent while muted.
+    fn playing_volume(&self) -> f32 {
+        self.settings.user.unless_muted(self.settings.user.volume)
+    }
+
+    /// Moves the volume setting and the slider along with the ramp
+    fn follow_ramp(&mut self, volume: f32) {
+        self.settings
+            .user
+            .set_volume(self.output_device.active(), volume);
+        self.audio_tracker.volume = amplitude_to_slider(volume as f64);
+    }
+
+    /// Tells listeners the playing song is over, once however it ended
+    fn song_ended(&mut self, completed: bool) {
+        if std::mem::replace(&mut self.song_over, true) {
+            return;
+        }
+        if let Some(id) = &self.now_playing {
+            self.show_song_state(id, SongState::Cached);
+            self.events.emit(AppEvent::SongEnded {
+                id: id.clone(),
+                completed,
+            });
+        }
+    }
+
+    /// Plays the sound, returning the wait for its notification and the play's write to the history
+    fn play(&mut self, sd: SoundData) -> Cm<YtmrsMsg> {
+        let resumed = self
+            .resuming
+            .take()
+            .filter(|session| session.key == *sd.id());
+        // Whatever replaced the song, it ended early. A resumed song carries on instead.
+        if resumed.is_none() {
+            self.song_ended(false);
+        }
+        self.song_over = false;
+        // Keep the playing sound in memory
+        if let Some(previous) = &self.now_playing {
+            self.cache.sounds.policy.unpin(previous);
+            self.show_song_state(previous, SongState::Cached);
+        }
+        self.cache.sounds.policy.pin(sd.id().clone());
+        self.show_song_state(sd.id(), SongState::Playing);
+        self.now_playing = Some(sd.id().clone());
+        let trigger = match resumed.is_some() {
+            true => PlayTrigger::Resumed,
+            false => self.play_trigger.take().unwrap_or_default(),
+        };
+        let recorded = self.history.record(sd.id().clone(), trigger, Local::now());
+        let meta = self
+            .cache
+            .song_metadata
+            .read()
+            .items()
+            .get(sd.id())
+            .map(|song| song.read().clone());
+        let notes = meta.as_ref().and_then(|song| song.notes.as_deref());
+        self.notes = text_editor::Content::with_text(notes.unwrap_or_default());
+        self.now_playing_source = meta
+            .as_ref()
+            .map(|song| song.source.clone())
+            .unwrap_or_default();
+        let notice = self.queue_notice(meta.as_ref().map(SongNotice::new));
+        self.events.emit(AppEvent::SongStarted {
+            id: sd.id().clone(),
+            meta,
+        });
+        let gain = self.gain_for(sd.id());
+        let fade = self.settings.user.stop_fade();
+        self.audio_manager.play_once(sd, gain, fade);
+        if let Some(ramp) = &mut self.volume_ramp {
+            let now = time::Instant::now();
+            ramp.start(now);
+            self.tickers.volume_ramp.0 = true;
+            let volume = ramp.volume(now);
+            self.follow_ramp(volume);
+        }
+        self.audio_manager.set_volume(self.playing_volume() as f64);
+        // The song starts at full volume, so it's picked up where it's quiet
+        self.slept = false;
+        let now = time::Instant::now();
+        if let Some(timer) = self.sleep_timer.as_ref().filter(|timer| timer.fading()) {
+            self.fade_for_sleep(timer.remaining(now));
+        }
+        // A restart never blasts music, the resumed song waits for play to be pressed
+        let paused = match resumed {
+            Some(session) => {
+                self.audio_manager.seek(session.elapsed);
+                self.audio_manager.pause();
+                true
+            }
+            None => false,
+        };
         self.audio_tracker.update_from_manager(&self.audio_manager);
-        self.tickers.playing_status.0 = true;
-        println!["Played sound."];
+        self.audio_tracker.paused = paused;
+        Cm::batch([notice, Cm::perform(recorded, |()| YtmrsMsg::Null)])
+    }
+
+    /// Replaces the notification waiting to be shown, so songs skipped past aren't shown
+    fn queue_notice(&mut self, notice: Option<SongNotice>) -> Cm<YtmrsMsg> {
+        let enabled = cfg!(feature = "song-notifications") && self.settings.user.song_notifications;
+        match (notice, enabled) {
+            (Some(notice), true) => {
+                let id = self.song_notifier.queue(notice);
+                Cm::perform(song_notifications::wait(id), YtmrsMsg::SongNoticeDue)
+            }
+            _ => {
+                self.song_notifier.cancel();
+                Cm::none()
+            }
+        }
+    }
+
+    pub fn set_focused(&mut self, focused: bool) {
+        self.in_background = !focused;
+    }
+
+    /// Worked out from playback whenever the subscriptions are built, so the ticks can't be
+    /// left running while paused. The progress bar is smooth only while it can be seen.
+    fn progress_ticks(&self) -> ProgressTicks {
+        let seen = !self.in_background && self.now_playing.is_some();
+        ProgressTicks::of(self.audio_manager.playback_state(), seen)
+    }
+}
+
+fn settings_modified() -> Option<time::SystemTime> {
+    std::fs::metadata(settings_path())
+        .and_then(|m| m.modified())
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::VecDeque, time::Duration};
+
+    use crate::song_operations::{
+        NextResult, OperationTracker, RecursiveSongOp, Repeat, SongOpTracker,
+    };
+
+    use kira::sound::PlaybackState;
+
+    use super::{
+        backend_retry_interval, next_backend_poll, PlayerState, ProgressTicks, Tickers,
+        BACKEND_MAX_RETRY_INTERVAL, BACKEND_POLL_INTERVAL, SMOOTH_PROGRESS_INTERVAL,
+    };
+
+    #[test]
+    fn backend_retries_back_off() {
+        let intervals: Vec<_> = (0..4).map(backend_retry_interval).collect();
+        assert_eq![intervals, [1, 2, 4, 8].map(|n| BACKEND_POLL_INTERVAL * n)];
+        assert_eq![backend_retry_interval(10), BACKEND_MAX_RETRY_INTERVAL];
+        assert_eq![backend_retry_interval(u32::MAX), BACKEND_MAX_RETRY_INTERVAL];
+    }
+
+    #[test]
+    fn relaunched_backends_back_off_until_they_answer() {
+        let mut failures = 0;
+        // Not found, then launched twice without answering a poll
+        let intervals: Vec<_> = (0..3)
+            .map(|_| next_backend_poll(false, &mut failures))
+            .collect();
+        assert_eq![intervals, [1, 2, 4].map(|n| BACKEND_POLL_INTERVAL * n)];
+        assert_eq![
+            next_backend_poll(true, &mut failures),
+            BACKEND_POLL_INTERVAL
+        ];
+        assert_eq![failures, 0];
+        assert_eq![
+            next_backend_poll(false, &mut failures),
+            BACKEND_POLL_INTERVAL
+        ];
+    }
+
+    #[test]
+    fn progress_only_ticks_while_playing() {
+        let ticks = |playback| ProgressTicks::of(playback, true);
+        assert_eq![ticks(PlaybackState::Paused), ProgressTicks::Off];
+        assert_eq![ticks(PlaybackState::Stopped), ProgressTicks::Off];
+        assert_eq![ticks(PlaybackState::Playing), ProgressTicks::Smooth];
+        // Fading out, or not seen
+        assert_eq![ticks(PlaybackState::Pausing), ProgressTicks::Background];
+        let background = ProgressTicks::of(PlaybackState::Playing, false);
+        assert_eq![background, ProgressTicks::Background];
+
+        let tickers = Tickers::default();
+        let interval = |ticks| tickers.progress_interval(ticks);
+        let smooth = interval(ProgressTicks::Smooth);
+        assert_eq![smooth, Some(SMOOTH_PROGRESS_INTERVAL)];
+        let background = interval(ProgressTicks::Background);
+        assert_eq![background, Some(Duration::from_secs(1))];
+        assert_eq![interval(ProgressTicks::Off), None];
+    }
+
+    #[test]
+    fn the_prefetched_song_is_the_one_that_plays() {
+        let songs = (0..8).map(|i| RecursiveSongOp::SinglePlay(i.to_string()));
+        let op = RecursiveSongOp::InfiniteRandom(songs.collect());
+        let mut state = PlayerState::new(SongOpTracker::from_song_op(&op, VecDeque::from([0])));
+        for _ in 0..50 {
+            let next = state.peek_next().unwrap();
+            // Peeking again doesn't pick again
+            assert_eq![state.peek_next().unwrap(), next];
+            assert_eq![state.move_next(), NextResult::Current];
+            assert_eq![state.tracker.get_current().collect::<Vec<_>>(), next];
+        }
+
+        // Moving the tracker some other way forgets the peek
+        state.peek_next();
+        state.tracker_mut().set_current(VecDeque::from([3]));
+        assert![state.next.is_none()];
+    }
+
+    #[test]
+    fn shuffling_plays_the_rest_once_then_back_in_order() {
+        let songs = (0..6).map(|i| RecursiveSongOp::SinglePlay(i.to_string()));
+        let op = RecursiveSongOp::PlayOnce(songs.collect());
+        let tracker = SongOpTracker::from_song_op(&op, VecDeque::from([0]));
+        let mut state = PlayerState::new(tracker).with_shuffle(true);
+
+        let mut played = vec![];
+        for _ in 0..3 {
+            let next = state.peek_next().unwrap();
+            assert_eq![state.move_next(), NextResult::Current];
+            played.push(next[0]);
+        }
+        // Turning it off carries on after the current song
+        let current = played[2];
+        state.set_shuffle(false);
+        match current {
+            5 => assert_eq![state.move_next(), NextResult::Ended],
+            _ => {
+                state.move_next();
+                assert_eq![
+                    state.tracker.get_current().collect::<Vec<_>>(),
+                    vec![current + 1]
+                ];
+            }
+        }
+
+        let mut state = PlayerState::new(SongOpTracker::from_song_op(&op, VecDeque::from([0])))
+            .with_shuffle(true);
+        let mut played = vec![];
+        while state.move_next() == NextResult::Current {
+            played.extend(state.tracker.get_current());
+        }
+        played.sort();
+        assert_eq![played, vec![1, 2, 3, 4, 5]];
+    }
+
+    #[test]
+    fn repeating_the_queue_starts_it_over() {
+        let songs = (0..3).map(|i| RecursiveSongOp::SinglePlay(i.to_string()));
+        let op = RecursiveSongOp::PlayOnce(songs.collect());
+        let tracker = SongOpTracker::from_song_op(&op, VecDeque::from([0]));
+        let mut state = PlayerState::new(tracker).with_repeat(Repeat::All);
+
+        let mut played = vec![];
+        for _ in 0..5 {
+            assert_eq![state.move_next(), NextResult::Current];
+            played.extend(state.tracker.get_current());
+        }
+        assert_eq![played, vec![1, 2, 0, 1, 2]];
+
+        state.set_repeat(Repeat::Off);
+        assert_eq![state.move_next(), NextResult::Ended];
+    }
+
+    #[test]
+    fn songs_played_next_cut_in_then_the_queue_goes_on() {
+        let songs = (0..5).map(|i| RecursiveSongOp::SinglePlay(i.to_string()));
+        let op = RecursiveSongOp::PlayOnce(songs.collect());
+        let tracker = SongOpTracker::from_song_op(&op, VecDeque::from([0]));
+        let mut state = PlayerState::new(tracker);
+
+        state.peek_next();
+        state.play_next(vec![4]);
+        state.play_next(vec![3]);
+        let mut played = vec![];
+        while state.move_next() == NextResult::Current {
+            played.extend(state.tracker.get_current());
+        }
+        assert_eq![played, vec![4, 3, 1, 2, 3, 4]];
     }
 }